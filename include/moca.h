@@ -95,7 +95,7 @@
 /**
  * Current bytecode format version
  */
-#define VERSION 2
+#define VERSION 4
 
 /**
  * Result codes for FFI operations.
@@ -232,8 +232,10 @@ uint32_t moca_version_patch(void)
 /**
  * Call a moca function by name.
  *
- * Arguments must be pushed onto the stack before calling.
- * The result will be on the stack after a successful call.
+ * Arguments must be pushed onto the stack before calling, first argument
+ * first. For functions of the loaded chunk, the arguments are popped and
+ * the result is pushed in their place; host functions manage the stack
+ * themselves. The chunk's top-level code is not run by a call.
  *
  * # Arguments
  * - `vm`: Valid VM instance
@@ -242,8 +244,10 @@ uint32_t moca_version_patch(void)
  *
  * # Returns
  * - `MOCA_OK` on success
+ * - `MOCA_ERROR_INVALID_ARG` if `nargs` doesn't match the function's arity
+ *   or exceeds the stack size
  * - `MOCA_ERROR_NOT_FOUND` if function not found
- * - `MOCA_ERROR_RUNTIME` on execution error
+ * - `MOCA_ERROR_RUNTIME` on execution error (including uncaught throws)
  */
 
 MocaResult moca_call(MocaVm *vm,
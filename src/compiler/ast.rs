@@ -44,6 +44,8 @@ pub struct StructDef {
     /// Type parameters for generic structs: `struct Container<T> { ... }`
     pub type_params: Vec<String>,
     pub fields: Vec<StructField>,
+    /// Text of the `///` doc comment preceding the definition, if any.
+    pub doc: Option<String>,
     pub span: Span,
 }
 
@@ -106,6 +108,8 @@ pub struct FnDef {
     pub return_type: Option<TypeAnnotation>,
     pub body: Block,
     pub attributes: Vec<Attribute>,
+    /// Text of the `///` doc comment preceding the definition, if any.
+    pub doc: Option<String>,
     pub span: Span,
 }
 
@@ -303,6 +307,15 @@ pub enum Expr {
         span: Span,
         inferred_type: Option<Type>,
     },
+    /// Conditional expression: `cond ? then_expr : else_expr`.
+    /// Only the selected branch is evaluated.
+    Conditional {
+        condition: Box<Expr>,
+        then_expr: Box<Expr>,
+        else_expr: Box<Expr>,
+        span: Span,
+        inferred_type: Option<Type>,
+    },
     Call {
         callee: String,
         /// Type arguments for generic function calls: `identity<int>(42)`
@@ -357,7 +370,8 @@ pub enum Expr {
         inferred_type: Option<Type>,
     },
     /// Block expression: `{ stmt1; stmt2; expr }` - evaluates to the final expression.
-    /// This is generated by the desugar phase to expand NewLiteral.
+    /// This is generated by the desugar phase to expand NewLiteral, and by the
+    /// parser to bind the operands of a compound assignment.
     Block {
         /// Statements to execute before the final expression
         statements: Vec<Statement>,
@@ -406,6 +420,7 @@ impl Expr {
             Expr::Field { span, .. } => *span,
             Expr::Unary { span, .. } => *span,
             Expr::Binary { span, .. } => *span,
+            Expr::Conditional { span, .. } => *span,
             Expr::Call { span, .. } => *span,
             Expr::StructLiteral { span, .. } => *span,
             Expr::MethodCall { span, .. } => *span,
@@ -435,6 +450,7 @@ impl Expr {
             | Expr::Field { inferred_type, .. }
             | Expr::Unary { inferred_type, .. }
             | Expr::Binary { inferred_type, .. }
+            | Expr::Conditional { inferred_type, .. }
             | Expr::Call { inferred_type, .. }
             | Expr::StructLiteral { inferred_type, .. }
             | Expr::MethodCall { inferred_type, .. }
@@ -462,6 +478,7 @@ impl Expr {
             | Expr::Field { inferred_type, .. }
             | Expr::Unary { inferred_type, .. }
             | Expr::Binary { inferred_type, .. }
+            | Expr::Conditional { inferred_type, .. }
             | Expr::Call { inferred_type, .. }
             | Expr::StructLiteral { inferred_type, .. }
             | Expr::MethodCall { inferred_type, .. }
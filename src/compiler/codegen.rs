@@ -1,11 +1,14 @@
 use crate::compiler::ast::{AsmArg, BinaryOp, UnaryOp};
-
+use crate::compiler::lexer::Span;
 use crate::compiler::resolver::{
-    MatchDynArmKind, ResolvedAsmInstruction, ResolvedExpr, ResolvedFunction, ResolvedProgram,
-    ResolvedStatement, ResolvedStruct,
+    GENERIC_METHOD, MatchDynArmKind, ResolvedAsmInstruction, ResolvedExpr, ResolvedFunction,
+    ResolvedProgram, ResolvedStatement, ResolvedStruct,
 };
 use crate::compiler::types::Type;
-use crate::vm::{Chunk, DebugInfo, ElemKind, Function, FunctionDebugInfo, Op, ValueType};
+use crate::vm::{
+    Chunk, ChunkMetadata, DebugInfo, ElemKind, Function, FunctionDebugInfo, Op, Value, ValueType,
+    object_size_bytes,
+};
 use std::collections::HashMap;
 
 /// Maximum nesting depth for @inline expansion (prevents code explosion).
@@ -51,12 +54,139 @@ fn elem_kind_for_collection(object_type: &Option<Type>) -> ElemKind {
     }
 }
 
+/// Whether every path through `stmts` ends in a `return` or `throw`, so
+/// control cannot reach the code after them.
+fn always_exits(stmts: &[ResolvedStatement]) -> bool {
+    match stmts.last() {
+        Some(ResolvedStatement::Return { .. } | ResolvedStatement::Throw { .. }) => true,
+        Some(ResolvedStatement::If {
+            then_block,
+            else_block: Some(else_block),
+            ..
+        }) => always_exits(then_block) && always_exits(else_block),
+        Some(ResolvedStatement::Try {
+            try_block,
+            catch_block,
+            ..
+        }) => always_exits(try_block) && always_exits(catch_block),
+        Some(ResolvedStatement::MatchDyn {
+            arms,
+            default_block,
+            ..
+        }) => arms.iter().all(|arm| always_exits(&arm.body)) && always_exits(default_block),
+        _ => false,
+    }
+}
+
+/// Allocations of at least this many bytes are preceded by a `GcHint`.
+const LARGE_ALLOC_BYTES: usize = 4096;
+
+/// Bytes allocated by one run of `stmts`, counting only allocations whose size
+/// is known statically. Nested loops are skipped; they get their own hint.
+fn static_alloc_bytes(stmts: &[ResolvedStatement]) -> usize {
+    stmts.iter().map(stmt_alloc_bytes).sum()
+}
+
+fn stmt_alloc_bytes(stmt: &ResolvedStatement) -> usize {
+    match stmt {
+        ResolvedStatement::Let { init, .. } => expr_alloc_bytes(init),
+        ResolvedStatement::Assign { value, .. }
+        | ResolvedStatement::RefCellStore { value, .. }
+        | ResolvedStatement::Throw { value, .. } => expr_alloc_bytes(value),
+        ResolvedStatement::IndexAssign {
+            object,
+            index,
+            value,
+            ..
+        } => expr_alloc_bytes(object) + expr_alloc_bytes(index) + expr_alloc_bytes(value),
+        ResolvedStatement::FieldAssign { object, value, .. } => {
+            expr_alloc_bytes(object) + expr_alloc_bytes(value)
+        }
+        // Only one branch runs, so the larger one bounds the allocation
+        ResolvedStatement::If {
+            condition,
+            then_block,
+            else_block,
+            ..
+        } => {
+            expr_alloc_bytes(condition)
+                + static_alloc_bytes(then_block)
+                    .max(else_block.as_deref().map_or(0, static_alloc_bytes))
+        }
+        ResolvedStatement::Return { value, .. } => value.as_ref().map_or(0, expr_alloc_bytes),
+        ResolvedStatement::Expr { expr, .. } => expr_alloc_bytes(expr),
+        ResolvedStatement::Try { try_block, .. } => static_alloc_bytes(try_block),
+        ResolvedStatement::While { .. }
+        | ResolvedStatement::ForIn { .. }
+        | ResolvedStatement::MatchDyn { .. }
+        | ResolvedStatement::Break
+        | ResolvedStatement::Continue => 0,
+    }
+}
+
+fn expr_alloc_bytes(expr: &ResolvedExpr) -> usize {
+    let sum = |exprs: &[ResolvedExpr]| exprs.iter().map(expr_alloc_bytes).sum::<usize>();
+    match expr {
+        // Data array plus the [ptr, len] struct
+        ResolvedExpr::Array { elements } => {
+            object_size_bytes(elements.len() as u32) + object_size_bytes(2) + sum(elements)
+        }
+        ResolvedExpr::StructLiteral { fields, .. } => {
+            object_size_bytes(fields.len() as u32) + sum(fields)
+        }
+        ResolvedExpr::Closure { captures, .. } => object_size_bytes(1 + captures.len() as u32),
+        ResolvedExpr::RefCellNew { value } => object_size_bytes(1) + expr_alloc_bytes(value),
+        ResolvedExpr::AsDyn { expr, .. } => object_size_bytes(2) + expr_alloc_bytes(expr),
+        ResolvedExpr::Builtin { name, args, .. } => {
+            let own = match (name.as_str(), args.as_slice()) {
+                ("__alloc_heap", [ResolvedExpr::Int(n)]) if *n > 0 => object_size_bytes(*n as u32),
+                _ => 0,
+            };
+            own + sum(args)
+        }
+        ResolvedExpr::Index { object, index, .. } => {
+            expr_alloc_bytes(object) + expr_alloc_bytes(index)
+        }
+        ResolvedExpr::Field { object, .. } => expr_alloc_bytes(object),
+        ResolvedExpr::Unary { operand, .. } => expr_alloc_bytes(operand),
+        ResolvedExpr::Binary { left, right, .. } => {
+            expr_alloc_bytes(left) + expr_alloc_bytes(right)
+        }
+        // As with `if`, only the larger branch counts
+        ResolvedExpr::Conditional {
+            condition,
+            then_expr,
+            else_expr,
+        } => {
+            expr_alloc_bytes(condition)
+                + expr_alloc_bytes(then_expr).max(expr_alloc_bytes(else_expr))
+        }
+        ResolvedExpr::Call { args, .. } | ResolvedExpr::AssociatedFunctionCall { args, .. } => {
+            sum(args)
+        }
+        ResolvedExpr::MethodCall { object, args, .. }
+        | ResolvedExpr::VtableMethodCall { object, args, .. } => {
+            expr_alloc_bytes(object) + sum(args)
+        }
+        ResolvedExpr::CallIndirect { callee, args, .. } => expr_alloc_bytes(callee) + sum(args),
+        ResolvedExpr::Block { statements, expr } => {
+            static_alloc_bytes(statements) + expr_alloc_bytes(expr)
+        }
+        ResolvedExpr::CaptureStore { value, .. } => expr_alloc_bytes(value),
+        _ => 0,
+    }
+}
+
 /// Code generator that compiles resolved AST to bytecode.
 pub struct Codegen {
     functions: Vec<Function>,
     strings: Vec<String>,
     debug: DebugInfo,
     emit_debug: bool,
+    /// Return the value of a trailing top-level expression from main (`--eval-print`)
+    return_last_expr: bool,
+    /// Free non-escaping string concatenation intermediates with `FreeTemp`
+    free_temporaries: bool,
     /// Struct definitions for field access resolution
     structs: Vec<ResolvedStruct>,
     /// Map struct name -> (struct_index, field_name -> field_index)
@@ -83,6 +213,8 @@ pub struct Codegen {
     type_descriptors: Vec<crate::vm::TypeDescriptor>,
     /// Interface descriptors collected during compilation
     interface_descriptors: Vec<crate::vm::InterfaceDescriptor>,
+    /// Element templates of all-constant array literals (`ConstArray`)
+    const_arrays: Vec<Vec<Value>>,
     /// Interface name -> index in interface_descriptors
     interface_descriptor_indices: HashMap<String, usize>,
     /// Interface name -> sorted method names (from resolver)
@@ -97,6 +229,12 @@ pub struct Codegen {
     current_collection_elem_kind: Option<ElemKind>,
     /// Stack of loop contexts for break/continue backpatching
     loop_context_stack: Vec<LoopContext>,
+    /// Number of enclosing `try` blocks whose handler is active at this point
+    try_depth: usize,
+    /// Source names of locals in the current function (indexed by slot)
+    current_local_names: Vec<String>,
+    /// Debug info being collected for the current function
+    current_debug: FunctionDebugInfo,
 }
 
 /// Tracking info for break/continue inside a loop during codegen.
@@ -105,6 +243,8 @@ struct LoopContext {
     break_patches: Vec<usize>,
     /// Positions of continue Jmp(0) instructions to backpatch with continue_target
     continue_patches: Vec<usize>,
+    /// `try_depth` at loop entry; break/continue pop the handlers above it
+    try_depth: usize,
 }
 
 impl Default for Codegen {
@@ -124,6 +264,8 @@ impl Codegen {
             strings: Vec::new(),
             debug: DebugInfo::new(),
             emit_debug: true, // Enable debug info by default
+            return_last_expr: false,
+            free_temporaries: false,
             structs: Vec::new(),
             struct_field_indices: HashMap::new(),
             function_indices: HashMap::new(),
@@ -136,12 +278,16 @@ impl Codegen {
             type_descriptor_indices: HashMap::new(),
             type_descriptors: Vec::new(),
             interface_descriptors: Vec::new(),
+            const_arrays: Vec::new(),
             interface_descriptor_indices: HashMap::new(),
             interface_method_names: HashMap::new(),
             current_local_full_types: Vec::new(),
             struct_field_type_map: HashMap::new(),
             current_collection_elem_kind: None,
             loop_context_stack: Vec::new(),
+            try_depth: 0,
+            current_local_names: Vec::new(),
+            current_debug: FunctionDebugInfo::new(),
         }
     }
 
@@ -152,6 +298,8 @@ impl Codegen {
             strings: Vec::new(),
             debug: DebugInfo::new(),
             emit_debug: false,
+            return_last_expr: false,
+            free_temporaries: false,
             structs: Vec::new(),
             struct_field_indices: HashMap::new(),
             function_indices: HashMap::new(),
@@ -164,15 +312,31 @@ impl Codegen {
             type_descriptor_indices: HashMap::new(),
             type_descriptors: Vec::new(),
             interface_descriptors: Vec::new(),
+            const_arrays: Vec::new(),
             interface_descriptor_indices: HashMap::new(),
             interface_method_names: HashMap::new(),
             current_local_full_types: Vec::new(),
             struct_field_type_map: HashMap::new(),
             current_collection_elem_kind: None,
             loop_context_stack: Vec::new(),
+            try_depth: 0,
+            current_local_names: Vec::new(),
+            current_debug: FunctionDebugInfo::new(),
         }
     }
 
+    /// Make main return the value of its last statement when that statement
+    /// is an expression, instead of nil.
+    pub fn set_return_last_expr(&mut self, enabled: bool) {
+        self.return_last_expr = enabled;
+    }
+
+    /// Free intermediate results of string concatenation chains as soon as
+    /// they have been consumed, instead of leaving them to the GC.
+    pub fn set_free_temporaries(&mut self, enabled: bool) {
+        self.free_temporaries = enabled;
+    }
+
     /// Convert the typechecker's full Type to a simplified ValueType for the VM.
     fn type_to_value_type(ty: &Type) -> ValueType {
         match ty {
@@ -218,7 +382,7 @@ impl Codegen {
             ResolvedExpr::Array { .. } => ValueType::Ref,
             ResolvedExpr::Index { object_type, .. } => Self::infer_index_element_type(object_type),
             ResolvedExpr::Field { .. } => ValueType::I64, // Most common; TODO: improve
-            ResolvedExpr::Unary { op, operand } => match op {
+            ResolvedExpr::Unary { op, operand, .. } => match op {
                 UnaryOp::Neg => self.infer_expr_type(operand),
                 UnaryOp::Not => ValueType::I32, // bool result
             },
@@ -233,21 +397,41 @@ impl Codegen {
                 | BinaryOp::Or => ValueType::I32,
                 _ => self.infer_expr_type(left), // arithmetic: same type as operands
             },
-            ResolvedExpr::Call { func_index, .. } => self
-                .function_return_types
-                .get(*func_index)
-                .copied()
-                .unwrap_or(ValueType::I64),
+            ResolvedExpr::Conditional { then_expr, .. } => self.infer_expr_type(then_expr),
+            ResolvedExpr::Call { func_index, .. } | ResolvedExpr::MethodCall { func_index, .. } => {
+                self.function_return_types
+                    .get(*func_index)
+                    .copied()
+                    .unwrap_or(ValueType::I64)
+            }
             ResolvedExpr::StructLiteral { .. } => ValueType::Ref,
-            ResolvedExpr::MethodCall { .. } => ValueType::I64, // Default
             ResolvedExpr::AssociatedFunctionCall { .. } => ValueType::Ref,
             ResolvedExpr::SpawnFunc { .. } => ValueType::I64,
-            ResolvedExpr::Builtin { name, .. } => match name.as_str() {
-                "len" | "argc" | "__umul128_hi" | "__typeof" | "__heap_size" => ValueType::I64,
-                "channel" | "recv" | "argv" | "args" | "__alloc_heap" | "__alloc_string"
-                | "__null_ptr" | "__ptr_offset" => ValueType::Ref,
+            ResolvedExpr::SpawnPromise { .. } => ValueType::Ref,
+            ResolvedExpr::TypeOf { .. } => ValueType::Ref,
+            ResolvedExpr::Builtin { name, args, .. } => match name.as_str() {
+                "len" | "argc" | "__umul128_hi" | "udiv" | "urem" | "sat_add" | "sat_sub"
+                | "sat_mul" | "__typeof" | "__heap_size" | "dict_len" | "__string_index_of"
+                | "atomic_load" | "atomic_fetch_add" | "__cmp" => ValueType::I64,
+                "channel" | "atomic_new" | "recv" | "argv" | "args" | "checked_add"
+                | "__alloc_heap" | "__alloc_string" | "__null_ptr" | "__ptr_offset" | "slice"
+                | "to_bytes" | "from_bytes" | "dict_new" | "dict_set" | "dict_keys"
+                | "dict_values" | "dict_entries" | "__string_split" | "__string_join"
+                | "__string_substring" | "__string_replace" | "__sb_finish" | "type_name" => {
+                    ValueType::Ref
+                }
+                "dict_has" | "dict_delete" | "atomic_compare_exchange" => ValueType::I32,
                 "__heap_load" => ValueType::I64, // Returns raw slot value; type unknown at compile time
-                "send" | "join" | "print" | "__heap_store" => ValueType::Ref, // returns null
+                "__deep_eq" => ValueType::I32,
+                "__assert_type" => match args.get(1) {
+                    Some(ResolvedExpr::Int(1)) => ValueType::F64,
+                    Some(ResolvedExpr::Int(2)) => ValueType::I32,
+                    _ => ValueType::I64,
+                },
+                "send" | "join" | "print" | "__heap_store" | "atomic_store" | "__sb_append"
+                | "breakpoint" => {
+                    ValueType::Ref // returns null
+                }
                 _ => ValueType::I64,
             },
             ResolvedExpr::AsmBlock { .. } => ValueType::I64,
@@ -277,8 +461,10 @@ impl Codegen {
     /// Recursively scan a statement for return expressions and infer their type.
     fn scan_return_type(&self, stmt: &ResolvedStatement) -> Option<ValueType> {
         match stmt {
-            ResolvedStatement::Return { value: Some(expr) } => Some(self.infer_expr_type(expr)),
-            ResolvedStatement::Return { value: None } => Some(ValueType::Ref),
+            ResolvedStatement::Return {
+                value: Some(expr), ..
+            } => Some(self.infer_expr_type(expr)),
+            ResolvedStatement::Return { value: None, .. } => Some(ValueType::Ref),
             ResolvedStatement::If {
                 then_block,
                 else_block,
@@ -373,6 +559,106 @@ impl Codegen {
         }
     }
 
+    /// Add a constant array template to the pool and return its index.
+    fn add_const_array(&mut self, template: Vec<Value>) -> usize {
+        // Compare floats bitwise so 0.0 and -0.0 templates stay distinct
+        let same = |a: &Value, b: &Value| match (a, b) {
+            (Value::F64(x), Value::F64(y)) => x.to_bits() == y.to_bits(),
+            _ => a == b,
+        };
+        if let Some(idx) = self.const_arrays.iter().position(|t| {
+            t.len() == template.len() && t.iter().zip(&template).all(|(a, b)| same(a, b))
+        }) {
+            idx
+        } else {
+            self.const_arrays.push(template);
+            self.const_arrays.len() - 1
+        }
+    }
+
+    /// The value `expr` pushes, if it is a primitive literal (optionally negated).
+    fn const_element(expr: &ResolvedExpr) -> Option<Value> {
+        match expr {
+            ResolvedExpr::Int(n) => Some(Value::I64(*n)),
+            ResolvedExpr::Float(f) => Some(Value::F64(*f)),
+            ResolvedExpr::Bool(b) => Some(Value::Bool(*b)),
+            ResolvedExpr::Nil => Some(Value::Null),
+            ResolvedExpr::Unary {
+                op: UnaryOp::Neg,
+                operand,
+                ..
+            } => match operand.as_ref() {
+                ResolvedExpr::Int(n) => n.checked_neg().map(Value::I64),
+                ResolvedExpr::Float(f) => Some(Value::F64(-f)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// `k` if `expr` is the integer literal `2^k` with `k >= 1`.
+    fn pow2_exponent(expr: &ResolvedExpr) -> Option<u32> {
+        match expr {
+            ResolvedExpr::Int(n) if *n > 1 && (*n as u64).is_power_of_two() => {
+                Some(n.trailing_zeros())
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether `expr` is an int that is never negative, e.g. a length or a
+    /// value masked with a non-negative literal.
+    fn is_non_negative_int(expr: &ResolvedExpr) -> bool {
+        match expr {
+            ResolvedExpr::Int(n) => *n >= 0,
+            ResolvedExpr::Builtin { name, .. } => {
+                matches!(name.as_str(), "len" | "dict_len" | "__heap_size")
+            }
+            ResolvedExpr::Binary {
+                op: BinaryOp::BitwiseAnd,
+                left,
+                right,
+                ..
+            } => Self::is_non_negative_int(left) || Self::is_non_negative_int(right),
+            _ => false,
+        }
+    }
+
+    /// Strength-reduce an int multiply or divide by a power-of-two literal
+    /// into a shift. Returns false (emitting nothing) when it doesn't apply.
+    ///
+    /// `x * 2^k` wraps exactly like `x << k`. `x / 2^k` truncates toward zero
+    /// while `x >> k` rounds toward negative infinity, so division is only
+    /// reduced when the dividend is known to be non-negative; fixing up a
+    /// negative dividend takes more ops than the `I64DivS` it replaces.
+    fn compile_pow2_arith(
+        &mut self,
+        op: BinaryOp,
+        left: &ResolvedExpr,
+        right: &ResolvedExpr,
+        span: Span,
+        ops: &mut Vec<Op>,
+    ) -> Result<bool, String> {
+        let (operand, k, shift) = match op {
+            // The literal has no side effects, so either operand order works
+            BinaryOp::Mul => match (Self::pow2_exponent(right), Self::pow2_exponent(left)) {
+                (Some(k), _) => (left, k, Op::I64Shl),
+                (None, Some(k)) => (right, k, Op::I64Shl),
+                (None, None) => return Ok(false),
+            },
+            BinaryOp::Div if Self::is_non_negative_int(left) => match Self::pow2_exponent(right) {
+                Some(k) => (left, k, Op::I64ShrS),
+                None => return Ok(false),
+            },
+            _ => return Ok(false),
+        };
+        self.compile_expr(operand, ops)?;
+        self.mark_debug_line(ops.len(), span);
+        ops.push(Op::I64Const(k as i64));
+        ops.push(shift);
+        Ok(true)
+    }
+
     /// Add a type descriptor (or return existing index) for dyn type info.
     /// If the tag_name already exists but field_names/field_type_tags/aux_type_tags are more complete, update them.
     fn add_type_descriptor(
@@ -469,11 +755,12 @@ impl Codegen {
                 .map(Self::type_to_value_type)
                 .collect();
             self.current_locals_count = func.locals_count;
+            self.current_local_names = func.local_names.clone();
             let compiled = self.compile_function(func)?;
             self.functions.push(compiled);
-            // Add debug info placeholder for each function
             if self.emit_debug {
-                self.debug.functions.push(FunctionDebugInfo::new());
+                let info = std::mem::take(&mut self.current_debug);
+                self.debug.functions.push(info);
             }
         }
 
@@ -489,12 +776,24 @@ impl Codegen {
         self.current_local_full_types = program.main_local_types.clone();
         self.current_collection_elem_kind =
             Self::infer_collection_elem_kind_from_locals(&program.main_local_types);
+        self.current_local_names = program.main_local_names;
+        let ends_with_expr = matches!(
+            program.main_body.last(),
+            Some(ResolvedStatement::Expr { .. })
+        );
         let mut main_ops = Vec::new();
         for stmt in program.main_body {
             self.compile_statement(&stmt, &mut main_ops)?;
         }
+        if self.emit_debug {
+            self.debug.main = std::mem::take(&mut self.current_debug);
+        }
         // End of main
-        main_ops.push(Op::RefNull); // Return value for main
+        if self.return_last_expr && ends_with_expr {
+            main_ops.pop(); // Keep the last expression's value instead of dropping it
+        } else {
+            main_ops.push(Op::RefNull); // Return value for main
+        }
         main_ops.push(Op::Ret);
 
         let main_local_types = self.current_local_types.clone();
@@ -535,14 +834,20 @@ impl Codegen {
             None
         };
 
-        Ok(Chunk {
+        let chunk = Chunk {
             functions: self.functions.clone(),
             main: main_func,
             strings: self.strings.clone(),
             type_descriptors: self.type_descriptors.clone(),
             interface_descriptors: self.interface_descriptors.clone(),
+            const_arrays: self.const_arrays.clone(),
             debug,
-        })
+            metadata: ChunkMetadata::default(),
+        };
+        // The resolver rejects bad calls it can see; this also covers
+        // desugared and `__emit`ted calls, so the VM needn't check `Call` arity
+        chunk.validate_call_arities()?;
+        Ok(chunk)
     }
 
     /// Determine the collection element ElemKind from a struct's `data` field type.
@@ -601,14 +906,33 @@ impl Codegen {
 
         let mut ops = Vec::new();
 
+        // Anchor pc 0 at the definition so the line table has no leading gap
+        self.mark_debug_line(0, func.span);
+
+        // Parameters are live for the whole function body
+        for slot in 0..func.params.len() {
+            self.declare_debug_local(slot, 0);
+        }
+
         for stmt in &func.body {
             self.compile_statement(stmt, &mut ops)?;
         }
 
-        // Implicit return nil
-        if !matches!(ops.last(), Some(Op::Ret)) {
-            ops.push(Op::RefNull);
-            ops.push(Op::Ret);
+        // Implicit return nil, unless every branch already returned or threw:
+        // then the end is unreachable, and reaching it is a compiler bug.
+        // Branches that skip an else block jump to the end even when the last
+        // op is a `Ret`, so those need the epilogue too.
+        let end = ops.len();
+        let jumps_to_end = ops.iter().any(|op| {
+            matches!(op, Op::Jmp(t) | Op::BrIf(t) | Op::BrIfFalse(t) | Op::TryBegin(t) if *t == end)
+        });
+        if jumps_to_end || !matches!(ops.last(), Some(Op::Ret)) {
+            if always_exits(&func.body) {
+                ops.push(Op::Unreachable);
+            } else {
+                ops.push(Op::RefNull);
+                ops.push(Op::Ret);
+            }
         }
 
         let local_types = func
@@ -627,6 +951,64 @@ impl Codegen {
         })
     }
 
+    /// Whether `expr` is a string `+`, compiled as a call to `string_concat`.
+    fn is_string_concat(&self, expr: &ResolvedExpr) -> bool {
+        matches!(expr, ResolvedExpr::Binary { op: BinaryOp::Add, left, .. }
+            if self.infer_expr_type(left) == ValueType::Ref)
+    }
+
+    /// Call `string_concat` on the two strings on top of the stack.
+    fn emit_string_concat(&mut self, ops: &mut Vec<Op>) -> Result<(), String> {
+        if let Some(&func_idx) = self.function_indices.get("string_concat") {
+            if self
+                .inline_functions
+                .get(func_idx)
+                .is_some_and(|f| f.is_inline)
+                && self.inline_return_patches_stack.len() < MAX_INLINE_DEPTH
+            {
+                self.compile_inline_call(func_idx, 2, ops)?;
+            } else {
+                ops.push(Op::Call(func_idx, 2));
+            }
+        } else {
+            ops.push(Op::I64Add); // fallback if no stdlib
+        }
+        Ok(())
+    }
+
+    /// Compile a string `left + right`, freeing operands that are themselves
+    /// concatenations. Such an operand is a fresh string that only flows into
+    /// `string_concat`, which copies its bytes, so it is unreachable once the
+    /// call returns. It is parked in a scratch local for the `FreeTemp`.
+    fn compile_string_concat_freeing_temps(
+        &mut self,
+        left: &ResolvedExpr,
+        right: &ResolvedExpr,
+        ops: &mut Vec<Op>,
+    ) -> Result<(), String> {
+        let mut temps = Vec::new();
+        for operand in [left, right] {
+            self.compile_expr(operand, ops)?;
+            if self.is_string_concat(operand) {
+                let slot = self.current_locals_count;
+                self.current_locals_count += 1;
+                if self.current_local_types.len() <= slot {
+                    self.current_local_types.resize(slot + 1, ValueType::I64);
+                }
+                self.current_local_types[slot] = ValueType::Ref;
+                ops.push(Op::Dup);
+                ops.push(Op::LocalSet(slot));
+                temps.push(slot);
+            }
+        }
+        self.emit_string_concat(ops)?;
+        for slot in temps {
+            ops.push(Op::LocalGet(slot));
+            ops.push(Op::FreeTemp);
+        }
+        Ok(())
+    }
+
     /// Inline-expand a function call. Arguments must already be on the stack.
     fn compile_inline_call(
         &mut self,
@@ -640,6 +1022,8 @@ impl Codegen {
         let saved_offset = self.local_offset;
         let saved_local_types = self.current_local_types.clone();
         let saved_collection_elem_kind = self.current_collection_elem_kind;
+        // Returns from the inlined body only leave the try blocks inside it
+        let saved_try_depth = std::mem::take(&mut self.try_depth);
 
         // Set collection elem kind for the inlined function (e.g., Vec__int::set → I64)
         let inline_ek = func
@@ -697,7 +1081,82 @@ impl Codegen {
             self.current_local_types.push(ValueType::I64);
         }
         self.current_collection_elem_kind = saved_collection_elem_kind;
+        self.try_depth = saved_try_depth;
+
+        Ok(())
+    }
+
+    /// Record that `slot` holds its named source variable from `pc` onward.
+    /// Compiler temporaries (`__` prefix) and inlined callee locals are skipped.
+    fn declare_debug_local(&mut self, slot: usize, pc: usize) {
+        if !self.emit_debug || !self.inline_return_patches_stack.is_empty() {
+            return;
+        }
+        if let Some(name) = self.current_local_names.get(slot)
+            && !name.starts_with("__")
+        {
+            self.current_debug.add_local(name.clone(), slot, pc);
+        }
+    }
+
+    /// Record that the ops emitted from `pc` onward belong to source `line`.
+    /// Inlined callee bodies keep the caller's line.
+    fn mark_debug_line(&mut self, pc: usize, span: Span) {
+        if !self.emit_debug || !self.inline_return_patches_stack.is_empty() || span.line == 0 {
+            return;
+        }
+        let entries = &mut self.current_debug.lines.entries;
+        match entries.last_mut() {
+            Some(last) if last.line as usize == span.line => {}
+            Some(last) if last.pc as usize == pc => {
+                last.line = span.line as u32;
+                last.column = span.column as u16;
+            }
+            _ => self.current_debug.lines.add(pc, span.line, span.column),
+        }
+    }
+
+    /// Mark the start of a lexical scope for debug info.
+    fn open_debug_scope(&self) -> usize {
+        self.current_debug.locals.len()
+    }
+
+    /// End all bindings declared since `mark` at `pc`.
+    fn close_debug_scope(&mut self, mark: usize, pc: usize) {
+        for local in &mut self.current_debug.locals[mark..] {
+            if local.scope_end == u32::MAX {
+                local.scope_end = pc as u32;
+            }
+        }
+    }
+
+    /// Compile a block of statements as its own lexical scope.
+    /// Emit a `GcHint` in front of an allocation of `bytes` when it is large
+    /// enough that collecting first beats growing the heap.
+    fn emit_large_alloc_gc_hint(&self, bytes: usize, ops: &mut Vec<Op>) {
+        if bytes >= LARGE_ALLOC_BYTES {
+            ops.push(Op::GcHint(bytes));
+        }
+    }
+
+    /// Emit a `GcHint` at the top of a loop body that allocates every iteration.
+    fn emit_loop_gc_hint(&self, body: &[ResolvedStatement], ops: &mut Vec<Op>) {
+        let bytes = static_alloc_bytes(body);
+        if bytes > 0 {
+            ops.push(Op::GcHint(bytes));
+        }
+    }
 
+    fn compile_block(
+        &mut self,
+        stmts: &[ResolvedStatement],
+        ops: &mut Vec<Op>,
+    ) -> Result<(), String> {
+        let mark = self.open_debug_scope();
+        for stmt in stmts {
+            self.compile_statement(stmt, ops)?;
+        }
+        self.close_debug_scope(mark, ops.len());
         Ok(())
     }
 
@@ -706,12 +1165,16 @@ impl Codegen {
         stmt: &ResolvedStatement,
         ops: &mut Vec<Op>,
     ) -> Result<(), String> {
+        if let Some(span) = stmt.span() {
+            self.mark_debug_line(ops.len(), span);
+        }
         match stmt {
-            ResolvedStatement::Let { slot, init } => {
+            ResolvedStatement::Let { slot, init, .. } => {
                 self.compile_expr(init, ops)?;
                 ops.push(Op::LocalSet(*slot + self.local_offset));
+                self.declare_debug_local(*slot, ops.len());
             }
-            ResolvedStatement::Assign { slot, value } => {
+            ResolvedStatement::Assign { slot, value, .. } => {
                 self.compile_expr(value, ops)?;
                 ops.push(Op::LocalSet(*slot + self.local_offset));
             }
@@ -750,6 +1213,7 @@ impl Codegen {
                 field,
                 value,
                 struct_name,
+                ..
             } => {
                 // Check if this might be a struct field (structs are compiled as arrays)
                 if let Some(idx) = self.get_field_index(field, struct_name.as_deref()) {
@@ -769,6 +1233,7 @@ impl Codegen {
                 condition,
                 then_block,
                 else_block,
+                ..
             } => {
                 self.compile_expr(condition, ops)?;
 
@@ -777,9 +1242,7 @@ impl Codegen {
                 ops.push(Op::BrIfFalse(0)); // Placeholder
 
                 // Then block
-                for stmt in then_block {
-                    self.compile_statement(stmt, ops)?;
-                }
+                self.compile_block(then_block, ops)?;
 
                 if let Some(else_stmts) = else_block {
                     // Jump over else block
@@ -791,9 +1254,7 @@ impl Codegen {
                     ops[jump_to_else] = Op::BrIfFalse(else_start);
 
                     // Else block
-                    for stmt in else_stmts {
-                        self.compile_statement(stmt, ops)?;
-                    }
+                    self.compile_block(else_stmts, ops)?;
 
                     // Patch jump over else
                     let after_else = ops.len();
@@ -808,6 +1269,7 @@ impl Codegen {
                 condition,
                 body,
                 post_body,
+                ..
             } => {
                 let loop_start = ops.len();
 
@@ -816,14 +1278,15 @@ impl Codegen {
                 let jump_to_end = ops.len();
                 ops.push(Op::BrIfFalse(0)); // Placeholder
 
+                self.emit_loop_gc_hint(body, ops);
+
                 self.loop_context_stack.push(LoopContext {
                     break_patches: Vec::new(),
                     continue_patches: Vec::new(),
+                    try_depth: self.try_depth,
                 });
 
-                for stmt in body {
-                    self.compile_statement(stmt, ops)?;
-                }
+                self.compile_block(body, ops)?;
 
                 // continue target: just before post_body (or loop_start if no post_body)
                 let continue_target = ops.len();
@@ -850,6 +1313,7 @@ impl Codegen {
                 slot,
                 iterable,
                 body,
+                ..
             } => {
                 // For-in loop: for x in arr { body }
                 // Desugars to:
@@ -892,16 +1356,20 @@ impl Codegen {
                 ops.push(Op::LocalGet(idx_slot));
                 ops.push(Op::HeapLoadDyn(ElemKind::Tagged));
                 ops.push(Op::LocalSet(var_slot));
+                let loop_var_scope = self.open_debug_scope();
+                self.declare_debug_local(*slot, ops.len());
+
+                self.emit_loop_gc_hint(body, ops);
 
                 self.loop_context_stack.push(LoopContext {
                     break_patches: Vec::new(),
                     continue_patches: Vec::new(),
+                    try_depth: self.try_depth,
                 });
 
                 // Body
-                for stmt in body {
-                    self.compile_statement(stmt, ops)?;
-                }
+                self.compile_block(body, ops)?;
+                self.close_debug_scope(loop_var_scope, ops.len());
 
                 // continue target: just before idx increment
                 let continue_target = ops.len();
@@ -928,11 +1396,31 @@ impl Codegen {
                     ops[patch_idx] = Op::Jmp(continue_target);
                 }
             }
-            ResolvedStatement::Return { value } => {
-                if let Some(value) = value {
-                    self.compile_expr(value, ops)?;
-                } else {
-                    ops.push(Op::RefNull); // Return nil for void
+            ResolvedStatement::Return { value, .. } => {
+                match value {
+                    // A call in tail position replaces the current frame, unless
+                    // a try handler in this frame must stay live or we are inside
+                    // an inline expansion that has no frame of its own
+                    Some(ResolvedExpr::Call {
+                        func_index, args, ..
+                    }) if self.try_depth == 0
+                        && self.inline_return_patches_stack.is_empty()
+                        && !self
+                            .inline_functions
+                            .get(*func_index)
+                            .is_some_and(|f| f.is_inline) =>
+                    {
+                        for arg in args {
+                            self.compile_expr(arg, ops)?;
+                        }
+                        ops.push(Op::TailCall(*func_index, args.len()));
+                    }
+                    Some(value) => self.compile_expr(value, ops)?,
+                    None => ops.push(Op::RefNull), // Return nil for void
+                }
+                // Leave enclosing try blocks after the value is computed
+                for _ in 0..self.try_depth {
+                    ops.push(Op::TryEnd);
                 }
                 if let Some(patches) = self.inline_return_patches_stack.last_mut() {
                     // Inside inline expansion: jump to end of inline block
@@ -942,7 +1430,7 @@ impl Codegen {
                     ops.push(Op::Ret);
                 }
             }
-            ResolvedStatement::Throw { value } => {
+            ResolvedStatement::Throw { value, .. } => {
                 self.compile_expr(value, ops)?;
                 ops.push(Op::Throw);
             }
@@ -950,15 +1438,17 @@ impl Codegen {
                 try_block,
                 catch_slot,
                 catch_block,
+                ..
             } => {
                 // TryBegin with placeholder for catch handler address
                 let try_begin_idx = ops.len();
                 ops.push(Op::TryBegin(0)); // Placeholder
 
                 // Compile try block
-                for stmt in try_block {
-                    self.compile_statement(stmt, ops)?;
-                }
+                self.try_depth += 1;
+                let result = self.compile_block(try_block, ops);
+                self.try_depth -= 1;
+                result?;
 
                 // End of try block - remove handler and jump over catch
                 ops.push(Op::TryEnd);
@@ -971,21 +1461,22 @@ impl Codegen {
 
                 // Exception value is on stack, store to catch variable slot
                 ops.push(Op::LocalSet(*catch_slot + self.local_offset));
+                let catch_scope = self.open_debug_scope();
+                self.declare_debug_local(*catch_slot, ops.len());
 
                 // Compile catch block
-                for stmt in catch_block {
-                    self.compile_statement(stmt, ops)?;
-                }
+                self.compile_block(catch_block, ops)?;
+                self.close_debug_scope(catch_scope, ops.len());
 
                 // Patch jump over catch
                 let after_catch = ops.len();
                 ops[jump_over_catch] = Op::Jmp(after_catch);
             }
-            ResolvedStatement::Expr { expr } => {
+            ResolvedStatement::Expr { expr, .. } => {
                 self.compile_expr(expr, ops)?;
                 ops.push(Op::Drop); // Discard result
             }
-            ResolvedStatement::RefCellStore { slot, value } => {
+            ResolvedStatement::RefCellStore { slot, value, .. } => {
                 // Store to a promoted var variable through its RefCell (outer scope)
                 // LocalGet(slot) gives the RefCell ref, then store value into RefCell[0]
                 ops.push(Op::LocalGet(*slot + self.local_offset));
@@ -997,6 +1488,7 @@ impl Codegen {
                 expr,
                 arms,
                 default_block,
+                ..
             } => {
                 // Compile the dyn expression and store in the dyn_slot
                 self.compile_expr(expr, ops)?;
@@ -1061,10 +1553,11 @@ impl Codegen {
                         }
                     }
 
-                    // Compile arm body
-                    for stmt in &arm.body {
-                        self.compile_statement(stmt, ops)?;
-                    }
+                    // Compile arm body with the bound variable in scope
+                    let arm_scope = self.open_debug_scope();
+                    self.declare_debug_local(arm.var_slot, ops.len());
+                    self.compile_block(&arm.body, ops)?;
+                    self.close_debug_scope(arm_scope, ops.len());
 
                     // Jump to end of match
                     jump_to_end_patches.push(ops.len());
@@ -1080,9 +1573,7 @@ impl Codegen {
                 }
 
                 // Default block
-                for stmt in default_block {
-                    self.compile_statement(stmt, ops)?;
-                }
+                self.compile_block(default_block, ops)?;
 
                 // Patch all jumps to end
                 let end = ops.len();
@@ -1095,6 +1586,10 @@ impl Codegen {
                     .loop_context_stack
                     .last_mut()
                     .expect("break outside of loop (should be caught by resolver)");
+                // Leave the try blocks entered inside the loop
+                for _ in ctx.try_depth..self.try_depth {
+                    ops.push(Op::TryEnd);
+                }
                 ctx.break_patches.push(ops.len());
                 ops.push(Op::Jmp(0)); // Placeholder, patched at loop end
             }
@@ -1103,6 +1598,9 @@ impl Codegen {
                     .loop_context_stack
                     .last_mut()
                     .expect("continue outside of loop (should be caught by resolver)");
+                for _ in ctx.try_depth..self.try_depth {
+                    ops.push(Op::TryEnd);
+                }
                 ctx.continue_patches.push(ops.len());
                 ops.push(Op::Jmp(0)); // Placeholder, patched at continue target
             }
@@ -1112,6 +1610,11 @@ impl Codegen {
     }
 
     fn compile_expr(&mut self, expr: &ResolvedExpr, ops: &mut Vec<Op>) -> Result<(), String> {
+        // Operand ops take their own lines; arms with a span re-mark before
+        // their own op so it maps back to the operator or call, not the last operand
+        if let Some(span) = expr.span() {
+            self.mark_debug_line(ops.len(), span);
+        }
         match expr {
             ResolvedExpr::Int(value) => {
                 ops.push(Op::I64Const(*value));
@@ -1137,12 +1640,24 @@ impl Codegen {
                 ops.push(Op::LocalGet(*slot + self.local_offset));
             }
             ResolvedExpr::Array { elements } => {
+                // Literals of constant primitives are copied from a template
+                // in one op instead of being pushed element by element
+                let template: Option<Vec<Value>> =
+                    elements.iter().map(Self::const_element).collect();
+                if let Some(template) = template.filter(|t| !t.is_empty()) {
+                    self.emit_large_alloc_gc_hint(object_size_bytes(template.len() as u32), ops);
+                    let idx = self.add_const_array(template);
+                    ops.push(Op::ConstArray(idx));
+                    return Ok(());
+                }
+
                 // Array<T> struct layout: [ptr, len]
                 // 1. Push all elements and allocate data array
                 let n = elements.len();
                 for elem in elements {
                     self.compile_expr(elem, ops)?;
                 }
+                self.emit_large_alloc_gc_hint(object_size_bytes(n as u32), ops);
                 ops.push(Op::HeapAlloc(n)); // data array with n elements
                 // Stack: [data_ptr]
 
@@ -1195,8 +1710,9 @@ impl Codegen {
                     ));
                 }
             }
-            ResolvedExpr::Unary { op, operand } => {
+            ResolvedExpr::Unary { op, operand, span } => {
                 self.compile_expr(operand, ops)?;
+                self.mark_debug_line(ops.len(), *span);
                 match op {
                     UnaryOp::Neg => match self.infer_expr_type(operand) {
                         ValueType::I64 => ops.push(Op::I64Neg),
@@ -1211,7 +1727,8 @@ impl Codegen {
                 op,
                 left,
                 right,
-                operand_type: _,
+                operand_type,
+                span,
             } => {
                 // Handle short-circuit evaluation for && and ||
                 // BrIfFalse/BrIf pop the condition value, so we need to Dup first
@@ -1248,8 +1765,19 @@ impl Codegen {
                     _ => {}
                 }
 
+                if self.free_temporaries && self.is_string_concat(expr) {
+                    return self.compile_string_concat_freeing_temps(left, right, ops);
+                }
+
+                if matches!(operand_type, Some(Type::Int))
+                    && self.compile_pow2_arith(*op, left, right, *span, ops)?
+                {
+                    return Ok(());
+                }
+
                 self.compile_expr(left, ops)?;
                 self.compile_expr(right, ops)?;
+                self.mark_debug_line(ops.len(), *span);
 
                 match op {
                     BinaryOp::Add => match self.infer_expr_type(left) {
@@ -1257,23 +1785,7 @@ impl Codegen {
                         ValueType::F64 => ops.push(Op::F64Add),
                         ValueType::I32 => ops.push(Op::I32Add),
                         ValueType::F32 => ops.push(Op::F32Add),
-                        ValueType::Ref => {
-                            // String concatenation: call string_concat(a, b)
-                            if let Some(&func_idx) = self.function_indices.get("string_concat") {
-                                if self
-                                    .inline_functions
-                                    .get(func_idx)
-                                    .is_some_and(|f| f.is_inline)
-                                    && self.inline_return_patches_stack.len() < MAX_INLINE_DEPTH
-                                {
-                                    self.compile_inline_call(func_idx, 2, ops)?;
-                                } else {
-                                    ops.push(Op::Call(func_idx, 2));
-                                }
-                            } else {
-                                ops.push(Op::I64Add); // fallback if no stdlib
-                            }
-                        }
+                        ValueType::Ref => self.emit_string_concat(ops)?,
                     },
                     BinaryOp::Sub => match self.infer_expr_type(left) {
                         ValueType::I64 => ops.push(Op::I64Sub),
@@ -1391,7 +1903,11 @@ impl Codegen {
                     BinaryOp::And | BinaryOp::Or => unreachable!(),
                 }
             }
-            ResolvedExpr::Call { func_index, args } => {
+            ResolvedExpr::Call {
+                func_index,
+                args,
+                span,
+            } => {
                 if self.inline_return_patches_stack.len() < MAX_INLINE_DEPTH
                     && self
                         .inline_functions
@@ -1402,12 +1918,14 @@ impl Codegen {
                     for arg in args {
                         self.compile_expr(arg, ops)?;
                     }
+                    self.mark_debug_line(ops.len(), *span);
                     self.compile_inline_call(*func_index, args.len(), ops)?;
                 } else {
                     // Normal call
                     for arg in args {
                         self.compile_expr(arg, ops)?;
                     }
+                    self.mark_debug_line(ops.len(), *span);
                     ops.push(Op::Call(*func_index, args.len()));
                 }
             }
@@ -1427,6 +1945,13 @@ impl Codegen {
                         self.compile_expr(&args[0], ops)?;
                         ops.push(Op::HeapSize);
                     }
+                    "type_name" => {
+                        if args.len() != 1 {
+                            return Err("type_name takes exactly 1 argument".to_string());
+                        }
+                        self.compile_expr(&args[0], ops)?;
+                        ops.push(Op::TypeName);
+                    }
                     "__hostcall" => {
                         // __hostcall(num, ...args) -> result
                         // First argument must be a compile-time constant (hostcall number)
@@ -1460,6 +1985,50 @@ impl Codegen {
                         // Both Array<T> and String have [ptr, len] layout
                         ops.push(Op::HeapLoad(1));
                     }
+                    "slice" => {
+                        if args.len() != 3 {
+                            return Err("slice takes exactly 3 arguments".to_string());
+                        }
+                        for arg in args {
+                            self.compile_expr(arg, ops)?;
+                        }
+                        ops.push(Op::ArraySlice);
+                    }
+                    "to_bytes" | "from_bytes" => {
+                        if args.len() != 1 {
+                            return Err(format!("{} takes exactly 1 argument", name));
+                        }
+                        self.compile_expr(&args[0], ops)?;
+                        ops.push(if name == "to_bytes" {
+                            Op::StringToBytes
+                        } else {
+                            Op::BytesToString
+                        });
+                    }
+                    "dict_new" | "dict_get" | "dict_set" | "dict_has" | "dict_delete"
+                    | "dict_len" | "dict_keys" | "dict_values" | "dict_entries" => {
+                        let (arity, op) = match name.as_str() {
+                            "dict_new" => (0, Op::MapNew),
+                            "dict_get" => (2, Op::MapGet),
+                            "dict_set" => (3, Op::MapSet),
+                            "dict_has" => (2, Op::MapHas),
+                            "dict_delete" => (2, Op::MapDelete),
+                            "dict_len" => (1, Op::MapLen),
+                            "dict_keys" => (1, Op::MapKeys),
+                            "dict_values" => (1, Op::MapValues),
+                            _ => (1, Op::MapEntries),
+                        };
+                        if args.len() != arity {
+                            return Err(format!("{} takes exactly {} arguments", name, arity));
+                        }
+                        for arg in args {
+                            self.compile_expr(arg, ops)?;
+                        }
+                        ops.push(op);
+                        if name == "dict_set" {
+                            ops.push(Op::RefNull);
+                        }
+                    }
                     "__umul128_hi" => {
                         if args.len() != 2 {
                             return Err("__umul128_hi takes exactly 2 arguments".to_string());
@@ -1468,6 +2037,48 @@ impl Codegen {
                         self.compile_expr(&args[1], ops)?;
                         ops.push(Op::UMul128Hi);
                     }
+                    "udiv" | "urem" => {
+                        if args.len() != 2 {
+                            return Err(format!("{} takes exactly 2 arguments", name));
+                        }
+                        self.compile_expr(&args[0], ops)?;
+                        self.compile_expr(&args[1], ops)?;
+                        ops.push(if name == "udiv" {
+                            Op::I64DivU
+                        } else {
+                            Op::I64RemU
+                        });
+                    }
+                    "sat_add" | "sat_sub" | "sat_mul" | "checked_add" => {
+                        if args.len() != 2 {
+                            return Err(format!("{} takes exactly 2 arguments", name));
+                        }
+                        self.compile_expr(&args[0], ops)?;
+                        self.compile_expr(&args[1], ops)?;
+                        ops.push(match name.as_str() {
+                            "sat_add" => Op::I64AddSat,
+                            "sat_sub" => Op::I64SubSat,
+                            "sat_mul" => Op::I64MulSat,
+                            _ => Op::I64AddChecked,
+                        });
+                    }
+                    "__deep_eq" => {
+                        // The resolver appends the array depth of the operands
+                        let [a, b, ResolvedExpr::Int(depth)] = args.as_slice() else {
+                            return Err("__deep_eq takes exactly 2 arguments".to_string());
+                        };
+                        self.compile_expr(a, ops)?;
+                        self.compile_expr(b, ops)?;
+                        ops.push(Op::DeepEq(*depth as usize));
+                    }
+                    "__cmp" => {
+                        if args.len() != 2 {
+                            return Err("__cmp takes exactly 2 arguments".to_string());
+                        }
+                        self.compile_expr(&args[0], ops)?;
+                        self.compile_expr(&args[1], ops)?;
+                        ops.push(Op::Compare);
+                    }
                     // Thread builtins
                     "spawn" => {
                         // spawn is handled specially in resolver as SpawnFunc
@@ -1505,6 +2116,36 @@ impl Codegen {
                         self.compile_expr(&args[0], ops)?;
                         ops.push(Op::ThreadJoin);
                     }
+                    "await" => {
+                        if args.len() != 1 {
+                            return Err("await takes exactly 1 argument (promise)".to_string());
+                        }
+                        self.compile_expr(&args[0], ops)?;
+                        ops.push(Op::Await);
+                    }
+                    "atomic_new"
+                    | "atomic_load"
+                    | "atomic_store"
+                    | "atomic_fetch_add"
+                    | "atomic_compare_exchange" => {
+                        let (arity, op) = match name.as_str() {
+                            "atomic_new" => (1, Op::AtomicNew),
+                            "atomic_load" => (1, Op::AtomicLoad),
+                            "atomic_store" => (2, Op::AtomicStore),
+                            "atomic_fetch_add" => (2, Op::AtomicFetchAdd),
+                            _ => (3, Op::AtomicCompareExchange),
+                        };
+                        if args.len() != arity {
+                            return Err(format!("{} takes exactly {} arguments", name, arity));
+                        }
+                        for arg in args {
+                            self.compile_expr(arg, ops)?;
+                        }
+                        ops.push(op);
+                        if name == "atomic_store" {
+                            ops.push(Op::RefNull);
+                        }
+                    }
                     // Low-level heap intrinsics (for stdlib implementation)
                     "__heap_load" => {
                         // __heap_load(ref, idx) -> value at ref[idx]
@@ -1537,6 +2178,11 @@ impl Codegen {
                             return Err("__alloc_heap takes exactly 1 argument (size)".to_string());
                         }
                         self.compile_expr(&args[0], ops)?;
+                        if let ResolvedExpr::Int(n) = args[0]
+                            && n > 0
+                        {
+                            self.emit_large_alloc_gc_hint(object_size_bytes(n as u32), ops);
+                        }
                         let ek = self
                             .current_collection_elem_kind
                             .unwrap_or(ElemKind::Tagged);
@@ -1570,6 +2216,52 @@ impl Codegen {
                         self.compile_expr(&args[1], ops)?;
                         ops.push(Op::HeapAlloc(2)); // String struct with [ptr, len]
                     }
+                    "__string_split" | "__string_join" | "__string_substring"
+                    | "__string_index_of" | "__string_replace" => {
+                        let (arity, op) = match name.as_str() {
+                            "__string_split" => (2, Op::StringSplit),
+                            "__string_join" => (2, Op::StringJoin),
+                            "__string_substring" => (3, Op::StringSubstring),
+                            "__string_index_of" => (2, Op::StringIndexOf),
+                            _ => (3, Op::StringReplace),
+                        };
+                        if args.len() != arity {
+                            return Err(format!("{} takes exactly {} arguments", name, arity));
+                        }
+                        for arg in args {
+                            self.compile_expr(arg, ops)?;
+                        }
+                        ops.push(op);
+                    }
+                    "__sb_append" | "__sb_finish" => {
+                        let (arity, op) = match name.as_str() {
+                            "__sb_append" => (2, Op::StringBuilderAppend),
+                            _ => (1, Op::StringBuilderFinish),
+                        };
+                        if args.len() != arity {
+                            return Err(format!("{} takes exactly {} arguments", name, arity));
+                        }
+                        for arg in args {
+                            self.compile_expr(arg, ops)?;
+                        }
+                        ops.push(op);
+                        if name == "__sb_append" {
+                            // sb_append returns nil
+                            ops.push(Op::RefNull);
+                        }
+                    }
+                    "__assert_type" => {
+                        // __assert_type(value, tag) -> value, erroring on a tag mismatch
+                        let tag = match args.get(1) {
+                            Some(ResolvedExpr::Int(tag)) if args.len() == 2 => *tag,
+                            _ => {
+                                return Err("__assert_type takes a value and a constant type tag"
+                                    .to_string());
+                            }
+                        };
+                        self.compile_expr(&args[0], ops)?;
+                        ops.push(Op::AssertType(tag as u8));
+                    }
                     "__call_func" => {
                         // __call_func(func_idx, arg) -> result
                         // Calls function by dynamic index with one argument
@@ -1589,6 +2281,13 @@ impl Codegen {
                         }
                         ops.push(Op::Argc);
                     }
+                    "breakpoint" => {
+                        if !args.is_empty() {
+                            return Err("breakpoint takes no arguments".to_string());
+                        }
+                        ops.push(Op::DebugBreak);
+                        ops.push(Op::RefNull);
+                    }
                     "argv" => {
                         if args.len() != 1 {
                             return Err("argv takes exactly 1 argument (index)".to_string());
@@ -1605,11 +2304,48 @@ impl Codegen {
                     _ => return Err(format!("unknown builtin '{}'", name)),
                 }
             }
-            ResolvedExpr::SpawnFunc { func_index } => {
-                ops.push(Op::ThreadSpawn(*func_index));
+            ResolvedExpr::Conditional {
+                condition,
+                then_expr,
+                else_expr,
+            } => {
+                // Each branch leaves its value on the stack; only one of them runs
+                self.compile_expr(condition, ops)?;
+                let jump_to_else = ops.len();
+                ops.push(Op::BrIfFalse(0)); // Placeholder
+                self.compile_expr(then_expr, ops)?;
+                let jump_to_end = ops.len();
+                ops.push(Op::Jmp(0)); // Placeholder
+                ops[jump_to_else] = Op::BrIfFalse(ops.len());
+                self.compile_expr(else_expr, ops)?;
+                ops[jump_to_end] = Op::Jmp(ops.len());
+            }
+            ResolvedExpr::SpawnFunc { func_index, args } => {
+                for arg in args {
+                    self.compile_expr(arg, ops)?;
+                }
+                ops.push(Op::ThreadSpawn(*func_index, args.len()));
+            }
+            ResolvedExpr::SpawnPromise { func_index } => {
+                ops.push(Op::Spawn(*func_index));
+            }
+            ResolvedExpr::TypeOf { expr, tag_name } => {
+                self.compile_expr(expr, ops)?;
+                match tag_name {
+                    Some(tag_name) => {
+                        ops.push(Op::Drop);
+                        let idx = self.add_string(tag_name.clone());
+                        ops.push(Op::StringConst(idx));
+                    }
+                    None => {
+                        // dyn object [type_info, value]; type_info slot 1 is the tag string
+                        ops.push(Op::HeapLoad(0));
+                        ops.push(Op::HeapLoad(1));
+                    }
+                }
             }
             ResolvedExpr::StructLiteral {
-                struct_index: _,
+                struct_index,
                 fields,
             } => {
                 // Compile struct as slots with [field0, field1, ...] layout
@@ -1617,6 +2353,17 @@ impl Codegen {
                     self.compile_expr(value, ops)?;
                 }
                 ops.push(Op::HeapAlloc(fields.len()));
+                // Tag with its descriptor so the VM can print field names
+                let s = &self.structs[*struct_index];
+                let (name, field_names) = (s.name.clone(), s.fields.clone());
+                let td_idx = self.add_type_descriptor(&name, &field_names, &[], &[]);
+                ops.push(Op::TypeTag(td_idx));
+            }
+            ResolvedExpr::MethodCall {
+                func_index: GENERIC_METHOD,
+                ..
+            } => {
+                ops.push(Op::Unreachable);
             }
             ResolvedExpr::MethodCall {
                 object,
@@ -1624,12 +2371,14 @@ impl Codegen {
                 func_index,
                 args,
                 return_struct_name: _,
+                span,
             } => {
                 // Push object (self) as first argument, then other args
                 self.compile_expr(object, ops)?;
                 for arg in args {
                     self.compile_expr(arg, ops)?;
                 }
+                self.mark_debug_line(ops.len(), *span);
 
                 let total_args = args.len() + 1; // self + args
 
@@ -1648,11 +2397,13 @@ impl Codegen {
                 func_index,
                 args,
                 return_struct_name: _,
+                span,
             } => {
                 // Push arguments (no self for associated functions)
                 for arg in args {
                     self.compile_expr(arg, ops)?;
                 }
+                self.mark_debug_line(ops.len(), *span);
 
                 if self.inline_return_patches_stack.len() < MAX_INLINE_DEPTH
                     && self
@@ -1690,11 +2441,13 @@ impl Codegen {
             }
             ResolvedExpr::Block { statements, expr } => {
                 // Compile all statements in the block
+                let mark = self.open_debug_scope();
                 for stmt in statements {
                     self.compile_statement(stmt, ops)?;
                 }
                 // Compile the final expression - its result is the block's result
                 self.compile_expr(expr, ops)?;
+                self.close_debug_scope(mark, ops.len());
             }
             ResolvedExpr::Closure {
                 func_index,
@@ -1702,7 +2455,7 @@ impl Codegen {
             } => {
                 // Build closure heap object using generic heap instructions:
                 // slots[0] = func_index, slots[1..] = captured values or RefCell refs
-                ops.push(Op::I64Const(*func_index as i64));
+                ops.push(Op::FuncRef(*func_index));
                 for cap in captures {
                     // For both let and var: LocalGet pushes the value.
                     // For var (mutable): the local already holds a RefCell reference,
@@ -1712,13 +2465,14 @@ impl Codegen {
                 // HeapAlloc pops (1 + n_captures) values from stack in push order
                 ops.push(Op::HeapAlloc(1 + captures.len()));
             }
-            ResolvedExpr::CallIndirect { callee, args } => {
+            ResolvedExpr::CallIndirect { callee, args, span } => {
                 // Push the closure reference first
                 self.compile_expr(callee, ops)?;
                 // Then push arguments
                 for arg in args {
                     self.compile_expr(arg, ops)?;
                 }
+                self.mark_debug_line(ops.len(), *span);
                 // CallIndirect pops argc args + callable ref, calls the function
                 ops.push(Op::CallIndirect(args.len()));
             }
@@ -1855,6 +2609,10 @@ impl Codegen {
                 let value = self.expect_int_arg(args, 0, op_name)?;
                 Ok(Op::I64Const(value))
             }
+            "FuncRef" => {
+                let func_index = self.expect_int_arg(args, 0, "FuncRef")? as usize;
+                Ok(Op::FuncRef(func_index))
+            }
             "F32Const" => {
                 let value = self.expect_float_arg(args, 0, "F32Const")? as f32;
                 Ok(Op::F32Const(value))
@@ -1909,6 +2667,12 @@ impl Codegen {
             "I64Mul" | "Mul" => Ok(Op::I64Mul),
             "I64DivS" | "Div" => Ok(Op::I64DivS),
             "I64RemS" | "Mod" => Ok(Op::I64RemS),
+            "I64DivU" => Ok(Op::I64DivU),
+            "I64RemU" => Ok(Op::I64RemU),
+            "I64AddSat" => Ok(Op::I64AddSat),
+            "I64SubSat" => Ok(Op::I64SubSat),
+            "I64MulSat" => Ok(Op::I64MulSat),
+            "I64AddChecked" => Ok(Op::I64AddChecked),
             "I64Neg" | "Neg" => Ok(Op::I64Neg),
             "I64And" => Ok(Op::I64And),
             "I64Or" => Ok(Op::I64Or),
@@ -1981,6 +2745,11 @@ impl Codegen {
             // ========================
             "RefEq" => Ok(Op::RefEq),
             "RefIsNull" => Ok(Op::RefIsNull),
+            "DeepEq" => {
+                let n = self.expect_int_arg(args, 0, "DeepEq")? as usize;
+                Ok(Op::DeepEq(n))
+            }
+            "Compare" => Ok(Op::Compare),
 
             // ========================
             // Type Conversion
@@ -2018,6 +2787,7 @@ impl Codegen {
 
             // Functions - FORBIDDEN
             "Call" => Err("Call instruction is forbidden in asm block".to_string()),
+            "TailCall" => Err("TailCall instruction is forbidden in asm block".to_string()),
             "Ret" => Err("Ret instruction is forbidden in asm block".to_string()),
 
             // ========================
@@ -2044,6 +2814,7 @@ impl Codegen {
             // Type operations
             // Exception handling
             "Throw" => Ok(Op::Throw),
+            "Unreachable" => Ok(Op::Unreachable),
             "TryBegin" => {
                 let target = self.expect_int_arg(args, 0, "TryBegin")? as usize;
                 Ok(Op::TryBegin(target))
@@ -2052,13 +2823,42 @@ impl Codegen {
 
             // Builtins
             "TypeOf" => Ok(Op::TypeOf),
+            "TypeName" => Ok(Op::TypeName),
+            "AssertType" => {
+                let tag = self.expect_int_arg(args, 0, "AssertType")?;
+                if !(0..=4).contains(&tag) {
+                    return Err(format!("invalid type tag {} for AssertType", tag));
+                }
+                Ok(Op::AssertType(tag as u8))
+            }
             "HeapSize" => Ok(Op::HeapSize),
+            "FreeTemp" => Ok(Op::FreeTemp),
+            "ArraySlice" => Ok(Op::ArraySlice),
+            "MapNew" => Ok(Op::MapNew),
+            "MapGet" => Ok(Op::MapGet),
+            "MapSet" => Ok(Op::MapSet),
+            "MapHas" => Ok(Op::MapHas),
+            "MapDelete" => Ok(Op::MapDelete),
+            "MapLen" => Ok(Op::MapLen),
+            "MapKeys" => Ok(Op::MapKeys),
+            "MapValues" => Ok(Op::MapValues),
+            "MapEntries" => Ok(Op::MapEntries),
+            "StringSplit" => Ok(Op::StringSplit),
+            "StringJoin" => Ok(Op::StringJoin),
+            "StringSubstring" => Ok(Op::StringSubstring),
+            "StringIndexOf" => Ok(Op::StringIndexOf),
+            "StringReplace" => Ok(Op::StringReplace),
+            "StringBuilderAppend" => Ok(Op::StringBuilderAppend),
+            "StringBuilderFinish" => Ok(Op::StringBuilderFinish),
+            "StringToBytes" => Ok(Op::StringToBytes),
+            "BytesToString" => Ok(Op::BytesToString),
 
             // GC hint
             "GcHint" => {
                 let size = self.expect_int_arg(args, 0, "GcHint")? as usize;
                 Ok(Op::GcHint(size))
             }
+            "DebugBreak" => Ok(Op::DebugBreak),
 
             // CLI arguments
             "Argc" => Ok(Op::Argc),
@@ -2068,12 +2868,27 @@ impl Codegen {
             // Thread operations
             "ThreadSpawn" => {
                 let func_index = self.expect_int_arg(args, 0, "ThreadSpawn")? as usize;
-                Ok(Op::ThreadSpawn(func_index))
+                // The atomic count is optional: plain spawns pass none
+                let argc = match args.len() {
+                    1 => 0,
+                    _ => self.expect_int_arg(args, 1, "ThreadSpawn")? as usize,
+                };
+                Ok(Op::ThreadSpawn(func_index, argc))
             }
             "ChannelCreate" => Ok(Op::ChannelCreate),
             "ChannelSend" => Ok(Op::ChannelSend),
             "ChannelRecv" => Ok(Op::ChannelRecv),
             "ThreadJoin" => Ok(Op::ThreadJoin),
+            "Spawn" => {
+                let func_index = self.expect_int_arg(args, 0, "Spawn")? as usize;
+                Ok(Op::Spawn(func_index))
+            }
+            "Await" => Ok(Op::Await),
+            "AtomicNew" => Ok(Op::AtomicNew),
+            "AtomicLoad" => Ok(Op::AtomicLoad),
+            "AtomicStore" => Ok(Op::AtomicStore),
+            "AtomicFetchAdd" => Ok(Op::AtomicFetchAdd),
+            "AtomicCompareExchange" => Ok(Op::AtomicCompareExchange),
 
             // Hostcall
             "Hostcall" => {
@@ -2181,6 +2996,72 @@ mod tests {
         assert_eq!(chunk.functions[0].name, "foo");
     }
 
+    #[test]
+    fn test_debug_local_table() {
+        let chunk = compile(
+            "fun f(a: int) { let b = a + 1; if b > 0 { let a = b * 2; __typeof(a); } let c = a; }",
+        )
+        .unwrap();
+        let info = &chunk.debug.as_ref().unwrap().functions[0];
+        let table: Vec<(&str, u16)> = info
+            .locals
+            .iter()
+            .map(|l| (l.name.as_str(), l.slot))
+            .collect();
+        assert_eq!(table, vec![("a", 0), ("b", 1), ("a", 2), ("c", 3)]);
+
+        // The inner `a` shadows the parameter only inside the if block
+        let inner = &info.locals[2];
+        let pc_in_block = inner.scope_start as usize;
+        let pc_after = inner.scope_end as usize;
+        let visible_a = |pc: usize| {
+            info.get_locals_at(pc)
+                .into_iter()
+                .filter(|l| l.name == "a")
+                .max_by_key(|l| l.scope_start)
+                .map(|l| l.slot)
+        };
+        assert_eq!(visible_a(pc_in_block), Some(2));
+        assert_eq!(visible_a(pc_after), Some(0));
+        assert_eq!(visible_a(0), Some(0));
+    }
+
+    #[test]
+    fn test_debug_line_table_covers_every_op() {
+        let chunk = compile(
+            "fun f(a: int) {\n    let b = a +\n        2 * 3;\n    return b;\n}\nfun g() {}\nlet z = 1 +\n    4 * 5;",
+        )
+        .unwrap();
+        let debug = chunk.debug.as_ref().unwrap();
+        let bodies = chunk
+            .functions
+            .iter()
+            .zip(&debug.functions)
+            .chain(std::iter::once((&chunk.main, &debug.main)));
+        for (func, info) in bodies {
+            for pc in 0..func.code.len() {
+                assert!(
+                    info.lines.find_location(pc).is_some(),
+                    "{} has no line for pc {}",
+                    func.name,
+                    pc
+                );
+            }
+        }
+
+        // The nested product sits on its own line; the sum it feeds stays on
+        // the line of its left operand
+        let line_of = |func: &Function, info: &FunctionDebugInfo, op: Op| {
+            let pc = func.code.iter().position(|o| *o == op).unwrap();
+            info.lines.find_location(pc).unwrap().0
+        };
+        let f_info = &debug.functions[0];
+        assert_eq!(line_of(&chunk.functions[0], f_info, Op::I64Mul), 3);
+        assert_eq!(line_of(&chunk.functions[0], f_info, Op::I64Add), 2);
+        assert_eq!(line_of(&chunk.main, &debug.main, Op::I64Mul), 8);
+        assert_eq!(line_of(&chunk.main, &debug.main, Op::I64Add), 7);
+    }
+
     // =========================================================================
     // Expression Code Generation Tests
     // =========================================================================
@@ -2196,8 +3077,8 @@ mod tests {
 
     #[test]
     fn test_float_literal() {
-        let chunk = compile("__typeof(3.14);").unwrap();
-        assert!(chunk.main.code.contains(&Op::F64Const(3.14)));
+        let chunk = compile("__typeof(2.5);").unwrap();
+        assert!(chunk.main.code.contains(&Op::F64Const(2.5)));
     }
 
     #[test]
@@ -2352,13 +3233,96 @@ mod tests {
 
     #[test]
     fn test_array_literal() {
-        let chunk = compile("__typeof([1, 2, 3]);").unwrap();
+        let chunk = compile("let x = 1; __typeof([x, 2, 3]);").unwrap();
         // Array creates data array + struct
         assert!(chunk.main.code.contains(&Op::HeapAlloc(3))); // data array
         assert!(chunk.main.code.contains(&Op::HeapAlloc(2))); // Array struct [ptr, len]
         assert!(chunk.main.code.contains(&Op::I64Const(3))); // len = 3
     }
 
+    #[test]
+    fn test_constant_array_literal_uses_template() {
+        let chunk =
+            compile("let a = [1, -2, 3.5, true, nil]; let b = [1, -2, 3.5, true, nil];").unwrap();
+        // Both literals share one template and allocate nothing element-wise
+        assert_eq!(
+            chunk.const_arrays,
+            vec![vec![
+                Value::I64(1),
+                Value::I64(-2),
+                Value::F64(3.5),
+                Value::Bool(true),
+                Value::Null
+            ]]
+        );
+        let code = &chunk.main.code;
+        assert_eq!(
+            code.iter().filter(|op| **op == Op::ConstArray(0)).count(),
+            2
+        );
+        assert!(!code.iter().any(|op| matches!(op, Op::HeapAlloc(_))));
+
+        // Signed zeros are different templates; empty literals aren't templated
+        let chunk = compile("let a = [0.0]; let b = [-0.0]; let c = [];").unwrap();
+        assert_eq!(chunk.const_arrays.len(), 2);
+        assert!(chunk.main.code.contains(&Op::HeapAlloc(0)));
+    }
+
+    #[test]
+    fn test_large_array_literal_gc_hint() {
+        let elems = vec!["x"; 300].join(", ");
+        let chunk = compile(&format!("let x = 0; let a = [{}];", elems)).unwrap();
+        let dump = crate::compiler::dump::format_bytecode(&chunk);
+        let bytes = object_size_bytes(300);
+        assert!(
+            dump.contains(&format!("GcHint {}\n", bytes)),
+            "missing hint in:\n{}",
+            dump
+        );
+        let code = &chunk.main.code;
+        let alloc = code
+            .iter()
+            .position(|op| *op == Op::HeapAlloc(300))
+            .unwrap();
+        assert_eq!(code[alloc - 1], Op::GcHint(bytes));
+
+        // A constant literal hints before copying its template
+        let elems = vec!["0"; 300].join(", ");
+        let chunk = compile(&format!("let a = [{}];", elems)).unwrap();
+        let code = &chunk.main.code;
+        let alloc = code.iter().position(|op| *op == Op::ConstArray(0)).unwrap();
+        assert_eq!(code[alloc - 1], Op::GcHint(bytes));
+
+        // Small literals allocate without a hint
+        let chunk = compile("let a = [1, 2, 3];").unwrap();
+        assert!(!chunk.main.code.iter().any(|op| matches!(op, Op::GcHint(_))));
+    }
+
+    #[test]
+    fn test_breakpoint_emits_debug_break() {
+        let chunk = compile("let x = 1; breakpoint(); x = 2;").unwrap();
+        let code = &chunk.main.code;
+        let at = code.iter().position(|op| *op == Op::DebugBreak).unwrap();
+        // The call's nil result is discarded like any expression statement
+        assert_eq!(&code[at + 1..at + 3], &[Op::RefNull, Op::Drop]);
+    }
+
+    #[test]
+    fn test_allocating_loop_gc_hint() {
+        let chunk = compile("let i = 0; while i < 10 { let p = [i, i]; i = i + 1; }").unwrap();
+        let code = &chunk.main.code;
+        let exit = code
+            .iter()
+            .position(|op| matches!(op, Op::BrIfFalse(_)))
+            .unwrap();
+        let per_iteration = object_size_bytes(2) * 2;
+        assert_eq!(code[exit + 1], Op::GcHint(per_iteration));
+
+        // A loop that doesn't allocate gets no hint
+        let chunk = compile("let i = 0; while i < 10 { i = i + 1; }").unwrap();
+        assert!(!chunk.main.code.iter().any(|op| matches!(op, Op::GcHint(_))));
+    }
+
     #[test]
     fn test_heap_size_builtin() {
         let chunk = compile("__heap_size([1]);").unwrap();
@@ -2458,6 +3422,22 @@ mod tests {
         assert!(chunk.functions[0].code.contains(&Op::Throw));
     }
 
+    #[test]
+    fn test_exhaustive_if_ends_in_unreachable() {
+        let chunk = compile(
+            "fun sign(x) { if x > 0 { return 1; } else if x < 0 { return 2; } else { throw 3; } } \
+             fun maybe(x) { if x > 0 { __typeof(x); } else { return 2; } }",
+        )
+        .unwrap();
+        // Jumps past the else blocks land on an explicit Unreachable
+        let code = &chunk.functions[0].code;
+        assert_eq!(code.last(), Some(&Op::Unreachable));
+        assert!(code.contains(&Op::Jmp(code.len() - 1)));
+        // A branch that can fall through still returns nil
+        let code = &chunk.functions[1].code;
+        assert_eq!(code[code.len() - 2..], [Op::RefNull, Op::Ret]);
+    }
+
     #[test]
     fn test_try_catch() {
         let chunk = compile("try { throw 1; } catch e { __typeof(e); }").unwrap();
@@ -2621,6 +3601,36 @@ mod tests {
         assert_eq!(call_count, 2);
     }
 
+    #[test]
+    fn test_tail_position_calls_emit_tail_call() {
+        let source = "
+            fun count(n: int, acc: int) -> int {
+                if n == 0 { return acc; }
+                return count(n - 1, acc + 1);
+            }
+            fun guarded(n: int) -> int {
+                try { return count(n, 0); } catch e { return 0; }
+            }
+            __typeof(count(3, 0) + guarded(3));
+        ";
+        let chunk = compile(source).unwrap();
+        let code = &chunk.functions[0].code;
+        assert!(code.contains(&Op::TailCall(0, 2)));
+        assert!(!code.contains(&Op::Call(0, 2)));
+        // A call inside a try block keeps its frame so the handler stays live
+        let guarded = &chunk.functions[1].code;
+        assert!(guarded.contains(&Op::Call(0, 2)));
+        assert!(!guarded.iter().any(|op| matches!(op, Op::TailCall(..))));
+        // Calls outside tail position stay ordinary calls
+        assert!(
+            !chunk
+                .main
+                .code
+                .iter()
+                .any(|op| matches!(op, Op::TailCall(..)))
+        );
+    }
+
     #[test]
     fn test_deeply_nested_expressions() {
         // Test deeply nested arithmetic: ((((1 + 2) + 3) + 4) + 5)
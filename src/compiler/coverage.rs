@@ -0,0 +1,165 @@
+//! Line coverage for the test runner.
+//!
+//! The VM records executed `(func_index, pc)` pairs; this module maps them
+//! through the debug line table to source lines and aggregates the result
+//! per file.
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::compiler::ast::{Item, Program};
+use crate::vm::{Chunk, FunctionDebugInfo};
+
+/// Name used for the main (top-level) code in [`FunctionLines`].
+pub const MAIN_NAME: &str = "<main>";
+
+/// Coverable and executed source lines of one compiled function.
+#[derive(Debug, Clone, Default)]
+pub struct FunctionLines {
+    pub name: String,
+    /// Lines that have at least one op attributed to them
+    pub coverable: BTreeSet<usize>,
+    /// Lines with at least one executed op
+    pub covered: BTreeSet<usize>,
+}
+
+/// Map executed ops of a chunk to per-function source lines.
+pub fn function_lines(chunk: &Chunk, executed: &HashSet<(usize, usize)>) -> Vec<FunctionLines> {
+    let Some(debug) = &chunk.debug else {
+        return Vec::new();
+    };
+    let mut result = Vec::new();
+    for (func_index, info) in debug.functions.iter().enumerate() {
+        let func = &chunk.functions[func_index];
+        result.push(lines_for(
+            &func.name,
+            func_index,
+            func.code.len(),
+            info,
+            executed,
+        ));
+    }
+    result.push(lines_for(
+        MAIN_NAME,
+        usize::MAX,
+        chunk.main.code.len(),
+        &debug.main,
+        executed,
+    ));
+    result
+}
+
+fn lines_for(
+    name: &str,
+    func_index: usize,
+    code_len: usize,
+    info: &FunctionDebugInfo,
+    executed: &HashSet<(usize, usize)>,
+) -> FunctionLines {
+    let mut lines = FunctionLines {
+        name: name.to_string(),
+        ..Default::default()
+    };
+    for pc in 0..code_len {
+        if let Some((line, _)) = info.lines.find_location(pc) {
+            let line = line as usize;
+            lines.coverable.insert(line);
+            if executed.contains(&(func_index, pc)) {
+                lines.covered.insert(line);
+            }
+        }
+    }
+    lines
+}
+
+/// Names of the compiled functions whose code comes from `program`'s own
+/// source: top-level functions, impl methods (`Type::method`) and main.
+pub fn defined_function_names(program: &Program) -> HashSet<String> {
+    let mut names = HashSet::from([MAIN_NAME.to_string()]);
+    for item in &program.items {
+        match item {
+            Item::FnDef(fn_def) => {
+                names.insert(fn_def.name.clone());
+            }
+            Item::ImplBlock(impl_block) => {
+                for method in &impl_block.methods {
+                    names.insert(format!("{}::{}", impl_block.struct_name, method.name));
+                }
+            }
+            _ => {}
+        }
+    }
+    names
+}
+
+/// Line coverage of a single source file.
+#[derive(Debug, Clone, Default)]
+pub struct FileCoverage {
+    pub coverable: BTreeSet<usize>,
+    pub covered: BTreeSet<usize>,
+}
+
+impl FileCoverage {
+    /// Percentage of coverable lines that were executed.
+    pub fn percent(&self) -> f64 {
+        if self.coverable.is_empty() {
+            100.0
+        } else {
+            self.covered.len() as f64 * 100.0 / self.coverable.len() as f64
+        }
+    }
+
+    /// Coverable lines that were never executed.
+    pub fn uncovered(&self) -> impl Iterator<Item = usize> + '_ {
+        self.coverable.difference(&self.covered).copied()
+    }
+}
+
+/// Coverage aggregated over all tests, keyed by source file.
+#[derive(Debug, Clone, Default)]
+pub struct CoverageReport {
+    pub files: BTreeMap<PathBuf, FileCoverage>,
+}
+
+impl CoverageReport {
+    /// Merge the lines of the functions defined in `file` into the report.
+    /// Monomorphised copies (`name$$...`) count towards their generic source.
+    /// Lines past `max_line` (e.g. an appended test call) are ignored.
+    pub fn add(
+        &mut self,
+        file: &Path,
+        functions: &[FunctionLines],
+        defined: &HashSet<String>,
+        max_line: usize,
+    ) {
+        let entry = self.files.entry(file.to_path_buf()).or_default();
+        for func in functions {
+            let base = func.name.split("$$").next().unwrap_or(&func.name);
+            if !defined.contains(base) {
+                continue;
+            }
+            let in_file = |line: &&usize| **line >= 1 && **line <= max_line;
+            entry
+                .coverable
+                .extend(func.coverable.iter().filter(in_file));
+            entry.covered.extend(func.covered.iter().filter(in_file));
+        }
+    }
+
+    /// Render the report in LCOV tracefile format.
+    pub fn to_lcov(&self) -> String {
+        let mut out = String::new();
+        for (path, file) in &self.files {
+            out.push_str("TN:\n");
+            out.push_str(&format!("SF:{}\n", path.display()));
+            for line in &file.coverable {
+                let hits = usize::from(file.covered.contains(line));
+                out.push_str(&format!("DA:{},{}\n", line, hits));
+            }
+            out.push_str(&format!("LF:{}\n", file.coverable.len()));
+            out.push_str(&format!("LH:{}\n", file.covered.len()));
+            out.push_str("end_of_record\n");
+        }
+        out
+    }
+}
@@ -151,6 +151,7 @@ impl Desugar {
             return_type: fn_def.return_type,
             body: self.desugar_block(fn_def.body),
             attributes: fn_def.attributes,
+            doc: fn_def.doc,
             span: fn_def.span,
         }
     }
@@ -170,6 +171,7 @@ impl Desugar {
                 .into_iter()
                 .map(|f| self.desugar_struct_field(f))
                 .collect(),
+            doc: struct_def.doc,
             span: struct_def.span,
         }
     }
@@ -603,11 +605,19 @@ impl Desugar {
                 let right = Box::new(self.desugar_expr(*right));
 
                 // String equality: a == b → _string_eq(a, b), a != b → !_string_eq(a, b)
-                if left_type.as_ref().is_some_and(|t| t.is_string())
+                // Array equality: a == b → __deep_eq(a, b) (element-wise, nested arrays too),
+                // also for `array<T>?`, whose depth `Type::array_depth` reads through the `?`
+                // Other references (structs, Vec, Map) keep identity comparison.
+                let eq_callee = match left_type.as_ref() {
+                    Some(t) if t.is_string() => Some("_string_eq"),
+                    Some(t) if t.array_depth() > 0 => Some("__deep_eq"),
+                    _ => None,
+                };
+                if let Some(callee) = eq_callee
                     && matches!(op, BinaryOp::Eq | BinaryOp::Ne)
                 {
                     let call = Expr::Call {
-                        callee: "_string_eq".to_string(),
+                        callee: callee.to_string(),
                         type_args: vec![],
                         args: vec![*left, *right],
                         span,
@@ -623,6 +633,30 @@ impl Desugar {
                     } else {
                         call
                     }
+                } else if left_type.as_ref().is_some_and(|t| t.is_string())
+                    && matches!(
+                        op,
+                        BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge
+                    )
+                {
+                    // String ordering: a < b → __cmp(a, b) < 0
+                    Expr::Binary {
+                        op,
+                        left: Box::new(Expr::Call {
+                            callee: "__cmp".to_string(),
+                            type_args: vec![],
+                            args: vec![*left, *right],
+                            span,
+                            inferred_type: Some(Type::Int),
+                        }),
+                        right: Box::new(Expr::Int {
+                            value: 0,
+                            span,
+                            inferred_type: Some(Type::Int),
+                        }),
+                        span,
+                        inferred_type,
+                    }
                 } else {
                     Expr::Binary {
                         op,
@@ -634,6 +668,20 @@ impl Desugar {
                 }
             }
 
+            Expr::Conditional {
+                condition,
+                then_expr,
+                else_expr,
+                span,
+                inferred_type,
+            } => Expr::Conditional {
+                condition: Box::new(self.desugar_expr(*condition)),
+                then_expr: Box::new(self.desugar_expr(*then_expr)),
+                else_expr: Box::new(self.desugar_expr(*else_expr)),
+                span,
+                inferred_type,
+            },
+
             // Call - desugar arguments
             Expr::Call {
                 callee,
@@ -1338,10 +1386,12 @@ impl Desugar {
             })
             .collect();
 
-        // Determine if this is a Map (KeyValue elements) or Vec (Value elements)
-        let is_map = elements
-            .first()
-            .is_some_and(|e| matches!(e, NewLiteralElement::KeyValue { .. }));
+        // Determine if this is a Map (KeyValue elements) or Vec (Value elements);
+        // an empty literal has neither, so go by the type name
+        let is_map = match elements.first() {
+            Some(e) => matches!(e, NewLiteralElement::KeyValue { .. }),
+            None => type_name == "Map",
+        };
 
         if is_map {
             self.desugar_map_literal(type_name, type_args, elements, span)
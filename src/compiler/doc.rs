@@ -0,0 +1,188 @@
+//! Markdown documentation generation from `///` doc comments.
+//!
+//! Every top-level function and struct whose name does not start with `_`
+//! is treated as public. Each entry is rendered as its signature followed by
+//! the doc comment text; methods from `impl` blocks are listed under their
+//! struct.
+
+use crate::compiler::ast::{FnDef, ImplBlock, Item, Program, StructDef};
+
+/// Whether an item with this name should appear in the generated docs.
+fn is_public(name: &str) -> bool {
+    !name.starts_with('_')
+}
+
+/// Render the signature of a function, e.g. `fun add(a: int, b: int) -> int`.
+pub fn fn_signature(fn_def: &FnDef) -> String {
+    let mut sig = format!("fun {}", fn_def.name);
+    if !fn_def.type_params.is_empty() {
+        let params: Vec<String> = fn_def
+            .type_params
+            .iter()
+            .enumerate()
+            .map(|(i, name)| match fn_def.type_param_bounds.get(i) {
+                Some(bounds) if !bounds.is_empty() => format!("{}: {}", name, bounds.join(" + ")),
+                _ => name.clone(),
+            })
+            .collect();
+        sig.push_str(&format!("<{}>", params.join(", ")));
+    }
+    let params: Vec<String> = fn_def
+        .params
+        .iter()
+        .map(|p| match &p.type_annotation {
+            Some(ty) => format!("{}: {}", p.name, ty),
+            None => p.name.clone(),
+        })
+        .collect();
+    sig.push_str(&format!("({})", params.join(", ")));
+    if let Some(ret) = &fn_def.return_type {
+        sig.push_str(&format!(" -> {}", ret));
+    }
+    sig
+}
+
+/// Render the signature of a struct, e.g. `struct Point { x: int, y: int }`.
+pub fn struct_signature(struct_def: &StructDef) -> String {
+    let mut sig = format!("struct {}", struct_def.name);
+    if !struct_def.type_params.is_empty() {
+        sig.push_str(&format!("<{}>", struct_def.type_params.join(", ")));
+    }
+    let fields: Vec<String> = struct_def
+        .fields
+        .iter()
+        .map(|f| format!("{}: {}", f.name, f.type_annotation))
+        .collect();
+    sig.push_str(&format!(" {{ {} }}", fields.join(", ")));
+    sig
+}
+
+fn push_entry(out: &mut String, heading: &str, name: &str, signature: &str, doc: Option<&str>) {
+    out.push_str(&format!("{} `{}`\n\n", heading, name));
+    out.push_str(&format!("```\n{}\n```\n\n", signature));
+    if let Some(doc) = doc {
+        out.push_str(doc);
+        out.push_str("\n\n");
+    }
+}
+
+fn push_methods(out: &mut String, impl_block: &ImplBlock) {
+    for method in &impl_block.methods {
+        if !is_public(&method.name) {
+            continue;
+        }
+        push_entry(
+            out,
+            "####",
+            &format!("{}.{}", impl_block.struct_name, method.name),
+            &fn_signature(method),
+            method.doc.as_deref(),
+        );
+    }
+}
+
+/// Generate markdown documentation for the items of a parsed program.
+pub fn generate_markdown(title: &str, program: &Program) -> String {
+    let mut functions = Vec::new();
+    let mut structs = Vec::new();
+    let mut impls = Vec::new();
+    for item in &program.items {
+        match item {
+            Item::FnDef(fn_def) if is_public(&fn_def.name) => functions.push(fn_def),
+            Item::StructDef(struct_def) if is_public(&struct_def.name) => structs.push(struct_def),
+            Item::ImplBlock(impl_block) => impls.push(impl_block),
+            _ => {}
+        }
+    }
+
+    let mut out = format!("# {}\n\n", title);
+
+    if !functions.is_empty() {
+        out.push_str("## Functions\n\n");
+        for fn_def in functions {
+            push_entry(
+                &mut out,
+                "###",
+                &fn_def.name,
+                &fn_signature(fn_def),
+                fn_def.doc.as_deref(),
+            );
+        }
+    }
+
+    if !structs.is_empty() {
+        out.push_str("## Structs\n\n");
+        for struct_def in &structs {
+            push_entry(
+                &mut out,
+                "###",
+                &struct_def.name,
+                &struct_signature(struct_def),
+                struct_def.doc.as_deref(),
+            );
+            for impl_block in impls.iter().filter(|i| i.struct_name == struct_def.name) {
+                push_methods(&mut out, impl_block);
+            }
+        }
+    }
+
+    // Impl blocks for types defined elsewhere (e.g. in the prelude)
+    let foreign: Vec<&&ImplBlock> = impls
+        .iter()
+        .filter(|i| is_public(&i.struct_name) && !structs.iter().any(|s| s.name == i.struct_name))
+        .collect();
+    if !foreign.is_empty() {
+        out.push_str("## Impls\n\n");
+        for impl_block in foreign {
+            push_methods(&mut out, impl_block);
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::{Lexer, Parser};
+
+    fn doc_for(source: &str) -> String {
+        let mut lexer = Lexer::new("test.mc", source);
+        let tokens = lexer.scan_tokens().unwrap();
+        let mut parser = Parser::new("test.mc", tokens);
+        let program = parser.parse().unwrap();
+        generate_markdown("test", &program)
+    }
+
+    #[test]
+    fn test_documented_function() {
+        let md = doc_for(
+            "/// Adds two numbers.\n/// Overflow wraps.\nfun add(a: int, b: int) -> int { return a + b; }\n",
+        );
+        assert!(md.contains(
+            "### `add`\n\n```\nfun add(a: int, b: int) -> int\n```\n\nAdds two numbers.\nOverflow wraps.\n"
+        ));
+    }
+
+    #[test]
+    fn test_struct_and_methods() {
+        let md = doc_for(
+            "// not a doc comment\n\
+             /// A point in 2D.\n\
+             struct Point { x: int, y: int }\n\
+             impl Point {\n\
+                 /// Sum of coordinates.\n\
+                 fun sum(self) -> int { return self.x + self.y; }\n\
+                 fun _hidden(self) {}\n\
+             }\n\
+             fun _private() {}\n",
+        );
+        assert!(md.contains("```\nstruct Point { x: int, y: int }\n```\n\nA point in 2D.\n"));
+        assert!(md.contains(
+            "#### `Point.sum`\n\n```\nfun sum(self) -> int\n```\n\nSum of coordinates.\n"
+        ));
+        assert!(!md.contains("not a doc comment"));
+        assert!(!md.contains("_hidden"));
+        assert!(!md.contains("_private"));
+    }
+}
@@ -12,7 +12,7 @@ use crate::compiler::resolver::{
 use crate::compiler::types::Type;
 use crate::vm::microop::{CmpCond, MicroOp, VReg};
 use crate::vm::microop_converter;
-use crate::vm::{Chunk, Function, Op};
+use crate::vm::{Chunk, Function, Op, Value};
 use std::collections::HashMap;
 
 /// A span-based key for looking up types.
@@ -499,6 +499,23 @@ impl<'a> AstPrinter<'a> {
                 self.print_expr(right, "└── ", true, &child_prefix);
             }
 
+            Expr::Conditional {
+                condition,
+                then_expr,
+                else_expr,
+                ..
+            } => {
+                self.write(&format!("{}Conditional", prefix));
+                self.write_type_suffix(expr);
+                self.newline();
+                self.write_indent_with(&child_prefix);
+                self.print_expr(condition, "├── cond: ", false, &child_prefix);
+                self.write_indent_with(&child_prefix);
+                self.print_expr(then_expr, "├── then: ", false, &child_prefix);
+                self.write_indent_with(&child_prefix);
+                self.print_expr(else_expr, "└── else: ", true, &child_prefix);
+            }
+
             Expr::Call { callee, args, .. } => {
                 self.write(&format!("{}Call: {}({})", prefix, callee, args.len()));
                 self.write_type_suffix(expr);
@@ -858,6 +875,10 @@ pub fn format_ast_with_types(program: &Program, type_map: &ExprTypeMap) -> Strin
 pub struct ResolvedProgramPrinter {
     output: String,
     indent: usize,
+    /// Source names of the function being printed (indexed by slot)
+    local_names: Vec<String>,
+    /// Block depths of the function being printed (indexed by slot)
+    local_depths: Vec<usize>,
 }
 
 impl ResolvedProgramPrinter {
@@ -865,6 +886,8 @@ impl ResolvedProgramPrinter {
         Self {
             output: String::new(),
             indent: 0,
+            local_names: Vec::new(),
+            local_depths: Vec::new(),
         }
     }
 
@@ -897,6 +920,8 @@ impl ResolvedProgramPrinter {
         // Print main body
         if !program.main_body.is_empty() {
             self.writeln(&format!("Main [locals: {}]:", program.main_locals_count));
+            self.local_names = program.main_local_names.clone();
+            self.local_depths = program.main_local_depths.clone();
             self.indent += 1;
             for (i, stmt) in program.main_body.iter().enumerate() {
                 let is_last = i == program.main_body.len() - 1;
@@ -948,6 +973,8 @@ impl ResolvedProgramPrinter {
         self.newline();
 
         // Print body
+        self.local_names = func.local_names.clone();
+        self.local_depths = func.local_depths.clone();
         self.indent += 1;
         for (i, stmt) in func.body.iter().enumerate() {
             let stmt_is_last = i == func.body.len() - 1;
@@ -967,18 +994,45 @@ impl ResolvedProgramPrinter {
         self.indent -= 1;
     }
 
+    /// ` (name, depth:N)` for a slot of the function being printed, so
+    /// declarations can be matched up with the slots that refer to them.
+    fn binding(&self, slot: usize) -> String {
+        match (self.local_names.get(slot), self.local_depths.get(slot)) {
+            (Some(name), Some(depth)) => format!(" ({}, depth:{})", name, depth),
+            _ => String::new(),
+        }
+    }
+
+    /// ` name` for a slot of the function being printed.
+    fn name(&self, slot: usize) -> String {
+        self.local_names
+            .get(slot)
+            .map(|name| format!(" {}", name))
+            .unwrap_or_default()
+    }
+
     fn print_statement(&mut self, stmt: &ResolvedStatement, prefix: &str, parent_prefix: &str) {
         match stmt {
-            ResolvedStatement::Let { slot, init } => {
-                self.write(&format!("{}Let slot:{}", prefix, slot));
+            ResolvedStatement::Let { slot, init, .. } => {
+                self.write(&format!(
+                    "{}Let slot:{}{}",
+                    prefix,
+                    slot,
+                    self.binding(*slot)
+                ));
                 self.newline();
                 self.write_indent_with(parent_prefix);
                 let expr_child = format!("{}    ", parent_prefix);
                 self.print_expr(init, "└── init: ", &expr_child);
             }
 
-            ResolvedStatement::Assign { slot, value } => {
-                self.write(&format!("{}Assign slot:{}", prefix, slot));
+            ResolvedStatement::Assign { slot, value, .. } => {
+                self.write(&format!(
+                    "{}Assign slot:{}{}",
+                    prefix,
+                    slot,
+                    self.name(*slot)
+                ));
                 self.newline();
                 self.write_indent_with(parent_prefix);
                 let expr_child = format!("{}    ", parent_prefix);
@@ -1023,6 +1077,7 @@ impl ResolvedProgramPrinter {
                 condition,
                 then_block,
                 else_block,
+                ..
             } => {
                 self.write(&format!("{}If", prefix));
                 self.newline();
@@ -1068,8 +1123,14 @@ impl ResolvedProgramPrinter {
                 slot,
                 iterable,
                 body,
+                ..
             } => {
-                self.write(&format!("{}ForIn slot:{}", prefix, slot));
+                self.write(&format!(
+                    "{}ForIn slot:{}{}",
+                    prefix,
+                    slot,
+                    self.binding(*slot)
+                ));
                 self.newline();
                 let iter_child = format!("{}│   ", parent_prefix);
                 self.write_indent_with(parent_prefix);
@@ -1081,7 +1142,7 @@ impl ResolvedProgramPrinter {
                 self.print_block(body, &body_child);
             }
 
-            ResolvedStatement::Return { value } => {
+            ResolvedStatement::Return { value, .. } => {
                 self.write(&format!("{}Return", prefix));
                 self.newline();
                 if let Some(v) = value {
@@ -1091,7 +1152,7 @@ impl ResolvedProgramPrinter {
                 }
             }
 
-            ResolvedStatement::Throw { value } => {
+            ResolvedStatement::Throw { value, .. } => {
                 self.write(&format!("{}Throw", prefix));
                 self.newline();
                 self.write_indent_with(parent_prefix);
@@ -1103,6 +1164,7 @@ impl ResolvedProgramPrinter {
                 try_block,
                 catch_slot,
                 catch_block,
+                ..
             } => {
                 self.write(&format!("{}Try", prefix));
                 self.newline();
@@ -1112,13 +1174,17 @@ impl ResolvedProgramPrinter {
                 let try_child = format!("{}│   ", parent_prefix);
                 self.print_block(try_block, &try_child);
                 self.write_indent_with(parent_prefix);
-                self.write(&format!("└── catch slot:{}:", catch_slot));
+                self.write(&format!(
+                    "└── catch slot:{}{}:",
+                    catch_slot,
+                    self.binding(*catch_slot)
+                ));
                 self.newline();
                 let catch_child = format!("{}    ", parent_prefix);
                 self.print_block(catch_block, &catch_child);
             }
 
-            ResolvedStatement::Expr { expr } => {
+            ResolvedStatement::Expr { expr, .. } => {
                 self.write(&format!("{}Expr", prefix));
                 self.newline();
                 self.write_indent_with(parent_prefix);
@@ -1126,7 +1192,7 @@ impl ResolvedProgramPrinter {
                 self.print_expr(expr, "└── ", &expr_child);
             }
 
-            ResolvedStatement::RefCellStore { slot, value } => {
+            ResolvedStatement::RefCellStore { slot, value, .. } => {
                 self.write(&format!("{}RefCellStore slot:{}", prefix, slot));
                 self.newline();
                 self.write_indent_with(parent_prefix);
@@ -1138,6 +1204,7 @@ impl ResolvedProgramPrinter {
                 expr,
                 arms,
                 default_block,
+                ..
             } => {
                 self.write(&format!("{}MatchDyn dyn_slot:{}", prefix, dyn_slot));
                 self.newline();
@@ -1223,7 +1290,12 @@ impl ResolvedProgramPrinter {
             }
 
             ResolvedExpr::Local(slot) => {
-                self.write(&format!("{}Local(slot:{})", prefix, slot));
+                self.write(&format!(
+                    "{}Local(slot:{}{})",
+                    prefix,
+                    slot,
+                    self.name(*slot)
+                ));
                 self.newline();
             }
 
@@ -1262,7 +1334,7 @@ impl ResolvedProgramPrinter {
                 self.print_expr(object, "└── ", &obj_child);
             }
 
-            ResolvedExpr::Unary { op, operand } => {
+            ResolvedExpr::Unary { op, operand, .. } => {
                 let op_str = match op {
                     UnaryOp::Neg => "-",
                     UnaryOp::Not => "!",
@@ -1307,7 +1379,26 @@ impl ResolvedProgramPrinter {
                 self.print_expr(right, "└── ", &right_child);
             }
 
-            ResolvedExpr::Call { func_index, args } => {
+            ResolvedExpr::Conditional {
+                condition,
+                then_expr,
+                else_expr,
+            } => {
+                self.write(&format!("{}Conditional", prefix));
+                self.newline();
+                let mid_child = format!("{}│   ", parent_prefix);
+                let last_child = format!("{}    ", parent_prefix);
+                self.write_indent_with(parent_prefix);
+                self.print_expr(condition, "├── cond: ", &mid_child);
+                self.write_indent_with(parent_prefix);
+                self.print_expr(then_expr, "├── then: ", &mid_child);
+                self.write_indent_with(parent_prefix);
+                self.print_expr(else_expr, "└── else: ", &last_child);
+            }
+
+            ResolvedExpr::Call {
+                func_index, args, ..
+            } => {
                 self.write(&format!(
                     "{}Call func:{} args:{}",
                     prefix,
@@ -1344,11 +1435,43 @@ impl ResolvedProgramPrinter {
                 }
             }
 
-            ResolvedExpr::SpawnFunc { func_index } => {
-                self.write(&format!("{}SpawnFunc func:{}", prefix, func_index));
+            ResolvedExpr::SpawnFunc { func_index, args } => {
+                self.write(&format!(
+                    "{}SpawnFunc func:{} args:{}",
+                    prefix,
+                    func_index,
+                    args.len()
+                ));
+                self.newline();
+                for (i, arg) in args.iter().enumerate() {
+                    let is_last = i == args.len() - 1;
+                    let arg_prefix = if is_last { "└── " } else { "├── " };
+                    let arg_child = if is_last {
+                        format!("{}    ", parent_prefix)
+                    } else {
+                        format!("{}│   ", parent_prefix)
+                    };
+                    self.write_indent_with(parent_prefix);
+                    self.print_expr(arg, arg_prefix, &arg_child);
+                }
+            }
+
+            ResolvedExpr::SpawnPromise { func_index } => {
+                self.write(&format!("{}SpawnPromise func:{}", prefix, func_index));
                 self.newline();
             }
 
+            ResolvedExpr::TypeOf { expr, tag_name } => {
+                match tag_name {
+                    Some(tag_name) => self.write(&format!("{}TypeOf {:?}", prefix, tag_name)),
+                    None => self.write(&format!("{}TypeOf dyn", prefix)),
+                }
+                self.newline();
+                let expr_child = format!("{}    ", parent_prefix);
+                self.write_indent_with(parent_prefix);
+                self.print_expr(expr, "└── expr: ", &expr_child);
+            }
+
             ResolvedExpr::StructLiteral {
                 struct_index,
                 fields,
@@ -1374,6 +1497,7 @@ impl ResolvedProgramPrinter {
                 func_index,
                 args,
                 return_struct_name,
+                ..
             } => {
                 let ret_info = return_struct_name
                     .as_ref()
@@ -1448,6 +1572,7 @@ impl ResolvedProgramPrinter {
                 func_index,
                 args,
                 return_struct_name,
+                ..
             } => {
                 let ret_str = return_struct_name
                     .as_ref()
@@ -1552,7 +1677,7 @@ impl ResolvedProgramPrinter {
                 self.newline();
             }
 
-            ResolvedExpr::CallIndirect { callee, args } => {
+            ResolvedExpr::CallIndirect { callee, args, .. } => {
                 self.write(&format!("{}CallIndirect(args:{})", prefix, args.len()));
                 self.newline();
                 let has_args = !args.is_empty();
@@ -1743,6 +1868,16 @@ impl<'a> Disassembler<'a> {
                 self.output
                     .push_str(&format!("StringConst {} ; \"{}\"", idx, escaped));
             }
+            Op::ConstArray(idx) => {
+                let elems = self
+                    .chunk
+                    .const_arrays
+                    .get(*idx)
+                    .map(|t| format_const_array(t))
+                    .unwrap_or_else(|| "<?>".to_string());
+                self.output
+                    .push_str(&format!("ConstArray {} ; {}", idx, elems));
+            }
 
             // Local variables
             Op::LocalGet(slot) => self.output.push_str(&format!("LocalGet {}", slot)),
@@ -1768,6 +1903,12 @@ impl<'a> Disassembler<'a> {
             Op::I64Mul => self.output.push_str("I64Mul"),
             Op::I64DivS => self.output.push_str("I64DivS"),
             Op::I64RemS => self.output.push_str("I64RemS"),
+            Op::I64DivU => self.output.push_str("I64DivU"),
+            Op::I64RemU => self.output.push_str("I64RemU"),
+            Op::I64AddSat => self.output.push_str("I64AddSat"),
+            Op::I64SubSat => self.output.push_str("I64SubSat"),
+            Op::I64MulSat => self.output.push_str("I64MulSat"),
+            Op::I64AddChecked => self.output.push_str("I64AddChecked"),
             Op::I64Neg => self.output.push_str("I64Neg"),
             Op::I64And => self.output.push_str("I64And"),
             Op::I64Or => self.output.push_str("I64Or"),
@@ -1825,6 +1966,8 @@ impl<'a> Disassembler<'a> {
             // Ref Comparison
             Op::RefEq => self.output.push_str("RefEq"),
             Op::RefIsNull => self.output.push_str("RefIsNull"),
+            Op::DeepEq(depth) => self.output.push_str(&format!("DeepEq {}", depth)),
+            Op::Compare => self.output.push_str("Compare"),
 
             // Type Conversion
             Op::I32WrapI64 => self.output.push_str("I32WrapI64"),
@@ -1842,6 +1985,17 @@ impl<'a> Disassembler<'a> {
             Op::F64PromoteF32 => self.output.push_str("F64PromoteF32"),
             Op::F64ReinterpretAsI64 => self.output.push_str("F64ReinterpretAsI64"),
 
+            Op::FuncRef(func_idx) => {
+                let func_name = self
+                    .chunk
+                    .functions
+                    .get(*func_idx)
+                    .map(|f| f.name.as_str())
+                    .unwrap_or("<?>");
+                self.output
+                    .push_str(&format!("FuncRef {} ; {}", func_idx, func_name));
+            }
+
             // Control flow
             Op::Jmp(target) => self.output.push_str(&format!("Jmp {}", target)),
             Op::BrIf(target) => self.output.push_str(&format!("BrIf {}", target)),
@@ -1858,6 +2012,16 @@ impl<'a> Disassembler<'a> {
                 self.output
                     .push_str(&format!("Call {}, {} ; {}", func_idx, argc, func_name));
             }
+            Op::TailCall(func_idx, argc) => {
+                let func_name = self
+                    .chunk
+                    .functions
+                    .get(*func_idx)
+                    .map(|f| f.name.as_str())
+                    .unwrap_or("<?>");
+                self.output
+                    .push_str(&format!("TailCall {}, {} ; {}", func_idx, argc, func_name));
+            }
             Op::Ret => self.output.push_str("Ret"),
 
             // Heap operations
@@ -1873,14 +2037,39 @@ impl<'a> Disassembler<'a> {
             Op::HeapLoad2(_) => self.output.push_str("HeapLoad2"),
             Op::HeapStore2(_) => self.output.push_str("HeapStore2"),
             Op::HeapOffsetRef => self.output.push_str("HeapOffsetRef"),
+            Op::TypeTag(idx) => self.output.push_str(&format!("TypeTag {}", idx)),
             // System / Builtins
             Op::Hostcall(num, argc) => self.output.push_str(&format!("Hostcall {} {}", num, argc)),
             Op::GcHint(size) => self.output.push_str(&format!("GcHint {}", size)),
+            Op::DebugBreak => self.output.push_str("DebugBreak"),
             Op::UMul128Hi => self.output.push_str("UMul128Hi"),
             Op::TypeOf => self.output.push_str("TypeOf"),
+            Op::TypeName => self.output.push_str("TypeName"),
+            Op::AssertType(tag) => self.output.push_str(&format!("AssertType {}", tag)),
             Op::HeapSize => self.output.push_str("HeapSize"),
+            Op::ArraySlice => self.output.push_str("ArraySlice"),
+            Op::FreeTemp => self.output.push_str("FreeTemp"),
+            Op::MapNew => self.output.push_str("MapNew"),
+            Op::MapGet => self.output.push_str("MapGet"),
+            Op::MapSet => self.output.push_str("MapSet"),
+            Op::MapHas => self.output.push_str("MapHas"),
+            Op::MapDelete => self.output.push_str("MapDelete"),
+            Op::MapLen => self.output.push_str("MapLen"),
+            Op::MapKeys => self.output.push_str("MapKeys"),
+            Op::MapValues => self.output.push_str("MapValues"),
+            Op::MapEntries => self.output.push_str("MapEntries"),
+            Op::StringSplit => self.output.push_str("StringSplit"),
+            Op::StringJoin => self.output.push_str("StringJoin"),
+            Op::StringSubstring => self.output.push_str("StringSubstring"),
+            Op::StringIndexOf => self.output.push_str("StringIndexOf"),
+            Op::StringReplace => self.output.push_str("StringReplace"),
+            Op::StringBuilderAppend => self.output.push_str("StringBuilderAppend"),
+            Op::StringBuilderFinish => self.output.push_str("StringBuilderFinish"),
+            Op::StringToBytes => self.output.push_str("StringToBytes"),
+            Op::BytesToString => self.output.push_str("BytesToString"),
             // Exception handling
             Op::Throw => self.output.push_str("Throw"),
+            Op::Unreachable => self.output.push_str("Unreachable"),
             Op::TryBegin(target) => self.output.push_str(&format!("TryBegin {}", target)),
             Op::TryEnd => self.output.push_str("TryEnd"),
 
@@ -1890,20 +2079,38 @@ impl<'a> Disassembler<'a> {
             Op::Args => self.output.push_str("Args"),
 
             // Threading
-            Op::ThreadSpawn(func_idx) => {
+            Op::ThreadSpawn(func_idx, argc) => {
                 let func_name = self
                     .chunk
                     .functions
                     .get(*func_idx)
                     .map(|f| f.name.as_str())
                     .unwrap_or("<?>");
-                self.output
-                    .push_str(&format!("ThreadSpawn {} ; {}", func_idx, func_name));
+                self.output.push_str(&format!(
+                    "ThreadSpawn {}, {} ; {}",
+                    func_idx, argc, func_name
+                ));
             }
             Op::ChannelCreate => self.output.push_str("ChannelCreate"),
             Op::ChannelSend => self.output.push_str("ChannelSend"),
             Op::ChannelRecv => self.output.push_str("ChannelRecv"),
             Op::ThreadJoin => self.output.push_str("ThreadJoin"),
+            Op::Spawn(func_idx) => {
+                let func_name = self
+                    .chunk
+                    .functions
+                    .get(*func_idx)
+                    .map(|f| f.name.as_str())
+                    .unwrap_or("<?>");
+                self.output
+                    .push_str(&format!("Spawn {} ; {}", func_idx, func_name));
+            }
+            Op::Await => self.output.push_str("Await"),
+            Op::AtomicNew => self.output.push_str("AtomicNew"),
+            Op::AtomicLoad => self.output.push_str("AtomicLoad"),
+            Op::AtomicStore => self.output.push_str("AtomicStore"),
+            Op::AtomicFetchAdd => self.output.push_str("AtomicFetchAdd"),
+            Op::AtomicCompareExchange => self.output.push_str("AtomicCompareExchange"),
 
             // Closures
             Op::CallIndirect(argc) => {
@@ -1925,6 +2132,26 @@ impl<'a> Disassembler<'a> {
 }
 
 /// Format a chunk as a disassembled bytecode string.
+/// Render a constant array template like an array literal, eliding
+/// elements past the eighth.
+fn format_const_array(template: &[Value]) -> String {
+    let mut elems: Vec<String> = template
+        .iter()
+        .take(8)
+        .map(|v| match v {
+            Value::I64(n) => n.to_string(),
+            Value::F64(f) => format!("{:?}", f),
+            Value::Bool(b) => b.to_string(),
+            Value::Null => "nil".to_string(),
+            Value::Ref(_) => "<ref>".to_string(),
+        })
+        .collect();
+    if template.len() > 8 {
+        elems.push(format!("... ({} elements)", template.len()));
+    }
+    format!("[{}]", elems.join(", "))
+}
+
 pub fn format_bytecode(chunk: &Chunk) -> String {
     let mut disassembler = Disassembler::new(chunk);
     disassembler.disassemble().to_string()
@@ -1994,7 +2221,7 @@ fn format_single_microop(output: &mut String, mop: &MicroOp, chunk: &Chunk) {
             format_vreg(cond),
             target
         )),
-        MicroOp::Call { func_id, args, ret } => {
+        MicroOp::Call { func_id, args, ret } | MicroOp::TailCall { func_id, args, ret } => {
             let func_name = chunk
                 .functions
                 .get(*func_id)
@@ -2005,8 +2232,14 @@ fn format_single_microop(output: &mut String, mop: &MicroOp, chunk: &Chunk) {
                 Some(r) => format!(" → {}", format_vreg(r)),
                 None => String::new(),
             };
+            let name = if matches!(mop, MicroOp::TailCall { .. }) {
+                "TailCall"
+            } else {
+                "Call"
+            };
             output.push_str(&format!(
-                "Call {}({}){}  ; {}",
+                "{} {}({}){}  ; {}",
+                name,
                 func_id,
                 args_str.join(", "),
                 ret_str,
@@ -2102,6 +2335,36 @@ fn format_single_microop(output: &mut String, mop: &MicroOp, chunk: &Chunk) {
             format_vreg(a),
             format_vreg(b)
         )),
+        MicroOp::DivU64 { dst, a, b } => output.push_str(&format!(
+            "DivU64 {}, {}, {}",
+            format_vreg(dst),
+            format_vreg(a),
+            format_vreg(b)
+        )),
+        MicroOp::RemU64 { dst, a, b } => output.push_str(&format!(
+            "RemU64 {}, {}, {}",
+            format_vreg(dst),
+            format_vreg(a),
+            format_vreg(b)
+        )),
+        MicroOp::AddSatI64 { dst, a, b } => output.push_str(&format!(
+            "AddSatI64 {}, {}, {}",
+            format_vreg(dst),
+            format_vreg(a),
+            format_vreg(b)
+        )),
+        MicroOp::SubSatI64 { dst, a, b } => output.push_str(&format!(
+            "SubSatI64 {}, {}, {}",
+            format_vreg(dst),
+            format_vreg(a),
+            format_vreg(b)
+        )),
+        MicroOp::MulSatI64 { dst, a, b } => output.push_str(&format!(
+            "MulSatI64 {}, {}, {}",
+            format_vreg(dst),
+            format_vreg(a),
+            format_vreg(b)
+        )),
         MicroOp::NegI64 { dst, src } => output.push_str(&format!(
             "NegI64 {}, {}",
             format_vreg(dst),
@@ -2430,6 +2693,9 @@ fn format_single_microop(output: &mut String, mop: &MicroOp, chunk: &Chunk) {
             format_vreg(src),
             format_vreg(offset)
         )),
+        MicroOp::TypeTag { obj, type_index } => {
+            output.push_str(&format!("TypeTag {}, {}", format_vreg(obj), type_index))
+        }
 
         // Heap allocation operations
         MicroOp::HeapAlloc { dst, args } => {
@@ -2624,6 +2890,20 @@ mod tests {
         assert!(output.contains("Call func:0"));
     }
 
+    #[test]
+    fn test_resolved_locals_show_slot_and_depth() {
+        let resolved = resolve(
+            "fun f(a, b) { let s = a + b; if s > 0 { let t = s; s = t; } return s; } f(1, 2);",
+        );
+        let output = format_resolved(&resolved);
+        assert!(output.contains("f(a -> slot:0, b -> slot:1)"), "{}", output);
+        assert!(output.contains("Let slot:2 (s, depth:0)"), "{}", output);
+        assert!(output.contains("Let slot:3 (t, depth:1)"), "{}", output);
+        assert!(output.contains("Assign slot:2 s"), "{}", output);
+        assert!(output.contains("Local(slot:0 a)"), "{}", output);
+        assert!(output.contains("Local(slot:3 t)"), "{}", output);
+    }
+
     #[test]
     fn test_resolved_struct() {
         let resolved = resolve("struct Point { x: int, y: int } let p = Point { x: 1, y: 2 };");
@@ -77,6 +77,20 @@ pub enum TokenKind {
     DotDot,   // ..
     DotDotEq, // ..=
 
+    // Compound assignment
+    PlusEq,      // +=
+    MinusEq,     // -=
+    StarEq,      // *=
+    SlashEq,     // /=
+    PercentEq,   // %=
+    AmpersandEq, // &=
+    PipeEq,      // |=
+    CaretEq,     // ^=
+    LtLtEq,      // <<=
+    GtGtEq,      // >>=
+    PlusPlus,    // ++
+    MinusMinus,  // --
+
     // Delimiters
     LParen,
     RParen,
@@ -111,11 +125,17 @@ impl Span {
 pub struct Token {
     pub kind: TokenKind,
     pub span: Span,
+    /// `///` doc comment lines immediately preceding this token, joined by newlines.
+    pub doc: Option<String>,
 }
 
 impl Token {
     pub fn new(kind: TokenKind, span: Span) -> Self {
-        Self { kind, span }
+        Self {
+            kind,
+            span,
+            doc: None,
+        }
     }
 }
 
@@ -127,6 +147,8 @@ pub struct Lexer<'a> {
     line: usize,
     column: usize,
     line_start: usize,
+    /// Doc comment lines collected since the last token.
+    pending_doc: Vec<String>,
 }
 
 impl<'a> Lexer<'a> {
@@ -138,6 +160,7 @@ impl<'a> Lexer<'a> {
             line: 1,
             column: 1,
             line_start: 0,
+            pending_doc: Vec::new(),
         }
     }
 
@@ -145,7 +168,7 @@ impl<'a> Lexer<'a> {
         let mut tokens = Vec::new();
 
         loop {
-            self.skip_whitespace_and_comments();
+            self.skip_whitespace_and_comments()?;
 
             let span = Span::new(self.line, self.column);
 
@@ -209,12 +232,22 @@ impl<'a> Lexer<'a> {
                 }
                 '+' => {
                     self.advance();
-                    TokenKind::Plus
+                    if self.match_char('=') {
+                        TokenKind::PlusEq
+                    } else if self.match_char('+') {
+                        TokenKind::PlusPlus
+                    } else {
+                        TokenKind::Plus
+                    }
                 }
                 '-' => {
                     self.advance();
                     if self.match_char('>') {
                         TokenKind::Arrow
+                    } else if self.match_char('=') {
+                        TokenKind::MinusEq
+                    } else if self.match_char('-') {
+                        TokenKind::MinusMinus
                     } else {
                         TokenKind::Minus
                     }
@@ -225,15 +258,27 @@ impl<'a> Lexer<'a> {
                 }
                 '*' => {
                     self.advance();
-                    TokenKind::Star
+                    if self.match_char('=') {
+                        TokenKind::StarEq
+                    } else {
+                        TokenKind::Star
+                    }
                 }
                 '/' => {
                     self.advance();
-                    TokenKind::Slash
+                    if self.match_char('=') {
+                        TokenKind::SlashEq
+                    } else {
+                        TokenKind::Slash
+                    }
                 }
                 '%' => {
                     self.advance();
-                    TokenKind::Percent
+                    if self.match_char('=') {
+                        TokenKind::PercentEq
+                    } else {
+                        TokenKind::Percent
+                    }
                 }
                 '!' => {
                     self.advance();
@@ -258,7 +303,11 @@ impl<'a> Lexer<'a> {
                     if self.match_char('=') {
                         TokenKind::Le
                     } else if self.match_char('<') {
-                        TokenKind::LtLt
+                        if self.match_char('=') {
+                            TokenKind::LtLtEq
+                        } else {
+                            TokenKind::LtLt
+                        }
                     } else {
                         TokenKind::Lt
                     }
@@ -268,7 +317,11 @@ impl<'a> Lexer<'a> {
                     if self.match_char('=') {
                         TokenKind::Ge
                     } else if self.match_char('>') {
-                        TokenKind::GtGt
+                        if self.match_char('=') {
+                            TokenKind::GtGtEq
+                        } else {
+                            TokenKind::GtGt
+                        }
                     } else {
                         TokenKind::Gt
                     }
@@ -277,6 +330,8 @@ impl<'a> Lexer<'a> {
                     self.advance();
                     if self.match_char('&') {
                         TokenKind::AndAnd
+                    } else if self.match_char('=') {
+                        TokenKind::AmpersandEq
                     } else {
                         TokenKind::Ampersand
                     }
@@ -285,13 +340,19 @@ impl<'a> Lexer<'a> {
                     self.advance();
                     if self.match_char('|') {
                         TokenKind::OrOr
+                    } else if self.match_char('=') {
+                        TokenKind::PipeEq
                     } else {
                         TokenKind::Pipe
                     }
                 }
                 '^' => {
                     self.advance();
-                    TokenKind::Caret
+                    if self.match_char('=') {
+                        TokenKind::CaretEq
+                    } else {
+                        TokenKind::Caret
+                    }
                 }
                 '$' => {
                     // $"..." is an interpolated string
@@ -305,6 +366,7 @@ impl<'a> Lexer<'a> {
                     }
                 }
                 '"' => self.scan_string()?,
+                'r' if self.at_raw_string() => self.scan_raw_string()?,
                 '0'..='9' => self.scan_number()?,
                 'a'..='z' | 'A'..='Z' | '_' => self.scan_identifier(),
                 '@' => {
@@ -315,7 +377,11 @@ impl<'a> Lexer<'a> {
                 _ => return Err(self.error(&format!("unexpected character '{}'", ch))),
             };
 
-            tokens.push(Token::new(kind, span));
+            let mut token = Token::new(kind, span);
+            if !self.pending_doc.is_empty() {
+                token.doc = Some(std::mem::take(&mut self.pending_doc).join("\n"));
+            }
+            tokens.push(token);
         }
 
         Ok(tokens)
@@ -347,7 +413,7 @@ impl<'a> Lexer<'a> {
         }
     }
 
-    fn skip_whitespace_and_comments(&mut self) {
+    fn skip_whitespace_and_comments(&mut self) -> Result<(), String> {
         loop {
             match self.peek() {
                 Some((_, ' ' | '\t' | '\r' | '\n')) => {
@@ -359,14 +425,26 @@ impl<'a> Lexer<'a> {
                     chars.next(); // consume '/'
                     if chars.peek().map(|(_, c)| *c) == Some('/') {
                         // Line comment
+                        let (start, _) = self.advance().unwrap(); // '/'
                         self.advance(); // '/'
-                        self.advance(); // '/'
-                        while let Some((_, ch)) = self.peek() {
+                        let mut end = start + 2;
+                        while let Some((i, ch)) = self.peek() {
                             if ch == '\n' {
                                 break;
                             }
+                            end = i + ch.len_utf8();
                             self.advance();
                         }
+                        // `///` (but not `////`) is a doc comment for the next item
+                        let text = &self.source[start + 2..end];
+                        if let Some(doc) = text.strip_prefix('/')
+                            && !doc.starts_with('/')
+                        {
+                            let doc = doc.strip_prefix(' ').unwrap_or(doc);
+                            self.pending_doc.push(doc.trim_end().to_string());
+                        }
+                    } else if chars.peek().map(|(_, c)| *c) == Some('*') {
+                        self.skip_block_comment()?;
                     } else {
                         break;
                     }
@@ -374,20 +452,54 @@ impl<'a> Lexer<'a> {
                 _ => break,
             }
         }
+        Ok(())
+    }
+
+    /// Skip a `/* ... */` comment. Block comments nest, so `/* a /* b */ c */`
+    /// is one comment; an unterminated one is reported at its opening `/*`.
+    fn skip_block_comment(&mut self) -> Result<(), String> {
+        let span = Span::new(self.line, self.column);
+        self.advance(); // '/'
+        self.advance(); // '*'
+        let mut depth = 1;
+        while depth > 0 {
+            match self.advance() {
+                None => return Err(self.error_at(span, "unterminated block comment")),
+                Some((_, '/')) if matches!(self.peek(), Some((_, '*'))) => {
+                    self.advance();
+                    depth += 1;
+                }
+                Some((_, '*')) if matches!(self.peek(), Some((_, '/'))) => {
+                    self.advance();
+                    depth -= 1;
+                }
+                Some(_) => {}
+            }
+        }
+        Ok(())
     }
 
     fn scan_number(&mut self) -> Result<TokenKind, String> {
-        let start = self.peek().map(|(i, _)| i).unwrap_or(0);
-        let mut is_float = false;
+        let span = Span::new(self.line, self.column);
 
-        while let Some((_, ch)) = self.peek() {
-            if ch.is_ascii_digit() {
-                self.advance();
-            } else {
-                break;
+        // Radix prefix: 0x (hex), 0b (binary), 0o (octal)
+        if let Some((_, '0')) = self.peek() {
+            let mut chars = self.chars.clone();
+            chars.next(); // '0'
+            let radix = match chars.peek().map(|(_, c)| *c) {
+                Some('x' | 'X') => Some((16, "hexadecimal")),
+                Some('b' | 'B') => Some((2, "binary")),
+                Some('o' | 'O') => Some((8, "octal")),
+                _ => None,
+            };
+            if let Some((radix, base_name)) = radix {
+                return self.scan_radix_number(radix, base_name, span);
             }
         }
 
+        let mut num_str = self.scan_digits(10, span)?;
+        let mut is_float = false;
+
         // Check for decimal point
         if let Some((_, '.')) = self.peek() {
             // Look ahead to see if it's followed by a digit
@@ -398,13 +510,8 @@ impl<'a> Lexer<'a> {
             {
                 is_float = true;
                 self.advance(); // consume '.'
-                while let Some((_, ch)) = self.peek() {
-                    if ch.is_ascii_digit() {
-                        self.advance();
-                    } else {
-                        break;
-                    }
-                }
+                num_str.push('.');
+                num_str.push_str(&self.scan_digits(10, span)?);
             }
         }
 
@@ -414,37 +521,94 @@ impl<'a> Lexer<'a> {
         {
             is_float = true;
             self.advance(); // consume 'e'/'E'
+            num_str.push('e');
             if let Some((_, ch)) = self.peek()
                 && (ch == '+' || ch == '-')
             {
                 self.advance(); // consume sign
+                num_str.push(ch);
             }
-            while let Some((_, ch)) = self.peek() {
-                if ch.is_ascii_digit() {
-                    self.advance();
-                } else {
-                    break;
-                }
-            }
+            num_str.push_str(&self.scan_digits(10, span)?);
         }
 
-        let end = self.peek().map(|(i, _)| i).unwrap_or(self.source.len());
-        let num_str = &self.source[start..end];
-
         if is_float {
             let value: f64 = num_str
                 .parse()
-                .map_err(|_| self.error(&format!("invalid float '{}'", num_str)))?;
+                .map_err(|_| self.error_at(span, &format!("invalid float '{}'", num_str)))?;
             Ok(TokenKind::Float(value))
         } else {
             let value: i64 = num_str
                 .parse()
-                .map_err(|_| self.error(&format!("invalid number '{}'", num_str)))?;
+                .map_err(|_| self.error_at(span, &format!("invalid number '{}'", num_str)))?;
             Ok(TokenKind::Int(value))
         }
     }
 
+    /// Scan `0x...`, `0b...` or `0o...`. Literals use all 64 bits, so
+    /// `0xFFFF_FFFF_FFFF_FFFF` is -1.
+    fn scan_radix_number(
+        &mut self,
+        radix: u32,
+        base_name: &str,
+        span: Span,
+    ) -> Result<TokenKind, String> {
+        self.advance(); // '0'
+        let (_, prefix) = self.advance().expect("radix prefix");
+        let digits = self.scan_digits(radix, span)?;
+        if let Some((_, ch)) = self.peek()
+            && ch.is_ascii_alphanumeric()
+        {
+            return Err(self.error_at(
+                span,
+                &format!("invalid digit '{}' in {} literal", ch, base_name),
+            ));
+        }
+        if digits.is_empty() {
+            return Err(self.error_at(
+                span,
+                &format!("{} literal '0{}' has no digits", base_name, prefix),
+            ));
+        }
+        let value = u64::from_str_radix(&digits, radix).map_err(|_| {
+            self.error_at(
+                span,
+                &format!(
+                    "{} literal '0{}{}' does not fit in 64 bits",
+                    base_name, prefix, digits
+                ),
+            )
+        })?;
+        Ok(TokenKind::Int(value as i64))
+    }
+
+    /// Consume a run of digits in `radix`, allowing single `_` separators
+    /// between digits, and return the digits without the separators.
+    fn scan_digits(&mut self, radix: u32, span: Span) -> Result<String, String> {
+        let mut digits = String::new();
+        let mut after_separator = false;
+        while let Some((_, ch)) = self.peek() {
+            if ch == '_' {
+                if digits.is_empty() || after_separator {
+                    break;
+                }
+                after_separator = true;
+            } else if ch.is_digit(radix) {
+                digits.push(ch);
+                after_separator = false;
+            } else {
+                break;
+            }
+            self.advance();
+        }
+        if after_separator || matches!(self.peek(), Some((_, '_'))) {
+            return Err(self.error_at(span, "digit separator '_' must be between two digits"));
+        }
+        Ok(digits)
+    }
+
+    /// Scan an escape sequence; errors point at its backslash.
     fn scan_escape(&mut self) -> Result<char, String> {
+        let span = Span::new(self.line, self.column);
         self.advance(); // consume backslash
         match self.peek() {
             Some((_, 'n')) => {
@@ -467,11 +631,106 @@ impl<'a> Lexer<'a> {
                 self.advance();
                 Ok('"')
             }
-            Some((_, ch)) => Err(self.error(&format!("invalid escape sequence '\\{}'", ch))),
+            Some((_, '0')) => {
+                self.advance();
+                Ok('\0')
+            }
+            Some((_, 'u')) => {
+                self.advance();
+                self.scan_unicode_escape(span)
+            }
+            Some((_, ch)) => {
+                Err(self.error_at(span, &format!("invalid escape sequence '\\{}'", ch)))
+            }
             None => Err(self.error("unterminated string")),
         }
     }
 
+    /// Scan the `{XXXX}` of a `\u{XXXX}` escape: 1 to 6 hex digits naming a
+    /// Unicode scalar value.
+    fn scan_unicode_escape(&mut self, span: Span) -> Result<char, String> {
+        if !matches!(self.peek(), Some((_, '{'))) {
+            return Err(self.error_at(span, "unicode escape must be written '\\u{XXXX}'"));
+        }
+        self.advance(); // consume '{'
+        let mut digits = String::new();
+        loop {
+            match self.peek() {
+                Some((_, '}')) => {
+                    self.advance();
+                    break;
+                }
+                Some((_, ch)) if ch.is_ascii_hexdigit() && digits.len() < 6 => {
+                    self.advance();
+                    digits.push(ch);
+                }
+                _ => {
+                    return Err(self.error_at(
+                        span,
+                        "unicode escape must be 1 to 6 hex digits followed by '}'",
+                    ));
+                }
+            }
+        }
+        if digits.is_empty() {
+            return Err(self.error_at(span, "unicode escape '\\u{}' has no digits"));
+        }
+        let code = u32::from_str_radix(&digits, 16).expect("hex digits");
+        char::from_u32(code).ok_or_else(|| {
+            self.error_at(
+                span,
+                &format!("'\\u{{{}}}' is not a Unicode scalar value", digits),
+            )
+        })
+    }
+
+    /// Whether the upcoming `r` starts a raw string: `r"` or `r#...#"`.
+    fn at_raw_string(&self) -> bool {
+        let mut chars = self.chars.clone();
+        chars.next(); // 'r'
+        loop {
+            match chars.next() {
+                Some((_, '#')) => {}
+                Some((_, '"')) => return true,
+                _ => return false,
+            }
+        }
+    }
+
+    /// Scan `r"..."` or `r#"..."#`. Escapes are not processed, and the string
+    /// ends at a `"` followed by as many `#` as opened it, so `r#"say "hi""#`
+    /// can contain quotes.
+    fn scan_raw_string(&mut self) -> Result<TokenKind, String> {
+        let span = Span::new(self.line, self.column);
+        self.advance(); // consume 'r'
+        let mut hashes = 0;
+        while let Some((_, '#')) = self.peek() {
+            self.advance();
+            hashes += 1;
+        }
+        self.advance(); // consume opening quote
+
+        let mut value = String::new();
+        loop {
+            match self.advance() {
+                None => return Err(self.error_at(span, "unterminated raw string")),
+                Some((_, '"')) => {
+                    let mut chars = self.chars.clone();
+                    if (0..hashes).all(|_| matches!(chars.next(), Some((_, '#')))) {
+                        for _ in 0..hashes {
+                            self.advance();
+                        }
+                        break;
+                    }
+                    value.push('"');
+                }
+                Some((_, ch)) => value.push(ch),
+            }
+        }
+
+        Ok(TokenKind::Str(value))
+    }
+
     fn scan_string(&mut self) -> Result<TokenKind, String> {
         self.advance(); // consume opening quote
 
@@ -684,6 +943,13 @@ impl<'a> Lexer<'a> {
             message, self.filename, self.line, self.column
         )
     }
+
+    fn error_at(&self, span: Span, message: &str) -> String {
+        format!(
+            "error: {}\n  --> {}:{}:{}",
+            message, self.filename, span.line, span.column
+        )
+    }
 }
 
 #[cfg(test)]
@@ -705,6 +971,36 @@ mod tests {
         assert_eq!(tokens[5].kind, TokenKind::Eof);
     }
 
+    #[test]
+    fn test_compound_assignment_operators() {
+        let source = "+= -= *= /= %= &= |= ^= <<= >>= ++ -- -> <= >=";
+        let mut lexer = Lexer::new("test.mc", source);
+        let tokens = lexer.scan_tokens().unwrap();
+
+        let expected = vec![
+            TokenKind::PlusEq,
+            TokenKind::MinusEq,
+            TokenKind::StarEq,
+            TokenKind::SlashEq,
+            TokenKind::PercentEq,
+            TokenKind::AmpersandEq,
+            TokenKind::PipeEq,
+            TokenKind::CaretEq,
+            TokenKind::LtLtEq,
+            TokenKind::GtGtEq,
+            TokenKind::PlusPlus,
+            TokenKind::MinusMinus,
+            TokenKind::Arrow,
+            TokenKind::Le,
+            TokenKind::Ge,
+            TokenKind::Eof,
+        ];
+
+        for (i, exp) in expected.iter().enumerate() {
+            assert_eq!(&tokens[i].kind, exp, "mismatch at index {}", i);
+        }
+    }
+
     #[test]
     fn test_operators() {
         let source = "+ - * / % == != < <= > >= && || !";
@@ -791,11 +1087,11 @@ mod tests {
 
     #[test]
     fn test_float_literals() {
-        let source = "3.14 0.5 42.0";
+        let source = "2.5 0.5 42.0";
         let mut lexer = Lexer::new("test.mc", source);
         let tokens = lexer.scan_tokens().unwrap();
 
-        assert_eq!(tokens[0].kind, TokenKind::Float(3.14));
+        assert_eq!(tokens[0].kind, TokenKind::Float(2.5));
         assert_eq!(tokens[1].kind, TokenKind::Float(0.5));
         assert_eq!(tokens[2].kind, TokenKind::Float(42.0));
     }
@@ -813,6 +1109,63 @@ mod tests {
         assert_eq!(tokens[4].kind, TokenKind::Float(1.7976931348623157e308));
     }
 
+    #[test]
+    fn test_radix_and_separated_integers() {
+        let source = "0xFF 0Xff 0b1010 0o777 1_000_000 0xDEAD_BEEF 0b1111_0000 0o7_7 \
+                      0xFFFF_FFFF_FFFF_FFFF 1_000.5 0..3";
+        let mut lexer = Lexer::new("test.mc", source);
+        let tokens = lexer.scan_tokens().unwrap();
+
+        let expected = vec![
+            TokenKind::Int(255),
+            TokenKind::Int(255),
+            TokenKind::Int(10),
+            TokenKind::Int(511),
+            TokenKind::Int(1_000_000),
+            TokenKind::Int(0xDEAD_BEEF),
+            TokenKind::Int(0b1111_0000),
+            TokenKind::Int(0o77),
+            TokenKind::Int(-1),
+            TokenKind::Float(1000.5),
+            TokenKind::Int(0),
+            TokenKind::DotDot,
+            TokenKind::Int(3),
+        ];
+        for (i, exp) in expected.iter().enumerate() {
+            assert_eq!(&tokens[i].kind, exp, "mismatch at index {}", i);
+        }
+    }
+
+    #[test]
+    fn test_malformed_integer_literals() {
+        let cases = [
+            ("0x", "hexadecimal literal '0x' has no digits"),
+            ("0b;", "binary literal '0b' has no digits"),
+            ("0b102", "invalid digit '2' in binary literal"),
+            ("0o8", "invalid digit '8' in octal literal"),
+            ("0xFG", "invalid digit 'G' in hexadecimal literal"),
+            ("1__0", "digit separator '_' must be between two digits"),
+            ("1_", "digit separator '_' must be between two digits"),
+            ("0x_1", "digit separator '_' must be between two digits"),
+            ("0b1_", "digit separator '_' must be between two digits"),
+            (
+                "0x1_0000_0000_0000_0000",
+                "hexadecimal literal '0x10000000000000000' does not fit in 64 bits",
+            ),
+        ];
+        for (source, message) in cases {
+            let err = Lexer::new("test.mc", &format!("let x = {};", source))
+                .scan_tokens()
+                .unwrap_err();
+            assert_eq!(
+                err,
+                format!("error: {}\n  --> test.mc:1:9", message),
+                "for {}",
+                source
+            );
+        }
+    }
+
     #[test]
     fn test_string_literals() {
         let source = r#""hello" "world" "line1\nline2""#;
@@ -824,6 +1177,98 @@ mod tests {
         assert_eq!(tokens[2].kind, TokenKind::Str("line1\nline2".to_string()));
     }
 
+    #[test]
+    fn test_string_escapes() {
+        let source = r#""\n" "\t" "\r" "\\" "\"" "\0" "\u{41}" "\u{e9}" "\u{1F600}""#;
+        let mut lexer = Lexer::new("test.mc", source);
+        let tokens = lexer.scan_tokens().unwrap();
+
+        let expected = [
+            "\n",
+            "\t",
+            "\r",
+            "\\",
+            "\"",
+            "\0",
+            "A",
+            "\u{e9}",
+            "\u{1F600}",
+        ];
+        for (i, exp) in expected.iter().enumerate() {
+            assert_eq!(tokens[i].kind, TokenKind::Str(exp.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_invalid_escapes_point_at_backslash() {
+        let cases = [
+            (r#""ab\q""#, "invalid escape sequence '\\q'"),
+            (r#""ab\u41""#, "unicode escape must be written '\\u{XXXX}'"),
+            (r#""ab\u{}""#, "unicode escape '\\u{}' has no digits"),
+            (
+                r#""ab\u{1234567}""#,
+                "unicode escape must be 1 to 6 hex digits followed by '}'",
+            ),
+            (
+                r#""ab\u{D800}""#,
+                "'\\u{D800}' is not a Unicode scalar value",
+            ),
+        ];
+        for (source, message) in cases {
+            let err = Lexer::new("test.mc", source).scan_tokens().unwrap_err();
+            assert_eq!(err, format!("error: {}\n  --> test.mc:1:4", message));
+        }
+    }
+
+    #[test]
+    fn test_raw_strings() {
+        let source = r###"r"C:\dir\n" r#"say "hi" \t"# r##"a "# b"## r"###;
+        let mut lexer = Lexer::new("test.mc", source);
+        let tokens = lexer.scan_tokens().unwrap();
+
+        assert_eq!(tokens[0].kind, TokenKind::Str(r"C:\dir\n".to_string()));
+        assert_eq!(tokens[1].kind, TokenKind::Str(r#"say "hi" \t"#.to_string()));
+        assert_eq!(tokens[2].kind, TokenKind::Str(r##"a "# b"##.to_string()));
+        // A lone `r` is still an identifier
+        assert_eq!(tokens[3].kind, TokenKind::Ident("r".to_string()));
+
+        let err = Lexer::new("test.mc", r##"let p = r#"open"##)
+            .scan_tokens()
+            .unwrap_err();
+        assert_eq!(err, "error: unterminated raw string\n  --> test.mc:1:9");
+    }
+
+    #[test]
+    fn test_block_comments() {
+        let source = "let /* a /* nested */ comment */ x\n/* spans\nlines */ = 8 /2;";
+        let mut lexer = Lexer::new("test.mc", source);
+        let tokens = lexer.scan_tokens().unwrap();
+
+        let kinds: Vec<_> = tokens.iter().map(|t| t.kind.clone()).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::Let,
+                TokenKind::Ident("x".to_string()),
+                TokenKind::Eq,
+                TokenKind::Int(8),
+                TokenKind::Slash,
+                TokenKind::Int(2),
+                TokenKind::Semi,
+                TokenKind::Eof,
+            ]
+        );
+        // Lines inside a comment still count
+        assert_eq!(tokens[2].span, Span::new(3, 10));
+    }
+
+    #[test]
+    fn test_unterminated_block_comment() {
+        let source = "let x = 1;\n  /* outer /* inner */\nlet y = 2;";
+        let err = Lexer::new("test.mc", source).scan_tokens().unwrap_err();
+        assert_eq!(err, "error: unterminated block comment\n  --> test.mc:2:3");
+    }
+
     #[test]
     fn test_nil_keyword() {
         let source = "let x = nil;";
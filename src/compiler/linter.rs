@@ -231,6 +231,16 @@ fn lint_expr(expr: &Expr, rules: &[Box<dyn LintRule>], diagnostics: &mut Vec<Dia
             lint_expr(left, rules, diagnostics);
             lint_expr(right, rules, diagnostics);
         }
+        Expr::Conditional {
+            condition,
+            then_expr,
+            else_expr,
+            ..
+        } => {
+            lint_expr(condition, rules, diagnostics);
+            lint_expr(then_expr, rules, diagnostics);
+            lint_expr(else_expr, rules, diagnostics);
+        }
         Expr::Call { args, .. } => {
             for arg in args {
                 lint_expr(arg, rules, diagnostics);
@@ -865,6 +875,16 @@ fn collect_usages_expr(expr: &Expr, used: &mut HashSet<String>) {
             collect_usages_expr(left, used);
             collect_usages_expr(right, used);
         }
+        Expr::Conditional {
+            condition,
+            then_expr,
+            else_expr,
+            ..
+        } => {
+            collect_usages_expr(condition, used);
+            collect_usages_expr(then_expr, used);
+            collect_usages_expr(else_expr, used);
+        }
         Expr::Call { callee, args, .. } => {
             used.insert(callee.clone());
             for arg in args {
@@ -3,7 +3,9 @@
 
 pub mod ast;
 mod codegen;
+pub mod coverage;
 pub mod desugar;
+pub mod doc;
 pub mod dump;
 pub mod lexer;
 pub mod linter;
@@ -13,6 +15,7 @@ mod parser;
 pub mod resolver;
 pub mod typechecker;
 pub mod types;
+pub mod watch;
 
 pub use codegen::Codegen;
 pub use lexer::Lexer;
@@ -25,7 +28,7 @@ pub use typechecker::TypeChecker;
 pub const STDLIB_PRELUDE: &str = include_str!("../../std/prelude.mc");
 
 use crate::compiler::ast::{Item, Program};
-use crate::config::{CompilerTimings, JitMode, RuntimeConfig, TimingsFormat};
+use crate::config::{CompilerTimings, RuntimeConfig, TimingsFormat};
 use std::collections::HashSet;
 use std::time::Instant;
 
@@ -72,11 +75,12 @@ pub(crate) fn prepend_stdlib(mut user_program: Program) -> Result<Program, Strin
 
     Ok(user_program)
 }
-use crate::vm::VM;
+use crate::vm::{Chunk, VM};
 use std::fs::File;
-use std::io::{Cursor, Write};
+use std::io::{self, Cursor, Write};
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, mpsc};
 
 /// Options for dumping intermediate representations.
 ///
@@ -164,6 +168,8 @@ pub struct CapturedOutput {
     pub stderr: String,
     /// Number of functions that were JIT compiled
     pub jit_compile_count: usize,
+    /// Per-function executed lines (empty unless `config.coverage` is set)
+    pub coverage: Vec<coverage::FunctionLines>,
 }
 
 /// Compile and run a file, capturing output for testing.
@@ -179,6 +185,7 @@ pub fn run_file_capturing_output(
     let stderr_buffer = Arc::new(Mutex::new(Cursor::new(Vec::new())));
     let stdout_clone = Arc::clone(&stdout_buffer);
     let stderr_clone = Arc::clone(&stderr_buffer);
+    let mut function_lines = Vec::new();
 
     let result: Result<usize, String> = (|| {
         let root_dir = path.parent().unwrap_or(Path::new(".")).to_path_buf();
@@ -194,6 +201,8 @@ pub fn run_file_capturing_output(
 
         // Type checking (writes inferred types to AST)
         let mut typechecker = TypeChecker::new(&filename);
+        typechecker.set_runtime_type_checks(config.runtime_type_checks);
+        typechecker.set_strict_bool(config.strict_bool);
         typechecker
             .check_program(&mut program)
             .map_err(|errors| format_type_errors(&filename, &errors))?;
@@ -214,22 +223,26 @@ pub fn run_file_capturing_output(
 
         // Code generation
         let mut codegen = Codegen::new();
+        codegen.set_return_last_expr(config.eval_print);
+        codegen.set_free_temporaries(config.free_temporaries);
         let chunk = codegen.compile(resolved)?;
 
         // Execution with output capture using wrappers that write to shared buffers
-        let mut vm = VM::new_with_config(
-            config.heap_limit,
-            config.gc_enabled,
+        let mut vm = VM::with_runtime_config(
+            config,
             Box::new(SharedWriter(stdout_clone)),
             Box::new(SharedWriter(stderr_clone)),
         );
-        vm.set_jit_config(
-            config.jit_mode != JitMode::Off,
-            config.jit_threshold,
-            config.trace_jit,
-        );
 
-        vm.run(&chunk)?;
+        let run_result = if config.eval_print {
+            vm.run_and_print_result(&chunk)
+        } else {
+            vm.run(&chunk)
+        };
+        if config.coverage {
+            function_lines = coverage::function_lines(&chunk, &vm.take_coverage());
+        }
+        run_result?;
 
         Ok(vm.jit_compile_count())
     })();
@@ -247,6 +260,7 @@ pub fn run_file_capturing_output(
             stdout: String::from_utf8_lossy(stdout.get_ref()).to_string(),
             stderr: String::from_utf8_lossy(stderr.get_ref()).to_string(),
             jit_compile_count,
+            coverage: function_lines,
         }
     };
 
@@ -281,6 +295,8 @@ pub fn run_file_with_config(path: &Path, config: &RuntimeConfig) -> Result<(), S
 
     // Type checking (writes inferred types to AST)
     let mut typechecker = TypeChecker::new(&filename);
+    typechecker.set_runtime_type_checks(config.runtime_type_checks);
+    typechecker.set_strict_bool(config.strict_bool);
     typechecker
         .check_program(&mut program)
         .map_err(|errors| format_type_errors(&filename, &errors))?;
@@ -312,12 +328,7 @@ pub fn run_file_with_config(path: &Path, config: &RuntimeConfig) -> Result<(), S
     }
 
     // Execution with runtime configuration
-    let mut vm = VM::new_with_heap_config(config.heap_limit, config.gc_enabled);
-    vm.set_jit_config(
-        config.jit_mode != JitMode::Off,
-        config.jit_threshold,
-        config.trace_jit,
-    );
+    let mut vm = VM::with_runtime_config(config, Box::new(io::stdout()), Box::new(io::stderr()));
 
     vm.run(&chunk)?;
 
@@ -334,12 +345,32 @@ pub fn run_file_with_config(path: &Path, config: &RuntimeConfig) -> Result<(), S
 }
 
 /// Compile and run a file with dump options.
+/// Per-phase timings are printed to stderr when `timings_format` is set.
 pub fn run_file_with_dump(
     path: &Path,
     config: &RuntimeConfig,
     dump_opts: &DumpOptions,
     cli_args: Vec<String>,
     timings_format: Option<TimingsFormat>,
+) -> Result<(), String> {
+    run_file_with_dump_to(
+        path,
+        config,
+        dump_opts,
+        cli_args,
+        timings_format,
+        &mut io::stderr(),
+    )
+}
+
+/// [`run_file_with_dump`], writing the timings report to `timings_out`.
+fn run_file_with_dump_to(
+    path: &Path,
+    config: &RuntimeConfig,
+    dump_opts: &DumpOptions,
+    cli_args: Vec<String>,
+    timings_format: Option<TimingsFormat>,
+    timings_out: &mut dyn Write,
 ) -> Result<(), String> {
     let mut timings = CompilerTimings::default();
 
@@ -371,6 +402,8 @@ pub fn run_file_with_dump(
     // Type checking (writes inferred types to AST)
     let start = Instant::now();
     let mut typechecker = TypeChecker::new(&filename);
+    typechecker.set_runtime_type_checks(config.runtime_type_checks);
+    typechecker.set_strict_bool(config.strict_bool);
     typechecker
         .check_program(&mut program)
         .map_err(|errors| format_type_errors(&filename, &errors))?;
@@ -411,6 +444,8 @@ pub fn run_file_with_dump(
     // Code generation
     let start = Instant::now();
     let mut codegen = Codegen::new();
+    codegen.set_return_last_expr(config.eval_print);
+    codegen.set_free_temporaries(config.free_temporaries);
     let chunk = codegen.compile(resolved)?;
     timings.codegen = start.elapsed();
 
@@ -435,17 +470,15 @@ pub fn run_file_with_dump(
     }
 
     // Execution with runtime configuration
-    let mut vm = VM::new_with_heap_config(config.heap_limit, config.gc_enabled);
-    vm.set_jit_config(
-        config.jit_mode != JitMode::Off,
-        config.jit_threshold,
-        config.trace_jit,
-    );
-    vm.set_profile_opcodes(config.profile_opcodes);
+    let mut vm = VM::with_runtime_config(config, Box::new(io::stdout()), Box::new(io::stderr()));
     vm.set_cli_args(cli_args);
 
     let start = Instant::now();
-    vm.run(&chunk)?;
+    if config.eval_print {
+        vm.run_and_print_result(&chunk)?;
+    } else {
+        vm.run(&chunk)?;
+    }
     timings.execution = start.elapsed();
 
     // Print GC stats if requested
@@ -477,7 +510,9 @@ pub fn run_file_with_dump(
 
     // Print timings if requested
     if let Some(format) = timings_format {
-        timings.print(format);
+        timings
+            .write(format, timings_out)
+            .map_err(|e| format!("io error: {}", e))?;
     }
 
     Ok(())
@@ -523,6 +558,8 @@ pub fn run_source(
     // Type checking (writes inferred types to AST)
     let start = Instant::now();
     let mut typechecker = TypeChecker::new(&filename);
+    typechecker.set_runtime_type_checks(config.runtime_type_checks);
+    typechecker.set_strict_bool(config.strict_bool);
     typechecker
         .check_program(&mut program)
         .map_err(|errors| format_type_errors(&filename, &errors))?;
@@ -557,6 +594,8 @@ pub fn run_source(
     // Code generation
     let start = Instant::now();
     let mut codegen = Codegen::new();
+    codegen.set_return_last_expr(config.eval_print);
+    codegen.set_free_temporaries(config.free_temporaries);
     let chunk = codegen.compile(resolved)?;
     timings.codegen = start.elapsed();
 
@@ -581,17 +620,15 @@ pub fn run_source(
     }
 
     // Execution with runtime configuration
-    let mut vm = VM::new_with_heap_config(config.heap_limit, config.gc_enabled);
-    vm.set_jit_config(
-        config.jit_mode != JitMode::Off,
-        config.jit_threshold,
-        config.trace_jit,
-    );
-    vm.set_profile_opcodes(config.profile_opcodes);
+    let mut vm = VM::with_runtime_config(config, Box::new(io::stdout()), Box::new(io::stderr()));
     vm.set_cli_args(cli_args);
 
     let start = Instant::now();
-    vm.run(&chunk)?;
+    if config.eval_print {
+        vm.run_and_print_result(&chunk)?;
+    } else {
+        vm.run(&chunk)?;
+    }
     timings.execution = start.elapsed();
 
     // Print GC stats if requested
@@ -704,6 +741,24 @@ pub fn check_file(path: &Path) -> Result<(), String> {
     Ok(())
 }
 
+/// Generate markdown documentation from the `///` doc comments in a file.
+pub fn doc_file(path: &Path) -> Result<String, String> {
+    let source = std::fs::read_to_string(path)
+        .map_err(|e| format!("failed to read file '{}': {}", path.display(), e))?;
+    let filename = path.to_string_lossy().to_string();
+
+    let mut lexer = Lexer::new(&filename, &source);
+    let tokens = lexer.scan_tokens()?;
+    let mut parser = Parser::new(&filename, tokens);
+    let program = parser.parse()?;
+
+    let title = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or(filename.clone());
+    Ok(doc::generate_markdown(&title, &program))
+}
+
 /// Compile a file and return the AST dump as a string.
 pub fn dump_ast(path: &Path) -> Result<String, String> {
     let root_dir = path.parent().unwrap_or(Path::new(".")).to_path_buf();
@@ -717,22 +772,59 @@ pub fn dump_ast(path: &Path) -> Result<String, String> {
 
 /// Compile a file and return the bytecode dump as a string.
 pub fn dump_bytecode(path: &Path) -> Result<String, String> {
+    let chunk = compile_file_to_chunk(path)?;
+    Ok(dump::format_bytecode(&chunk))
+}
+
+/// Compile source code to a `Chunk` without running it (no import support).
+pub fn compile_to_chunk(source: &str, filename: &str) -> Result<Chunk, String> {
+    let mut lexer = Lexer::new(filename, source);
+    let tokens = lexer.scan_tokens()?;
+    let mut parser = Parser::new(filename, tokens);
+    let user_program = parser.parse()?;
+    let mut chunk = compile_program(user_program, filename)?;
+    chunk.metadata.source_hash = crate::vm::source_hash(source);
+    Ok(chunk)
+}
+
+/// Load a cached chunk for `source`, recompiling it when the cache was
+/// produced by another compiler version or from different source.
+pub fn load_or_compile(cached: &[u8], source: &str, filename: &str) -> Result<Chunk, String> {
+    match crate::vm::bytecode::deserialize_for_source(cached, source) {
+        Ok(chunk) => Ok(chunk),
+        Err(_) => compile_to_chunk(source, filename),
+    }
+}
+
+/// Compile a file and its imports to a `Chunk` without running it.
+///
+/// The recorded source hash covers the main file only; edits to imported
+/// modules are not detected.
+pub fn compile_file_to_chunk(path: &Path) -> Result<Chunk, String> {
     let root_dir = path.parent().unwrap_or(Path::new(".")).to_path_buf();
     let mut loader = ModuleLoader::new(root_dir);
 
     // Load main file with all imports
     let user_program = loader.load_with_imports(path)?;
 
+    let mut chunk = compile_program(user_program, &path.to_string_lossy())?;
+    let source = std::fs::read_to_string(path)
+        .map_err(|e| format!("failed to read file '{}': {}", path.display(), e))?;
+    chunk.metadata.source_hash = crate::vm::source_hash(&source);
+    Ok(chunk)
+}
+
+/// Run a parsed user program through the rest of the pipeline, with the
+/// standard library prepended, and return the bytecode.
+fn compile_program(user_program: Program, filename: &str) -> Result<Chunk, String> {
     // Prepend standard library
     let mut program = prepend_stdlib(user_program)?;
 
-    let filename = path.to_string_lossy().to_string();
-
     // Type checking (writes inferred types to AST)
-    let mut typechecker = TypeChecker::new(&filename);
+    let mut typechecker = TypeChecker::new(filename);
     typechecker
         .check_program(&mut program)
-        .map_err(|errors| format_type_errors(&filename, &errors))?;
+        .map_err(|errors| format_type_errors(filename, &errors))?;
 
     // Desugar (expand syntax sugar like new literals, index operations)
     let program = desugar::desugar_program(program);
@@ -741,7 +833,7 @@ pub fn dump_bytecode(path: &Path) -> Result<String, String> {
     let program = monomorphise::monomorphise_program(program);
 
     // Name resolution
-    let mut resolver = Resolver::new(&filename);
+    let mut resolver = Resolver::new(filename);
     resolver.set_interface_info(
         typechecker.interface_impls().clone(),
         typechecker.interface_method_names(),
@@ -750,9 +842,7 @@ pub fn dump_bytecode(path: &Path) -> Result<String, String> {
 
     // Code generation
     let mut codegen = Codegen::new();
-    let chunk = codegen.compile(resolved)?;
-
-    Ok(dump::format_bytecode(&chunk))
+    codegen.compile(resolved)
 }
 
 // ============================================================================
@@ -781,6 +871,8 @@ pub struct TestResults {
     pub passed: usize,
     /// Number of failed tests
     pub failed: usize,
+    /// Line coverage across all tests (only when `config.coverage` is set)
+    pub coverage: Option<coverage::CoverageReport>,
 }
 
 impl TestResults {
@@ -812,14 +904,38 @@ pub struct TestInfo {
     pub name: String,
     /// File path where the test is defined
     pub file: PathBuf,
+    /// Whether the test takes a parameter, which receives `_setup_`'s return value
+    pub takes_fixture: bool,
+    /// The `_setup_`/`_teardown_` functions of the test's file
+    pub hooks: TestHooks,
+}
+
+/// Per-file functions run around each test in the file.
+///
+/// `_setup_` runs before the test; if it throws, the test fails with
+/// "setup failed" and its body doesn't run. `_teardown_` runs after a test
+/// body that returns normally. Both the test and `_teardown_` may declare one
+/// parameter to receive the value `_setup_` returned.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TestHooks {
+    /// Whether the file defines `_setup_`
+    pub setup: bool,
+    /// Whether the file defines `_teardown_`
+    pub teardown: bool,
+    /// Whether `_teardown_` takes a parameter
+    pub teardown_takes_fixture: bool,
 }
 
 /// Discover all test functions in a directory.
 ///
 /// Scans all .mc files recursively and finds functions with `_test_` prefix.
-pub fn discover_tests(dir: &Path) -> Result<Vec<TestInfo>, String> {
+/// With a `filter`, only tests whose name contains it are returned.
+pub fn discover_tests(dir: &Path, filter: Option<&str>) -> Result<Vec<TestInfo>, String> {
     let mut tests = Vec::new();
     collect_test_files(dir, &mut tests)?;
+    if let Some(filter) = filter {
+        tests.retain(|test| test.name.contains(filter));
+    }
     Ok(tests)
 }
 
@@ -866,6 +982,20 @@ fn collect_tests_from_file(path: &Path, tests: &mut Vec<TestInfo>) -> Result<(),
         Err(_) => return Ok(()), // Skip files with parser errors
     };
 
+    let mut hooks = TestHooks::default();
+    for item in &program.items {
+        if let Item::FnDef(fn_def) = item {
+            match fn_def.name.as_str() {
+                "_setup_" => hooks.setup = true,
+                "_teardown_" => {
+                    hooks.teardown = true;
+                    hooks.teardown_takes_fixture = !fn_def.params.is_empty();
+                }
+                _ => {}
+            }
+        }
+    }
+
     // Find functions with _test_ prefix
     for item in &program.items {
         if let Item::FnDef(fn_def) = item
@@ -874,6 +1004,8 @@ fn collect_tests_from_file(path: &Path, tests: &mut Vec<TestInfo>) -> Result<(),
             tests.push(TestInfo {
                 name: fn_def.name.clone(),
                 file: path.to_path_buf(),
+                takes_fixture: !fn_def.params.is_empty(),
+                hooks,
             });
         }
     }
@@ -881,59 +1013,170 @@ fn collect_tests_from_file(path: &Path, tests: &mut Vec<TestInfo>) -> Result<(),
     Ok(())
 }
 
-/// Run all tests in a directory.
+/// Run all tests in a directory, or those matching `filter` (see [`discover_tests`]).
+///
+/// Tests run on up to `jobs` threads, each in its own VM. Results are
+/// reported in discovery order regardless of `jobs`.
 ///
 /// Returns TestResults with information about each test execution.
-pub fn run_tests(dir: &Path, config: &RuntimeConfig) -> Result<TestResults, String> {
-    let tests = discover_tests(dir)?;
+pub fn run_tests(
+    dir: &Path,
+    config: &RuntimeConfig,
+    filter: Option<&str>,
+    jobs: usize,
+) -> Result<TestResults, String> {
+    let tests = discover_tests(dir, filter)?;
     let mut results = TestResults::new();
+    if config.coverage {
+        results.coverage = Some(coverage::CoverageReport::default());
+    }
 
-    for test in tests {
-        let result = run_single_test(&test, config);
+    let outcomes = run_tests_in_parallel(&tests, config, jobs);
+    for (test, (result, function_lines)) in tests.iter().zip(outcomes) {
+        if let Some(report) = &mut results.coverage {
+            add_test_coverage(report, &test.file, &function_lines)?;
+        }
         results.add(result);
     }
 
     Ok(results)
 }
 
-/// Run a single test function.
-fn run_single_test(test: &TestInfo, config: &RuntimeConfig) -> TestResult {
+/// Run `tests` on a pool of `jobs` threads, returning outcomes in input order.
+fn run_tests_in_parallel(
+    tests: &[TestInfo],
+    config: &RuntimeConfig,
+    jobs: usize,
+) -> Vec<(TestResult, Vec<coverage::FunctionLines>)> {
+    if jobs <= 1 || tests.len() <= 1 {
+        return tests
+            .iter()
+            .map(|test| run_single_test(test, config))
+            .collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let (tx, rx) = mpsc::channel();
+    std::thread::scope(|scope| {
+        for _ in 0..jobs.min(tests.len()) {
+            let tx = tx.clone();
+            let next = &next;
+            scope.spawn(move || {
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(test) = tests.get(index) else {
+                        break;
+                    };
+                    if tx.send((index, run_single_test(test, config))).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    drop(tx);
+
+    let mut outcomes: Vec<_> = rx.into_iter().collect();
+    outcomes.sort_by_key(|(index, _)| *index);
+    outcomes.into_iter().map(|(_, outcome)| outcome).collect()
+}
+
+/// Merge the lines a test executed in its own file into the report.
+fn add_test_coverage(
+    report: &mut coverage::CoverageReport,
+    file: &Path,
+    function_lines: &[coverage::FunctionLines],
+) -> Result<(), String> {
+    let source = std::fs::read_to_string(file)
+        .map_err(|e| format!("failed to read file '{}': {}", file.display(), e))?;
+    let filename = file.to_string_lossy().to_string();
+    let tokens = Lexer::new(&filename, &source).scan_tokens()?;
+    let program = Parser::new(&filename, tokens).parse()?;
+
+    let defined = coverage::defined_function_names(&program);
+    report.add(file, function_lines, &defined, source.lines().count());
+    Ok(())
+}
+
+/// Top-level statements that run `test` together with its file's hooks.
+fn test_driver(test: &TestInfo) -> String {
+    let fixture_arg = |takes_fixture: bool| if takes_fixture { "__fixture" } else { "" };
+    let mut driver = String::new();
+    if test.hooks.setup {
+        // The guard returns `any` so it typechecks whatever `_setup_` returns;
+        // the trailing return is unreachable but functions need one.
+        driver.push_str(concat!(
+            "fun __moca_setup__() -> any {\n",
+            "    try { return _setup_(); } catch e { throw $\"setup failed: {e}\"; }\n",
+            "    return nil;\n",
+            "}\n",
+            "let __fixture = __moca_setup__();\n",
+        ));
+    }
+    driver.push_str(&format!(
+        "{}({});\n",
+        test.name,
+        fixture_arg(test.hooks.setup && test.takes_fixture)
+    ));
+    if test.hooks.teardown {
+        driver.push_str(&format!(
+            "_teardown_({});\n",
+            fixture_arg(test.hooks.setup && test.hooks.teardown_takes_fixture)
+        ));
+    }
+    driver
+}
+
+/// Run a single test function, returning its result and executed lines.
+fn run_single_test(
+    test: &TestInfo,
+    config: &RuntimeConfig,
+) -> (TestResult, Vec<coverage::FunctionLines>) {
     // Read the test file
     let source = match std::fs::read_to_string(&test.file) {
         Ok(s) => s,
         Err(e) => {
-            return TestResult {
+            let result = TestResult {
                 name: test.name.clone(),
                 file: test.file.clone(),
                 passed: false,
                 error: Some(format!("failed to read file: {}", e)),
             };
+            return (result, Vec::new());
         }
     };
 
     // Append a call to the test function
-    let source_with_call = format!("{}\n{}();", source, test.name);
+    let source_with_call = format!("{}\n{}", source, test_driver(test));
 
-    // Create a temporary file with the test call
+    // Create a temporary file with the test call. Same-named tests in
+    // different files (or concurrent runs) must not share it.
+    static TEMP_FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
     let temp_dir = std::env::temp_dir();
-    let temp_file = temp_dir.join(format!("moca_test_{}.mc", test.name));
+    let temp_file = temp_dir.join(format!(
+        "moca_test_{}_{}_{}.mc",
+        std::process::id(),
+        TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed),
+        test.name
+    ));
 
     if let Err(e) = std::fs::write(&temp_file, &source_with_call) {
-        return TestResult {
+        let result = TestResult {
             name: test.name.clone(),
             file: test.file.clone(),
             passed: false,
             error: Some(format!("failed to write temp file: {}", e)),
         };
+        return (result, Vec::new());
     }
 
     // Run the test
-    let (_, result) = run_file_capturing_output(&temp_file, config);
+    let (output, result) = run_file_capturing_output(&temp_file, config);
 
     // Clean up temp file
     let _ = std::fs::remove_file(&temp_file);
 
-    match result {
+    let result = match result {
         Ok(()) => TestResult {
             name: test.name.clone(),
             file: test.file.clone(),
@@ -946,7 +1189,8 @@ fn run_single_test(test: &TestInfo, config: &RuntimeConfig) -> TestResult {
             passed: false,
             error: Some(e),
         },
-    }
+    };
+    (result, output.coverage)
 }
 
 // ============================================================================
@@ -973,3 +1217,475 @@ fn format_type_errors(filename: &str, errors: &[typechecker::TypeError]) -> Stri
 
     output
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vm::Op;
+
+    #[test]
+    fn test_compile_to_chunk() {
+        let source = "fun double(x: int) -> int { return x * 2; }\nprint(double(21));\n";
+        let chunk = compile_to_chunk(source, "test.mc").unwrap();
+        assert_eq!(chunk.main.name, "__main__");
+        let names: Vec<&str> = chunk.functions.iter().map(|f| f.name.as_str()).collect();
+        assert!(names.contains(&"double"), "{:?}", names);
+        // The prelude is compiled in too
+        assert!(names.contains(&"O_RDONLY"), "{:?}", names);
+
+        assert!(compile_to_chunk("let x: int = true;", "test.mc").is_err());
+    }
+
+    #[test]
+    fn test_const_alloc_size_is_folded() {
+        let chunk =
+            compile_to_chunk("const N = 4;\nlet a = __alloc_heap(N * 2 + 1);\n", "t.mc").unwrap();
+        let code = &chunk.main.code;
+        let alloc = code
+            .iter()
+            .position(|op| matches!(op, Op::HeapAllocDynSimple(_)))
+            .unwrap();
+        assert!(matches!(code[alloc - 1], Op::I64Const(9)), "{:?}", code);
+    }
+
+    #[test]
+    fn test_load_or_compile_recompiles_stale_chunks() {
+        let old = "print(1);\n";
+        let new = "print(2);\n";
+        let cached = crate::vm::bytecode::serialize(&compile_to_chunk(old, "a.mc").unwrap());
+
+        let chunk = load_or_compile(&cached, old, "a.mc").unwrap();
+        assert_eq!(chunk.metadata.source_hash, crate::vm::source_hash(old));
+
+        // The source changed since the chunk was cached
+        let chunk = load_or_compile(&cached, new, "a.mc").unwrap();
+        assert_eq!(chunk.metadata.source_hash, crate::vm::source_hash(new));
+    }
+
+    #[test]
+    fn test_run_file_with_dump_writes_json_timings() {
+        let path = std::env::temp_dir().join(format!("timings_json_{}.mc", std::process::id()));
+        std::fs::write(&path, "let x = 1 + 2;\n").unwrap();
+        let mut out = Vec::new();
+        let result = run_file_with_dump_to(
+            &path,
+            &RuntimeConfig::default(),
+            &DumpOptions::default(),
+            vec![],
+            Some(TimingsFormat::Json),
+            &mut out,
+        );
+        let _ = std::fs::remove_file(&path);
+        result.unwrap();
+
+        let text = String::from_utf8(out).unwrap();
+        let json: serde_json::Value = serde_json::from_str(text.trim()).unwrap();
+        for key in ["lexer_ms", "typecheck_ms", "codegen_ms", "execution_ms"] {
+            assert!(json[key].as_f64().is_some(), "missing {key} in {text}");
+        }
+        assert!(json["total_ms"].as_f64().unwrap() > 0.0, "{text}");
+    }
+
+    #[test]
+    fn test_constant_array_literal_matches_element_by_element() {
+        // `{N}` is a literal -1 in one program and a variable in the other
+        let template = "fun table(n: int) -> array<int> { return [3, {N}, 4]; }\n\
+                        let a = table(-1);\nlet b = table(-1);\n\
+                        a[1] = 7;\n\
+                        print(a);\nprint(b);\nprint(len(b));\n\
+                        let f = [0.5, -2.0];\nprint(f[0] + f[1]);\n\
+                        let flags = [true, false];\nprint(flags[1]);\n";
+        let run = |name: &str, n: &str| {
+            let path = std::env::temp_dir().join(format!("{}_{}.mc", name, std::process::id()));
+            std::fs::write(&path, template.replace("{N}", n)).unwrap();
+            let dump = dump_bytecode(&path);
+            let (output, result) = run_file_capturing_output(&path, &RuntimeConfig::default());
+            let _ = std::fs::remove_file(&path);
+            result.unwrap();
+            (dump.unwrap(), output.stdout)
+        };
+        let (const_dump, const_out) = run("const_array", "-1");
+        let (dyn_dump, dyn_out) = run("dyn_array", "n");
+        let has_table = |dump: &str| {
+            dump.lines()
+                .any(|l| l.contains("ConstArray") && l.ends_with("; [3, -1, 4]"))
+        };
+        assert!(has_table(&const_dump), "{}", const_dump);
+        assert!(!has_table(&dyn_dump), "{}", dyn_dump);
+        // Each evaluation gets its own copy of the template
+        assert_eq!(const_out, "[3, 7, 4]\n[3, -1, 4]\n3\n-1.5\nfalse\n");
+        assert_eq!(const_out, dyn_out);
+    }
+
+    #[test]
+    fn test_gc_off_stops_at_heap_limit() {
+        // About 7 MB of short-lived arrays, with at most one alive at a time
+        let source = "let i = 0;\n\
+                      while i < 100000 { let a = [i, i + 1, i + 2]; i = i + 1; }\n\
+                      print(i);\n";
+        let chunk = compile_to_chunk(source, "garbage.mc").unwrap();
+        let run = |gc_enabled: bool| {
+            let config = RuntimeConfig {
+                gc_enabled,
+                heap_limit: Some(4 * 1024 * 1024),
+                ..RuntimeConfig::default()
+            };
+            let mut vm =
+                VM::with_runtime_config(&config, Box::new(io::sink()), Box::new(io::sink()));
+            vm.run(&chunk)
+        };
+
+        // Collected as it goes, the garbage fits...
+        run(true).unwrap();
+        // ...but with GC off the heap only grows until it hits the limit
+        let err = run(false).unwrap_err();
+        assert!(
+            err.contains("runtime error: heap limit exceeded"),
+            "{}",
+            err
+        );
+        assert!(err.contains("limit: 4194304 bytes, GC off)"), "{}", err);
+    }
+
+    #[test]
+    fn test_profile_opcodes_counts_every_iteration_of_hot_loop() {
+        let source = "fun sum(n: int) -> int {\n\
+                          let i = 0;\n let s = 0;\n\
+                          while i < n { s = s + i; i = i + 1; }\n\
+                          return s;\n\
+                      }\n\
+                      let t = 0;\n\
+                      for _ in 0..20 { t = t + sum(500); }\n\
+                      print(t);\n";
+        let chunk = compile_to_chunk(source, "hot.mc").unwrap();
+        let run = |profile_opcodes: bool| {
+            let config = RuntimeConfig {
+                jit_mode: crate::config::JitMode::On,
+                jit_threshold: 5,
+                profile_opcodes,
+                ..RuntimeConfig::default()
+            };
+            let mut vm =
+                VM::with_runtime_config(&config, Box::new(io::sink()), Box::new(io::sink()));
+            vm.run(&chunk).unwrap();
+            vm
+        };
+
+        // The loop is hot enough to be compiled when nothing is profiled...
+        if cfg!(all(
+            any(target_arch = "aarch64", target_arch = "x86_64"),
+            feature = "jit"
+        )) {
+            assert!(run(false).jit_compile_count() > 0);
+        }
+        // ...but profiling keeps it interpreted, so each of the 20 * 501
+        // loop tests is counted
+        let vm = run(true);
+        assert_eq!(vm.jit_compile_count(), 0);
+        let lt = vm.opcode_profile().counts.get("I64LtS").copied();
+        assert!(lt >= Some(20 * 501), "{:?}", lt);
+    }
+
+    #[test]
+    fn test_rerunning_a_chunk_reuses_microop_conversions() {
+        let source = |op: &str, n: i64| {
+            format!(
+                "fun sq(x: int) -> int {{ return x {op} x; }}\n\
+                 fun sum(n: int) -> int {{\n\
+                     let s = 0;\n let i = 0;\n\
+                     while i < n {{ s = s + sq(i); i = i + 1; }}\n\
+                     return s;\n\
+                 }}\n\
+                 print(sum({n}));\n"
+            )
+        };
+        let out = Arc::new(Mutex::new(Cursor::new(Vec::new())));
+        let mut vm = VM::with_runtime_config(
+            &RuntimeConfig {
+                jit_mode: crate::config::JitMode::Off,
+                ..RuntimeConfig::default()
+            },
+            Box::new(SharedWriter(out.clone())),
+            Box::new(io::sink()),
+        );
+
+        let chunk = compile_to_chunk(&source("*", 4), "a.mc").unwrap();
+        vm.run(&chunk).unwrap();
+        let converted = vm.microop_conversion_count();
+        assert!(converted >= 2, "{}", converted);
+        // Same chunk: every function is converted once
+        vm.run(&chunk).unwrap();
+        assert_eq!(vm.microop_conversion_count(), converted);
+        // A copy elsewhere in memory is the same chunk
+        let copy = chunk.clone();
+        vm.run(&copy).unwrap();
+        assert_eq!(vm.microop_conversion_count(), converted);
+
+        // Different functions of the same sizes, possibly allocated where the
+        // dropped chunks' code was, start over
+        drop(chunk);
+        drop(copy);
+        let other = compile_to_chunk(&source("+", 4), "b.mc").unwrap();
+        vm.run(&other).unwrap();
+        assert!(vm.microop_conversion_count() > converted);
+        // So does code edited in place, which keeps its address and length
+        let mut other = other;
+        let sq = other.functions.iter_mut().find(|f| f.name == "sq").unwrap();
+        let add = sq.code.iter().position(|op| *op == Op::I64Add).unwrap();
+        sq.code[add] = Op::I64Mul;
+        vm.run(&other).unwrap();
+
+        let stdout = String::from_utf8(out.lock().unwrap().get_ref().clone()).unwrap();
+        assert_eq!(stdout, "14\n14\n14\n12\n14\n");
+    }
+
+    #[test]
+    fn test_empty_map_literal_calls_map_uninit() {
+        let chunk = compile_to_chunk(
+            "let m = new Map<string, int> {};\nprint(m.len());\n",
+            "m.mc",
+        )
+        .unwrap();
+        let uninit = chunk
+            .functions
+            .iter()
+            .position(|f| f.name == "Map__string_int::uninit")
+            .unwrap();
+        assert_eq!(chunk.functions[uninit].arity, 0);
+        // Not desugared as a Vec literal, which would pass a length
+        assert!(chunk.main.code.contains(&Op::Call(uninit, 0)));
+
+        // The stack interpreter checks arities
+        let mut vm = VM::with_runtime_config(
+            &RuntimeConfig::default(),
+            Box::new(io::sink()),
+            Box::new(io::sink()),
+        );
+        vm.set_use_microop(false);
+        vm.run(&chunk).unwrap();
+    }
+
+    #[test]
+    fn test_pow2_multiply_and_divide_strength_reduction() {
+        let chunk = compile_to_chunk(
+            "fun mul8(x: int) -> int { return x * 8; }\n\
+             fun mul16(x: int) -> int { return 16 * x; }\n\
+             fun mul6(x: int) -> int { return x * 6; }\n\
+             fun fmul8(x: float) -> float { return x * 8.0; }\n\
+             fun div8(x: int) -> int { return x / 8; }\n\
+             fun masked_div8(x: int) -> int { return (x & 255) / 8; }\n",
+            "pow2.mc",
+        )
+        .unwrap();
+        let code = |name: &str| {
+            &chunk
+                .functions
+                .iter()
+                .find(|f| f.name == name)
+                .unwrap()
+                .code
+        };
+        let has = |name: &str, seq: &[Op]| code(name).windows(seq.len()).any(|w| w == seq);
+
+        assert!(has("mul8", &[Op::I64Const(3), Op::I64Shl]));
+        assert!(!code("mul8").contains(&Op::I64Mul));
+        assert!(has("mul16", &[Op::I64Const(4), Op::I64Shl]));
+        assert!(code("mul6").contains(&Op::I64Mul));
+        assert!(code("fmul8").contains(&Op::F64Mul));
+        // x may be negative: -9 / 8 is -1 but -9 >> 3 is -2, so keep the divide
+        assert!(code("div8").contains(&Op::I64DivS));
+        assert!(!code("div8").contains(&Op::I64ShrS));
+        // A masked value is never negative, so the shift is exact
+        assert!(has("masked_div8", &[Op::I64Const(3), Op::I64ShrS]));
+        assert!(!code("masked_div8").contains(&Op::I64DivS));
+    }
+
+    #[test]
+    fn test_pow2_strength_reduction_preserves_results() {
+        let xs: [i64; 8] = [0, 1, 7, 9, -1, -9, -8, i64::MAX];
+        let source = format!(
+            "fun f(x: int) {{ print(x * 8); print(4 * x); print(x / 8); print((x & 1023) / 16); }}\n\
+             let xs = {:?};\n\
+             for x in xs {{ f(x); }}\n",
+            xs
+        );
+        let path = std::env::temp_dir().join(format!("pow2_{}.mc", std::process::id()));
+        std::fs::write(&path, source).unwrap();
+        let (output, result) = run_file_capturing_output(&path, &RuntimeConfig::default());
+        let _ = std::fs::remove_file(&path);
+        result.unwrap();
+
+        let expected: String = xs
+            .iter()
+            .map(|x| {
+                format!(
+                    "{}\n{}\n{}\n{}\n",
+                    x.wrapping_mul(8),
+                    x.wrapping_mul(4),
+                    x / 8,
+                    (x & 1023) / 16
+                )
+            })
+            .collect();
+        assert_eq!(output.stdout, expected);
+    }
+
+    #[test]
+    fn test_monomorphise_shares_identical_instantiations() {
+        let count = |uses: usize| {
+            let mut source = String::from(
+                "struct Box<T> { value: T }\n\
+                 impl<T> Box<T> { fun get(self) -> T { return self.value; } }\n\
+                 fun id<T>(x: T) -> T { return x; }\n",
+            );
+            for i in 0..uses {
+                source.push_str(&format!(
+                    "let b{i}: Box<int> = Box<int> {{ value: {i} }};\n\
+                     print(id(b{i}.get()));\n"
+                ));
+            }
+            let chunk = compile_to_chunk(&source, "mono.mc").unwrap();
+            let named = |name: &str| chunk.functions.iter().filter(|f| f.name == name).count();
+            assert_eq!(named("id__int"), 1);
+            assert_eq!(named("Box__int::get"), 1);
+            chunk.functions.len()
+        };
+        assert_eq!(count(1), count(10));
+    }
+
+    #[test]
+    fn test_generic_instantiations_have_distinct_type_descriptors() {
+        let chunk = compile_to_chunk(
+            "struct Box<T> { value: T }\n\
+             let a = Box<int> { value: 1 } as dyn;\n\
+             let b = Box<string> { value: \"x\" } as dyn;\n",
+            "td.mc",
+        )
+        .unwrap();
+        let field_tags = |tag: &str| {
+            chunk
+                .type_descriptors
+                .iter()
+                .find(|td| td.tag_name == tag)
+                .map(|td| td.field_type_tags.clone())
+        };
+        assert_eq!(field_tags("Box<int>"), Some(vec!["int".to_string()]));
+        assert_eq!(field_tags("Box<string>"), Some(vec!["string".to_string()]));
+    }
+
+    const SHAPES: &str = "interface Shape { fun area(self) -> int; }\n\
+         struct Square { s: int }\n\
+         impl Shape for Square { fun area(self) -> int { return self.s * self.s; } }\n\
+         struct Rect { w: int, h: int }\n\
+         impl Shape for Rect { fun area(self) -> int { return self.w * self.h; } }\n\
+         struct Tri { b: int, h: int }\n\
+         impl Shape for Tri { fun area(self) -> int { return self.b * self.h / 2; } }\n\
+         struct Hex { s: int }\n\
+         impl Shape for Hex { fun area(self) -> int { return 6 * self.s; } }\n\
+         struct Dot { x: int }\n\
+         impl Shape for Dot { fun area(self) -> int { return 1; } }\n\
+         fun area_of(d: dyn) -> int {\n\
+         match dyn d { v: Shape => { return v.area(); } _ => { return 0; } }\n\
+         }\n";
+
+    #[test]
+    fn test_monomorphic_vtable_lookup_walks_once() {
+        let source = format!(
+            "{SHAPES}let sq = Square {{ s: 3 }} as dyn;\n\
+             let total = 0;\nlet i = 0;\n\
+             while i < 1000 {{ total = total + area_of(sq); i = i + 1; }}\n\
+             if total != 9000 {{ throw \"wrong total\"; }}\n"
+        );
+        let chunk = compile_to_chunk(&source, "ic.mc").unwrap();
+        let mut vm = VM::new();
+        vm.run(&chunk).unwrap();
+        let stats = vm.vtable_cache_stats();
+        assert_eq!(stats.walks, 1, "{:?}", stats);
+        assert_eq!(stats.hits, 999, "{:?}", stats);
+    }
+
+    #[test]
+    fn test_polymorphic_vtable_lookup_goes_megamorphic() {
+        // area_of's lookup site sees 2 receiver types, then 5
+        let source = format!(
+            "{SHAPES}let sq = Square {{ s: 3 }} as dyn;\n\
+             let rect = Rect {{ w: 2, h: 5 }} as dyn;\n\
+             let tri = Tri {{ b: 4, h: 3 }} as dyn;\n\
+             let hex = Hex {{ s: 2 }} as dyn;\n\
+             let dot = Dot {{ x: 0 }} as dyn;\n\
+             let total = 0;\nlet i = 0;\n\
+             while i < 10 {{ total = total + area_of(sq) + area_of(rect); i = i + 1; }}\n\
+             let j = 0;\n\
+             while j < 10 {{\n\
+             total = total + area_of(sq) + area_of(rect) + area_of(tri) + area_of(hex) + area_of(dot);\n\
+             j = j + 1;\n\
+             }}\n\
+             if total != 570 {{ throw \"wrong total\"; }}\n"
+        );
+        let chunk = compile_to_chunk(&source, "ic.mc").unwrap();
+        let mut vm = VM::new();
+        vm.run(&chunk).unwrap();
+        let stats = vm.vtable_cache_stats();
+        // Two types: one walk each, then 18 hits. Five types: Square and Rect
+        // hit once more, Tri and Hex fill the site, Dot makes it megamorphic
+        // and the remaining 45 lookups all walk.
+        assert_eq!(stats.hits, 20, "{:?}", stats);
+        assert_eq!(stats.walks, 50, "{:?}", stats);
+        assert_eq!(stats.megamorphic, 46, "{:?}", stats);
+    }
+
+    #[test]
+    fn test_descriptors_allocated_on_first_use() {
+        let objects = |body: &str, eager: bool| {
+            let chunk = compile_to_chunk(&format!("{SHAPES}{body}"), "lazy.mc").unwrap();
+            let mut vm = VM::new();
+            vm.set_eager_descriptors(eager);
+            vm.run(&chunk).unwrap();
+            vm.heap().object_count()
+        };
+        // Nothing refers to a descriptor, so none are allocated
+        assert_eq!(objects("let x = 1 + 2;\n", false), 0);
+        let all = objects("let x = 1 + 2;\n", true);
+        assert!(all > 0);
+
+        // A dyn Square needs Square's descriptor and Shape's, not the others
+        let one = "if area_of(Square { s: 3 } as dyn) != 9 { throw \"wrong area\"; }\n";
+        let used = objects(one, false);
+        assert!(used > 0 && used < all, "{} of {} objects", used, all);
+    }
+
+    #[test]
+    fn test_string_builder_allocations() {
+        // Build a 100k-char string from 1000 chunks, checking the result in-program
+        let build = |body: &str| {
+            let source = format!(
+                "let chunk = \"{}\";\n{}\n\
+                 if len(s) != 100000 || substring(s, 99990, 100000) != \"0123456789\" {{\n\
+                 throw \"wrong result\";\n}}\n",
+                "0123456789".repeat(10),
+                body
+            );
+            let chunk = compile_to_chunk(&source, "sb.mc").unwrap();
+            let mut vm = VM::new();
+            vm.run(&chunk).unwrap();
+            vm.heap().allocation_count()
+        };
+        let with_builder = build(
+            "let sb = sb_new();\nlet i = 0;\n\
+             while i < 1000 { sb_append(sb, chunk); i = i + 1; }\n\
+             let s = sb_finish(sb);",
+        );
+        let with_plus = build(
+            "let s = \"\";\nlet i = 0;\n\
+             while i < 1000 { s = s + chunk; i = i + 1; }",
+        );
+        // `+` allocates a new string per step; the builder only when doubling
+        assert!(with_plus >= 2000, "{} allocations with +", with_plus);
+        assert!(
+            with_builder * 10 < with_plus,
+            "{} allocations with the builder vs {} with +",
+            with_builder,
+            with_plus
+        );
+    }
+}
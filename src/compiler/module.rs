@@ -93,6 +93,11 @@ impl ModuleLoader {
         ))
     }
 
+    /// Canonical paths of every module loaded so far (not including the main file).
+    pub fn loaded_paths(&self) -> impl Iterator<Item = PathBuf> + '_ {
+        self.cache.keys().cloned()
+    }
+
     /// Load a module from a file path.
     pub fn load_module(&mut self, path: &Path) -> Result<&Program, String> {
         self.load_module_timed(path, None)
@@ -306,7 +311,7 @@ mod tests {
         if temp.exists() {
             fs::remove_dir_all(&temp).unwrap();
         }
-        fs::create_dir_all(&temp.join("src")).unwrap();
+        fs::create_dir_all(temp.join("src")).unwrap();
 
         // Create a module file
         fs::write(temp.join("src/utils.mc"), "fun helper() { return 42; }").unwrap();
@@ -332,7 +337,7 @@ mod tests {
         if temp.exists() {
             fs::remove_dir_all(&temp).unwrap();
         }
-        fs::create_dir_all(&temp.join("src")).unwrap();
+        fs::create_dir_all(temp.join("src")).unwrap();
 
         // Create a relative module file
         fs::write(temp.join("src/local.mc"), "fun local_fn() { return 1; }").unwrap();
@@ -358,7 +363,7 @@ mod tests {
         if temp.exists() {
             fs::remove_dir_all(&temp).unwrap();
         }
-        fs::create_dir_all(&temp.join("src")).unwrap();
+        fs::create_dir_all(temp.join("src")).unwrap();
 
         // Create main file with import
         fs::write(
@@ -397,7 +402,7 @@ mod tests {
         if temp.exists() {
             fs::remove_dir_all(&temp).unwrap();
         }
-        fs::create_dir_all(&temp.join("src")).unwrap();
+        fs::create_dir_all(temp.join("src")).unwrap();
 
         // main imports a, a imports b
         fs::write(
@@ -446,7 +451,7 @@ mod tests {
         if temp.exists() {
             fs::remove_dir_all(&temp).unwrap();
         }
-        fs::create_dir_all(&temp.join("src")).unwrap();
+        fs::create_dir_all(temp.join("src")).unwrap();
 
         // main imports a and b; both a and b import shared
         fs::write(
@@ -492,7 +497,7 @@ mod tests {
         if temp.exists() {
             fs::remove_dir_all(&temp).unwrap();
         }
-        fs::create_dir_all(&temp.join("src")).unwrap();
+        fs::create_dir_all(temp.join("src")).unwrap();
 
         // main imports a, a imports b, b imports a (circular)
         fs::write(temp.join("src/main.mc"), "import a;\nprint(1);").unwrap();
@@ -519,7 +524,7 @@ mod tests {
         if temp.exists() {
             fs::remove_dir_all(&temp).unwrap();
         }
-        fs::create_dir_all(&temp.join("src")).unwrap();
+        fs::create_dir_all(temp.join("src")).unwrap();
 
         // main imports a, a imports itself
         fs::write(temp.join("src/main.mc"), "import a;\nprint(1);").unwrap();
@@ -16,7 +16,11 @@ use crate::compiler::types::Type;
 use std::collections::{HashMap, HashSet};
 
 /// Represents a specific instantiation of a generic function or struct.
-#[derive(Debug, Clone, PartialEq, Eq)]
+///
+/// Instantiations are identified by generic name and mangled type arguments,
+/// so every use of e.g. `Box<int>` maps to one specialization even when the
+/// collected `Type`s differ in incidental detail such as resolved field lists.
+#[derive(Debug, Clone)]
 pub struct Instantiation {
     /// Name of the generic function/struct
     pub name: String,
@@ -24,6 +28,16 @@ pub struct Instantiation {
     pub type_args: Vec<Type>,
 }
 
+impl PartialEq for Instantiation {
+    fn eq(&self, other: &Self) -> bool {
+        // Must agree with `Hash`: two instantiations that mangle to the same
+        // name would otherwise be specialized twice under one function name
+        self.name == other.name && self.mangled_name() == other.mangled_name()
+    }
+}
+
+impl Eq for Instantiation {}
+
 impl std::hash::Hash for Instantiation {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         self.name.hash(state);
@@ -159,14 +173,20 @@ impl InstantiationCollector {
         }
     }
 
+    /// Collect instantiations used by an item. Bodies of generic functions and
+    /// methods are skipped: their type arguments may still name type parameters
+    /// (e.g. `g<U>(u)` inside `fun h<U>`), and their specialized copies are
+    /// collected instead.
     pub fn collect_item(&mut self, item: &Item) {
         match item {
-            Item::FnDef(fn_def) => {
+            Item::FnDef(fn_def) if fn_def.type_params.is_empty() => {
                 self.collect_block(&fn_def.body);
             }
-            Item::ImplBlock(impl_block) => {
+            Item::ImplBlock(impl_block) if impl_block.type_params.is_empty() => {
                 for method in &impl_block.methods {
-                    self.collect_block(&method.body);
+                    if method.type_params.is_empty() {
+                        self.collect_block(&method.body);
+                    }
                 }
             }
             Item::Statement(stmt) => {
@@ -433,6 +453,16 @@ impl InstantiationCollector {
                 self.collect_expr(left);
                 self.collect_expr(right);
             }
+            Expr::Conditional {
+                condition,
+                then_expr,
+                else_expr,
+                ..
+            } => {
+                self.collect_expr(condition);
+                self.collect_expr(then_expr);
+                self.collect_expr(else_expr);
+            }
             Expr::NewLiteral {
                 type_name,
                 type_args,
@@ -642,6 +672,7 @@ impl Monomorphiser {
                 .map(|ta| substitute_type_annotation(ta, &type_map)),
             body: substitute_block(&generic_fn.body, &type_map),
             attributes: generic_fn.attributes.clone(),
+            doc: generic_fn.doc.clone(),
             span: generic_fn.span,
         };
 
@@ -678,6 +709,7 @@ impl Monomorphiser {
                     span: f.span,
                 })
                 .collect(),
+            doc: generic_struct.doc.clone(),
             span: generic_struct.span,
         };
 
@@ -744,6 +776,7 @@ impl Monomorphiser {
                             .map(|ann| substitute_type_annotation(ann, &type_map)),
                         body: substitute_block(&m.body, &type_map),
                         attributes: m.attributes.clone(),
+                        doc: m.doc.clone(),
                         span: m.span,
                     })
                     .collect(),
@@ -958,6 +991,7 @@ fn type_to_annotation(ty: &Type) -> crate::compiler::types::TypeAnnotation {
                         Box::new(iter.next().unwrap()),
                     )
                 }
+                "Atomic" => TypeAnnotation::Named(name.clone()),
                 _ => TypeAnnotation::Generic {
                     name: name.clone(),
                     type_args: ta,
@@ -1236,6 +1270,19 @@ fn substitute_expr(expr: &Expr, type_map: &HashMap<String, Type>) -> Expr {
             span: *span,
             inferred_type: substitute_inferred_type(inferred_type, type_map),
         },
+        Expr::Conditional {
+            condition,
+            then_expr,
+            else_expr,
+            span,
+            inferred_type,
+        } => Expr::Conditional {
+            condition: Box::new(substitute_expr(condition, type_map)),
+            then_expr: Box::new(substitute_expr(then_expr, type_map)),
+            else_expr: Box::new(substitute_expr(else_expr, type_map)),
+            span: *span,
+            inferred_type: substitute_inferred_type(inferred_type, type_map),
+        },
         Expr::Call {
             callee,
             type_args,
@@ -1501,6 +1548,7 @@ fn rewrite_item(item: Item, instantiations: &HashSet<Instantiation>) -> Item {
             return_type: fn_def.return_type,
             body: rewrite_block(&fn_def.body, instantiations),
             attributes: fn_def.attributes,
+            doc: fn_def.doc,
             span: fn_def.span,
         }),
         Item::ImplBlock(impl_block) => Item::ImplBlock(ImplBlock {
@@ -1519,6 +1567,7 @@ fn rewrite_item(item: Item, instantiations: &HashSet<Instantiation>) -> Item {
                     return_type: m.return_type,
                     body: rewrite_block(&m.body, instantiations),
                     attributes: m.attributes,
+                    doc: m.doc,
                     span: m.span,
                 })
                 .collect(),
@@ -1904,6 +1953,19 @@ fn rewrite_expr(expr: &Expr, instantiations: &HashSet<Instantiation>) -> Expr {
             span: *span,
             inferred_type: inferred_type.clone(),
         },
+        Expr::Conditional {
+            condition,
+            then_expr,
+            else_expr,
+            span,
+            inferred_type,
+        } => Expr::Conditional {
+            condition: Box::new(rewrite_expr(condition, instantiations)),
+            then_expr: Box::new(rewrite_expr(then_expr, instantiations)),
+            else_expr: Box::new(rewrite_expr(else_expr, instantiations)),
+            span: *span,
+            inferred_type: inferred_type.clone(),
+        },
         Expr::MethodCall {
             object,
             method,
@@ -2084,4 +2146,24 @@ mod tests {
         };
         assert_eq!(inst2.mangled_name(), "pair__int_string");
     }
+
+    #[test]
+    fn test_instantiation_identity_ignores_struct_fields() {
+        let bare = Instantiation {
+            name: "first".to_string(),
+            type_args: vec![Type::vector(Type::Int)],
+        };
+        let with_fields = Instantiation {
+            name: "first".to_string(),
+            type_args: vec![Type::GenericStruct {
+                name: "Vec".to_string(),
+                type_args: vec![Type::Int],
+                fields: vec![("len".to_string(), Type::Int)],
+            }],
+        };
+        let mut set = HashSet::new();
+        set.insert(bare);
+        set.insert(with_fields);
+        assert_eq!(set.len(), 1);
+    }
 }
@@ -14,6 +14,8 @@ pub struct Parser<'a> {
     current: usize,
     /// When true, struct literal parsing is suppressed (used inside `match dyn` etc.)
     no_struct_literal: bool,
+    /// Counter for temporaries introduced by compound assignment
+    compound_counter: usize,
 }
 
 impl<'a> Parser<'a> {
@@ -23,6 +25,7 @@ impl<'a> Parser<'a> {
             tokens,
             current: 0,
             no_struct_literal: false,
+            compound_counter: 0,
         }
     }
 
@@ -47,20 +50,32 @@ impl<'a> Parser<'a> {
         Ok(attributes)
     }
 
+    /// Doc comment attached to the current token, if any.
+    fn current_doc(&self) -> Option<String> {
+        self.peek().and_then(|t| t.doc.clone())
+    }
+
     fn item(&mut self) -> Result<Item, String> {
+        let doc = self.current_doc();
         if self.check(&TokenKind::At) {
             let attributes = self.parse_attributes()?;
             if self.check(&TokenKind::Fun) {
-                Ok(Item::FnDef(self.fn_def_with_attributes(attributes)?))
+                let mut fn_def = self.fn_def_with_attributes(attributes)?;
+                fn_def.doc = doc;
+                Ok(Item::FnDef(fn_def))
             } else {
                 Err(self.error("attributes can only be applied to function definitions"))
             }
         } else if self.check(&TokenKind::Import) {
             Ok(Item::Import(self.import_stmt()?))
         } else if self.check(&TokenKind::Fun) && self.check_ahead_ident(1) {
-            Ok(Item::FnDef(self.fn_def()?))
+            let mut fn_def = self.fn_def()?;
+            fn_def.doc = doc;
+            Ok(Item::FnDef(fn_def))
         } else if self.check(&TokenKind::Struct) {
-            Ok(Item::StructDef(self.struct_def()?))
+            let mut struct_def = self.struct_def()?;
+            struct_def.doc = doc;
+            Ok(Item::StructDef(struct_def))
         } else if self.check(&TokenKind::Interface) {
             Ok(Item::InterfaceDef(self.interface_def()?))
         } else if self.check(&TokenKind::Impl) {
@@ -182,6 +197,7 @@ impl<'a> Parser<'a> {
             return_type,
             body,
             attributes,
+            doc: None,
             span,
         })
     }
@@ -215,6 +231,7 @@ impl<'a> Parser<'a> {
             name,
             type_params,
             fields,
+            doc: None,
             span,
         })
     }
@@ -344,8 +361,11 @@ impl<'a> Parser<'a> {
 
         let mut methods = Vec::new();
         while !self.check(&TokenKind::RBrace) && !self.is_at_end() {
+            let doc = self.current_doc();
             let attributes = self.parse_attributes()?;
-            methods.push(self.fn_def_with_attributes(attributes)?);
+            let mut method = self.fn_def_with_attributes(attributes)?;
+            method.doc = doc;
+            methods.push(method);
         }
         self.expect(&TokenKind::RBrace)?;
 
@@ -510,6 +530,30 @@ impl<'a> Parser<'a> {
         let span = self.current_span();
         let expr = self.expression()?;
 
+        if let Some(op) = self.match_compound_assign_op() {
+            let value = self.expression()?;
+            self.expect(&TokenKind::Semi)?;
+            return self.compound_assign(expr, op, value, span);
+        }
+
+        // `i++;` / `i--;` are shorthands for `i += 1;` / `i -= 1;`
+        let step = if self.match_token(&TokenKind::PlusPlus) {
+            Some(BinaryOp::Add)
+        } else if self.match_token(&TokenKind::MinusMinus) {
+            Some(BinaryOp::Sub)
+        } else {
+            None
+        };
+        if let Some(op) = step {
+            let one = Expr::Int {
+                value: 1,
+                span: self.previous_span(),
+                inferred_type: None,
+            };
+            self.expect(&TokenKind::Semi)?;
+            return self.compound_assign(expr, op, one, span);
+        }
+
         // Check if this is an assignment
         if self.match_token(&TokenKind::Eq) {
             let value = self.expression()?;
@@ -533,11 +577,150 @@ impl<'a> Parser<'a> {
                 _ => Err(self.error("invalid assignment target")),
             }
         } else {
-            self.expect(&TokenKind::Semi)?;
+            // The final expression of a file may omit its semicolon (`moca run -c "1 + 2"`)
+            if !self.is_at_end() {
+                self.expect(&TokenKind::Semi)?;
+            }
             Ok(Statement::Expr { expr, span })
         }
     }
 
+    fn match_compound_assign_op(&mut self) -> Option<BinaryOp> {
+        let op = match self.peek_kind()? {
+            TokenKind::PlusEq => BinaryOp::Add,
+            TokenKind::MinusEq => BinaryOp::Sub,
+            TokenKind::StarEq => BinaryOp::Mul,
+            TokenKind::SlashEq => BinaryOp::Div,
+            TokenKind::PercentEq => BinaryOp::Mod,
+            TokenKind::AmpersandEq => BinaryOp::BitwiseAnd,
+            TokenKind::PipeEq => BinaryOp::BitwiseOr,
+            TokenKind::CaretEq => BinaryOp::BitwiseXor,
+            TokenKind::LtLtEq => BinaryOp::Shl,
+            TokenKind::GtGtEq => BinaryOp::Shr,
+            _ => return None,
+        };
+        self.advance();
+        Some(op)
+    }
+
+    /// Expand `target op= value` into a plain assignment of `target op value`.
+    ///
+    /// Field and index targets whose object or index is not a plain variable
+    /// or literal are first bound to temporaries inside a block, so that
+    /// `a[f()] += 1` calls `f` once:
+    ///
+    /// ```text
+    /// { let __compound_0 = a; let __compound_1 = f();
+    ///   __compound_0[__compound_1] = __compound_0[__compound_1] + 1; }
+    /// ```
+    fn compound_assign(
+        &mut self,
+        target: Expr,
+        op: BinaryOp,
+        value: Expr,
+        span: Span,
+    ) -> Result<Statement, String> {
+        let combine = |current: Expr| Expr::Binary {
+            op,
+            left: Box::new(current),
+            right: Box::new(value),
+            span,
+            inferred_type: None,
+        };
+
+        let mut temps = Vec::new();
+        let assign = match target {
+            Expr::Ident { name, .. } => Statement::Assign {
+                value: combine(Expr::Ident {
+                    name: name.clone(),
+                    span,
+                    inferred_type: None,
+                }),
+                name,
+                span,
+            },
+            Expr::Field { object, field, .. } => {
+                let object = self.evaluate_once(*object, &mut temps);
+                Statement::FieldAssign {
+                    value: combine(Expr::Field {
+                        object: Box::new(object.clone()),
+                        field: field.clone(),
+                        span,
+                        inferred_type: None,
+                    }),
+                    object,
+                    field,
+                    span,
+                }
+            }
+            Expr::Index { object, index, .. } => {
+                let object = self.evaluate_once(*object, &mut temps);
+                let index = self.evaluate_once(*index, &mut temps);
+                Statement::IndexAssign {
+                    value: combine(Expr::Index {
+                        object: Box::new(object.clone()),
+                        index: Box::new(index.clone()),
+                        span,
+                        object_type: None,
+                        inferred_type: None,
+                    }),
+                    object,
+                    index,
+                    span,
+                    object_type: None,
+                }
+            }
+            _ => return Err(self.error("invalid assignment target")),
+        };
+
+        if temps.is_empty() {
+            return Ok(assign);
+        }
+        temps.push(assign);
+        Ok(Statement::Expr {
+            expr: Expr::Block {
+                statements: temps,
+                expr: Box::new(Expr::Nil {
+                    span,
+                    inferred_type: None,
+                }),
+                span,
+                inferred_type: None,
+            },
+            span,
+        })
+    }
+
+    /// Return an expression that can be evaluated twice with the effect of
+    /// evaluating `expr` once, binding it to a fresh temporary if needed.
+    fn evaluate_once(&mut self, expr: Expr, temps: &mut Vec<Statement>) -> Expr {
+        if matches!(
+            expr,
+            Expr::Ident { .. }
+                | Expr::Int { .. }
+                | Expr::Float { .. }
+                | Expr::Str { .. }
+                | Expr::Bool { .. }
+        ) {
+            return expr;
+        }
+        let span = expr.span();
+        let name = format!("__compound_{}", self.compound_counter);
+        self.compound_counter += 1;
+        temps.push(Statement::Let {
+            name: name.clone(),
+            type_annotation: None,
+            init: expr,
+            span,
+            inferred_type: None,
+        });
+        Expr::Ident {
+            name,
+            span,
+            inferred_type: None,
+        }
+    }
+
     fn if_stmt(&mut self) -> Result<Statement, String> {
         let span = self.current_span();
         self.expect(&TokenKind::If)?;
@@ -834,7 +1017,27 @@ impl<'a> Parser<'a> {
     // Expression parsing with precedence climbing
 
     fn expression(&mut self) -> Result<Expr, String> {
-        self.or_expr()
+        self.conditional_expr()
+    }
+
+    /// `cond ? a : b`, binding looser than `||` and nesting to the right:
+    /// `a ? b : c ? d : e` is `a ? b : (c ? d : e)`.
+    fn conditional_expr(&mut self) -> Result<Expr, String> {
+        let condition = self.or_expr()?;
+        if !self.match_token(&TokenKind::Question) {
+            return Ok(condition);
+        }
+        let span = condition.span();
+        let then_expr = self.expression()?;
+        self.expect(&TokenKind::Colon)?;
+        let else_expr = self.conditional_expr()?;
+        Ok(Expr::Conditional {
+            condition: Box::new(condition),
+            then_expr: Box::new(then_expr),
+            else_expr: Box::new(else_expr),
+            span,
+            inferred_type: None,
+        })
     }
 
     fn or_expr(&mut self) -> Result<Expr, String> {
@@ -1013,6 +1216,11 @@ impl<'a> Parser<'a> {
         let mut left = self.mul_expr()?;
 
         loop {
+            // `a--b` is `a - -b`; a `--` ending the statement is a decrement
+            if self.check(&TokenKind::MinusMinus) && !self.check_ahead(&TokenKind::Semi, 1) {
+                self.split_minus_minus();
+            }
+
             let op = if self.match_token(&TokenKind::Plus) {
                 BinaryOp::Add
             } else if self.match_token(&TokenKind::Minus) {
@@ -1064,6 +1272,11 @@ impl<'a> Parser<'a> {
     }
 
     fn unary_expr(&mut self) -> Result<Expr, String> {
+        if self.check(&TokenKind::MinusMinus) {
+            // `--x` is a double negation, not a pre-decrement
+            self.split_minus_minus();
+        }
+
         if self.match_token(&TokenKind::Bang) {
             let span = self.previous_span();
             let operand = self.unary_expr()?;
@@ -1452,6 +1665,11 @@ impl<'a> Parser<'a> {
             let name = name.clone();
             self.advance();
 
+            // Async spawn block: spawn { ... }
+            if name == "spawn" && !self.no_struct_literal && self.check(&TokenKind::LBrace) {
+                return self.spawn_block(span);
+            }
+
             // Check if this is a struct literal: Name { field: value, ... }
             // Use lookahead to distinguish from blocks: { must be followed by ident :
             if !self.no_struct_literal
@@ -1681,6 +1899,53 @@ impl<'a> Parser<'a> {
         })
     }
 
+    /// Parse an async spawn block: `spawn { stmts; expr }`.
+    ///
+    /// The block becomes the body of a parameterless lambda passed to the
+    /// internal `__spawn_block` builtin. A trailing expression without a
+    /// semicolon is the block's result, as if written `return expr;`.
+    fn spawn_block(&mut self, span: Span) -> Result<Expr, String> {
+        let body_span = self.current_span();
+        self.expect(&TokenKind::LBrace)?;
+
+        let mut statements = Vec::new();
+        while !self.check(&TokenKind::RBrace) && !self.is_at_end() {
+            // Try the trailing expression first, backing out if more follows
+            let (start, no_struct_literal) = (self.current, self.no_struct_literal);
+            let expr_span = self.current_span();
+            if let Ok(expr) = self.expression()
+                && self.check(&TokenKind::RBrace)
+            {
+                statements.push(Statement::Return {
+                    value: Some(expr),
+                    span: expr_span,
+                });
+                break;
+            }
+            self.current = start;
+            self.no_struct_literal = no_struct_literal;
+            statements.push(self.statement()?);
+        }
+        self.expect(&TokenKind::RBrace)?;
+
+        Ok(Expr::Call {
+            callee: "__spawn_block".to_string(),
+            type_args: vec![],
+            args: vec![Expr::Lambda {
+                params: vec![],
+                return_type: None,
+                body: Block {
+                    statements,
+                    span: body_span,
+                },
+                span,
+                inferred_type: None,
+            }],
+            span,
+            inferred_type: None,
+        })
+    }
+
     /// Parse a new literal: new TypeName { expr, ... } or new TypeName { key: value, ... }
     fn new_literal(&mut self, span: Span) -> Result<Expr, String> {
         // Parse type name
@@ -1913,16 +2178,22 @@ impl<'a> Parser<'a> {
             // Split '>>' into '>' + '>' for nested generic types like Vec<Vec<int>>
             // Replace current GtGt with Gt (the remaining one) and don't advance.
             let span = self.peek().unwrap().span;
-            self.tokens[self.current] = Token {
-                kind: TokenKind::Gt,
-                span,
-            };
+            self.tokens[self.current] = Token::new(TokenKind::Gt, span);
             Ok(())
         } else {
             Err(self.error(&format!("expected {:?}", kind)))
         }
     }
 
+    /// Split the current `--` token into two `-` tokens, like `expect` does
+    /// for `>>` closing nested generics.
+    fn split_minus_minus(&mut self) {
+        let span = self.peek().unwrap().span;
+        self.tokens[self.current] = Token::new(TokenKind::Minus, span);
+        self.tokens
+            .insert(self.current + 1, Token::new(TokenKind::Minus, span));
+    }
+
     fn expect_ident(&mut self) -> Result<String, String> {
         if let Some(TokenKind::Ident(name)) = self.peek_kind() {
             let name = name.clone();
@@ -1988,6 +2259,16 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_trailing_expression_without_semicolon() {
+        let program = parse("let x = 1; x + 2").unwrap();
+        assert!(matches!(
+            program.items.last(),
+            Some(Item::Statement(Statement::Expr { .. }))
+        ));
+        assert!(parse("1 + 2 3;").is_err());
+    }
+
     #[test]
     fn test_function_definition() {
         let program = parse("fun add(a, b) { return a + b; }").unwrap();
@@ -2045,6 +2326,30 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_spawn_block() {
+        let program = parse("let p = spawn { let x = 1; x + 2 };").unwrap();
+        let Some(Item::Statement(Statement::Let { init, .. })) = program.items.first() else {
+            panic!("expected let statement");
+        };
+        let Expr::Call { callee, args, .. } = init else {
+            panic!("expected call, got {:?}", init);
+        };
+        assert_eq!(callee, "__spawn_block");
+        let [Expr::Lambda { params, body, .. }] = args.as_slice() else {
+            panic!("expected a lambda argument");
+        };
+        assert!(params.is_empty());
+        // The trailing expression becomes the block's return value
+        assert!(matches!(
+            body.statements.as_slice(),
+            [
+                Statement::Let { .. },
+                Statement::Return { value: Some(_), .. }
+            ]
+        ));
+    }
+
     #[test]
     fn test_function_call() {
         let program = parse("print(42);").unwrap();
@@ -2062,11 +2367,11 @@ mod tests {
 
     #[test]
     fn test_float_literal() {
-        let program = parse("let x = 3.14;").unwrap();
+        let program = parse("let x = 2.5;").unwrap();
         match &program.items[0] {
             Item::Statement(Statement::Let { init, .. }) => match init {
                 Expr::Float { value, .. } => {
-                    assert_eq!(*value, 3.14);
+                    assert_eq!(*value, 2.5);
                 }
                 _ => panic!("expected float"),
             },
@@ -2453,6 +2758,112 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_conditional_nests_to_the_right() {
+        let program = parse("let g = a ? 1 : b ? 2 : 3;").unwrap();
+        match &program.items[0] {
+            Item::Statement(Statement::Let { init, .. }) => match init {
+                Expr::Conditional {
+                    then_expr,
+                    else_expr,
+                    ..
+                } => {
+                    assert!(matches!(**then_expr, Expr::Int { value: 1, .. }));
+                    assert!(matches!(**else_expr, Expr::Conditional { .. }));
+                }
+                _ => panic!("expected conditional"),
+            },
+            _ => panic!("expected let statement"),
+        }
+    }
+
+    #[test]
+    fn test_compound_assign_binds_impure_index_once() {
+        let program = parse("a[f()] += 1;").unwrap();
+        match &program.items[0] {
+            Item::Statement(Statement::Expr {
+                expr: Expr::Block { statements, .. },
+                ..
+            }) => {
+                assert_eq!(statements.len(), 2);
+                assert!(matches!(
+                    &statements[0],
+                    Statement::Let {
+                        init: Expr::Call { .. },
+                        ..
+                    }
+                ));
+                match &statements[1] {
+                    Statement::IndexAssign { index, value, .. } => {
+                        assert!(matches!(index, Expr::Ident { .. }));
+                        assert!(matches!(
+                            value,
+                            Expr::Binary {
+                                op: BinaryOp::Add,
+                                ..
+                            }
+                        ));
+                    }
+                    _ => panic!("expected index assignment"),
+                }
+            }
+            _ => panic!("expected block statement"),
+        }
+    }
+
+    #[test]
+    fn test_compound_assign_local() {
+        let program = parse("x -= 2;").unwrap();
+        match &program.items[0] {
+            Item::Statement(Statement::Assign { name, value, .. }) => {
+                assert_eq!(name, "x");
+                assert!(matches!(
+                    value,
+                    Expr::Binary {
+                        op: BinaryOp::Sub,
+                        ..
+                    }
+                ));
+            }
+            _ => panic!("expected assignment"),
+        }
+    }
+
+    #[test]
+    fn test_decrement_statement_and_double_negation() {
+        let program = parse("i--; let y = --x;").unwrap();
+        match &program.items[0] {
+            Item::Statement(Statement::Assign { name, value, .. }) => {
+                assert_eq!(name, "i");
+                assert!(matches!(
+                    value,
+                    Expr::Binary {
+                        op: BinaryOp::Sub,
+                        ..
+                    }
+                ));
+            }
+            _ => panic!("expected assignment"),
+        }
+        match &program.items[1] {
+            Item::Statement(Statement::Let { init, .. }) => match init {
+                Expr::Unary {
+                    op: UnaryOp::Neg,
+                    operand,
+                    ..
+                } => assert!(matches!(
+                    **operand,
+                    Expr::Unary {
+                        op: UnaryOp::Neg,
+                        ..
+                    }
+                )),
+                _ => panic!("expected negation"),
+            },
+            _ => panic!("expected let statement"),
+        }
+    }
+
     #[test]
     fn test_method_call() {
         let program = parse("let a = rect.area();").unwrap();
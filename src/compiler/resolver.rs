@@ -3,6 +3,11 @@ use crate::compiler::lexer::Span;
 use crate::compiler::types::{Type, TypeAnnotation};
 use std::collections::{HashMap, HashSet};
 
+/// `MethodCall::func_index` of a method called on a generic type parameter.
+/// Only monomorphised copies of the generic body run, so codegen emits
+/// `Unreachable` instead of a call.
+pub(crate) const GENERIC_METHOD: usize = usize::MAX;
+
 /// A type descriptor entry: (tag_name, field_names, field_type_tags, aux_type_tags).
 type TypeDescriptorEntry = (String, Vec<String>, Vec<String>, Vec<String>);
 
@@ -24,6 +29,10 @@ pub struct ResolvedProgram {
     pub main_locals_count: usize,
     /// Type information for main body local variables (indexed by slot)
     pub main_local_types: Vec<Type>,
+    /// Source names of main body local variables (indexed by slot)
+    pub main_local_names: Vec<String>,
+    /// Block nesting depth each main body local was declared at (indexed by slot)
+    pub main_local_depths: Vec<usize>,
     /// Interface definitions: interface_name -> method_names (sorted)
     pub interface_methods: HashMap<String, Vec<String>>,
 }
@@ -46,8 +55,16 @@ pub struct ResolvedFunction {
     pub body: Vec<ResolvedStatement>,
     /// Type information for local variables (indexed by slot number)
     pub local_types: Vec<Type>,
+    /// Source names of local variables (indexed by slot number).
+    /// Shadowed bindings get distinct slots, so each slot has one name.
+    pub local_names: Vec<String>,
+    /// Block nesting depth each local was declared at (indexed by slot).
+    /// Parameters and top-level locals are at depth 0.
+    pub local_depths: Vec<usize>,
     /// Whether this function is marked with @inline
     pub is_inline: bool,
+    /// Source location of the definition (the lambda or spawn block for lifted functions)
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
@@ -56,10 +73,12 @@ pub enum ResolvedStatement {
     Let {
         slot: usize,
         init: ResolvedExpr,
+        span: Span,
     },
     Assign {
         slot: usize,
         value: ResolvedExpr,
+        span: Span,
     },
     IndexAssign {
         object: ResolvedExpr,
@@ -75,43 +94,52 @@ pub enum ResolvedStatement {
         value: ResolvedExpr,
         /// Struct name for field index resolution (from typechecker)
         struct_name: Option<String>,
+        span: Span,
     },
     If {
         condition: ResolvedExpr,
         then_block: Vec<ResolvedStatement>,
         else_block: Option<Vec<ResolvedStatement>>,
+        span: Span,
     },
     While {
         condition: ResolvedExpr,
         body: Vec<ResolvedStatement>,
         post_body: Vec<ResolvedStatement>,
+        span: Span,
     },
     ForIn {
         slot: usize,
         iterable: ResolvedExpr,
         body: Vec<ResolvedStatement>,
+        span: Span,
     },
     Break,
     Continue,
     Return {
         value: Option<ResolvedExpr>,
+        span: Span,
     },
     Throw {
         value: ResolvedExpr,
+        span: Span,
     },
     Try {
         try_block: Vec<ResolvedStatement>,
         catch_slot: usize,
         catch_block: Vec<ResolvedStatement>,
+        span: Span,
     },
     Expr {
         expr: ResolvedExpr,
+        span: Span,
     },
     /// Store to a promoted var variable through its RefCell (outer scope).
     /// Compiles to: LocalGet(slot) + compile(value) + HeapStore(0)
     RefCellStore {
         slot: usize,
         value: ResolvedExpr,
+        span: Span,
     },
     /// Match dyn statement: runtime type dispatch on a dyn value.
     MatchDyn {
@@ -119,9 +147,32 @@ pub enum ResolvedStatement {
         expr: ResolvedExpr,
         arms: Vec<ResolvedMatchDynArm>,
         default_block: Vec<ResolvedStatement>,
+        span: Span,
     },
 }
 
+impl ResolvedStatement {
+    /// Source location of the statement, if it carries one.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            ResolvedStatement::Let { span, .. }
+            | ResolvedStatement::Assign { span, .. }
+            | ResolvedStatement::IndexAssign { span, .. }
+            | ResolvedStatement::FieldAssign { span, .. }
+            | ResolvedStatement::If { span, .. }
+            | ResolvedStatement::While { span, .. }
+            | ResolvedStatement::ForIn { span, .. }
+            | ResolvedStatement::Return { span, .. }
+            | ResolvedStatement::Throw { span, .. }
+            | ResolvedStatement::Try { span, .. }
+            | ResolvedStatement::Expr { span, .. }
+            | ResolvedStatement::RefCellStore { span, .. }
+            | ResolvedStatement::MatchDyn { span, .. } => Some(*span),
+            ResolvedStatement::Break | ResolvedStatement::Continue => None,
+        }
+    }
+}
+
 /// An arm in a resolved match dyn statement.
 #[derive(Debug, Clone)]
 pub struct ResolvedMatchDynArm {
@@ -178,6 +229,7 @@ pub enum ResolvedExpr {
     Unary {
         op: UnaryOp,
         operand: Box<ResolvedExpr>,
+        span: Span,
     },
     Binary {
         op: BinaryOp,
@@ -185,19 +237,38 @@ pub enum ResolvedExpr {
         right: Box<ResolvedExpr>,
         /// Type of the left operand (from typechecker, for codegen to emit type-specific ops)
         operand_type: Option<Type>,
+        span: Span,
+    },
+    /// `cond ? then_expr : else_expr`
+    Conditional {
+        condition: Box<ResolvedExpr>,
+        then_expr: Box<ResolvedExpr>,
+        else_expr: Box<ResolvedExpr>,
     },
     Call {
         func_index: usize,
         args: Vec<ResolvedExpr>,
+        span: Span,
     },
     Builtin {
         name: String,
         args: Vec<ResolvedExpr>,
         span: Span,
     },
-    /// Spawn a thread with a specific function
+    /// Spawn a thread with a specific function, passing it atomics
     SpawnFunc {
         func_index: usize,
+        args: Vec<ResolvedExpr>,
+    },
+    /// Spawn a thread running a lifted `spawn { ... }` block, yielding a promise
+    SpawnPromise {
+        func_index: usize,
+    },
+    /// `type_of(expr)`: the static type tag when known, otherwise (for `dyn`)
+    /// the tag read from the value's type descriptor at runtime
+    TypeOf {
+        expr: Box<ResolvedExpr>,
+        tag_name: Option<String>,
     },
     /// Struct literal: `Point { x: 1, y: 2 }`
     /// Fields are resolved to expressions in declaration order (struct field order).
@@ -211,10 +282,12 @@ pub enum ResolvedExpr {
     MethodCall {
         object: Box<ResolvedExpr>,
         method: String,
+        /// [`GENERIC_METHOD`] for a call on a type parameter
         func_index: usize,
         args: Vec<ResolvedExpr>,
         /// If the method returns a struct, the struct name
         return_struct_name: Option<String>,
+        span: Span,
     },
     /// Associated function call: `Type::func(args)`
     /// Statically dispatched to the resolved function.
@@ -223,6 +296,7 @@ pub enum ResolvedExpr {
         args: Vec<ResolvedExpr>,
         /// If the function returns a struct, the struct name
         return_struct_name: Option<String>,
+        span: Span,
     },
     /// Inline assembly block.
     AsmBlock {
@@ -259,6 +333,7 @@ pub enum ResolvedExpr {
     CallIndirect {
         callee: Box<ResolvedExpr>,
         args: Vec<ResolvedExpr>,
+        span: Span,
     },
     /// Load a captured variable from the closure reference (local slot 0).
     /// When is_ref is false: LocalGet(0) + HeapLoad(offset) (copy capture, let variable)
@@ -308,6 +383,51 @@ pub enum ResolvedExpr {
     },
 }
 
+impl ResolvedExpr {
+    /// Source location of the expression, if it carries one.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            ResolvedExpr::Index { span, .. }
+            | ResolvedExpr::Unary { span, .. }
+            | ResolvedExpr::Binary { span, .. }
+            | ResolvedExpr::Call { span, .. }
+            | ResolvedExpr::Builtin { span, .. }
+            | ResolvedExpr::MethodCall { span, .. }
+            | ResolvedExpr::AssociatedFunctionCall { span, .. }
+            | ResolvedExpr::CallIndirect { span, .. } => Some(*span),
+            _ => None,
+        }
+    }
+
+    /// Value of an integer expression built from literals (and inlined
+    /// consts) with negation, `+`, `-`, `*`, `/` and `%`, or None if it isn't
+    /// constant or overflows.
+    pub fn const_int(&self) -> Option<i64> {
+        match self {
+            ResolvedExpr::Int(n) => Some(*n),
+            ResolvedExpr::Unary {
+                op: UnaryOp::Neg,
+                operand,
+                ..
+            } => operand.const_int()?.checked_neg(),
+            ResolvedExpr::Binary {
+                op, left, right, ..
+            } => {
+                let (l, r) = (left.const_int()?, right.const_int()?);
+                match op {
+                    BinaryOp::Add => l.checked_add(r),
+                    BinaryOp::Sub => l.checked_sub(r),
+                    BinaryOp::Mul => l.checked_mul(r),
+                    BinaryOp::Div => l.checked_div(r),
+                    BinaryOp::Mod => l.checked_rem(r),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
 /// An element in a resolved new literal.
 #[derive(Debug, Clone)]
 pub enum ResolvedNewLiteralElement {
@@ -336,6 +456,8 @@ struct StructDefInfo {
 pub struct Resolver<'a> {
     filename: &'a str,
     functions: HashMap<String, usize>,
+    /// Parameter count of each registered function, keyed by func_index
+    function_arities: HashMap<usize, usize>,
     builtins: Vec<String>,
     /// Struct definitions: struct_name -> info
     structs: HashMap<String, StructDefInfo>,
@@ -368,19 +490,40 @@ impl<'a> Resolver<'a> {
         Self {
             filename,
             functions: HashMap::new(),
+            function_arities: HashMap::new(),
             interface_impls: HashSet::new(),
             interface_methods: HashMap::new(),
             builtins: vec![
                 "__typeof".to_string(),
                 "__heap_size".to_string(),
                 "len".to_string(),
+                "slice".to_string(),
+                "to_bytes".to_string(),
+                "from_bytes".to_string(),
                 "type_of".to_string(),
+                "type_name".to_string(),
+                // Native hash maps
+                "dict_new".to_string(),
+                "dict_get".to_string(),
+                "dict_set".to_string(),
+                "dict_has".to_string(),
+                "dict_delete".to_string(),
+                "dict_len".to_string(),
+                "dict_keys".to_string(),
+                "dict_values".to_string(),
+                "dict_entries".to_string(),
                 // Thread operations
                 "spawn".to_string(),
                 "channel".to_string(),
                 "send".to_string(),
                 "recv".to_string(),
                 "join".to_string(),
+                "await".to_string(),
+                "atomic_new".to_string(),
+                "atomic_load".to_string(),
+                "atomic_store".to_string(),
+                "atomic_fetch_add".to_string(),
+                "atomic_compare_exchange".to_string(),
                 // Hostcall operations (generic hostcall builtin)
                 "__hostcall".to_string(),
                 // Low-level heap intrinsics (for stdlib implementation)
@@ -390,14 +533,39 @@ impl<'a> Resolver<'a> {
                 "__alloc_string".to_string(),
                 "__null_ptr".to_string(),
                 "__ptr_offset".to_string(),
+                // Native string helpers (backing split/str_join/substring/index_of/replace)
+                "__string_split".to_string(),
+                "__string_join".to_string(),
+                "__string_substring".to_string(),
+                "__string_index_of".to_string(),
+                "__string_replace".to_string(),
+                // String builder (see StringBuilder in the prelude)
+                "__sb_append".to_string(),
+                "__sb_finish".to_string(),
                 // 128-bit multiply high
                 "__umul128_hi".to_string(),
+                // Unsigned division/remainder
+                "udiv".to_string(),
+                "urem".to_string(),
+                // Saturating/checked arithmetic
+                "sat_add".to_string(),
+                "sat_sub".to_string(),
+                "sat_mul".to_string(),
+                "checked_add".to_string(),
+                // Structural array/string equality
+                "__deep_eq".to_string(),
+                // Three-way ordering of numbers and strings
+                "__cmp".to_string(),
                 // Dynamic call by function index
                 "__call_func".to_string(),
+                // Runtime type guard inserted by the typechecker
+                "__assert_type".to_string(),
                 // CLI argument operations
                 "argc".to_string(),
                 "argv".to_string(),
                 "args".to_string(),
+                // Source-level debugger breakpoint
+                "breakpoint".to_string(),
             ],
             structs: HashMap::new(),
             primitive_methods: HashMap::new(),
@@ -559,6 +727,16 @@ impl<'a> Resolver<'a> {
                 Self::collect_var_types_from_expr(left, type_map);
                 Self::collect_var_types_from_expr(right, type_map);
             }
+            Expr::Conditional {
+                condition,
+                then_expr,
+                else_expr,
+                ..
+            } => {
+                Self::collect_var_types_from_expr(condition, type_map);
+                Self::collect_var_types_from_expr(then_expr, type_map);
+                Self::collect_var_types_from_expr(else_expr, type_map);
+            }
             Expr::Unary { operand, .. } => {
                 Self::collect_var_types_from_expr(operand, type_map);
             }
@@ -685,6 +863,7 @@ impl<'a> Resolver<'a> {
                 ));
             }
             self.functions.insert(fn_def.name.clone(), index);
+            self.function_arities.insert(index, fn_def.params.len());
         }
 
         // Register impl block methods as functions
@@ -731,6 +910,8 @@ impl<'a> Resolver<'a> {
                     ));
                 }
                 self.functions.insert(func_name.clone(), func_index);
+                self.function_arities
+                    .insert(func_index, method.params.len());
 
                 // Get return type struct name if method returns a struct
                 let return_struct_name = method.return_type.as_ref().and_then(|rt| {
@@ -804,6 +985,8 @@ impl<'a> Resolver<'a> {
             structs: self.resolved_structs.clone(),
             main_locals_count,
             main_local_types,
+            main_local_names: scope.slot_names,
+            main_local_depths: scope.slot_depths,
             interface_methods: self.interface_methods.clone(),
         })
     }
@@ -837,7 +1020,14 @@ impl<'a> Resolver<'a> {
         }
 
         let mut method_type_map = Self::collect_var_types(&method.body.statements);
-        // Add parameter types
+        // Add parameter types, starting with the receiver
+        if has_self
+            && let Some(ty) = self.type_from_annotation(&Some(
+                crate::compiler::types::TypeAnnotation::Named(struct_name.to_string()),
+            ))
+        {
+            method_type_map.insert("self".to_string(), ty);
+        }
         for param in &method.params {
             if param.name != "self"
                 && let Some(ty) = self.type_from_annotation(&param.type_annotation)
@@ -873,7 +1063,10 @@ impl<'a> Resolver<'a> {
             locals_count: scope.locals_count,
             body,
             local_types,
+            local_names: scope.slot_names,
+            local_depths: scope.slot_depths,
             is_inline,
+            span: method.span,
         })
     }
 
@@ -915,7 +1108,10 @@ impl<'a> Resolver<'a> {
             locals_count: scope.locals_count,
             body,
             local_types,
+            local_names: scope.slot_names,
+            local_depths: scope.slot_depths,
             is_inline,
+            span: fn_def.span,
         })
     }
 
@@ -1106,6 +1302,16 @@ impl<'a> Resolver<'a> {
                 Self::collect_reassigned_vars_expr(left, reassigned);
                 Self::collect_reassigned_vars_expr(right, reassigned);
             }
+            Expr::Conditional {
+                condition,
+                then_expr,
+                else_expr,
+                ..
+            } => {
+                Self::collect_reassigned_vars_expr(condition, reassigned);
+                Self::collect_reassigned_vars_expr(then_expr, reassigned);
+                Self::collect_reassigned_vars_expr(else_expr, reassigned);
+            }
             Expr::Unary { operand, .. } => {
                 Self::collect_reassigned_vars_expr(operand, reassigned);
             }
@@ -1283,6 +1489,16 @@ impl<'a> Resolver<'a> {
                 Self::scan_expr_for_lambdas(left, var_names, captured);
                 Self::scan_expr_for_lambdas(right, var_names, captured);
             }
+            Expr::Conditional {
+                condition,
+                then_expr,
+                else_expr,
+                ..
+            } => {
+                Self::scan_expr_for_lambdas(condition, var_names, captured);
+                Self::scan_expr_for_lambdas(then_expr, var_names, captured);
+                Self::scan_expr_for_lambdas(else_expr, var_names, captured);
+            }
             Expr::Call { args, .. } => {
                 for a in args {
                     Self::scan_expr_for_lambdas(a, var_names, captured);
@@ -1365,7 +1581,7 @@ impl<'a> Resolver<'a> {
                 type_annotation,
                 init,
                 inferred_type,
-                span: _,
+                span,
             } => {
                 let init = self.resolve_expr(init, scope)?;
                 let inferred_fallback =
@@ -1411,9 +1627,10 @@ impl<'a> Resolver<'a> {
                         init: ResolvedExpr::RefCellNew {
                             value: Box::new(init),
                         },
+                        span,
                     })
                 } else {
-                    Ok(ResolvedStatement::Let { slot, init })
+                    Ok(ResolvedStatement::Let { slot, init, span })
                 }
             }
             Statement::Assign { name, value, span } => {
@@ -1432,6 +1649,7 @@ impl<'a> Resolver<'a> {
                             offset,
                             value: Box::new(value),
                         },
+                        span,
                     });
                 }
 
@@ -1442,16 +1660,16 @@ impl<'a> Resolver<'a> {
                 let value = self.resolve_expr(value, scope)?;
                 // If this var is promoted to RefCell, use RefCellStore
                 if scope.promoted_vars.contains(&name) {
-                    Ok(ResolvedStatement::RefCellStore { slot, value })
+                    Ok(ResolvedStatement::RefCellStore { slot, value, span })
                 } else {
-                    Ok(ResolvedStatement::Assign { slot, value })
+                    Ok(ResolvedStatement::Assign { slot, value, span })
                 }
             }
             Statement::If {
                 condition,
                 then_block,
                 else_block,
-                ..
+                span,
             } => {
                 let condition = self.resolve_expr(condition, scope)?;
 
@@ -1472,13 +1690,14 @@ impl<'a> Resolver<'a> {
                     condition,
                     then_block: then_resolved,
                     else_block: else_resolved,
+                    span,
                 })
             }
             Statement::While {
                 condition,
                 body,
                 post_body,
-                ..
+                span,
             } => {
                 let condition = self.resolve_expr(condition, scope)?;
 
@@ -1496,6 +1715,7 @@ impl<'a> Resolver<'a> {
                     condition,
                     body: body_resolved,
                     post_body: post_body_resolved,
+                    span,
                 })
             }
             Statement::Break { span } => {
@@ -1516,17 +1736,17 @@ impl<'a> Resolver<'a> {
                 }
                 Ok(ResolvedStatement::Continue)
             }
-            Statement::Return { value, .. } => {
+            Statement::Return { value, span } => {
                 let value = if let Some(v) = value {
                     Some(self.resolve_expr(v, scope)?)
                 } else {
                     None
                 };
-                Ok(ResolvedStatement::Return { value })
+                Ok(ResolvedStatement::Return { value, span })
             }
-            Statement::Expr { expr, .. } => {
+            Statement::Expr { expr, span } => {
                 let expr = self.resolve_expr(expr, scope)?;
-                Ok(ResolvedStatement::Expr { expr })
+                Ok(ResolvedStatement::Expr { expr, span })
             }
             Statement::IndexAssign {
                 object,
@@ -1550,7 +1770,7 @@ impl<'a> Resolver<'a> {
                 object,
                 field,
                 value,
-                ..
+                span,
             } => {
                 let struct_name = object.inferred_type().and_then(|ty| match ty {
                     Type::Struct { name, .. } | Type::GenericStruct { name, .. } => {
@@ -1565,13 +1785,14 @@ impl<'a> Resolver<'a> {
                     field,
                     value,
                     struct_name,
+                    span,
                 })
             }
             Statement::ForIn {
                 var,
                 iterable,
                 body,
-                ..
+                span,
             } => {
                 let iterable = self.resolve_expr(iterable, scope)?;
 
@@ -1590,16 +1811,19 @@ impl<'a> Resolver<'a> {
                     slot,
                     iterable,
                     body: body_resolved,
+                    span,
                 })
             }
             Statement::ForRange { .. } => {
                 unreachable!("ForRange should be desugared before resolution")
             }
-            Statement::Throw { value, .. } => {
+            Statement::Throw { value, span } => {
                 let value = self.resolve_expr(value, scope)?;
-                Ok(ResolvedStatement::Throw { value })
+                Ok(ResolvedStatement::Throw { value, span })
             }
-            Statement::Const { name, init, .. } => {
+            Statement::Const {
+                name, init, span, ..
+            } => {
                 // Resolve the init expression (should be a literal)
                 let resolved_init = self.resolve_expr(init, scope)?;
                 // Register const name for reassignment checking
@@ -1609,13 +1833,14 @@ impl<'a> Resolver<'a> {
                 // Const produces no runtime code (no slot allocation)
                 Ok(ResolvedStatement::Expr {
                     expr: ResolvedExpr::Nil,
+                    span,
                 })
             }
             Statement::Try {
                 try_block,
                 catch_var,
                 catch_block,
-                ..
+                span,
             } => {
                 scope.enter_scope();
                 let try_resolved = self.resolve_statements(try_block.statements, scope)?;
@@ -1630,13 +1855,14 @@ impl<'a> Resolver<'a> {
                     try_block: try_resolved,
                     catch_slot,
                     catch_block: catch_resolved,
+                    span,
                 })
             }
             Statement::MatchDyn {
                 expr,
                 arms,
                 default_block,
-                ..
+                span,
             } => {
                 // Allocate a local slot for the dyn value
                 let dyn_slot = scope.declare("__match_dyn".to_string(), false);
@@ -1720,11 +1946,64 @@ impl<'a> Resolver<'a> {
                     expr: resolved_expr,
                     arms: resolved_arms,
                     default_block: resolved_default,
+                    span,
                 })
             }
         }
     }
 
+    /// Lift the body of `spawn { ... }` into a parameterless function.
+    ///
+    /// The block runs on another thread with its own VM and heap, so unlike a
+    /// lambda it cannot capture locals; data goes in through channels or
+    /// atomics instead.
+    fn resolve_spawn_block(
+        &mut self,
+        body: Block,
+        span: Span,
+        scope: &Scope,
+    ) -> Result<ResolvedExpr, String> {
+        for var_name in collect_free_vars_block(&body, &[]) {
+            if scope.lookup(&var_name).is_some() || scope.lookup_capture(&var_name).is_some() {
+                return Err(self.error(
+                    &format!(
+                        "spawn block cannot capture local variable '{}'; pass it through a channel",
+                        var_name
+                    ),
+                    span,
+                ));
+            }
+            if !self.builtins.contains(&var_name) && !self.functions.contains_key(&var_name) {
+                return Err(self.error(
+                    &format!("undefined variable '{}' in spawn block", var_name),
+                    span,
+                ));
+            }
+        }
+
+        let mut block_scope = Scope::new();
+        let fn_type_map = Self::collect_var_types(&body.statements);
+        let resolved_body = self.resolve_statements(body.statements, &mut block_scope)?;
+        let local_types = Self::build_local_types(&block_scope, &fn_type_map);
+
+        let lambda_id = self.next_lambda_id;
+        self.next_lambda_id += 1;
+        let func_index = self.base_func_count + self.lifted_functions.len();
+        self.lifted_functions.push(ResolvedFunction {
+            name: format!("__spawn_{}", lambda_id),
+            params: vec![],
+            locals_count: block_scope.locals_count,
+            body: resolved_body,
+            local_types,
+            local_names: block_scope.slot_names.clone(),
+            local_depths: block_scope.slot_depths.clone(),
+            is_inline: false,
+            span,
+        });
+
+        Ok(ResolvedExpr::SpawnPromise { func_index })
+    }
+
     fn resolve_expr(&mut self, expr: Expr, scope: &mut Scope) -> Result<ResolvedExpr, String> {
         match expr {
             Expr::Int { value, .. } => Ok(ResolvedExpr::Int(value)),
@@ -1791,15 +2070,22 @@ impl<'a> Resolver<'a> {
                     struct_name,
                 })
             }
-            Expr::Unary { op, operand, .. } => {
+            Expr::Unary {
+                op, operand, span, ..
+            } => {
                 let operand = self.resolve_expr(*operand, scope)?;
                 Ok(ResolvedExpr::Unary {
                     op,
                     operand: Box::new(operand),
+                    span,
                 })
             }
             Expr::Binary {
-                op, left, right, ..
+                op,
+                left,
+                right,
+                span,
+                ..
             } => {
                 let operand_type = left.inferred_type().cloned();
                 let left = self.resolve_expr(*left, scope)?;
@@ -1809,28 +2095,46 @@ impl<'a> Resolver<'a> {
                     left: Box::new(left),
                     right: Box::new(right),
                     operand_type,
+                    span,
                 })
             }
+            Expr::Conditional {
+                condition,
+                then_expr,
+                else_expr,
+                ..
+            } => Ok(ResolvedExpr::Conditional {
+                condition: Box::new(self.resolve_expr(*condition, scope)?),
+                then_expr: Box::new(self.resolve_expr(*then_expr, scope)?),
+                else_expr: Box::new(self.resolve_expr(*else_expr, scope)?),
+            }),
             Expr::Call {
                 callee, args, span, ..
             } => {
                 // Special handling for spawn - it takes a function name, not a value
                 if callee == "spawn" {
-                    if args.len() != 1 {
-                        return Err(
-                            self.error("spawn takes exactly 1 argument (function name)", span)
-                        );
-                    }
+                    let Some(first) = args.first() else {
+                        return Err(self.error(
+                            "spawn takes a function name followed by its arguments",
+                            span,
+                        ));
+                    };
 
                     // Check if the argument is an identifier referring to a function
                     if let Expr::Ident {
                         name,
                         span: arg_span,
                         ..
-                    } = &args[0]
+                    } = first
                     {
                         if let Some(&func_index) = self.functions.get(name) {
-                            return Ok(ResolvedExpr::SpawnFunc { func_index });
+                            self.check_arity(name, func_index, args.len() - 1, *arg_span)?;
+                            let args = args
+                                .into_iter()
+                                .skip(1)
+                                .map(|a| self.resolve_expr(a, scope))
+                                .collect::<Result<_, _>>()?;
+                            return Ok(ResolvedExpr::SpawnFunc { func_index, args });
                         } else {
                             return Err(self.error(
                                 &format!("spawn: '{}' is not a function", name),
@@ -1842,10 +2146,62 @@ impl<'a> Resolver<'a> {
                     }
                 }
 
-                let resolved_args: Vec<_> = args
+                // spawn { ... }: lift the block into its own function
+                if callee == "__spawn_block" {
+                    let Some(Expr::Lambda { body, .. }) = args.into_iter().next() else {
+                        return Err(self.error("spawn expects a block", span));
+                    };
+                    return self.resolve_spawn_block(body, span, scope);
+                }
+
+                // type_of: each generic instantiation has its own tag, e.g.
+                // `Box<int>` and `Box<string>`, matching the dyn type descriptors
+                if callee == "type_of" && args.len() == 1 {
+                    let arg = args.into_iter().next().expect("type_of has one argument");
+                    let tag_name = match arg.inferred_type() {
+                        Some(Type::Dyn) => None,
+                        Some(ty) => Some(type_to_dyn_tag_name(
+                            &self.enrich_type_with_struct_fields(ty),
+                        )),
+                        None => Some("any".to_string()),
+                    };
+                    let expr = self.resolve_expr(arg, scope)?;
+                    return Ok(ResolvedExpr::TypeOf {
+                        expr: Box::new(expr),
+                        tag_name,
+                    });
+                }
+
+                // __deep_eq: how many array layers to compare by content
+                // comes from the (monomorphised) operand type
+                let deep_eq_depth = (callee == "__deep_eq")
+                    .then(|| args.first().and_then(|a| a.inferred_type()))
+                    .flatten()
+                    .map(Type::array_depth);
+
+                let mut resolved_args: Vec<_> = args
                     .into_iter()
                     .map(|a| self.resolve_expr(a, scope))
                     .collect::<Result<_, _>>()?;
+                if let Some(depth) = deep_eq_depth {
+                    resolved_args.push(ResolvedExpr::Int(depth as i64));
+                }
+
+                // A constant allocation size is folded, so codegen sees a
+                // static size, and a negative one is rejected here rather
+                // than failing at runtime
+                if callee == "__alloc_heap"
+                    && let [size] = resolved_args.as_mut_slice()
+                    && let Some(n) = size.const_int()
+                {
+                    if n < 0 {
+                        return Err(self.error(
+                            &format!("__alloc_heap size must not be negative, got {}", n),
+                            span,
+                        ));
+                    }
+                    *size = ResolvedExpr::Int(n);
+                }
 
                 // Check if it's a builtin
                 if self.builtins.contains(&callee) {
@@ -1858,9 +2214,11 @@ impl<'a> Resolver<'a> {
 
                 // Check if it's a user-defined function
                 if let Some(&func_index) = self.functions.get(&callee) {
+                    self.check_arity(&callee, func_index, resolved_args.len(), span)?;
                     return Ok(ResolvedExpr::Call {
                         func_index,
                         args: resolved_args,
+                        span,
                     });
                 }
 
@@ -1870,6 +2228,7 @@ impl<'a> Resolver<'a> {
                     return Ok(ResolvedExpr::CallIndirect {
                         callee: Box::new(ResolvedExpr::CaptureLoad { offset, is_ref }),
                         args: resolved_args,
+                        span,
                     });
                 }
 
@@ -1878,6 +2237,7 @@ impl<'a> Resolver<'a> {
                     return Ok(ResolvedExpr::CallIndirect {
                         callee: Box::new(ResolvedExpr::Local(slot)),
                         args: resolved_args,
+                        span,
                     });
                 }
 
@@ -1994,7 +2354,7 @@ impl<'a> Resolver<'a> {
 
                 // Handle Type::Param — method calls on generic type parameters.
                 // The generic function body is never executed directly (only
-                // monomorphised versions are), so use a placeholder func_index.
+                // monomorphised versions are), so there is nothing to call.
                 if matches!(object_type.as_ref(), Some(Type::Param { .. })) {
                     let resolved_object = self.resolve_expr(*object, scope)?;
                     let resolved_args: Vec<_> = args
@@ -2005,9 +2365,10 @@ impl<'a> Resolver<'a> {
                     return Ok(ResolvedExpr::MethodCall {
                         object: Box::new(resolved_object),
                         method,
-                        func_index: 0, // placeholder — never executed
+                        func_index: GENERIC_METHOD,
                         args: resolved_args,
                         return_struct_name: None,
+                        span,
                     });
                 }
 
@@ -2040,6 +2401,7 @@ impl<'a> Resolver<'a> {
                         func_index,
                         args: resolved_args,
                         return_struct_name: None,
+                        span,
                     });
                 }
 
@@ -2097,6 +2459,7 @@ impl<'a> Resolver<'a> {
                     func_index,
                     args: resolved_args,
                     return_struct_name,
+                    span,
                 })
             }
             Expr::AssociatedFunctionCall {
@@ -2126,6 +2489,7 @@ impl<'a> Resolver<'a> {
                         func_index,
                         args: resolved_args,
                         return_struct_name,
+                        span,
                     });
                 }
 
@@ -2137,6 +2501,7 @@ impl<'a> Resolver<'a> {
                         func_index,
                         args: resolved_args,
                         return_struct_name: None,
+                        span,
                     });
                 }
 
@@ -2313,7 +2678,10 @@ impl<'a> Resolver<'a> {
                     locals_count: lambda_scope.locals_count,
                     body: resolved_body,
                     local_types,
+                    local_names: lambda_scope.slot_names.clone(),
+                    local_depths: lambda_scope.slot_depths.clone(),
                     is_inline: false,
+                    span,
                 });
 
                 Ok(ResolvedExpr::Closure {
@@ -2322,7 +2690,9 @@ impl<'a> Resolver<'a> {
                 })
             }
 
-            Expr::CallExpr { callee, args, .. } => {
+            Expr::CallExpr {
+                callee, args, span, ..
+            } => {
                 let resolved_callee = self.resolve_expr(*callee, scope)?;
                 let resolved_args: Vec<_> = args
                     .into_iter()
@@ -2332,6 +2702,7 @@ impl<'a> Resolver<'a> {
                 Ok(ResolvedExpr::CallIndirect {
                     callee: Box::new(resolved_callee),
                     args: resolved_args,
+                    span,
                 })
             }
             Expr::StringInterpolation { .. } => {
@@ -2423,6 +2794,7 @@ impl<'a> Resolver<'a> {
                 condition,
                 then_block,
                 else_block,
+                ..
             } => {
                 self.expr_calls_function(condition, target_index)
                     || self.body_calls_function(then_block, target_index)
@@ -2441,10 +2813,10 @@ impl<'a> Resolver<'a> {
                     || self.body_calls_function(body, target_index)
             }
             ResolvedStatement::Break | ResolvedStatement::Continue => false,
-            ResolvedStatement::Return { value } => value
+            ResolvedStatement::Return { value, .. } => value
                 .as_ref()
                 .is_some_and(|v| self.expr_calls_function(v, target_index)),
-            ResolvedStatement::Throw { value } => self.expr_calls_function(value, target_index),
+            ResolvedStatement::Throw { value, .. } => self.expr_calls_function(value, target_index),
             ResolvedStatement::Try {
                 try_block,
                 catch_block,
@@ -2453,7 +2825,7 @@ impl<'a> Resolver<'a> {
                 self.body_calls_function(try_block, target_index)
                     || self.body_calls_function(catch_block, target_index)
             }
-            ResolvedStatement::Expr { expr } => self.expr_calls_function(expr, target_index),
+            ResolvedStatement::Expr { expr, .. } => self.expr_calls_function(expr, target_index),
             ResolvedStatement::RefCellStore { value, .. } => {
                 self.expr_calls_function(value, target_index)
             }
@@ -2474,7 +2846,9 @@ impl<'a> Resolver<'a> {
 
     fn expr_calls_function(&self, expr: &ResolvedExpr, target_index: usize) -> bool {
         match expr {
-            ResolvedExpr::Call { func_index, args } => {
+            ResolvedExpr::Call {
+                func_index, args, ..
+            } => {
                 *func_index == target_index
                     || args
                         .iter()
@@ -2513,6 +2887,15 @@ impl<'a> Resolver<'a> {
                 self.expr_calls_function(left, target_index)
                     || self.expr_calls_function(right, target_index)
             }
+            ResolvedExpr::Conditional {
+                condition,
+                then_expr,
+                else_expr,
+            } => {
+                self.expr_calls_function(condition, target_index)
+                    || self.expr_calls_function(then_expr, target_index)
+                    || self.expr_calls_function(else_expr, target_index)
+            }
             ResolvedExpr::Builtin { args, .. } => args
                 .iter()
                 .any(|a| self.expr_calls_function(a, target_index)),
@@ -2534,6 +2917,27 @@ impl<'a> Resolver<'a> {
         }
     }
 
+    /// Reject a direct call whose argument count doesn't match the callee,
+    /// so it fails at compile time instead of in `Op::Call`.
+    fn check_arity(
+        &self,
+        name: &str,
+        func_index: usize,
+        argc: usize,
+        span: Span,
+    ) -> Result<(), String> {
+        match self.function_arities.get(&func_index) {
+            Some(&arity) if arity != argc => Err(self.error(
+                &format!(
+                    "function '{}' expects {} arguments, got {}",
+                    name, arity, argc
+                ),
+                span,
+            )),
+            _ => Ok(()),
+        }
+    }
+
     fn error(&self, message: &str, span: Span) -> String {
         format!(
             "error: {}\n  --> {}:{}:{}",
@@ -2743,6 +3147,8 @@ struct Scope {
     locals_count: usize,
     /// Maps slot number → variable name (accumulated across all scopes).
     slot_names: Vec<String>,
+    /// Maps slot number → depth of the block scope it was declared in.
+    slot_depths: Vec<usize>,
     /// For lambda scopes: outer variables available for capture.
     /// Maps outer variable name → outer slot index.
     outer_vars: HashMap<String, usize>,
@@ -2769,6 +3175,7 @@ impl Scope {
             locals: vec![HashMap::new()],
             locals_count: 0,
             slot_names: Vec::new(),
+            slot_depths: Vec::new(),
             outer_vars: HashMap::new(),
             captured_vars: Vec::new(),
             capture_heap_offsets: HashMap::new(),
@@ -2784,6 +3191,7 @@ impl Scope {
             locals: vec![HashMap::new()],
             locals_count: 0,
             slot_names: Vec::new(),
+            slot_depths: Vec::new(),
             outer_vars,
             captured_vars: Vec::new(),
             capture_heap_offsets: HashMap::new(),
@@ -2807,6 +3215,7 @@ impl Scope {
         let slot = self.locals_count;
         self.locals_count += 1;
         self.slot_names.push(name.clone());
+        self.slot_depths.push(self.locals.len() - 1);
         self.locals
             .last_mut()
             .unwrap()
@@ -3031,6 +3440,16 @@ fn collect_free_vars_expr(
             collect_free_vars_expr(left, bound, free);
             collect_free_vars_expr(right, bound, free);
         }
+        Expr::Conditional {
+            condition,
+            then_expr,
+            else_expr,
+            ..
+        } => {
+            collect_free_vars_expr(condition, bound, free);
+            collect_free_vars_expr(then_expr, bound, free);
+            collect_free_vars_expr(else_expr, bound, free);
+        }
         Expr::Call { callee, args, .. } => {
             // The callee might be a variable holding a closure (not just a function name)
             if !bound.contains(callee.as_str()) && !free.contains(callee) {
@@ -3158,10 +3577,39 @@ mod tests {
         assert!(result.unwrap_err().contains("cannot assign to constant"));
     }
 
+    #[test]
+    fn test_negative_alloc_size_error() {
+        let err = resolve("let a = __alloc_heap(-1);").unwrap_err();
+        assert!(
+            err.contains("__alloc_heap size must not be negative, got -1"),
+            "{}",
+            err
+        );
+        let err = resolve("const N = 2; let a = __alloc_heap(N - 3);").unwrap_err();
+        assert!(err.contains("got -1"), "{}", err);
+        // Sizes only known at runtime are left to the VM
+        assert!(resolve("fun f(n) { return __alloc_heap(n - 3); }").is_ok());
+    }
+
     #[test]
     fn test_function_resolution() {
         let program = resolve("fun add(a, b) { return a + b; } let r = add(1, 2);").unwrap();
         assert_eq!(program.functions.len(), 1);
         assert_eq!(program.functions[0].name, "add");
     }
+
+    #[test]
+    fn test_call_arity_mismatch() {
+        let result = resolve("fun add(a, b) { return a + b; } add(1);");
+        let err = result.unwrap_err();
+        assert!(err.contains("function 'add' expects 2 arguments, got 1"));
+        assert!(err.contains("test.mc:1:33"));
+    }
+
+    #[test]
+    fn test_spawn_requires_zero_arity() {
+        let result = resolve("fun work(n) { return n; } spawn(work);");
+        assert!(result.unwrap_err().contains("expects 1 arguments, got 0"));
+        assert!(resolve("fun work() { return 1; } spawn(work);").is_ok());
+    }
 }
@@ -239,6 +239,15 @@ pub struct TypeChecker {
     current_type_param_bounds: HashMap<String, Vec<String>>,
     /// Name of the function currently being type-checked (None for top-level)
     current_function_name: Option<String>,
+    /// Declared return type of the function or lambda being checked, which
+    /// every `return` in its body must agree with. None for top-level code
+    /// and unannotated functions, whose return type is inferred from the
+    /// end of the body.
+    current_return_type: Option<Type>,
+    /// Insert `__assert_type` guards where `any` values flow into typed bindings
+    runtime_type_checks: bool,
+    /// Reject `any` values in conditions, which the VM would coerce to bool
+    strict_bool: bool,
 }
 
 impl TypeChecker {
@@ -282,9 +291,74 @@ impl TypeChecker {
             current_type_params: Vec::new(),
             current_type_param_bounds: HashMap::new(),
             current_function_name: None,
+            current_return_type: None,
+            runtime_type_checks: false,
+            strict_bool: false,
         }
     }
 
+    /// Enable runtime type guards at dynamic boundaries (`--runtime-type-checks`).
+    pub fn set_runtime_type_checks(&mut self, enabled: bool) {
+        self.runtime_type_checks = enabled;
+    }
+
+    /// Require conditions to be genuine `bool`s (`--strict-bool`).
+    pub fn set_strict_bool(&mut self, enabled: bool) {
+        self.strict_bool = enabled;
+    }
+
+    /// Check that a condition or logical operand is a `bool`. Under
+    /// `--strict-bool` an `any` value is rejected too, instead of being
+    /// coerced at runtime (0 is false, 1 is true).
+    fn expect_bool(&mut self, ty: &Type, span: Span) {
+        if let Err(e) = self.unify(ty, &Type::Bool, span) {
+            self.errors.push(e);
+        } else if self.strict_bool && matches!(self.substitution.apply(ty), Type::Any) {
+            self.errors.push(TypeError::new(
+                "expected `bool`, found `any` (conditions must be bool with --strict-bool)",
+                span,
+            ));
+        }
+    }
+
+    /// Wrap `expr` in an `__assert_type` guard when an untyped (`any`) value,
+    /// e.g. the result of a dynamic call, is used as a primitive type: bound
+    /// by a typed `let` or assignment, passed as an argument, returned, or
+    /// used as an arithmetic or ordering operand.
+    fn guard_dynamic_value(&self, expr: &mut Expr, value_type: &Type, expected: &Type) {
+        if !self.runtime_type_checks || !matches!(self.substitution.apply(value_type), Type::Any) {
+            return;
+        }
+        let tag = match self.substitution.apply(expected) {
+            Type::Int => 0,
+            Type::Float => 1,
+            Type::Bool => 2,
+            _ => return,
+        };
+        let span = expr.span();
+        let inner = mem::replace(
+            expr,
+            Expr::Nil {
+                span,
+                inferred_type: None,
+            },
+        );
+        *expr = Expr::Call {
+            callee: "__assert_type".to_string(),
+            type_args: Vec::new(),
+            args: vec![
+                inner,
+                Expr::Int {
+                    value: tag,
+                    span,
+                    inferred_type: Some(Type::Int),
+                },
+            ],
+            span,
+            inferred_type: Some(self.substitution.apply(expected)),
+        };
+    }
+
     /// Generate a fresh type variable.
     fn fresh_var(&mut self) -> Type {
         let id = self.next_var_id;
@@ -307,6 +381,7 @@ impl TypeChecker {
                     "nil" => Ok(Type::Nil),
                     "any" => Ok(Type::Any),
                     "dyn" => Ok(Type::Dyn),
+                    "Atomic" => Ok(Type::atomic()),
                     _ => {
                         // Check if it's a type parameter in scope
                         if self.current_type_params.contains(name) {
@@ -361,6 +436,18 @@ impl TypeChecker {
                     return Ok(Type::Ptr(Box::new(elem)));
                 }
 
+                // Handle Promise<T>
+                if name == "Promise" {
+                    if type_args.len() != 1 {
+                        return Err(TypeError::new(
+                            "Promise expects exactly 1 type argument",
+                            span,
+                        ));
+                    }
+                    let result = self.resolve_type_annotation(&type_args[0], span)?;
+                    return Ok(Type::promise(result));
+                }
+
                 // Look up struct definition
                 if let Some(struct_info) = self.structs.get(name).cloned() {
                     // Check type argument count
@@ -855,7 +942,9 @@ impl TypeChecker {
         }
 
         // Infer body type
+        self.current_return_type = fn_def.return_type.as_ref().map(|_| expected_ret.clone());
         let body_type = self.infer_block(&mut fn_def.body, &mut env);
+        self.current_return_type = None;
 
         // Unify return type
         if let Err(e) = self.unify(&body_type, &expected_ret, fn_def.span) {
@@ -1003,6 +1092,16 @@ impl TypeChecker {
                 Self::resolve_expr_types(subst, left);
                 Self::resolve_expr_types(subst, right);
             }
+            Expr::Conditional {
+                condition,
+                then_expr,
+                else_expr,
+                ..
+            } => {
+                Self::resolve_expr_types(subst, condition);
+                Self::resolve_expr_types(subst, then_expr);
+                Self::resolve_expr_types(subst, else_expr);
+            }
             Expr::Unary { operand, .. } => {
                 Self::resolve_expr_types(subst, operand);
             }
@@ -1285,6 +1384,7 @@ impl TypeChecker {
             return_type: Some(TypeAnnotation::Named("string".to_string())),
             body,
             attributes: Vec::new(),
+            doc: None,
             span,
         };
 
@@ -1384,6 +1484,7 @@ impl TypeChecker {
             return_type: None,
             body,
             attributes: Vec::new(),
+            doc: None,
             span,
         };
 
@@ -1595,7 +1696,13 @@ impl TypeChecker {
             }
 
             // Infer body type
+            self.current_return_type = method
+                .return_type
+                .as_ref()
+                .filter(|_| !is_builtin_type || has_self)
+                .map(|_| expected_ret.clone());
             let body_type = self.infer_block(&mut method.body, &mut env);
+            self.current_return_type = None;
 
             // Unify return type
             // Skip type checking for builtin type associated functions
@@ -1646,6 +1753,7 @@ impl TypeChecker {
                             if let Err(e) = self.unify(&init_type, &declared_type, *span) {
                                 self.errors.push(e);
                             }
+                            self.guard_dynamic_value(init, &init_type, &declared_type);
                             env.bind(name.clone(), declared_type);
                         }
                         Err(e) => {
@@ -1667,10 +1775,11 @@ impl TypeChecker {
 
             Statement::Assign { name, value, span } => {
                 let value_type = self.infer_expr(value, env);
-                if let Some(var_type) = env.lookup(name).cloned()
-                    && let Err(e) = self.unify(&value_type, &var_type, *span)
-                {
-                    self.errors.push(e);
+                if let Some(var_type) = env.lookup(name).cloned() {
+                    if let Err(e) = self.unify(&value_type, &var_type, *span) {
+                        self.errors.push(e);
+                    }
+                    self.guard_dynamic_value(value, &value_type, &var_type);
                 }
                 Type::Nil
             }
@@ -1682,9 +1791,7 @@ impl TypeChecker {
                 span,
             } => {
                 let cond_type = self.infer_expr(condition, env);
-                if let Err(e) = self.unify(&cond_type, &Type::Bool, *span) {
-                    self.errors.push(e);
-                }
+                self.expect_bool(&cond_type, *span);
 
                 let then_type = self.infer_block(then_block, env);
 
@@ -1705,9 +1812,7 @@ impl TypeChecker {
                 span,
             } => {
                 let cond_type = self.infer_expr(condition, env);
-                if let Err(e) = self.unify(&cond_type, &Type::Bool, *span) {
-                    self.errors.push(e);
-                }
+                self.expect_bool(&cond_type, *span);
                 self.infer_block(body, env);
                 for stmt in post_body {
                     self.infer_statement(stmt, env);
@@ -1778,12 +1883,24 @@ impl TypeChecker {
                 Type::Nil
             }
 
-            Statement::Return { value, span: _ } => {
-                if let Some(expr) = value {
+            Statement::Return { value, span } => {
+                let value_type = if let Some(expr) = value {
                     self.infer_expr(expr, env)
                 } else {
                     Type::Nil
+                };
+                // Check every return, not only the one ending the body
+                if let Some(expected) = self.current_return_type.clone() {
+                    if let Err(e) = self.unify(&expected, &value_type, *span) {
+                        self.errors.push(e);
+                        // Reported once: the body's final unify sees the expected type
+                        return expected;
+                    }
+                    if let Some(expr) = value {
+                        self.guard_dynamic_value(expr, &value_type, &expected);
+                    }
                 }
+                value_type
             }
 
             Statement::Const { name, init, .. } => {
@@ -2245,14 +2362,36 @@ impl TypeChecker {
                         }
                     }
                     UnaryOp::Not => {
-                        if let Err(e) = self.unify(&operand_type, &Type::Bool, *span) {
-                            self.errors.push(e);
-                        }
+                        self.expect_bool(&operand_type, *span);
                         Type::Bool
                     }
                 }
             }
 
+            Expr::Conditional {
+                condition,
+                then_expr,
+                else_expr,
+                span,
+                ..
+            } => {
+                let cond_type = self.infer_expr(condition, env);
+                self.expect_bool(&cond_type, *span);
+                let then_type = self.infer_expr(then_expr, env);
+                let else_type = self.infer_expr(else_expr, env);
+                if self.unify(&then_type, &else_type, *span).is_err() {
+                    self.errors.push(TypeError::new(
+                        format!(
+                            "branches of `?:` have different types: `{}` and `{}`",
+                            self.substitution.apply(&then_type),
+                            self.substitution.apply(&else_type)
+                        ),
+                        *span,
+                    ));
+                }
+                self.substitution.apply(&then_type)
+            }
+
             Expr::Binary {
                 op,
                 left,
@@ -2263,7 +2402,7 @@ impl TypeChecker {
                 let left_type = self.infer_expr(left, env);
                 let right_type = self.infer_expr(right, env);
 
-                match op {
+                let result_type = match op {
                     // Arithmetic operations: int/float -> int/float
                     BinaryOp::Add => {
                         // + can be int+int, float+float, or string+string
@@ -2315,14 +2454,17 @@ impl TypeChecker {
                     }
 
                     BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => {
-                        // Only numeric types
+                        // Numbers, or strings (lexicographic)
                         let is_int_comparison = self.unify(&left_type, &Type::Int, *span).is_ok()
                             && self.unify(&right_type, &Type::Int, *span).is_ok();
                         let is_float_comparison =
                             self.unify(&left_type, &Type::Float, *span).is_ok()
                                 && self.unify(&right_type, &Type::Float, *span).is_ok();
+                        let is_string_comparison =
+                            self.unify(&left_type, &Type::string(), *span).is_ok()
+                                && self.unify(&right_type, &Type::string(), *span).is_ok();
 
-                        if !is_int_comparison && !is_float_comparison {
+                        if !is_int_comparison && !is_float_comparison && !is_string_comparison {
                             self.errors.push(TypeError::new(
                                 format!("cannot compare `{}` and `{}`", left_type, right_type),
                                 *span,
@@ -2355,15 +2497,25 @@ impl TypeChecker {
 
                     // Logical: bool -> bool
                     BinaryOp::And | BinaryOp::Or => {
-                        if let Err(e) = self.unify(&left_type, &Type::Bool, *span) {
-                            self.errors.push(e);
-                        }
-                        if let Err(e) = self.unify(&right_type, &Type::Bool, *span) {
-                            self.errors.push(e);
-                        }
+                        self.expect_bool(&left_type, *span);
+                        self.expect_bool(&right_type, *span);
                         Type::Bool
                     }
-                }
+                };
+
+                // An `any` operand must have the type the operation settled on
+                let (left_expected, right_expected) = match op {
+                    BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => {
+                        (right_type.clone(), left_type.clone())
+                    }
+                    BinaryOp::Eq | BinaryOp::Ne | BinaryOp::And | BinaryOp::Or => {
+                        (Type::Any, Type::Any)
+                    }
+                    _ => (result_type.clone(), result_type.clone()),
+                };
+                self.guard_dynamic_value(left, &left_type, &left_expected);
+                self.guard_dynamic_value(right, &right_type, &right_expected);
+                result_type
             }
 
             Expr::Call {
@@ -2458,28 +2610,11 @@ impl TypeChecker {
                                 *callee = selected.internal_name.clone();
                             } else {
                                 // Check interface bounds (single overload)
-                                for (i, bounds) in generic_info.type_param_bounds.iter().enumerate()
-                                {
-                                    if bounds.is_empty() {
-                                        continue;
-                                    }
-                                    let concrete_type = &resolved_args[i];
-                                    let type_name = self.type_to_impl_name(concrete_type);
-                                    for bound in bounds {
-                                        if !self
-                                            .interface_impls
-                                            .contains(&(bound.clone(), type_name.clone()))
-                                        {
-                                            self.errors.push(TypeError::new(
-                                                format!(
-                                                    "type `{}` does not implement interface `{}`",
-                                                    concrete_type, bound
-                                                ),
-                                                *span,
-                                            ));
-                                        }
-                                    }
-                                }
+                                self.check_type_param_bounds(
+                                    &generic_info.type_param_bounds,
+                                    &resolved_args,
+                                    *span,
+                                );
                             }
 
                             instantiated
@@ -2541,29 +2676,11 @@ impl TypeChecker {
                                         let selected = &overloads[selected_idx];
                                         *callee = selected.internal_name.clone();
                                     } else {
-                                        for (i, bounds) in
-                                            generic_info.type_param_bounds.iter().enumerate()
-                                        {
-                                            if bounds.is_empty() {
-                                                continue;
-                                            }
-                                            let concrete_type = &resolved_args[i];
-                                            let type_name = self.type_to_impl_name(concrete_type);
-                                            for bound in bounds {
-                                                if !self
-                                                    .interface_impls
-                                                    .contains(&(bound.clone(), type_name.clone()))
-                                                {
-                                                    self.errors.push(TypeError::new(
-                                                        format!(
-                                                            "type `{}` does not implement interface `{}`",
-                                                            concrete_type, bound
-                                                        ),
-                                                        *span,
-                                                    ));
-                                                }
-                                            }
-                                        }
+                                        self.check_type_param_bounds(
+                                            &generic_info.type_param_bounds,
+                                            &resolved_args,
+                                            *span,
+                                        );
                                     }
                                 }
                             }
@@ -3227,9 +3344,10 @@ impl TypeChecker {
                 span: _,
                 ..
             } => {
-                // Block is generated by desugar, which runs after type checking.
-                // This should not be encountered during type checking, but we handle it
-                // for completeness. Type check all statements and return the type of the final expr.
+                // Blocks reach the type checker from compound assignments that bind
+                // their operands to temporaries (`a[f()] += 1`); desugar also builds
+                // them, but after type checking. Type check all statements and return
+                // the type of the final expr.
                 for stmt in statements.iter_mut() {
                     self.infer_statement(stmt, env);
                 }
@@ -3276,6 +3394,10 @@ impl TypeChecker {
                 }
 
                 // Infer body type
+                let outer_return_type = std::mem::replace(
+                    &mut self.current_return_type,
+                    return_type.as_ref().map(|_| expected_ret.clone()),
+                );
                 let body_type = {
                     let mut result_type = Type::Nil;
                     for stmt in &mut body.statements {
@@ -3283,6 +3405,7 @@ impl TypeChecker {
                     }
                     result_type
                 };
+                self.current_return_type = outer_return_type;
 
                 env.exit_scope();
 
@@ -3499,8 +3622,11 @@ impl TypeChecker {
                     inferred_type: Some(Type::Dyn),
                     is_implicit: true,
                 };
-            } else if let Err(e) = self.unify(&arg_type, param_type, arg.span()) {
-                self.errors.push(e);
+            } else {
+                if let Err(e) = self.unify(&arg_type, param_type, arg.span()) {
+                    self.errors.push(e);
+                }
+                self.guard_dynamic_value(arg, &arg_type, param_type);
             }
         }
     }
@@ -3572,6 +3698,131 @@ impl TypeChecker {
                 // The resolved type is now on the argument's inferred_type via infer_expr
                 Some(Type::Int)
             }
+            "slice" => {
+                if args.len() != 3 {
+                    self.errors
+                        .push(TypeError::new("slice expects 3 arguments", span));
+                    return Some(self.fresh_var());
+                }
+                let arr_type = self.infer_expr(&mut args[0], env);
+                let expected = Type::array(self.fresh_var());
+                if let Err(e) = self.unify(&arr_type, &expected, span) {
+                    self.errors.push(e);
+                }
+                for arg in args.iter_mut().skip(1) {
+                    let index_type = self.infer_expr(arg, env);
+                    if let Err(e) = self.unify(&index_type, &Type::Int, span) {
+                        self.errors.push(e);
+                    }
+                }
+                Some(self.substitution.apply(&arr_type))
+            }
+            "to_bytes" | "from_bytes" => {
+                let (param, result) = if name == "to_bytes" {
+                    (Type::string(), Type::array(Type::Byte))
+                } else {
+                    (Type::array(Type::Byte), Type::string())
+                };
+                if args.len() != 1 {
+                    self.errors
+                        .push(TypeError::new(format!("{} expects 1 argument", name), span));
+                    return Some(result);
+                }
+                let arg_type = self.infer_expr(&mut args[0], env);
+                if let Err(e) = self.unify(&arg_type, &param, span) {
+                    self.errors.push(e);
+                }
+                Some(result)
+            }
+            "dict_new" => {
+                if !args.is_empty() {
+                    self.errors
+                        .push(TypeError::new("dict_new expects 0 arguments", span));
+                }
+                Some(Type::dict(self.fresh_var(), self.fresh_var()))
+            }
+            "dict_get" | "dict_set" | "dict_has" | "dict_delete" | "dict_len" | "dict_keys"
+            | "dict_values" | "dict_entries" => {
+                let arity = match name {
+                    "dict_set" => 3,
+                    "dict_get" | "dict_has" | "dict_delete" => 2,
+                    _ => 1,
+                };
+                let key_type = self.fresh_var();
+                let value_type = self.fresh_var();
+                if args.len() != arity {
+                    self.errors.push(TypeError::new(
+                        format!("{} expects {} arguments", name, arity),
+                        span,
+                    ));
+                } else {
+                    let expected = [
+                        Type::dict(key_type.clone(), value_type.clone()),
+                        key_type.clone(),
+                        value_type.clone(),
+                    ];
+                    for (arg, expected) in args.iter_mut().zip(expected.iter()) {
+                        let arg_type = self.infer_expr(arg, env);
+                        if let Err(e) = self.unify(&arg_type, expected, span) {
+                            self.errors.push(e);
+                        }
+                    }
+                }
+                Some(match name {
+                    "dict_get" => self.substitution.apply(&value_type),
+                    "dict_set" => Type::Nil,
+                    "dict_len" => Type::Int,
+                    "dict_keys" => Type::array(self.substitution.apply(&key_type)),
+                    "dict_values" => Type::array(self.substitution.apply(&value_type)),
+                    "dict_entries" => {
+                        let key_type = self.substitution.apply(&key_type);
+                        let value_type = self.substitution.apply(&value_type);
+                        Type::array(Type::GenericStruct {
+                            name: "DictEntry".to_string(),
+                            type_args: vec![key_type.clone(), value_type.clone()],
+                            fields: vec![
+                                ("key".to_string(), key_type),
+                                ("value".to_string(), value_type),
+                            ],
+                        })
+                    }
+                    _ => Type::Bool,
+                })
+            }
+            "atomic_new"
+            | "atomic_load"
+            | "atomic_store"
+            | "atomic_fetch_add"
+            | "atomic_compare_exchange" => {
+                let arity = match name {
+                    "atomic_compare_exchange" => 3,
+                    "atomic_store" | "atomic_fetch_add" => 2,
+                    _ => 1,
+                };
+                if args.len() != arity {
+                    self.errors.push(TypeError::new(
+                        format!("{} expects {} arguments", name, arity),
+                        span,
+                    ));
+                }
+                for (i, arg) in args.iter_mut().enumerate() {
+                    // The atomic itself, then its integer operands
+                    let expected = match (name, i) {
+                        ("atomic_new", _) | (_, 1..) => Type::Int,
+                        _ => Type::atomic(),
+                    };
+                    let arg_type = self.infer_expr(arg, env);
+                    if let Err(e) = self.unify(&arg_type, &expected, span) {
+                        self.errors.push(e);
+                    }
+                }
+                Some(match name {
+                    "atomic_new" => Type::atomic(),
+                    "atomic_store" => Type::Nil,
+                    "atomic_compare_exchange" => Type::Bool,
+                    _ => Type::Int,
+                })
+            }
             "push" => {
                 if args.len() != 2 {
                     self.errors
@@ -3605,18 +3856,92 @@ impl TypeChecker {
                 }
                 Some(self.substitution.apply(&elem_type))
             }
-            "type_of" => {
+            "type_of" | "type_name" => {
                 if args.len() != 1 {
                     self.errors
-                        .push(TypeError::new("type_of expects 1 argument", span));
+                        .push(TypeError::new(format!("{} expects 1 argument", name), span));
                 }
                 for arg in args {
                     self.infer_expr(arg, env);
                 }
                 Some(Type::string())
             }
+            "spawn" => {
+                // The spawned function is called with the remaining arguments,
+                // which must be atomics: nothing else can cross to the new VM
+                let mut arg_types = Vec::new();
+                for arg in args.iter_mut().skip(1) {
+                    let arg_type = self.infer_expr(arg, env);
+                    if self.unify(&arg_type, &Type::atomic(), span).is_err() {
+                        self.errors.push(TypeError::new(
+                            format!(
+                                "spawn can only pass atomics to a thread, got `{}`; send other values through a channel",
+                                arg_type
+                            ),
+                            arg.span(),
+                        ));
+                    }
+                    arg_types.push(arg_type);
+                }
+                if let Some(Expr::Ident { name, span, .. }) = args.first()
+                    && let Some(Type::Function { params, .. }) =
+                        self.functions.get(name.as_str()).cloned()
+                {
+                    if params.len() != arg_types.len() {
+                        self.errors.push(TypeError::new(
+                            format!(
+                                "function `{}` expects {} arguments, got {}",
+                                name,
+                                params.len(),
+                                arg_types.len()
+                            ),
+                            *span,
+                        ));
+                    }
+                    for (param, arg_type) in params.iter().zip(&arg_types) {
+                        if let Err(e) = self.unify(param, arg_type, *span) {
+                            self.errors.push(e);
+                        }
+                    }
+                }
+                if let Some(arg) = args.first_mut() {
+                    self.infer_expr(arg, env);
+                }
+                Some(self.fresh_var())
+            }
+            "__spawn_block" => {
+                // spawn { ... }: the block is a parameterless lambda
+                let ret = match args.first_mut().map(|arg| self.infer_expr(arg, env)) {
+                    Some(Type::Function { ret, .. }) => *ret,
+                    _ => self.fresh_var(),
+                };
+                Some(Type::promise(ret))
+            }
+            "await" => {
+                if args.len() != 1 {
+                    self.errors
+                        .push(TypeError::new("await expects 1 argument (promise)", span));
+                }
+                let result = self.fresh_var();
+                if let Some(arg) = args.first_mut() {
+                    let arg_type = self.infer_expr(arg, env);
+                    if self
+                        .unify(&arg_type, &Type::promise(result.clone()), span)
+                        .is_err()
+                    {
+                        self.errors.push(TypeError::new(
+                            format!(
+                                "await expects a promise from `spawn {{ ... }}`, got `{}`",
+                                self.substitution.apply(&arg_type)
+                            ),
+                            span,
+                        ));
+                    }
+                }
+                Some(result)
+            }
             // Thread operations - for now just return appropriate types
-            "spawn" | "channel" | "send" | "recv" | "join" => {
+            "channel" | "send" | "recv" | "join" => {
                 for arg in args {
                     self.infer_expr(arg, env);
                 }
@@ -3723,6 +4048,45 @@ impl TypeChecker {
                 }
                 Some(Type::Int)
             }
+            "udiv" | "urem" | "sat_add" | "sat_sub" | "sat_mul" | "checked_add" => {
+                if args.len() != 2 {
+                    self.errors.push(TypeError::new(
+                        format!("{} expects 2 arguments", name),
+                        span,
+                    ));
+                }
+                for arg in args {
+                    let arg_type = self.infer_expr(arg, env);
+                    if let Err(e) = self.unify(&arg_type, &Type::Int, span) {
+                        self.errors.push(e);
+                    }
+                }
+                if name == "checked_add" {
+                    Some(Type::nullable(Type::Int))
+                } else {
+                    Some(Type::Int)
+                }
+            }
+            "__deep_eq" => {
+                if args.len() != 2 {
+                    self.errors
+                        .push(TypeError::new("__deep_eq expects 2 arguments", span));
+                }
+                for arg in args {
+                    self.infer_expr(arg, env);
+                }
+                Some(Type::Bool)
+            }
+            "__cmp" => {
+                if args.len() != 2 {
+                    self.errors
+                        .push(TypeError::new("__cmp expects 2 arguments", span));
+                }
+                for arg in args {
+                    self.infer_expr(arg, env);
+                }
+                Some(Type::Int)
+            }
             "__alloc_string" => {
                 if args.len() != 2 {
                     self.errors.push(TypeError::new(
@@ -3735,6 +4099,92 @@ impl TypeChecker {
                 }
                 Some(Type::Any) // Returns a string reference
             }
+            "__string_split" | "__string_join" => {
+                // __string_split(s, sep) -> array<string>
+                // __string_join(parts: array<string>, sep) -> string
+                if args.len() != 2 {
+                    self.errors.push(TypeError::new(
+                        format!("{} expects 2 arguments", name),
+                        span,
+                    ));
+                    return Some(self.fresh_var());
+                }
+                let (first, result) = if name == "__string_split" {
+                    (Type::string(), Type::array(Type::string()))
+                } else {
+                    (Type::array(Type::string()), Type::string())
+                };
+                for (arg, expected) in args.iter_mut().zip([first, Type::string()]) {
+                    let arg_type = self.infer_expr(arg, env);
+                    if let Err(e) = self.unify(&arg_type, &expected, span) {
+                        self.errors.push(e);
+                    }
+                }
+                Some(result)
+            }
+            "__string_substring" | "__string_index_of" | "__string_replace" => {
+                // __string_substring(s, start, end) -> string
+                // __string_index_of(s, needle) -> int
+                // __string_replace(s, from, to) -> string
+                let (params, result) = match name {
+                    "__string_substring" => {
+                        (vec![Type::string(), Type::Int, Type::Int], Type::string())
+                    }
+                    "__string_index_of" => (vec![Type::string(), Type::string()], Type::Int),
+                    _ => (
+                        vec![Type::string(), Type::string(), Type::string()],
+                        Type::string(),
+                    ),
+                };
+                if args.len() != params.len() {
+                    self.errors.push(TypeError::new(
+                        format!("{} expects {} arguments", name, params.len()),
+                        span,
+                    ));
+                    return Some(result);
+                }
+                for (arg, expected) in args.iter_mut().zip(params) {
+                    let arg_type = self.infer_expr(arg, env);
+                    if let Err(e) = self.unify(&arg_type, &expected, span) {
+                        self.errors.push(e);
+                    }
+                }
+                Some(result)
+            }
+            "__sb_append" | "__sb_finish" => {
+                // __sb_append(builder, s), __sb_finish(builder) -> string
+                let arity = if name == "__sb_append" { 2 } else { 1 };
+                if args.len() != arity {
+                    self.errors.push(TypeError::new(
+                        format!("{} expects {} arguments", name, arity),
+                        span,
+                    ));
+                }
+                for (i, arg) in args.iter_mut().enumerate() {
+                    let arg_type = self.infer_expr(arg, env);
+                    if i == 1
+                        && let Err(e) = self.unify(&arg_type, &Type::string(), span)
+                    {
+                        self.errors.push(e);
+                    }
+                }
+                if name == "__sb_append" {
+                    Some(Type::Nil)
+                } else {
+                    Some(Type::string())
+                }
+            }
+            "__assert_type" => {
+                // __assert_type(value, tag) → value's expected type (inserted by the typechecker)
+                for arg in args.iter_mut() {
+                    self.infer_expr(arg, env);
+                }
+                match args.get(1) {
+                    Some(Expr::Int { value: 1, .. }) => Some(Type::Float),
+                    Some(Expr::Int { value: 2, .. }) => Some(Type::Bool),
+                    _ => Some(Type::Int),
+                }
+            }
             "__call_func" => {
                 // __call_func(func_idx, arg) → any
                 // Calls function by dynamic index with one argument
@@ -3749,6 +4199,13 @@ impl TypeChecker {
                 }
                 Some(Type::Any)
             }
+            "breakpoint" => {
+                if !args.is_empty() {
+                    self.errors
+                        .push(TypeError::new("breakpoint expects 0 arguments", span));
+                }
+                Some(Type::Nil)
+            }
             // CLI argument operations
             "argc" => {
                 if !args.is_empty() {
@@ -3873,7 +4330,37 @@ impl TypeChecker {
         }
     }
 
-    /// Convert a concrete Type to the name used in interface_impls lookup.
+    /// Report an error at the call site for each type argument that does not
+    /// implement the interfaces its type parameter is bounded by.
+    fn check_type_param_bounds(&mut self, bounds: &[Vec<String>], type_args: &[Type], span: Span) {
+        for (bounds, type_arg) in bounds.iter().zip(type_args) {
+            for bound in bounds {
+                if !self.satisfies_bound(type_arg, bound) {
+                    self.errors.push(TypeError::new(
+                        format!(
+                            "type `{}` does not implement interface `{}`",
+                            type_arg, bound
+                        ),
+                        span,
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Whether `ty` implements `interface`. A type parameter of the function
+    /// being checked does when its own bounds include the interface.
+    fn satisfies_bound(&self, ty: &Type, interface: &str) -> bool {
+        if let Type::Param { name } = ty {
+            return self
+                .current_type_param_bounds
+                .get(name)
+                .is_some_and(|bounds| bounds.iter().any(|b| b == interface));
+        }
+        self.interface_impls
+            .contains(&(interface.to_string(), self.type_to_impl_name(ty)))
+    }
+
     /// Resolve the best overload for a function call based on concrete type arguments.
     /// Returns the index of the overload whose bounds are all satisfied and has the
     /// highest specificity (most total bounds). Falls back to an unbounded overload
@@ -3895,12 +4382,8 @@ impl TypeChecker {
                     break;
                 }
                 let concrete_type = &resolved_args[j];
-                let type_name = self.type_to_impl_name(concrete_type);
                 for bound in bounds {
-                    if self
-                        .interface_impls
-                        .contains(&(bound.clone(), type_name.clone()))
-                    {
+                    if self.satisfies_bound(concrete_type, bound) {
                         score += 1;
                     } else {
                         all_satisfied = false;
@@ -4020,6 +4503,27 @@ mod tests {
         assert!(check("fun add(a, b) { return a + b; } let r = add(1, 2);").is_ok());
     }
 
+    #[test]
+    fn test_every_return_matches_declared_type() {
+        assert!(check("fun f(x: int) -> int { if x > 0 { return 1; } return 2; }").is_ok());
+        let errors =
+            check("fun f(x: int) -> int { if x > 0 { return 1.5; } return 2; }").unwrap_err();
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(errors[0].message.contains("expected `int`, found `float`"));
+
+        // Methods and lambdas too
+        assert!(
+            check("struct P { x: int } impl P { fun get(self) -> int { if true { return 0.5; } return self.x; } }")
+                .is_err()
+        );
+        assert!(
+            check("let f = fun(x: int) -> int { if x > 0 { return true; } return x; };").is_err()
+        );
+        assert!(check("let f = fun(x: int) -> int { if x > 0 { return 0; } return x; };").is_ok());
+        // Unannotated functions keep inferring from the end of the body
+        assert!(check("fun g(x: int) { if x > 0 { return; } print(x); }").is_ok());
+    }
+
     #[test]
     fn test_function_with_types() {
         assert!(check("fun add(a: int, b: int) -> int { return a + b; }").is_ok());
@@ -4058,6 +4562,28 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_call_arity_mismatch() {
+        let errors =
+            check("fun f(a: int, b: int) -> int { return a + b; }\nlet r = f(1);").unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert!(
+            errors[0]
+                .message
+                .contains("function `f` expects 2 arguments, got 1")
+        );
+        assert_eq!((errors[0].span.line, errors[0].span.column), (2, 9));
+
+        assert!(check("fun f(a: int, b: int) -> int { return a + b; }\nlet r = f(1, 2);").is_ok());
+    }
+
+    #[test]
+    fn test_spawn_arity_mismatch() {
+        let result = check("fun work(n: int) -> int { return n; }\nlet h = spawn(work);");
+        assert!(result.is_err());
+        assert!(check("fun work() -> int { return 1; }\nlet h = spawn(work);").is_ok());
+    }
+
     #[test]
     fn test_ac8_array_type_inferred() {
         // AC8: `[1, 2, 3]` has type `array<int>`
@@ -4087,6 +4613,22 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_strict_bool_rejects_any_condition() {
+        let source = "let x: any = 5; if x { let y = 1; }";
+        // Default: the VM coerces the integer at runtime
+        assert!(check(source).is_ok());
+
+        let mut lexer = Lexer::new("test.mc", source);
+        let mut parser = Parser::new("test.mc", lexer.scan_tokens().unwrap());
+        let mut program = prepend_stdlib(parser.parse().unwrap()).unwrap();
+        let mut checker = TypeChecker::new("test.mc");
+        checker.set_strict_bool(true);
+        let errors = checker.check_program(&mut program).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].message.contains("--strict-bool"));
+    }
+
     #[test]
     fn test_while_condition_must_be_bool() {
         assert!(check("while false { let x = 1; }").is_ok());
@@ -101,11 +101,51 @@ impl Type {
         }
     }
 
+    /// Create a promise type: the result of a `spawn { ... }` block.
+    pub fn promise(result: Type) -> Type {
+        Type::GenericStruct {
+            name: "Promise".to_string(),
+            type_args: vec![result],
+            fields: vec![],
+        }
+    }
+
+    /// Create the type of an atomic integer (see `atomic_new`).
+    pub fn atomic() -> Type {
+        Type::GenericStruct {
+            name: "Atomic".to_string(),
+            type_args: vec![],
+            fields: vec![],
+        }
+    }
+
+    /// Create a new native dictionary type (see `dict_new`).
+    pub fn dict(key: Type, value: Type) -> Type {
+        Type::GenericStruct {
+            name: "Dict".to_string(),
+            type_args: vec![key, value],
+            fields: vec![],
+        }
+    }
+
     /// Check if this type is an Array.
     pub fn is_array(&self) -> bool {
         matches!(self, Type::GenericStruct { name, .. } if name == "Array")
     }
 
+    /// Number of nested `Array` layers, looking through `?`: 1 for strings and
+    /// `array<int>`, 2 for `array<string>`. `==` compares that many layers by
+    /// content (see `Op::DeepEq`).
+    pub fn array_depth(&self) -> usize {
+        match self {
+            Type::Nullable(inner) => inner.array_depth(),
+            Type::GenericStruct {
+                name, type_args, ..
+            } if name == "Array" && type_args.len() == 1 => 1 + type_args[0].array_depth(),
+            _ => 0,
+        }
+    }
+
     /// Check if this type is a Vec.
     pub fn is_vec(&self) -> bool {
         matches!(self, Type::GenericStruct { name, .. } if name == "Vec")
@@ -390,6 +430,9 @@ impl fmt::Display for Type {
                     "Map" => "map",
                     _ => name.as_str(),
                 };
+                if type_args.is_empty() {
+                    return write!(f, "{}", display_name);
+                }
                 write!(f, "{}<", display_name)?;
                 for (i, arg) in type_args.iter().enumerate() {
                     if i > 0 {
@@ -0,0 +1,156 @@
+//! File watching for `moca run --watch`.
+//!
+//! Changes are detected by polling modification times, so no platform
+//! notification API is needed. A file that disappears is assumed to be in
+//! the middle of an editor's atomic save (write temp file, delete, rename)
+//! and is waited for rather than reported as a change.
+
+use super::ModuleLoader;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Default interval between polls.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// A source of "something changed" notifications.
+pub trait ChangeSource {
+    /// Block until one of `files` changes. Returns false to stop watching.
+    fn wait_for_change(&mut self, files: &[PathBuf]) -> bool;
+}
+
+/// Modification time and length, compared to spot edits.
+type Stamp = (SystemTime, u64);
+
+fn stamp(path: &Path) -> Option<Stamp> {
+    let meta = std::fs::metadata(path).ok()?;
+    Some((meta.modified().ok()?, meta.len()))
+}
+
+/// Detects changes by polling file metadata.
+pub struct MtimePoller {
+    interval: Duration,
+    stamps: HashMap<PathBuf, Stamp>,
+}
+
+impl MtimePoller {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            stamps: HashMap::new(),
+        }
+    }
+
+    /// Record the current state of `files` as the baseline.
+    pub fn snapshot(&mut self, files: &[PathBuf]) {
+        self.stamps = files
+            .iter()
+            .filter_map(|f| stamp(f).map(|s| (f.clone(), s)))
+            .collect();
+    }
+
+    /// Whether any of `files` differs from the baseline. Missing files are
+    /// not a change; they are compared again once they reappear.
+    pub fn changed(&self, files: &[PathBuf]) -> bool {
+        files.iter().any(|f| match stamp(f) {
+            Some(current) => self.stamps.get(f) != Some(&current),
+            None => false,
+        })
+    }
+}
+
+impl ChangeSource for MtimePoller {
+    fn wait_for_change(&mut self, files: &[PathBuf]) -> bool {
+        self.snapshot(files);
+        while !self.changed(files) {
+            std::thread::sleep(self.interval);
+        }
+        true
+    }
+}
+
+/// The files a run of `main_path` depends on: the file itself plus every
+/// module it transitively imports. Imports of a file that doesn't parse
+/// can't be discovered, so only the file itself is returned then.
+pub fn watched_files(main_path: &Path) -> Vec<PathBuf> {
+    let root_dir = main_path.parent().unwrap_or(Path::new(".")).to_path_buf();
+    let mut loader = ModuleLoader::new(root_dir);
+    let _ = loader.load_with_imports(main_path);
+    let mut files = vec![main_path.to_path_buf()];
+    files.extend(loader.loaded_paths());
+    files
+}
+
+/// Run `run` once, then again every time `source` reports a change to the
+/// files `main_path` depends on, until `source` stops.
+pub fn watch(main_path: &Path, source: &mut impl ChangeSource, mut run: impl FnMut()) {
+    loop {
+        run();
+        let files = watched_files(main_path);
+        if !source.wait_for_change(&files) {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// Reports a fixed number of changes, recording what it was asked to watch.
+    struct FakeSource {
+        remaining: usize,
+        seen: Vec<Vec<PathBuf>>,
+    }
+
+    impl ChangeSource for FakeSource {
+        fn wait_for_change(&mut self, files: &[PathBuf]) -> bool {
+            self.seen.push(files.to_vec());
+            if self.remaining == 0 {
+                return false;
+            }
+            self.remaining -= 1;
+            true
+        }
+    }
+
+    #[test]
+    fn test_change_triggers_rerun() {
+        let dir = tempfile::tempdir().unwrap();
+        let main = dir.path().join("main.mc");
+        fs::write(&main, "import .util;\nprint(helper());\n").unwrap();
+        fs::write(dir.path().join("util.mc"), "fun helper() { return 1; }\n").unwrap();
+
+        let mut source = FakeSource {
+            remaining: 1,
+            seen: Vec::new(),
+        };
+        let mut runs = 0;
+        watch(&main, &mut source, || runs += 1);
+
+        assert_eq!(runs, 2);
+        assert_eq!(source.seen.len(), 2);
+        assert!(source.seen[0].iter().any(|f| f.ends_with("util.mc")));
+    }
+
+    #[test]
+    fn test_poller_survives_atomic_save() {
+        let dir = tempfile::tempdir().unwrap();
+        let main = dir.path().join("main.mc");
+        fs::write(&main, "print(1);\n").unwrap();
+        let files = vec![main.clone()];
+
+        let mut poller = MtimePoller::new(POLL_INTERVAL);
+        poller.snapshot(&files);
+        assert!(!poller.changed(&files));
+
+        // Deleted mid-save: keep waiting instead of re-running
+        fs::remove_file(&main).unwrap();
+        assert!(!poller.changed(&files));
+
+        // Recreated with new contents
+        fs::write(&main, "print(12);\n").unwrap();
+        assert!(poller.changed(&files));
+    }
+}
@@ -1,5 +1,7 @@
 //! Runtime configuration types.
 
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
 use std::time::Duration;
 
 /// Format for timing output
@@ -48,33 +50,61 @@ impl CompilerTimings {
         }
     }
 
-    /// Output timings in human-readable table format to stderr
-    pub fn print_human(&self) {
-        eprintln!("=== Compiler Timings ===");
-        eprintln!("lexer:         {:>10}", Self::format_duration(self.lexer));
-        eprintln!("parser:        {:>10}", Self::format_duration(self.parser));
-        eprintln!(
+    /// Write timings as a human-readable table
+    fn write_human(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "=== Compiler Timings ===")?;
+        writeln!(
+            out,
+            "lexer:         {:>10}",
+            Self::format_duration(self.lexer)
+        )?;
+        writeln!(
+            out,
+            "parser:        {:>10}",
+            Self::format_duration(self.parser)
+        )?;
+        writeln!(
+            out,
             "typecheck:     {:>10}",
             Self::format_duration(self.typecheck)
-        );
-        eprintln!("desugar:       {:>10}", Self::format_duration(self.desugar));
-        eprintln!(
+        )?;
+        writeln!(
+            out,
+            "desugar:       {:>10}",
+            Self::format_duration(self.desugar)
+        )?;
+        writeln!(
+            out,
             "monomorphise:  {:>10}",
             Self::format_duration(self.monomorphise)
-        );
-        eprintln!("resolve:       {:>10}", Self::format_duration(self.resolve));
-        eprintln!("codegen:       {:>10}", Self::format_duration(self.codegen));
-        eprintln!(
+        )?;
+        writeln!(
+            out,
+            "resolve:       {:>10}",
+            Self::format_duration(self.resolve)
+        )?;
+        writeln!(
+            out,
+            "codegen:       {:>10}",
+            Self::format_duration(self.codegen)
+        )?;
+        writeln!(
+            out,
             "execution:     {:>10}",
             Self::format_duration(self.execution)
-        );
-        eprintln!("------------------------");
-        eprintln!("total:         {:>10}", Self::format_duration(self.total()));
+        )?;
+        writeln!(out, "------------------------")?;
+        writeln!(
+            out,
+            "total:         {:>10}",
+            Self::format_duration(self.total())
+        )?;
+        Ok(())
     }
 
-    /// Output timings in JSON format to stderr
-    pub fn print_json(&self) {
-        let json = format!(
+    /// Render timings as a single-line JSON object of milliseconds per phase
+    pub fn to_json(&self) -> String {
+        format!(
             r#"{{"lexer_ms":{:.2},"parser_ms":{:.2},"typecheck_ms":{:.2},"desugar_ms":{:.2},"monomorphise_ms":{:.2},"resolve_ms":{:.2},"codegen_ms":{:.2},"execution_ms":{:.2},"total_ms":{:.2}}}"#,
             self.lexer.as_secs_f64() * 1000.0,
             self.parser.as_secs_f64() * 1000.0,
@@ -85,33 +115,55 @@ impl CompilerTimings {
             self.codegen.as_secs_f64() * 1000.0,
             self.execution.as_secs_f64() * 1000.0,
             self.total().as_secs_f64() * 1000.0,
-        );
-        eprintln!("{}", json);
+        )
     }
 
-    /// Print timings based on format
-    pub fn print(&self, format: TimingsFormat) {
+    /// Write timings to `out` in the given format
+    pub fn write(&self, format: TimingsFormat, out: &mut dyn Write) -> io::Result<()> {
         match format {
-            TimingsFormat::Human => self.print_human(),
-            TimingsFormat::Json => self.print_json(),
+            TimingsFormat::Human => self.write_human(out),
+            TimingsFormat::Json => writeln!(out, "{}", self.to_json()),
         }
     }
+
+    /// Print timings to stderr based on format
+    pub fn print(&self, format: TimingsFormat) {
+        let _ = self.write(format, &mut io::stderr());
+    }
 }
 
 /// JIT compilation mode
-#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum JitMode {
     /// JIT disabled, interpreter only
     Off,
     /// JIT enabled (compile hot functions)
     On,
-    /// Automatic: JIT enabled if supported on this platform
+    /// Automatic: JIT enabled if supported on this platform and the program
+    /// has loops, recursion or large functions (see `VM::set_jit_auto`)
     #[default]
     Auto,
 }
 
+impl JitMode {
+    /// Whether the VM should JIT-compile hot code in this mode. `Auto`
+    /// enables it only when this build has a JIT backend for the host.
+    pub fn is_enabled(self) -> bool {
+        match self {
+            JitMode::Off => false,
+            JitMode::On => true,
+            JitMode::Auto => cfg!(all(
+                any(target_arch = "x86_64", target_arch = "aarch64"),
+                feature = "jit"
+            )),
+        }
+    }
+}
+
 /// GC mode
-#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum GcMode {
     /// Stop-the-world GC
     #[default]
@@ -121,20 +173,65 @@ pub enum GcMode {
 }
 
 /// Runtime configuration for the VM
-#[derive(Debug, Clone)]
+///
+/// Also read from the `[runtime]` table of pkg.toml; fields left out of the
+/// table keep their defaults, and per-invocation diagnostics are not read.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct RuntimeConfig {
+    #[serde(rename = "jit")]
     pub jit_mode: JitMode,
     pub jit_threshold: u32,
+    /// Total executable memory JIT-compiled code may use, in bytes; hot code
+    /// beyond it stays interpreted (`--jit-code-budget`)
+    pub jit_code_budget: usize,
+    #[serde(skip)]
     pub trace_jit: bool,
     #[allow(dead_code)] // Reserved for future GC configuration
+    #[serde(rename = "gc")]
     pub gc_mode: GcMode,
+    #[serde(skip)]
     pub gc_stats: bool,
     /// Whether GC is enabled (default: true)
     pub gc_enabled: bool,
     /// Hard limit on heap size in bytes (None = unlimited)
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub heap_limit: Option<usize>,
     /// Whether to profile opcode execution counts
+    #[serde(skip)]
     pub profile_opcodes: bool,
+    /// Whether to trace function calls and returns to stderr
+    #[serde(skip)]
+    pub trace_calls: bool,
+    /// Whether to record executed lines for `moca test --coverage`
+    #[serde(skip)]
+    pub coverage: bool,
+    /// Whether to guard `any` values used as `int`/`float`/`bool` (bindings,
+    /// arguments, returns, operands) with `AssertType`
+    pub runtime_type_checks: bool,
+    /// Whether conditions must be `bool` at compile time, rejecting `any`
+    /// values the VM would coerce (`--strict-bool`)
+    pub strict_bool: bool,
+    /// Whether to print the value of the last top-level expression after running
+    #[serde(skip)]
+    pub eval_print: bool,
+    /// Whether uncaught errors print a backtrace (`--backtrace` or `MOCA_BACKTRACE`)
+    pub backtrace: bool,
+    /// Whether uncaught errors print a heap summary and the innermost frames
+    /// (`--print-heap-on-error`)
+    pub print_heap_on_error: bool,
+    /// Whether codegen frees string concatenation intermediates eagerly (`--free-temps`)
+    #[serde(rename = "free_temps")]
+    pub free_temporaries: bool,
+    /// Maximum number of values on the operand stack before a push fails with
+    /// "runtime error: operand stack overflow"
+    pub max_stack_size: usize,
+    /// Whether spawned threads run on a fixed, reproducible schedule
+    /// (`--deterministic-threads`)
+    pub deterministic_threads: bool,
+    /// Maximum number of OS threads running spawned work at once; further
+    /// spawns are queued (`--max-threads`)
+    pub max_threads: usize,
 }
 
 impl Default for RuntimeConfig {
@@ -142,12 +239,166 @@ impl Default for RuntimeConfig {
         Self {
             jit_mode: JitMode::Auto,
             jit_threshold: 1000,
+            jit_code_budget: crate::vm::DEFAULT_JIT_CODE_BUDGET,
             trace_jit: false,
             gc_mode: GcMode::Stw,
             gc_stats: false,
             gc_enabled: true,
             heap_limit: None,
             profile_opcodes: false,
+            trace_calls: false,
+            coverage: false,
+            runtime_type_checks: false,
+            strict_bool: false,
+            eval_print: false,
+            backtrace: false,
+            print_heap_on_error: false,
+            free_temporaries: false,
+            max_stack_size: crate::vm::DEFAULT_MAX_STACK_SIZE,
+            deterministic_threads: false,
+            max_threads: crate::vm::DEFAULT_MAX_THREADS,
+        }
+    }
+}
+
+impl RuntimeConfig {
+    /// Reject settings that are zero or contradict each other.
+    ///
+    /// `jit_threshold` counts calls before a function is compiled, so it must
+    /// be at least 1; a threshold of 0 would never be reached rather than
+    /// meaning "always", and the JIT is disabled with `jit = "off"` instead.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.jit_threshold == 0 {
+            return Err(
+                "invalid runtime config: jit_threshold must be at least 1 (use --jit=off to disable the JIT)"
+                    .to_string(),
+            );
+        }
+        if self.jit_mode != JitMode::Off && self.jit_code_budget == 0 {
+            return Err(format!(
+                "invalid runtime config: jit_code_budget is 0 but the JIT is {}; raise the budget or use --jit=off",
+                match self.jit_mode {
+                    JitMode::On => "on",
+                    _ => "auto",
+                }
+            ));
         }
+        if self.heap_limit == Some(0) {
+            return Err(
+                "invalid runtime config: heap_limit must be greater than 0 (omit it for no limit)"
+                    .to_string(),
+            );
+        }
+        if self.max_stack_size == 0 {
+            return Err(
+                "invalid runtime config: max_stack_size must be greater than 0".to_string(),
+            );
+        }
+        if self.max_threads == 0 {
+            return Err("invalid runtime config: max_threads must be greater than 0".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timings_json_has_every_phase() {
+        let timings = CompilerTimings {
+            lexer: Duration::from_millis(1),
+            parser: Duration::from_millis(2),
+            typecheck: Duration::from_millis(3),
+            desugar: Duration::from_millis(4),
+            monomorphise: Duration::from_millis(5),
+            resolve: Duration::from_millis(6),
+            codegen: Duration::from_millis(7),
+            execution: Duration::from_millis(8),
+        };
+        let json: serde_json::Value = serde_json::from_str(&timings.to_json()).unwrap();
+        let phases = [
+            ("lexer_ms", 1.0),
+            ("parser_ms", 2.0),
+            ("typecheck_ms", 3.0),
+            ("desugar_ms", 4.0),
+            ("monomorphise_ms", 5.0),
+            ("resolve_ms", 6.0),
+            ("codegen_ms", 7.0),
+            ("execution_ms", 8.0),
+            ("total_ms", 36.0),
+        ];
+        for (key, ms) in phases {
+            assert_eq!(json[key].as_f64(), Some(ms), "{key}");
+        }
+    }
+
+    #[test]
+    fn test_default_runtime_config_is_valid() {
+        assert_eq!(RuntimeConfig::default().validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_rejects_invalid_runtime_config() {
+        let cases = [
+            (
+                RuntimeConfig {
+                    jit_threshold: 0,
+                    ..Default::default()
+                },
+                "jit_threshold must be at least 1",
+            ),
+            (
+                RuntimeConfig {
+                    jit_mode: JitMode::On,
+                    jit_code_budget: 0,
+                    ..Default::default()
+                },
+                "jit_code_budget is 0 but the JIT is on",
+            ),
+            (
+                RuntimeConfig {
+                    jit_code_budget: 0,
+                    ..Default::default()
+                },
+                "jit_code_budget is 0 but the JIT is auto",
+            ),
+            (
+                RuntimeConfig {
+                    heap_limit: Some(0),
+                    ..Default::default()
+                },
+                "heap_limit must be greater than 0",
+            ),
+            (
+                RuntimeConfig {
+                    max_stack_size: 0,
+                    ..Default::default()
+                },
+                "max_stack_size must be greater than 0",
+            ),
+            (
+                RuntimeConfig {
+                    max_threads: 0,
+                    ..Default::default()
+                },
+                "max_threads must be greater than 0",
+            ),
+        ];
+        for (config, expected) in cases {
+            let err = config.validate().unwrap_err();
+            assert!(err.contains(expected), "{err}");
+        }
+    }
+
+    #[test]
+    fn test_validate_allows_zero_code_budget_with_jit_off() {
+        let config = RuntimeConfig {
+            jit_mode: JitMode::Off,
+            jit_code_budget: 0,
+            ..Default::default()
+        };
+        assert_eq!(config.validate(), Ok(()));
     }
 }
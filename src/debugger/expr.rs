@@ -0,0 +1,527 @@
+//! Small expression language for debugger conditions and `eval`.
+//!
+//! This is not moca's expression syntax: it is a fixed subset evaluated
+//! directly against the paused frame, without the compiler. Expressions are
+//! compiled once (e.g. when a conditional breakpoint is set) and evaluated
+//! against the frame's locals each time they are checked. Evaluation is
+//! read-only: there are no assignments or calls.
+//!
+//! Supported syntax:
+//! - literals: integers, floats, `true`, `false`, `nil`
+//! - local slots: `$N` (slot index)
+//! - named locals: resolved to slots by a caller-supplied lookup
+//! - element reads: `expr[i]` on an array, string or vec (bounds-checked
+//!   against its length, like moca's own indexing)
+//! - unary `-` and `!`
+//! - binary `* / %`, `+ -`, `< <= > >=`, `== !=`, `&&`, `||`
+//!
+//! Other moca syntax (calls, field access, string literals, assignments,
+//! keywords, ...) is rejected with an error naming the construct.
+
+use crate::vm::{Heap, Value};
+
+/// Everything an expression can read from a paused frame.
+pub struct EvalContext<'a> {
+    pub locals: &'a [Value],
+    pub heap: &'a Heap,
+    /// Maps a variable name to its local slot, if known
+    pub resolve: &'a dyn Fn(&str) -> Option<usize>,
+}
+
+/// A compiled debugger expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DebugExpr {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Nil,
+    Slot(usize),
+    Name(String),
+    Index(Box<DebugExpr>, Box<DebugExpr>),
+    Unary(UnaryOp, Box<DebugExpr>),
+    Binary(BinaryOp, Box<DebugExpr>, Box<DebugExpr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+}
+
+impl BinaryOp {
+    /// Binding power for precedence climbing (higher binds tighter).
+    fn precedence(self) -> u8 {
+        match self {
+            BinaryOp::Or => 1,
+            BinaryOp::And => 2,
+            BinaryOp::Eq | BinaryOp::Ne => 3,
+            BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => 4,
+            BinaryOp::Add | BinaryOp::Sub => 5,
+            BinaryOp::Mul | BinaryOp::Div | BinaryOp::Mod => 6,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Int(i64),
+    Float(f64),
+    Ident(String),
+    Slot(usize),
+    Op(&'static str),
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    /// moca syntax the subset doesn't accept, with the error to report
+    Unsupported(String),
+}
+
+/// Also `,`, which nothing accepts, so calls and lists are reported by the
+/// parser rather than as a stray character.
+const OPERATORS: [&str; 19] = [
+    "==", "!=", "<=", ">=", "&&", "||", "+", "-", "*", "/", "%", "<", ">", "!", "(", ")", "[", "]",
+    ",",
+];
+
+fn tokenize(src: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = src.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            if text.contains('.') {
+                let v = text
+                    .parse::<f64>()
+                    .map_err(|_| format!("invalid number '{}'", text))?;
+                tokens.push(Token::Float(v));
+            } else {
+                let v = text
+                    .parse::<i64>()
+                    .map_err(|_| format!("invalid number '{}'", text))?;
+                tokens.push(Token::Int(v));
+            }
+        } else if c == '$' {
+            let start = i + 1;
+            i = start;
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            let slot = text
+                .parse::<usize>()
+                .map_err(|_| "expected slot number after '$'".to_string())?;
+            tokens.push(Token::Slot(slot));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Ident(chars[start..i].iter().collect()));
+        } else {
+            let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+            let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(**op)) else {
+                // moca syntax outside the subset is reported by the parser,
+                // so the first unsupported construct is the one named
+                let what = match c {
+                    '"' | '\'' => "string and char literals",
+                    '.' => "field access and method calls (read raw slots with `e[i]`)",
+                    '=' => "assignments",
+                    '{' | '}' => "blocks and struct literals",
+                    _ => return Err(format!("unexpected character '{}'", c)),
+                };
+                tokens.push(Token::Unsupported(unsupported(what)));
+                i += 1;
+                continue;
+            };
+            tokens.push(match *op {
+                "(" => Token::LParen,
+                ")" => Token::RParen,
+                "[" => Token::LBracket,
+                "]" => Token::RBracket,
+                op => Token::Op(op),
+            });
+            i += op.len();
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Error for moca syntax outside the debugger's subset.
+fn unsupported(what: &str) -> String {
+    format!("{} are not supported in debugger expressions", what)
+}
+
+/// moca keywords, none of which the debugger's subset accepts.
+const KEYWORDS: [&str; 15] = [
+    "let", "const", "fun", "if", "else", "while", "for", "in", "return", "try", "catch", "throw",
+    "spawn", "match", "new",
+];
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let tok = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        tok
+    }
+
+    fn peek_binary(&self) -> Option<BinaryOp> {
+        match self.peek()? {
+            Token::Op(op) => Some(match *op {
+                "+" => BinaryOp::Add,
+                "-" => BinaryOp::Sub,
+                "*" => BinaryOp::Mul,
+                "/" => BinaryOp::Div,
+                "%" => BinaryOp::Mod,
+                "==" => BinaryOp::Eq,
+                "!=" => BinaryOp::Ne,
+                "<" => BinaryOp::Lt,
+                "<=" => BinaryOp::Le,
+                ">" => BinaryOp::Gt,
+                ">=" => BinaryOp::Ge,
+                "&&" => BinaryOp::And,
+                "||" => BinaryOp::Or,
+                _ => return None,
+            }),
+            _ => None,
+        }
+    }
+
+    fn parse_expr(&mut self, min_prec: u8) -> Result<DebugExpr, String> {
+        let mut lhs = self.parse_unary()?;
+        while let Some(op) = self.peek_binary() {
+            if op.precedence() < min_prec {
+                break;
+            }
+            self.pos += 1;
+            let rhs = self.parse_expr(op.precedence() + 1)?;
+            lhs = DebugExpr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> Result<DebugExpr, String> {
+        match self.peek() {
+            Some(Token::Op("-")) => {
+                self.pos += 1;
+                Ok(DebugExpr::Unary(
+                    UnaryOp::Neg,
+                    Box::new(self.parse_unary()?),
+                ))
+            }
+            Some(Token::Op("!")) => {
+                self.pos += 1;
+                Ok(DebugExpr::Unary(
+                    UnaryOp::Not,
+                    Box::new(self.parse_unary()?),
+                ))
+            }
+            _ => self.parse_postfix(),
+        }
+    }
+
+    fn parse_postfix(&mut self) -> Result<DebugExpr, String> {
+        let mut expr = self.parse_primary()?;
+        while self.peek() == Some(&Token::LBracket) {
+            self.pos += 1;
+            let index = self.parse_expr(0)?;
+            if self.next() != Some(Token::RBracket) {
+                return Err("expected ']'".to_string());
+            }
+            expr = DebugExpr::Index(Box::new(expr), Box::new(index));
+        }
+        Ok(expr)
+    }
+
+    fn parse_primary(&mut self) -> Result<DebugExpr, String> {
+        match self.next() {
+            Some(Token::Int(v)) => Ok(DebugExpr::Int(v)),
+            Some(Token::Float(v)) => Ok(DebugExpr::Float(v)),
+            Some(Token::Slot(s)) => Ok(DebugExpr::Slot(s)),
+            Some(Token::Ident(name)) => Ok(match name.as_str() {
+                "true" => DebugExpr::Bool(true),
+                "false" => DebugExpr::Bool(false),
+                "nil" => DebugExpr::Nil,
+                _ if KEYWORDS.contains(&name.as_str()) => {
+                    return Err(format!(
+                        "'{}' is not supported in debugger expressions",
+                        name
+                    ));
+                }
+                _ if self.peek() == Some(&Token::LParen) => {
+                    return Err(unsupported("function calls"));
+                }
+                _ => DebugExpr::Name(name),
+            }),
+            Some(Token::LParen) => {
+                let inner = self.parse_expr(0)?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(inner),
+                    _ => Err("expected ')'".to_string()),
+                }
+            }
+            Some(Token::LBracket) => Err(unsupported("array literals")),
+            Some(Token::Unsupported(msg)) => Err(msg),
+            Some(tok) => Err(format!("unexpected token {:?}", tok)),
+            None => Err("unexpected end of expression".to_string()),
+        }
+    }
+}
+
+impl DebugExpr {
+    /// Compile an expression from source text.
+    pub fn parse(src: &str) -> Result<Self, String> {
+        let tokens = tokenize(src)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.parse_expr(0)?;
+        match parser.next() {
+            Some(Token::Unsupported(msg)) => return Err(msg),
+            Some(tok) => return Err(format!("unexpected token {:?}", tok)),
+            None => {}
+        }
+        Ok(expr)
+    }
+
+    /// Evaluate against a paused frame.
+    pub fn eval(&self, ctx: &EvalContext) -> Result<Value, String> {
+        let locals = ctx.locals;
+        match self {
+            DebugExpr::Int(v) => Ok(Value::I64(*v)),
+            DebugExpr::Float(v) => Ok(Value::F64(*v)),
+            DebugExpr::Bool(b) => Ok(Value::Bool(*b)),
+            DebugExpr::Nil => Ok(Value::Null),
+            DebugExpr::Slot(slot) => read_slot(locals, *slot),
+            DebugExpr::Name(name) => {
+                let slot =
+                    (ctx.resolve)(name).ok_or_else(|| format!("unknown variable '{}'", name))?;
+                read_slot(locals, slot)
+            }
+            DebugExpr::Index(base, index) => {
+                let base = base.eval(ctx)?;
+                let index = index.eval(ctx)?;
+                let (Value::Ref(r), Value::I64(i)) = (base, index) else {
+                    return Err(format!(
+                        "cannot index {} with {}",
+                        base.type_name(),
+                        index.type_name()
+                    ));
+                };
+                // Arrays, strings and vecs all share the `[ptr, len, ...]`
+                // layout, so index through the data pointer like HeapLoad2.
+                let (Some(Value::Ref(data)), Some(Value::I64(len))) =
+                    (ctx.heap.read_slot(r, 0), ctx.heap.read_slot(r, 1))
+                else {
+                    return Err("cannot index a value that is not an array".to_string());
+                };
+                if i < 0 || i >= len {
+                    return Err(format!("index {} out of bounds (length {})", i, len));
+                }
+                ctx.heap
+                    .read_slot(data, i as usize)
+                    .ok_or_else(|| format!("index {} out of bounds (length {})", i, len))
+            }
+            DebugExpr::Unary(op, operand) => {
+                let v = operand.eval(ctx)?;
+                match (op, v) {
+                    (UnaryOp::Neg, Value::I64(n)) => Ok(Value::I64(n.wrapping_neg())),
+                    (UnaryOp::Neg, Value::F64(f)) => Ok(Value::F64(-f)),
+                    (UnaryOp::Not, v) => Ok(Value::Bool(!is_truthy(&v))),
+                    (UnaryOp::Neg, v) => Err(format!("cannot negate {}", v.type_name())),
+                }
+            }
+            DebugExpr::Binary(BinaryOp::And, lhs, rhs) => {
+                if !is_truthy(&lhs.eval(ctx)?) {
+                    return Ok(Value::Bool(false));
+                }
+                Ok(Value::Bool(is_truthy(&rhs.eval(ctx)?)))
+            }
+            DebugExpr::Binary(BinaryOp::Or, lhs, rhs) => {
+                if is_truthy(&lhs.eval(ctx)?) {
+                    return Ok(Value::Bool(true));
+                }
+                Ok(Value::Bool(is_truthy(&rhs.eval(ctx)?)))
+            }
+            DebugExpr::Binary(op, lhs, rhs) => {
+                let a = lhs.eval(ctx)?;
+                let b = rhs.eval(ctx)?;
+                eval_binary(*op, a, b)
+            }
+        }
+    }
+}
+
+fn read_slot(locals: &[Value], slot: usize) -> Result<Value, String> {
+    locals
+        .get(slot)
+        .copied()
+        .ok_or_else(|| format!("slot {} out of range", slot))
+}
+
+/// Truthiness as used by the debugger's interpreter.
+pub fn is_truthy(val: &Value) -> bool {
+    match val {
+        Value::Bool(b) => *b,
+        Value::Null => false,
+        Value::I64(0) => false,
+        _ => true,
+    }
+}
+
+fn eval_binary(op: BinaryOp, a: Value, b: Value) -> Result<Value, String> {
+    match op {
+        BinaryOp::Eq => return Ok(Value::Bool(a.value_eq(&b))),
+        BinaryOp::Ne => return Ok(Value::Bool(!a.value_eq(&b))),
+        _ => {}
+    }
+
+    match (a, b) {
+        (Value::I64(x), Value::I64(y)) => match op {
+            BinaryOp::Add => Ok(Value::I64(x.wrapping_add(y))),
+            BinaryOp::Sub => Ok(Value::I64(x.wrapping_sub(y))),
+            BinaryOp::Mul => Ok(Value::I64(x.wrapping_mul(y))),
+            BinaryOp::Div | BinaryOp::Mod if y == 0 => Err("division by zero".to_string()),
+            BinaryOp::Div => Ok(Value::I64(x.wrapping_div(y))),
+            BinaryOp::Mod => Ok(Value::I64(x.wrapping_rem(y))),
+            BinaryOp::Lt => Ok(Value::Bool(x < y)),
+            BinaryOp::Le => Ok(Value::Bool(x <= y)),
+            BinaryOp::Gt => Ok(Value::Bool(x > y)),
+            BinaryOp::Ge => Ok(Value::Bool(x >= y)),
+            _ => unreachable!(),
+        },
+        (Value::I64(_) | Value::F64(_), Value::I64(_) | Value::F64(_)) => {
+            let x = a.as_f64().unwrap();
+            let y = b.as_f64().unwrap();
+            match op {
+                BinaryOp::Add => Ok(Value::F64(x + y)),
+                BinaryOp::Sub => Ok(Value::F64(x - y)),
+                BinaryOp::Mul => Ok(Value::F64(x * y)),
+                BinaryOp::Div => Ok(Value::F64(x / y)),
+                BinaryOp::Mod => Ok(Value::F64(x % y)),
+                BinaryOp::Lt => Ok(Value::Bool(x < y)),
+                BinaryOp::Le => Ok(Value::Bool(x <= y)),
+                BinaryOp::Gt => Ok(Value::Bool(x > y)),
+                BinaryOp::Ge => Ok(Value::Bool(x >= y)),
+                _ => unreachable!(),
+            }
+        }
+        (a, b) => Err(format!(
+            "unsupported operand types: {} and {}",
+            a.type_name(),
+            b.type_name()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval_with(src: &str, locals: &[Value], heap: &Heap) -> Result<Value, String> {
+        let resolve = |name: &str| (name == "x").then_some(1);
+        let ctx = EvalContext {
+            locals,
+            heap,
+            resolve: &resolve,
+        };
+        DebugExpr::parse(src)?.eval(&ctx)
+    }
+
+    #[test]
+    fn test_precedence() {
+        let heap = Heap::new();
+        let result = eval_with("1 + 2 * 3 == 7 && !false", &[], &heap);
+        assert_eq!(result, Ok(Value::Bool(true)));
+    }
+
+    #[test]
+    fn test_slots_and_names() {
+        let heap = Heap::new();
+        let locals = [Value::I64(4), Value::F64(0.5)];
+        assert_eq!(eval_with("$0 * x", &locals, &heap), Ok(Value::F64(2.0)));
+        assert!(eval_with("$0 * y", &locals, &heap).is_err());
+    }
+
+    #[test]
+    fn test_heap_index() {
+        let mut heap = Heap::new();
+        let data = heap
+            .alloc_slots(vec![Value::I64(10), Value::I64(20)])
+            .unwrap();
+        let arr = heap
+            .alloc_slots(vec![Value::Ref(data), Value::I64(2)])
+            .unwrap();
+        let s = heap.alloc_string("hi".to_string()).unwrap();
+        let locals = [Value::Ref(arr), Value::Ref(s), Value::Ref(data)];
+        assert_eq!(eval_with("$0[0]", &locals, &heap), Ok(Value::I64(10)));
+        assert_eq!(eval_with("$0[1] - 5", &locals, &heap), Ok(Value::I64(15)));
+        assert_eq!(
+            eval_with("$0[2]", &locals, &heap),
+            Err("index 2 out of bounds (length 2)".to_string())
+        );
+        assert!(eval_with("$0[-1]", &locals, &heap).is_err());
+        assert_eq!(eval_with("$1[1]", &locals, &heap), Ok(Value::I64(105)));
+        assert!(eval_with("$2[0]", &locals, &heap).is_err());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(DebugExpr::parse("1 +").is_err());
+        assert!(DebugExpr::parse("(1").is_err());
+        assert!(DebugExpr::parse("1 2").is_err());
+        assert!(DebugExpr::parse("a # b").is_err());
+        assert!(DebugExpr::parse("a[1").is_err());
+    }
+
+    #[test]
+    fn test_unsupported_syntax_is_named() {
+        let err = |src: &str| DebugExpr::parse(src).unwrap_err();
+        assert_eq!(
+            err("len(xs)"),
+            "function calls are not supported in debugger expressions"
+        );
+        assert!(err("p.x").starts_with("field access and method calls"));
+        assert!(err("s == \"a\"").starts_with("string and char literals"));
+        assert!(err("x = 1").starts_with("assignments"));
+        assert!(err("[1, 2]").starts_with("array literals"));
+        assert_eq!(
+            err("if x { 1 }"),
+            "'if' is not supported in debugger expressions"
+        );
+    }
+}
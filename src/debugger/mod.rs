@@ -1,7 +1,9 @@
 // Some fields are stored for future use
 #![allow(dead_code)]
 
-use std::collections::HashSet;
+mod expr;
+
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::io::{self};
 use std::path::Path;
 
@@ -14,7 +16,12 @@ use ratatui::prelude::*;
 use ratatui::widgets::{Block, Borders, List, ListItem, Paragraph};
 
 use crate::compiler::{Codegen, ModuleLoader, Resolver};
-use crate::vm::{Chunk, Heap, Op, Value};
+use crate::vm::{Chunk, Heap, HeapSnapshot, Op, Value, debug_string};
+
+use expr::DebugExpr;
+
+/// Maximum number of save-states kept for the `back` command.
+const HISTORY_LIMIT: usize = 64;
 
 /// Debugger state.
 pub struct Debugger {
@@ -28,8 +35,10 @@ pub struct Debugger {
     func_index: i32,
     /// Breakpoints: (func_index, pc)
     breakpoints: HashSet<(i32, usize)>,
-    /// Line-based breakpoints: line number (1-based)
-    line_breakpoints: HashSet<usize>,
+    /// Line-based breakpoints: line number (1-based) -> optional condition
+    line_breakpoints: HashMap<usize, Option<Condition>>,
+    /// Watchpoints: local slot -> last observed value
+    watchpoints: HashMap<usize, Value>,
     /// VM stack
     stack: Vec<Value>,
     /// VM locals
@@ -48,6 +57,25 @@ pub struct Debugger {
     status: String,
     /// Output messages
     output: Vec<String>,
+    /// Ring of recent save-states, oldest first
+    history: VecDeque<SaveState>,
+}
+
+/// A breakpoint condition, kept with its source text for display.
+struct Condition {
+    source: String,
+    expr: DebugExpr,
+}
+
+/// Execution state captured before each step, for rewinding.
+struct SaveState {
+    pc: usize,
+    func_index: i32,
+    stack: Vec<Value>,
+    locals: Vec<Value>,
+    heap: HeapSnapshot,
+    call_stack: Vec<CallFrame>,
+    finished: bool,
 }
 
 /// A call frame for the backtrace.
@@ -78,13 +106,19 @@ impl Debugger {
         let mut codegen = Codegen::new();
         let chunk = codegen.compile(resolved)?;
 
-        Ok(Self {
+        Ok(Self::with_chunk(chunk, source_lines))
+    }
+
+    /// Create a debugger for an already compiled chunk.
+    fn with_chunk(chunk: Chunk, source_lines: Vec<String>) -> Self {
+        Self {
             source_lines,
             chunk,
             pc: 0,
             func_index: -1, // Main
             breakpoints: HashSet::new(),
-            line_breakpoints: HashSet::new(),
+            line_breakpoints: HashMap::new(),
+            watchpoints: HashMap::new(),
             stack: Vec::new(),
             locals: vec![Value::Null; 256], // Pre-allocate locals
             heap: Heap::new(),
@@ -98,7 +132,8 @@ impl Debugger {
             input: String::new(),
             status: "Ready. Type 'h' for help.".to_string(),
             output: Vec::new(),
-        })
+            history: VecDeque::new(),
+        }
     }
 
     /// Get the current instruction.
@@ -134,6 +169,8 @@ impl Debugger {
             }
         };
 
+        self.save_state();
+
         // Execute the operation (simplified VM)
         match &op {
             Op::I32Const(v) => self.stack.push(Value::I64(*v as i64)),
@@ -179,6 +216,8 @@ impl Debugger {
                     return;
                 }
             }
+            // `continue_exec` stops after this; stepping over it does nothing
+            Op::DebugBreak => {}
             _ => {
                 // Other ops not fully implemented for debugger
                 self.status = format!("Executed: {:?}", op);
@@ -189,6 +228,43 @@ impl Debugger {
         self.status = format!("PC: {}, Stack size: {}", self.pc, self.stack.len());
     }
 
+    /// Record the current state in the history ring, evicting the oldest entry.
+    fn save_state(&mut self) {
+        if self.history.len() == HISTORY_LIMIT {
+            self.history.pop_front();
+        }
+        self.history.push_back(SaveState {
+            pc: self.pc,
+            func_index: self.func_index,
+            stack: self.stack.clone(),
+            locals: self.locals.clone(),
+            heap: self.heap.snapshot(),
+            call_stack: self.call_stack.clone(),
+            finished: self.finished,
+        });
+    }
+
+    /// Rewind to the state before the most recent step.
+    fn step_back(&mut self) {
+        let Some(state) = self.history.pop_back() else {
+            self.status = "No earlier state to go back to.".to_string();
+            return;
+        };
+        self.pc = state.pc;
+        self.func_index = state.func_index;
+        self.stack = state.stack;
+        self.locals = state.locals;
+        self.heap.restore(&state.heap);
+        self.call_stack = state.call_stack;
+        self.finished = state.finished;
+        self.status = format!(
+            "PC: {}, Stack size: {} ({} earlier states)",
+            self.pc,
+            self.stack.len(),
+            self.history.len()
+        );
+    }
+
     fn binary_op<F>(&mut self, op: F)
     where
         F: Fn(i64, i64) -> i64,
@@ -199,45 +275,160 @@ impl Debugger {
     }
 
     fn is_truthy(&self, val: &Value) -> bool {
-        match val {
-            Value::Bool(b) => *b,
-            Value::Null => false,
-            Value::I64(0) => false,
-            _ => true,
-        }
+        expr::is_truthy(val)
     }
 
     fn format_value(&self, val: &Value) -> String {
-        match val {
-            Value::Null => "nil".to_string(),
-            Value::Bool(b) => b.to_string(),
-            Value::I64(i) => i.to_string(),
-            Value::F64(f) => f.to_string(),
-            Value::Ref(_) => "<object>".to_string(),
-        }
+        debug_string(&self.heap, &[], val).unwrap_or_else(|_| "<invalid reference>".to_string())
     }
 
-    /// Continue execution until breakpoint or end.
+    /// Continue execution until breakpoint, `breakpoint()` call or end.
     fn continue_exec(&mut self) {
         loop {
             if self.finished {
                 break;
             }
+            let at_debug_break = matches!(self.current_op(), Some(Op::DebugBreak));
             self.step();
-            // Check breakpoints
-            if self.line_breakpoints.contains(&self.current_line()) {
-                self.status = format!("Breakpoint hit at line {}", self.current_line());
+            if at_debug_break {
+                self.status = format!("breakpoint() hit at line {}", self.current_line());
                 break;
             }
+            if self.check_watchpoints() || self.check_breakpoint() {
+                break;
+            }
+        }
+    }
+
+    /// Check whether the breakpoint on the current line should fire.
+    /// A condition that fails to evaluate stops execution and reports the error.
+    fn check_breakpoint(&mut self) -> bool {
+        let line = self.current_line();
+        let Some(cond) = self.line_breakpoints.get(&line) else {
+            return false;
+        };
+        let Some(cond) = cond else {
+            self.status = format!("Breakpoint hit at line {}", line);
+            return true;
+        };
+        let status = match self.eval_expr(&cond.expr) {
+            Ok(v) if expr::is_truthy(&v) => {
+                format!("Breakpoint hit at line {} ({})", line, cond.source)
+            }
+            Ok(_) => return false,
+            Err(e) => format!("Breakpoint at line {}: condition error: {}", line, e),
+        };
+        self.status = status;
+        true
+    }
+
+    /// Display label for a local slot: its source name when in scope, else `slot[N]`.
+    fn local_label(&self, slot: usize) -> String {
+        let debug = self.chunk.debug.as_ref();
+        let info = debug.and_then(|d| {
+            if self.func_index < 0 {
+                Some(&d.main)
+            } else {
+                d.functions.get(self.func_index as usize)
+            }
+        });
+        info.and_then(|info| {
+            info.get_locals_at(self.pc)
+                .into_iter()
+                .filter(|l| l.slot as usize == slot)
+                .max_by_key(|l| l.scope_start)
+                .map(|l| l.name.clone())
+        })
+        .unwrap_or_else(|| format!("slot[{}]", slot))
+    }
+
+    /// Find the slot of a named local in scope at the current PC.
+    /// The innermost binding wins when a name is shadowed.
+    fn resolve_local(&self, name: &str) -> Option<usize> {
+        let debug = self.chunk.debug.as_ref()?;
+        let info = if self.func_index < 0 {
+            &debug.main
+        } else {
+            debug.functions.get(self.func_index as usize)?
+        };
+        info.get_locals_at(self.pc)
+            .into_iter()
+            .filter(|l| l.name == name)
+            .max_by_key(|l| l.scope_start)
+            .map(|l| l.slot as usize)
+    }
+
+    /// Evaluate an expression against the paused frame.
+    fn eval_expr(&self, expr: &DebugExpr) -> Result<Value, String> {
+        let resolve = |name: &str| self.resolve_local(name);
+        let ctx = expr::EvalContext {
+            locals: &self.locals,
+            heap: &self.heap,
+            resolve: &resolve,
+        };
+        expr.eval(&ctx)
+    }
+
+    /// Handle the `eval` command, reporting errors in the status line.
+    fn eval_command(&mut self, source: &str) {
+        let result = DebugExpr::parse(source).and_then(|expr| self.eval_expr(&expr));
+        self.status = match result {
+            Ok(v) => format!("{} = {}", source, self.format_value(&v)),
+            Err(e) => format!("eval error: {}", e),
+        };
+    }
+
+    /// Check watched slots for changes, updating the recorded values.
+    fn check_watchpoints(&mut self) -> bool {
+        let mut changed = Vec::new();
+        for (&slot, old) in self.watchpoints.iter_mut() {
+            let new = self.locals[slot];
+            if !new.value_eq(old) {
+                changed.push((slot, *old, new));
+                *old = new;
+            }
         }
+        let Some(&(slot, old, new)) = changed.iter().min_by_key(|c| c.0) else {
+            return false;
+        };
+        self.status = format!(
+            "Watchpoint slot[{}]: {} -> {}",
+            slot,
+            self.format_value(&old),
+            self.format_value(&new)
+        );
+        true
     }
 
     /// Set a breakpoint at line number.
     fn set_breakpoint(&mut self, line: usize) {
-        self.line_breakpoints.insert(line);
+        self.line_breakpoints.insert(line, None);
         self.status = format!("Breakpoint set at line {}", line);
     }
 
+    /// Set a breakpoint that only fires when `source` evaluates truthy.
+    fn set_conditional_breakpoint(&mut self, line: usize, source: &str) {
+        match DebugExpr::parse(source) {
+            Ok(expr) => {
+                let source = source.to_string();
+                self.status = format!("Breakpoint set at line {} if {}", line, source);
+                self.line_breakpoints
+                    .insert(line, Some(Condition { source, expr }));
+            }
+            Err(e) => self.status = format!("Invalid condition: {}", e),
+        }
+    }
+
+    /// Watch a local slot, breaking when its value changes.
+    fn set_watchpoint(&mut self, slot: usize) {
+        let Some(&val) = self.locals.get(slot) else {
+            self.status = format!("Slot {} out of range", slot);
+            return;
+        };
+        self.watchpoints.insert(slot, val);
+        self.status = format!("Watching slot[{}] = {}", slot, self.format_value(&val));
+    }
+
     /// Delete a breakpoint at line number.
     fn delete_breakpoint(&mut self, line: usize) {
         self.line_breakpoints.remove(&line);
@@ -255,15 +446,28 @@ impl Debugger {
             "s" | "step" => self.step(),
             "n" | "next" => self.step(), // Same as step for now
             "c" | "continue" => self.continue_exec(),
-            "b" => {
+            "back" => self.step_back(),
+            "b" => match (
+                parts.get(1).and_then(|l| l.parse::<usize>().ok()),
+                parts.get(2),
+            ) {
+                (Some(line), None) => self.set_breakpoint(line),
+                (Some(line), Some(&"if")) if parts.len() > 3 => {
+                    self.set_conditional_breakpoint(line, &parts[3..].join(" "));
+                }
+                _ => self.status = "Usage: b <line> [if <expr>]".to_string(),
+            },
+            "eval" => {
                 if parts.len() > 1 {
-                    if let Ok(line) = parts[1].parse::<usize>() {
-                        self.set_breakpoint(line);
-                    }
+                    self.eval_command(&parts[1..].join(" "));
                 } else {
-                    self.status = "Usage: b <line>".to_string();
+                    self.status = "Usage: eval <expr>".to_string();
                 }
             }
+            "watch" => match parts.get(1).and_then(|s| s.parse::<usize>().ok()) {
+                Some(slot) => self.set_watchpoint(slot),
+                None => self.status = "Usage: watch <slot>".to_string(),
+            },
             "d" => {
                 if parts.len() > 1 {
                     if let Ok(line) = parts[1].parse::<usize>() {
@@ -274,19 +478,30 @@ impl Debugger {
                 }
             }
             "bl" => {
-                let bps: Vec<String> = self
-                    .line_breakpoints
-                    .iter()
-                    .map(|l| l.to_string())
+                let mut lines: Vec<_> = self.line_breakpoints.iter().collect();
+                lines.sort_by_key(|(l, _)| **l);
+                let mut bps: Vec<String> = lines
+                    .into_iter()
+                    .map(|(l, cond)| match cond {
+                        Some(c) => format!("{} if {}", l, c.source),
+                        None => l.to_string(),
+                    })
                     .collect();
+                let mut slots: Vec<_> = self.watchpoints.keys().collect();
+                slots.sort();
+                bps.extend(slots.into_iter().map(|s| format!("watch slot[{}]", s)));
                 self.status = format!("Breakpoints: {}", bps.join(", "));
             }
             "p" => {
                 if parts.len() > 1 {
-                    // Print local variable by slot
-                    if let Ok(slot) = parts[1].parse::<usize>() {
-                        let val = &self.locals[slot];
-                        self.status = format!("slot[{}] = {}", slot, self.format_value(val));
+                    // Print local variable by slot or name
+                    let slot = parts[1]
+                        .parse::<usize>()
+                        .ok()
+                        .or_else(|| self.resolve_local(parts[1]));
+                    if let Some(val) = slot.and_then(|s| self.locals.get(s)) {
+                        let label = self.local_label(slot.unwrap());
+                        self.status = format!("{} = {}", label, self.format_value(val));
                     } else {
                         self.status = format!("Unknown: {}", parts[1]);
                     }
@@ -298,7 +513,11 @@ impl Debugger {
                 let mut locals_str = String::new();
                 for (i, val) in self.locals.iter().enumerate().take(8) {
                     if !matches!(val, Value::Null) {
-                        locals_str.push_str(&format!("[{}]={} ", i, self.format_value(val)));
+                        locals_str.push_str(&format!(
+                            "{}={} ",
+                            self.local_label(i),
+                            self.format_value(val)
+                        ));
                     }
                 }
                 self.status = format!("Locals: {}", locals_str);
@@ -316,7 +535,7 @@ impl Debugger {
                 self.running = false;
             }
             "h" | "help" => {
-                self.status = "Commands: s(tep) n(ext) c(ontinue) b <line> d <line> bl p <slot> locals bt q(uit)".to_string();
+                self.status = "Commands: s(tep) n(ext) back c(ontinue) b <line> [if <expr>] watch <slot> d <line> bl p <slot|name> eval <expr> locals bt q(uit)".to_string();
             }
             _ => {
                 self.status = format!("Unknown command: {}", parts[0]);
@@ -384,7 +603,7 @@ impl Debugger {
             .enumerate()
             .map(|(i, line)| {
                 let line_num = i + 1;
-                let prefix = if self.line_breakpoints.contains(&line_num) {
+                let prefix = if self.line_breakpoints.contains_key(&line_num) {
                     "●"
                 } else {
                     " "
@@ -416,7 +635,7 @@ impl Debugger {
             .enumerate()
             .take(8)
             .filter(|(_, v)| !matches!(v, Value::Null))
-            .map(|(i, v)| format!("[{}] = {}", i, self.format_value(v)))
+            .map(|(i, v)| format!("{} = {}", self.local_label(i), self.format_value(v)))
             .collect();
         let locals = Paragraph::new(locals_text.join("\n"))
             .block(Block::default().title("Locals").borders(Borders::ALL));
@@ -463,3 +682,128 @@ pub fn run_debugger(path: &Path) -> Result<(), String> {
     let mut debugger = Debugger::new(path)?;
     debugger.run().map_err(|e| e.to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vm::{DebugInfo, Function, FunctionDebugInfo};
+
+    /// Build a debugger over straight-line main code. The debugger maps
+    /// pc N to line N + 1, so each op occupies its own line.
+    fn debugger_for(code: Vec<Op>) -> Debugger {
+        let source_lines = (0..code.len() + 1).map(|i| format!("op {}", i)).collect();
+        let chunk = Chunk {
+            functions: vec![],
+            main: Function {
+                name: "__main__".to_string(),
+                arity: 0,
+                locals_count: 2,
+                code,
+                stackmap: None,
+                local_types: vec![],
+            },
+            strings: vec![],
+            type_descriptors: vec![],
+            interface_descriptors: vec![],
+            const_arrays: vec![],
+            debug: None,
+            metadata: Default::default(),
+        };
+        Debugger::with_chunk(chunk, source_lines)
+    }
+
+    fn counting_code() -> Vec<Op> {
+        vec![
+            Op::I64Const(1), // 0
+            Op::LocalSet(0), // 1
+            Op::I64Const(2), // 2
+            Op::LocalSet(0), // 3
+            Op::I64Const(7), // 4
+            Op::LocalSet(1), // 5
+            Op::I64Const(3), // 6
+            Op::LocalSet(0), // 7
+        ]
+    }
+
+    #[test]
+    fn test_conditional_breakpoint() {
+        // Line 5 is reached with slot 0 == 2, so this condition never holds
+        let mut dbg = debugger_for(counting_code());
+        dbg.process_command("b 5 if $0 == 1");
+        dbg.process_command("c");
+        assert!(dbg.finished);
+
+        // Line 3 is reached with slot 0 == 1
+        let mut dbg = debugger_for(counting_code());
+        dbg.process_command("b 3 if $0 == 1");
+        dbg.process_command("c");
+        assert!(!dbg.finished);
+        assert_eq!(dbg.pc, 2);
+        assert_eq!(dbg.status, "Breakpoint hit at line 3 ($0 == 1)");
+    }
+
+    #[test]
+    fn test_eval() {
+        let mut dbg = debugger_for(vec![
+            Op::I64Const(3),
+            Op::LocalSet(0),
+            Op::I64Const(4),
+            Op::LocalSet(1),
+            Op::I64Const(0),
+        ]);
+        let mut info = FunctionDebugInfo::new();
+        info.add_local("a".to_string(), 0, 0);
+        info.add_local("b".to_string(), 1, 0);
+        dbg.chunk.debug = Some(DebugInfo {
+            files: vec![],
+            functions: vec![],
+            main: info,
+        });
+
+        dbg.process_command("b 5");
+        dbg.process_command("c");
+        dbg.process_command("eval a + b");
+        assert_eq!(dbg.status, "a + b = 7");
+        dbg.process_command("p 1");
+        assert_eq!(dbg.status, "b = 4");
+
+        dbg.process_command("eval a + c");
+        assert_eq!(dbg.status, "eval error: unknown variable 'c'");
+        dbg.process_command("eval a +");
+        assert_eq!(dbg.status, "eval error: unexpected end of expression");
+    }
+
+    #[test]
+    fn test_debug_break_pauses_continue() {
+        let mut dbg = debugger_for(vec![
+            Op::I64Const(1),
+            Op::LocalSet(0),
+            Op::DebugBreak,
+            Op::I64Const(2),
+            Op::LocalSet(0),
+        ]);
+        dbg.process_command("c");
+        assert!(!dbg.finished);
+        assert_eq!(dbg.pc, 3);
+        assert_eq!(dbg.locals[0], Value::I64(1));
+        assert_eq!(dbg.status, "breakpoint() hit at line 4");
+
+        dbg.process_command("c");
+        assert!(dbg.finished);
+        assert_eq!(dbg.locals[0], Value::I64(2));
+    }
+
+    #[test]
+    fn test_watchpoint() {
+        let mut dbg = debugger_for(counting_code());
+        dbg.process_command("watch 1");
+        dbg.process_command("c");
+        assert_eq!(dbg.pc, 6);
+        assert_eq!(dbg.locals[1], Value::I64(7));
+        assert_eq!(dbg.status, "Watchpoint slot[1]: nil -> 7");
+
+        // No further writes to slot 1: runs to completion
+        dbg.process_command("c");
+        assert!(dbg.finished);
+    }
+}
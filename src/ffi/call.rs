@@ -5,12 +5,15 @@
 
 use super::types::{HostFunction, MocaCFunc, MocaResult, MocaVm};
 use super::vm_ffi::get_wrapper_mut;
+use crate::vm::{Function, Op, Value, ValueType};
 use std::ffi::{CStr, c_char};
 
 /// Call a moca function by name.
 ///
-/// Arguments must be pushed onto the stack before calling.
-/// The result will be on the stack after a successful call.
+/// Arguments must be pushed onto the stack before calling, first argument
+/// first. For functions of the loaded chunk, the arguments are popped and
+/// the result is pushed in their place; host functions manage the stack
+/// themselves. The chunk's top-level code is not run by a call.
 ///
 /// # Arguments
 /// - `vm`: Valid VM instance
@@ -19,8 +22,10 @@ use std::ffi::{CStr, c_char};
 ///
 /// # Returns
 /// - `MOCA_OK` on success
+/// - `MOCA_ERROR_INVALID_ARG` if `nargs` doesn't match the function's arity
+///   or exceeds the stack size
 /// - `MOCA_ERROR_NOT_FOUND` if function not found
-/// - `MOCA_ERROR_RUNTIME` on execution error
+/// - `MOCA_ERROR_RUNTIME` on execution error (including uncaught throws)
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn moca_call(
     vm: *mut MocaVm,
@@ -60,34 +65,71 @@ pub unsafe extern "C" fn moca_call(
     }
 
     // Look for moca function in chunk
-    let Some(chunk) = &wrapper.chunk else {
+    let Some(chunk) = &mut wrapper.chunk else {
         wrapper.set_error("No bytecode loaded");
         return MocaResult::ErrorNotFound;
     };
 
     // Find function by name
-    let func_idx = chunk
-        .functions
-        .iter()
-        .position(|f| f.name == name)
-        .or_else(|| {
-            if chunk.main.name == name {
-                Some(usize::MAX) // Special marker for main
-            } else {
-                None
-            }
-        });
-
-    let Some(_func_idx) = func_idx else {
+    let Some(func_idx) = chunk.functions.iter().position(|f| f.name == name) else {
         wrapper.set_error(format!("Function '{}' not found", name));
         return MocaResult::ErrorNotFound;
     };
 
-    // TODO: Implement actual function call
-    // This requires transferring values from ffi_stack to VM stack,
-    // executing the function, and transferring result back
-    wrapper.set_error("Function calls not yet implemented");
-    MocaResult::ErrorRuntime
+    let argc = nargs.max(0) as usize;
+    let arity = chunk.functions[func_idx].arity;
+    if argc != arity {
+        wrapper.set_error(format!(
+            "Function '{}' expects {} arguments, got {}",
+            name, arity, nargs
+        ));
+        return MocaResult::ErrorInvalidArg;
+    }
+    let Some(first_arg) = wrapper.ffi_stack.len().checked_sub(argc) else {
+        wrapper.set_error(format!("Stack has fewer than {} values", argc));
+        return MocaResult::ErrorInvalidArg;
+    };
+
+    // Run a main whose locals are the whole FFI stack, so values below the
+    // arguments stay GC roots, and which passes the top `argc` to the function
+    let values = std::mem::take(&mut wrapper.ffi_stack);
+    let trampoline = Function {
+        name: "__ffi_call__".to_string(),
+        arity: 0,
+        locals_count: values.len(),
+        code: (first_arg..values.len())
+            .map(Op::LocalGet)
+            .chain([Op::Call(func_idx, argc), Op::Ret])
+            .collect(),
+        stackmap: None,
+        local_types: values.iter().map(value_type).collect(),
+    };
+    let main = std::mem::replace(&mut chunk.main, trampoline);
+    let result = wrapper.vm.run_with_args(chunk, values.clone());
+    chunk.main = main;
+    wrapper.ffi_stack = values;
+    wrapper.ffi_stack.truncate(first_arg);
+
+    match result {
+        Ok(value) => {
+            wrapper.ffi_stack.push(value);
+            wrapper.clear_error();
+            MocaResult::Ok
+        }
+        Err(e) => {
+            wrapper.set_error(e);
+            MocaResult::ErrorRuntime
+        }
+    }
+}
+
+/// The slot type an argument is passed in.
+fn value_type(value: &Value) -> ValueType {
+    match value {
+        Value::F64(_) => ValueType::F64,
+        Value::Ref(_) | Value::Null => ValueType::Ref,
+        _ => ValueType::I64,
+    }
 }
 
 /// Protected call - catches errors instead of aborting.
@@ -266,6 +308,61 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_call_precompiled_function() {
+        let source = r#"
+fun add(a: int, b: int) -> int { return a + b; }
+fun greet(name: string) -> string { return "hello, " + name; }
+fun fail() { throw "boom"; }
+print("top-level code is not run by calls");
+"#;
+        let chunk = crate::compiler::compile_to_chunk(source, "lib.mc").unwrap();
+        let bytes = crate::vm::bytecode::serialize(&chunk);
+
+        unsafe {
+            let vm = moca_vm_new();
+            let load = crate::ffi::load::moca_load_chunk(vm, bytes.as_ptr(), bytes.len());
+            assert_eq!(load, MocaResult::Ok);
+
+            // Called many times without reloading; each call replaces its
+            // arguments with the result
+            let add = CString::new("add").unwrap();
+            for i in 0..3 {
+                moca_push_i64(vm, i);
+                moca_push_i64(vm, 40);
+                assert_eq!(moca_call(vm, add.as_ptr(), 2), MocaResult::Ok);
+                assert_eq!(moca_get_top(vm), 1);
+                assert_eq!(moca_to_i64(vm, -1), 40 + i);
+                moca_pop(vm, 1);
+            }
+
+            let greet = CString::new("greet").unwrap();
+            let name = "moca";
+            moca_push_string(vm, name.as_ptr() as *const c_char, name.len());
+            assert_eq!(moca_call(vm, greet.as_ptr(), 1), MocaResult::Ok);
+            let mut len = 0;
+            let out = moca_to_string(vm, -1, &mut len);
+            let text = std::slice::from_raw_parts(out as *const u8, len);
+            assert_eq!(text, b"hello, moca");
+            moca_pop(vm, 1);
+
+            // Arity mismatches and runtime errors are reported, not run
+            assert_eq!(moca_call(vm, add.as_ptr(), 1), MocaResult::ErrorInvalidArg);
+            let fail = CString::new("fail").unwrap();
+            assert_eq!(moca_call(vm, fail.as_ptr(), 0), MocaResult::ErrorRuntime);
+            let error = CStr::from_ptr(crate::ffi::error::moca_get_error(vm));
+            assert!(error.to_str().unwrap().contains("boom"));
+
+            // The VM is still usable after an error
+            moca_push_i64(vm, 1);
+            moca_push_i64(vm, 2);
+            assert_eq!(moca_call(vm, add.as_ptr(), 2), MocaResult::Ok);
+            assert_eq!(moca_to_i64(vm, -1), 3);
+
+            moca_vm_free(vm);
+        }
+    }
+
     #[test]
     fn test_call_not_found() {
         unsafe {
@@ -195,7 +195,9 @@ mod tests {
             strings: vec![],
             type_descriptors: vec![],
             interface_descriptors: vec![],
+            const_arrays: vec![],
             debug: None,
+            metadata: Default::default(),
         };
 
         // Serialize it
@@ -271,7 +273,9 @@ mod tests {
             strings: vec!["test".to_string()],
             type_descriptors: vec![],
             interface_descriptors: vec![],
+            const_arrays: vec![],
             debug: None,
+            metadata: Default::default(),
         };
 
         // Use a temp file
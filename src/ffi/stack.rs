@@ -240,11 +240,15 @@ pub unsafe extern "C" fn moca_to_string(
                 if let Some(obj) = wrapper.vm.heap().get(r) {
                     // String struct: [ptr, len], follow ptr to data array
                     let str_value = if let Some(data_ref) = obj.slots[0].as_ref() {
+                        let len = obj.slots.get(1).and_then(|v| v.as_i64()).unwrap_or(0);
                         if let Some(data) = wrapper.vm.heap().get(data_ref) {
-                            data.slots_to_string()
+                            data.slots_to_string_len(len.max(0) as usize)
                         } else {
                             obj.slots_to_string()
                         }
+                    } else if obj.slots[0] == Value::Null {
+                        // Empty string: no data array
+                        String::new()
                     } else {
                         obj.slots_to_string()
                     };
@@ -335,7 +339,7 @@ mod tests {
             let vm = moca_vm_new();
 
             moca_push_i64(vm, 42);
-            moca_push_f64(vm, 3.14);
+            moca_push_f64(vm, 2.5);
             moca_push_bool(vm, true);
             moca_push_null(vm);
 
@@ -349,7 +353,7 @@ mod tests {
             moca_pop(vm, 2);
             assert_eq!(moca_get_top(vm), 2);
 
-            assert_eq!(moca_to_f64(vm, -1), 3.14);
+            assert_eq!(moca_to_f64(vm, -1), 2.5);
             assert_eq!(moca_to_i64(vm, -2), 42);
 
             moca_vm_free(vm);
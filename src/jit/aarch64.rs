@@ -174,6 +174,16 @@ impl<'a> AArch64Assembler<'a> {
         self.emit_raw(inst);
     }
 
+    /// UDIV Xd, Xn, Xm (64-bit unsigned divide)
+    pub fn udiv(&mut self, rd: Reg, rn: Reg, rm: Reg) {
+        // 1001 1010 110m mmmm 0000 10nn nnnd dddd
+        let inst = 0x9AC00800
+            | ((rm.code() as u32) << 16)
+            | ((rn.code() as u32) << 5)
+            | (rd.code() as u32);
+        self.emit_raw(inst);
+    }
+
     /// AND Xd, Xn, Xm
     pub fn and(&mut self, rd: Reg, rn: Reg, rm: Reg) {
         // 1000 1010 000m mmmm 0000 00nn nnnd dddd
@@ -17,8 +17,14 @@ use super::compiler::{CompiledCode, CompiledLoop, value_tags};
 #[cfg(target_arch = "aarch64")]
 use super::memory::ExecutableMemory;
 #[cfg(target_arch = "aarch64")]
+use super::stackmap::{FRAME_REF_MAP, frame_ref_map};
+#[cfg(target_arch = "aarch64")]
 use crate::vm::ElemKind;
 #[cfg(target_arch = "aarch64")]
+use crate::vm::HEADER_ELEM_KIND_SHIFT;
+#[cfg(target_arch = "aarch64")]
+use crate::vm::Value;
+#[cfg(target_arch = "aarch64")]
 use crate::vm::ValueType;
 #[cfg(target_arch = "aarch64")]
 use crate::vm::microop::{CmpCond, ConvertedFunction, MicroOp, VReg};
@@ -161,7 +167,10 @@ impl MicroOpJitCompiler {
         Ok(CompiledCode {
             memory,
             entry_offset: 0,
-            stack_map: HashMap::new(),
+            stack_map: HashMap::from([(
+                FRAME_REF_MAP,
+                frame_ref_map(&self.vreg_types, self.total_regs),
+            )]),
             total_regs: self.total_regs,
         })
     }
@@ -186,6 +195,13 @@ impl MicroOpJitCompiler {
         loop_start_op_pc: usize,
         loop_end_op_pc: usize,
     ) -> Result<CompiledLoop, String> {
+        // Every exit must land where the interpreter resumes
+        if let Some(target) =
+            converted.loop_exit_outside(loop_start_microop_pc, loop_end_microop_pc)
+        {
+            return Err(format!("Loop exits to MicroOp PC {}", target));
+        }
+
         self.total_regs = locals_count + converted.temps_count;
         self.self_func_index = func_index;
         self.self_locals_count = locals_count;
@@ -299,7 +315,10 @@ impl MicroOpJitCompiler {
             entry_offset: 0,
             loop_start_pc: loop_start_op_pc,
             loop_end_pc: loop_end_op_pc,
-            stack_map: HashMap::new(),
+            stack_map: HashMap::from([(
+                FRAME_REF_MAP,
+                frame_ref_map(&self.vreg_types, self.total_regs),
+            )]),
             total_regs: self.total_regs,
         })
     }
@@ -351,6 +370,11 @@ impl MicroOpJitCompiler {
                 | MicroOp::MulI64 { dst, .. }
                 | MicroOp::DivI64 { dst, .. }
                 | MicroOp::RemI64 { dst, .. }
+                | MicroOp::DivU64 { dst, .. }
+                | MicroOp::RemU64 { dst, .. }
+                | MicroOp::AddSatI64 { dst, .. }
+                | MicroOp::SubSatI64 { dst, .. }
+                | MicroOp::MulSatI64 { dst, .. }
                 | MicroOp::NegI64 { dst, .. }
                 | MicroOp::AddI64Imm { dst, .. }
                 | MicroOp::AndI64 { dst, .. }
@@ -418,6 +442,7 @@ impl MicroOpJitCompiler {
                     record(&mut vreg_tags, dst.0, u64::MAX);
                 }
                 MicroOp::Call { ret: Some(ret), .. }
+                | MicroOp::TailCall { ret: Some(ret), .. }
                 | MicroOp::CallIndirect { ret: Some(ret), .. } => {
                     record(&mut vreg_tags, ret.0, u64::MAX);
                 }
@@ -495,6 +520,8 @@ impl MicroOpJitCompiler {
             MicroOp::MulI64 { dst, a, b } => self.emit_binop_i64(dst, a, b, BinOp::Mul),
             MicroOp::DivI64 { dst, a, b } => self.emit_binop_i64(dst, a, b, BinOp::Div),
             MicroOp::RemI64 { dst, a, b } => self.emit_rem_i64(dst, a, b),
+            MicroOp::DivU64 { dst, a, b } => self.emit_divrem_u64(dst, a, b, false),
+            MicroOp::RemU64 { dst, a, b } => self.emit_divrem_u64(dst, a, b, true),
             MicroOp::NegI64 { dst, src } => self.emit_neg_i64(dst, src),
             MicroOp::AddI64Imm { dst, a, imm } => self.emit_add_i64_imm(dst, a, *imm),
             MicroOp::AndI64 { dst, a, b } => self.emit_binop_i64(dst, a, b, BinOp::And),
@@ -516,6 +543,10 @@ impl MicroOpJitCompiler {
             MicroOp::Jmp { target, .. } => self.emit_jmp(*target),
 
             MicroOp::Call { func_id, args, ret } => self.emit_call(*func_id, args, ret.as_ref()),
+            // A native call can't replace the caller's frame, so functions
+            // with tail calls stay in the interpreter, which runs them in
+            // constant stack
+            MicroOp::TailCall { .. } => Err("TailCall is not supported by the JIT".to_string()),
             MicroOp::Ret { src } => self.emit_ret(src.as_ref()),
 
             MicroOp::HeapLoad { dst, src, offset } => self.emit_heap_load(dst, src, *offset),
@@ -590,7 +621,7 @@ impl MicroOpJitCompiler {
             MicroOp::I64TruncF32S { dst, src } => self.emit_i64_trunc_f64s(dst, src),
             MicroOp::F32DemoteF64 { dst, src } => self.emit_mov(dst, src),
             MicroOp::F64PromoteF32 { dst, src } => self.emit_mov(dst, src),
-            MicroOp::F64ReinterpretAsI64 { dst, src } => self.emit_mov(dst, src),
+            MicroOp::F64ReinterpretAsI64 { dst, src } => self.emit_f64_reinterpret_as_i64(dst, src),
 
             // Ref ops
             MicroOp::RefEq { dst, a, b } => self.emit_ref_eq(dst, a, b),
@@ -614,6 +645,19 @@ impl MicroOpJitCompiler {
             // Stack bridge (spill/restore across calls)
             MicroOp::StackPush { src } => self.emit_stack_push(src),
             MicroOp::StackPop { dst } => self.emit_stack_pop(dst),
+            // Structs allocated by JIT code print without their type name
+            MicroOp::TypeTag { .. } => Ok(()),
+            // GC doesn't run inside JIT code, so allocation hints are dropped,
+            // and breakpoints only mean something to the debugger
+            MicroOp::Raw {
+                op: crate::vm::Op::GcHint(_) | crate::vm::Op::DebugBreak,
+            } => Ok(()),
+            MicroOp::Raw {
+                op: crate::vm::Op::Unreachable,
+            } => {
+                self.emit_unreachable();
+                Ok(())
+            }
 
             _ => Err(format!(
                 "Unsupported MicroOp for JIT: {:?}",
@@ -695,6 +739,26 @@ impl MicroOpJitCompiler {
         Ok(())
     }
 
+    fn emit_divrem_u64(&mut self, dst: &VReg, a: &VReg, b: &VReg, rem: bool) -> Result<(), String> {
+        let shadow = self.needs_shadow_update(dst, value_tags::TAG_INT);
+        let mut asm = AArch64Assembler::new(&mut self.buf);
+        asm.ldr(regs::TMP0, regs::FRAME_BASE, Self::vreg_offset(a));
+        asm.ldr(regs::TMP1, regs::FRAME_BASE, Self::vreg_offset(b));
+        if rem {
+            asm.udiv(regs::TMP2, regs::TMP0, regs::TMP1);
+            asm.mul(regs::TMP2, regs::TMP2, regs::TMP1);
+            asm.sub(regs::TMP0, regs::TMP0, regs::TMP2);
+        } else {
+            asm.udiv(regs::TMP0, regs::TMP0, regs::TMP1);
+        }
+        asm.str(regs::TMP0, regs::FRAME_BASE, Self::vreg_offset(dst));
+        drop(asm);
+        if let Some(off) = shadow {
+            self.emit_shadow_update(off, value_tags::TAG_INT);
+        }
+        Ok(())
+    }
+
     fn emit_neg_i64(&mut self, dst: &VReg, src: &VReg) -> Result<(), String> {
         let shadow = self.needs_shadow_update(dst, value_tags::TAG_INT);
         let mut asm = AArch64Assembler::new(&mut self.buf);
@@ -1086,6 +1150,34 @@ impl MicroOpJitCompiler {
 
     /// JitCallContext offset for jit_function_table pointer.
     const JIT_FUNC_TABLE_OFFSET: u16 = 80;
+    /// JitCallContext offset for the pending_error flag.
+    const PENDING_ERROR_OFFSET: u16 = 96;
+
+    /// Return from the compiled code if the call just made raised an error.
+    /// The VM re-raises the error once native code has returned, so it never
+    /// unwinds across JIT frames.
+    fn emit_pending_error_check(&mut self) {
+        {
+            let mut asm = AArch64Assembler::new(&mut self.buf);
+            asm.ldr(regs::TMP0, regs::VM_CTX, Self::PENDING_ERROR_OFFSET);
+            // Skip the cbz itself, the SP reset and the 4-instruction epilogue
+            asm.cbz(regs::TMP0, 24);
+            // Drop any values StackPush left on the machine stack
+            asm.add_imm(Reg::Sp, Reg::Fp, 0);
+        }
+        self.emit_epilogue();
+    }
+
+    /// Bail out with the pending error flag set and no VM error recorded,
+    /// which the VM reports as reaching unreachable code.
+    fn emit_unreachable(&mut self) {
+        self.emit_load_imm64(1, regs::TMP0);
+        {
+            let mut asm = AArch64Assembler::new(&mut self.buf);
+            asm.str(regs::TMP0, regs::VM_CTX, Self::PENDING_ERROR_OFFSET);
+        }
+        self.emit_epilogue();
+    }
 
     /// Emit a function call that looks up the callee in the JIT function table at runtime.
     /// If the callee is compiled (entry != 0), calls it directly. Otherwise falls back to
@@ -1293,6 +1385,8 @@ impl MicroOpJitCompiler {
             code[b_done_pos..b_done_pos + 4].copy_from_slice(&patched.to_le_bytes());
         }
 
+        self.emit_pending_error_check();
+
         Ok(())
     }
 
@@ -1361,6 +1455,8 @@ impl MicroOpJitCompiler {
             asm.str(Reg::X0, regs::FRAME_BASE, ret_shadow_off);
         }
 
+        self.emit_pending_error_check();
+
         Ok(())
     }
 
@@ -1454,6 +1550,8 @@ impl MicroOpJitCompiler {
             asm.str(Reg::X0, regs::FRAME_BASE, ret_shadow_off);
         }
 
+        self.emit_pending_error_check();
+
         Ok(())
     }
 
@@ -1674,6 +1772,26 @@ impl MicroOpJitCompiler {
         Ok(())
     }
 
+    /// Float bits as an int, with every NaN canonicalized like the interpreter does.
+    fn emit_f64_reinterpret_as_i64(&mut self, dst: &VReg, src: &VReg) -> Result<(), String> {
+        let shadow = self.needs_shadow_update(dst, value_tags::TAG_INT);
+        self.emit_load_imm64((f64::INFINITY.to_bits() << 1) as i64, regs::TMP2);
+        self.emit_load_imm64(Value::CANONICAL_NAN_BITS as i64, regs::TMP3);
+        let mut asm = AArch64Assembler::new(&mut self.buf);
+        asm.ldr(regs::TMP0, regs::FRAME_BASE, Self::vreg_offset(src));
+        // NaN iff the bits without the sign are above those of infinity
+        asm.lsl_imm(regs::TMP1, regs::TMP0, 1);
+        asm.cmp(regs::TMP1, regs::TMP2);
+        asm.b_cond(Cond::Ls, 8); // skip the mov below
+        asm.mov(regs::TMP0, regs::TMP3);
+        asm.str(regs::TMP0, regs::FRAME_BASE, Self::vreg_offset(dst));
+        drop(asm);
+        if let Some(off) = shadow {
+            self.emit_shadow_update(off, value_tags::TAG_INT);
+        }
+        Ok(())
+    }
+
     // ==================== Ref Operations ====================
 
     /// RefEq: dst = (a == b) as i64, comparing payloads (reference identity)
@@ -1786,12 +1904,35 @@ impl MicroOpJitCompiler {
                 asm.str(regs::TMP0, regs::FRAME_BASE, shadow_off);
             }
             ElemKind::Tagged => {
+                // Untyped access (`__heap_load`, fields) follows the element
+                // kind in the header, like `Heap::read_slot`
+                Self::emit_header_elem_kind(&mut asm);
+                asm.cbz(regs::TMP3, 72); // -> Tagged
+                asm.cmp_imm(regs::TMP3, ElemKind::U8 as u16);
+                asm.b_cond(Cond::Ne, 20); // -> I64/Ref/F64
+                // U8: addr = heap_base + ref_bytes + 8 + idx
+                asm.add(regs::TMP1, regs::TMP1, regs::TMP2);
+                asm.ldrb(regs::TMP2, regs::TMP1, 8);
+                asm.mov_imm(regs::TMP0, value_tags::TAG_INT as u16);
+                asm.b(64); // -> done
+                // I64/Ref/F64: addr = heap_base + ref_bytes + 8 + idx * 8
+                asm.lsl_imm(regs::TMP2, regs::TMP2, 3);
+                asm.add(regs::TMP1, regs::TMP1, regs::TMP2);
+                asm.ldr(regs::TMP2, regs::TMP1, 8);
+                asm.mov_imm(regs::TMP0, value_tags::TAG_INT as u16);
+                asm.cmp_imm(regs::TMP3, ElemKind::Ref as u16);
+                asm.b_cond(Cond::Ne, 8);
+                asm.mov_imm(regs::TMP0, value_tags::TAG_PTR as u16);
+                asm.cmp_imm(regs::TMP3, ElemKind::F64 as u16);
+                asm.b_cond(Cond::Ne, 8);
+                asm.mov_imm(regs::TMP0, value_tags::TAG_FLOAT as u16);
+                asm.b(20); // -> done
                 // Tagged: addr = heap_base + ref_bytes + 8 + idx * 16
                 asm.lsl_imm(regs::TMP2, regs::TMP2, 4); // idx * 16
-                asm.add_imm(regs::TMP2, regs::TMP2, 8); // + 8 (skip header)
                 asm.add(regs::TMP1, regs::TMP1, regs::TMP2);
-                asm.ldr(regs::TMP0, regs::TMP1, 0); // tag
-                asm.ldr(regs::TMP2, regs::TMP1, 8); // payload
+                asm.ldr(regs::TMP0, regs::TMP1, 8); // tag
+                asm.ldr(regs::TMP2, regs::TMP1, 16); // payload
+                // done
                 asm.str(regs::TMP2, regs::FRAME_BASE, Self::vreg_offset(dst));
                 asm.str(regs::TMP0, regs::FRAME_BASE, shadow_off);
             }
@@ -1799,6 +1940,18 @@ impl MicroOpJitCompiler {
         Ok(())
     }
 
+    /// Load the element kind of the object at TMP1 (heap_base + ref) into
+    /// TMP3 (3 instructions).
+    fn emit_header_elem_kind(asm: &mut AArch64Assembler) {
+        asm.ldr(regs::TMP3, regs::TMP1, 0);
+        asm.lsl_imm(
+            regs::TMP3,
+            regs::TMP3,
+            64 - (HEADER_ELEM_KIND_SHIFT as u8 + 3),
+        );
+        asm.lsr_imm(regs::TMP3, regs::TMP3, 61);
+    }
+
     /// Emit HeapStore: heap[dst_obj][offset] = src (static offset field store).
     /// Reads tag from shadow area; stores tag+payload to heap.
     fn emit_heap_store(&mut self, dst_obj: &VReg, offset: usize, src: &VReg) -> Result<(), String> {
@@ -1859,18 +2012,31 @@ impl MicroOpJitCompiler {
                 asm.str(regs::TMP5, regs::TMP1, 0);
             }
             ElemKind::Tagged => {
-                // Tagged: tag+payload, 16B stride
+                // Untyped access follows the header, like `Heap::write_slot`
                 asm.ldr(regs::TMP4, regs::FRAME_BASE, shadow_off);
                 asm.ldr(regs::TMP5, regs::FRAME_BASE, Self::vreg_offset(src));
                 asm.ldr(regs::TMP2, regs::FRAME_BASE, Self::vreg_offset(idx));
                 asm.ldr(regs::TMP0, regs::FRAME_BASE, Self::vreg_offset(obj));
                 asm.ldr(regs::TMP1, regs::VM_CTX, 48);
                 asm.add(regs::TMP1, regs::TMP1, regs::TMP0);
-                asm.lsl_imm(regs::TMP2, regs::TMP2, 4); // idx * 16
-                asm.add_imm(regs::TMP2, regs::TMP2, 8); // + 8 (skip header)
+                Self::emit_header_elem_kind(&mut asm);
+                asm.cbz(regs::TMP3, 40); // -> Tagged
+                asm.cmp_imm(regs::TMP3, ElemKind::U8 as u16);
+                asm.b_cond(Cond::Ne, 16); // -> I64/Ref/F64
+                // U8: store low byte only
+                asm.add(regs::TMP1, regs::TMP1, regs::TMP2);
+                asm.strb(regs::TMP5, regs::TMP1, 8);
+                asm.b(36); // -> done
+                // I64/Ref/F64: only payload, 8B stride
+                asm.lsl_imm(regs::TMP2, regs::TMP2, 3);
                 asm.add(regs::TMP1, regs::TMP1, regs::TMP2);
-                asm.str(regs::TMP4, regs::TMP1, 0);
                 asm.str(regs::TMP5, regs::TMP1, 8);
+                asm.b(20); // -> done
+                // Tagged: tag+payload, 16B stride
+                asm.lsl_imm(regs::TMP2, regs::TMP2, 4); // idx * 16
+                asm.add(regs::TMP1, regs::TMP1, regs::TMP2);
+                asm.str(regs::TMP4, regs::TMP1, 8);
+                asm.str(regs::TMP5, regs::TMP1, 16);
             }
         }
         Ok(())
@@ -2116,6 +2282,8 @@ impl MicroOpJitCompiler {
             asm.str(Reg::X1, regs::FRAME_BASE, Self::vreg_offset(dst));
             asm.str(Reg::X0, regs::FRAME_BASE, dst_shadow_off);
         }
+        // A negative size (or the heap limit) raises an error
+        self.emit_pending_error_check();
         Ok(())
     }
 
@@ -2138,6 +2306,8 @@ impl MicroOpJitCompiler {
             asm.str(Reg::X1, regs::FRAME_BASE, Self::vreg_offset(dst));
             asm.str(Reg::X0, regs::FRAME_BASE, dst_shadow_off);
         }
+        // The heap limit raises an error
+        self.emit_pending_error_check();
         // 2. Store each arg into the allocated object's slots
         for (i, arg) in args.iter().enumerate() {
             self.emit_heap_store(dst, i, arg)?;
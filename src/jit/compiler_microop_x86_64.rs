@@ -14,15 +14,19 @@ use super::compiler_x86_64::{CompiledCode, CompiledLoop, value_tags};
 #[cfg(target_arch = "x86_64")]
 use super::memory::ExecutableMemory;
 #[cfg(target_arch = "x86_64")]
+use super::stackmap::{FRAME_REF_MAP, JitFrameRecord, frame_ref_map};
+#[cfg(target_arch = "x86_64")]
 use super::x86_64::{Cond, Reg, X86_64Assembler};
 #[cfg(target_arch = "x86_64")]
 use crate::vm::ElemKind;
 #[cfg(target_arch = "x86_64")]
+use crate::vm::Value;
+#[cfg(target_arch = "x86_64")]
 use crate::vm::ValueType;
 #[cfg(target_arch = "x86_64")]
 use crate::vm::microop::{CmpCond, ConvertedFunction, MicroOp, VReg};
 #[cfg(target_arch = "x86_64")]
-use crate::vm::{Function, microop_converter};
+use crate::vm::{Function, HEADER_ELEM_KIND_SHIFT, microop_converter};
 #[cfg(target_arch = "x86_64")]
 use std::collections::{HashMap, HashSet};
 
@@ -91,6 +95,9 @@ pub struct MicroOpJitCompiler {
     /// Code offset after loop_reg_loads (backward jump target).
     /// Forward jumps use labels[ls] (before loads), backward jump uses this (after loads).
     loop_body_offset: Option<usize>,
+    /// Frame reference map linked into the JIT frame chain by the prologue.
+    /// Its buffer address is embedded in the code, so it must not be reallocated.
+    ref_map: Vec<bool>,
 }
 
 /// Kind of forward reference for patching.
@@ -125,6 +132,7 @@ impl MicroOpJitCompiler {
             all_reg_map: HashMap::new(),
             loop_range: None,
             loop_body_offset: None,
+            ref_map: Vec::new(),
         }
     }
 
@@ -153,6 +161,11 @@ impl MicroOpJitCompiler {
                 | MicroOp::MulI64 { dst, .. }
                 | MicroOp::DivI64 { dst, .. }
                 | MicroOp::RemI64 { dst, .. }
+                | MicroOp::DivU64 { dst, .. }
+                | MicroOp::RemU64 { dst, .. }
+                | MicroOp::AddSatI64 { dst, .. }
+                | MicroOp::SubSatI64 { dst, .. }
+                | MicroOp::MulSatI64 { dst, .. }
                 | MicroOp::NegI64 { dst, .. }
                 | MicroOp::AddI64Imm { dst, .. }
                 | MicroOp::AndI64 { dst, .. }
@@ -223,6 +236,7 @@ impl MicroOpJitCompiler {
                     record(&mut vreg_tags, dst.0, u64::MAX);
                 }
                 MicroOp::Call { ret: Some(ret), .. }
+                | MicroOp::TailCall { ret: Some(ret), .. }
                 | MicroOp::CallIndirect { ret: Some(ret), .. } => {
                     record(&mut vreg_tags, ret.0, u64::MAX);
                 }
@@ -264,7 +278,7 @@ impl MicroOpJitCompiler {
                 MicroOp::BrIf { cond, .. } | MicroOp::BrIfFalse { cond, .. } => {
                     mark_read(cond.0);
                 }
-                MicroOp::Call { args, ret, .. } => {
+                MicroOp::Call { args, ret, .. } | MicroOp::TailCall { args, ret, .. } => {
                     for a in args {
                         mark_read(a.0);
                     }
@@ -321,6 +335,11 @@ impl MicroOpJitCompiler {
                 | MicroOp::MulI64 { dst, a, b }
                 | MicroOp::DivI64 { dst, a, b }
                 | MicroOp::RemI64 { dst, a, b }
+                | MicroOp::DivU64 { dst, a, b }
+                | MicroOp::RemU64 { dst, a, b }
+                | MicroOp::AddSatI64 { dst, a, b }
+                | MicroOp::SubSatI64 { dst, a, b }
+                | MicroOp::MulSatI64 { dst, a, b }
                 | MicroOp::AndI64 { dst, a, b }
                 | MicroOp::OrI64 { dst, a, b }
                 | MicroOp::XorI64 { dst, a, b }
@@ -442,6 +461,10 @@ impl MicroOpJitCompiler {
                     mark_write(dst.0);
                 }
 
+                MicroOp::TypeTag { obj, .. } => {
+                    mark_read(obj.0);
+                }
+
                 // Stack bridge
                 MicroOp::StackPush { src } => {
                     mark_read(src.0);
@@ -523,7 +546,7 @@ impl MicroOpJitCompiler {
                 MicroOp::BrIf { cond, .. } | MicroOp::BrIfFalse { cond, .. } => {
                     mark_read(cond.0);
                 }
-                MicroOp::Call { args, ret, .. } => {
+                MicroOp::Call { args, ret, .. } | MicroOp::TailCall { args, ret, .. } => {
                     for a in args {
                         mark_read(a.0);
                     }
@@ -576,6 +599,11 @@ impl MicroOpJitCompiler {
                 | MicroOp::MulI64 { dst, a, b }
                 | MicroOp::DivI64 { dst, a, b }
                 | MicroOp::RemI64 { dst, a, b }
+                | MicroOp::DivU64 { dst, a, b }
+                | MicroOp::RemU64 { dst, a, b }
+                | MicroOp::AddSatI64 { dst, a, b }
+                | MicroOp::SubSatI64 { dst, a, b }
+                | MicroOp::MulSatI64 { dst, a, b }
                 | MicroOp::AndI64 { dst, a, b }
                 | MicroOp::OrI64 { dst, a, b }
                 | MicroOp::XorI64 { dst, a, b }
@@ -685,6 +713,9 @@ impl MicroOpJitCompiler {
                     mark_read(iface_desc.0);
                     mark_write(dst.0);
                 }
+                MicroOp::TypeTag { obj, .. } => {
+                    mark_read(obj.0);
+                }
                 MicroOp::StackPush { src } => {
                     mark_read(src.0);
                 }
@@ -737,18 +768,17 @@ impl MicroOpJitCompiler {
                 _ => {
                     // Track all VRegs read by non-cmp ops
                     match &ops[pc] {
-                        MicroOp::BrIfFalse { cond, .. } | MicroOp::BrIf { cond, .. } => {
-                            // This is fine if it's part of a fused pair (handled above)
-                            // But if the cond is read elsewhere, mark it
+                        // This is fine if it's part of a fused pair (handled above)
+                        // But if the cond is read elsewhere, mark it
+                        MicroOp::BrIfFalse { cond, .. } | MicroOp::BrIf { cond, .. }
                             if pc == loop_start
                                 || !matches!(
                                     &ops[pc - 1],
                                     MicroOp::CmpI64 { dst, .. } | MicroOp::CmpI64Imm { dst, .. }
                                     if dst == cond
-                                )
-                            {
-                                non_fused_readers.insert(cond.0);
-                            }
+                                ) =>
+                        {
+                            non_fused_readers.insert(cond.0);
                         }
                         MicroOp::AddI64 { a, b, .. }
                         | MicroOp::SubI64 { a, b, .. }
@@ -798,6 +828,11 @@ impl MicroOpJitCompiler {
                 | MicroOp::MulI64 { dst, .. }
                 | MicroOp::DivI64 { dst, .. }
                 | MicroOp::RemI64 { dst, .. }
+                | MicroOp::DivU64 { dst, .. }
+                | MicroOp::RemU64 { dst, .. }
+                | MicroOp::AddSatI64 { dst, .. }
+                | MicroOp::SubSatI64 { dst, .. }
+                | MicroOp::MulSatI64 { dst, .. }
                 | MicroOp::NegI64 { dst, .. }
                 | MicroOp::AddI64Imm { dst, .. }
                 | MicroOp::AndI64 { dst, .. }
@@ -1015,6 +1050,7 @@ impl MicroOpJitCompiler {
 
         // Pre-scan for inlinable call targets
         self.scan_inline_candidates(&converted.micro_ops, all_functions);
+        self.ref_map = self.build_ref_map(&converted.micro_ops);
 
         // Emit prologue and shadow tag initialization
         self.emit_prologue();
@@ -1307,7 +1343,7 @@ impl MicroOpJitCompiler {
         Ok(CompiledCode {
             memory,
             entry_offset: 0,
-            stack_map: HashMap::new(),
+            stack_map: HashMap::from([(FRAME_REF_MAP, self.ref_map)]),
             total_regs: self.total_regs,
         })
     }
@@ -1325,6 +1361,13 @@ impl MicroOpJitCompiler {
         loop_end_op_pc: usize,
         all_functions: &[Function],
     ) -> Result<CompiledLoop, String> {
+        // Every exit must land where the interpreter resumes
+        if let Some(target) =
+            converted.loop_exit_outside(loop_start_microop_pc, loop_end_microop_pc)
+        {
+            return Err(format!("Loop exits to MicroOp PC {}", target));
+        }
+
         self.total_regs = locals_count + converted.temps_count;
         self.self_func_index = func_index;
         self.self_locals_count = locals_count;
@@ -1347,6 +1390,7 @@ impl MicroOpJitCompiler {
 
         // Pre-scan for inlinable call targets
         self.scan_inline_candidates(&converted.micro_ops, all_functions);
+        self.ref_map = self.build_ref_map(&converted.micro_ops);
 
         // Emit prologue and shadow tag initialization
         self.emit_prologue();
@@ -1535,7 +1579,7 @@ impl MicroOpJitCompiler {
             entry_offset: 0,
             loop_start_pc: loop_start_op_pc,
             loop_end_pc: loop_end_op_pc,
-            stack_map: HashMap::new(),
+            stack_map: HashMap::from([(FRAME_REF_MAP, self.ref_map)]),
             total_regs: self.total_regs,
         })
     }
@@ -1565,6 +1609,10 @@ impl MicroOpJitCompiler {
     // ==================== Prologue / Epilogue ====================
 
     fn emit_prologue(&mut self) {
+        let shadow_offset = (self.total_regs * 8) as i32;
+        let ref_len = self.ref_map.len() as i64;
+        let ref_map_ptr = self.ref_map.as_ptr() as i64;
+        let links = self.links_frame_record();
         let mut asm = X86_64Assembler::new(&mut self.buf);
         // Save callee-saved registers
         asm.push(Reg::Rbp);
@@ -1574,13 +1622,74 @@ impl MicroOpJitCompiler {
         asm.push(Reg::R13);
         asm.push(Reg::R14);
         asm.push(Reg::R15);
-        // We pushed 6 registers (rbp + 5) = 6 pushes. With the return address that's 7 * 8 = 56.
-        // 56 mod 16 = 8, so RSP is 8-byte aligned but not 16-byte aligned.
-        // Add sub rsp, 8 to align to 16 bytes before any CALL.
-        asm.sub_ri32(Reg::Rsp, 8);
         // Set up context registers: RDI=ctx, RSI=frame_base
         asm.mov_rr(regs::VM_CTX, Reg::Rdi);
         asm.mov_rr(regs::FRAME_BASE, Reg::Rsi);
+        if !links {
+            // Nothing to scan. 6 pushes + return address + 1 padding word
+            // = 64 bytes, keeping RSP 16-byte aligned before any CALL.
+            asm.sub_ri32(Reg::Rsp, 8);
+            return;
+        }
+        // Link a JitFrameRecord (5 words, pushed last field first) into the
+        // VM's frame chain so GC can scan this frame while helpers run.
+        // 6 pushes + return address + 5 record words = 96 bytes, keeping
+        // RSP 16-byte aligned before any CALL.
+        asm.mov_rm(regs::TMP0, regs::VM_CTX, Self::JIT_FRAMES_OFFSET);
+        asm.mov_rm(regs::TMP1, regs::TMP0, 0);
+        asm.push(regs::TMP1); // prev
+        asm.mov_rr(regs::TMP1, regs::FRAME_BASE);
+        asm.add_ri32(regs::TMP1, shadow_offset);
+        asm.push(regs::TMP1); // shadow_tags
+        asm.push(regs::FRAME_BASE); // frame
+        asm.mov_ri64(regs::TMP1, ref_len);
+        asm.push(regs::TMP1); // ref_len
+        asm.mov_ri64(regs::TMP1, ref_map_ptr);
+        asm.push(regs::TMP1); // ref_map
+        asm.mov_mr(regs::TMP0, 0, Reg::Rsp);
+    }
+
+    /// Whether the prologue links a JitFrameRecord: only frames that may hold
+    /// references need scanning, and linking costs every call.
+    fn links_frame_record(&self) -> bool {
+        self.ref_map.iter().any(|&is_ref| is_ref)
+    }
+
+    /// Size of what the prologue pushes after the callee-saved registers:
+    /// the JitFrameRecord, or a padding word.
+    fn frame_record_size(&self) -> i32 {
+        if self.links_frame_record() {
+            JitFrameRecord::SIZE
+        } else {
+            8
+        }
+    }
+
+    /// Unlink this frame's JitFrameRecord (if `links`) and pop it.
+    /// Clobbers TMP1 and TMP4 only, so RAX/RDX return values are preserved.
+    fn emit_unlink_frame_record(asm: &mut X86_64Assembler, links: bool) {
+        if !links {
+            asm.add_ri32(Reg::Rsp, 8);
+            return;
+        }
+        asm.mov_rm(regs::TMP4, regs::VM_CTX, Self::JIT_FRAMES_OFFSET);
+        asm.mov_rm(regs::TMP1, Reg::Rsp, JitFrameRecord::PREV_OFFSET);
+        asm.mov_mr(regs::TMP4, 0, regs::TMP1);
+        asm.add_ri32(Reg::Rsp, JitFrameRecord::SIZE);
+    }
+
+    /// Build the frame reference map: VRegs typed as references either
+    /// function-wide or by any write in the function body.
+    fn build_ref_map(&self, ops: &[MicroOp]) -> Vec<bool> {
+        let mut types = self.vreg_types.clone();
+        if !ops.is_empty() {
+            Self::override_vreg_types_for_loop(&mut types, ops, 0, ops.len() - 1);
+        }
+        frame_ref_map(&self.vreg_types, self.total_regs)
+            .into_iter()
+            .zip(frame_ref_map(&types, self.total_regs))
+            .map(|(a, b)| a || b)
+            .collect()
     }
 
     /// Initialize the shadow tag area from vreg_types.
@@ -1597,8 +1706,9 @@ impl MicroOpJitCompiler {
     }
 
     fn emit_epilogue(&mut self) {
+        let links = self.links_frame_record();
         let mut asm = X86_64Assembler::new(&mut self.buf);
-        asm.add_ri32(Reg::Rsp, 8);
+        Self::emit_unlink_frame_record(&mut asm, links);
         asm.pop(Reg::R15);
         asm.pop(Reg::R14);
         asm.pop(Reg::R13);
@@ -1629,6 +1739,11 @@ impl MicroOpJitCompiler {
                 | MicroOp::MulI64 { .. }
                 | MicroOp::DivI64 { .. }
                 | MicroOp::RemI64 { .. }
+                | MicroOp::DivU64 { .. }
+                | MicroOp::RemU64 { .. }
+                | MicroOp::AddSatI64 { .. }
+                | MicroOp::SubSatI64 { .. }
+                | MicroOp::MulSatI64 { .. }
                 | MicroOp::NegI64 { .. }
                 | MicroOp::AddI64Imm { .. }
                 | MicroOp::CmpI64 { .. }
@@ -1654,7 +1769,7 @@ impl MicroOpJitCompiler {
         let mut max_non_arg_vregs = 0usize;
 
         for op in ops {
-            if let MicroOp::Call { func_id, .. } = op {
+            if let MicroOp::Call { func_id, .. } | MicroOp::TailCall { func_id, .. } = op {
                 let func_id = *func_id;
                 if func_id == self.self_func_index {
                     continue; // Skip self-recursion
@@ -1758,6 +1873,31 @@ impl MicroOpJitCompiler {
                 a: Self::remap_vreg(a, vreg_map),
                 b: Self::remap_vreg(b, vreg_map),
             },
+            MicroOp::DivU64 { dst, a, b } => MicroOp::DivU64 {
+                dst: Self::remap_vreg(dst, vreg_map),
+                a: Self::remap_vreg(a, vreg_map),
+                b: Self::remap_vreg(b, vreg_map),
+            },
+            MicroOp::RemU64 { dst, a, b } => MicroOp::RemU64 {
+                dst: Self::remap_vreg(dst, vreg_map),
+                a: Self::remap_vreg(a, vreg_map),
+                b: Self::remap_vreg(b, vreg_map),
+            },
+            MicroOp::AddSatI64 { dst, a, b } => MicroOp::AddSatI64 {
+                dst: Self::remap_vreg(dst, vreg_map),
+                a: Self::remap_vreg(a, vreg_map),
+                b: Self::remap_vreg(b, vreg_map),
+            },
+            MicroOp::SubSatI64 { dst, a, b } => MicroOp::SubSatI64 {
+                dst: Self::remap_vreg(dst, vreg_map),
+                a: Self::remap_vreg(a, vreg_map),
+                b: Self::remap_vreg(b, vreg_map),
+            },
+            MicroOp::MulSatI64 { dst, a, b } => MicroOp::MulSatI64 {
+                dst: Self::remap_vreg(dst, vreg_map),
+                a: Self::remap_vreg(a, vreg_map),
+                b: Self::remap_vreg(b, vreg_map),
+            },
             MicroOp::NegI64 { dst, src } => MicroOp::NegI64 {
                 dst: Self::remap_vreg(dst, vreg_map),
                 src: Self::remap_vreg(src, vreg_map),
@@ -1896,6 +2036,11 @@ impl MicroOpJitCompiler {
             MicroOp::MulI64 { dst, a, b } => self.emit_binop_i64(dst, a, b, BinOp::Mul),
             MicroOp::DivI64 { dst, a, b } => self.emit_binop_i64(dst, a, b, BinOp::Div),
             MicroOp::RemI64 { dst, a, b } => self.emit_rem_i64(dst, a, b),
+            MicroOp::DivU64 { dst, a, b } => self.emit_divrem_u64(dst, a, b, false),
+            MicroOp::RemU64 { dst, a, b } => self.emit_divrem_u64(dst, a, b, true),
+            MicroOp::AddSatI64 { dst, a, b } => self.emit_sat_i64(dst, a, b, BinOp::Add),
+            MicroOp::SubSatI64 { dst, a, b } => self.emit_sat_i64(dst, a, b, BinOp::Sub),
+            MicroOp::MulSatI64 { dst, a, b } => self.emit_sat_i64(dst, a, b, BinOp::Mul),
             MicroOp::NegI64 { dst, src } => self.emit_neg_i64(dst, src),
             MicroOp::AddI64Imm { dst, a, imm } => self.emit_add_i64_imm(dst, a, *imm),
             MicroOp::AndI64 { dst, a, b } => self.emit_binop_i64(dst, a, b, BinOp::And),
@@ -1917,6 +2062,10 @@ impl MicroOpJitCompiler {
             MicroOp::Jmp { target, .. } => self.emit_jmp(*target),
 
             MicroOp::Call { func_id, args, ret } => self.emit_call(*func_id, args, ret.as_ref()),
+            // A native call can't replace the caller's frame, so functions
+            // with tail calls stay in the interpreter, which runs them in
+            // constant stack
+            MicroOp::TailCall { .. } => Err("TailCall is not supported by the JIT".to_string()),
             MicroOp::Ret { src } => self.emit_ret(src.as_ref()),
 
             MicroOp::HeapLoad { dst, src, offset } => self.emit_heap_load(dst, src, *offset),
@@ -1991,7 +2140,7 @@ impl MicroOpJitCompiler {
             MicroOp::I64TruncF32S { dst, src } => self.emit_i64_trunc_f64s(dst, src),
             MicroOp::F32DemoteF64 { dst, src } => self.emit_mov(dst, src),
             MicroOp::F64PromoteF32 { dst, src } => self.emit_mov(dst, src),
-            MicroOp::F64ReinterpretAsI64 { dst, src } => self.emit_mov(dst, src),
+            MicroOp::F64ReinterpretAsI64 { dst, src } => self.emit_f64_reinterpret_as_i64(dst, src),
 
             // Ref ops
             MicroOp::RefEq { dst, a, b } => self.emit_ref_eq(dst, a, b),
@@ -2015,6 +2164,19 @@ impl MicroOpJitCompiler {
             // Stack bridge (spill/restore across calls)
             MicroOp::StackPush { src } => self.emit_stack_push(src),
             MicroOp::StackPop { dst } => self.emit_stack_pop(dst),
+            // Structs allocated by JIT code print without their type name
+            MicroOp::TypeTag { .. } => Ok(()),
+            // GC doesn't run inside JIT code, so allocation hints are dropped,
+            // and breakpoints only mean something to the debugger
+            MicroOp::Raw {
+                op: crate::vm::Op::GcHint(_) | crate::vm::Op::DebugBreak,
+            } => Ok(()),
+            MicroOp::Raw {
+                op: crate::vm::Op::Unreachable,
+            } => {
+                self.emit_unreachable();
+                Ok(())
+            }
 
             _ => Err(format!(
                 "Unsupported MicroOp for JIT: {:?}",
@@ -2129,6 +2291,68 @@ impl MicroOpJitCompiler {
         Ok(())
     }
 
+    fn emit_divrem_u64(&mut self, dst: &VReg, a: &VReg, b: &VReg, rem: bool) -> Result<(), String> {
+        let shadow = self.needs_shadow_update(dst, value_tags::TAG_INT);
+        let reg_map = &self.all_reg_map;
+        let mut asm = X86_64Assembler::new(&mut self.buf);
+        Self::load_vreg(&mut asm, regs::TMP0, a, reg_map);
+        Self::load_vreg(&mut asm, regs::TMP1, b, reg_map);
+        // Zero-extend RAX into RDX:RAX for the unsigned divide
+        asm.xor_rr(regs::TMP2, regs::TMP2);
+        asm.div(regs::TMP1);
+        // Quotient is in RAX (TMP0), remainder in RDX (TMP2)
+        let result = if rem { regs::TMP2 } else { regs::TMP0 };
+        Self::store_vreg(&mut asm, result, dst, reg_map);
+        if let Some(off) = shadow {
+            Self::emit_shadow_update(&mut asm, off, value_tags::TAG_INT);
+        }
+        Ok(())
+    }
+
+    fn emit_sat_i64(&mut self, dst: &VReg, a: &VReg, b: &VReg, op: BinOp) -> Result<(), String> {
+        let shadow = self.needs_shadow_update(dst, value_tags::TAG_INT);
+        let reg_map = &self.all_reg_map;
+        {
+            let mut asm = X86_64Assembler::new(&mut self.buf);
+            Self::load_vreg(&mut asm, regs::TMP0, a, reg_map);
+            Self::load_vreg(&mut asm, regs::TMP1, b, reg_map);
+            asm.mov_rr(regs::TMP2, regs::TMP0);
+            match op {
+                BinOp::Add => asm.add_rr(regs::TMP0, regs::TMP1),
+                BinOp::Sub => asm.sub_rr(regs::TMP0, regs::TMP1),
+                BinOp::Mul => asm.imul_rr(regs::TMP0, regs::TMP1),
+                _ => unreachable!(),
+            }
+        }
+        let jno_offset = self.buf.len();
+        {
+            let mut asm = X86_64Assembler::new(&mut self.buf);
+            asm.jcc_rel8(Cond::No, 0); // placeholder, patched below
+            // Overflowed: the sign of the true result picks i64::MIN or i64::MAX.
+            // add: sign(b), sub: !sign(b), mul: sign(a) ^ sign(b)
+            asm.mov_rr(regs::TMP0, regs::TMP1);
+            if matches!(op, BinOp::Mul) {
+                asm.xor_rr(regs::TMP0, regs::TMP2);
+            }
+            asm.sar_ri(regs::TMP0, 63);
+            let bound = if matches!(op, BinOp::Sub) {
+                i64::MIN
+            } else {
+                i64::MAX
+            };
+            asm.mov_ri64(regs::TMP3, bound);
+            asm.xor_rr(regs::TMP0, regs::TMP3);
+        }
+        let skip = self.buf.len() - (jno_offset + 2);
+        self.buf.code_mut()[jno_offset + 1] = skip as u8;
+        let mut asm = X86_64Assembler::new(&mut self.buf);
+        Self::store_vreg(&mut asm, regs::TMP0, dst, reg_map);
+        if let Some(off) = shadow {
+            Self::emit_shadow_update(&mut asm, off, value_tags::TAG_INT);
+        }
+        Ok(())
+    }
+
     fn emit_shl_i64(&mut self, dst: &VReg, a: &VReg, b: &VReg) -> Result<(), String> {
         let shadow = self.needs_shadow_update(dst, value_tags::TAG_INT);
         let reg_map = &self.all_reg_map;
@@ -2688,6 +2912,47 @@ impl MicroOpJitCompiler {
 
     /// JitCallContext offset for jit_function_table pointer.
     const JIT_FUNC_TABLE_OFFSET: i32 = 80;
+    /// Offset of `jit_frames` in JitCallContext.
+    const JIT_FRAMES_OFFSET: i32 = 88;
+    /// Offset of `pending_error` in JitCallContext.
+    const PENDING_ERROR_OFFSET: i32 = 96;
+
+    /// Return from the compiled code if the call just made raised an error.
+    /// The VM re-raises the error once native code has returned, so it never
+    /// unwinds across JIT frames. Loop registers were spilled before the call,
+    /// so the frame already holds the current values.
+    fn emit_pending_error_check(&mut self) {
+        // RBP sits above five callee-saved pushes and the frame record
+        let record_rbp_offset = 5 * 8 + self.frame_record_size();
+        {
+            let mut asm = X86_64Assembler::new(&mut self.buf);
+            asm.mov_rm(regs::TMP0, regs::VM_CTX, Self::PENDING_ERROR_OFFSET);
+            asm.test_rr(regs::TMP0, regs::TMP0);
+        }
+        let jcc_site = self.buf.len();
+        {
+            let mut asm = X86_64Assembler::new(&mut self.buf);
+            asm.jcc_rel8(Cond::E, 0);
+            // Drop any values StackPush left on the machine stack
+            asm.mov_rr(Reg::Rsp, Reg::Rbp);
+            asm.sub_ri32(Reg::Rsp, record_rbp_offset);
+        }
+        self.emit_epilogue();
+        let skip = self.buf.len() - (jcc_site + 2);
+        debug_assert!(skip < 128, "pending error bail-out too long: {}", skip);
+        self.buf.code_mut()[jcc_site + 1] = skip as u8;
+    }
+
+    /// Bail out with the pending error flag set and no VM error recorded,
+    /// which the VM reports as reaching unreachable code.
+    fn emit_unreachable(&mut self) {
+        {
+            let mut asm = X86_64Assembler::new(&mut self.buf);
+            asm.mov_ri32(regs::TMP0, 1);
+            asm.mov_mr(regs::VM_CTX, Self::PENDING_ERROR_OFFSET, regs::TMP0);
+        }
+        self.emit_epilogue();
+    }
 
     fn emit_call(
         &mut self,
@@ -2915,6 +3180,8 @@ impl MicroOpJitCompiler {
             self.buf.code_mut()[jmp_done_site + 1..jmp_done_site + 5].copy_from_slice(&bytes);
         }
 
+        self.emit_pending_error_check();
+
         Ok(())
     }
 
@@ -2990,6 +3257,8 @@ impl MicroOpJitCompiler {
         // Reload loop-variant registers (R10/R11 are caller-saved)
         self.emit_loop_reg_reloads();
 
+        self.emit_pending_error_check();
+
         Ok(())
     }
 
@@ -3091,6 +3360,8 @@ impl MicroOpJitCompiler {
         // Reload loop-variant registers (R10/R11 are caller-saved)
         self.emit_loop_reg_reloads();
 
+        self.emit_pending_error_check();
+
         Ok(())
     }
 
@@ -3112,8 +3383,9 @@ impl MicroOpJitCompiler {
         }
 
         // Inline epilogue
+        let links = self.links_frame_record();
         let mut asm = X86_64Assembler::new(&mut self.buf);
-        asm.add_ri32(Reg::Rsp, 8);
+        Self::emit_unlink_frame_record(&mut asm, links);
         asm.pop(Reg::R15);
         asm.pop(Reg::R14);
         asm.pop(Reg::R13);
@@ -3283,6 +3555,35 @@ impl MicroOpJitCompiler {
         Ok(())
     }
 
+    /// Float bits as an int, with every NaN canonicalized like the interpreter does.
+    fn emit_f64_reinterpret_as_i64(&mut self, dst: &VReg, src: &VReg) -> Result<(), String> {
+        let shadow = self.needs_shadow_update(dst, value_tags::TAG_INT);
+        let reg_map = &self.all_reg_map;
+        {
+            let mut asm = X86_64Assembler::new(&mut self.buf);
+            Self::load_vreg(&mut asm, regs::TMP0, src, reg_map);
+            // NaN iff the bits without the sign are above those of infinity
+            asm.mov_rr(regs::TMP1, regs::TMP0);
+            asm.shl_ri(regs::TMP1, 1);
+            asm.mov_ri64(regs::TMP2, (f64::INFINITY.to_bits() << 1) as i64);
+            asm.cmp_rr(regs::TMP1, regs::TMP2);
+        }
+        let jbe_offset = self.buf.len();
+        {
+            let mut asm = X86_64Assembler::new(&mut self.buf);
+            asm.jcc_rel8(Cond::Be, 0); // placeholder, patched below
+            asm.mov_ri64(regs::TMP0, Value::CANONICAL_NAN_BITS as i64);
+        }
+        let skip = self.buf.len() - (jbe_offset + 2);
+        self.buf.code_mut()[jbe_offset + 1] = skip as u8;
+        let mut asm = X86_64Assembler::new(&mut self.buf);
+        Self::store_vreg(&mut asm, regs::TMP0, dst, reg_map);
+        if let Some(off) = shadow {
+            Self::emit_shadow_update(&mut asm, off, value_tags::TAG_INT);
+        }
+        Ok(())
+    }
+
     // ==================== Ref Operations ====================
 
     fn emit_ref_eq(&mut self, dst: &VReg, a: &VReg, b: &VReg) -> Result<(), String> {
@@ -3459,6 +3760,8 @@ impl MicroOpJitCompiler {
         // Reload loop-variant registers after helper call
         // (if dst is a loop-reg, reload picks up the new value from frame)
         self.emit_loop_reg_reloads();
+        // A negative size (or the heap limit) raises an error
+        self.emit_pending_error_check();
         Ok(())
     }
 
@@ -3488,6 +3791,8 @@ impl MicroOpJitCompiler {
         }
         // Reload loop-variant registers after helper call
         self.emit_loop_reg_reloads();
+        // The heap limit raises an error
+        self.emit_pending_error_check();
         // 2. Store each arg into the allocated object's slots
         for (i, arg) in args.iter().enumerate() {
             self.emit_heap_store(dst, i, arg)?;
@@ -3597,12 +3902,52 @@ impl MicroOpJitCompiler {
                 asm.mov_mr(regs::FRAME_BASE, shadow_off, regs::TMP0);
             }
             ElemKind::Tagged => {
+                // Untyped access (`__heap_load`, fields) follows the element
+                // kind in the header, like `Heap::read_slot`
+                let (tagged, wide) = self.emit_header_elem_kind_dispatch();
+                let mut asm = X86_64Assembler::new(&mut self.buf);
+                // U8: addr = heap_base + ref_bytes + 8 + idx
+                asm.add_rr(regs::TMP1, regs::TMP2);
+                asm.movzx_rm_byte(regs::TMP2, regs::TMP1, 8);
+                asm.mov_ri64(regs::TMP0, value_tags::TAG_INT as i64);
+                let u8_done = self.buf.len();
+                X86_64Assembler::new(&mut self.buf).jmp_rel8(0);
+
+                // I64/Ref/F64: addr = heap_base + ref_bytes + 8 + idx * 8
+                self.patch_rel8_here(wide);
+                let mut asm = X86_64Assembler::new(&mut self.buf);
+                asm.shl_ri(regs::TMP2, 3);
+                asm.add_rr(regs::TMP1, regs::TMP2);
+                asm.mov_rm(regs::TMP2, regs::TMP1, 8);
+                asm.mov_ri64(regs::TMP0, value_tags::TAG_INT as i64);
+                asm.cmp_ri32(regs::TMP3, ElemKind::Ref as i32);
+                let not_ref = self.buf.len();
+                X86_64Assembler::new(&mut self.buf).jcc_rel8(Cond::Ne, 0);
+                X86_64Assembler::new(&mut self.buf)
+                    .mov_ri64(regs::TMP0, value_tags::TAG_PTR as i64);
+                self.patch_rel8_here(not_ref);
+                let mut asm = X86_64Assembler::new(&mut self.buf);
+                asm.cmp_ri32(regs::TMP3, ElemKind::F64 as i32);
+                let not_f64 = self.buf.len();
+                X86_64Assembler::new(&mut self.buf).jcc_rel8(Cond::Ne, 0);
+                X86_64Assembler::new(&mut self.buf)
+                    .mov_ri64(regs::TMP0, value_tags::TAG_FLOAT as i64);
+                self.patch_rel8_here(not_f64);
+                let wide_done = self.buf.len();
+                X86_64Assembler::new(&mut self.buf).jmp_rel8(0);
+
                 // Tagged: addr = heap_base + ref_bytes + 8 + idx * 16
+                self.patch_rel8_here(tagged);
+                let mut asm = X86_64Assembler::new(&mut self.buf);
                 asm.shl_ri(regs::TMP2, 4); // idx * 16
-                asm.add_ri32(regs::TMP2, 8); // + 8 (skip header)
                 asm.add_rr(regs::TMP1, regs::TMP2);
-                asm.mov_rm(regs::TMP0, regs::TMP1, 0); // tag
-                asm.mov_rm(regs::TMP2, regs::TMP1, 8); // payload
+                asm.mov_rm(regs::TMP0, regs::TMP1, 8); // tag
+                asm.mov_rm(regs::TMP2, regs::TMP1, 16); // payload
+
+                self.patch_rel8_here(u8_done);
+                self.patch_rel8_here(wide_done);
+                let reg_map = &self.all_reg_map;
+                let mut asm = X86_64Assembler::new(&mut self.buf);
                 Self::store_vreg(&mut asm, regs::TMP2, dst, reg_map);
                 asm.mov_mr(regs::FRAME_BASE, shadow_off, regs::TMP0);
             }
@@ -3610,6 +3955,30 @@ impl MicroOpJitCompiler {
         Ok(())
     }
 
+    /// Branch on the element kind of the object at TMP1 (heap_base + ref),
+    /// leaving the kind in TMP3 and falling through for U8. Returns the sites
+    /// of the jumps to the Tagged and the 8-byte (I64/Ref/F64) paths, to be
+    /// patched with [`Self::patch_rel8_here`].
+    fn emit_header_elem_kind_dispatch(&mut self) -> (usize, usize) {
+        let mut asm = X86_64Assembler::new(&mut self.buf);
+        asm.mov_rm(regs::TMP3, regs::TMP1, 0);
+        asm.shr_ri(regs::TMP3, HEADER_ELEM_KIND_SHIFT as u8);
+        asm.and_ri32(regs::TMP3, 0b111);
+        let tagged = self.buf.len();
+        X86_64Assembler::new(&mut self.buf).jcc_rel8(Cond::E, 0);
+        X86_64Assembler::new(&mut self.buf).cmp_ri32(regs::TMP3, ElemKind::U8 as i32);
+        let wide = self.buf.len();
+        X86_64Assembler::new(&mut self.buf).jcc_rel8(Cond::Ne, 0);
+        (tagged, wide)
+    }
+
+    /// Point the 2-byte jump at `site` to the current position.
+    fn patch_rel8_here(&mut self, site: usize) {
+        let rel = self.buf.len() - (site + 2);
+        debug_assert!(rel < 128, "rel8 jump too long: {}", rel);
+        self.buf.code_mut()[site + 1] = rel as u8;
+    }
+
     /// Emit HeapStore: heap[dst_obj][offset] = src (static offset field store).
     /// Reads tag from shadow area (set by HeapLoad); stores tag+payload to heap.
     fn emit_heap_store(&mut self, dst_obj: &VReg, offset: usize, src: &VReg) -> Result<(), String> {
@@ -3672,18 +4041,40 @@ impl MicroOpJitCompiler {
                 asm.mov_mr(regs::TMP1, 0, regs::TMP5);
             }
             ElemKind::Tagged => {
-                // Tagged: tag+payload, 16B stride
+                // Untyped access follows the header, like `Heap::write_slot`
                 asm.mov_rm(regs::TMP4, regs::FRAME_BASE, shadow_off);
                 Self::load_vreg(&mut asm, regs::TMP5, src, reg_map);
                 Self::load_vreg(&mut asm, regs::TMP2, idx, reg_map);
                 Self::load_vreg(&mut asm, regs::TMP0, obj, reg_map);
                 asm.mov_rm(regs::TMP1, regs::VM_CTX, 48);
                 asm.add_rr(regs::TMP1, regs::TMP0);
-                asm.shl_ri(regs::TMP2, 4); // idx * 16
-                asm.add_ri32(regs::TMP2, 8); // + 8 (skip header)
+                let (tagged, wide) = self.emit_header_elem_kind_dispatch();
+                // U8: store low byte only
+                let mut asm = X86_64Assembler::new(&mut self.buf);
+                asm.add_rr(regs::TMP1, regs::TMP2);
+                asm.mov_mr_byte(regs::TMP1, 8, regs::TMP5);
+                let u8_done = self.buf.len();
+                X86_64Assembler::new(&mut self.buf).jmp_rel8(0);
+
+                // I64/Ref/F64: only payload, 8B stride
+                self.patch_rel8_here(wide);
+                let mut asm = X86_64Assembler::new(&mut self.buf);
+                asm.shl_ri(regs::TMP2, 3);
                 asm.add_rr(regs::TMP1, regs::TMP2);
-                asm.mov_mr(regs::TMP1, 0, regs::TMP4);
                 asm.mov_mr(regs::TMP1, 8, regs::TMP5);
+                let wide_done = self.buf.len();
+                X86_64Assembler::new(&mut self.buf).jmp_rel8(0);
+
+                // Tagged: tag+payload, 16B stride
+                self.patch_rel8_here(tagged);
+                let mut asm = X86_64Assembler::new(&mut self.buf);
+                asm.shl_ri(regs::TMP2, 4); // idx * 16
+                asm.add_rr(regs::TMP1, regs::TMP2);
+                asm.mov_mr(regs::TMP1, 8, regs::TMP4);
+                asm.mov_mr(regs::TMP1, 16, regs::TMP5);
+
+                self.patch_rel8_here(u8_done);
+                self.patch_rel8_here(wide_done);
             }
         }
         Ok(())
@@ -2,6 +2,7 @@
 //!
 //! VM uses Rust enum `Value`, JIT uses 128-bit (tag: u64, payload: u64) format.
 
+use super::stackmap::JitFrameRecord;
 use crate::vm::Value;
 
 /// Value tags for JIT representation.
@@ -200,6 +201,14 @@ pub struct JitCallContext {
     /// Layout: [entry_0, total_regs_0, entry_1, total_regs_1, ...] (u64 pairs).
     /// entry == 0 means the function is not yet JIT-compiled.
     pub jit_function_table: *const u64,
+    /// Pointer to the VM's head of the active JIT frame chain.
+    /// Compiled prologues link a `JitFrameRecord` here so GC can scan the frame.
+    pub jit_frames: *mut *const JitFrameRecord,
+    /// Nonzero when a call made from JIT code raised an uncaught error, or when
+    /// compiled code reached an `Op::Unreachable`.
+    /// Compiled code checks this after every call and returns immediately so
+    /// the error is re-raised by the VM instead of unwinding through native frames.
+    pub pending_error: u64,
 }
 
 /// Type signature for call helper function.
@@ -232,13 +241,13 @@ mod tests {
 
     #[test]
     fn test_float_roundtrip() {
-        let value = Value::F64(3.14);
+        let value = Value::F64(2.5);
         let jit_val = JitValue::from_value(&value);
         assert_eq!(jit_val.tag, tags::TAG_FLOAT);
 
         let back = jit_val.to_value();
         if let Value::F64(f) = back {
-            assert!((f - 3.14).abs() < 0.0001);
+            assert!((f - 2.5).abs() < 0.0001);
         } else {
             panic!("Expected Float");
         }
@@ -5,6 +5,9 @@
 
 use std::collections::HashMap;
 
+use super::marshal::tags::TAG_PTR;
+use crate::vm::ValueType;
+
 /// A single stack map entry for a safepoint.
 #[derive(Debug, Clone)]
 pub struct StackMapEntry {
@@ -226,6 +229,75 @@ impl Default for StackMapBuilder {
     }
 }
 
+/// Key in `CompiledCode::stack_map` / `CompiledLoop::stack_map` holding the
+/// function-wide reference map used while the frame is active.
+pub(crate) const FRAME_REF_MAP: usize = 0;
+
+/// Build a frame reference map from static VReg types: `map[i]` is true if
+/// VReg `i` may hold a heap reference.
+pub fn frame_ref_map(vreg_types: &[ValueType], total_regs: usize) -> Vec<bool> {
+    let mut map: Vec<bool> = vreg_types
+        .iter()
+        .take(total_regs)
+        .map(|ty| *ty == ValueType::Ref)
+        .collect();
+    map.resize(total_regs, false);
+    map
+}
+
+/// An active JIT frame, linked into the VM's frame chain while native code runs.
+///
+/// Records live on the native stack: the x86-64 prologue pushes one for every
+/// compiled frame whose reference map is non-empty. The AArch64 backend links
+/// none, so the VM defers collection until its native code has returned.
+/// The layout is shared with generated code and must not change.
+#[repr(C)]
+#[derive(Debug)]
+pub struct JitFrameRecord {
+    /// Reference map of the frame (see [`frame_ref_map`]).
+    pub ref_map: *const bool,
+    /// Number of entries in `ref_map`.
+    pub ref_len: usize,
+    /// Base of the frame's payload slots.
+    pub frame: *const u64,
+    /// Runtime tag of each slot, or null if the backend keeps no shadow tags.
+    pub shadow_tags: *const u64,
+    /// The next older active frame, or null.
+    pub prev: *const JitFrameRecord,
+}
+
+impl JitFrameRecord {
+    /// Byte offset of `prev` within the record (used by generated code).
+    pub const PREV_OFFSET: i32 = 32;
+    /// Size of the record in bytes.
+    pub const SIZE: i32 = 40;
+
+    /// Collect the payloads of every slot that may hold a reference, walking
+    /// from `head` through all older frames.
+    ///
+    /// A slot is reported if the reference map marks it, or if its shadow tag
+    /// says it currently holds a pointer. Payloads may be stale, so callers
+    /// must validate them before treating them as roots.
+    ///
+    /// # Safety
+    /// Every record in the chain must point to live frame memory.
+    pub unsafe fn collect_ref_payloads(head: *const JitFrameRecord, out: &mut Vec<u64>) {
+        let mut cur = head;
+        while !cur.is_null() {
+            let record = unsafe { &*cur };
+            for i in 0..record.ref_len {
+                let is_ref = unsafe { *record.ref_map.add(i) }
+                    || (!record.shadow_tags.is_null()
+                        && unsafe { *record.shadow_tags.add(i) } == TAG_PTR);
+                if is_ref {
+                    out.push(unsafe { *record.frame.add(i) });
+                }
+            }
+            cur = record.prev;
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
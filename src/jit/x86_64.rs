@@ -306,6 +306,13 @@ impl<'a> X86_64Assembler<'a> {
         self.buf.emit_u8(Self::modrm(0b11, 7, src.code()));
     }
 
+    /// DIV r64 (unsigned divide RDX:RAX by r64, quotient in RAX, remainder in RDX)
+    pub fn div(&mut self, src: Reg) {
+        self.emit_rex_w_single(src);
+        self.buf.emit_u8(0xF7); // DIV r/m64
+        self.buf.emit_u8(Self::modrm(0b11, 6, src.code()));
+    }
+
     /// CQO (sign-extend RAX into RDX:RAX, needed before IDIV)
     pub fn cqo(&mut self) {
         self.buf.emit_u8(0x48); // REX.W
@@ -925,6 +932,16 @@ mod tests {
         assert_eq!(buf.code(), &[0x48, 0xF7, 0xF9]);
     }
 
+    #[test]
+    fn test_div() {
+        let mut buf = CodeBuffer::new();
+        let mut asm = X86_64Assembler::new(&mut buf);
+        asm.div(Reg::Rcx);
+
+        // DIV RCX = 48 F7 F1
+        assert_eq!(buf.code(), &[0x48, 0xF7, 0xF1]);
+    }
+
     #[test]
     fn test_cqo() {
         let mut buf = CodeBuffer::new();
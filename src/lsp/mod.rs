@@ -351,7 +351,33 @@ impl LanguageServer for MocaLanguageServer {
             "nil", "try", "catch", "throw", "import",
         ];
 
-        let builtins = ["print", "len", "push", "pop", "type_of", "to_string"];
+        let builtins = [
+            "print",
+            "len",
+            "slice",
+            "to_bytes",
+            "from_bytes",
+            "dict_new",
+            "dict_get",
+            "dict_set",
+            "dict_has",
+            "dict_delete",
+            "dict_len",
+            "dict_keys",
+            "dict_values",
+            "dict_entries",
+            "atomic_new",
+            "atomic_load",
+            "atomic_store",
+            "atomic_fetch_add",
+            "atomic_compare_exchange",
+            "push",
+            "pop",
+            "type_of",
+            "type_name",
+            "to_string",
+            "breakpoint",
+        ];
 
         let mut items: Vec<CompletionItem> = keywords
             .iter()
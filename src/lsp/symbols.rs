@@ -466,6 +466,16 @@ impl SymbolTable {
                 self.collect_expr(left);
                 self.collect_expr(right);
             }
+            Expr::Conditional {
+                condition,
+                then_expr,
+                else_expr,
+                ..
+            } => {
+                self.collect_expr(condition);
+                self.collect_expr(then_expr);
+                self.collect_expr(else_expr);
+            }
             Expr::Call {
                 callee, args, span, ..
             } => {
@@ -38,13 +38,17 @@ pub enum GcModeArg {
     #[default]
     Stw,
     Concurrent,
+    /// Never collect: the heap only grows, up to `heap_limit` if one is set
+    Off,
 }
 
-impl From<GcModeArg> for GcMode {
-    fn from(arg: GcModeArg) -> Self {
-        match arg {
-            GcModeArg::Stw => GcMode::Stw,
-            GcModeArg::Concurrent => GcMode::Concurrent,
+impl GcModeArg {
+    /// The collector to use, or None for `off`.
+    fn mode(self) -> Option<GcMode> {
+        match self {
+            GcModeArg::Stw => Some(GcMode::Stw),
+            GcModeArg::Concurrent => Some(GcMode::Concurrent),
+            GcModeArg::Off => None,
         }
     }
 }
@@ -97,21 +101,66 @@ enum Commands {
         #[arg(long, default_value = "0")]
         timeout: u64,
 
-        /// JIT compilation mode (off, on, auto)
-        #[arg(long, value_enum, default_value = "auto")]
-        jit: JitModeArg,
+        /// Re-run whenever the file or one of its imports changes
+        #[arg(long, conflicts_with_all = ["code", "timeout"])]
+        watch: bool,
 
-        /// JIT compilation threshold (number of calls before JIT)
-        #[arg(long, default_value = "1000")]
-        jit_threshold: u32,
+        /// JIT compilation mode (off, on, auto) [default: auto]
+        #[arg(long, value_enum)]
+        jit: Option<JitModeArg>,
+
+        /// JIT compilation threshold (number of calls before JIT) [default: 1000]
+        #[arg(long)]
+        jit_threshold: Option<u32>,
+
+        /// Bytes of executable memory JIT-compiled code may use [default: 64 MiB]
+        #[arg(long, value_name = "BYTES")]
+        jit_code_budget: Option<usize>,
 
         /// Trace JIT compilation events
         #[arg(long)]
         trace_jit: bool,
 
-        /// GC mode (stw, concurrent)
-        #[arg(long, value_enum, default_value = "stw")]
-        gc_mode: GcModeArg,
+        /// Trace function calls and returns to stderr
+        #[arg(long)]
+        trace_calls: bool,
+
+        /// Check at runtime that `any` values used as int, float or bool
+        /// have that type
+        #[arg(long)]
+        runtime_type_checks: bool,
+
+        /// Require conditions to be bool at compile time instead of coercing `any` values
+        #[arg(long)]
+        strict_bool: bool,
+
+        /// Print the value of the last top-level expression after execution
+        #[arg(long)]
+        eval_print: bool,
+
+        /// Print a backtrace for uncaught errors (also enabled by MOCA_BACKTRACE=1)
+        #[arg(long)]
+        backtrace: bool,
+
+        /// Print a heap summary and the innermost frames for uncaught errors
+        #[arg(long)]
+        print_heap_on_error: bool,
+
+        /// Free string concatenation intermediates as soon as they are consumed
+        #[arg(long)]
+        free_temps: bool,
+
+        /// Run spawned threads one at a time on a reproducible schedule
+        #[arg(long)]
+        deterministic_threads: bool,
+
+        /// Maximum number of OS threads running spawned work at once [default: 64]
+        #[arg(long)]
+        max_threads: Option<usize>,
+
+        /// GC mode (stw, concurrent, off) [default: stw]
+        #[arg(long, visible_alias = "gc", value_enum)]
+        gc_mode: Option<GcModeArg>,
 
         /// Print GC statistics
         #[arg(long)]
@@ -137,7 +186,7 @@ enum Commands {
         #[arg(long, value_name = "FILE", num_args = 0..=1)]
         dump_microops: Option<Option<PathBuf>>,
 
-        /// Profile opcode execution counts
+        /// Profile opcode execution counts (disables JIT)
         #[arg(long)]
         profile_opcodes: bool,
 
@@ -162,10 +211,35 @@ enum Commands {
         /// The source file to lint (defaults to pkg.toml entry if in a project)
         file: Option<PathBuf>,
     },
+    /// Generate markdown documentation from `///` doc comments
+    Doc {
+        /// The source file to document (defaults to pkg.toml entry if in a project)
+        file: Option<PathBuf>,
+    },
     /// Run tests in the project
     Test {
         /// Directory to search for tests (defaults to src/ or pkg.toml entry directory)
         dir: Option<PathBuf>,
+
+        /// Report per-file line coverage after running the tests
+        #[arg(long)]
+        coverage: bool,
+
+        /// Write coverage in LCOV format to FILE (implies --coverage)
+        #[arg(long, value_name = "FILE")]
+        lcov: Option<PathBuf>,
+
+        /// Only run tests whose name contains PATTERN
+        #[arg(long, value_name = "PATTERN")]
+        filter: Option<String>,
+
+        /// Print the names of the discovered tests without running them
+        #[arg(long)]
+        list: bool,
+
+        /// Number of tests to run in parallel
+        #[arg(long, short = 'j', value_name = "N", default_value_t = 1)]
+        jobs: usize,
     },
 }
 
@@ -185,9 +259,20 @@ fn main() -> ExitCode {
             script_args,
             code,
             timeout,
+            watch,
             jit,
             jit_threshold,
+            jit_code_budget,
             trace_jit,
+            trace_calls,
+            runtime_type_checks,
+            strict_bool,
+            eval_print,
+            backtrace,
+            print_heap_on_error,
+            free_temps,
+            deterministic_threads,
+            max_threads,
             gc_mode,
             gc_stats,
             dump_ast,
@@ -198,16 +283,42 @@ fn main() -> ExitCode {
             profile_opcodes,
             timings,
         } => {
+            // Settings from pkg.toml's [runtime] table, overridden by flags
+            let base = match manifest_runtime_config() {
+                Ok(config) => config,
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    return ExitCode::FAILURE;
+                }
+            };
             let config = RuntimeConfig {
-                jit_mode: jit.into(),
-                jit_threshold,
+                jit_mode: jit.map_or(base.jit_mode, Into::into),
+                jit_threshold: jit_threshold.unwrap_or(base.jit_threshold),
+                jit_code_budget: jit_code_budget.unwrap_or(base.jit_code_budget),
                 trace_jit,
-                gc_mode: gc_mode.into(),
+                gc_mode: gc_mode.and_then(GcModeArg::mode).unwrap_or(base.gc_mode),
+                gc_enabled: gc_mode.map_or(base.gc_enabled, |m| m.mode().is_some()),
                 gc_stats,
                 profile_opcodes,
-                ..Default::default()
+                trace_calls,
+                runtime_type_checks: runtime_type_checks || base.runtime_type_checks,
+                strict_bool: strict_bool || base.strict_bool,
+                eval_print,
+                backtrace: backtrace
+                    || base.backtrace
+                    || std::env::var_os("MOCA_BACKTRACE").is_some_and(|v| v != "0"),
+                print_heap_on_error: print_heap_on_error || base.print_heap_on_error,
+                free_temporaries: free_temps || base.free_temporaries,
+                deterministic_threads: deterministic_threads || base.deterministic_threads,
+                max_threads: max_threads.unwrap_or(base.max_threads),
+                ..base
             };
 
+            if let Err(e) = config.validate() {
+                eprintln!("error: {}", e);
+                return ExitCode::FAILURE;
+            }
+
             let dump_opts = compiler::DumpOptions {
                 dump_ast,
                 dump_monomorphised,
@@ -218,6 +329,36 @@ fn main() -> ExitCode {
 
             let timings_format: Option<TimingsFormat> = timings.map(|t| t.into());
 
+            if watch {
+                let path = match file {
+                    Some(p) => p,
+                    None => {
+                        let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+                        match package::PackageManifest::load(&cwd) {
+                            Ok(manifest) => cwd.join(&manifest.package.entry),
+                            Err(_) => {
+                                eprintln!("error: no file specified and no pkg.toml found");
+                                return ExitCode::FAILURE;
+                            }
+                        }
+                    }
+                };
+                let mut cli_args = vec![path.to_string_lossy().to_string()];
+                cli_args.extend(script_args);
+                let mut poller = compiler::watch::MtimePoller::new(compiler::watch::POLL_INTERVAL);
+                compiler::watch::watch(&path, &mut poller, || {
+                    // Clear the screen so each run's output stands alone
+                    print!("\x1b[2J\x1b[H");
+                    if let Err(e) =
+                        run_file(&path, &config, &dump_opts, cli_args.clone(), timings_format)
+                    {
+                        eprintln!("{}", e);
+                    }
+                    eprintln!("[watch] waiting for changes to {}...", path.display());
+                });
+                return ExitCode::SUCCESS;
+            }
+
             // Run with timeout if specified
             let run_result = if timeout > 0 {
                 use std::sync::mpsc;
@@ -380,7 +521,40 @@ fn main() -> ExitCode {
                 }
             }
         }
-        Commands::Test { dir } => {
+        Commands::Doc { file } => {
+            let path = match file {
+                Some(p) => p,
+                None => {
+                    let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+                    match package::PackageManifest::load(&cwd) {
+                        Ok(manifest) => cwd.join(&manifest.package.entry),
+                        Err(_) => {
+                            eprintln!("error: no file specified and no pkg.toml found");
+                            eprintln!(
+                                "usage: moca doc <file> or run from a moca project directory"
+                            );
+                            return ExitCode::FAILURE;
+                        }
+                    }
+                }
+            };
+
+            match compiler::doc_file(&path) {
+                Ok(markdown) => print!("{}", markdown),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+        Commands::Test {
+            dir,
+            coverage,
+            lcov,
+            filter,
+            list,
+            jobs,
+        } => {
             let test_dir = match dir {
                 Some(d) => d,
                 None => {
@@ -397,9 +571,39 @@ fn main() -> ExitCode {
                 }
             };
 
-            let config = RuntimeConfig::default();
+            if list {
+                match compiler::discover_tests(&test_dir, filter.as_deref()) {
+                    Ok(tests) => {
+                        for test in &tests {
+                            println!("{}", test.name);
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("error: {}", e);
+                        return ExitCode::FAILURE;
+                    }
+                }
+                return ExitCode::SUCCESS;
+            }
+
+            let base = match manifest_runtime_config() {
+                Ok(config) => config,
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    return ExitCode::FAILURE;
+                }
+            };
+            let config = RuntimeConfig {
+                coverage: coverage || lcov.is_some(),
+                ..base
+            };
+
+            if let Err(e) = config.validate() {
+                eprintln!("error: {}", e);
+                return ExitCode::FAILURE;
+            }
 
-            match compiler::run_tests(&test_dir, &config) {
+            match compiler::run_tests(&test_dir, &config, filter.as_deref(), jobs) {
                 Ok(results) => {
                     // Print individual test results
                     for result in &results.results {
@@ -415,6 +619,26 @@ fn main() -> ExitCode {
                     println!();
                     println!("{} passed, {} failed", results.passed, results.failed);
 
+                    if let Some(report) = &results.coverage {
+                        println!();
+                        println!("coverage:");
+                        for (path, file) in &report.files {
+                            println!(
+                                "  {}: {:.1}% ({}/{} lines)",
+                                path.display(),
+                                file.percent(),
+                                file.covered.len(),
+                                file.coverable.len()
+                            );
+                        }
+                        if let Some(lcov_path) = &lcov
+                            && let Err(e) = std::fs::write(lcov_path, report.to_lcov())
+                        {
+                            eprintln!("error: failed to write '{}': {}", lcov_path.display(), e);
+                            return ExitCode::FAILURE;
+                        }
+                    }
+
                     if !results.all_passed() {
                         return ExitCode::FAILURE;
                     }
@@ -430,6 +654,13 @@ fn main() -> ExitCode {
     ExitCode::SUCCESS
 }
 
+/// Runtime settings from the `[runtime]` table of the pkg.toml in the
+/// current directory, if there is one.
+fn manifest_runtime_config() -> Result<RuntimeConfig, String> {
+    let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    package::PackageManifest::runtime_config(&cwd)
+}
+
 fn run_file(
     path: &Path,
     config: &RuntimeConfig,
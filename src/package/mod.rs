@@ -1,3 +1,4 @@
+use crate::config::RuntimeConfig;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
@@ -11,6 +12,10 @@ pub struct PackageManifest {
     pub dependencies: HashMap<String, Dependency>,
     #[serde(default, rename = "dev-dependencies")]
     pub dev_dependencies: HashMap<String, Dependency>,
+    /// Execution settings applied by `moca run`/`moca test` unless
+    /// overridden on the command line
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub runtime: Option<RuntimeConfig>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -47,6 +52,7 @@ impl PackageManifest {
             },
             dependencies: HashMap::new(),
             dev_dependencies: HashMap::new(),
+            runtime: None,
         }
     }
 
@@ -58,6 +64,15 @@ impl PackageManifest {
         toml::from_str(&content).map_err(|e| format!("failed to parse pkg.toml: {}", e))
     }
 
+    /// Runtime settings from the `[runtime]` table of the pkg.toml in `dir`.
+    /// Defaults when there is no pkg.toml or it has no `[runtime]` table.
+    pub fn runtime_config(dir: &Path) -> Result<RuntimeConfig, String> {
+        if !dir.join("pkg.toml").exists() {
+            return Ok(RuntimeConfig::default());
+        }
+        Ok(Self::load(dir)?.runtime.unwrap_or_default())
+    }
+
     /// Save manifest to a directory
     pub fn save(&self, dir: &Path) -> Result<(), String> {
         let manifest_path = dir.join("pkg.toml");
@@ -109,6 +124,7 @@ print("Hello, world!");
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::{GcMode, JitMode};
     use std::env::temp_dir;
 
     #[test]
@@ -127,6 +143,41 @@ mod tests {
         assert!(toml_str.contains("version = \"0.1.0\""));
     }
 
+    #[test]
+    fn test_runtime_section() {
+        let manifest: PackageManifest = toml::from_str(
+            r#"
+[package]
+name = "app"
+version = "0.1.0"
+
+[runtime]
+jit = "off"
+jit_threshold = 50
+"#,
+        )
+        .unwrap();
+        let config = manifest.runtime.unwrap();
+        assert_eq!(config.jit_mode, JitMode::Off);
+        assert!(!config.jit_mode.is_enabled());
+        assert_eq!(config.jit_threshold, 50);
+        // Fields left out keep their defaults
+        assert_eq!(config.gc_mode, GcMode::Stw);
+        assert!(config.gc_enabled);
+
+        let manifest: PackageManifest =
+            toml::from_str("[package]\nname = \"app\"\nversion = \"0.1.0\"\n").unwrap();
+        assert!(manifest.runtime.is_none());
+    }
+
+    #[test]
+    fn test_runtime_section_rejects_unknown_keys() {
+        let result: Result<PackageManifest, _> = toml::from_str(
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\n[runtime]\ntrace_jit = true\n",
+        );
+        assert!(result.is_err());
+    }
+
     #[test]
     fn test_init_project() {
         let temp = temp_dir().join("moca_test_init");
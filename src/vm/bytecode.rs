@@ -3,21 +3,24 @@
 //! Binary format:
 //! - Magic: "MOCA" (4 bytes)
 //! - Version: u32 (little-endian)
+//! - Metadata: compiler version string + source hash (u64)
 //! - String pool: length + strings
 //! - Functions: count + function data
 //! - Main function
+//! - Type and interface descriptors
+//! - Constant array templates: count + (length + tagged primitive values)
 //! - Debug info (optional)
 
 use super::heap::ElemKind;
 use super::stackmap::{FunctionStackMap, RefBitset, StackMapEntry};
-use super::{Chunk, Function, Op, ValueType};
+use super::{COMPILER_VERSION, Chunk, ChunkMetadata, Function, Op, Value, ValueType};
 use std::io::{self, Read, Write};
 
 /// Magic bytes for moca bytecode files
 pub const MAGIC: &[u8; 4] = b"MOCA";
 
 /// Current bytecode format version
-pub const VERSION: u32 = 2;
+pub const VERSION: u32 = 4;
 
 /// Error type for bytecode operations
 #[derive(Debug)]
@@ -36,6 +39,12 @@ pub enum BytecodeError {
     InvalidUtf8,
     /// Invalid value type tag
     InvalidValueType(u8),
+    /// Well-formed data describing an inconsistent chunk
+    InvalidChunk(String),
+    /// Chunk produced by a different compiler version
+    CompilerMismatch(String),
+    /// Chunk compiled from a different version of the source
+    SourceChanged,
 }
 
 impl From<io::Error> for BytecodeError {
@@ -54,6 +63,18 @@ impl std::fmt::Display for BytecodeError {
             BytecodeError::Io(e) => write!(f, "I/O error: {}", e),
             BytecodeError::InvalidUtf8 => write!(f, "invalid UTF-8 string"),
             BytecodeError::InvalidValueType(t) => write!(f, "invalid value type tag: {}", t),
+            BytecodeError::InvalidChunk(e) => write!(f, "invalid chunk: {}", e),
+            BytecodeError::CompilerMismatch(v) => write!(
+                f,
+                "chunk was compiled by moca {}, but this is moca {}",
+                v, COMPILER_VERSION
+            ),
+            BytecodeError::SourceChanged => {
+                write!(
+                    f,
+                    "chunk was compiled from a different version of the source"
+                )
+            }
         }
     }
 }
@@ -73,6 +94,41 @@ pub fn deserialize(data: &[u8]) -> Result<Chunk, BytecodeError> {
     read_chunk(&mut cursor)
 }
 
+/// Deserialize a cached Chunk for `source`, rejecting it if it was compiled
+/// from different source text. Chunks with no recorded source hash are
+/// rejected too, since they can't be shown to be current.
+pub fn deserialize_for_source(data: &[u8], source: &str) -> Result<Chunk, BytecodeError> {
+    let chunk = deserialize(data)?;
+    if chunk.metadata.source_hash != super::source_hash(source) {
+        return Err(BytecodeError::SourceChanged);
+    }
+    Ok(chunk)
+}
+
+/// FNV-1a hash of `functions` as serialized, so equal code hashes the same
+/// wherever it lives in memory.
+pub fn functions_hash(functions: &[Function]) -> u64 {
+    struct Fnv(u64);
+    impl Write for Fnv {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            for &b in buf {
+                self.0 ^= b as u64;
+                self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+            }
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut hasher = Fnv(0xcbf2_9ce4_8422_2325);
+    for func in functions {
+        write_function(&mut hasher, func).expect("hashing cannot fail");
+    }
+    hasher.0
+}
+
 /// Write a Chunk to a writer
 pub fn write_chunk<W: Write>(w: &mut W, chunk: &Chunk) -> io::Result<()> {
     // Magic
@@ -81,6 +137,10 @@ pub fn write_chunk<W: Write>(w: &mut W, chunk: &Chunk) -> io::Result<()> {
     // Version
     w.write_all(&VERSION.to_le_bytes())?;
 
+    // Metadata
+    write_string(w, &chunk.metadata.compiler_version)?;
+    write_u64(w, chunk.metadata.source_hash)?;
+
     // String pool
     write_u32(w, chunk.strings.len() as u32)?;
     for s in &chunk.strings {
@@ -133,6 +193,15 @@ pub fn write_chunk<W: Write>(w: &mut W, chunk: &Chunk) -> io::Result<()> {
         }
     }
 
+    // Constant array templates
+    write_u32(w, chunk.const_arrays.len() as u32)?;
+    for template in &chunk.const_arrays {
+        write_u32(w, template.len() as u32)?;
+        for value in template {
+            write_const_value(w, value)?;
+        }
+    }
+
     // Debug info (not serialized for now)
     w.write_all(&[0u8])?; // has_debug = false
 
@@ -155,6 +224,16 @@ pub fn read_chunk<R: Read>(r: &mut R) -> Result<Chunk, BytecodeError> {
         return Err(BytecodeError::UnsupportedVersion(version));
     }
 
+    // Metadata: a chunk from another compiler is stale even if the format matches
+    let compiler_version = read_string(r)?;
+    if compiler_version != COMPILER_VERSION {
+        return Err(BytecodeError::CompilerMismatch(compiler_version));
+    }
+    let metadata = ChunkMetadata {
+        compiler_version,
+        source_hash: read_u64(r)?,
+    };
+
     // String pool
     let string_count = read_u32(r)? as usize;
     let mut strings = Vec::with_capacity(string_count);
@@ -225,6 +304,18 @@ pub fn read_chunk<R: Read>(r: &mut R) -> Result<Chunk, BytecodeError> {
         interface_descriptors.push(super::InterfaceDescriptor { name, method_names });
     }
 
+    // Constant array templates
+    let const_array_count = read_u32(r)? as usize;
+    let mut const_arrays = Vec::with_capacity(const_array_count);
+    for _ in 0..const_array_count {
+        let len = read_u32(r)? as usize;
+        let mut template = Vec::with_capacity(len);
+        for _ in 0..len {
+            template.push(read_const_value(r)?);
+        }
+        const_arrays.push(template);
+    }
+
     // Debug info
     let has_debug = read_u8(r)?;
     let debug = if has_debug != 0 {
@@ -234,14 +325,20 @@ pub fn read_chunk<R: Read>(r: &mut R) -> Result<Chunk, BytecodeError> {
         None
     };
 
-    Ok(Chunk {
+    let chunk = Chunk {
         functions,
         main,
         strings,
         type_descriptors,
         interface_descriptors,
+        const_arrays,
         debug,
-    })
+        metadata,
+    };
+    chunk
+        .validate_call_arities()
+        .map_err(BytecodeError::InvalidChunk)?;
+    Ok(chunk)
 }
 
 fn write_function<W: Write>(w: &mut W, func: &Function) -> io::Result<()> {
@@ -509,6 +606,49 @@ const OP_GLOBAL_GET: u8 = 119;
 // 120 is unused (was OP_IFACE_DESC_LOAD)
 const OP_CALL_DYNAMIC: u8 = 121;
 const OP_VTABLE_LOOKUP: u8 = 122;
+const OP_DEEP_EQ: u8 = 123;
+const OP_I64_DIV_U: u8 = 124;
+const OP_I64_REM_U: u8 = 125;
+const OP_I64_ADD_SAT: u8 = 126;
+const OP_I64_SUB_SAT: u8 = 127;
+const OP_I64_MUL_SAT: u8 = 128;
+const OP_I64_ADD_CHECKED: u8 = 129;
+const OP_ASSERT_TYPE: u8 = 130;
+const OP_FREE_TEMP: u8 = 131;
+const OP_ARRAY_SLICE: u8 = 132;
+const OP_MAP_NEW: u8 = 133;
+const OP_MAP_GET: u8 = 134;
+const OP_MAP_SET: u8 = 135;
+const OP_MAP_HAS: u8 = 136;
+const OP_MAP_DELETE: u8 = 137;
+const OP_MAP_LEN: u8 = 138;
+const OP_MAP_KEYS: u8 = 139;
+const OP_MAP_VALUES: u8 = 140;
+const OP_MAP_ENTRIES: u8 = 141;
+const OP_STRING_SPLIT: u8 = 142;
+const OP_STRING_JOIN: u8 = 143;
+const OP_STRING_SUBSTRING: u8 = 144;
+const OP_STRING_INDEX_OF: u8 = 145;
+const OP_STRING_REPLACE: u8 = 146;
+const OP_STRING_TO_BYTES: u8 = 147;
+const OP_BYTES_TO_STRING: u8 = 148;
+const OP_ATOMIC_NEW: u8 = 149;
+const OP_ATOMIC_LOAD: u8 = 150;
+const OP_ATOMIC_STORE: u8 = 151;
+const OP_ATOMIC_FETCH_ADD: u8 = 152;
+const OP_ATOMIC_COMPARE_EXCHANGE: u8 = 153;
+const OP_FUNC_REF: u8 = 154;
+const OP_TAIL_CALL: u8 = 155;
+const OP_COMPARE: u8 = 156;
+const OP_SPAWN: u8 = 157;
+const OP_AWAIT: u8 = 158;
+const OP_STRING_BUILDER_APPEND: u8 = 159;
+const OP_STRING_BUILDER_FINISH: u8 = 160;
+const OP_UNREACHABLE: u8 = 161;
+const OP_DEBUG_BREAK: u8 = 162;
+const OP_CONST_ARRAY: u8 = 163;
+const OP_TYPE_TAG: u8 = 164;
+const OP_TYPE_NAME: u8 = 165;
 
 fn write_op<W: Write>(w: &mut W, op: &Op) -> io::Result<()> {
     match op {
@@ -534,6 +674,10 @@ fn write_op<W: Write>(w: &mut W, op: &Op) -> io::Result<()> {
             w.write_all(&[OP_STRING_CONST])?;
             write_u32(w, *idx as u32)?;
         }
+        Op::ConstArray(idx) => {
+            w.write_all(&[OP_CONST_ARRAY])?;
+            write_u32(w, *idx as u32)?;
+        }
 
         // Local Variables
         Op::LocalGet(idx) => {
@@ -568,6 +712,12 @@ fn write_op<W: Write>(w: &mut W, op: &Op) -> io::Result<()> {
         Op::I64Mul => w.write_all(&[OP_I64_MUL])?,
         Op::I64DivS => w.write_all(&[OP_I64_DIV_S])?,
         Op::I64RemS => w.write_all(&[OP_I64_REM_S])?,
+        Op::I64DivU => w.write_all(&[OP_I64_DIV_U])?,
+        Op::I64RemU => w.write_all(&[OP_I64_REM_U])?,
+        Op::I64AddSat => w.write_all(&[OP_I64_ADD_SAT])?,
+        Op::I64SubSat => w.write_all(&[OP_I64_SUB_SAT])?,
+        Op::I64MulSat => w.write_all(&[OP_I64_MUL_SAT])?,
+        Op::I64AddChecked => w.write_all(&[OP_I64_ADD_CHECKED])?,
         Op::I64Neg => w.write_all(&[OP_I64_NEG])?,
         Op::I64And => w.write_all(&[OP_I64_AND])?,
         Op::I64Or => w.write_all(&[OP_I64_OR])?,
@@ -627,6 +777,11 @@ fn write_op<W: Write>(w: &mut W, op: &Op) -> io::Result<()> {
         // Ref Comparison
         Op::RefEq => w.write_all(&[OP_REF_EQ])?,
         Op::RefIsNull => w.write_all(&[OP_REF_IS_NULL])?,
+        Op::DeepEq(depth) => {
+            w.write_all(&[OP_DEEP_EQ])?;
+            write_u32(w, *depth as u32)?;
+        }
+        Op::Compare => w.write_all(&[OP_COMPARE])?,
 
         // Type Conversion
         Op::I32WrapI64 => w.write_all(&[OP_I32_WRAP_I64])?,
@@ -661,6 +816,15 @@ fn write_op<W: Write>(w: &mut W, op: &Op) -> io::Result<()> {
             write_u32(w, *func_idx as u32)?;
             write_u32(w, *argc as u32)?;
         }
+        Op::TailCall(func_idx, argc) => {
+            w.write_all(&[OP_TAIL_CALL])?;
+            write_u32(w, *func_idx as u32)?;
+            write_u32(w, *argc as u32)?;
+        }
+        Op::FuncRef(func_idx) => {
+            w.write_all(&[OP_FUNC_REF])?;
+            write_u32(w, *func_idx as u32)?;
+        }
         Op::Ret => w.write_all(&[OP_RET])?,
 
         // Heap Operations
@@ -693,6 +857,10 @@ fn write_op<W: Write>(w: &mut W, op: &Op) -> io::Result<()> {
         Op::HeapLoad2(_) => w.write_all(&[OP_HEAP_LOAD2])?,
         Op::HeapStore2(_) => w.write_all(&[OP_HEAP_STORE2])?,
         Op::HeapOffsetRef => w.write_all(&[OP_HEAP_OFFSET_REF])?,
+        Op::TypeTag(idx) => {
+            w.write_all(&[OP_TYPE_TAG])?;
+            write_u32(w, *idx as u32)?;
+        }
         // System / Builtins
         Op::Hostcall(num, argc) => {
             w.write_all(&[OP_HOSTCALL])?;
@@ -703,10 +871,34 @@ fn write_op<W: Write>(w: &mut W, op: &Op) -> io::Result<()> {
             w.write_all(&[OP_GC_HINT])?;
             write_u32(w, *size as u32)?;
         }
+        Op::DebugBreak => w.write_all(&[OP_DEBUG_BREAK])?,
         Op::TypeOf => w.write_all(&[OP_TYPE_OF])?,
+        Op::TypeName => w.write_all(&[OP_TYPE_NAME])?,
+        Op::AssertType(tag) => w.write_all(&[OP_ASSERT_TYPE, *tag])?,
         Op::HeapSize => w.write_all(&[OP_HEAP_SIZE])?,
+        Op::ArraySlice => w.write_all(&[OP_ARRAY_SLICE])?,
+        Op::FreeTemp => w.write_all(&[OP_FREE_TEMP])?,
+        Op::MapNew => w.write_all(&[OP_MAP_NEW])?,
+        Op::MapGet => w.write_all(&[OP_MAP_GET])?,
+        Op::MapSet => w.write_all(&[OP_MAP_SET])?,
+        Op::MapHas => w.write_all(&[OP_MAP_HAS])?,
+        Op::MapDelete => w.write_all(&[OP_MAP_DELETE])?,
+        Op::MapLen => w.write_all(&[OP_MAP_LEN])?,
+        Op::MapKeys => w.write_all(&[OP_MAP_KEYS])?,
+        Op::MapValues => w.write_all(&[OP_MAP_VALUES])?,
+        Op::MapEntries => w.write_all(&[OP_MAP_ENTRIES])?,
+        Op::StringSplit => w.write_all(&[OP_STRING_SPLIT])?,
+        Op::StringJoin => w.write_all(&[OP_STRING_JOIN])?,
+        Op::StringSubstring => w.write_all(&[OP_STRING_SUBSTRING])?,
+        Op::StringIndexOf => w.write_all(&[OP_STRING_INDEX_OF])?,
+        Op::StringReplace => w.write_all(&[OP_STRING_REPLACE])?,
+        Op::StringBuilderAppend => w.write_all(&[OP_STRING_BUILDER_APPEND])?,
+        Op::StringBuilderFinish => w.write_all(&[OP_STRING_BUILDER_FINISH])?,
+        Op::StringToBytes => w.write_all(&[OP_STRING_TO_BYTES])?,
+        Op::BytesToString => w.write_all(&[OP_BYTES_TO_STRING])?,
         // Exception Handling
         Op::Throw => w.write_all(&[OP_THROW])?,
+        Op::Unreachable => w.write_all(&[OP_UNREACHABLE])?,
         Op::TryBegin(target) => {
             w.write_all(&[OP_TRY_BEGIN])?;
             write_u32(w, *target as u32)?;
@@ -719,14 +911,25 @@ fn write_op<W: Write>(w: &mut W, op: &Op) -> io::Result<()> {
         Op::Args => w.write_all(&[OP_ARGS])?,
 
         // Threading
-        Op::ThreadSpawn(func_idx) => {
+        Op::ThreadSpawn(func_idx, argc) => {
             w.write_all(&[OP_THREAD_SPAWN])?;
             write_u32(w, *func_idx as u32)?;
+            write_u32(w, *argc as u32)?;
         }
         Op::ChannelCreate => w.write_all(&[OP_CHANNEL_CREATE])?,
         Op::ChannelSend => w.write_all(&[OP_CHANNEL_SEND])?,
         Op::ChannelRecv => w.write_all(&[OP_CHANNEL_RECV])?,
         Op::ThreadJoin => w.write_all(&[OP_THREAD_JOIN])?,
+        Op::Spawn(func_idx) => {
+            w.write_all(&[OP_SPAWN])?;
+            write_u32(w, *func_idx as u32)?;
+        }
+        Op::Await => w.write_all(&[OP_AWAIT])?,
+        Op::AtomicNew => w.write_all(&[OP_ATOMIC_NEW])?,
+        Op::AtomicLoad => w.write_all(&[OP_ATOMIC_LOAD])?,
+        Op::AtomicStore => w.write_all(&[OP_ATOMIC_STORE])?,
+        Op::AtomicFetchAdd => w.write_all(&[OP_ATOMIC_FETCH_ADD])?,
+        Op::AtomicCompareExchange => w.write_all(&[OP_ATOMIC_COMPARE_EXCHANGE])?,
 
         // Closures
         Op::CallIndirect(argc) => {
@@ -764,6 +967,7 @@ fn read_op<R: Read>(r: &mut R) -> Result<Op, BytecodeError> {
         OP_F64_CONST => Op::F64Const(read_f64(r)?),
         OP_REF_NULL => Op::RefNull,
         OP_STRING_CONST => Op::StringConst(read_u32(r)? as usize),
+        OP_CONST_ARRAY => Op::ConstArray(read_u32(r)? as usize),
 
         // Local Variables
         OP_LOCAL_GET => Op::LocalGet(read_u32(r)? as usize),
@@ -789,6 +993,12 @@ fn read_op<R: Read>(r: &mut R) -> Result<Op, BytecodeError> {
         OP_I64_MUL => Op::I64Mul,
         OP_I64_DIV_S => Op::I64DivS,
         OP_I64_REM_S => Op::I64RemS,
+        OP_I64_DIV_U => Op::I64DivU,
+        OP_I64_REM_U => Op::I64RemU,
+        OP_I64_ADD_SAT => Op::I64AddSat,
+        OP_I64_SUB_SAT => Op::I64SubSat,
+        OP_I64_MUL_SAT => Op::I64MulSat,
+        OP_I64_ADD_CHECKED => Op::I64AddChecked,
         OP_I64_NEG => Op::I64Neg,
         OP_I64_AND => Op::I64And,
         OP_I64_OR => Op::I64Or,
@@ -848,6 +1058,8 @@ fn read_op<R: Read>(r: &mut R) -> Result<Op, BytecodeError> {
         // Ref Comparison
         OP_REF_EQ => Op::RefEq,
         OP_REF_IS_NULL => Op::RefIsNull,
+        OP_DEEP_EQ => Op::DeepEq(read_u32(r)? as usize),
+        OP_COMPARE => Op::Compare,
 
         // Type Conversion
         OP_I32_WRAP_I64 => Op::I32WrapI64,
@@ -873,6 +1085,12 @@ fn read_op<R: Read>(r: &mut R) -> Result<Op, BytecodeError> {
             let argc = read_u32(r)? as usize;
             Op::Call(func_idx, argc)
         }
+        OP_TAIL_CALL => {
+            let func_idx = read_u32(r)? as usize;
+            let argc = read_u32(r)? as usize;
+            Op::TailCall(func_idx, argc)
+        }
+        OP_FUNC_REF => Op::FuncRef(read_u32(r)? as usize),
         OP_RET => Op::Ret,
 
         // Heap Operations
@@ -897,13 +1115,44 @@ fn read_op<R: Read>(r: &mut R) -> Result<Op, BytecodeError> {
         OP_HEAP_LOAD2 => Op::HeapLoad2(ElemKind::Tagged),
         OP_HEAP_STORE2 => Op::HeapStore2(ElemKind::Tagged),
         OP_HEAP_OFFSET_REF => Op::HeapOffsetRef,
+        OP_TYPE_TAG => Op::TypeTag(read_u32(r)? as usize),
         // System / Builtins
         OP_HOSTCALL => Op::Hostcall(read_u32(r)? as usize, read_u32(r)? as usize),
         OP_GC_HINT => Op::GcHint(read_u32(r)? as usize),
+        OP_DEBUG_BREAK => Op::DebugBreak,
         OP_TYPE_OF => Op::TypeOf,
+        OP_TYPE_NAME => Op::TypeName,
+        OP_ASSERT_TYPE => {
+            let tag = read_u8(r)?;
+            if tag > 4 {
+                return Err(BytecodeError::InvalidValueType(tag));
+            }
+            Op::AssertType(tag)
+        }
         OP_HEAP_SIZE => Op::HeapSize,
+        OP_ARRAY_SLICE => Op::ArraySlice,
+        OP_FREE_TEMP => Op::FreeTemp,
+        OP_MAP_NEW => Op::MapNew,
+        OP_MAP_GET => Op::MapGet,
+        OP_MAP_SET => Op::MapSet,
+        OP_MAP_HAS => Op::MapHas,
+        OP_MAP_DELETE => Op::MapDelete,
+        OP_MAP_LEN => Op::MapLen,
+        OP_MAP_KEYS => Op::MapKeys,
+        OP_MAP_VALUES => Op::MapValues,
+        OP_MAP_ENTRIES => Op::MapEntries,
+        OP_STRING_SPLIT => Op::StringSplit,
+        OP_STRING_JOIN => Op::StringJoin,
+        OP_STRING_SUBSTRING => Op::StringSubstring,
+        OP_STRING_INDEX_OF => Op::StringIndexOf,
+        OP_STRING_REPLACE => Op::StringReplace,
+        OP_STRING_BUILDER_APPEND => Op::StringBuilderAppend,
+        OP_STRING_BUILDER_FINISH => Op::StringBuilderFinish,
+        OP_STRING_TO_BYTES => Op::StringToBytes,
+        OP_BYTES_TO_STRING => Op::BytesToString,
         // Exception Handling
         OP_THROW => Op::Throw,
+        OP_UNREACHABLE => Op::Unreachable,
         OP_TRY_BEGIN => Op::TryBegin(read_u32(r)? as usize),
         OP_TRY_END => Op::TryEnd,
 
@@ -913,11 +1162,22 @@ fn read_op<R: Read>(r: &mut R) -> Result<Op, BytecodeError> {
         OP_ARGS => Op::Args,
 
         // Threading
-        OP_THREAD_SPAWN => Op::ThreadSpawn(read_u32(r)? as usize),
+        OP_THREAD_SPAWN => {
+            let func_idx = read_u32(r)? as usize;
+            let argc = read_u32(r)? as usize;
+            Op::ThreadSpawn(func_idx, argc)
+        }
         OP_CHANNEL_CREATE => Op::ChannelCreate,
         OP_CHANNEL_SEND => Op::ChannelSend,
         OP_CHANNEL_RECV => Op::ChannelRecv,
         OP_THREAD_JOIN => Op::ThreadJoin,
+        OP_SPAWN => Op::Spawn(read_u32(r)? as usize),
+        OP_AWAIT => Op::Await,
+        OP_ATOMIC_NEW => Op::AtomicNew,
+        OP_ATOMIC_LOAD => Op::AtomicLoad,
+        OP_ATOMIC_STORE => Op::AtomicStore,
+        OP_ATOMIC_FETCH_ADD => Op::AtomicFetchAdd,
+        OP_ATOMIC_COMPARE_EXCHANGE => Op::AtomicCompareExchange,
 
         // Closures
         OP_CALL_INDIRECT => Op::CallIndirect(read_u32(r)? as usize),
@@ -970,6 +1230,48 @@ fn read_value_type<R: Read>(r: &mut R) -> Result<ValueType, BytecodeError> {
     }
 }
 
+// ============================================================
+// Constant array element serialization
+// ============================================================
+
+const CONST_NULL: u8 = 0;
+const CONST_BOOL: u8 = 1;
+const CONST_I64: u8 = 2;
+const CONST_F64: u8 = 3;
+
+fn write_const_value<W: Write>(w: &mut W, value: &Value) -> io::Result<()> {
+    match value {
+        Value::Null => write_u8(w, CONST_NULL),
+        Value::Bool(b) => {
+            write_u8(w, CONST_BOOL)?;
+            write_u8(w, *b as u8)
+        }
+        Value::I64(n) => {
+            write_u8(w, CONST_I64)?;
+            write_i64(w, *n)
+        }
+        Value::F64(f) => {
+            write_u8(w, CONST_F64)?;
+            write_f64(w, *f)
+        }
+        Value::Ref(_) => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "constant array templates cannot hold references",
+        )),
+    }
+}
+
+fn read_const_value<R: Read>(r: &mut R) -> Result<Value, BytecodeError> {
+    let tag = read_u8(r)?;
+    match tag {
+        CONST_NULL => Ok(Value::Null),
+        CONST_BOOL => Ok(Value::Bool(read_u8(r)? != 0)),
+        CONST_I64 => Ok(Value::I64(read_i64(r)?)),
+        CONST_F64 => Ok(Value::F64(read_f64(r)?)),
+        _ => Err(BytecodeError::InvalidValueType(tag)),
+    }
+}
+
 // ============================================================
 // Helper functions for reading/writing primitives
 // ============================================================
@@ -1089,7 +1391,7 @@ mod tests {
                 locals_count: 2,
                 code: vec![
                     Op::I32Const(42),
-                    Op::F64Const(3.14),
+                    Op::F64Const(2.5),
                     Op::RefNull,
                     Op::I64Add,
                     Op::Ret,
@@ -1100,17 +1402,33 @@ mod tests {
             strings: vec!["hello".to_string(), "world".to_string()],
             type_descriptors: vec![],
             interface_descriptors: vec![],
+            const_arrays: vec![
+                vec![
+                    Value::I64(-1),
+                    Value::F64(2.5),
+                    Value::Bool(true),
+                    Value::Null,
+                ],
+                vec![],
+            ],
             debug: None,
+            metadata: ChunkMetadata {
+                compiler_version: COMPILER_VERSION.to_string(),
+                source_hash: 0x0123_4567_89ab_cdef,
+            },
         };
 
         let bytes = serialize(&chunk);
         let restored = deserialize(&bytes).unwrap();
 
+        assert_eq!(restored.metadata, chunk.metadata);
+
         assert_eq!(restored.main.name, chunk.main.name);
         assert_eq!(restored.main.arity, chunk.main.arity);
         assert_eq!(restored.main.locals_count, chunk.main.locals_count);
         assert_eq!(restored.main.code.len(), chunk.main.code.len());
         assert_eq!(restored.strings, chunk.strings);
+        assert_eq!(restored.const_arrays, chunk.const_arrays);
     }
 
     #[test]
@@ -1141,7 +1459,9 @@ mod tests {
             strings: vec![],
             type_descriptors: vec![],
             interface_descriptors: vec![],
+            const_arrays: vec![],
             debug: None,
+            metadata: Default::default(),
         };
 
         let bytes = serialize(&chunk);
@@ -1183,7 +1503,9 @@ mod tests {
             strings: vec![],
             type_descriptors: vec![],
             interface_descriptors: vec![],
+            const_arrays: vec![],
             debug: None,
+            metadata: Default::default(),
         };
 
         let bytes = serialize(&chunk);
@@ -1226,6 +1548,49 @@ mod tests {
         ));
     }
 
+    fn empty_chunk() -> Chunk {
+        Chunk {
+            functions: vec![],
+            main: Function {
+                name: "main".to_string(),
+                arity: 0,
+                locals_count: 0,
+                code: vec![Op::Ret],
+                stackmap: None,
+                local_types: vec![],
+            },
+            strings: vec![],
+            type_descriptors: vec![],
+            interface_descriptors: vec![],
+            const_arrays: vec![],
+            debug: None,
+            metadata: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_chunk_from_older_compiler_rejected() {
+        let mut chunk = empty_chunk();
+        chunk.metadata.compiler_version = "0.0.1".to_string();
+        let bytes = serialize(&chunk);
+        assert!(matches!(
+            deserialize(&bytes),
+            Err(BytecodeError::CompilerMismatch(v)) if v == "0.0.1"
+        ));
+    }
+
+    #[test]
+    fn test_chunk_for_other_source_rejected() {
+        let mut chunk = empty_chunk();
+        chunk.metadata.source_hash = crate::vm::source_hash("print(1);");
+        let bytes = serialize(&chunk);
+        assert!(deserialize_for_source(&bytes, "print(1);").is_ok());
+        assert!(matches!(
+            deserialize_for_source(&bytes, "print(2);"),
+            Err(BytecodeError::SourceChanged)
+        ));
+    }
+
     #[test]
     fn test_all_opcodes() {
         // Test that all opcodes roundtrip correctly
@@ -1242,6 +1607,7 @@ mod tests {
             Op::F64Const(-0.0f64),
             Op::RefNull,
             Op::StringConst(42),
+            Op::ConstArray(7),
             // Local Variables
             Op::LocalGet(100),
             Op::LocalSet(200),
@@ -1263,7 +1629,19 @@ mod tests {
             Op::I64Mul,
             Op::I64DivS,
             Op::I64RemS,
+            Op::I64DivU,
+            Op::I64RemU,
+            Op::I64AddSat,
+            Op::I64SubSat,
+            Op::I64MulSat,
+            Op::I64AddChecked,
             Op::I64Neg,
+            Op::I64And,
+            Op::I64Or,
+            Op::I64Xor,
+            Op::I64Shl,
+            Op::I64ShrS,
+            Op::I64ShrU,
             // f32 Arithmetic
             Op::F32Add,
             Op::F32Sub,
@@ -1307,6 +1685,8 @@ mod tests {
             // Ref Comparison
             Op::RefEq,
             Op::RefIsNull,
+            Op::DeepEq(2),
+            Op::Compare,
             // Type Conversion
             Op::I32WrapI64,
             Op::I64ExtendI32S,
@@ -1321,11 +1701,14 @@ mod tests {
             Op::I64TruncF32S,
             Op::F32DemoteF64,
             Op::F64PromoteF32,
+            Op::F64ReinterpretAsI64,
             // Control Flow
             Op::Jmp(1000),
             Op::BrIf(2000),
             Op::BrIfFalse(3000),
             Op::Call(5, 3),
+            Op::TailCall(6, 2),
+            Op::FuncRef(4),
             Op::Ret,
             // Heap Operations
             Op::HeapAlloc(5),
@@ -1339,13 +1722,39 @@ mod tests {
             Op::HeapLoad2(ElemKind::Tagged),
             Op::HeapStore2(ElemKind::Tagged),
             Op::HeapOffsetRef,
+            Op::TypeTag(4),
             // System / Builtins
             Op::Hostcall(7, 2),
             Op::GcHint(1024),
+            Op::DebugBreak,
+            Op::UMul128Hi,
             Op::TypeOf,
+            Op::TypeName,
+            Op::AssertType(1),
             Op::HeapSize,
+            Op::ArraySlice,
+            Op::FreeTemp,
+            Op::MapNew,
+            Op::MapGet,
+            Op::MapSet,
+            Op::MapHas,
+            Op::MapDelete,
+            Op::MapLen,
+            Op::MapKeys,
+            Op::MapValues,
+            Op::MapEntries,
+            Op::StringSplit,
+            Op::StringJoin,
+            Op::StringSubstring,
+            Op::StringIndexOf,
+            Op::StringReplace,
+            Op::StringBuilderAppend,
+            Op::StringBuilderFinish,
+            Op::StringToBytes,
+            Op::BytesToString,
             // Exception Handling
             Op::Throw,
+            Op::Unreachable,
             Op::TryBegin(100),
             Op::TryEnd,
             // CLI Arguments
@@ -1353,11 +1762,24 @@ mod tests {
             Op::Argv,
             Op::Args,
             // Threading
-            Op::ThreadSpawn(1),
+            Op::ThreadSpawn(1, 2),
             Op::ChannelCreate,
             Op::ChannelSend,
             Op::ChannelRecv,
             Op::ThreadJoin,
+            Op::Spawn(1),
+            Op::Await,
+            Op::AtomicNew,
+            Op::AtomicLoad,
+            Op::AtomicStore,
+            Op::AtomicFetchAdd,
+            Op::AtomicCompareExchange,
+            // Indirect call
+            Op::CallIndirect(2),
+            Op::CallDynamic(3),
+            // Globals
+            Op::GlobalGet(9),
+            Op::VtableLookup,
         ];
 
         let chunk = Chunk {
@@ -1373,7 +1795,9 @@ mod tests {
             strings: vec![],
             type_descriptors: vec![],
             interface_descriptors: vec![],
+            const_arrays: vec![],
             debug: None,
+            metadata: Default::default(),
         };
 
         let bytes = serialize(&chunk);
@@ -1385,6 +1809,40 @@ mod tests {
         }
     }
 
+    /// Every op the decoder knows about, built from zeroed operands.
+    /// Legacy tags that decode to an op written under another tag are skipped.
+    fn every_op() -> Vec<Op> {
+        (0..=u8::MAX)
+            .filter_map(|tag| {
+                let mut bytes = vec![tag];
+                bytes.extend([0u8; 16]);
+                let op = read_op(&mut &bytes[..]).ok()?;
+                let mut encoded = Vec::new();
+                write_op(&mut encoded, &op).unwrap();
+                (encoded[0] == tag).then_some(op)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_every_op_has_distinct_profile_name() {
+        // Opcode profiling keys counts by `Op::name()`, so an empty or
+        // shared name would silently merge counts for different ops.
+        let ops = every_op();
+        let mut seen = std::collections::HashMap::new();
+        for op in &ops {
+            let name = op.name();
+            assert!(!name.is_empty(), "{:?} has an empty name", op);
+            let debug = format!("{:?}", op);
+            let variant = debug.split('(').next().unwrap();
+            assert_eq!(name, variant, "{:?} has a mismatched name", op);
+            if let Some(other) = seen.insert(name, op) {
+                panic!("{:?} and {:?} share the name {:?}", other, op, name);
+            }
+        }
+        assert_eq!(seen.len(), 158, "op count changed; update this test");
+    }
+
     #[test]
     fn test_value_type_roundtrip() {
         let chunk = Chunk {
@@ -1413,7 +1871,9 @@ mod tests {
             strings: vec![],
             type_descriptors: vec![],
             interface_descriptors: vec![],
+            const_arrays: vec![],
             debug: None,
+            metadata: Default::default(),
         };
 
         let bytes = serialize(&chunk);
@@ -1450,7 +1910,9 @@ mod tests {
             strings: vec![],
             type_descriptors: vec![],
             interface_descriptors: vec![],
+            const_arrays: vec![],
             debug: None,
+            metadata: Default::default(),
         };
 
         let bytes = serialize(&chunk);
@@ -1486,7 +1948,9 @@ mod tests {
             strings: vec![],
             type_descriptors: vec![],
             interface_descriptors: vec![],
+            const_arrays: vec![],
             debug: None,
+            metadata: Default::default(),
         };
 
         let bytes = serialize(&chunk);
@@ -0,0 +1,391 @@
+//! Native hash maps (`Op::MapNew` .. `Op::MapLen`).
+//!
+//! A map is built from ordinary tagged heap objects so the GC traces keys
+//! and values without any special casing:
+//!
+//! - map: `[buckets, len, cap, first, last]`
+//! - buckets: `cap` slots, each the head entry of a chain or nil
+//! - entry: `[key, value, next, before, after]`
+//!
+//! `before`/`after` link every entry into a list in insertion order, which
+//! is the order [`entries`] returns. Overwriting a key keeps its position.
+//!
+//! Keys are ints, floats, bools, nil or strings. Strings are hashed and
+//! compared by content.
+
+use super::{GcRef, Heap, Value};
+
+/// Number of buckets in a freshly created map.
+pub(crate) const INITIAL_CAPACITY: usize = 8;
+
+const BUCKETS: usize = 0;
+const LEN: usize = 1;
+const CAP: usize = 2;
+const FIRST: usize = 3;
+const LAST: usize = 4;
+
+const KEY: usize = 0;
+const VALUE: usize = 1;
+const NEXT: usize = 2;
+const BEFORE: usize = 3;
+const AFTER: usize = 4;
+
+/// A hashable map key, with strings read out of the heap.
+#[derive(Debug, PartialEq)]
+enum Key {
+    Int(i64),
+    Float(u64),
+    Bool(bool),
+    Nil,
+    Str(Vec<u8>),
+}
+
+impl Key {
+    fn hash(&self) -> u64 {
+        // FNV-1a over a discriminant byte followed by the payload
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        let mut feed = |bytes: &[u8]| {
+            for &b in bytes {
+                hash ^= b as u64;
+                hash = hash.wrapping_mul(0x0100_0000_01b3);
+            }
+        };
+        match self {
+            Key::Int(n) => {
+                feed(&[0]);
+                feed(&n.to_le_bytes());
+            }
+            Key::Float(bits) => {
+                feed(&[1]);
+                feed(&bits.to_le_bytes());
+            }
+            Key::Bool(b) => feed(&[2, *b as u8]),
+            Key::Nil => feed(&[3]),
+            Key::Str(bytes) => {
+                feed(&[4]);
+                feed(bytes);
+            }
+        }
+        hash
+    }
+}
+
+fn read_key(heap: &Heap, value: Value) -> Result<Key, String> {
+    match value {
+        Value::I64(n) => Ok(Key::Int(n)),
+        // Matches -0.0 too: the two compare equal, so they must hash alike
+        Value::F64(0.0) => Ok(Key::Float(0)),
+        Value::F64(f) => Ok(Key::Float(Value::canonical_f64_bits(f))),
+        Value::Bool(b) => Ok(Key::Bool(b)),
+        Value::Null => Ok(Key::Nil),
+        Value::Ref(r) => read_string(heap, r).map(Key::Str).ok_or_else(|| {
+            "runtime error: map keys must be int, float, bool, nil or string".into()
+        }),
+    }
+}
+
+/// Read the bytes of a string object (`[data, len]` with U8 data).
+pub(super) fn read_string(heap: &Heap, r: GcRef) -> Option<Vec<u8>> {
+    if heap.slot_count(r)? != 2 {
+        return None;
+    }
+    let len = heap.read_slot(r, 1)?.as_i64()? as usize;
+    match heap.read_slot(r, 0)? {
+        Value::Null if len == 0 => Some(Vec::new()),
+        Value::Ref(data) if heap.get_elem_kind(data) == super::ElemKind::U8 => (0..len)
+            .map(|i| heap.read_typed(data, i).map(|b| b as u8))
+            .collect(),
+        _ => None,
+    }
+}
+
+fn field(heap: &Heap, r: GcRef, index: usize) -> Result<Value, String> {
+    heap.read_slot(r, index)
+        .ok_or_else(|| "runtime error: invalid map".to_string())
+}
+
+fn int_field(heap: &Heap, r: GcRef, index: usize) -> Result<usize, String> {
+    field(heap, r, index)?
+        .as_i64()
+        .map(|n| n as usize)
+        .ok_or_else(|| "runtime error: invalid map".to_string())
+}
+
+fn ref_field(heap: &Heap, r: GcRef, index: usize) -> Result<GcRef, String> {
+    field(heap, r, index)?
+        .as_ref()
+        .ok_or_else(|| "runtime error: invalid map".to_string())
+}
+
+/// Allocate an empty map.
+pub fn new(heap: &mut Heap) -> Result<GcRef, String> {
+    with_capacity(heap, INITIAL_CAPACITY)
+}
+
+fn with_capacity(heap: &mut Heap, cap: usize) -> Result<GcRef, String> {
+    let buckets = heap.alloc_slots(vec![Value::Null; cap])?;
+    heap.alloc_slots(vec![
+        Value::Ref(buckets),
+        Value::I64(0),
+        Value::I64(cap as i64),
+        Value::Null,
+        Value::Null,
+    ])
+}
+
+/// Number of entries in the map.
+pub fn len(heap: &Heap, map: GcRef) -> Result<usize, String> {
+    int_field(heap, map, LEN)
+}
+
+/// Find the entry for `key`, returning the bucket index, the entry and its
+/// predecessor in the chain.
+fn find(
+    heap: &Heap,
+    map: GcRef,
+    key: &Key,
+) -> Result<(usize, Option<GcRef>, Option<GcRef>), String> {
+    let buckets = ref_field(heap, map, BUCKETS)?;
+    let cap = int_field(heap, map, CAP)?;
+    let bucket = (key.hash() % cap as u64) as usize;
+    let mut prev = None;
+    let mut cursor = field(heap, buckets, bucket)?.as_ref();
+    while let Some(entry) = cursor {
+        if read_key(heap, field(heap, entry, KEY)?)? == *key {
+            return Ok((bucket, Some(entry), prev));
+        }
+        prev = Some(entry);
+        cursor = field(heap, entry, NEXT)?.as_ref();
+    }
+    Ok((bucket, None, prev))
+}
+
+/// Look up `key`, returning `None` if it is absent.
+pub fn get(heap: &Heap, map: GcRef, key: Value) -> Result<Option<Value>, String> {
+    let key = read_key(heap, key)?;
+    match find(heap, map, &key)? {
+        (_, Some(entry), _) => Ok(Some(field(heap, entry, VALUE)?)),
+        _ => Ok(None),
+    }
+}
+
+/// Whether `key` is present.
+pub fn has(heap: &Heap, map: GcRef, key: Value) -> Result<bool, String> {
+    Ok(get(heap, map, key)?.is_some())
+}
+
+/// Insert or overwrite the value for `key`.
+pub fn set(heap: &mut Heap, map: GcRef, key: Value, value: Value) -> Result<(), String> {
+    let parsed = read_key(heap, key)?;
+    let (bucket, entry, _) = find(heap, map, &parsed)?;
+    if let Some(entry) = entry {
+        return heap.write_slot(entry, VALUE, value);
+    }
+
+    let buckets = ref_field(heap, map, BUCKETS)?;
+    let head = field(heap, buckets, bucket)?;
+    let last = field(heap, map, LAST)?;
+    let entry = heap.alloc_slots(vec![key, value, head, last, Value::Null])?;
+    heap.write_slot(buckets, bucket, Value::Ref(entry))?;
+    match last {
+        Value::Ref(last) => heap.write_slot(last, AFTER, Value::Ref(entry))?,
+        _ => heap.write_slot(map, FIRST, Value::Ref(entry))?,
+    }
+    heap.write_slot(map, LAST, Value::Ref(entry))?;
+    let len = len(heap, map)? + 1;
+    heap.write_slot(map, LEN, Value::I64(len as i64))?;
+
+    let cap = int_field(heap, map, CAP)?;
+    if len * 4 > cap * 3 {
+        grow(heap, map, cap * 2)?;
+    }
+    Ok(())
+}
+
+/// Remove `key`, returning whether it was present.
+pub fn delete(heap: &mut Heap, map: GcRef, key: Value) -> Result<bool, String> {
+    let key = read_key(heap, key)?;
+    let (bucket, Some(entry), prev) = find(heap, map, &key)? else {
+        return Ok(false);
+    };
+    let next = field(heap, entry, NEXT)?;
+    match prev {
+        Some(prev) => heap.write_slot(prev, NEXT, next)?,
+        None => {
+            let buckets = ref_field(heap, map, BUCKETS)?;
+            heap.write_slot(buckets, bucket, next)?;
+        }
+    }
+    let before = field(heap, entry, BEFORE)?;
+    let after = field(heap, entry, AFTER)?;
+    match before {
+        Value::Ref(before) => heap.write_slot(before, AFTER, after)?,
+        _ => heap.write_slot(map, FIRST, after)?,
+    }
+    match after {
+        Value::Ref(after) => heap.write_slot(after, BEFORE, before)?,
+        _ => heap.write_slot(map, LAST, before)?,
+    }
+    let len = len(heap, map)? - 1;
+    heap.write_slot(map, LEN, Value::I64(len as i64))?;
+    Ok(true)
+}
+
+/// Collect every entry as a `(key, value)` pair, in insertion order.
+pub fn entries(heap: &Heap, map: GcRef) -> Result<Vec<(Value, Value)>, String> {
+    let mut out = Vec::with_capacity(len(heap, map)?);
+    let mut cursor = field(heap, map, FIRST)?.as_ref();
+    while let Some(entry) = cursor {
+        out.push((field(heap, entry, KEY)?, field(heap, entry, VALUE)?));
+        cursor = field(heap, entry, AFTER)?.as_ref();
+    }
+    Ok(out)
+}
+
+/// Rehash every entry into a bucket array of `new_cap` slots. Entries are
+/// relinked in place, so no entry objects are reallocated.
+fn grow(heap: &mut Heap, map: GcRef, new_cap: usize) -> Result<(), String> {
+    let old_buckets = ref_field(heap, map, BUCKETS)?;
+    let old_cap = int_field(heap, map, CAP)?;
+    let new_buckets = heap.alloc_slots(vec![Value::Null; new_cap])?;
+    for bucket in 0..old_cap {
+        let mut cursor = field(heap, old_buckets, bucket)?.as_ref();
+        while let Some(entry) = cursor {
+            cursor = field(heap, entry, NEXT)?.as_ref();
+            let key = read_key(heap, field(heap, entry, KEY)?)?;
+            let target = (key.hash() % new_cap as u64) as usize;
+            let head = field(heap, new_buckets, target)?;
+            heap.write_slot(entry, NEXT, head)?;
+            heap.write_slot(new_buckets, target, Value::Ref(entry))?;
+        }
+    }
+    heap.write_slot(map, BUCKETS, Value::Ref(new_buckets))?;
+    heap.write_slot(map, CAP, Value::I64(new_cap as i64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(heap: &mut Heap, s: &str) -> Value {
+        Value::Ref(heap.alloc_string(s.to_string()).unwrap())
+    }
+
+    #[test]
+    fn test_insert_lookup_overwrite_delete() {
+        let mut heap = Heap::new();
+        let map = new(&mut heap).unwrap();
+
+        set(&mut heap, map, Value::I64(1), Value::I64(10)).unwrap();
+        let key = string(&mut heap, "two");
+        set(&mut heap, map, key, Value::I64(20)).unwrap();
+        assert_eq!(len(&heap, map).unwrap(), 2);
+        assert_eq!(
+            get(&heap, map, Value::I64(1)).unwrap(),
+            Some(Value::I64(10))
+        );
+
+        // A different string object with the same content finds the entry
+        let lookup = string(&mut heap, "two");
+        assert_eq!(get(&heap, map, lookup).unwrap(), Some(Value::I64(20)));
+        assert_eq!(get(&heap, map, Value::I64(2)).unwrap(), None);
+        // Int and float keys are distinct
+        assert_eq!(get(&heap, map, Value::F64(1.0)).unwrap(), None);
+
+        set(&mut heap, map, Value::I64(1), Value::I64(11)).unwrap();
+        assert_eq!(len(&heap, map).unwrap(), 2);
+        assert_eq!(
+            get(&heap, map, Value::I64(1)).unwrap(),
+            Some(Value::I64(11))
+        );
+
+        assert!(delete(&mut heap, map, lookup).unwrap());
+        assert!(!delete(&mut heap, map, lookup).unwrap());
+        assert!(!has(&heap, map, lookup).unwrap());
+        assert_eq!(len(&heap, map).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_collisions_and_growth() {
+        let mut heap = Heap::new();
+        let map = with_capacity(&mut heap, 1).unwrap();
+        // Every key shares the single bucket until the map grows
+        for i in 0..100 {
+            set(&mut heap, map, Value::I64(i), Value::I64(i * i)).unwrap();
+        }
+        assert_eq!(len(&heap, map).unwrap(), 100);
+        assert!(int_field(&heap, map, CAP).unwrap() >= 128);
+        for i in 0..100 {
+            assert_eq!(
+                get(&heap, map, Value::I64(i)).unwrap(),
+                Some(Value::I64(i * i))
+            );
+        }
+        // Delete from the middle of chains and check the rest survive
+        for i in (0..100).step_by(3) {
+            assert!(delete(&mut heap, map, Value::I64(i)).unwrap());
+        }
+        for i in 0..100 {
+            assert_eq!(has(&heap, map, Value::I64(i)).unwrap(), i % 3 != 0);
+        }
+        assert_eq!(entries(&heap, map).unwrap().len(), 66);
+    }
+
+    #[test]
+    fn test_entries_follow_insertion_order() {
+        let mut heap = Heap::new();
+        let map = with_capacity(&mut heap, 2).unwrap();
+        for key in [5, 1, 9, 3, 7] {
+            set(&mut heap, map, Value::I64(key), Value::I64(key * 10)).unwrap();
+        }
+        // Overwriting keeps the position; deleting and re-inserting moves to the end
+        set(&mut heap, map, Value::I64(1), Value::I64(-1)).unwrap();
+        delete(&mut heap, map, Value::I64(5)).unwrap();
+        delete(&mut heap, map, Value::I64(7)).unwrap();
+        set(&mut heap, map, Value::I64(5), Value::I64(0)).unwrap();
+
+        let entries = entries(&heap, map).unwrap();
+        let expected = [(1, -1), (9, 90), (3, 30), (5, 0)];
+        assert_eq!(entries.len(), expected.len());
+        for ((k, v), (ek, ev)) in entries.iter().zip(expected) {
+            assert_eq!((*k, *v), (Value::I64(ek), Value::I64(ev)));
+        }
+    }
+
+    #[test]
+    fn test_rejects_non_string_ref_keys() {
+        let mut heap = Heap::new();
+        let map = new(&mut heap).unwrap();
+        let obj = heap.alloc_slots(vec![Value::I64(1)]).unwrap();
+        let err = set(&mut heap, map, Value::Ref(obj), Value::Null).unwrap_err();
+        assert!(err.contains("map keys must be"), "{}", err);
+    }
+
+    #[test]
+    fn test_contents_survive_gc() {
+        let mut heap = Heap::new();
+        let map = new(&mut heap).unwrap();
+        for i in 0..50 {
+            let key = string(&mut heap, &format!("k{}", i));
+            let value = string(&mut heap, &format!("v{}", i));
+            set(&mut heap, map, key, value).unwrap();
+        }
+        // Garbage that should be reclaimed alongside the live map
+        for _ in 0..50 {
+            heap.alloc_slots(vec![Value::I64(0); 4]).unwrap();
+        }
+
+        heap.collect(&[Value::Ref(map)]);
+        // Reuse freed memory so stale entries would be overwritten
+        for _ in 0..50 {
+            heap.alloc_slots(vec![Value::I64(-1); 4]).unwrap();
+        }
+
+        for i in 0..50 {
+            let key = string(&mut heap, &format!("k{}", i));
+            let value = get(&heap, map, key).unwrap().unwrap();
+            let bytes = read_string(&heap, value.as_ref().unwrap()).unwrap();
+            assert_eq!(bytes, format!("v{}", i).into_bytes());
+        }
+    }
+}
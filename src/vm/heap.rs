@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::fmt;
 
 use super::Value;
@@ -17,7 +18,7 @@ use super::Value;
 /// Value 2 (I32) is reserved for future 4B element support.
 /// I64 and F64 have identical heap behavior (8B, no trace) but differ in
 /// how the interpreter/JIT reconstructs the Value tag on load.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 #[repr(u8)]
 pub enum ElemKind {
     Tagged = 0,
@@ -88,7 +89,7 @@ fn try_read_u64(memory: &[u8], byte_offset: usize) -> Option<u64> {
 // =============================================================================
 //
 // +--------+------+------------------+-----------+--------------------+
-// | marked | free | count (32)       | elem_kind | reserved (27)      |
+// | marked | free | count (32)       | elem_kind | type tag (27)      |
 // | bit 63 | bit 62| bits 30-61      | bits 27-29| bits 0-26          |
 // +--------+------+------------------+-----------+--------------------+
 //
@@ -96,7 +97,7 @@ fn try_read_u64(memory: &[u8], byte_offset: usize) -> Option<u64> {
 // - Bit 62: free flag (1 = free block in free list, 0 = allocated)
 // - Bits 30-61: element/slot count (max 2^32 - 1)
 // - Bits 27-29: ElemKind (0=Tagged, 3=I64, 4=Ref)
-// - Bits 0-26: reserved for future use
+// - Bits 0-26: struct type descriptor index + 1 (0 = untagged)
 //
 // Free block layout:
 // +----------------+----------------+
@@ -108,8 +109,14 @@ const HEADER_MARKED_BIT: u64 = 1 << 63;
 const HEADER_FREE_BIT: u64 = 1 << 62;
 const HEADER_SLOT_COUNT_SHIFT: u32 = 30;
 const HEADER_SLOT_COUNT_MASK: u64 = 0xFFFF_FFFF << HEADER_SLOT_COUNT_SHIFT;
-const HEADER_ELEM_KIND_SHIFT: u32 = 27;
+pub(crate) const HEADER_ELEM_KIND_SHIFT: u32 = 27;
 const HEADER_ELEM_KIND_MASK: u64 = 0b111 << HEADER_ELEM_KIND_SHIFT;
+const HEADER_TYPE_TAG_MASK: u64 = (1 << HEADER_ELEM_KIND_SHIFT) - 1;
+
+/// Fill pattern for the body of freed blocks in debug builds (past the free
+/// header and next pointer), so reads through a stale reference produce
+/// obviously bogus values instead of the object's old contents.
+pub(crate) const POISON_WORD: u64 = 0xDEAD_DEAD_DEAD_DEAD;
 
 /// Encode a header word from marked flag, slot count, and element kind.
 fn encode_header(marked: bool, slot_count: u32) -> u64 {
@@ -197,7 +204,7 @@ fn object_size_from_header(header: u64) -> usize {
 }
 
 /// Calculate the total size in bytes for a Tagged object with n slots.
-fn object_size_bytes(slot_count: u32) -> usize {
+pub fn object_size_bytes(slot_count: u32) -> usize {
     object_size_words(slot_count) * 8
 }
 
@@ -306,15 +313,19 @@ impl HeapObject {
         Some(HeapObject { marked, slots })
     }
 
-    /// Convert slots to a Rust String (interpreting slots as UTF-8 bytes)
+    /// Convert slots to a Rust String (interpreting slots as UTF-8 bytes).
+    ///
+    /// Each slot holds one byte of the encoding. Invalid UTF-8 sequences,
+    /// and slots that are not a byte at all (outside `0..=255`, or not an
+    /// integer), decode to U+FFFD rather than being truncated or dropped.
     pub fn slots_to_string(&self) -> String {
-        let bytes: Vec<u8> = self
-            .slots
-            .iter()
-            .filter_map(|v| v.as_i64())
-            .map(|b| b as u8)
-            .collect();
-        String::from_utf8_lossy(&bytes).into_owned()
+        decode_utf8_slots(&self.slots)
+    }
+
+    /// `slots_to_string` over the first `len` slots: the data array of a
+    /// string whose length field is `len`.
+    pub fn slots_to_string_len(&self, len: usize) -> String {
+        decode_utf8_slots(&self.slots[..len.min(self.slots.len())])
     }
 
     /// Get all Value references in this object for GC tracing.
@@ -323,6 +334,23 @@ impl HeapObject {
     }
 }
 
+fn decode_utf8_slots(slots: &[Value]) -> String {
+    let mut out = String::with_capacity(slots.len());
+    let mut bytes = Vec::with_capacity(slots.len());
+    for slot in slots {
+        match slot.as_i64().and_then(|b| u8::try_from(b).ok()) {
+            Some(b) => bytes.push(b),
+            None => {
+                out.push_str(&String::from_utf8_lossy(&bytes));
+                bytes.clear();
+                out.push(char::REPLACEMENT_CHARACTER);
+            }
+        }
+    }
+    out.push_str(&String::from_utf8_lossy(&bytes));
+    out
+}
+
 impl fmt::Display for HeapObject {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "HeapObject({:?})", self.slots)
@@ -390,6 +418,121 @@ impl GcRef {
 // Heap - Linear memory based heap
 // =============================================================================
 
+/// What a heap object is, as far as its header tells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ObjectKind {
+    /// A struct tagged with its type descriptor index
+    Struct(usize),
+    /// Any other object with tagged slots
+    Slots,
+    /// A typed array (string bytes, `ptr<int>` data, ...)
+    Array(ElemKind),
+}
+
+/// One line of [`Heap::object_histogram`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistogramEntry {
+    pub kind: ObjectKind,
+    /// Number of objects of this kind
+    pub count: usize,
+    /// Total size of those objects, headers included
+    pub bytes: usize,
+}
+
+/// A point-in-time copy of the heap's mutable state.
+///
+/// Produced by [`Heap::snapshot`] and consumed by [`Heap::restore`].
+/// Configuration (heap limit, GC enabled) is not captured.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeapSnapshot {
+    memory: Vec<u8>,
+    next_alloc: usize,
+    free_list_head: usize,
+    small_free_lists: [usize; SMALL_SLOT_CLASSES],
+    bytes_allocated: usize,
+    gc_threshold: usize,
+    /// Header offsets of the objects that had a finalizer
+    finalizer_offsets: Vec<usize>,
+}
+
+impl HeapSnapshot {
+    /// Size of the captured memory in bytes.
+    pub fn size_bytes(&self) -> usize {
+        self.memory.len()
+    }
+}
+
+/// A value copied out of one heap together with every object it reaches, so
+/// that another heap can rebuild it.
+///
+/// Produced by [`Heap::export`] and consumed by [`Heap::import`]; this is how
+/// a thread's result reaches the VM that joins it. References, in the value
+/// and inside the objects, hold `1 + index` into `objects` instead of a heap
+/// offset. Memo entries are not copied.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportedValue {
+    value: Value,
+    objects: Vec<ExportedObject>,
+}
+
+/// One object of an [`ExportedValue`]: its header word and its body as laid
+/// out in linear memory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ExportedObject {
+    header: u64,
+    body: Vec<u8>,
+}
+
+/// Byte positions, within the body of an object with this header, of the
+/// words that hold a (valid) reference.
+fn ref_positions(header: u64, body: &[u8]) -> Vec<usize> {
+    let count = decode_slot_count(header) as usize;
+    let is_ref = |pos: usize| read_u64(body, pos) != 0;
+    match decode_elem_kind(header) {
+        ElemKind::Tagged => (0..count)
+            .filter(|&i| {
+                matches!(
+                    Value::decode(read_u64(body, 16 * i), read_u64(body, 16 * i + 8)),
+                    Some(Value::Ref(_))
+                )
+            })
+            .map(|i| 16 * i + 8)
+            .filter(|&pos| is_ref(pos))
+            .collect(),
+        ElemKind::Ref => (0..count)
+            .map(|i| 8 * i)
+            .filter(|&pos| is_ref(pos))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The exported form of `r`: the index of its object in `pending` (adding
+/// the object if it is new), keeping the slot offset.
+fn export_ref(r: GcRef, indices: &mut HashMap<usize, usize>, pending: &mut Vec<usize>) -> GcRef {
+    if !r.is_valid() {
+        return r;
+    }
+    let index = *indices.entry(r.base()).or_insert_with(|| {
+        pending.push(r.base());
+        pending.len() - 1
+    });
+    GcRef::new_with_slot_offset(index + 1, r.slot_offset())
+}
+
+/// Cleanup registered on a heap object, run once the object has been swept.
+///
+/// Finalizers get no access to the heap, so they can't resurrect the object
+/// they were registered on or allocate while a collection is in progress.
+pub type Finalizer = Box<dyn FnOnce()>;
+
+/// Longest string (in UTF-8 bytes) whose data array shares the string
+/// struct's allocation.
+pub(crate) const SMALL_STRING_MAX_BYTES: usize = 8;
+
+/// Slot counts (1..=N) of tagged objects served by the size-class free lists.
+const SMALL_SLOT_CLASSES: usize = 4;
+
 /// The garbage-collected heap using linear memory (Vec<u8>).
 pub struct Heap {
     /// Linear memory buffer (byte-addressed)
@@ -398,6 +541,9 @@ pub struct Heap {
     next_alloc: usize,
     /// Head of free list (byte offset, or 0 if empty)
     free_list_head: usize,
+    /// Heads of exact-size free lists for blocks the size of a 1..=4 slot
+    /// object (index = slot count - 1), popped in O(1) by small allocations
+    small_free_lists: [usize; SMALL_SLOT_CLASSES],
     /// Bytes allocated (for GC threshold)
     bytes_allocated: usize,
     /// GC threshold in bytes
@@ -406,6 +552,17 @@ pub struct Heap {
     heap_limit: Option<usize>,
     /// Whether GC is enabled
     gc_enabled: bool,
+    /// Finalizers keyed by the header offset of the object they belong to
+    finalizers: HashMap<usize, Finalizer>,
+    /// Finalizers of freed objects with their header offsets, run at the end
+    /// of the next `collect`
+    pending_finalizers: Vec<(usize, Finalizer)>,
+    /// Number of allocations since the heap was created
+    allocations: usize,
+    /// Memoized values keyed by the header offset of their key object.
+    /// Keys are held weakly: an entry is dropped when its key is collected,
+    /// and its value is kept alive only while the key is.
+    memo: HashMap<usize, Value>,
 }
 
 impl Heap {
@@ -429,10 +586,15 @@ impl Heap {
             memory,
             next_alloc: 8, // Start after reserved 8-byte null word
             free_list_head: 0,
+            small_free_lists: [0; SMALL_SLOT_CLASSES],
             bytes_allocated: 0,
             gc_threshold: 1024 * 1024, // 1MB initial threshold
             heap_limit,
             gc_enabled,
+            finalizers: HashMap::new(),
+            pending_finalizers: Vec::new(),
+            allocations: 0,
+            memo: HashMap::new(),
         }
     }
 
@@ -452,8 +614,10 @@ impl Heap {
             let new_total = self.bytes_allocated + additional_bytes;
             if new_total > limit {
                 return Err(format!(
-                    "runtime error: heap limit exceeded (allocated: {} bytes, limit: {} bytes)",
-                    new_total, limit
+                    "runtime error: heap limit exceeded (allocated: {} bytes, limit: {} bytes{})",
+                    new_total,
+                    limit,
+                    if self.gc_enabled { "" } else { ", GC off" }
                 ));
             }
         }
@@ -463,41 +627,85 @@ impl Heap {
     /// Allocate a new string on the heap.
     /// String is stored as a struct [ptr, len] where ptr points to a data array
     /// containing UTF-8 bytes (ElemKind::U8).
+    ///
+    /// Strings of up to [`SMALL_STRING_MAX_BYTES`] bytes take a single
+    /// allocation: the data array is laid out directly after the struct in the
+    /// same block. Both remain ordinary objects with their own headers, so
+    /// `s.data` works the same for either form (including in JIT code) and the
+    /// GC sweeps them independently.
     pub fn alloc_string(&mut self, value: String) -> Result<GcRef, String> {
         let bytes = value.as_bytes();
         let len = bytes.len();
-        let data_ref = self.alloc_typed_array(len as u32, ElemKind::U8)?;
-        for (i, &b) in bytes.iter().enumerate() {
-            self.write_typed(data_ref, i, b as u64)?;
+        if len > SMALL_STRING_MAX_BYTES {
+            let data_ref = self.alloc_typed_array(len as u32, ElemKind::U8)?;
+            for (i, &b) in bytes.iter().enumerate() {
+                self.write_typed(data_ref, i, b as u64)?;
+            }
+            let struct_slots = vec![Value::Ref(data_ref), Value::I64(len as i64)];
+            return self.alloc_slots(struct_slots);
         }
-        let struct_slots = vec![Value::Ref(data_ref), Value::I64(len as i64)];
-        self.alloc_slots(struct_slots)
-    }
 
-    /// Allocate a new slot-based heap object.
-    pub fn alloc_slots(&mut self, slots: Vec<Value>) -> Result<GcRef, String> {
-        let slot_count = slots.len() as u32;
-        let obj_size_bytes = object_size_bytes(slot_count);
+        let struct_size = object_size_bytes(2);
+        let data_size = object_size_bytes_for_kind(len as u32, ElemKind::U8);
+        let offset = self.reserve_block(struct_size + data_size)?;
+        let data_offset = offset + struct_size;
 
-        self.check_heap_limit(obj_size_bytes)?;
+        write_u64(
+            &mut self.memory,
+            data_offset,
+            encode_header_with_kind(false, len as u32, ElemKind::U8),
+        );
+        let data = &mut self.memory[data_offset + 8..data_offset + data_size];
+        data.fill(0);
+        data[..len].copy_from_slice(bytes);
+
+        write_u64(&mut self.memory, offset, encode_header(false, 2));
+        let slots = [
+            Value::Ref(GcRef::from_offset(data_offset)),
+            Value::I64(len as i64),
+        ];
+        for (i, value) in slots.iter().enumerate() {
+            let (tag, payload) = value.encode();
+            write_u64(&mut self.memory, offset + 8 + 16 * i, tag);
+            write_u64(&mut self.memory, offset + 8 + 16 * i + 8, payload);
+        }
+
+        Ok(GcRef::from_offset(offset))
+    }
+
+    /// Take a block of `size_bytes` for a new allocation: an exact-size block
+    /// from the small free lists, else the first fitting free block, else the
+    /// bump pointer. The caller writes the header(s).
+    fn reserve_block(&mut self, size_bytes: usize) -> Result<usize, String> {
+        self.check_heap_limit(size_bytes)?;
 
-        // Try to find a suitable free block (first-fit)
-        let offset = if let Some(offset) = self.find_free_block(obj_size_bytes) {
+        // Small objects pop an exact-size block; others search the free list (first-fit)
+        let offset = if let Some(offset) = self.pop_small_block(size_bytes) {
+            offset
+        } else if let Some(offset) = self.find_free_block(size_bytes) {
             offset
         } else {
             // No suitable free block, allocate from bump pointer
-            let required_len = self.next_alloc + obj_size_bytes;
+            let required_len = self.next_alloc + size_bytes;
             if required_len > self.memory.len() {
                 self.memory
                     .resize(required_len.max(self.memory.len() * 2), 0);
             }
 
             let offset = self.next_alloc;
-            self.next_alloc += obj_size_bytes;
+            self.next_alloc += size_bytes;
             offset
         };
 
-        self.bytes_allocated += obj_size_bytes;
+        self.bytes_allocated += size_bytes;
+        self.allocations += 1;
+        Ok(offset)
+    }
+
+    /// Allocate a new slot-based heap object.
+    pub fn alloc_slots(&mut self, slots: Vec<Value>) -> Result<GcRef, String> {
+        let slot_count = slots.len() as u32;
+        let offset = self.reserve_block(object_size_bytes(slot_count))?;
 
         // Write header (not marked, not free)
         write_u64(&mut self.memory, offset, encode_header(false, slot_count));
@@ -522,24 +730,7 @@ impl Heap {
             "alloc_typed_array only supports typed kinds (U8, I64, F64, Ref)"
         );
 
-        let obj_size_bytes = object_size_bytes_for_kind(count, kind);
-
-        self.check_heap_limit(obj_size_bytes)?;
-
-        let offset = if let Some(offset) = self.find_free_block(obj_size_bytes) {
-            offset
-        } else {
-            let required_len = self.next_alloc + obj_size_bytes;
-            if required_len > self.memory.len() {
-                self.memory
-                    .resize(required_len.max(self.memory.len() * 2), 0);
-            }
-            let offset = self.next_alloc;
-            self.next_alloc += obj_size_bytes;
-            offset
-        };
-
-        self.bytes_allocated += obj_size_bytes;
+        let offset = self.reserve_block(object_size_bytes_for_kind(count, kind))?;
 
         // Write header with elem_kind
         write_u64(
@@ -564,6 +755,37 @@ impl Heap {
         Ok(GcRef::from_offset(offset))
     }
 
+    /// Allocate a new object holding `count` elements of `src` starting at
+    /// `start`, with the same element kind.
+    pub fn copy_slots(&mut self, src: GcRef, start: usize, count: usize) -> Result<GcRef, String> {
+        let available = self
+            .slot_count(src)
+            .ok_or("invalid reference")?
+            .saturating_sub(src.slot_offset());
+        if start + count > available {
+            return Err(format!(
+                "copy range {}..{} out of bounds (count: {})",
+                start,
+                start + count,
+                available
+            ));
+        }
+        let kind = self.get_elem_kind(src);
+        if kind.is_typed() {
+            let dst = self.alloc_typed_array(count as u32, kind)?;
+            for i in 0..count {
+                let payload = self.read_typed(src, start + i).unwrap_or(0);
+                self.write_typed(dst, i, payload)?;
+            }
+            Ok(dst)
+        } else {
+            let slots = (start..start + count)
+                .map(|i| self.read_slot(src, i).unwrap_or(Value::Null))
+                .collect();
+            self.alloc_slots(slots)
+        }
+    }
+
     /// Get the ElemKind of the object at the given reference.
     pub fn get_elem_kind(&self, r: GcRef) -> ElemKind {
         if !r.is_valid() {
@@ -576,6 +798,36 @@ impl Heap {
         }
     }
 
+    /// Record that `r` is an instance of the struct with type descriptor
+    /// `type_index`, so it can be printed with its field names. Indices too
+    /// large for the header are not recorded.
+    pub fn set_type_tag(&mut self, r: GcRef, type_index: usize) {
+        let tag = type_index as u64 + 1;
+        if !r.is_valid() || tag > HEADER_TYPE_TAG_MASK {
+            return;
+        }
+        let offset = r.base();
+        if let Some(header) = try_read_u64(&self.memory, offset) {
+            write_u64(
+                &mut self.memory,
+                offset,
+                (header & !HEADER_TYPE_TAG_MASK) | tag,
+            );
+        }
+    }
+
+    /// Type descriptor index recorded by [`Heap::set_type_tag`], if any.
+    pub fn type_tag(&self, r: GcRef) -> Option<usize> {
+        if !r.is_valid() {
+            return None;
+        }
+        let header = try_read_u64(&self.memory, r.base())?;
+        match header & HEADER_TYPE_TAG_MASK {
+            0 => None,
+            tag => Some(tag as usize - 1),
+        }
+    }
+
     /// Read a single element from a typed array.
     /// Returns the raw u64 payload without tag.
     pub fn read_typed(&self, r: GcRef, index: usize) -> Option<u64> {
@@ -660,18 +912,7 @@ impl Heap {
                 let remaining = block_size - needed_bytes;
                 if remaining >= MIN_FREE_BLOCK_SIZE {
                     // Split: create a new free block for the remainder
-                    let new_free_offset = current + needed_bytes;
-                    write_u64(
-                        &mut self.memory,
-                        new_free_offset,
-                        encode_free_header(remaining),
-                    );
-                    write_u64(
-                        &mut self.memory,
-                        new_free_offset + 8,
-                        self.free_list_head as u64,
-                    );
-                    self.free_list_head = new_free_offset;
+                    self.add_to_free_list(current + needed_bytes, remaining);
                 }
 
                 return Some(current);
@@ -684,14 +925,48 @@ impl Heap {
         None
     }
 
-    /// Add a block to the free list.
+    /// Size-class index for a block of exactly `size_bytes`, if it matches
+    /// a small tagged object (1..=SMALL_SLOT_CLASSES slots).
+    fn small_class(size_bytes: usize) -> Option<usize> {
+        (1..=SMALL_SLOT_CLASSES)
+            .find(|&n| object_size_bytes(n as u32) == size_bytes)
+            .map(|n| n - 1)
+    }
+
+    /// Pop a block of exactly `size_bytes` from its size-class free list.
+    fn pop_small_block(&mut self, size_bytes: usize) -> Option<usize> {
+        let class = Self::small_class(size_bytes)?;
+        let head = self.small_free_lists[class];
+        if head == 0 {
+            return None;
+        }
+        self.small_free_lists[class] = read_u64(&self.memory, head + 8) as usize;
+        Some(head)
+    }
+
+    /// Add a block to its size-class free list, or the general free list.
+    /// Debug builds overwrite the block's old contents with [`POISON_WORD`].
     fn add_to_free_list(&mut self, offset: usize, size_bytes: usize) {
+        if cfg!(debug_assertions) {
+            for word in (offset + 16..offset + size_bytes).step_by(8) {
+                write_u64(&mut self.memory, word, POISON_WORD);
+            }
+        }
+        let head = match Self::small_class(size_bytes) {
+            Some(class) => &mut self.small_free_lists[class],
+            None => &mut self.free_list_head,
+        };
+        let next = std::mem::replace(head, offset);
         // Write free block header
         write_u64(&mut self.memory, offset, encode_free_header(size_bytes));
-        // Link to current head
-        write_u64(&mut self.memory, offset + 8, self.free_list_head as u64);
-        // Update head
-        self.free_list_head = offset;
+        // Link to previous head
+        write_u64(&mut self.memory, offset + 8, next as u64);
+    }
+
+    /// Whether any freed block is available for reuse.
+    #[cfg(test)]
+    fn has_free_blocks(&self) -> bool {
+        self.free_list_head != 0 || self.small_free_lists.iter().any(|&head| head != 0)
     }
 
     /// Get an object by reference, constructing a HeapObject view.
@@ -712,6 +987,9 @@ impl Heap {
         let actual_slot = slot_index + r.slot_offset();
         let offset = r.base();
         let header = try_read_u64(&self.memory, offset)?;
+        if decode_free(header) {
+            return None;
+        }
         let elem_kind = decode_elem_kind(header);
         let slot_count = decode_slot_count(header) as usize;
 
@@ -758,6 +1036,9 @@ impl Heap {
         let offset = r.base();
         let header =
             try_read_u64(&self.memory, offset).ok_or("invalid reference: out of bounds")?;
+        if decode_free(header) {
+            return Err("invalid reference: object has been freed".to_string());
+        }
         let elem_kind = decode_elem_kind(header);
         let slot_count = decode_slot_count(header) as usize;
 
@@ -834,11 +1115,23 @@ impl Heap {
         self.gc_enabled && self.bytes_allocated >= self.gc_threshold
     }
 
+    /// Check if GC should be triggered before allocating `upcoming` more bytes,
+    /// so the allocation can reuse freed blocks instead of growing memory.
+    pub fn should_gc_before(&self, upcoming: usize) -> bool {
+        self.gc_enabled && self.bytes_allocated + upcoming >= self.gc_threshold
+    }
+
     /// Get the number of bytes currently allocated.
     pub fn bytes_allocated(&self) -> usize {
         self.bytes_allocated
     }
 
+    /// Total number of allocations so far, including ones since freed. A
+    /// short string's struct and data array share one allocation.
+    pub fn allocation_count(&self) -> usize {
+        self.allocations
+    }
+
     /// Set the marked flag for an object.
     fn set_marked(&mut self, offset: usize, marked: bool) {
         if let Some(header) = try_read_u64(&self.memory, offset) {
@@ -870,7 +1163,12 @@ impl Heap {
             }
 
             let offset = r.offset();
-            if self.is_marked(offset) {
+            let header = match try_read_u64(&self.memory, offset) {
+                Some(h) => h,
+                None => continue,
+            };
+            // Stale references to blocks released by `free_temporary` are ignored
+            if decode_marked(header) || decode_free(header) {
                 continue;
             }
 
@@ -878,10 +1176,6 @@ impl Heap {
             self.set_marked(offset, true);
 
             // Trace children based on elem_kind
-            let header = match try_read_u64(&self.memory, offset) {
-                Some(h) => h,
-                None => continue,
-            };
             let kind = decode_elem_kind(header);
             let count = decode_slot_count(header) as usize;
 
@@ -943,6 +1237,7 @@ impl Heap {
                 if obj_size >= 16 {
                     self.add_to_free_list(offset, obj_size);
                 }
+                self.queue_finalizer(offset);
             }
 
             offset += obj_size;
@@ -952,10 +1247,135 @@ impl Heap {
         self.gc_threshold = (self.bytes_allocated * 2).max(1024 * 1024);
     }
 
+    /// Immediately free a temporary object and the objects its slots refer to
+    /// (e.g. a string and its byte buffer). The caller guarantees none of them
+    /// is reachable anymore; leftover copies of the references are harmless
+    /// because `mark` skips free blocks.
+    pub fn free_temporary(&mut self, r: GcRef) {
+        if !r.is_valid() || r.slot_offset() != 0 {
+            return;
+        }
+        let children = match self.get_elem_kind(r) {
+            ElemKind::Tagged => self.get(r).map(|obj| obj.trace()).unwrap_or_default(),
+            _ => Vec::new(),
+        };
+        for child in children {
+            if child.slot_offset() == 0 {
+                self.free_block(child.offset());
+            }
+        }
+        self.free_block(r.offset());
+    }
+
+    /// Return a live object's block to the free lists.
+    fn free_block(&mut self, offset: usize) {
+        let Some(header) = try_read_u64(&self.memory, offset) else {
+            return;
+        };
+        if decode_free(header) {
+            return;
+        }
+        let size = object_size_bytes_from_header(header);
+        // Same limit as sweep: a free block must hold a header and next pointer
+        if size >= 16 {
+            self.add_to_free_list(offset, size);
+            self.bytes_allocated = self.bytes_allocated.saturating_sub(size);
+        }
+        self.memo.remove(&offset);
+        self.queue_finalizer(offset);
+    }
+
     /// Perform a full garbage collection cycle.
+    ///
+    /// Finalizers of swept objects run after sweeping has finished.
     pub fn collect(&mut self, roots: &[Value]) {
         self.mark(roots);
+        self.mark_memo_values();
         self.sweep();
+        self.run_finalizers();
+    }
+
+    /// Mark the memo values of live keys, repeating until no new key becomes
+    /// live, then drop the entries whose keys are about to be swept.
+    fn mark_memo_values(&mut self) {
+        let mut pending: Vec<(usize, Value)> = self.memo.iter().map(|(&k, &v)| (k, v)).collect();
+        loop {
+            let (live, dead): (Vec<_>, Vec<_>) = pending
+                .into_iter()
+                .partition(|&(key, _)| self.is_marked(key));
+            if live.is_empty() {
+                break;
+            }
+            let values: Vec<Value> = live.into_iter().map(|(_, v)| v).collect();
+            self.mark(&values);
+            pending = dead;
+        }
+        let mut memo = std::mem::take(&mut self.memo);
+        memo.retain(|&key, _| self.is_marked(key));
+        self.memo = memo;
+    }
+
+    /// The value memoized for `key`'s object, if any.
+    pub fn memo_get(&self, key: GcRef) -> Option<Value> {
+        self.memo.get(&key.offset()).copied()
+    }
+
+    /// Memoize `value` for `key`'s object until the object is collected.
+    pub fn memo_set(&mut self, key: GcRef, value: Value) {
+        self.memo.insert(key.offset(), value);
+    }
+
+    /// Number of memo entries.
+    pub fn memo_len(&self) -> usize {
+        self.memo.len()
+    }
+
+    /// Register `finalizer` to run once `r`'s object is collected, replacing
+    /// any finalizer already registered on it.
+    pub fn set_finalizer(&mut self, r: GcRef, finalizer: Finalizer) {
+        self.finalizers.insert(r.offset(), finalizer);
+    }
+
+    /// Whether a finalizer is registered on `r`.
+    pub fn has_finalizer(&self, r: GcRef) -> bool {
+        self.finalizers.contains_key(&r.offset())
+    }
+
+    /// Drop the finalizer registered on `r` without running it.
+    /// Returns whether one was registered.
+    pub fn clear_finalizer(&mut self, r: GcRef) -> bool {
+        self.finalizers.remove(&r.offset()).is_some()
+    }
+
+    /// Move the finalizer of the object freed at `offset` to the pending list.
+    fn queue_finalizer(&mut self, offset: usize) {
+        if let Some(finalizer) = self.finalizers.remove(&offset) {
+            self.pending_finalizers.push((offset, finalizer));
+        }
+    }
+
+    /// Run the finalizers of objects freed since the last run.
+    pub fn run_finalizers(&mut self) {
+        for (_, finalizer) in std::mem::take(&mut self.pending_finalizers) {
+            finalizer();
+        }
+    }
+
+    /// Whether `r` points at the header of an allocated object: in bounds,
+    /// 8-byte aligned, not a free block, and ending before the allocation
+    /// frontier.
+    ///
+    /// Used to validate conservative roots such as JIT frame slots, which may
+    /// hold stale payloads. Like marking itself, it trusts a plausible header.
+    pub fn is_object_header(&self, r: GcRef) -> bool {
+        let offset = r.base();
+        if !r.is_valid() || !offset.is_multiple_of(8) || offset >= self.next_alloc {
+            return false;
+        }
+        let Some(header) = try_read_u64(&self.memory, offset) else {
+            return false;
+        };
+        !decode_free(header) && offset + object_size_bytes_from_header(header) <= self.next_alloc
     }
 
     /// Get count of allocated (non-free) objects.
@@ -981,6 +1401,162 @@ impl Heap {
         count
     }
 
+    /// Allocated (non-free) objects grouped by kind, largest total size
+    /// first. Like [`Heap::object_count`], this includes garbage not yet
+    /// collected.
+    pub fn object_histogram(&self) -> Vec<HistogramEntry> {
+        let mut groups: HashMap<ObjectKind, (usize, usize)> = HashMap::new();
+        let mut offset = 8;
+
+        while offset < self.next_alloc {
+            let header = read_u64(&self.memory, offset);
+
+            if decode_free(header) {
+                offset += decode_free_size_bytes(header);
+                continue;
+            }
+
+            let kind = match (decode_elem_kind(header), header & HEADER_TYPE_TAG_MASK) {
+                (ElemKind::Tagged, 0) => ObjectKind::Slots,
+                (ElemKind::Tagged, tag) => ObjectKind::Struct(tag as usize - 1),
+                (elem_kind, _) => ObjectKind::Array(elem_kind),
+            };
+            let size = object_size_bytes_from_header(header);
+            let group = groups.entry(kind).or_default();
+            group.0 += 1;
+            group.1 += size;
+            offset += size;
+        }
+
+        let mut entries: Vec<HistogramEntry> = groups
+            .into_iter()
+            .map(|(kind, (count, bytes))| HistogramEntry { kind, count, bytes })
+            .collect();
+        entries.sort_by(|a, b| {
+            (b.bytes.cmp(&a.bytes))
+                .then(b.count.cmp(&a.count))
+                .then(a.kind.cmp(&b.kind))
+        });
+        entries
+    }
+
+    /// Capture the current heap state.
+    ///
+    /// Only the used prefix of linear memory is copied, so the cost is
+    /// proportional to live allocation rather than reserved capacity.
+    pub fn snapshot(&self) -> HeapSnapshot {
+        HeapSnapshot {
+            memory: self.memory[..self.next_alloc].to_vec(),
+            next_alloc: self.next_alloc,
+            free_list_head: self.free_list_head,
+            small_free_lists: self.small_free_lists,
+            bytes_allocated: self.bytes_allocated,
+            gc_threshold: self.gc_threshold,
+            finalizer_offsets: self.finalizers.keys().copied().collect(),
+        }
+    }
+
+    /// Restore the heap to a previously captured state.
+    ///
+    /// All references handed out after the snapshot was taken become invalid.
+    /// Finalizers can't be copied, so an object keeps its finalizer only if
+    /// it had one in the snapshot and it hasn't been run or cleared since;
+    /// the others are dropped without running.
+    pub fn restore(&mut self, snap: &HeapSnapshot) {
+        let len = self.memory.len().max(snap.memory.len());
+        self.memory.clear();
+        self.memory.extend_from_slice(&snap.memory);
+        self.memory.resize(len, 0);
+        self.next_alloc = snap.next_alloc;
+        self.free_list_head = snap.free_list_head;
+        self.small_free_lists = snap.small_free_lists;
+        self.bytes_allocated = snap.bytes_allocated;
+        self.gc_threshold = snap.gc_threshold;
+        // Keys may not exist in the restored memory
+        self.memo.clear();
+        // Objects freed since the snapshot exist again
+        for (offset, finalizer) in std::mem::take(&mut self.pending_finalizers) {
+            self.finalizers.insert(offset, finalizer);
+        }
+        self.finalizers
+            .retain(|offset, _| snap.finalizer_offsets.contains(offset));
+    }
+
+    /// Copy `value` and every object reachable from it out of this heap.
+    /// Shared objects are copied once and cycles are kept. Objects with a
+    /// finalizer (atomics, open files) stand for state owned by this VM, so
+    /// reaching one is an error.
+    pub fn export(&self, value: Value) -> Result<ExportedValue, String> {
+        let mut indices = HashMap::new();
+        let mut pending = Vec::new();
+        let value = match value {
+            Value::Ref(r) => Value::Ref(export_ref(r, &mut indices, &mut pending)),
+            other => other,
+        };
+
+        let mut objects = Vec::new();
+        while let Some(&base) = pending.get(objects.len()) {
+            let header = try_read_u64(&self.memory, base)
+                .filter(|&header| !decode_free(header))
+                .ok_or("invalid reference")?;
+            if self.finalizers.contains_key(&base) {
+                return Err(
+                    "runtime error: a value holding an atomic or file handle cannot leave its thread"
+                        .to_string(),
+                );
+            }
+            let size = object_size_bytes_from_header(header);
+            let mut body = self
+                .memory
+                .get(base + 8..base + size)
+                .ok_or("invalid reference: out of bounds")?
+                .to_vec();
+            for pos in ref_positions(header, &body) {
+                let r = GcRef {
+                    index: read_u64(&body, pos) as usize,
+                };
+                let exported = export_ref(r, &mut indices, &mut pending);
+                write_u64(&mut body, pos, exported.index as u64);
+            }
+            objects.push(ExportedObject {
+                header: header & !HEADER_MARKED_BIT,
+                body,
+            });
+        }
+        Ok(ExportedValue { value, objects })
+    }
+
+    /// Rebuild an [`ExportedValue`] in this heap and return its value.
+    pub fn import(&mut self, exported: &ExportedValue) -> Result<Value, String> {
+        let mut bases = Vec::with_capacity(exported.objects.len());
+        for object in &exported.objects {
+            let offset = self.reserve_block(8 + object.body.len())?;
+            write_u64(&mut self.memory, offset, object.header);
+            self.memory[offset + 8..offset + 8 + object.body.len()].copy_from_slice(&object.body);
+            bases.push(offset);
+        }
+
+        let import_ref = |r: GcRef| {
+            if r.is_valid() {
+                GcRef::new_with_slot_offset(bases[r.base() - 1], r.slot_offset())
+            } else {
+                r
+            }
+        };
+        for (object, &base) in exported.objects.iter().zip(&bases) {
+            for pos in ref_positions(object.header, &object.body) {
+                let r = GcRef {
+                    index: read_u64(&object.body, pos) as usize,
+                };
+                write_u64(&mut self.memory, base + 8 + pos, import_ref(r).index as u64);
+            }
+        }
+        Ok(match exported.value {
+            Value::Ref(r) => Value::Ref(import_ref(r)),
+            other => other,
+        })
+    }
+
     /// Get raw memory for testing/debugging.
     #[cfg(test)]
     pub fn memory(&self) -> &[u8] {
@@ -1046,6 +1622,112 @@ mod tests {
         assert_eq!(str_value, "hello");
     }
 
+    #[test]
+    fn test_export_import_copies_reachable_objects() {
+        let mut src = Heap::new();
+        let s = src.alloc_string("héllo, world".to_string()).unwrap();
+        let short = src.alloc_string("hi".to_string()).unwrap();
+        let arr = src.alloc_typed_array(3, ElemKind::Ref).unwrap();
+        src.write_typed(arr, 0, s.index as u64).unwrap();
+        src.write_typed(arr, 1, short.index as u64).unwrap();
+        // Slot 2 stays a null reference; slot 1 of the pair points back at it
+        let pair = src.alloc_slots(vec![Value::Ref(arr), Value::Null]).unwrap();
+        src.write_slot(pair, 1, Value::Ref(pair)).unwrap();
+        let exported = src.export(Value::Ref(pair)).unwrap();
+
+        // Offsets in the destination differ from the source
+        let mut dst = Heap::new();
+        dst.alloc_slots(vec![Value::I64(0); 5]).unwrap();
+        let copy = dst.import(&exported).unwrap().as_ref().unwrap();
+        assert_ne!(copy, pair);
+        assert_eq!(dst.read_slot(copy, 1), Some(Value::Ref(copy)));
+
+        let read_string = |heap: &Heap, r: GcRef| {
+            let obj = heap.get(r).unwrap();
+            let data = heap.get(obj.slots[0].as_ref().unwrap()).unwrap();
+            data.slots_to_string_len(obj.slots[1].as_i64().unwrap() as usize)
+        };
+        let arr = dst.read_slot(copy, 0).unwrap().as_ref().unwrap();
+        let element = |i| GcRef::from_offset(dst.read_typed(arr, i).unwrap() as usize);
+        assert_eq!(read_string(&dst, element(0)), "héllo, world");
+        assert_eq!(read_string(&dst, element(1)), "hi");
+        assert_eq!(dst.read_typed(arr, 2), Some(0));
+
+        // Plain values need no objects
+        let exported = src.export(Value::F64(1.5)).unwrap();
+        assert_eq!(dst.import(&exported).unwrap(), Value::F64(1.5));
+    }
+
+    #[test]
+    fn test_short_strings_take_one_allocation() {
+        let mut heap = Heap::new();
+        let read = |heap: &Heap, r: GcRef| {
+            let obj = heap.get(r).unwrap();
+            let data_ref = obj.slots[0].as_ref().unwrap();
+            assert_eq!(heap.get_elem_kind(data_ref), ElemKind::U8);
+            let len = obj.slots[1].as_i64().unwrap() as usize;
+            (
+                data_ref,
+                heap.get(data_ref).unwrap().slots_to_string_len(len),
+            )
+        };
+
+        for text in ["", "a", "héllo", "12345678"] {
+            let before = heap.allocation_count();
+            let r = heap.alloc_string(text.to_string()).unwrap();
+            assert_eq!(heap.allocation_count() - before, 1, "{:?}", text);
+            let (data_ref, value) = read(&heap, r);
+            // The data array directly follows the [ptr, len] struct
+            assert_eq!(data_ref.offset(), r.offset() + object_size_bytes(2));
+            assert_eq!(value, text);
+        }
+        for text in ["123456789", "hello, world"] {
+            let before = heap.allocation_count();
+            let r = heap.alloc_string(text.to_string()).unwrap();
+            assert_eq!(heap.allocation_count() - before, 2, "{:?}", text);
+            assert_eq!(read(&heap, r).1, text);
+        }
+
+        // The struct and its data are still separate objects to the GC
+        let short = heap.alloc_string("hi".to_string()).unwrap();
+        let (data_ref, _) = read(&heap, short);
+        heap.collect(&[Value::Ref(data_ref)]);
+        assert!(heap.get(short).is_none());
+        assert_eq!(heap.get(data_ref).unwrap().slots_to_string(), "hi");
+        heap.collect(&[]);
+        assert!(heap.get(data_ref).is_none());
+    }
+
+    #[test]
+    fn test_slots_to_string_utf8() {
+        let mut heap = Heap::new();
+        let text = "héllo, 世界 🍵";
+        let r = heap.alloc_string(text.to_string()).unwrap();
+        let data_ref = heap.get(r).unwrap().slots[0].as_ref().unwrap();
+        let data = heap.get(data_ref).unwrap();
+        assert_eq!(data.slots.len(), text.len()); // one slot per UTF-8 byte
+        assert_eq!(data.slots_to_string(), text);
+        assert_eq!(data.slots_to_string_len(2), "h\u{FFFD}"); // cut inside 'é'
+        assert_eq!(data.slots_to_string_len(100), text);
+    }
+
+    #[test]
+    fn test_slots_to_string_corrupted_slot() {
+        let byte = |b: i64| Value::I64(b);
+        let obj = |slots| HeapObject {
+            marked: false,
+            slots,
+        };
+        // Out-of-range byte and non-integer slots become U+FFFD, not truncated or dropped
+        let s = obj(vec![byte(b'o' as i64), byte(300), byte(b'k' as i64)]).slots_to_string();
+        assert_eq!(s, "o\u{FFFD}k");
+        let s = obj(vec![byte(-1), Value::Null, Value::F64(65.0)]).slots_to_string();
+        assert_eq!(s, "\u{FFFD}\u{FFFD}\u{FFFD}");
+        // Invalid UTF-8: a lone continuation byte and a truncated sequence
+        let s = obj(vec![byte(0x80), byte(b'a' as i64), byte(0xE4), byte(0xB8)]).slots_to_string();
+        assert_eq!(s, "\u{FFFD}a\u{FFFD}");
+    }
+
     #[test]
     fn test_read_write_slot() {
         let mut heap = Heap::new();
@@ -1098,6 +1780,46 @@ mod tests {
         assert!(heap.is_marked(r1.offset()));
     }
 
+    #[test]
+    fn test_object_histogram_groups_by_kind() {
+        let mut heap = Heap::new();
+        for i in 0..3 {
+            let point = heap
+                .alloc_slots(vec![Value::I64(i), Value::I64(i)])
+                .unwrap();
+            heap.set_type_tag(point, 0);
+        }
+        heap.alloc_slots(vec![Value::Null]).unwrap();
+        heap.alloc_typed_array(4, ElemKind::I64).unwrap();
+
+        let histogram = heap.object_histogram();
+        let kinds: Vec<(ObjectKind, usize)> = histogram.iter().map(|e| (e.kind, e.count)).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (ObjectKind::Struct(0), 3),
+                (ObjectKind::Array(ElemKind::I64), 1),
+                (ObjectKind::Slots, 1),
+            ]
+        );
+        assert_eq!(histogram[0].bytes, 3 * object_size_bytes(2));
+    }
+
+    #[test]
+    fn test_type_tag_survives_gc() {
+        let mut heap = Heap::new();
+        let point = heap
+            .alloc_slots(vec![Value::I64(1), Value::I64(2)])
+            .unwrap();
+        assert_eq!(heap.type_tag(point), None);
+
+        heap.set_type_tag(point, 5);
+        heap.collect(&[Value::Ref(point)]);
+        assert_eq!(heap.type_tag(point), Some(5));
+        assert_eq!(heap.slot_count(point), Some(2));
+        assert_eq!(heap.read_slot(point, 1), Some(Value::I64(2)));
+    }
+
     #[test]
     fn test_gc_traces_references() {
         let mut heap = Heap::new();
@@ -1178,13 +1900,93 @@ mod tests {
 
         // r2 should have been freed and added to free list
         assert_eq!(heap.object_count(), 2);
-        assert!(heap.free_list_head != 0); // Free list should not be empty
+        assert!(heap.has_free_blocks()); // Free list should not be empty
 
         // r1 and r3 should still be accessible
         assert_eq!(heap.get(r1).unwrap().slots[0], Value::I64(1));
         assert_eq!(heap.get(r3).unwrap().slots[0], Value::I64(5));
     }
 
+    #[test]
+    fn test_finalizer_runs_once_object_is_swept() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        let mut heap = Heap::new();
+        let live = heap.alloc_slots(vec![Value::I64(1)]).unwrap();
+        let dead = heap.alloc_slots(vec![Value::I64(2)]).unwrap();
+        let ran = Rc::new(Cell::new(0));
+        for r in [live, dead] {
+            let ran = Rc::clone(&ran);
+            heap.set_finalizer(r, Box::new(move || ran.set(ran.get() + 1)));
+        }
+
+        heap.collect(&[Value::Ref(live)]);
+        assert_eq!(ran.get(), 1);
+        heap.collect(&[Value::Ref(live)]);
+        assert_eq!(ran.get(), 1);
+
+        assert!(heap.clear_finalizer(live));
+        heap.collect(&[]);
+        assert_eq!(ran.get(), 1);
+    }
+
+    #[test]
+    fn test_restore_keeps_finalizers_from_the_snapshot() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        let mut heap = Heap::new();
+        let ran = Rc::new(Cell::new(0));
+        let finalizer = || {
+            let ran = Rc::clone(&ran);
+            Box::new(move || ran.set(ran.get() + 1)) as Finalizer
+        };
+        let old = heap
+            .alloc_slots(vec![Value::I64(1), Value::I64(2)])
+            .unwrap();
+        heap.set_finalizer(old, finalizer());
+        let snap = heap.snapshot();
+
+        // Registered after the snapshot: dropped on restore, never run
+        let new = heap
+            .alloc_slots(vec![Value::I64(3), Value::I64(4)])
+            .unwrap();
+        heap.set_finalizer(new, finalizer());
+        // Freed after the snapshot but not yet finalized: registered again
+        heap.free_temporary(old);
+        assert!(!heap.has_finalizer(old));
+
+        heap.restore(&snap);
+        assert!(heap.has_finalizer(old));
+        assert!(!heap.has_finalizer(new));
+        heap.collect(&[]);
+        assert_eq!(ran.get(), 1);
+    }
+
+    #[test]
+    fn test_memo_entries_are_weak_on_keys() {
+        let mut heap = Heap::new();
+        let key = heap.alloc_slots(vec![Value::I64(1)]).unwrap();
+        let value = heap.alloc_slots(vec![Value::I64(2)]).unwrap();
+        // A value reachable only through a chain of memo entries
+        let inner_value = heap.alloc_slots(vec![Value::I64(3)]).unwrap();
+        heap.memo_set(key, Value::Ref(value));
+        heap.memo_set(value, Value::Ref(inner_value));
+
+        // The key keeps its value, and transitively the next entry, alive
+        heap.collect(&[Value::Ref(key)]);
+        assert_eq!(heap.memo_get(key), Some(Value::Ref(value)));
+        assert_eq!(heap.memo_get(value), Some(Value::Ref(inner_value)));
+        assert_eq!(heap.object_count(), 3);
+
+        // A value referring back to its key does not keep the key alive
+        heap.memo_set(inner_value, Value::Ref(key));
+        heap.collect(&[]);
+        assert_eq!(heap.memo_len(), 0);
+        assert_eq!(heap.object_count(), 0);
+    }
+
     #[test]
     fn test_free_list_reuse() {
         let mut heap = Heap::new();
@@ -1218,6 +2020,157 @@ mod tests {
         assert_eq!(heap.get(r4).unwrap().slots[1], Value::I64(8));
     }
 
+    #[test]
+    fn test_free_temporary_releases_children() {
+        let mut heap = Heap::new();
+
+        // A string-like temporary: [data, len] with a U8 buffer
+        let data = heap.alloc_typed_array(5, ElemKind::U8).unwrap();
+        let temp = heap
+            .alloc_slots(vec![Value::Ref(data), Value::I64(5)])
+            .unwrap();
+        let keep = heap.alloc_slots(vec![Value::I64(1)]).unwrap();
+        let before = heap.bytes_allocated();
+
+        heap.free_temporary(temp);
+        assert!(heap.bytes_allocated() < before);
+        assert_eq!(heap.object_count(), 1);
+
+        // A stale reference to the freed block is ignored by the collector
+        heap.collect(&[Value::Ref(temp), Value::Ref(keep)]);
+        assert_eq!(heap.object_count(), 1);
+        let reused = heap
+            .alloc_slots(vec![Value::I64(7), Value::I64(8)])
+            .unwrap();
+        assert_eq!(reused.offset(), temp.offset());
+    }
+
+    #[test]
+    fn test_is_object_header() {
+        let mut heap = Heap::new();
+        let a = heap
+            .alloc_slots(vec![Value::I64(1), Value::I64(2)])
+            .unwrap();
+        let b = heap.alloc_slots(vec![Value::I64(3)]).unwrap();
+        assert!(heap.is_object_header(a));
+        assert!(heap.is_object_header(b));
+
+        // Misaligned, past the frontier, or freed: not an object
+        assert!(!heap.is_object_header(GcRef {
+            index: a.base() + 4
+        }));
+        assert!(!heap.is_object_header(GcRef { index: 1 << 20 }));
+        heap.free_temporary(a);
+        assert!(!heap.is_object_header(a));
+        assert!(heap.is_object_header(b));
+    }
+
+    #[test]
+    fn test_stale_ref_sees_poison() {
+        let mut heap = Heap::new();
+        let stale = heap
+            .alloc_slots(vec![Value::I64(10), Value::I64(20), Value::I64(30)])
+            .unwrap();
+        heap.collect(&[]);
+
+        // Everything past the free header and next pointer is overwritten
+        if cfg!(debug_assertions) {
+            let end = stale.offset() + object_size_bytes(3);
+            for word in (stale.offset() + 16..end).step_by(8) {
+                assert_eq!(read_u64(&heap.memory, word), POISON_WORD);
+            }
+        }
+
+        // Slot access through the stale reference fails cleanly
+        assert_eq!(heap.read_slot(stale, 1), None);
+        assert!(heap.write_slot(stale, 1, Value::I64(1)).is_err());
+    }
+
+    #[test]
+    fn test_small_alloc_uses_size_class() {
+        let mut heap = Heap::new();
+
+        // A freed 5-slot block goes to the general list, a freed 2-slot block
+        // to its size class
+        let big = heap.alloc_slots(vec![Value::I64(0); 5]).unwrap();
+        let small = heap
+            .alloc_slots(vec![Value::I64(1), Value::I64(2)])
+            .unwrap();
+        let keep = heap.alloc_slots(vec![Value::I64(3)]).unwrap();
+        let (big_offset, small_offset) = (big.offset(), small.offset());
+        heap.collect(&[Value::Ref(keep)]);
+        assert_eq!(heap.free_list_head, big_offset);
+
+        // A 2-slot allocation pops the exact-size block instead of splitting
+        let r = heap
+            .alloc_slots(vec![Value::I64(7), Value::I64(8)])
+            .unwrap();
+        assert_eq!(r.offset(), small_offset);
+        assert_eq!(heap.free_list_head, big_offset);
+        assert_eq!(
+            heap.get(r).unwrap().slots,
+            vec![Value::I64(7), Value::I64(8)]
+        );
+    }
+
+    #[test]
+    fn test_gc_hint_collects_before_large_alloc() {
+        let large = object_size_bytes(1000);
+        // Fill a heap with dead large objects up to just below its threshold,
+        // then make one more, collecting first only if the hint is honoured
+        let grow = |honour_hint: bool| {
+            let mut heap = Heap::new();
+            heap.gc_threshold = 64 * 1024;
+            while heap.bytes_allocated() + large < heap.gc_threshold {
+                heap.alloc_slots(vec![Value::Null; 1000]).unwrap();
+            }
+            assert!(!heap.should_gc());
+            let before = heap.next_alloc;
+            if honour_hint && heap.should_gc_before(large) {
+                heap.collect(&[]);
+            }
+            heap.alloc_slots(vec![Value::Null; 1000]).unwrap();
+            heap.next_alloc - before
+        };
+
+        // Without the hint the heap grows by the whole object; with it the
+        // object reuses memory freed by the collection
+        assert_eq!(grow(false), large);
+        assert_eq!(grow(true), 0);
+    }
+
+    #[test]
+    fn test_small_alloc_many_objects() {
+        const TOTAL: i64 = 2_000_000;
+        const BATCH: i64 = 1_000;
+
+        let mut heap = Heap::new();
+        let mut live = Vec::new();
+        let mut peak = 0;
+        for i in 0..TOTAL {
+            let r = heap
+                .alloc_slots(vec![Value::I64(i), Value::I64(-i)])
+                .unwrap();
+            // Keep every 100th object alive across collections
+            if i % 100 == 0 {
+                live.push(Value::Ref(r));
+            }
+            if i % BATCH == BATCH - 1 {
+                live.truncate(50);
+                heap.collect(&live);
+                peak = peak.max(heap.next_alloc);
+            }
+        }
+
+        // Freed blocks are reused, so memory stays bounded by a couple of batches
+        assert!(peak < 4 * BATCH as usize * object_size_bytes(2));
+        for (k, value) in live.iter().enumerate() {
+            let obj = heap.get(value.as_ref().unwrap()).unwrap();
+            let i = (k * 100) as i64;
+            assert_eq!(obj.slots, vec![Value::I64(i), Value::I64(-i)]);
+        }
+    }
+
     #[test]
     fn test_free_list_block_splitting() {
         let mut heap = Heap::new();
@@ -1285,7 +2238,7 @@ mod tests {
         heap.collect(&[]);
 
         assert_eq!(heap.object_count(), 0);
-        assert!(heap.free_list_head != 0); // Free list should have all the blocks
+        assert!(heap.has_free_blocks()); // Free lists should have all the blocks
     }
 
     // =========================================================================
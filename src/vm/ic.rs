@@ -0,0 +1,216 @@
+//! Inline caches for virtual dispatch.
+//!
+//! `VtableLookup` finds an interface's vtable by walking the entries of the
+//! receiver's type_info. Each call site remembers the (type_info, iface_desc)
+//! pairs it has resolved and the vtables it found, so the walk only runs the
+//! first time a site sees a receiver type. A site holds up to
+//! [`MAX_SITE_ENTRIES`] pairs; one that sees more is megamorphic and from
+//! then on always walks, without touching its entries again.
+//!
+//! type_info and iface_desc objects are allocated on first use (`VM::global`)
+//! and then stay rooted through globals, never reallocated, so once a site
+//! has seen a ref it is stable for the rest of the run and can be compared by
+//! identity. Rewinding to a snapshot restores globals and clears the caches.
+//!
+//! Sites share a fixed, direct-mapped table rather than a hash map so a hit
+//! costs about as much as one vtable entry comparison; two sites that land in
+//! the same slot simply evict each other.
+
+use super::{GcRef, Value};
+
+/// Number of slots in the site table (a power of two).
+const SITE_SLOTS: usize = 1024;
+
+/// Receiver types a call site caches before it goes megamorphic.
+pub(crate) const MAX_SITE_ENTRIES: usize = 4;
+
+/// Hit/miss counters for the vtable inline caches.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VtableCacheStats {
+    /// Lookups answered from a call site's cache
+    pub hits: u64,
+    /// Lookups that walked the type_info's vtable entries
+    pub walks: u64,
+    /// Walks at megamorphic sites (included in `walks`)
+    pub megamorphic: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    type_info: GcRef,
+    iface_desc: GcRef,
+    vtable: Value,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Site {
+    site: (usize, usize),
+    entries: [Option<Entry>; MAX_SITE_ENTRIES],
+    megamorphic: bool,
+}
+
+impl Site {
+    fn new(site: (usize, usize)) -> Self {
+        Self {
+            site,
+            entries: [None; MAX_SITE_ENTRIES],
+            megamorphic: false,
+        }
+    }
+}
+
+/// Polymorphic vtable caches keyed by call site (function index, pc).
+#[derive(Debug, Default)]
+pub struct VtableCache {
+    /// Allocated on the first insert, so VMs that never dispatch through a
+    /// vtable don't pay for the table
+    sites: Vec<Option<Site>>,
+    stats: VtableCacheStats,
+}
+
+impl VtableCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    fn slot((func_index, pc): (usize, usize)) -> usize {
+        (func_index.wrapping_mul(0x9E37_79B9) ^ pc) & (SITE_SLOTS - 1)
+    }
+
+    /// The vtable cached at `site` for this receiver type and interface, if any.
+    #[inline]
+    pub fn get(
+        &mut self,
+        site: (usize, usize),
+        type_info: GcRef,
+        iface_desc: GcRef,
+    ) -> Option<Value> {
+        let Some(Some(cached)) = self.sites.get(Self::slot(site)) else {
+            return None;
+        };
+        if cached.site != site {
+            return None;
+        }
+        let vtable = cached
+            .entries
+            .iter()
+            .map_while(|e| e.as_ref())
+            .find(|e| e.type_info == type_info && e.iface_desc == iface_desc)?
+            .vtable;
+        self.stats.hits += 1;
+        Some(vtable)
+    }
+
+    /// Record the result of a vtable walk at `site`. A site that already
+    /// holds [`MAX_SITE_ENTRIES`] pairs becomes megamorphic instead.
+    pub fn insert(
+        &mut self,
+        site: (usize, usize),
+        type_info: GcRef,
+        iface_desc: GcRef,
+        vtable: Value,
+    ) {
+        self.stats.walks += 1;
+        if self.sites.is_empty() {
+            self.sites = vec![None; SITE_SLOTS];
+        }
+        let slot = &mut self.sites[Self::slot(site)];
+        let cached = match slot {
+            Some(cached) if cached.site == site => cached,
+            _ => slot.insert(Site::new(site)),
+        };
+        if cached.megamorphic {
+            self.stats.megamorphic += 1;
+            return;
+        }
+        let entry = Entry {
+            type_info,
+            iface_desc,
+            vtable,
+        };
+        match cached.entries.iter_mut().find(|e| e.is_none()) {
+            Some(free) => *free = Some(entry),
+            None => {
+                // Drop the entries: a megamorphic site never hits
+                cached.entries = [None; MAX_SITE_ENTRIES];
+                cached.megamorphic = true;
+                self.stats.megamorphic += 1;
+            }
+        }
+    }
+
+    /// Drop every cached entry, e.g. after the heap is replaced.
+    pub fn clear(&mut self) {
+        self.sites.fill(None);
+    }
+
+    pub fn stats(&self) -> VtableCacheStats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn r(index: usize) -> GcRef {
+        GcRef { index }
+    }
+
+    #[test]
+    fn test_site_misses_on_type_change() {
+        let mut cache = VtableCache::new();
+        let site = (0, 4);
+        assert_eq!(cache.get(site, r(8), r(16)), None);
+        cache.insert(site, r(8), r(16), Value::Ref(r(24)));
+        assert_eq!(cache.get(site, r(8), r(16)), Some(Value::Ref(r(24))));
+        // Another receiver type or interface at the same site misses
+        assert_eq!(cache.get(site, r(32), r(16)), None);
+        assert_eq!(cache.get(site, r(8), r(40)), None);
+        // ...and so does the same pair at another site
+        assert_eq!(cache.get((0, 5), r(8), r(16)), None);
+        assert_eq!(
+            cache.stats(),
+            VtableCacheStats {
+                hits: 1,
+                walks: 1,
+                megamorphic: 0
+            }
+        );
+
+        cache.clear();
+        assert_eq!(cache.get(site, r(8), r(16)), None);
+    }
+
+    #[test]
+    fn test_site_goes_megamorphic_past_entry_limit() {
+        let mut cache = VtableCache::new();
+        let site = (1, 2);
+        let type_info = |i: usize| r(100 + 8 * i);
+        for i in 0..MAX_SITE_ENTRIES {
+            cache.insert(site, type_info(i), r(16), Value::I64(i as i64));
+        }
+        for i in 0..MAX_SITE_ENTRIES {
+            assert_eq!(
+                cache.get(site, type_info(i), r(16)),
+                Some(Value::I64(i as i64))
+            );
+        }
+        assert_eq!(cache.stats().megamorphic, 0);
+
+        // One more type makes the site megamorphic: nothing hits any more
+        cache.insert(site, type_info(MAX_SITE_ENTRIES), r(16), Value::Null);
+        assert_eq!(cache.get(site, type_info(0), r(16)), None);
+        cache.insert(site, type_info(0), r(16), Value::I64(0));
+        assert_eq!(cache.get(site, type_info(0), r(16)), None);
+        assert_eq!(
+            cache.stats(),
+            VtableCacheStats {
+                hits: MAX_SITE_ENTRIES as u64,
+                walks: MAX_SITE_ENTRIES as u64 + 2,
+                megamorphic: 2
+            }
+        );
+    }
+}
@@ -1,6 +1,7 @@
-use super::ValueType;
+use super::bytecode::functions_hash;
 use super::heap::ElemKind;
 use super::ops::Op;
+use super::{Function, ValueType};
 
 /// Virtual register index into a frame's register file.
 ///
@@ -56,6 +57,13 @@ pub enum MicroOp {
         args: Vec<VReg>,
         ret: Option<VReg>,
     },
+    /// Tail call: the callee replaces the current frame and returns straight
+    /// to its caller. `ret` is only used when falling back to a plain call.
+    TailCall {
+        func_id: usize,
+        args: Vec<VReg>,
+        ret: Option<VReg>,
+    },
     /// Return a value (or unit if src is None).
     Ret {
         src: Option<VReg>,
@@ -138,6 +146,36 @@ pub enum MicroOp {
         a: VReg,
         b: VReg,
     },
+    /// dst = (a as u64) / (b as u64) (i64 unsigned divide)
+    DivU64 {
+        dst: VReg,
+        a: VReg,
+        b: VReg,
+    },
+    /// dst = (a as u64) % (b as u64) (i64 unsigned remainder)
+    RemU64 {
+        dst: VReg,
+        a: VReg,
+        b: VReg,
+    },
+    /// dst = a.saturating_add(b) (i64 saturating arithmetic)
+    AddSatI64 {
+        dst: VReg,
+        a: VReg,
+        b: VReg,
+    },
+    /// dst = a.saturating_sub(b) (i64 saturating arithmetic)
+    SubSatI64 {
+        dst: VReg,
+        a: VReg,
+        b: VReg,
+    },
+    /// dst = a.saturating_mul(b) (i64 saturating arithmetic)
+    MulSatI64 {
+        dst: VReg,
+        a: VReg,
+        b: VReg,
+    },
     NegI64 {
         dst: VReg,
         src: VReg,
@@ -473,6 +511,11 @@ pub enum MicroOp {
         src: VReg,
         offset: VReg,
     },
+    /// Record type descriptor `type_index` on the struct in `obj`
+    TypeTag {
+        obj: VReg,
+        type_index: usize,
+    },
 
     // ========================================
     // Heap allocation operations
@@ -549,3 +592,70 @@ pub struct ConvertedFunction {
     /// Used by JIT to reconstruct tags at boundaries (Ret, HeapStore, call_helper).
     pub vreg_types: Vec<ValueType>,
 }
+
+impl ConvertedFunction {
+    /// First jump target in `start..=end` that leaves the loop somewhere other
+    /// than `end + 1`, where the interpreter resumes after a compiled loop.
+    pub fn loop_exit_outside(&self, start: usize, end: usize) -> Option<usize> {
+        self.micro_ops[start..=end].iter().find_map(|op| match op {
+            MicroOp::Jmp { target, .. }
+            | MicroOp::BrIf { target, .. }
+            | MicroOp::BrIfFalse { target, .. }
+                if (*target < start || *target > end + 1) =>
+            {
+                Some(*target)
+            }
+            _ => None,
+        })
+    }
+}
+
+/// Converted functions kept across runs, so running a chunk again doesn't
+/// convert its functions again. `main` isn't cached: callers such as the FFI
+/// swap it out between runs of the same chunk.
+#[derive(Debug, Default)]
+pub struct ConversionCache {
+    /// [`functions_hash`] of the functions the entries were converted from;
+    /// functions that don't match are a different chunk
+    fingerprint: Option<u64>,
+    /// Whether the entries were converted with small calls inlined
+    inline_calls: bool,
+    /// Converted functions, indexed by func_index
+    pub functions: Vec<Option<ConvertedFunction>>,
+    /// Conversions whose entries have since been dropped
+    dropped_conversions: usize,
+}
+
+impl ConversionCache {
+    /// Keep the cached entries if `functions` are the ones they were converted
+    /// from (with the same inlining), otherwise drop them.
+    ///
+    /// Chunks are told apart by the contents of their functions, not by where
+    /// they live, so a new chunk allocated where a dropped one was, or code
+    /// edited in place, is converted afresh.
+    pub fn prepare(&mut self, functions: &[Function], inline_calls: bool) {
+        let fingerprint = functions_hash(functions);
+        if self.inline_calls == inline_calls
+            && self.fingerprint == Some(fingerprint)
+            && self.functions.len() == functions.len()
+        {
+            return;
+        }
+        self.clear();
+        self.fingerprint = Some(fingerprint);
+        self.inline_calls = inline_calls;
+        self.functions = vec![None; functions.len()];
+    }
+
+    /// Number of functions converted so far, including dropped entries.
+    pub fn conversions(&self) -> usize {
+        self.dropped_conversions + self.functions.iter().flatten().count()
+    }
+
+    /// Drop every cached entry.
+    pub fn clear(&mut self) {
+        self.dropped_conversions += self.functions.iter().flatten().count();
+        self.fingerprint = None;
+        self.functions.clear();
+    }
+}
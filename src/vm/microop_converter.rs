@@ -76,6 +76,9 @@ pub fn convert(func: &Function) -> ConvertedFunction {
                 // Defer: push immediate onto vstack, materialize only if needed
                 vstack.push(Vse::ImmI64(*n));
             }
+            Op::FuncRef(func_index) => {
+                vstack.push(Vse::ImmI64(*func_index as i64));
+            }
             Op::I32Const(n) => {
                 let dst = alloc_temp(
                     &mut next_temp,
@@ -158,6 +161,26 @@ pub fn convert(func: &Function) -> ConvertedFunction {
                     }
                 }
 
+                // === Constant store: load the immediate straight into the local ===
+                if let Some(Vse::ImmI64(imm)) = vstack.last().copied()
+                    && matches!(func.local_types.get(*slot), None | Some(ValueType::I64))
+                {
+                    vstack.pop();
+                    save_local_aliases(
+                        &mut vstack,
+                        dst_local,
+                        &mut micro_ops,
+                        &mut next_temp,
+                        &mut max_temp,
+                        &mut vreg_types,
+                    );
+                    micro_ops.push(MicroOp::ConstI64 {
+                        dst: dst_local,
+                        imm,
+                    });
+                    continue;
+                }
+
                 // === Normal path (patch not applicable) ===
                 let src = pop_vreg(
                     &mut vstack,
@@ -167,24 +190,14 @@ pub fn convert(func: &Function) -> ConvertedFunction {
                     &mut vreg_types,
                 );
                 if src != dst_local {
-                    // Materialize any vstack references to this local before overwriting
-                    for entry in vstack.iter_mut() {
-                        if let Vse::Reg(v) = entry
-                            && *v == dst_local
-                        {
-                            let temp = alloc_temp(
-                                &mut next_temp,
-                                &mut max_temp,
-                                &mut vreg_types,
-                                ValueType::I64,
-                            );
-                            micro_ops.push(MicroOp::Mov {
-                                dst: temp,
-                                src: dst_local,
-                            });
-                            *entry = Vse::Reg(temp);
-                        }
-                    }
+                    save_local_aliases(
+                        &mut vstack,
+                        dst_local,
+                        &mut micro_ops,
+                        &mut next_temp,
+                        &mut max_temp,
+                        &mut vreg_types,
+                    );
                     micro_ops.push(MicroOp::Mov {
                         dst: dst_local,
                         src,
@@ -487,6 +500,61 @@ pub fn convert(func: &Function) -> ConvertedFunction {
                     |dst, a, b| MicroOp::RemI64 { dst, a, b },
                 );
             }
+            Op::I64DivU => {
+                emit_binop(
+                    &mut vstack,
+                    &mut micro_ops,
+                    &mut next_temp,
+                    &mut max_temp,
+                    &mut vreg_types,
+                    ValueType::I64,
+                    |dst, a, b| MicroOp::DivU64 { dst, a, b },
+                );
+            }
+            Op::I64RemU => {
+                emit_binop(
+                    &mut vstack,
+                    &mut micro_ops,
+                    &mut next_temp,
+                    &mut max_temp,
+                    &mut vreg_types,
+                    ValueType::I64,
+                    |dst, a, b| MicroOp::RemU64 { dst, a, b },
+                );
+            }
+            Op::I64AddSat => {
+                emit_binop(
+                    &mut vstack,
+                    &mut micro_ops,
+                    &mut next_temp,
+                    &mut max_temp,
+                    &mut vreg_types,
+                    ValueType::I64,
+                    |dst, a, b| MicroOp::AddSatI64 { dst, a, b },
+                );
+            }
+            Op::I64SubSat => {
+                emit_binop(
+                    &mut vstack,
+                    &mut micro_ops,
+                    &mut next_temp,
+                    &mut max_temp,
+                    &mut vreg_types,
+                    ValueType::I64,
+                    |dst, a, b| MicroOp::SubSatI64 { dst, a, b },
+                );
+            }
+            Op::I64MulSat => {
+                emit_binop(
+                    &mut vstack,
+                    &mut micro_ops,
+                    &mut next_temp,
+                    &mut max_temp,
+                    &mut vreg_types,
+                    ValueType::I64,
+                    |dst, a, b| MicroOp::MulSatI64 { dst, a, b },
+                );
+            }
             Op::I64And => {
                 let b = pop_entry(
                     &mut vstack,
@@ -1376,7 +1444,7 @@ pub fn convert(func: &Function) -> ConvertedFunction {
                     target: *target,
                 });
             }
-            Op::Call(func_id, argc) => {
+            Op::Call(func_id, argc) | Op::TailCall(func_id, argc) => {
                 let mut args = Vec::with_capacity(*argc);
                 for _ in 0..*argc {
                     args.push(pop_vreg(
@@ -1401,10 +1469,19 @@ pub fn convert(func: &Function) -> ConvertedFunction {
                     &mut vreg_types,
                     ValueType::I64,
                 );
-                micro_ops.push(MicroOp::Call {
-                    func_id: *func_id,
-                    args,
-                    ret: Some(ret),
+                let (func_id, ret_vreg) = (*func_id, Some(ret));
+                micro_ops.push(if matches!(op, Op::TailCall(..)) {
+                    MicroOp::TailCall {
+                        func_id,
+                        args,
+                        ret: ret_vreg,
+                    }
+                } else {
+                    MicroOp::Call {
+                        func_id,
+                        args,
+                        ret: ret_vreg,
+                    }
                 });
                 vstack.push(Vse::Reg(ret));
             }
@@ -1612,6 +1689,31 @@ pub fn convert(func: &Function) -> ConvertedFunction {
                 micro_ops.push(MicroOp::HeapOffsetRef { dst, src, offset });
                 vstack.push(Vse::Reg(dst));
             }
+            Op::TypeTag(type_index) => {
+                // Tags the struct in place: the ref stays on the vstack
+                let entry = pop_entry(
+                    &mut vstack,
+                    &mut micro_ops,
+                    &mut next_temp,
+                    &mut max_temp,
+                    &mut vreg_types,
+                );
+                let obj = mat(
+                    entry,
+                    &mut micro_ops,
+                    &mut next_temp,
+                    &mut max_temp,
+                    &mut vreg_types,
+                );
+                micro_ops.push(MicroOp::TypeTag {
+                    obj,
+                    type_index: *type_index,
+                });
+                vstack.push(match entry {
+                    Vse::Reg(_) => Vse::Reg(obj),
+                    _ => Vse::RegRef(obj),
+                });
+            }
 
             // ============================================================
             // Raw with PC target remapping
@@ -1896,7 +1998,7 @@ fn extract_vregs(mop: &MicroOp) -> Vec<usize> {
         MicroOp::Jmp { .. } => {}
         MicroOp::BrIf { cond, .. } => vregs.push(cond.0),
         MicroOp::BrIfFalse { cond, .. } => vregs.push(cond.0),
-        MicroOp::Call { args, ret, .. } => {
+        MicroOp::Call { args, ret, .. } | MicroOp::TailCall { args, ret, .. } => {
             for a in args {
                 vregs.push(a.0);
             }
@@ -1944,6 +2046,11 @@ fn extract_vregs(mop: &MicroOp) -> Vec<usize> {
         | MicroOp::MulI64 { dst, a, b }
         | MicroOp::DivI64 { dst, a, b }
         | MicroOp::RemI64 { dst, a, b }
+        | MicroOp::DivU64 { dst, a, b }
+        | MicroOp::RemU64 { dst, a, b }
+        | MicroOp::AddSatI64 { dst, a, b }
+        | MicroOp::SubSatI64 { dst, a, b }
+        | MicroOp::MulSatI64 { dst, a, b }
         | MicroOp::AndI64 { dst, a, b }
         | MicroOp::OrI64 { dst, a, b }
         | MicroOp::XorI64 { dst, a, b }
@@ -2075,6 +2182,7 @@ fn extract_vregs(mop: &MicroOp) -> Vec<usize> {
             vregs.push(src.0);
             vregs.push(offset.0);
         }
+        MicroOp::TypeTag { obj, .. } => vregs.push(obj.0),
         MicroOp::HeapAlloc { dst, args } => {
             vregs.push(dst.0);
             for a in args {
@@ -2182,6 +2290,30 @@ fn pop_vreg(
     mat(e, micro_ops, next_temp, max_temp, vreg_types)
 }
 
+/// Copy `local` into a temp for every vstack entry that still reads it, so
+/// the entries keep the old value once the local is overwritten.
+fn save_local_aliases(
+    vstack: &mut [Vse],
+    local: VReg,
+    micro_ops: &mut Vec<MicroOp>,
+    next_temp: &mut usize,
+    max_temp: &mut usize,
+    vreg_types: &mut Vec<ValueType>,
+) {
+    for entry in vstack.iter_mut() {
+        if let Vse::Reg(v) = entry
+            && *v == local
+        {
+            let temp = alloc_temp(next_temp, max_temp, vreg_types, ValueType::I64);
+            micro_ops.push(MicroOp::Mov {
+                dst: temp,
+                src: local,
+            });
+            *entry = Vse::Reg(temp);
+        }
+    }
+}
+
 /// Flush all virtual stack entries to the real operand stack.
 fn flush_vstack(
     vstack: &mut Vec<Vse>,
@@ -2326,6 +2458,11 @@ fn try_patch_dst(mop: &mut MicroOp, new_dst: VReg) -> Option<VReg> {
         | MicroOp::MulI64 { dst, .. }
         | MicroOp::DivI64 { dst, .. }
         | MicroOp::RemI64 { dst, .. }
+        | MicroOp::DivU64 { dst, .. }
+        | MicroOp::RemU64 { dst, .. }
+        | MicroOp::AddSatI64 { dst, .. }
+        | MicroOp::SubSatI64 { dst, .. }
+        | MicroOp::MulSatI64 { dst, .. }
         | MicroOp::AndI64 { dst, .. }
         | MicroOp::OrI64 { dst, .. }
         | MicroOp::XorI64 { dst, .. }
@@ -2430,24 +2567,45 @@ mod tests {
 
     #[test]
     fn test_const_and_local_set() {
-        // I64Const(42) is deferred, then materialized by LocalSet
+        // I64Const(42) is deferred, then LocalSet loads it straight into v0
         let func = make_func(vec![Op::I64Const(42), Op::LocalSet(0)]);
         let converted = convert(&func);
-        // ConstI64 + Mov = 2 MicroOps
-        assert_eq!(converted.micro_ops.len(), 2);
         assert_eq!(
-            converted.micro_ops[0],
-            MicroOp::ConstI64 {
-                dst: VReg(2),
+            converted.micro_ops,
+            vec![MicroOp::ConstI64 {
+                dst: VReg(0),
                 imm: 42
-            }
+            }]
         );
+    }
+
+    #[test]
+    fn test_const_local_set_saves_pending_reads() {
+        // `x = 5` while the old x is still on the vstack: the old value is
+        // copied out before the constant overwrites the local
+        let func = make_func(vec![
+            Op::LocalGet(0),
+            Op::I64Const(5),
+            Op::LocalSet(0),
+            Op::LocalSet(1),
+        ]);
+        let converted = convert(&func);
         assert_eq!(
-            converted.micro_ops[1],
-            MicroOp::Mov {
-                dst: VReg(0),
-                src: VReg(2)
-            }
+            converted.micro_ops,
+            vec![
+                MicroOp::Mov {
+                    dst: VReg(2),
+                    src: VReg(0)
+                },
+                MicroOp::ConstI64 {
+                    dst: VReg(0),
+                    imm: 5
+                },
+                MicroOp::Mov {
+                    dst: VReg(1),
+                    src: VReg(2)
+                },
+            ]
         );
     }
 
@@ -2575,10 +2733,10 @@ mod tests {
     fn test_jmp_target_remapping() {
         let func = make_func(vec![Op::I64Const(0), Op::LocalSet(0), Op::Jmp(0)]);
         let converted = convert(&func);
-        // ConstI64 + Mov + Jmp = 3 MicroOps
-        assert_eq!(converted.micro_ops.len(), 3);
+        // ConstI64 + Jmp = 2 MicroOps
+        assert_eq!(converted.micro_ops.len(), 2);
         assert_eq!(
-            converted.micro_ops[2],
+            converted.micro_ops[1],
             MicroOp::Jmp {
                 target: 0,
                 old_pc: 2,
@@ -2685,8 +2843,8 @@ mod tests {
         ]);
         let converted = convert(&func);
         // I64Const(1) deferred, I64Const(2) deferred, Drop consumes 2,
-        // LocalSet materializes 1 → ConstI64 + Mov = 2
-        assert_eq!(converted.micro_ops.len(), 2);
+        // LocalSet loads 1 straight into the local → a single ConstI64
+        assert_eq!(converted.micro_ops.len(), 1);
     }
 
     #[test]
@@ -2718,7 +2876,7 @@ mod tests {
             name: "test".to_string(),
             arity: 0,
             locals_count: 1,
-            code: vec![Op::F64Const(3.14), Op::LocalSet(0)],
+            code: vec![Op::F64Const(2.5), Op::LocalSet(0)],
             stackmap: None,
             local_types: vec![ValueType::F64],
         };
@@ -0,0 +1,344 @@
+//! Inlining of small leaf functions at the MicroOp level.
+//!
+//! A `Call` to a callee that is straight-line, short, non-allocating and
+//! free of calls is replaced by a copy of the callee's body whose vregs are
+//! renamed into a fresh register pool appended to the caller's frame.
+
+use std::collections::HashMap;
+
+use super::Function;
+use super::ValueType;
+use super::microop::{ConvertedFunction, MicroOp, VReg};
+use super::microop_converter;
+use super::ops::Op;
+
+/// Maximum number of MicroOps (excluding the final `Ret`) in an inlined callee.
+pub(crate) const INLINE_MAX_OPS: usize = 8;
+
+/// Convert a function to MicroOps and inline small leaf callees into it.
+/// `func_index` is `usize::MAX` for main.
+pub fn convert_inlined(
+    func: &Function,
+    func_index: usize,
+    functions: &[Function],
+) -> ConvertedFunction {
+    let mut converted = microop_converter::convert(func);
+    inline_small_calls(&mut converted, func.locals_count, func_index, functions);
+    converted
+}
+
+/// Inline eligible `Call`s in `caller` (see [`inlinable_body`]).
+pub fn inline_small_calls(
+    caller: &mut ConvertedFunction,
+    locals_count: usize,
+    func_index: usize,
+    functions: &[Function],
+) {
+    let has_candidate = caller.micro_ops.iter().any(|mop| {
+        matches!(mop, MicroOp::Call { func_id, args, .. }
+            if *func_id != func_index
+                && functions.get(*func_id).is_some_and(|f| f.arity == args.len()))
+    });
+    if !has_candidate {
+        return;
+    }
+
+    // Inlinable callee bodies and the base of their register pool in the caller.
+    let mut bodies: HashMap<usize, Option<(ConvertedFunction, usize)>> = HashMap::new();
+    let mut pool_end = locals_count + caller.temps_count;
+    caller.vreg_types.resize(pool_end, ValueType::I64);
+
+    let old_ops = std::mem::take(&mut caller.micro_ops);
+    let mut new_pc = Vec::with_capacity(old_ops.len() + 1);
+    let mut micro_ops = Vec::with_capacity(old_ops.len());
+
+    for mop in old_ops {
+        new_pc.push(micro_ops.len());
+        let MicroOp::Call { func_id, args, ret } = &mop else {
+            micro_ops.push(mop);
+            continue;
+        };
+        if *func_id == func_index
+            || functions
+                .get(*func_id)
+                .is_none_or(|f| f.arity != args.len())
+        {
+            micro_ops.push(mop);
+            continue;
+        }
+        let entry = bodies.entry(*func_id).or_insert_with(|| {
+            let callee = &functions[*func_id];
+            let body = microop_converter::convert(callee);
+            inlinable_body(&body)?;
+            let base = pool_end;
+            let regs = callee.locals_count + body.temps_count;
+            let mut types = body.vreg_types.clone();
+            types.resize(regs, ValueType::I64);
+            caller.vreg_types.extend(types);
+            pool_end += regs;
+            Some((body, base))
+        });
+        let Some((body, base)) = entry else {
+            micro_ops.push(mop);
+            continue;
+        };
+
+        let rename = |v: VReg| VReg(*base + v.0);
+        for (i, arg) in args.iter().enumerate() {
+            micro_ops.push(MicroOp::Mov {
+                dst: rename(VReg(i)),
+                src: *arg,
+            });
+        }
+        let ops = inlinable_body(body).expect("checked when the body was cached");
+        for op in ops {
+            match op {
+                MicroOp::Ret { src } => {
+                    match (ret, src) {
+                        (Some(dst), Some(src)) => micro_ops.push(MicroOp::Mov {
+                            dst: *dst,
+                            src: rename(*src),
+                        }),
+                        (Some(dst), None) => micro_ops.push(MicroOp::RefNull { dst: *dst }),
+                        (None, _) => {}
+                    }
+                    break;
+                }
+                _ => micro_ops.push(rename_vregs(op, &rename)),
+            }
+        }
+    }
+    new_pc.push(micro_ops.len());
+
+    for mop in &mut micro_ops {
+        match mop {
+            MicroOp::Jmp { target, .. }
+            | MicroOp::BrIf { target, .. }
+            | MicroOp::BrIfFalse { target, .. }
+            | MicroOp::Raw {
+                op: Op::TryBegin(target),
+            } => *target = new_pc[*target],
+            _ => {}
+        }
+    }
+    for pc in &mut caller.pc_map {
+        *pc = new_pc[*pc];
+    }
+    caller.micro_ops = micro_ops;
+    caller.temps_count = pool_end - locals_count;
+}
+
+/// Return the ops of `callee` up to and including its first `Ret` if it can
+/// be inlined: at most [`INLINE_MAX_OPS`] ops, no control flow, no calls, no
+/// allocation, and nothing that can throw besides a field or element load.
+fn inlinable_body(callee: &ConvertedFunction) -> Option<&[MicroOp]> {
+    let ret_pc = callee
+        .micro_ops
+        .iter()
+        .position(|mop| matches!(mop, MicroOp::Ret { .. }))?;
+    if ret_pc > INLINE_MAX_OPS {
+        return None;
+    }
+    let body = &callee.micro_ops[..=ret_pc];
+    body.iter()
+        .all(|mop| {
+            matches!(
+                mop,
+                MicroOp::Ret { .. }
+                    | MicroOp::Mov { .. }
+                    | MicroOp::ConstI64 { .. }
+                    | MicroOp::ConstI32 { .. }
+                    | MicroOp::ConstF64 { .. }
+                    | MicroOp::ConstF32 { .. }
+                    | MicroOp::AddI64 { .. }
+                    | MicroOp::AddI64Imm { .. }
+                    | MicroOp::SubI64 { .. }
+                    | MicroOp::MulI64 { .. }
+                    | MicroOp::AddSatI64 { .. }
+                    | MicroOp::SubSatI64 { .. }
+                    | MicroOp::MulSatI64 { .. }
+                    | MicroOp::NegI64 { .. }
+                    | MicroOp::AndI64 { .. }
+                    | MicroOp::OrI64 { .. }
+                    | MicroOp::XorI64 { .. }
+                    | MicroOp::ShlI64 { .. }
+                    | MicroOp::ShlI64Imm { .. }
+                    | MicroOp::ShrI64 { .. }
+                    | MicroOp::ShrI64Imm { .. }
+                    | MicroOp::ShrU64 { .. }
+                    | MicroOp::ShrU64Imm { .. }
+                    | MicroOp::AddF64 { .. }
+                    | MicroOp::SubF64 { .. }
+                    | MicroOp::MulF64 { .. }
+                    | MicroOp::DivF64 { .. }
+                    | MicroOp::NegF64 { .. }
+                    | MicroOp::CmpI64 { .. }
+                    | MicroOp::CmpI64Imm { .. }
+                    | MicroOp::CmpF64 { .. }
+                    | MicroOp::EqzI32 { .. }
+                    | MicroOp::RefEq { .. }
+                    | MicroOp::RefIsNull { .. }
+                    | MicroOp::RefNull { .. }
+                    | MicroOp::HeapLoad { .. }
+                    | MicroOp::HeapLoadDyn { .. }
+            )
+        })
+        .then_some(body)
+}
+
+/// Apply `f` to every vreg of an op accepted by [`inlinable_body`].
+fn rename_vregs(mop: &MicroOp, f: &impl Fn(VReg) -> VReg) -> MicroOp {
+    let mut mop = mop.clone();
+    match &mut mop {
+        MicroOp::Mov { dst, src }
+        | MicroOp::NegI64 { dst, src }
+        | MicroOp::NegF64 { dst, src }
+        | MicroOp::EqzI32 { dst, src }
+        | MicroOp::RefIsNull { dst, src }
+        | MicroOp::HeapLoad { dst, src, .. } => {
+            *dst = f(*dst);
+            *src = f(*src);
+        }
+        MicroOp::HeapLoadDyn { dst, obj, idx, .. } => {
+            *dst = f(*dst);
+            *obj = f(*obj);
+            *idx = f(*idx);
+        }
+        MicroOp::ConstI64 { dst, .. }
+        | MicroOp::ConstI32 { dst, .. }
+        | MicroOp::ConstF64 { dst, .. }
+        | MicroOp::ConstF32 { dst, .. }
+        | MicroOp::RefNull { dst } => *dst = f(*dst),
+        MicroOp::AddI64Imm { dst, a, .. }
+        | MicroOp::ShlI64Imm { dst, a, .. }
+        | MicroOp::ShrI64Imm { dst, a, .. }
+        | MicroOp::ShrU64Imm { dst, a, .. }
+        | MicroOp::CmpI64Imm { dst, a, .. } => {
+            *dst = f(*dst);
+            *a = f(*a);
+        }
+        MicroOp::AddI64 { dst, a, b }
+        | MicroOp::SubI64 { dst, a, b }
+        | MicroOp::MulI64 { dst, a, b }
+        | MicroOp::AddSatI64 { dst, a, b }
+        | MicroOp::SubSatI64 { dst, a, b }
+        | MicroOp::MulSatI64 { dst, a, b }
+        | MicroOp::AndI64 { dst, a, b }
+        | MicroOp::OrI64 { dst, a, b }
+        | MicroOp::XorI64 { dst, a, b }
+        | MicroOp::ShlI64 { dst, a, b }
+        | MicroOp::ShrI64 { dst, a, b }
+        | MicroOp::ShrU64 { dst, a, b }
+        | MicroOp::AddF64 { dst, a, b }
+        | MicroOp::SubF64 { dst, a, b }
+        | MicroOp::MulF64 { dst, a, b }
+        | MicroOp::DivF64 { dst, a, b }
+        | MicroOp::CmpI64 { dst, a, b, .. }
+        | MicroOp::CmpF64 { dst, a, b, .. }
+        | MicroOp::RefEq { dst, a, b } => {
+            *dst = f(*dst);
+            *a = f(*a);
+            *b = f(*b);
+        }
+        other => unreachable!("op not accepted by inlinable_body: {:?}", other),
+    }
+    mop
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vm::ElemKind;
+
+    fn func(name: &str, arity: usize, locals_count: usize, code: Vec<Op>) -> Function {
+        Function {
+            name: name.to_string(),
+            arity,
+            locals_count,
+            code,
+            stackmap: None,
+            local_types: vec![],
+        }
+    }
+
+    #[test]
+    fn test_getter_is_inlined() {
+        // fun get_x(self) { return self.x; }
+        let getter = func(
+            "Point::get_x",
+            1,
+            1,
+            vec![
+                Op::LocalGet(0),
+                Op::I64Const(0),
+                Op::HeapLoadDyn(ElemKind::Tagged),
+                Op::Ret,
+            ],
+        );
+        // main: let p = [40, 2]; return get_x(p) + 2
+        let main = func(
+            "main",
+            0,
+            1,
+            vec![
+                Op::I64Const(40),
+                Op::I64Const(2),
+                Op::HeapAlloc(2),
+                Op::LocalSet(0),
+                Op::LocalGet(0),
+                Op::Call(0, 1),
+                Op::I64Const(2),
+                Op::I64Add,
+                Op::Ret,
+            ],
+        );
+        let functions = vec![getter];
+
+        let converted = convert_inlined(&main, usize::MAX, &functions);
+        assert!(
+            !converted
+                .micro_ops
+                .iter()
+                .any(|mop| matches!(mop, MicroOp::Call { .. })),
+            "getter call should be inlined: {:?}",
+            converted.micro_ops
+        );
+        assert!(
+            converted
+                .micro_ops
+                .iter()
+                .any(|mop| matches!(mop, MicroOp::HeapLoadDyn { .. }))
+        );
+        assert_eq!(
+            converted.vreg_types.len(),
+            main.locals_count + converted.temps_count
+        );
+        assert_eq!(*converted.pc_map.last().unwrap(), converted.micro_ops.len());
+    }
+
+    #[test]
+    fn test_recursive_and_allocating_calls_are_kept() {
+        // fun f(n) { return f(n); } — self-recursive
+        let recursive = func("f", 1, 1, vec![Op::LocalGet(0), Op::Call(0, 1), Op::Ret]);
+        // fun g() { return [1]; } — allocates
+        let allocating = func("g", 0, 0, vec![Op::I64Const(1), Op::HeapAlloc(1), Op::Ret]);
+        let functions = vec![recursive, allocating];
+
+        let converted = convert_inlined(&functions[0], 0, &functions);
+        assert!(
+            converted
+                .micro_ops
+                .iter()
+                .any(|mop| matches!(mop, MicroOp::Call { func_id: 0, .. }))
+        );
+
+        let main = func("main", 0, 0, vec![Op::Call(1, 0), Op::Ret]);
+        let converted = convert_inlined(&main, usize::MAX, &functions);
+        assert!(
+            converted
+                .micro_ops
+                .iter()
+                .any(|mop| matches!(mop, MicroOp::Call { func_id: 1, .. }))
+        );
+    }
+}
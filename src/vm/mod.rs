@@ -4,9 +4,12 @@
 pub mod bytecode;
 pub mod concurrent_gc;
 pub mod debug;
+mod dict;
 mod heap;
+pub mod ic;
 pub mod microop;
 pub mod microop_converter;
+pub mod microop_inline;
 mod ops;
 pub mod stackmap;
 pub mod threads;
@@ -16,7 +19,9 @@ pub mod verifier;
 mod vm;
 
 pub use debug::{DebugInfo, FunctionDebugInfo};
-pub use heap::{ElemKind, GcRef, Heap};
+pub use heap::{ElemKind, ExportedValue, GcRef, Heap, HeapSnapshot, ObjectKind, object_size_bytes};
+// Generated code decodes element kinds from object headers
+pub(crate) use heap::HEADER_ELEM_KIND_SHIFT;
 pub use ops::Op;
 // StackMap types for precise GC (used by embedders/tools)
 #[allow(unused_imports)]
@@ -29,6 +34,29 @@ pub use verifier::{Verifier, VerifyError};
 #[allow(unused_imports)]
 pub use vm::OpcodeProfile;
 pub use vm::VM;
+pub use vm::debug_string;
+// VmJitStats exported for embedders inspecting JIT memory use
+#[allow(unused_imports)]
+pub use vm::VmJitStats;
+// VmSnapshot exported for embedders implementing save-states
+#[allow(unused_imports)]
+pub use vm::VmSnapshot;
+
+/// Default operand stack limit in values; far above what normal programs use.
+pub(crate) const DEFAULT_MAX_STACK_SIZE: usize = 1 << 24;
+
+/// Default number of OS threads spawned work may occupy at once.
+pub(crate) const DEFAULT_MAX_THREADS: usize = 64;
+
+/// Default limit on executable memory used by JIT-compiled code (64 MiB).
+pub(crate) const DEFAULT_JIT_CODE_BUDGET: usize = 64 << 20;
+
+/// Op count at which a function alone makes `--jit=auto` keep JIT enabled.
+pub(crate) const AUTO_JIT_MIN_FUNCTION_OPS: usize = 64;
+
+/// `--jit=auto` divides the threshold by this once the first function or
+/// loop turns hot.
+pub(crate) const AUTO_JIT_THRESHOLD_DIVISOR: u32 = 4;
 
 /// VM-level value type for the typed bytecode architecture.
 ///
@@ -89,6 +117,41 @@ pub struct InterfaceDescriptor {
     pub method_names: Vec<String>,
 }
 
+/// Version of the compiler that produced a chunk, as recorded in
+/// [`ChunkMetadata`].
+pub const COMPILER_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Provenance of a chunk, serialized with it so a cached chunk from another
+/// compiler or an older version of the source can be recognized as stale.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkMetadata {
+    /// Version of the compiler that produced the chunk
+    pub compiler_version: String,
+    /// [`source_hash`] of the source the chunk was compiled from, or 0 if
+    /// unknown (e.g. a chunk built by hand)
+    pub source_hash: u64,
+}
+
+impl Default for ChunkMetadata {
+    fn default() -> Self {
+        Self {
+            compiler_version: COMPILER_VERSION.to_string(),
+            source_hash: 0,
+        }
+    }
+}
+
+/// Hash of a program's source text for [`ChunkMetadata::source_hash`]
+/// (FNV-1a, so it is the same across builds and platforms).
+pub fn source_hash(source: &str) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for &b in source.as_bytes() {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
 /// A compiled chunk of bytecode.
 #[derive(Debug, Clone)]
 pub struct Chunk {
@@ -100,6 +163,171 @@ pub struct Chunk {
     pub type_descriptors: Vec<TypeDescriptor>,
     /// Interface descriptor table for runtime interface dispatch
     pub interface_descriptors: Vec<InterfaceDescriptor>,
+    /// Element templates of all-constant array literals, allocated by `ConstArray`.
+    /// Elements are always primitives (int, float, bool or nil).
+    pub const_arrays: Vec<Vec<Value>>,
     /// Debug information (optional)
     pub debug: Option<DebugInfo>,
+    /// Compiler version and source hash, checked when the chunk is loaded
+    pub metadata: ChunkMetadata,
+}
+
+/// `Chunk::remap_functions` mapping entry for a function to drop.
+pub const REMOVED_FUNCTION: usize = usize::MAX;
+
+impl Chunk {
+    /// Check that every function index in the chunk (`Call`, `ThreadSpawn`, `Spawn`,
+    /// `FuncRef` and vtable entries) names an existing function.
+    pub fn validate_function_indices(&self) -> Result<(), String> {
+        let count = self.functions.len();
+        let check = |owner: &str, func_index: usize| {
+            if func_index < count {
+                Ok(())
+            } else {
+                Err(format!(
+                    "{} refers to function {}, but the chunk has {}",
+                    owner, func_index, count
+                ))
+            }
+        };
+        for func in self.functions.iter().chain(std::iter::once(&self.main)) {
+            for op in &func.code {
+                if let Some(func_index) = op.func_index() {
+                    check(&func.name, func_index)?;
+                }
+            }
+        }
+        for td in &self.type_descriptors {
+            for (_, entries) in &td.vtables {
+                for &func_index in entries {
+                    check(&format!("vtable of {}", td.tag_name), func_index)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that every `Call` and `TailCall` passes as many arguments as its
+    /// callee takes. The interpreters trust this (only debug builds re-check
+    /// at run time); calls through `CallDynamic` and `CallIndirect` are still
+    /// checked when they run. Calls to unknown functions are left to
+    /// [`Chunk::validate_function_indices`].
+    pub fn validate_call_arities(&self) -> Result<(), String> {
+        for func in self.functions.iter().chain(std::iter::once(&self.main)) {
+            for op in &func.code {
+                if let Op::Call(func_index, argc) | Op::TailCall(func_index, argc) = *op
+                    && let Some(callee) = self.functions.get(func_index)
+                    && callee.arity != argc
+                {
+                    return Err(format!(
+                        "{}: function '{}' expects {} arguments, got {}",
+                        func.name, callee.name, callee.arity, argc
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reorder or prune functions: function `i` moves to index `mapping[i]`,
+    /// or is dropped when `mapping[i]` is [`REMOVED_FUNCTION`]. Every function
+    /// index in the chunk is rewritten to match, along with the per-function
+    /// debug info.
+    ///
+    /// The kept functions must land exactly on `0..kept`, and nothing that
+    /// stays may refer to a dropped function. On error the chunk is unchanged.
+    pub fn remap_functions(&mut self, mapping: &[usize]) -> Result<(), String> {
+        if mapping.len() != self.functions.len() {
+            return Err(format!(
+                "function mapping has {} entries, but the chunk has {} functions",
+                mapping.len(),
+                self.functions.len()
+            ));
+        }
+        let kept = mapping.iter().filter(|&&m| m != REMOVED_FUNCTION).count();
+        let mut taken = vec![false; kept];
+        for (old, &new) in mapping.iter().enumerate() {
+            if new == REMOVED_FUNCTION {
+                continue;
+            }
+            if new >= kept || std::mem::replace(&mut taken[new], true) {
+                return Err(format!(
+                    "function {} maps to {}, which is out of range or already taken",
+                    old, new
+                ));
+            }
+        }
+
+        self.validate_function_indices()?;
+        let live_code = self
+            .functions
+            .iter()
+            .zip(mapping)
+            .filter(|(_, new)| **new != REMOVED_FUNCTION)
+            .map(|(func, _)| func)
+            .chain(std::iter::once(&self.main));
+        for func in live_code {
+            for op in &func.code {
+                if let Some(func_index) = op.func_index()
+                    && mapping[func_index] == REMOVED_FUNCTION
+                {
+                    return Err(format!(
+                        "{} refers to removed function {}",
+                        func.name, self.functions[func_index].name
+                    ));
+                }
+            }
+        }
+        for td in &self.type_descriptors {
+            for (_, entries) in &td.vtables {
+                if let Some(&func_index) = entries.iter().find(|&&f| mapping[f] == REMOVED_FUNCTION)
+                {
+                    return Err(format!(
+                        "vtable of {} refers to removed function {}",
+                        td.tag_name, self.functions[func_index].name
+                    ));
+                }
+            }
+        }
+
+        for func in self
+            .functions
+            .iter_mut()
+            .chain(std::iter::once(&mut self.main))
+        {
+            for op in &mut func.code {
+                if let Some(func_index) = op.func_index_mut()
+                    && mapping[*func_index] != REMOVED_FUNCTION
+                {
+                    *func_index = mapping[*func_index];
+                }
+            }
+        }
+        for td in &mut self.type_descriptors {
+            for (_, entries) in &mut td.vtables {
+                for func_index in entries {
+                    *func_index = mapping[*func_index];
+                }
+            }
+        }
+
+        self.functions = permute(std::mem::take(&mut self.functions), mapping, kept);
+        if let Some(debug) = &mut self.debug
+            && debug.functions.len() == mapping.len()
+        {
+            debug.functions = permute(std::mem::take(&mut debug.functions), mapping, kept);
+        }
+        Ok(())
+    }
+}
+
+/// Place `items[i]` at `mapping[i]`, dropping items mapped to `REMOVED_FUNCTION`.
+fn permute<T>(items: Vec<T>, mapping: &[usize], kept: usize) -> Vec<T> {
+    let mut slots: Vec<Option<T>> = (0..kept).map(|_| None).collect();
+    for (item, &new) in items.into_iter().zip(mapping) {
+        if new != REMOVED_FUNCTION {
+            slots[new] = Some(item);
+        }
+    }
+    slots.into_iter().map(Option::unwrap).collect()
 }
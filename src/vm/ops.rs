@@ -18,6 +18,12 @@ pub enum Op {
     F64Const(f64),
     RefNull,
     StringConst(usize), // string pool index → ref
+    /// [] → [ref]: a new `Array` holding a copy of the chunk's constant array
+    /// template at this index (see `Chunk::const_arrays`).
+    ConstArray(usize),
+    /// [] → [i64]: a function index as a value (e.g. a closure's target).
+    /// Kept apart from `I64Const` so `Chunk::remap_functions` can rewrite it.
+    FuncRef(usize),
 
     // ========================================
     // Local Variables
@@ -51,7 +57,13 @@ pub enum Op {
     I64Mul,
     I64DivS,
     I64RemS,
-    I64Neg, // [i64] → [i64] (0 - x)
+    I64DivU,       // [i64, i64] → [i64] (unsigned)
+    I64RemU,       // [i64, i64] → [i64] (unsigned)
+    I64AddSat,     // [i64, i64] → [i64] (saturating)
+    I64SubSat,     // [i64, i64] → [i64] (saturating)
+    I64MulSat,     // [i64, i64] → [i64] (saturating)
+    I64AddChecked, // [i64, i64] → [i64 | null] (null on overflow)
+    I64Neg,        // [i64] → [i64] (0 - x)
     I64And,
     I64Or,
     I64Xor,
@@ -122,6 +134,14 @@ pub enum Op {
     // ========================================
     RefEq,
     RefIsNull,
+    /// Structural equality for arrays and strings → i32.
+    /// Compares lengths and elements of this many nested array layers (from
+    /// the static type: 1 for `array<int>` and strings, 2 for `array<string>`);
+    /// past them, references are compared by identity.
+    DeepEq(usize),
+    /// Three-way comparison of two strings → i64 (-1, 0 or 1), lexicographic
+    /// by bytes. Emitted for orderings whose operands are statically strings.
+    Compare,
 
     // ========================================
     // Type Conversion
@@ -148,6 +168,10 @@ pub enum Op {
     BrIf(usize),        // [i32] → [] (branch if != 0)
     BrIfFalse(usize),   // [i32] → [] (branch if == 0)
     Call(usize, usize), // (func_index, argc)
+    /// Call in tail position: replaces the current frame with the callee's
+    /// instead of pushing a new one. Codegen always follows it with `Ret`,
+    /// which runs when the VM falls back to an ordinary call.
+    TailCall(usize, usize), // (func_index, argc)
     Ret,
 
     // ========================================
@@ -172,22 +196,76 @@ pub enum Op {
     HeapStore2(super::heap::ElemKind),
     /// Offset a reference: pop offset, pop ref → push ref with slot_offset += offset
     HeapOffsetRef,
+    /// Record type descriptor `idx` on the struct on top of the stack (left in
+    /// place), so it prints with its type and field names
+    TypeTag(usize),
 
     // ========================================
     // System / Builtins
     // ========================================
     Hostcall(usize, usize),
     GcHint(usize),
+    /// Source-level breakpoint (`breakpoint()`): pauses the debugger, no-op otherwise
+    DebugBreak,
     UMul128Hi,
     /// Returns the runtime type tag of a value: 0=I64, 1=F64, 2=Bool, 3=Null, 4=Ref
     TypeOf,
+    /// Pops a value and pushes its type name as a string: `"i64"`, `"f64"`,
+    /// `"bool"`, `"nil"`, `"string"`, a struct's name, or `"ref"`
+    TypeName,
+    /// Errors unless the top value's type tag (as in `TypeOf`) matches; leaves it on the stack
+    AssertType(u8),
     /// Returns the number of slots in a heap object
     HeapSize,
+    /// [array, start, end] → [array]: copy of the elements in `[start, end)`
+    ArraySlice,
+    /// Pops a reference to an unshared temporary and frees it (and the objects
+    /// its slots point to) without waiting for GC
+    FreeTemp,
+    /// [] → [map]: allocates an empty hash map
+    MapNew,
+    /// [map, key] → [value]: errors if the key is absent
+    MapGet,
+    /// [map, key, value] → []: inserts or overwrites
+    MapSet,
+    /// [map, key] → [bool]
+    MapHas,
+    /// [map, key] → [bool]: removes the key, returning whether it was present
+    MapDelete,
+    /// [map] → [int]: number of entries
+    MapLen,
+    /// [map] → [array]: keys in insertion order
+    MapKeys,
+    /// [map] → [array]: values in insertion order
+    MapValues,
+    /// [map] → [array]: `[key, value]` pairs in insertion order
+    MapEntries,
+    /// [string, sep] → [array]: pieces of the string between separators
+    StringSplit,
+    /// [array, sep] → [string]: strings concatenated with the separator between them
+    StringJoin,
+    /// [string, start, end] → [string]: chars in `[start, end)`, indices clamped
+    StringSubstring,
+    /// [string, needle] → [int]: char index of the first match, or -1
+    StringIndexOf,
+    /// [string, from, to] → [string]: every occurrence of `from` replaced
+    StringReplace,
+    /// [builder, string] → []: append to a `StringBuilder` `[data, len]`,
+    /// doubling its byte buffer when full
+    StringBuilderAppend,
+    /// [builder] → [string]: copy of the builder's contents; the builder is unchanged
+    StringBuilderFinish,
+    /// [string] → [array<byte>]: the string's UTF-8 bytes
+    StringToBytes,
+    /// [array<byte>] → [string]: errors unless the bytes are valid UTF-8
+    BytesToString,
 
     // ========================================
     // Exception Handling
     // ========================================
     Throw,
+    /// Marks code the compiler proved cannot run; errors if reached
+    Unreachable,
     TryBegin(usize),
     TryEnd,
 
@@ -201,11 +279,32 @@ pub enum Op {
     // ========================================
     // Threading
     // ========================================
-    ThreadSpawn(usize),
+    /// [atomic × argc] → [thread_id]: call the function on a new thread,
+    /// passing it the atomics (the only values that can cross threads)
+    ThreadSpawn(usize, usize),
     ChannelCreate,
     ChannelSend,
     ChannelRecv,
+    /// [thread_id] → [result]: wait for the thread, copy its result into
+    /// this VM's heap, and rethrow its error if it failed
     ThreadJoin,
+    /// [] → [promise]: like `ThreadSpawn`, but pushes a promise object
+    /// `[thread_id, resolved, result]` for `Await`
+    Spawn(usize),
+    /// [promise] → [result]: join the promise's thread on first use (as
+    /// `ThreadJoin`) and cache its result; later awaits return the cached value
+    Await,
+    /// [init] → [atomic]: new atomic integer, shared with the threads it is
+    /// passed to by `ThreadSpawn`
+    AtomicNew,
+    /// [atomic] → [i64]
+    AtomicLoad,
+    /// [atomic, value] → []
+    AtomicStore,
+    /// [atomic, delta] → [i64]: adds `delta` and returns the previous value
+    AtomicFetchAdd,
+    /// [atomic, expected, new] → [bool]: stores `new` only if the current value is `expected`
+    AtomicCompareExchange,
 
     // ========================================
     // Indirect call
@@ -240,10 +339,12 @@ impl Op {
         match self {
             Op::I32Const(_) => "I32Const",
             Op::I64Const(_) => "I64Const",
+            Op::FuncRef(_) => "FuncRef",
             Op::F32Const(_) => "F32Const",
             Op::F64Const(_) => "F64Const",
             Op::RefNull => "RefNull",
             Op::StringConst(_) => "StringConst",
+            Op::ConstArray(_) => "ConstArray",
             Op::LocalGet(_) => "LocalGet",
             Op::LocalSet(_) => "LocalSet",
             Op::Drop => "Drop",
@@ -261,6 +362,12 @@ impl Op {
             Op::I64Mul => "I64Mul",
             Op::I64DivS => "I64DivS",
             Op::I64RemS => "I64RemS",
+            Op::I64DivU => "I64DivU",
+            Op::I64RemU => "I64RemU",
+            Op::I64AddSat => "I64AddSat",
+            Op::I64SubSat => "I64SubSat",
+            Op::I64MulSat => "I64MulSat",
+            Op::I64AddChecked => "I64AddChecked",
             Op::I64Neg => "I64Neg",
             Op::I64And => "I64And",
             Op::I64Or => "I64Or",
@@ -304,6 +411,8 @@ impl Op {
             Op::F64Ge => "F64Ge",
             Op::RefEq => "RefEq",
             Op::RefIsNull => "RefIsNull",
+            Op::DeepEq(_) => "DeepEq",
+            Op::Compare => "Compare",
             Op::I32WrapI64 => "I32WrapI64",
             Op::I64ExtendI32S => "I64ExtendI32S",
             Op::I64ExtendI32U => "I64ExtendI32U",
@@ -322,10 +431,12 @@ impl Op {
             Op::BrIf(_) => "BrIf",
             Op::BrIfFalse(_) => "BrIfFalse",
             Op::Call(_, _) => "Call",
+            Op::TailCall(_, _) => "TailCall",
             Op::Ret => "Ret",
             Op::HeapAlloc(_) => "HeapAlloc",
             Op::HeapAllocDyn => "HeapAllocDyn",
             Op::HeapAllocDynSimple(_) => "HeapAllocDynSimple",
+            Op::TypeTag(_) => "TypeTag",
             Op::HeapLoad(_) => "HeapLoad",
             Op::HeapStore(_) => "HeapStore",
             Op::HeapLoadDyn(_) => "HeapLoadDyn",
@@ -335,24 +446,79 @@ impl Op {
             Op::HeapOffsetRef => "HeapOffsetRef",
             Op::Hostcall(_, _) => "Hostcall",
             Op::GcHint(_) => "GcHint",
+            Op::DebugBreak => "DebugBreak",
             Op::UMul128Hi => "UMul128Hi",
             Op::TypeOf => "TypeOf",
+            Op::TypeName => "TypeName",
+            Op::AssertType(_) => "AssertType",
             Op::HeapSize => "HeapSize",
+            Op::ArraySlice => "ArraySlice",
+            Op::FreeTemp => "FreeTemp",
+            Op::MapNew => "MapNew",
+            Op::MapGet => "MapGet",
+            Op::MapSet => "MapSet",
+            Op::MapHas => "MapHas",
+            Op::MapDelete => "MapDelete",
+            Op::MapLen => "MapLen",
+            Op::MapKeys => "MapKeys",
+            Op::MapValues => "MapValues",
+            Op::MapEntries => "MapEntries",
+            Op::StringSplit => "StringSplit",
+            Op::StringJoin => "StringJoin",
+            Op::StringSubstring => "StringSubstring",
+            Op::StringIndexOf => "StringIndexOf",
+            Op::StringReplace => "StringReplace",
+            Op::StringBuilderAppend => "StringBuilderAppend",
+            Op::StringBuilderFinish => "StringBuilderFinish",
+            Op::StringToBytes => "StringToBytes",
+            Op::BytesToString => "BytesToString",
             Op::Throw => "Throw",
+            Op::Unreachable => "Unreachable",
             Op::TryBegin(_) => "TryBegin",
             Op::TryEnd => "TryEnd",
             Op::Argc => "Argc",
             Op::Argv => "Argv",
             Op::Args => "Args",
-            Op::ThreadSpawn(_) => "ThreadSpawn",
+            Op::ThreadSpawn(_, _) => "ThreadSpawn",
             Op::ChannelCreate => "ChannelCreate",
             Op::ChannelSend => "ChannelSend",
             Op::ChannelRecv => "ChannelRecv",
             Op::ThreadJoin => "ThreadJoin",
+            Op::Spawn(_) => "Spawn",
+            Op::Await => "Await",
+            Op::AtomicNew => "AtomicNew",
+            Op::AtomicLoad => "AtomicLoad",
+            Op::AtomicStore => "AtomicStore",
+            Op::AtomicFetchAdd => "AtomicFetchAdd",
+            Op::AtomicCompareExchange => "AtomicCompareExchange",
             Op::CallIndirect(_) => "CallIndirect",
             Op::CallDynamic(_) => "CallDynamic",
             Op::VtableLookup => "VtableLookup",
             Op::GlobalGet(_) => "GlobalGet",
         }
     }
+
+    /// The function index this op refers to, if any.
+    pub fn func_index(&self) -> Option<usize> {
+        match self {
+            Op::Call(func_index, _)
+            | Op::TailCall(func_index, _)
+            | Op::ThreadSpawn(func_index, _)
+            | Op::Spawn(func_index)
+            | Op::FuncRef(func_index) => Some(*func_index),
+            _ => None,
+        }
+    }
+
+    /// Mutable access to the function index this op refers to, if any.
+    pub fn func_index_mut(&mut self) -> Option<&mut usize> {
+        match self {
+            Op::Call(func_index, _)
+            | Op::TailCall(func_index, _)
+            | Op::ThreadSpawn(func_index, _)
+            | Op::Spawn(func_index)
+            | Op::FuncRef(func_index) => Some(func_index),
+            _ => None,
+        }
+    }
 }
@@ -170,7 +170,7 @@ pub fn is_safepoint(op: &super::ops::Op, pc: usize) -> bool {
 
     match op {
         // CALL is always a safepoint
-        Op::Call(_, _) => true,
+        Op::Call(_, _) | Op::TailCall(_, _) => true,
 
         // Heap allocation is also a safepoint
         Op::HeapAlloc(_) => true,
@@ -181,7 +181,7 @@ pub fn is_safepoint(op: &super::ops::Op, pc: usize) -> bool {
         Op::BrIfFalse(target) => *target < pc,
 
         // Thread operations may allocate
-        Op::ThreadSpawn(_) | Op::ChannelCreate => true,
+        Op::ThreadSpawn(_, _) | Op::Spawn(_) | Op::ChannelCreate => true,
 
         _ => false,
     }
@@ -2,22 +2,32 @@
 //!
 //! This module provides OS thread support with:
 //! - Thread spawning with independent VM instances
+//! - An optional bounded worker pool that queues spawns beyond its size
 //! - Join handles for waiting on thread completion
 //! - Channel-based communication between threads
+//! - A deterministic mode where threads take turns on a fixed schedule
 
 // Thread support is not yet integrated, allow dead code
 #![allow(dead_code)]
 
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
+use std::panic::{self, AssertUnwindSafe};
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Condvar, Mutex};
 use std::thread::{self, JoinHandle};
 
-use super::Value;
+use super::{ExportedValue, Value};
 
 /// Thread ID counter for generating unique IDs.
 static NEXT_THREAD_ID: AtomicUsize = AtomicUsize::new(1);
 
+/// What a thread hands to whoever joins it: its result copied out of its own
+/// heap, or the error it stopped with.
+pub type ThreadResult = Result<ExportedValue, String>;
+
+/// Result of a thread whose code panicked.
+const PANICKED_ERROR: &str = "runtime error: thread panicked";
+
 /// Generate a new unique thread ID.
 fn next_thread_id() -> usize {
     NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed)
@@ -28,31 +38,49 @@ pub struct ThreadHandle {
     /// Unique thread ID
     pub id: usize,
     /// Join handle for the OS thread
-    handle: Option<JoinHandle<Value>>,
+    handle: Option<JoinHandle<ThreadResult>>,
+    /// The queued task, when spawned onto a worker pool
+    task: Option<Arc<PoolTask>>,
     /// Whether the thread has been joined
     joined: bool,
 }
 
 impl ThreadHandle {
     /// Create a new thread handle.
-    fn new(id: usize, handle: JoinHandle<Value>) -> Self {
+    fn new(id: usize, handle: JoinHandle<ThreadResult>) -> Self {
         Self {
             id,
             handle: Some(handle),
+            task: None,
+            joined: false,
+        }
+    }
+
+    /// Create a handle for a task submitted to a worker pool.
+    fn pooled(id: usize, task: Arc<PoolTask>) -> Self {
+        Self {
+            id,
+            handle: None,
+            task: Some(task),
             joined: false,
         }
     }
 
     /// Wait for the thread to complete and return its result.
-    pub fn join(&mut self) -> Result<Value, String> {
+    pub fn join(&mut self) -> ThreadResult {
         if self.joined {
             return Err("Thread already joined".to_string());
         }
 
+        if let Some(task) = self.task.take() {
+            self.joined = true;
+            return task.join();
+        }
+
         match self.handle.take() {
             Some(h) => {
                 self.joined = true;
-                h.join().map_err(|e| format!("Thread panicked: {:?}", e))
+                h.join().unwrap_or_else(|_| Err(PANICKED_ERROR.to_string()))
             }
             None => Err("Thread handle already taken".to_string()),
         }
@@ -68,6 +96,12 @@ impl ThreadHandle {
 pub struct ThreadSpawner {
     /// Active thread handles
     handles: Vec<ThreadHandle>,
+    /// Scheduler shared by every thread of a deterministic run
+    scheduler: Option<Arc<Scheduler>>,
+    /// The scheduler task this spawner belongs to (0 = main)
+    task: usize,
+    /// Worker pool bounding the number of OS threads (None = one per spawn)
+    pool: Option<Arc<ThreadPool>>,
 }
 
 impl ThreadSpawner {
@@ -75,28 +109,156 @@ impl ThreadSpawner {
     pub fn new() -> Self {
         Self {
             handles: Vec::new(),
+            scheduler: None,
+            task: 0,
+            pool: None,
+        }
+    }
+
+    /// Create a spawner that runs spawned work on at most `max_threads` OS
+    /// threads at once. Further spawns wait in a queue until a worker frees
+    /// up; joining a task that hasn't started yet runs it on the joining
+    /// thread, so a full pool can't deadlock on nested joins.
+    pub fn with_max_threads(max_threads: usize) -> Self {
+        Self {
+            pool: Some(ThreadPool::new(max_threads)),
+            ..Self::new()
         }
     }
 
+    /// Create a spawner for the main thread of a deterministic run.
+    ///
+    /// Spawned threads still get their own OS thread, but only one of them
+    /// runs at a time. The running thread hands over at every channel
+    /// operation, round-robin in spawn order, and blocking (`recv` on an
+    /// empty channel, `join` on a live thread) passes the turn on as well.
+    /// The interleaving therefore depends only on the program, and a program
+    /// where every thread is blocked fails with a deadlock error instead of
+    /// hanging.
+    pub fn deterministic() -> Self {
+        Self {
+            scheduler: Some(Scheduler::new()),
+            ..Self::new()
+        }
+    }
+
+    /// Whether this spawner schedules threads deterministically.
+    pub fn is_deterministic(&self) -> bool {
+        self.scheduler.is_some()
+    }
+
     /// Spawn a new thread that runs the given closure.
     /// The closure should set up a VM and run moca code.
     pub fn spawn<F>(&mut self, f: F) -> usize
     where
-        F: FnOnce() -> Value + Send + 'static,
+        F: FnOnce() -> ThreadResult + Send + 'static,
+    {
+        self.spawn_with_spawner(move |_| f())
+    }
+
+    /// Spawn a new thread, handing the closure the spawner its own VM should
+    /// use so that nested spawns and channel operations join the same
+    /// schedule.
+    pub fn spawn_with_spawner<F>(&mut self, f: F) -> usize
+    where
+        F: FnOnce(ThreadSpawner) -> ThreadResult + Send + 'static,
     {
-        let id = next_thread_id();
-        let handle = thread::spawn(f);
+        let Some(scheduler) = self.scheduler.clone() else {
+            let id = next_thread_id();
+            let child = ThreadSpawner {
+                pool: self.pool.clone(),
+                ..Self::new()
+            };
+            match &self.pool {
+                Some(pool) => {
+                    let task = pool.submit(Box::new(move || f(child)));
+                    self.handles.push(ThreadHandle::pooled(id, task));
+                }
+                None => {
+                    let handle = thread::spawn(move || f(child));
+                    self.handles.push(ThreadHandle::new(id, handle));
+                }
+            }
+            return id;
+        };
+
+        let id = scheduler.register();
+        let child = ThreadSpawner {
+            scheduler: Some(Arc::clone(&scheduler)),
+            task: id,
+            ..Self::new()
+        };
+        let handle = thread::spawn(move || {
+            let result = scheduler.wait_turn(id).and_then(|()| {
+                panic::catch_unwind(AssertUnwindSafe(|| f(child)))
+                    .unwrap_or_else(|_| Err(PANICKED_ERROR.to_string()))
+            });
+            scheduler.finish(id, result.clone());
+            result
+        });
         self.handles.push(ThreadHandle::new(id, handle));
         id
     }
 
+    /// Send `value` on `channel`, then (when deterministic) hand the turn to
+    /// the next thread.
+    pub fn send(
+        &self,
+        channel_id: usize,
+        channel: &ValueChannel,
+        value: Value,
+    ) -> Result<(), String> {
+        channel
+            .send(value)
+            .map_err(|_| "runtime error: channel closed".to_string())?;
+        if let Some(scheduler) = &self.scheduler {
+            scheduler.wake(Wait::Message(channel_id));
+            scheduler.yield_now(self.task)?;
+        }
+        Ok(())
+    }
+
+    /// Receive from `channel`, blocking while it is empty. Returns nil once
+    /// the channel is closed and drained.
+    pub fn recv(&self, channel_id: usize, channel: &ValueChannel) -> Result<Value, String> {
+        let Some(scheduler) = &self.scheduler else {
+            return Ok(channel.recv().unwrap_or(Value::Null));
+        };
+        loop {
+            if let Some(value) = channel.try_recv() {
+                scheduler.yield_now(self.task)?;
+                return Ok(value);
+            }
+            if channel.is_closed() {
+                return Ok(Value::Null);
+            }
+            scheduler.block(self.task, Wait::Message(channel_id))?;
+        }
+    }
+
     /// Get a thread handle by ID.
     pub fn get_handle(&mut self, id: usize) -> Option<&mut ThreadHandle> {
         self.handles.iter_mut().find(|h| h.id == id)
     }
 
-    /// Join a thread by ID and return its result.
-    pub fn join(&mut self, id: usize) -> Result<Value, String> {
+    /// Join a thread by ID and return its result. A thread that failed
+    /// returns its error.
+    pub fn join(&mut self, id: usize) -> ThreadResult {
+        if let Some(scheduler) = self.scheduler.clone() {
+            match self.get_handle(id) {
+                Some(handle) if handle.is_joined() => {
+                    return Err("Thread already joined".to_string());
+                }
+                Some(_) => {}
+                None => return Err(format!("Thread {} not found", id)),
+            }
+            let result = scheduler.join(self.task, id)?;
+            // The thread has finished; reap the OS thread
+            if let Some(handle) = self.get_handle(id) {
+                let _ = handle.join();
+            }
+            return result;
+        }
         match self.get_handle(id) {
             Some(handle) => handle.join(),
             None => Err(format!("Thread {} not found", id)),
@@ -115,6 +277,247 @@ impl Default for ThreadSpawner {
     }
 }
 
+/// Work submitted to a `ThreadPool`.
+type Job = Box<dyn FnOnce() -> ThreadResult + Send>;
+
+/// A spawned task queued on or running in a `ThreadPool`.
+struct PoolTask {
+    /// The work, taken by whichever thread runs it first
+    job: Mutex<Option<Job>>,
+    /// The result once the task has run
+    result: Mutex<Option<ThreadResult>>,
+    done: Condvar,
+}
+
+impl PoolTask {
+    /// Run the job unless another thread already took it.
+    fn run(&self) {
+        let Some(job) = self.job.lock().unwrap().take() else {
+            return;
+        };
+        let result = panic::catch_unwind(AssertUnwindSafe(job))
+            .unwrap_or_else(|_| Err(PANICKED_ERROR.to_string()));
+        *self.result.lock().unwrap() = Some(result);
+        self.done.notify_all();
+    }
+
+    /// Wait for the result, running the job here if no worker has started it.
+    fn join(&self) -> ThreadResult {
+        self.run();
+        let mut result = self.result.lock().unwrap();
+        loop {
+            if let Some(value) = result.take() {
+                return value;
+            }
+            result = self.done.wait(result).unwrap();
+        }
+    }
+}
+
+/// Queue state of a `ThreadPool`.
+struct PoolState {
+    queue: VecDeque<Arc<PoolTask>>,
+    /// Number of live worker threads
+    workers: usize,
+}
+
+/// A bounded set of worker threads shared by a VM and the threads it spawns.
+/// Workers start on demand and exit once the queue is empty.
+struct ThreadPool {
+    max_workers: usize,
+    state: Mutex<PoolState>,
+}
+
+impl ThreadPool {
+    fn new(max_workers: usize) -> Arc<Self> {
+        Arc::new(Self {
+            max_workers: max_workers.max(1),
+            state: Mutex::new(PoolState {
+                queue: VecDeque::new(),
+                workers: 0,
+            }),
+        })
+    }
+
+    /// Queue `job`, starting a worker for it if the pool isn't full.
+    fn submit(self: &Arc<Self>, job: Job) -> Arc<PoolTask> {
+        let task = Arc::new(PoolTask {
+            job: Mutex::new(Some(job)),
+            result: Mutex::new(None),
+            done: Condvar::new(),
+        });
+        let mut state = self.state.lock().unwrap();
+        state.queue.push_back(Arc::clone(&task));
+        if state.workers < self.max_workers {
+            state.workers += 1;
+            let pool = Arc::clone(self);
+            thread::spawn(move || pool.work());
+        }
+        task
+    }
+
+    /// Worker loop: run queued tasks until none are left.
+    fn work(&self) {
+        loop {
+            let task = {
+                let mut state = self.state.lock().unwrap();
+                match state.queue.pop_front() {
+                    Some(task) => task,
+                    None => {
+                        state.workers -= 1;
+                        return;
+                    }
+                }
+            };
+            task.run();
+        }
+    }
+}
+
+/// What a blocked task is waiting for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Wait {
+    /// A message on the channel with this id
+    Message(usize),
+    /// The task with this id to finish
+    Task(usize),
+}
+
+/// Shared state of a deterministic run.
+struct SchedulerState {
+    /// Live task ids in spawn order (0 = main)
+    tasks: Vec<usize>,
+    /// The task allowed to run
+    current: usize,
+    /// Blocked tasks and what they wait for
+    waiting: HashMap<usize, Wait>,
+    /// Results of finished tasks not yet joined
+    results: HashMap<usize, ThreadResult>,
+    next_id: usize,
+    /// Set once every live task is blocked; all waits then fail
+    deadlocked: bool,
+}
+
+impl SchedulerState {
+    /// Hand the turn to the first runnable task after `me` in spawn order,
+    /// wrapping around (so `me` itself comes last). Returns false if no task
+    /// is runnable.
+    fn pass_turn(&mut self, me: usize) -> bool {
+        let runnable = |id: &&usize| !self.waiting.contains_key(*id);
+        let next = self
+            .tasks
+            .iter()
+            .filter(|id| **id > me)
+            .find(runnable)
+            .or_else(|| self.tasks.iter().filter(|id| **id <= me).find(runnable));
+        match next {
+            Some(&id) => {
+                self.current = id;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Cooperative scheduler behind `ThreadSpawner::deterministic`: every task
+/// runs on its own OS thread but waits for its turn before doing anything.
+struct Scheduler {
+    state: Mutex<SchedulerState>,
+    turn: Condvar,
+}
+
+const DEADLOCK_ERROR: &str = "runtime error: deadlock: every thread is blocked";
+
+impl Scheduler {
+    fn new() -> Arc<Self> {
+        Arc::new(Self {
+            state: Mutex::new(SchedulerState {
+                tasks: vec![0],
+                current: 0,
+                waiting: HashMap::new(),
+                results: HashMap::new(),
+                next_id: 1,
+                deadlocked: false,
+            }),
+            turn: Condvar::new(),
+        })
+    }
+
+    /// Add a new task at the end of the schedule.
+    fn register(&self) -> usize {
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.tasks.push(id);
+        id
+    }
+
+    /// Block the calling OS thread until it is task `me`'s turn.
+    fn wait_turn(&self, me: usize) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        while state.current != me && !state.deadlocked {
+            state = self.turn.wait(state).unwrap();
+        }
+        if state.deadlocked {
+            return Err(DEADLOCK_ERROR.to_string());
+        }
+        Ok(())
+    }
+
+    /// Let the next runnable task go first.
+    fn yield_now(&self, me: usize) -> Result<(), String> {
+        {
+            let mut state = self.state.lock().unwrap();
+            state.pass_turn(me);
+            self.turn.notify_all();
+        }
+        self.wait_turn(me)
+    }
+
+    /// Park task `me` until `wait` is satisfied and its turn comes again.
+    fn block(&self, me: usize, wait: Wait) -> Result<(), String> {
+        {
+            let mut state = self.state.lock().unwrap();
+            state.waiting.insert(me, wait);
+            if !state.pass_turn(me) {
+                state.deadlocked = true;
+                self.turn.notify_all();
+                return Err(DEADLOCK_ERROR.to_string());
+            }
+            self.turn.notify_all();
+        }
+        self.wait_turn(me)
+    }
+
+    /// Make every task waiting for `wait` runnable again.
+    fn wake(&self, wait: Wait) {
+        self.state.lock().unwrap().waiting.retain(|_, w| *w != wait);
+    }
+
+    /// Record the result of task `me` and pass the turn on for good.
+    fn finish(&self, me: usize, result: ThreadResult) {
+        let mut state = self.state.lock().unwrap();
+        state.results.insert(me, result);
+        state.tasks.retain(|id| *id != me);
+        state.waiting.retain(|_, w| *w != Wait::Task(me));
+        if !state.pass_turn(me) && !state.tasks.is_empty() {
+            state.deadlocked = true;
+        }
+        self.turn.notify_all();
+    }
+
+    /// Wait (as task `me`) for task `id` to finish and take its result.
+    fn join(&self, me: usize, id: usize) -> Result<ThreadResult, String> {
+        loop {
+            if let Some(result) = self.state.lock().unwrap().results.remove(&id) {
+                return Ok(result);
+            }
+            self.block(me, Wait::Task(id))?;
+        }
+    }
+}
+
 /// A channel for communication between threads.
 ///
 /// Channels are multiple-producer, multiple-consumer (MPMC) queues.
@@ -317,6 +720,12 @@ pub fn value_channel() -> (ValueSender, ValueReceiver) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::vm::Heap;
+
+    /// The exported form of an integer result.
+    fn int(n: i64) -> ExportedValue {
+        Heap::new().export(Value::I64(n)).unwrap()
+    }
 
     #[test]
     fn test_thread_spawn_and_join() {
@@ -325,28 +734,79 @@ mod tests {
         let id = spawner.spawn(|| {
             // Simulate some work
             std::thread::sleep(std::time::Duration::from_millis(10));
-            Value::I64(42)
+            Ok(int(42))
         });
 
         let result = spawner.join(id).unwrap();
-        assert_eq!(result, Value::I64(42));
+        assert_eq!(result, int(42));
     }
 
     #[test]
     fn test_multiple_threads() {
         let mut spawner = ThreadSpawner::new();
 
-        let id1 = spawner.spawn(|| Value::I64(1));
-        let id2 = spawner.spawn(|| Value::I64(2));
-        let id3 = spawner.spawn(|| Value::I64(3));
+        let id1 = spawner.spawn(|| Ok(int(1)));
+        let id2 = spawner.spawn(|| Ok(int(2)));
+        let id3 = spawner.spawn(|| Ok(int(3)));
 
         let r1 = spawner.join(id1).unwrap();
         let r2 = spawner.join(id2).unwrap();
         let r3 = spawner.join(id3).unwrap();
 
-        assert_eq!(r1, Value::I64(1));
-        assert_eq!(r2, Value::I64(2));
-        assert_eq!(r3, Value::I64(3));
+        assert_eq!(r1, int(1));
+        assert_eq!(r2, int(2));
+        assert_eq!(r3, int(3));
+    }
+
+    #[test]
+    fn test_pool_runs_more_tasks_than_workers() {
+        let mut spawner = ThreadSpawner::with_max_threads(4);
+
+        let ids: Vec<usize> = (0..1000)
+            .map(|i| {
+                spawner.spawn(move || {
+                    std::thread::sleep(std::time::Duration::from_micros(10));
+                    Ok(int(i * 2))
+                })
+            })
+            .collect();
+
+        for (i, id) in ids.into_iter().enumerate() {
+            assert_eq!(spawner.join(id).unwrap(), int(i as i64 * 2));
+        }
+        assert!(spawner.pool.as_ref().unwrap().state.lock().unwrap().workers <= 4);
+    }
+
+    #[test]
+    fn test_deterministic_join_runs_thread() {
+        let mut spawner = ThreadSpawner::deterministic();
+
+        let id1 = spawner.spawn(|| Ok(int(1)));
+        let id2 = spawner.spawn(|| Ok(int(2)));
+
+        assert_eq!(spawner.join(id2).unwrap(), int(2));
+        assert_eq!(spawner.join(id1).unwrap(), int(1));
+        assert!(spawner.join(id1).is_err());
+    }
+
+    #[test]
+    fn test_join_returns_thread_error() {
+        let mut spawner = ThreadSpawner::new();
+
+        let id = spawner.spawn(|| Err("runtime error: boom".to_string()));
+        let panicked = spawner.spawn(|| panic!("boom"));
+
+        assert_eq!(spawner.join(id), Err("runtime error: boom".to_string()));
+        assert_eq!(spawner.join(panicked), Err(PANICKED_ERROR.to_string()));
+    }
+
+    #[test]
+    fn test_deterministic_recv_on_empty_channel_deadlocks() {
+        let spawner = ThreadSpawner::deterministic();
+        let channel = ValueChannel::new();
+
+        let err = spawner.recv(0, &channel).unwrap_err();
+        assert_eq!(err, DEADLOCK_ERROR);
     }
 
     #[test]
@@ -396,10 +856,7 @@ mod tests {
         tx2.send(2).unwrap();
         tx.send(3).unwrap();
 
-        let mut values = vec![];
-        values.push(rx.recv().unwrap());
-        values.push(rx.recv().unwrap());
-        values.push(rx.recv().unwrap());
+        let values = vec![rx.recv().unwrap(), rx.recv().unwrap(), rx.recv().unwrap()];
 
         assert_eq!(values, vec![1, 2, 3]);
     }
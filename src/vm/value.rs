@@ -19,6 +19,10 @@ pub enum Value {
     Ref(GcRef),
 }
 
+// A tag plus a 64-bit payload. Deliberately not NaN-boxed: see "Why not
+// NaN-boxing" in docs/vm.md.
+const _: () = assert!(std::mem::size_of::<Value>() == 16);
+
 impl Value {
     pub fn is_null(&self) -> bool {
         matches!(self, Value::Null)
@@ -88,6 +92,28 @@ impl Value {
         }
     }
 
+    /// Runtime type tag as reported by `__typeof`: 0=int, 1=float, 2=bool, 3=nil, 4=ref.
+    pub fn type_tag(&self) -> u8 {
+        match self {
+            Value::I64(_) => 0,
+            Value::F64(_) => 1,
+            Value::Bool(_) => 2,
+            Value::Null => 3,
+            Value::Ref(_) => 4,
+        }
+    }
+
+    /// Language-level name of a runtime type tag.
+    pub fn tag_name(tag: u8) -> &'static str {
+        match tag {
+            0 => "int",
+            1 => "float",
+            2 => "bool",
+            3 => "nil",
+            _ => "ref",
+        }
+    }
+
     /// Check if two values are equal.
     /// Note: This allows cross-type comparison (e.g., I64 == F64).
     pub fn value_eq(&self, other: &Value) -> bool {
@@ -151,6 +177,19 @@ impl Value {
     const TAG_NULL: u64 = 3;
     const TAG_REF: u64 = 4;
 
+    /// Bit pattern every NaN reinterprets as, so the payload a NaN picked up
+    /// from the operation that produced it is never observable.
+    pub const CANONICAL_NAN_BITS: u64 = 0x7FF8_0000_0000_0000;
+
+    /// `f.to_bits()`, with every NaN mapped to `CANONICAL_NAN_BITS`.
+    pub fn canonical_f64_bits(f: f64) -> u64 {
+        if f.is_nan() {
+            Self::CANONICAL_NAN_BITS
+        } else {
+            f.to_bits()
+        }
+    }
+
     /// Encode this Value into two u64 words for linear memory storage.
     /// Returns (tag, payload).
     pub fn encode(&self) -> (u64, u64) {
@@ -226,7 +265,7 @@ mod tests {
 
     #[test]
     fn test_f64_as_f64() {
-        assert_eq!(Value::F64(3.14).as_f64(), Some(3.14));
+        assert_eq!(Value::F64(2.5).as_f64(), Some(2.5));
     }
 
     #[test]
@@ -261,7 +300,7 @@ mod tests {
     #[test]
     fn test_equality() {
         assert!(Value::I64(42).value_eq(&Value::I64(42)));
-        assert!(Value::F64(3.14).value_eq(&Value::F64(3.14)));
+        assert!(Value::F64(2.5).value_eq(&Value::F64(2.5)));
         assert!(Value::I64(42).value_eq(&Value::F64(42.0)));
         assert!(Value::Null.value_eq(&Value::Null));
         assert!(!Value::I64(1).value_eq(&Value::Null));
@@ -368,7 +407,7 @@ mod tests {
         let values = [
             Value::F64(0.0),
             Value::F64(-0.0),
-            Value::F64(3.14159),
+            Value::F64(1.23456),
             Value::F64(-273.15),
             Value::F64(f64::INFINITY),
             Value::F64(f64::NEG_INFINITY),
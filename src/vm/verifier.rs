@@ -204,11 +204,9 @@ impl Verifier {
                         leaders.insert(pc + 1);
                     }
                 }
-                Op::Ret | Op::Throw => {
-                    // Instruction after terminator is a leader (if any)
-                    if pc + 1 < len {
-                        leaders.insert(pc + 1);
-                    }
+                // Instruction after terminator is a leader (if any)
+                Op::Ret | Op::Throw | Op::Unreachable if pc + 1 < len => {
+                    leaders.insert(pc + 1);
                 }
                 _ => {}
             }
@@ -264,7 +262,7 @@ impl Verifier {
                         }
                     }
                 }
-                Op::Ret | Op::Throw => {
+                Op::Ret | Op::Throw | Op::Unreachable => {
                     // No successors (terminator)
                 }
                 _ => {
@@ -366,7 +364,9 @@ impl Verifier {
             | Op::F32Const(_)
             | Op::F64Const(_)
             | Op::RefNull
-            | Op::StringConst(_) => (0, 1),
+            | Op::StringConst(_)
+            | Op::ConstArray(_)
+            | Op::FuncRef(_) => (0, 1),
 
             // Stack operations
             Op::Drop => (1, 0),
@@ -390,6 +390,12 @@ impl Verifier {
             | Op::I64Mul
             | Op::I64DivS
             | Op::I64RemS
+            | Op::I64DivU
+            | Op::I64RemU
+            | Op::I64AddSat
+            | Op::I64SubSat
+            | Op::I64MulSat
+            | Op::I64AddChecked
             | Op::I64And
             | Op::I64Or
             | Op::I64Xor
@@ -428,6 +434,8 @@ impl Verifier {
             // Ref comparison
             Op::RefEq => (2, 1),     // pop 2 refs, push i32
             Op::RefIsNull => (1, 1), // pop 1 ref, push i32
+            Op::DeepEq(_) => (2, 1), // pop 2 values, push i32
+            Op::Compare => (2, 1),   // pop 2 values, push i64
 
             // Type conversions: all pop 1, push 1
             Op::I32WrapI64
@@ -450,8 +458,8 @@ impl Verifier {
             Op::BrIf(_) | Op::BrIfFalse(_) => (1, 0),
 
             // Functions
-            Op::Call(_, argc) => (*argc, 1), // pops argc args, pushes result
-            Op::Ret => (1, 0),               // pops return value
+            Op::Call(_, argc) | Op::TailCall(_, argc) => (*argc, 1), // pops argc args, pushes result
+            Op::Ret => (1, 0),                                       // pops return value
 
             // Heap operations
             Op::HeapAlloc(n) => (*n, 1), // pops n slots, pushes ref
@@ -464,13 +472,29 @@ impl Verifier {
             Op::HeapLoad2(_) => (2, 1),  // pops ref and index, pushes value (indirect via slot 0)
             Op::HeapStore2(_) => (3, 0), // pops ref, index, and value (indirect via slot 0)
             Op::HeapOffsetRef => (2, 1), // pops ref and offset, pushes offset ref
+            Op::TypeTag(_) => (1, 1),    // tags the ref and leaves it
             // System / Builtins
             Op::Hostcall(_, argc) => (*argc, 1), // pops argc args, pushes result
             Op::GcHint(_) => (0, 0),
-            Op::TypeOf => (1, 1),   // pops value, pushes type tag
+            Op::DebugBreak => (0, 0),
+            Op::TypeOf => (1, 1),        // pops value, pushes type tag
+            Op::TypeName => (1, 1),      // pops value, pushes type name string
+            Op::AssertType(_) => (1, 1), // checks value in place
+            Op::FreeTemp => (1, 0),
+            Op::ArraySlice => (3, 1),
+            Op::MapNew => (0, 1),
+            Op::MapGet | Op::MapHas | Op::MapDelete => (2, 1),
+            Op::MapSet => (3, 0),
+            Op::StringSplit | Op::StringJoin | Op::StringIndexOf => (2, 1),
+            Op::StringSubstring | Op::StringReplace => (3, 1),
+            Op::StringBuilderAppend => (2, 0),
+            Op::StringBuilderFinish => (1, 1),
+            Op::StringToBytes | Op::BytesToString => (1, 1),
+            Op::MapLen | Op::MapKeys | Op::MapValues | Op::MapEntries => (1, 1),
             Op::HeapSize => (1, 1), // pops ref, pushes slot count
             // Exception handling
             Op::Throw => (1, 0),
+            Op::Unreachable => (0, 0),
             Op::TryBegin(_) => (0, 0),
             Op::TryEnd => (0, 0),
 
@@ -480,11 +504,17 @@ impl Verifier {
             Op::Args => (0, 1), // pushes args array
 
             // Thread operations
-            Op::ThreadSpawn(_) => (0, 1), // pushes handle
-            Op::ChannelCreate => (0, 1),  // pushes [sender, receiver]
-            Op::ChannelSend => (2, 0),    // pops channel and value
-            Op::ChannelRecv => (1, 1),    // pops channel, pushes value
-            Op::ThreadJoin => (1, 1),     // pops handle, pushes result
+            Op::ThreadSpawn(_, argc) => (*argc, 1), // pops atomics, pushes handle
+            Op::ChannelCreate => (0, 1),            // pushes [sender, receiver]
+            Op::ChannelSend => (2, 0),              // pops channel and value
+            Op::ChannelRecv => (1, 1),              // pops channel, pushes value
+            Op::ThreadJoin => (1, 1),               // pops handle, pushes result
+            Op::Spawn(_) => (0, 1),                 // pushes promise
+            Op::Await => (1, 1),                    // pops promise, pushes result
+            Op::AtomicNew | Op::AtomicLoad => (1, 1),
+            Op::AtomicStore => (2, 0),
+            Op::AtomicFetchAdd => (2, 1),
+            Op::AtomicCompareExchange => (3, 1),
 
             // Indirect call
             Op::CallIndirect(argc) => (argc + 1, 1), // pops callable ref + argc args, pushes result
@@ -1,11 +1,22 @@
-use std::collections::{HashMap, HashSet};
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fs::{File, OpenOptions};
 use std::io::{self, Read, Write};
 use std::net::{TcpListener, TcpStream};
-use std::sync::Arc;
-
+use std::rc::Rc;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::config::{JitMode, RuntimeConfig};
+use crate::vm::dict;
+use crate::vm::ic::{VtableCache, VtableCacheStats};
+use crate::vm::microop::{ConversionCache, ConvertedFunction};
 use crate::vm::threads::{Channel, ThreadSpawner};
-use crate::vm::{Chunk, ElemKind, Function, GcRef, Heap, Op, Value, ValueType};
+use crate::vm::{
+    AUTO_JIT_MIN_FUNCTION_OPS, AUTO_JIT_THRESHOLD_DIVISOR, Chunk, DEFAULT_JIT_CODE_BUDGET,
+    DEFAULT_MAX_STACK_SIZE, ElemKind, Function, GcRef, Heap, HeapSnapshot, ObjectKind, Op, Value,
+    ValueType,
+};
 
 #[cfg(all(target_arch = "aarch64", feature = "jit"))]
 use crate::jit::compiler::{CompiledCode, CompiledLoop};
@@ -19,9 +30,18 @@ use crate::jit::compiler_x86_64::{CompiledCode, CompiledLoop};
 use crate::jit::function_table::JitFunctionTable;
 #[cfg(all(any(target_arch = "aarch64", target_arch = "x86_64"), feature = "jit"))]
 use crate::jit::marshal::{JitCallContext, JitReturn, JitValue};
+#[cfg(all(any(target_arch = "aarch64", target_arch = "x86_64"), feature = "jit"))]
+use crate::jit::stackmap::JitFrameRecord;
+
+/// Error raised by `Op::Throw`; `handle_exception` replaces it with the
+/// message for the thrown value kept in `VM::thrown`.
+const THROWN_VALUE: &str = "runtime error: <thrown value>";
+
+/// Error raised by `Op::Unreachable`.
+pub(crate) const UNREACHABLE_CODE: &str = "runtime error: reached unreachable code";
 
 /// A call frame for the VM.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 struct Frame {
     /// Index into the function table (usize::MAX for main)
     func_index: usize,
@@ -38,7 +58,7 @@ struct Frame {
 }
 
 /// Exception handler frame.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 struct TryFrame {
     /// Stack depth when try block started
     stack_depth: usize,
@@ -50,6 +70,36 @@ struct TryFrame {
     func_index: usize,
 }
 
+/// A saved copy of the VM's execution state.
+///
+/// Captures the operand stack, call frames, exception handlers, and heap,
+/// so execution can be rewound with [`VM::restore`]. JIT state, I/O
+/// descriptors and threads are not part of the snapshot.
+#[derive(Debug, Clone)]
+pub struct VmSnapshot {
+    stack: Vec<Value>,
+    frames: Vec<Frame>,
+    try_frames: Vec<TryFrame>,
+    heap: HeapSnapshot,
+    string_cache: Vec<Option<GcRef>>,
+    globals: Vec<Value>,
+    descriptor_names: HashMap<String, GcRef>,
+    fd_handles: HashMap<i64, GcRef>,
+    atomics: Vec<Option<Arc<AtomicI64>>>,
+    free_atomics: Vec<usize>,
+}
+
+impl VmSnapshot {
+    /// Approximate memory footprint of this snapshot in bytes.
+    pub fn size_bytes(&self) -> usize {
+        self.heap.size_bytes()
+            + self.stack.len() * std::mem::size_of::<Value>()
+            + self.frames.len() * std::mem::size_of::<Frame>()
+            + self.try_frames.len() * std::mem::size_of::<TryFrame>()
+            + self.globals.len() * std::mem::size_of::<Value>()
+    }
+}
+
 /// GC statistics.
 #[derive(Debug, Clone, Default)]
 pub struct VmGcStats {
@@ -58,6 +108,31 @@ pub struct VmGcStats {
     pub max_pause_us: u64,
 }
 
+/// JIT compilation statistics.
+#[derive(Debug, Clone, Default)]
+pub struct VmJitStats {
+    /// Functions compiled to native code
+    pub functions: usize,
+    /// Loops compiled to native code
+    pub loops: usize,
+    /// Executable memory held by compiled code, in bytes
+    pub code_bytes: usize,
+    /// Limit on `code_bytes`; compilation stops once it would be exceeded
+    pub code_budget: usize,
+    /// Compiled functions/loops freed to make room for newer ones
+    pub evictions: usize,
+    /// Whether code has been refused because nothing could be evicted to fit it
+    pub budget_exhausted: bool,
+}
+
+/// A unit of JIT-compiled code: a function, or a loop keyed by
+/// (function index, backward jump PC).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum JitUnit {
+    Function(usize),
+    Loop(usize, usize),
+}
+
 /// Opcode execution profile data.
 #[derive(Debug, Clone, Default)]
 pub struct OpcodeProfile {
@@ -71,10 +146,11 @@ impl OpcodeProfile {
         self.counts.values().sum()
     }
 
-    /// Get sorted entries by count (descending).
+    /// Get sorted entries by count (descending). Opcodes with equal counts
+    /// are ordered by name, so the output is the same on every run.
     pub fn sorted_by_count(&self) -> Vec<(&'static str, u64)> {
         let mut entries: Vec<_> = self.counts.iter().map(|(&k, &v)| (k, v)).collect();
-        entries.sort_by(|a, b| b.1.cmp(&a.1));
+        entries.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
         entries
     }
 }
@@ -91,14 +167,39 @@ pub struct VM {
     jit_enabled: bool,
     /// JIT threshold
     jit_threshold: u32,
+    /// Whether JIT was requested as `--jit=auto`: decided per chunk on `run`
+    jit_auto: bool,
+    /// Set per run by `--jit=auto` when the chunk has nothing worth compiling
+    jit_idle: bool,
+    /// Threshold in effect for the current run (see `set_jit_auto`)
+    jit_run_threshold: u32,
     /// Whether to trace JIT events
     trace_jit: bool,
     /// GC statistics
     gc_stats: VmGcStats,
+    /// JIT statistics, including the code-size budget
+    jit_stats: VmJitStats,
+    /// Logical time each compiled function/loop was last entered from the
+    /// VM, used to pick what to evict when the code budget is full
+    jit_last_used: HashMap<JitUnit, u64>,
+    /// Clock behind `jit_last_used`, ticking on every entry into native code
+    jit_clock: u64,
+    /// Number of native code entries currently running; compiled code can
+    /// only be freed while it is zero
+    jit_native_depth: usize,
     /// Thread spawner for managing spawned threads
     thread_spawner: ThreadSpawner,
-    /// Channels for inter-thread communication (id -> channel)
-    channels: Vec<Arc<Channel<Value>>>,
+    /// Channels for inter-thread communication (id -> channel), shared with
+    /// spawned threads
+    channels: Arc<Mutex<Vec<Arc<Channel<Value>>>>>,
+    /// Cells of the live `Atomic` objects, indexed by the object's only slot.
+    /// A spawned thread gets clones of the cells passed to it, so both VMs
+    /// update the same integer.
+    atomics: Vec<Option<Arc<AtomicI64>>>,
+    /// Unused indices in `atomics`, reused by the next `AtomicNew`
+    free_atomics: Vec<usize>,
+    /// Cells whose `Atomic` objects were collected, released after the GC cycle
+    finalized_atomics: Rc<RefCell<Vec<usize>>>,
     /// JIT compiled functions (only on AArch64 with jit feature)
     #[cfg(all(target_arch = "aarch64", feature = "jit"))]
     jit_functions: HashMap<usize, CompiledCode>,
@@ -110,6 +211,17 @@ pub struct VM {
     /// Function table for JIT direct call dispatch
     #[cfg(all(any(target_arch = "aarch64", target_arch = "x86_64"), feature = "jit"))]
     jit_function_table: JitFunctionTable,
+    /// Head of the chain of active JIT frames (null when no native code is running).
+    /// Scanned by GC so references held only in JIT frames stay alive.
+    #[cfg(all(any(target_arch = "aarch64", target_arch = "x86_64"), feature = "jit"))]
+    jit_frames: *const JitFrameRecord,
+    /// Error raised by a call made from JIT code, re-raised once the
+    /// native code has bailed out (see `JitCallContext::pending_error`).
+    #[cfg(all(any(target_arch = "aarch64", target_arch = "x86_64"), feature = "jit"))]
+    jit_error: Option<String>,
+    /// Value of the `throw` in flight. It is only turned into the error
+    /// message once a handler binds it or it escapes (see `handle_exception`).
+    thrown: Option<Value>,
     /// Output stream for print statements (stdout)
     output: Box<dyn Write>,
     /// Output stream for stderr
@@ -122,20 +234,41 @@ pub struct VM {
     pending_sockets: HashSet<i64>,
     /// Listener descriptor table for TCP servers (fd >= 3)
     listener_descriptors: HashMap<i64, TcpListener>,
-    /// Next available file descriptor
+    /// Next never-used file descriptor
     next_fd: i64,
+    /// Closed fd numbers, reused lowest first by the next open
+    free_fds: BTreeSet<i64>,
+    /// Handle objects whose collection closes the fd they wrap (fd -> handle)
+    fd_handles: HashMap<i64, GcRef>,
+    /// Fds whose handle objects were collected, closed after the GC cycle
+    finalized_fds: Rc<RefCell<Vec<i64>>>,
     /// Command-line arguments passed to the script
     cli_args: Vec<String>,
     /// Whether opcode profiling is enabled
     profile_opcodes: bool,
     /// Opcode execution counts for profiling
     opcode_profile: OpcodeProfile,
+    /// Whether to trace call entry/return to stderr
+    trace_calls: bool,
+    /// Whether uncaught errors carry a backtrace of the call stack
+    backtrace: bool,
+    /// Whether uncaught errors carry a heap summary and the innermost frames
+    print_heap_on_error: bool,
+    /// Operand stack length beyond which pushes fail (see `check_stack_limit`)
+    max_stack_size: usize,
+    /// Executed (func_index, pc) pairs for coverage (None = not recording).
+    /// Main code uses `usize::MAX` as its func_index.
+    coverage: Option<HashSet<(usize, usize)>>,
     /// String constant cache: maps string index to heap reference
     /// Once a string constant is allocated, it's cached here for reuse.
     string_cache: Vec<Option<GcRef>>,
     /// Loop iteration counters for hot loop detection.
     /// Key: (function_index, backward_jump_pc), Value: iteration count
     loop_counts: HashMap<(usize, usize), u32>,
+    /// Per-call-site inline caches for `VtableLookup`
+    vtable_cache: VtableCache,
+    /// MicroOp conversions of the last chunk run, reused if it runs again
+    microop_cache: ConversionCache,
     /// JIT compiled loops (only on AArch64 with jit feature)
     #[cfg(all(target_arch = "aarch64", feature = "jit"))]
     jit_loops: HashMap<(usize, usize), CompiledLoop>,
@@ -147,6 +280,10 @@ pub struct VM {
     /// Global values table.
     /// Layout: globals[0..T] = type descriptor refs, globals[T..T+I] = interface descriptor refs.
     globals: Vec<Value>,
+    /// Heap strings of the names in allocated descriptors, shared between descriptors
+    descriptor_names: HashMap<String, GcRef>,
+    /// Allocate every descriptor up front instead of on first use
+    eager_descriptors: bool,
 }
 
 impl VM {
@@ -173,6 +310,33 @@ impl VM {
         )
     }
 
+    /// Create a VM with every setting of `config` applied: heap limit, GC,
+    /// JIT mode/threshold/tracing, call tracing, backtraces, heap reports,
+    /// opcode profiling, coverage and the operand stack limit.
+    pub fn with_runtime_config(
+        config: &RuntimeConfig,
+        output: Box<dyn Write>,
+        stderr: Box<dyn Write>,
+    ) -> Self {
+        let mut vm = Self::new_with_config(config.heap_limit, config.gc_enabled, output, stderr);
+        vm.set_jit_config(
+            config.jit_mode.is_enabled(),
+            config.jit_threshold,
+            config.trace_jit,
+        );
+        vm.set_jit_auto(config.jit_mode == JitMode::Auto);
+        vm.set_jit_code_budget(config.jit_code_budget);
+        vm.set_trace_calls(config.trace_calls);
+        vm.set_backtrace(config.backtrace);
+        vm.set_print_heap_on_error(config.print_heap_on_error);
+        vm.set_profile_opcodes(config.profile_opcodes);
+        vm.set_coverage(config.coverage);
+        vm.set_max_stack_size(config.max_stack_size);
+        vm.set_deterministic_threads(config.deterministic_threads);
+        vm.set_max_threads(config.max_threads);
+        vm
+    }
+
     /// Create a new VM with full configuration.
     ///
     /// # Arguments
@@ -194,10 +358,23 @@ impl VM {
             call_counts: Vec::new(),
             jit_enabled: true,
             jit_threshold: 1000,
+            jit_auto: false,
+            jit_idle: false,
+            jit_run_threshold: 1000,
             trace_jit: false,
             gc_stats: VmGcStats::default(),
+            jit_stats: VmJitStats {
+                code_budget: DEFAULT_JIT_CODE_BUDGET,
+                ..VmJitStats::default()
+            },
+            jit_last_used: HashMap::new(),
+            jit_clock: 0,
+            jit_native_depth: 0,
             thread_spawner: ThreadSpawner::new(),
-            channels: Vec::new(),
+            channels: Arc::new(Mutex::new(Vec::new())),
+            atomics: Vec::new(),
+            free_atomics: Vec::new(),
+            finalized_atomics: Rc::new(RefCell::new(Vec::new())),
             #[cfg(all(target_arch = "aarch64", feature = "jit"))]
             jit_functions: HashMap::new(),
             #[cfg(all(target_arch = "x86_64", feature = "jit"))]
@@ -205,6 +382,11 @@ impl VM {
             jit_compile_count: 0,
             #[cfg(all(any(target_arch = "aarch64", target_arch = "x86_64"), feature = "jit"))]
             jit_function_table: JitFunctionTable::new(0),
+            #[cfg(all(any(target_arch = "aarch64", target_arch = "x86_64"), feature = "jit"))]
+            jit_frames: std::ptr::null(),
+            #[cfg(all(any(target_arch = "aarch64", target_arch = "x86_64"), feature = "jit"))]
+            jit_error: None,
+            thrown: None,
             output,
             stderr,
             file_descriptors: HashMap::new(),
@@ -212,156 +394,210 @@ impl VM {
             pending_sockets: HashSet::new(),
             listener_descriptors: HashMap::new(),
             next_fd: 3, // fd 0, 1, 2 are reserved for stdin, stdout, stderr
+            free_fds: BTreeSet::new(),
+            fd_handles: HashMap::new(),
+            finalized_fds: Rc::new(RefCell::new(Vec::new())),
             cli_args: Vec::new(),
             profile_opcodes: false,
             opcode_profile: OpcodeProfile::default(),
+            trace_calls: false,
+            backtrace: false,
+            print_heap_on_error: false,
+            max_stack_size: DEFAULT_MAX_STACK_SIZE,
+            coverage: None,
             string_cache: Vec::new(),
             loop_counts: HashMap::new(),
+            vtable_cache: VtableCache::new(),
+            microop_cache: ConversionCache::default(),
             #[cfg(all(target_arch = "aarch64", feature = "jit"))]
             jit_loops: HashMap::new(),
             #[cfg(all(target_arch = "x86_64", feature = "jit"))]
             jit_loops: HashMap::new(),
             use_microop: true,
             globals: Vec::new(),
+            descriptor_names: HashMap::new(),
+            eager_descriptors: false,
         }
     }
-
-    /// Initialize the globals table with type descriptor and interface descriptor heap objects.
+    /// Initialize the globals table for a chunk.
     /// Layout: globals[0..T] = type descriptor refs, globals[T..T+I] = interface descriptor refs.
+    ///
+    /// Descriptors are allocated on first use by `global` (nil marks one not
+    /// allocated yet), unless eager descriptors are enabled.
     fn init_globals(&mut self, chunk: &Chunk) -> Result<(), String> {
         let td_count = chunk.type_descriptors.len();
         let iface_count = chunk.interface_descriptors.len();
         self.globals = vec![Value::Null; td_count + iface_count];
+        self.descriptor_names.clear();
+        self.vtable_cache.clear();
 
-        // --- Type descriptors (globals[0..T]) ---
+        if self.eager_descriptors {
+            for idx in 0..self.globals.len() {
+                self.global(idx, chunk)?;
+            }
+        }
+        Ok(())
+    }
 
-        // Build tag_name -> index map for resolving field type references
-        let tag_to_idx: std::collections::HashMap<&str, usize> = chunk
-            .type_descriptors
-            .iter()
-            .enumerate()
-            .map(|(i, td)| (td.tag_name.as_str(), i))
-            .collect();
+    /// Read `globals[idx]`, allocating its descriptor if this is its first use.
+    fn global(&mut self, idx: usize, chunk: &Chunk) -> Result<Value, String> {
+        let val = *self
+            .globals
+            .get(idx)
+            .ok_or_else(|| format!("invalid global index: {}", idx))?;
+        if !val.is_null() {
+            return Ok(val);
+        }
+        let td_count = chunk.type_descriptors.len();
+        let r = if idx < td_count {
+            self.alloc_type_descriptor(idx, chunk)?
+        } else {
+            self.alloc_interface_descriptor(idx - td_count, chunk)?
+        };
+        Ok(Value::Ref(r))
+    }
 
-        // Pass 1: Allocate all type descriptor heap objects with placeholder nils for type refs
-        for (i, td) in chunk.type_descriptors.iter().enumerate() {
-            let n = td.field_names.len();
-            let m = td.aux_type_tags.len();
-            let v = td.vtables.len();
-            let type_info_slots = 3 + n + n + 1 + m + 1 + 2 * v;
-            let mut slots = Vec::with_capacity(type_info_slots);
-
-            // slot 0: tag_id (string pool index as i64)
-            let tag_idx = chunk
-                .strings
-                .iter()
-                .position(|s| s == &td.tag_name)
-                .unwrap_or(0);
-            slots.push(Value::I64(tag_idx as i64));
+    /// Heap string for a type, field or interface name. Names repeat across
+    /// descriptors (`id`, `name`, ...), so each distinct name is allocated once.
+    fn descriptor_name(&mut self, name: &str) -> Result<GcRef, String> {
+        if let Some(&r) = self.descriptor_names.get(name) {
+            return Ok(r);
+        }
+        let r = self.heap.alloc_string(name.to_string())?;
+        self.descriptor_names.insert(name.to_string(), r);
+        Ok(r)
+    }
 
-            // slot 1: type_name (allocated string)
-            let name_ref = self.heap.alloc_string(td.tag_name.clone())?;
-            slots.push(Value::Ref(name_ref));
+    /// Allocate type descriptor `i` into `globals[i]`, along with the
+    /// descriptors it refers to.
+    fn alloc_type_descriptor(&mut self, i: usize, chunk: &Chunk) -> Result<GcRef, String> {
+        let td = &chunk.type_descriptors[i];
+        let n = td.field_names.len();
+        let m = td.aux_type_tags.len();
+        let v = td.vtables.len();
+        let type_info_slots = 3 + n + n + 1 + m + 1 + 2 * v;
+        let mut slots = Vec::with_capacity(type_info_slots);
+
+        // slot 0: tag_id (string pool index as i64)
+        let tag_idx = chunk
+            .strings
+            .iter()
+            .position(|s| s == &td.tag_name)
+            .unwrap_or(0);
+        slots.push(Value::I64(tag_idx as i64));
 
-            // slot 2: field_count
-            slots.push(Value::I64(n as i64));
+        // slot 1: type_name (allocated string)
+        let name_ref = self.descriptor_name(&td.tag_name)?;
+        slots.push(Value::Ref(name_ref));
 
-            // slot 3..3+n: field_names (allocated strings)
-            for field_name in &td.field_names {
-                let field_ref = self.heap.alloc_string(field_name.clone())?;
-                slots.push(Value::Ref(field_ref));
-            }
+        // slot 2: field_count
+        slots.push(Value::I64(n as i64));
 
-            // slot 3+n..3+2n: placeholder nils for field type descriptor refs
-            for _ in 0..n {
-                slots.push(Value::Null);
-            }
+        // slot 3..3+n: field_names (allocated strings)
+        for field_name in &td.field_names {
+            let field_ref = self.descriptor_name(field_name)?;
+            slots.push(Value::Ref(field_ref));
+        }
 
-            // slot 3+2n: aux_count
-            slots.push(Value::I64(m as i64));
+        // slot 3+n..3+2n: placeholder nils for field type descriptor refs
+        for _ in 0..n {
+            slots.push(Value::Null);
+        }
 
-            // slot 3+2n+1..3+2n+1+m: placeholder nils for aux type descriptor refs
-            for _ in 0..m {
-                slots.push(Value::Null);
-            }
+        // slot 3+2n: aux_count
+        slots.push(Value::I64(m as i64));
 
-            // slot BASE: vtable_count
-            slots.push(Value::I64(v as i64));
+        // slot 3+2n+1..3+2n+1+m: placeholder nils for aux type descriptor refs
+        for _ in 0..m {
+            slots.push(Value::Null);
+        }
 
-            // slot BASE+1..: placeholder nils for (iface_desc_ref, vtable_ref) pairs
-            for _ in 0..v {
-                slots.push(Value::Null); // iface_desc_ref placeholder
-                slots.push(Value::Null); // vtable_ref placeholder
-            }
+        // slot BASE: vtable_count
+        slots.push(Value::I64(v as i64));
 
-            let gc_ref = self.heap.alloc_slots(slots)?;
-            self.globals[i] = Value::Ref(gc_ref);
+        // slot BASE+1..: placeholder nils for (iface_desc_ref, vtable_ref) pairs
+        for _ in 0..v {
+            slots.push(Value::Null); // iface_desc_ref placeholder
+            slots.push(Value::Null); // vtable_ref placeholder
         }
 
-        // --- Interface descriptors (globals[T..T+I]) ---
-        for (i, id) in chunk.interface_descriptors.iter().enumerate() {
-            let name_ref = self.heap.alloc_string(id.name.clone())?;
-            let slots = vec![
-                Value::Ref(name_ref),                     // slot 0: iface_name
-                Value::I64(id.method_names.len() as i64), // slot 1: method_count
-            ];
-            let gc_ref = self.heap.alloc_slots(slots)?;
-            self.globals[td_count + i] = Value::Ref(gc_ref);
-        }
+        // Publish before filling in cross-references, so types that refer
+        // to each other (or themselves) find this descriptor already allocated
+        let td_gc_ref = self.heap.alloc_slots(slots)?;
+        self.globals[i] = Value::Ref(td_gc_ref);
 
-        // --- Pass 2: Fill in type descriptor cross-references ---
-        for (i, td) in chunk.type_descriptors.iter().enumerate() {
-            let n = td.field_names.len();
-            let m = td.aux_type_tags.len();
-            let td_gc_ref = match self.globals[i] {
-                Value::Ref(r) => r,
-                _ => unreachable!(),
-            };
-            // Fill field type desc refs
-            for (j, ft_tag) in td.field_type_tags.iter().enumerate() {
-                if let Some(&ft_idx) = tag_to_idx.get(ft_tag.as_str()) {
-                    let ft_ref = match self.globals[ft_idx] {
-                        Value::Ref(r) => r,
-                        _ => unreachable!(),
-                    };
-                    let slot_index = 3 + n + j;
-                    self.heap
-                        .write_slot(td_gc_ref, slot_index, Value::Ref(ft_ref))?;
-                }
+        let tag_index = |tag: &str| {
+            chunk
+                .type_descriptors
+                .iter()
+                .position(|td| td.tag_name == tag)
+        };
+        // Fill field type desc refs
+        for (j, ft_tag) in td.field_type_tags.iter().enumerate() {
+            if let Some(ft_idx) = tag_index(ft_tag) {
+                let ft_ref = self.global(ft_idx, chunk)?;
+                self.heap.write_slot(td_gc_ref, 3 + n + j, ft_ref)?;
             }
-            // Fill aux type desc refs
-            for (j, aux_tag) in td.aux_type_tags.iter().enumerate() {
-                if let Some(&aux_idx) = tag_to_idx.get(aux_tag.as_str()) {
-                    let aux_ref = match self.globals[aux_idx] {
-                        Value::Ref(r) => r,
-                        _ => unreachable!(),
-                    };
-                    let slot_index = 3 + 2 * n + 1 + j;
-                    self.heap
-                        .write_slot(td_gc_ref, slot_index, Value::Ref(aux_ref))?;
-                }
+        }
+        // Fill aux type desc refs
+        for (j, aux_tag) in td.aux_type_tags.iter().enumerate() {
+            if let Some(aux_idx) = tag_index(aux_tag) {
+                let aux_ref = self.global(aux_idx, chunk)?;
+                self.heap
+                    .write_slot(td_gc_ref, 3 + 2 * n + 1 + j, aux_ref)?;
             }
-            // Fill vtable entries
-            let vtable_base = 3 + 2 * n + 1 + m + 1;
-            for (j, (iface_idx, func_indices)) in td.vtables.iter().enumerate() {
-                // Set iface_desc_ref (from globals[td_count + iface_idx])
-                let iface_global_idx = td_count + iface_idx;
-                if let Some(Value::Ref(iface_ref)) = self.globals.get(iface_global_idx) {
-                    self.heap
-                        .write_slot(td_gc_ref, vtable_base + 2 * j, Value::Ref(*iface_ref))?;
-                }
-                // Allocate vtable heap object with func_indices
-                let vtable_slots: Vec<Value> = func_indices
-                    .iter()
-                    .map(|&fi| Value::I64(fi as i64))
-                    .collect();
-                let vtable_ref = self.heap.alloc_slots(vtable_slots)?;
+        }
+        // Fill vtable entries
+        let td_count = chunk.type_descriptors.len();
+        let vtable_base = 3 + 2 * n + 1 + m + 1;
+        for (j, (iface_idx, func_indices)) in td.vtables.iter().enumerate() {
+            // Set iface_desc_ref (from globals[td_count + iface_idx])
+            let iface_global_idx = td_count + iface_idx;
+            if iface_global_idx < self.globals.len() {
+                let iface_ref = self.global(iface_global_idx, chunk)?;
                 self.heap
-                    .write_slot(td_gc_ref, vtable_base + 2 * j + 1, Value::Ref(vtable_ref))?;
+                    .write_slot(td_gc_ref, vtable_base + 2 * j, iface_ref)?;
             }
+            // Allocate vtable heap object with func_indices
+            let vtable_slots: Vec<Value> = func_indices
+                .iter()
+                .map(|&fi| Value::I64(fi as i64))
+                .collect();
+            let vtable_ref = self.heap.alloc_slots(vtable_slots)?;
+            self.heap
+                .write_slot(td_gc_ref, vtable_base + 2 * j + 1, Value::Ref(vtable_ref))?;
         }
 
-        Ok(())
+        Ok(td_gc_ref)
+    }
+
+    /// Allocate interface descriptor `i` into `globals[T + i]`.
+    fn alloc_interface_descriptor(&mut self, i: usize, chunk: &Chunk) -> Result<GcRef, String> {
+        let id = &chunk.interface_descriptors[i];
+        let name_ref = self.descriptor_name(&id.name)?;
+        let slots = vec![
+            Value::Ref(name_ref),                     // slot 0: iface_name
+            Value::I64(id.method_names.len() as i64), // slot 1: method_count
+        ];
+        let gc_ref = self.heap.alloc_slots(slots)?;
+        self.globals[chunk.type_descriptors.len() + i] = Value::Ref(gc_ref);
+        Ok(gc_ref)
+    }
+
+    /// Allocate `size` null-initialized slots (or zeroed elements of a typed
+    /// array) for `HeapAllocDynSimple`. The size is only known at runtime.
+    fn alloc_dyn_simple(&mut self, size: i64, ek: ElemKind) -> Result<GcRef, String> {
+        if size < 0 {
+            return Err(format!(
+                "runtime error: allocation size must not be negative, got {}",
+                size
+            ));
+        }
+        if ek.is_typed() {
+            self.heap.alloc_typed_array(size as u32, ek)
+        } else {
+            self.heap.alloc_slots(vec![Value::Null; size as usize])
+        }
     }
 
     /// Initialize string constant cache for a chunk.
@@ -399,14 +635,148 @@ impl VM {
     pub fn set_jit_config(&mut self, enabled: bool, threshold: u32, trace: bool) {
         self.jit_enabled = enabled;
         self.jit_threshold = threshold;
+        self.jit_run_threshold = threshold;
         self.trace_jit = trace;
     }
 
+    /// Run spawned threads on a deterministic schedule (see
+    /// `ThreadSpawner::deterministic`) instead of letting the OS interleave them.
+    pub fn set_deterministic_threads(&mut self, enabled: bool) {
+        self.thread_spawner = if enabled {
+            ThreadSpawner::deterministic()
+        } else {
+            ThreadSpawner::new()
+        };
+    }
+
+    /// Run spawned threads on a pool of at most `max_threads` OS threads,
+    /// queueing the rest. Ignored under deterministic scheduling.
+    pub fn set_max_threads(&mut self, max_threads: usize) {
+        if !self.thread_spawner.is_deterministic() {
+            self.thread_spawner = ThreadSpawner::with_max_threads(max_threads);
+        }
+    }
+
+    /// Let the chunk decide whether JIT stays enabled (`--jit=auto`).
+    ///
+    /// When set, each `run` keeps JIT on only if the chunk has a loop, a
+    /// recursive call cycle or a function of at least
+    /// `AUTO_JIT_MIN_FUNCTION_OPS` ops; anything else is too short-lived to
+    /// repay compilation. The run starts at the configured threshold. Once
+    /// the first function or loop reaches it, the program has shown it runs
+    /// long enough, so the threshold drops to a quarter for the rest of the
+    /// run and the remaining counts start over.
+    pub fn set_jit_auto(&mut self, auto: bool) {
+        self.jit_auto = auto;
+    }
+
     /// Enable or disable opcode profiling.
+    /// Profiled runs use the stack interpreter with JIT compilation suspended,
+    /// so every executed bytecode op is counted: MicroOps fold some ops away
+    /// and native code never touches the counters.
     pub fn set_profile_opcodes(&mut self, enabled: bool) {
         self.profile_opcodes = enabled;
     }
 
+    /// Enable or disable call tracing.
+    /// JIT compilation is suspended while tracing so that every call is observed.
+    pub fn set_trace_calls(&mut self, enabled: bool) {
+        self.trace_calls = enabled;
+    }
+
+    /// Enable or disable backtraces on uncaught errors.
+    /// JIT compilation and inlining are suspended so that every frame is kept.
+    pub fn set_backtrace(&mut self, enabled: bool) {
+        self.backtrace = enabled;
+    }
+
+    /// Enable or disable the heap summary appended to uncaught errors.
+    /// JIT compilation and inlining are suspended so that every frame is kept.
+    pub fn set_print_heap_on_error(&mut self, enabled: bool) {
+        self.print_heap_on_error = enabled;
+    }
+
+    /// Allocate all type and interface descriptors when a run starts,
+    /// instead of on their first use.
+    pub fn set_eager_descriptors(&mut self, enabled: bool) {
+        self.eager_descriptors = enabled;
+    }
+
+    /// Limit the operand stack to `size` values.
+    pub fn set_max_stack_size(&mut self, size: usize) {
+        self.max_stack_size = size;
+    }
+
+    /// Make room for the current frame's locals above its arguments, so
+    /// operands pushed before a local is first set can't land in its slot.
+    fn reserve_locals(&mut self, locals_count: usize) {
+        let top = self.frames.last().unwrap().stack_base + locals_count;
+        if self.stack.len() < top {
+            self.stack.resize(top, Value::Null);
+        }
+    }
+
+    /// Fail with a catchable error once the operand stack outgrows its limit,
+    /// so runaway pushes error out instead of exhausting memory.
+    fn check_stack_limit(&self) -> Result<(), String> {
+        if self.stack.len() > self.max_stack_size {
+            return Err("runtime error: operand stack overflow".to_string());
+        }
+        Ok(())
+    }
+
+    /// Whether every call must run in the interpreter as a real frame
+    /// (call tracing, coverage, backtraces or heap reports).
+    fn observes_every_call(&self) -> bool {
+        self.trace_calls || self.coverage.is_some() || self.backtrace || self.print_heap_on_error
+    }
+
+    /// Whether hot functions and loops must stay interpreted.
+    fn jit_suspended(&self) -> bool {
+        self.observes_every_call() || self.profile_opcodes || self.jit_idle
+    }
+
+    /// Enable or disable recording of executed ops for coverage.
+    /// JIT compilation is suspended while recording so that every op is observed.
+    pub fn set_coverage(&mut self, enabled: bool) {
+        self.coverage = if enabled { Some(HashSet::new()) } else { None };
+    }
+
+    /// Take the executed (func_index, pc) pairs recorded so far.
+    pub fn take_coverage(&mut self) -> HashSet<(usize, usize)> {
+        self.coverage
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Write a call-entry trace line, indented by the caller's frame depth.
+    fn trace_call(&mut self, func_name: &str, args: &[Value]) {
+        let args: Vec<String> = args.iter().map(|v| self.trace_value(v)).collect();
+        let indent = "  ".repeat(self.frames.len().saturating_sub(1));
+        let _ = writeln!(
+            self.stderr,
+            "{}-> {}({})",
+            indent,
+            func_name,
+            args.join(", ")
+        );
+    }
+
+    /// Write a return trace line, indented to match the corresponding call.
+    fn trace_ret(&mut self, func_name: &str, value: &Value) {
+        let value = self.trace_value(value);
+        let indent = "  ".repeat(self.frames.len().saturating_sub(2));
+        let _ = writeln!(self.stderr, "{}<- {} = {}", indent, func_name, value);
+    }
+
+    fn trace_value(&self, value: &Value) -> String {
+        match self.value_to_string(value) {
+            Ok(text) => text.escape_debug().to_string(),
+            Err(_) => value.to_string(),
+        }
+    }
+
     /// Get opcode execution profile.
     pub fn opcode_profile(&self) -> &OpcodeProfile {
         &self.opcode_profile
@@ -446,6 +816,21 @@ impl VM {
         &self.gc_stats
     }
 
+    /// Get JIT statistics.
+    pub fn jit_stats(&self) -> &VmJitStats {
+        &self.jit_stats
+    }
+
+    /// Get hit/walk counts of the `VtableLookup` inline caches.
+    pub fn vtable_cache_stats(&self) -> VtableCacheStats {
+        self.vtable_cache.stats()
+    }
+
+    /// Set the total size of executable memory JIT compilation may use.
+    pub fn set_jit_code_budget(&mut self, bytes: usize) {
+        self.jit_stats.code_budget = bytes;
+    }
+
     /// Get immutable reference to the heap.
     pub fn heap(&self) -> &Heap {
         &self.heap
@@ -456,14 +841,160 @@ impl VM {
         &mut self.heap
     }
 
+    /// Capture the current execution state.
+    pub fn snapshot(&self) -> VmSnapshot {
+        VmSnapshot {
+            stack: self.stack.clone(),
+            frames: self.frames.clone(),
+            try_frames: self.try_frames.clone(),
+            heap: self.heap.snapshot(),
+            string_cache: self.string_cache.clone(),
+            globals: self.globals.clone(),
+            descriptor_names: self.descriptor_names.clone(),
+            fd_handles: self.fd_handles.clone(),
+            atomics: self.atomics.clone(),
+            free_atomics: self.free_atomics.clone(),
+        }
+    }
+
+    /// Rewind execution state to a previously captured snapshot.
+    ///
+    /// The string constant cache and globals are restored too, since cached
+    /// references and descriptors may point at objects allocated after the
+    /// snapshot was taken. Descriptors stay as they are, so a handle only
+    /// closes its fd on collection if the fd is still open and the handle
+    /// kept its finalizer (see [`Heap::restore`]).
+    pub fn restore(&mut self, snap: &VmSnapshot) {
+        self.stack.clone_from(&snap.stack);
+        self.frames.clone_from(&snap.frames);
+        self.try_frames.clone_from(&snap.try_frames);
+        self.heap.restore(&snap.heap);
+        self.string_cache.clone_from(&snap.string_cache);
+        self.globals.clone_from(&snap.globals);
+        self.descriptor_names.clone_from(&snap.descriptor_names);
+        let mut fd_handles = snap.fd_handles.clone();
+        fd_handles.retain(|&fd, &mut handle| {
+            self.is_open_descriptor(fd) && self.heap.has_finalizer(handle)
+        });
+        self.fd_handles = fd_handles;
+        self.atomics.clone_from(&snap.atomics);
+        self.free_atomics.clone_from(&snap.free_atomics);
+        self.vtable_cache.clear();
+    }
+
     /// Initialize call counts for a chunk.
     fn init_call_counts(&mut self, chunk: &Chunk) {
         self.call_counts = vec![0; chunk.functions.len()];
+        self.jit_run_threshold = self.jit_threshold;
+        self.jit_idle = self.jit_auto && self.jit_enabled && !Self::has_hot_code(chunk);
+        if self.jit_idle && self.trace_jit {
+            eprintln!("[JIT] auto: no loops, recursion or large functions, staying interpreted");
+        }
+    }
+
+    /// A function or loop reached the run's threshold. Under `--jit=auto` the
+    /// first one lowers the threshold for the rest of the run.
+    fn note_hot_unit(&mut self) {
+        if !self.jit_auto || self.jit_run_threshold != self.jit_threshold {
+            return;
+        }
+        let scaled = (self.jit_threshold / AUTO_JIT_THRESHOLD_DIVISOR).max(1);
+        if scaled == self.jit_threshold {
+            return;
+        }
+        self.jit_run_threshold = scaled;
+        self.call_counts.iter_mut().for_each(|count| *count = 0);
+        self.loop_counts.clear();
+        if self.trace_jit {
+            eprintln!(
+                "[JIT] auto: hot code found, threshold lowered to {}",
+                scaled
+            );
+        }
+    }
+
+    /// Whether `chunk` has anything `--jit=auto` considers worth compiling.
+    ///
+    /// Only code reachable from `main` through direct calls and spawns is
+    /// considered, so unused prelude functions don't count. It is hot if it
+    /// has a backward jump, a call cycle (direct or mutual recursion) or a
+    /// large function; an indirect call makes the callees unknown, so it
+    /// counts as hot too.
+    fn has_hot_code(chunk: &Chunk) -> bool {
+        #[derive(Clone, Copy, PartialEq)]
+        enum State {
+            New,
+            OnPath,
+            Done,
+        }
+        let callees = |func: &Function| -> Option<Vec<usize>> {
+            let mut callees = Vec::new();
+            for (pc, op) in func.code.iter().enumerate() {
+                match op {
+                    Op::Jmp(target) | Op::BrIf(target) | Op::BrIfFalse(target) if *target <= pc => {
+                        return None;
+                    }
+                    Op::CallIndirect(_) | Op::CallDynamic(_) => return None,
+                    Op::Call(callee, _)
+                    | Op::TailCall(callee, _)
+                    | Op::ThreadSpawn(callee, _)
+                    | Op::Spawn(callee)
+                        if *callee < chunk.functions.len() =>
+                    {
+                        callees.push(*callee)
+                    }
+                    _ => {}
+                }
+            }
+            Some(callees)
+        };
+
+        // Depth-first walk of the call graph; reaching a function that is
+        // still on the current path closes a cycle
+        let mut state = vec![State::New; chunk.functions.len()];
+        let Some(roots) = callees(&chunk.main) else {
+            return true;
+        };
+        let mut path: Vec<(usize, Vec<usize>)> = Vec::new();
+        for root in roots {
+            if state[root] != State::New {
+                continue;
+            }
+            let mut next = Some(root);
+            loop {
+                if let Some(index) = next.take() {
+                    let func = &chunk.functions[index];
+                    if func.code.len() >= AUTO_JIT_MIN_FUNCTION_OPS {
+                        return true;
+                    }
+                    let Some(calls) = callees(func) else {
+                        return true;
+                    };
+                    state[index] = State::OnPath;
+                    path.push((index, calls));
+                }
+                let Some((index, calls)) = path.last_mut() else {
+                    break;
+                };
+                match calls.pop() {
+                    Some(callee) => match state[callee] {
+                        State::OnPath => return true,
+                        State::New => next = Some(callee),
+                        State::Done => {}
+                    },
+                    None => {
+                        state[*index] = State::Done;
+                        path.pop();
+                    }
+                }
+            }
+        }
+        false
     }
 
     /// Increment call count and check if function should be JIT compiled.
     fn should_jit_compile(&mut self, func_index: usize, func_name: &str) -> bool {
-        if !self.jit_enabled {
+        if !self.jit_enabled || self.jit_suspended() {
             return false;
         }
 
@@ -473,13 +1004,14 @@ impl VM {
 
         self.call_counts[func_index] += 1;
 
-        if self.call_counts[func_index] == self.jit_threshold {
+        if self.call_counts[func_index] == self.jit_run_threshold {
             if self.trace_jit {
                 eprintln!(
                     "[JIT] Hot function detected: {} (calls: {})",
-                    func_name, self.jit_threshold
+                    func_name, self.jit_run_threshold
                 );
             }
+            self.note_hot_unit();
             return true;
         }
 
@@ -488,16 +1020,91 @@ impl VM {
 
     /// Check if a loop should be JIT compiled based on iteration count.
     /// Returns true when the loop reaches the hot threshold and JIT is enabled.
-    fn should_jit_compile_loop(&self, func_index: usize, back_jump_pc: usize) -> bool {
-        if !self.jit_enabled {
+    fn should_jit_compile_loop(&mut self, func_index: usize, back_jump_pc: usize) -> bool {
+        if !self.jit_enabled || self.jit_suspended() {
             return false;
         }
         let key = (func_index, back_jump_pc);
-        if let Some(&count) = self.loop_counts.get(&key) {
-            count == self.jit_threshold
-        } else {
-            false
+        let hot = self.loop_counts.get(&key) == Some(&self.jit_run_threshold);
+        if hot {
+            self.note_hot_unit();
+        }
+        hot
+    }
+
+    /// Charge freshly compiled code against the JIT code budget, evicting
+    /// the least recently entered functions and loops to make room. Code that
+    /// still doesn't fit is dropped and the hot function or loop stays
+    /// interpreted.
+    #[cfg(all(any(target_arch = "aarch64", target_arch = "x86_64"), feature = "jit"))]
+    fn admit_jit_code(&mut self, what: &str, size: usize) -> bool {
+        while self.jit_stats.code_bytes + size > self.jit_stats.code_budget {
+            if !self.evict_lru_jit_unit() {
+                self.jit_stats.budget_exhausted = true;
+                if self.trace_jit {
+                    eprintln!(
+                        "[JIT] Code budget exhausted ({} of {} bytes used), not compiling {} ({} bytes)",
+                        self.jit_stats.code_bytes, self.jit_stats.code_budget, what, size
+                    );
+                }
+                return false;
+            }
+        }
+        self.jit_stats.code_bytes += size;
+        true
+    }
+
+    /// Free the least recently entered compiled function or loop. Evicted
+    /// code starts counting towards the hot threshold again, so it is
+    /// recompiled if it stays hot. Nothing is evicted while native code is
+    /// running, since it may be on the call stack.
+    #[cfg(all(any(target_arch = "aarch64", target_arch = "x86_64"), feature = "jit"))]
+    fn evict_lru_jit_unit(&mut self) -> bool {
+        if self.jit_native_depth > 0 {
+            return false;
+        }
+        let Some((&unit, _)) = self.jit_last_used.iter().min_by_key(|&(_, &used)| used) else {
+            return false;
+        };
+        self.jit_last_used.remove(&unit);
+        let size = match unit {
+            JitUnit::Function(func_index) => {
+                self.jit_function_table.update(func_index, 0, 0);
+                if let Some(count) = self.call_counts.get_mut(func_index) {
+                    *count = 0;
+                }
+                self.jit_functions
+                    .remove(&func_index)
+                    .map(|c| c.memory.size())
+            }
+            JitUnit::Loop(func_index, back_jump_pc) => {
+                self.loop_counts.remove(&(func_index, back_jump_pc));
+                self.jit_loops
+                    .remove(&(func_index, back_jump_pc))
+                    .map(|c| c.memory.size())
+            }
+        }
+        .unwrap_or(0);
+        self.jit_stats.code_bytes -= size;
+        self.jit_stats.evictions += 1;
+        if self.trace_jit {
+            eprintln!("[JIT] Evicted {:?} ({} bytes)", unit, size);
         }
+        true
+    }
+
+    /// Mark `unit` as the most recently used compiled code.
+    #[cfg(all(any(target_arch = "aarch64", target_arch = "x86_64"), feature = "jit"))]
+    fn touch_jit_unit(&mut self, unit: JitUnit) {
+        self.jit_clock += 1;
+        self.jit_last_used.insert(unit, self.jit_clock);
+    }
+
+    /// Record that native code for `unit` is about to run from the VM.
+    #[cfg(all(any(target_arch = "aarch64", target_arch = "x86_64"), feature = "jit"))]
+    fn enter_jit_code(&mut self, unit: JitUnit) {
+        self.touch_jit_unit(unit);
+        self.jit_native_depth += 1;
     }
 
     /// Check if a loop has already been JIT compiled.
@@ -527,6 +1134,11 @@ impl VM {
         let compiler = MicroOpJitCompiler::new();
         match compiler.compile(&converted, func.locals_count, func_index) {
             Ok(compiled) => {
+                if !self
+                    .admit_jit_code(&format!("function '{}'", func.name), compiled.memory.size())
+                {
+                    return;
+                }
                 if self.trace_jit {
                     eprintln!(
                         "[JIT/MicroOp] Compiled function '{}' ({} bytes)",
@@ -543,6 +1155,9 @@ impl VM {
                     compiled.total_regs,
                 );
                 self.jit_functions.insert(func_index, compiled);
+                self.jit_last_used
+                    .insert(JitUnit::Function(func_index), self.jit_clock);
+                self.jit_stats.functions += 1;
                 self.jit_compile_count += 1;
             }
             Err(e) => {
@@ -580,6 +1195,11 @@ impl VM {
         let compiler = MicroOpJitCompiler::new();
         match compiler.compile(&converted, func.locals_count, func_index, all_functions) {
             Ok(compiled) => {
+                if !self
+                    .admit_jit_code(&format!("function '{}'", func.name), compiled.memory.size())
+                {
+                    return;
+                }
                 if self.trace_jit {
                     eprintln!(
                         "[JIT/MicroOp] Compiled function '{}' ({} bytes)",
@@ -596,6 +1216,9 @@ impl VM {
                     compiled.total_regs,
                 );
                 self.jit_functions.insert(func_index, compiled);
+                self.jit_last_used
+                    .insert(JitUnit::Function(func_index), self.jit_clock);
+                self.jit_stats.functions += 1;
                 self.jit_compile_count += 1;
             }
             Err(e) => {
@@ -644,7 +1267,7 @@ impl VM {
                 loop_end_pc,
                 loop_start_microop,
                 loop_end_microop,
-                self.jit_threshold
+                self.jit_run_threshold
             );
         }
 
@@ -660,6 +1283,10 @@ impl VM {
             all_functions,
         ) {
             Ok(compiled) => {
+                if !self.admit_jit_code(&format!("loop in '{}'", func.name), compiled.memory.size())
+                {
+                    return;
+                }
                 if self.trace_jit {
                     eprintln!(
                         "[JIT/MicroOp] Compiled loop in '{}' Op PC {}..{} ({} bytes)",
@@ -670,6 +1297,9 @@ impl VM {
                     );
                 }
                 self.jit_loops.insert(key, compiled);
+                self.jit_last_used
+                    .insert(JitUnit::Loop(func_index, loop_end_pc), self.jit_clock);
+                self.jit_stats.loops += 1;
                 self.jit_compile_count += 1;
             }
             Err(e) => {
@@ -713,7 +1343,7 @@ impl VM {
                 loop_end_pc,
                 loop_start_microop,
                 loop_end_microop,
-                self.jit_threshold
+                self.jit_run_threshold
             );
         }
 
@@ -728,6 +1358,10 @@ impl VM {
             loop_end_pc,
         ) {
             Ok(compiled) => {
+                if !self.admit_jit_code(&format!("loop in '{}'", func.name), compiled.memory.size())
+                {
+                    return;
+                }
                 if self.trace_jit {
                     eprintln!(
                         "[JIT/MicroOp] Compiled loop in '{}' Op PC {}..{} ({} bytes)",
@@ -738,6 +1372,9 @@ impl VM {
                     );
                 }
                 self.jit_loops.insert(key, compiled);
+                self.jit_last_used
+                    .insert(JitUnit::Loop(func_index, loop_end_pc), self.jit_clock);
+                self.jit_stats.loops += 1;
                 self.jit_compile_count += 1;
             }
             Err(e) => {
@@ -844,8 +1481,11 @@ impl VM {
             heap_alloc_dyn_simple_helper: jit_heap_alloc_dyn_simple_helper,
             // heap_alloc_typed_helper removed
             jit_function_table: self.jit_function_table.base_ptr(),
+            jit_frames: &mut self.jit_frames,
+            pending_error: 0,
         };
 
+        self.enter_jit_code(JitUnit::Loop(func_index, loop_end_pc));
         let _result: JitReturn = unsafe {
             entry(
                 &mut call_ctx as *mut JitCallContext as *mut u8,
@@ -853,6 +1493,7 @@ impl VM {
                 jit_frame.as_mut_ptr(), // unused
             )
         };
+        self.jit_native_depth -= 1;
 
         if self.trace_jit {
             eprintln!("[JIT] Executed loop in '{}' PC ..{}", func.name, loop_end);
@@ -872,6 +1513,8 @@ impl VM {
             }
         }
 
+        self.take_jit_error(&call_ctx)?;
+
         Ok(loop_end + 1)
     }
 
@@ -947,8 +1590,11 @@ impl VM {
             heap_alloc_dyn_simple_helper: jit_heap_alloc_dyn_simple_helper,
             // heap_alloc_typed_helper removed
             jit_function_table: self.jit_function_table.base_ptr(),
+            jit_frames: &mut self.jit_frames,
+            pending_error: 0,
         };
 
+        self.enter_jit_code(JitUnit::Loop(func_index, loop_end_pc));
         let _result: JitReturn = unsafe {
             entry(
                 &mut call_ctx as *mut JitCallContext as *mut u8,
@@ -956,6 +1602,7 @@ impl VM {
                 jit_frame.as_mut_ptr(), // unused
             )
         };
+        self.jit_native_depth -= 1;
 
         if self.trace_jit {
             eprintln!("[JIT] Executed loop in '{}' PC ..{}", func.name, loop_end);
@@ -974,6 +1621,8 @@ impl VM {
             }
         }
 
+        self.take_jit_error(&call_ctx)?;
+
         Ok(loop_end + 1)
     }
 
@@ -1033,9 +1682,12 @@ impl VM {
             heap_alloc_dyn_simple_helper: jit_heap_alloc_dyn_simple_helper,
             // heap_alloc_typed_helper removed
             jit_function_table: self.jit_function_table.base_ptr(),
+            jit_frames: &mut self.jit_frames,
+            pending_error: 0,
         };
 
         // Execute the JIT code
+        self.enter_jit_code(JitUnit::Function(func_index));
         let result: JitReturn = unsafe {
             entry(
                 &mut call_ctx as *mut JitCallContext as *mut u8,
@@ -1043,6 +1695,7 @@ impl VM {
                 frame.as_mut_ptr(), // unused
             )
         };
+        self.jit_native_depth -= 1;
 
         if self.trace_jit {
             eprintln!(
@@ -1051,6 +1704,8 @@ impl VM {
             );
         }
 
+        self.take_jit_error(&call_ctx)?;
+
         // Convert return value to VM Value (tag+payload from return registers)
         Ok(result.to_value())
     }
@@ -1111,9 +1766,12 @@ impl VM {
             heap_alloc_dyn_simple_helper: jit_heap_alloc_dyn_simple_helper,
             // heap_alloc_typed_helper removed
             jit_function_table: self.jit_function_table.base_ptr(),
+            jit_frames: &mut self.jit_frames,
+            pending_error: 0,
         };
 
         // Execute the JIT code
+        self.enter_jit_code(JitUnit::Function(func_index));
         let result: JitReturn = unsafe {
             entry(
                 &mut call_ctx as *mut JitCallContext as *mut u8,
@@ -1121,6 +1779,7 @@ impl VM {
                 frame.as_mut_ptr(), // unused
             )
         };
+        self.jit_native_depth -= 1;
 
         if self.trace_jit {
             eprintln!(
@@ -1129,22 +1788,49 @@ impl VM {
             );
         }
 
+        self.take_jit_error(&call_ctx)?;
+
         // Convert return value to VM Value (tag+payload from return registers)
         Ok(result.to_value())
     }
 
+    /// Re-raise an error that made JIT code bail out of a call.
+    #[cfg(all(any(target_arch = "aarch64", target_arch = "x86_64"), feature = "jit"))]
+    fn take_jit_error(&mut self, call_ctx: &JitCallContext) -> Result<(), String> {
+        if call_ctx.pending_error == 0 {
+            return Ok(());
+        }
+        // Compiled `Op::Unreachable` sets the flag without going through the VM
+        Err(self
+            .jit_error
+            .take()
+            .unwrap_or_else(|| UNREACHABLE_CODE.to_string()))
+    }
+
     /// Get the number of JIT compilations performed.
     pub fn jit_compile_count(&self) -> usize {
         self.jit_compile_count
     }
 
+    /// Number of functions the MicroOp interpreter has converted. Running
+    /// the same chunk again reuses the earlier conversions.
+    pub fn microop_conversion_count(&self) -> usize {
+        self.microop_cache.conversions()
+    }
+
     /// Enable or disable the MicroOp interpreter.
     pub fn set_use_microop(&mut self, enabled: bool) {
         self.use_microop = enabled;
     }
 
+    /// Whether `run` dispatches MicroOps; opcode profiling needs the stack
+    /// interpreter to see every bytecode op.
+    fn runs_microop(&self) -> bool {
+        self.use_microop && !self.profile_opcodes
+    }
+
     pub fn run(&mut self, chunk: &Chunk) -> Result<(), String> {
-        if self.use_microop {
+        if self.runs_microop() {
             return self.run_microop(chunk);
         }
 
@@ -1168,6 +1854,7 @@ impl VM {
             ret_vreg: None,
             stack_floor: 0,
         });
+        self.reserve_locals(chunk.main.locals_count);
 
         loop {
             // Check if GC should run
@@ -1188,6 +1875,9 @@ impl VM {
             }
 
             let op = func.code[frame.pc].clone();
+            if let Some(coverage) = &mut self.coverage {
+                coverage.insert((frame.func_index, frame.pc));
+            }
             frame.pc += 1;
 
             // Profile opcode execution if enabled
@@ -1195,7 +1885,9 @@ impl VM {
                 *self.opcode_profile.counts.entry(op.name()).or_insert(0) += 1;
             }
 
-            let result = self.execute_op(op, chunk);
+            let result = self
+                .execute_op(op, chunk)
+                .and_then(|flow| self.check_stack_limit().map(|_| flow));
             match result {
                 Ok(ControlFlow::Continue) => {}
                 Ok(ControlFlow::Return) => {
@@ -1204,10 +1896,10 @@ impl VM {
                     }
                 }
                 Ok(ControlFlow::Exit) => break,
-                Err(e) => {
+                Err(mut e) => {
                     // Try to handle exception
-                    if !self.handle_exception(e.clone(), chunk)? {
-                        return Err(e);
+                    if !self.handle_exception(&mut e, chunk)? {
+                        return Err(self.with_heap_report(e, chunk));
                     }
                 }
             }
@@ -1216,8 +1908,15 @@ impl VM {
         Ok(())
     }
 
-    /// Run a chunk and return the result value (used for thread execution).
+    /// Run a chunk and return the value returned by main (used for thread
+    /// execution and `--eval-print`).
     pub fn run_and_get_result(&mut self, chunk: &Chunk) -> Result<Value, String> {
+        if self.runs_microop() {
+            // Main's return value is left on the stack
+            self.run_microop(chunk)?;
+            return Ok(self.stack.pop().unwrap_or(Value::Null));
+        }
+
         // Initialize globals (type descriptors + interface descriptors)
         self.init_globals(chunk)?;
 
@@ -1229,6 +1928,7 @@ impl VM {
             ret_vreg: None,
             stack_floor: 0,
         });
+        self.reserve_locals(chunk.main.locals_count);
 
         let mut result = Value::Null;
 
@@ -1258,7 +1958,9 @@ impl VM {
                 *self.opcode_profile.counts.entry(op.name()).or_insert(0) += 1;
             }
 
-            let control = self.execute_op(op, chunk);
+            let control = self
+                .execute_op(op, chunk)
+                .and_then(|flow| self.check_stack_limit().map(|_| flow));
             match control {
                 Ok(ControlFlow::Continue) => {}
                 Ok(ControlFlow::Return) => {
@@ -1273,10 +1975,10 @@ impl VM {
                     result = self.stack.pop().unwrap_or(Value::Null);
                     break;
                 }
-                Err(e) => {
+                Err(mut e) => {
                     // Try to handle exception
-                    if !self.handle_exception(e.clone(), chunk)? {
-                        return Err(e);
+                    if !self.handle_exception(&mut e, chunk)? {
+                        return Err(self.with_heap_report(e, chunk));
                     }
                 }
             }
@@ -1285,14 +1987,46 @@ impl VM {
         Ok(result)
     }
 
+    /// Run a chunk from an empty stack with `args` as main's first locals,
+    /// and return main's result. Embedders call a function by running a
+    /// chunk whose main forwards its locals to it.
+    pub fn run_with_args(&mut self, chunk: &Chunk, args: Vec<Value>) -> Result<Value, String> {
+        self.stack.clear();
+        self.frames.clear();
+        self.try_frames.clear();
+        self.stack.extend(args);
+        self.run_and_get_result(chunk)
+    }
+
+    /// Run a chunk and write the value returned by main to the output
+    /// stream (`moca run --eval-print`).
+    pub fn run_and_print_result(&mut self, chunk: &Chunk) -> Result<(), String> {
+        let value = self.run_and_get_result(chunk)?;
+        let text = debug_string(&self.heap, &self.globals, &value)?;
+        writeln!(self.output, "{}", text).map_err(|e| format!("io error: {}", e))
+    }
+
     /// Run the VM using the MicroOp interpreter.
     ///
     /// Converts each function's Op bytecode to MicroOps lazily (on first call),
     /// caches the result, and executes using register-based MicroOps with
-    /// Raw fallback for unconverted operations.
+    /// Raw fallback for unconverted operations. The cache outlives the run,
+    /// so running the same chunk again skips conversion.
     fn run_microop(&mut self, chunk: &Chunk) -> Result<(), String> {
-        use super::microop::{CmpCond, ConvertedFunction, MicroOp};
-        use super::microop_converter;
+        let mut cache = std::mem::take(&mut self.microop_cache);
+        cache.prepare(&chunk.functions, !self.observes_every_call());
+        let result = self.run_microop_cached(chunk, &mut cache.functions);
+        self.microop_cache = cache;
+        result.map_err(|e| self.with_heap_report(e, chunk))
+    }
+
+    fn run_microop_cached(
+        &mut self,
+        chunk: &Chunk,
+        func_cache: &mut Vec<Option<ConvertedFunction>>,
+    ) -> Result<(), String> {
+        use super::microop::{CmpCond, MicroOp};
+        use super::{microop_converter, microop_inline};
 
         // Initialize (same as run())
         self.init_call_counts(chunk);
@@ -1303,9 +2037,17 @@ impl VM {
             self.jit_function_table = JitFunctionTable::new(chunk.functions.len());
         }
 
-        // Lazy conversion cache: indexed by func_index
-        let mut func_cache: Vec<Option<ConvertedFunction>> = vec![None; chunk.functions.len()];
-        let main_converted = microop_converter::convert(&chunk.main);
+        // Small leaf calls are inlined unless every call must stay observable
+        let inline_calls = !self.observes_every_call();
+        let convert = |func: &Function, func_index: usize| {
+            if inline_calls {
+                microop_inline::convert_inlined(func, func_index, &chunk.functions)
+            } else {
+                microop_converter::convert(func)
+            }
+        };
+
+        let main_converted = convert(&chunk.main, usize::MAX);
 
         // Push main frame with register file space
         let main_regs = chunk.main.locals_count + main_converted.temps_count;
@@ -1333,7 +2075,7 @@ impl VM {
                 &main_converted
             } else {
                 func_cache[func_index]
-                    .get_or_insert_with(|| microop_converter::convert(&chunk.functions[func_index]))
+                    .get_or_insert_with(|| convert(&chunk.functions[func_index], func_index))
             };
 
             // Check for end of code
@@ -1345,6 +2087,12 @@ impl VM {
             let mop = converted.micro_ops[pc].clone();
             self.frames.last_mut().unwrap().pc = pc + 1;
 
+            if let Some(coverage) = &mut self.coverage {
+                // Map back to the bytecode op that emitted this MicroOp
+                let op_pc = converted.pc_map.partition_point(|&m| m <= pc) - 1;
+                coverage.insert((func_index, op_pc));
+            }
+
             // Dispatch
             match mop {
                 MicroOp::Jmp {
@@ -1401,7 +2149,15 @@ impl VM {
                                     &chunk.functions[func_index]
                                 };
                                 let next_old_pc =
-                                    self.execute_jit_loop(func_index, old_pc, func, chunk)?;
+                                    match self.execute_jit_loop(func_index, old_pc, func, chunk) {
+                                        Ok(pc) => pc,
+                                        Err(mut e) => {
+                                            if !self.handle_exception(&mut e, chunk)? {
+                                                return Err(e);
+                                            }
+                                            continue;
+                                        }
+                                    };
                                 // Map returned Op PC back to MicroOp PC
                                 self.frames.last_mut().unwrap().pc = converted.pc_map[next_old_pc];
                                 continue;
@@ -1417,7 +2173,15 @@ impl VM {
                                     &chunk.functions[func_index]
                                 };
                                 let next_old_pc =
-                                    self.execute_jit_loop(func_index, old_pc, func, chunk)?;
+                                    match self.execute_jit_loop(func_index, old_pc, func, chunk) {
+                                        Ok(pc) => pc,
+                                        Err(mut e) => {
+                                            if !self.handle_exception(&mut e, chunk)? {
+                                                return Err(e);
+                                            }
+                                            continue;
+                                        }
+                                    };
                                 // Map returned Op PC back to MicroOp PC
                                 self.frames.last_mut().unwrap().pc = converted.pc_map[next_old_pc];
                                 continue;
@@ -1445,6 +2209,11 @@ impl VM {
                     func_id,
                     ref args,
                     ret,
+                }
+                | MicroOp::TailCall {
+                    func_id,
+                    ref args,
+                    ret,
                 } => {
                     let callee_func = &chunk.functions[func_id];
                     let caller_stack_base = self.frames.last().unwrap().stack_base;
@@ -1460,8 +2229,20 @@ impl VM {
                             for arg in args.iter() {
                                 self.stack.push(self.stack[caller_stack_base + arg.0]);
                             }
-                            let result =
-                                self.execute_jit_function(func_id, args.len(), callee_func, chunk)?;
+                            let result = match self.execute_jit_function(
+                                func_id,
+                                args.len(),
+                                callee_func,
+                                chunk,
+                            ) {
+                                Ok(result) => result,
+                                Err(mut e) => {
+                                    if !self.handle_exception(&mut e, chunk)? {
+                                        return Err(e);
+                                    }
+                                    continue;
+                                }
+                            };
                             // Store return value in caller's ret vreg
                             if let Some(ret_v) = ret {
                                 let sb = self.frames.last().unwrap().stack_base;
@@ -1479,8 +2260,20 @@ impl VM {
                             for arg in args.iter() {
                                 self.stack.push(self.stack[caller_stack_base + arg.0]);
                             }
-                            let result =
-                                self.execute_jit_function(func_id, args.len(), callee_func, chunk)?;
+                            let result = match self.execute_jit_function(
+                                func_id,
+                                args.len(),
+                                callee_func,
+                                chunk,
+                            ) {
+                                Ok(result) => result,
+                                Err(mut e) => {
+                                    if !self.handle_exception(&mut e, chunk)? {
+                                        return Err(e);
+                                    }
+                                    continue;
+                                }
+                            };
                             if let Some(ret_v) = ret {
                                 let sb = self.frames.last().unwrap().stack_base;
                                 self.stack[sb + ret_v.0] = result;
@@ -1489,14 +2282,39 @@ impl VM {
                         }
                     }
 
+                    if self.trace_calls {
+                        let arg_values: Vec<Value> = args
+                            .iter()
+                            .map(|a| self.stack[caller_stack_base + a.0])
+                            .collect();
+                        self.trace_call(&callee_func.name, &arg_values);
+                    }
+
                     // MicroOp interpreter path
                     if func_cache[func_id].is_none() {
-                        func_cache[func_id] =
-                            Some(microop_converter::convert(&chunk.functions[func_id]));
+                        func_cache[func_id] = Some(convert(&chunk.functions[func_id], func_id));
                     }
                     let callee_temps = func_cache[func_id].as_ref().unwrap().temps_count;
                     let callee_regs = callee_func.locals_count + callee_temps;
 
+                    if matches!(mop, MicroOp::TailCall { .. }) && !self.observes_every_call() {
+                        // Replace the current frame: the callee reuses its base
+                        // and returns straight into the caller's ret vreg
+                        let frame = self.frames.last_mut().unwrap();
+                        let arg_values: Vec<Value> = args
+                            .iter()
+                            .map(|a| self.stack[frame.stack_base + a.0])
+                            .collect();
+                        self.stack.truncate(frame.stack_base);
+                        self.stack.extend(arg_values);
+                        self.stack
+                            .resize(frame.stack_base + callee_regs, Value::Null);
+                        frame.func_index = func_id;
+                        frame.pc = 0;
+                        frame.stack_floor = frame.stack_base + callee_regs;
+                        continue;
+                    }
+
                     let new_stack_base = self.stack.len();
 
                     // Allocate register file for callee
@@ -1529,6 +2347,11 @@ impl VM {
                         None => Value::Null,
                     };
 
+                    if self.trace_calls && self.frames.len() > 1 {
+                        let func_index = self.frames.last().unwrap().func_index;
+                        self.trace_ret(&chunk.functions[func_index].name, &return_value);
+                    }
+
                     // Pop callee frame
                     let callee_frame = self.frames.pop().unwrap();
 
@@ -1572,6 +2395,23 @@ impl VM {
                         as usize;
 
                     let callee_func = &chunk.functions[func_index];
+                    if callee_func.arity != 1 + args.len() {
+                        return Err(format!(
+                            "runtime error: function '{}' expects {} arguments (1 closure_ref + {} params), got 1 + {} args",
+                            callee_func.name,
+                            callee_func.arity,
+                            callee_func.arity.saturating_sub(1),
+                            args.len()
+                        ));
+                    }
+
+                    if self.trace_calls {
+                        let arg_values: Vec<Value> = args
+                            .iter()
+                            .map(|a| self.stack[caller_stack_base + a.0])
+                            .collect();
+                        self.trace_call(&callee_func.name, &arg_values);
+                    }
 
                     // Convert and cache if needed
                     if func_cache.len() <= func_index {
@@ -1579,7 +2419,7 @@ impl VM {
                     }
                     if func_cache[func_index].is_none() {
                         func_cache[func_index] =
-                            Some(microop_converter::convert(&chunk.functions[func_index]));
+                            Some(convert(&chunk.functions[func_index], func_index));
                     }
                     let callee_temps = func_cache[func_index].as_ref().unwrap().temps_count;
                     let callee_regs = callee_func.locals_count + callee_temps;
@@ -1620,13 +2460,21 @@ impl VM {
 
                     let callee_func = &chunk.functions[func_index];
 
+                    if self.trace_calls {
+                        let arg_values: Vec<Value> = args
+                            .iter()
+                            .map(|a| self.stack[caller_stack_base + a.0])
+                            .collect();
+                        self.trace_call(&callee_func.name, &arg_values);
+                    }
+
                     // Convert and cache if needed
                     if func_cache.len() <= func_index {
                         func_cache.resize(func_index + 1, None);
                     }
                     if func_cache[func_index].is_none() {
                         func_cache[func_index] =
-                            Some(microop_converter::convert(&chunk.functions[func_index]));
+                            Some(convert(&chunk.functions[func_index], func_index));
                     }
                     let callee_temps = func_cache[func_index].as_ref().unwrap().temps_count;
                     let callee_regs = callee_func.locals_count + callee_temps;
@@ -1866,11 +2714,24 @@ impl VM {
                     let sb = self.frames.last().unwrap().stack_base;
                     self.stack[sb + dst.0] = Value::Ref(new_ref);
                 }
+                MicroOp::TypeTag { obj, type_index } => {
+                    // Printing a tagged struct reads its name from the descriptor
+                    self.global(type_index, chunk)?;
+                    let sb = self.frames.last().unwrap().stack_base;
+                    if let Value::Ref(r) = self.stack[sb + obj.0] {
+                        self.heap.set_type_tag(r, type_index);
+                    }
+                }
 
                 MicroOp::StackPush { src } => {
                     let frame = self.frames.last().unwrap();
                     let val = self.stack[frame.stack_base + src.0];
                     self.stack.push(val);
+                    if let Err(mut e) = self.check_stack_limit()
+                        && !self.handle_exception(&mut e, chunk)?
+                    {
+                        return Err(e);
+                    }
                 }
                 MicroOp::StackPop { dst } => {
                     let val = self.pop_operand()?;
@@ -1897,12 +2758,9 @@ impl VM {
                     self.stack[sb + dst.0] = Value::Ref(r);
                 }
                 MicroOp::GlobalGet { dst, idx } => {
-                    let val = self
-                        .globals
-                        .get(idx)
-                        .ok_or_else(|| format!("invalid global index: {}", idx))?;
+                    let val = self.global(idx, chunk)?;
                     let sb = self.frames.last().unwrap().stack_base;
-                    self.stack[sb + dst.0] = *val;
+                    self.stack[sb + dst.0] = val;
                 }
                 MicroOp::VtableLookup {
                     dst,
@@ -1916,7 +2774,7 @@ impl VM {
                     let iface_ref = self.stack[sb + iface_desc.0]
                         .as_ref()
                         .ok_or("runtime error: VtableLookup expects iface_desc reference")?;
-                    let result = self.vtable_lookup(ti_ref, iface_ref)?;
+                    let result = self.cached_vtable_lookup(ti_ref, iface_ref)?;
                     let sb = self.frames.last().unwrap().stack_base;
                     self.stack[sb + dst.0] = result;
                 }
@@ -1935,31 +2793,46 @@ impl VM {
                     let sb = self.frames.last().unwrap().stack_base;
                     let size_val = self.stack[sb + size.0]
                         .as_i64()
-                        .ok_or("runtime error: HeapAllocDynSimple requires integer size")?
-                        as usize;
-                    let r = if elem_kind.is_typed() {
-                        self.heap.alloc_typed_array(size_val as u32, elem_kind)?
-                    } else {
-                        let slots = vec![Value::Null; size_val];
-                        self.heap.alloc_slots(slots)?
-                    };
-                    self.stack[sb + dst.0] = Value::Ref(r);
+                        .ok_or("runtime error: HeapAllocDynSimple requires integer size")?;
+                    match self.alloc_dyn_simple(size_val, elem_kind) {
+                        Ok(r) => self.stack[sb + dst.0] = Value::Ref(r),
+                        Err(mut e) => {
+                            if !self.handle_exception(&mut e, chunk)? {
+                                return Err(e);
+                            }
+                        }
+                    }
                 }
                 MicroOp::Raw { op } => {
-                    // Profile if enabled
-                    if self.profile_opcodes {
-                        *self.opcode_profile.counts.entry(op.name()).or_insert(0) += 1;
-                    }
-
-                    match self.execute_op(op, chunk) {
+                    let result = self
+                        .execute_op(op, chunk)
+                        .and_then(|flow| self.check_stack_limit().map(|_| flow));
+                    match result {
                         Ok(ControlFlow::Continue) => {}
                         Ok(_) => {
                             // Control flow ops should never be Raw
                             // (converter ensures this)
                         }
-                        Err(e) => {
-                            if !self.handle_exception(e.clone(), chunk)? {
-                                return Err(e);
+                        Err(mut e) => {
+                            // Capture the frames before handle_exception unwinds them
+                            let frames: Option<Vec<(usize, usize)>> = self.backtrace.then(|| {
+                                self.frames.iter().map(|f| (f.func_index, f.pc)).collect()
+                            });
+                            if !self.handle_exception(&mut e, chunk)? {
+                                let Some(frames) = frames else {
+                                    return Err(e);
+                                };
+                                let op_pc = |func_index: usize, pc: usize| {
+                                    let converted = if func_index == usize::MAX {
+                                        Some(&main_converted)
+                                    } else {
+                                        func_cache[func_index].as_ref()
+                                    };
+                                    converted.map_or(0, |c| {
+                                        c.pc_map.partition_point(|&m| m <= pc).saturating_sub(1)
+                                    })
+                                };
+                                return Err(Self::format_backtrace(e, &frames, chunk, op_pc));
                             }
                         }
                     }
@@ -2044,6 +2917,42 @@ impl VM {
                     }
                     self.stack[sb + dst.0] = Value::I64(va % vb);
                 }
+                MicroOp::DivU64 { dst, a, b } => {
+                    let sb = self.frames.last().unwrap().stack_base;
+                    let va = self.stack[sb + a.0].as_i64().ok_or("expected integer")?;
+                    let vb = self.stack[sb + b.0].as_i64().ok_or("expected integer")?;
+                    if vb == 0 {
+                        return Err("runtime error: division by zero".to_string());
+                    }
+                    self.stack[sb + dst.0] = Value::I64(((va as u64) / (vb as u64)) as i64);
+                }
+                MicroOp::RemU64 { dst, a, b } => {
+                    let sb = self.frames.last().unwrap().stack_base;
+                    let va = self.stack[sb + a.0].as_i64().ok_or("expected integer")?;
+                    let vb = self.stack[sb + b.0].as_i64().ok_or("expected integer")?;
+                    if vb == 0 {
+                        return Err("runtime error: division by zero".to_string());
+                    }
+                    self.stack[sb + dst.0] = Value::I64(((va as u64) % (vb as u64)) as i64);
+                }
+                MicroOp::AddSatI64 { dst, a, b } => {
+                    let sb = self.frames.last().unwrap().stack_base;
+                    let va = self.stack[sb + a.0].as_i64().ok_or("expected integer")?;
+                    let vb = self.stack[sb + b.0].as_i64().ok_or("expected integer")?;
+                    self.stack[sb + dst.0] = Value::I64(va.saturating_add(vb));
+                }
+                MicroOp::SubSatI64 { dst, a, b } => {
+                    let sb = self.frames.last().unwrap().stack_base;
+                    let va = self.stack[sb + a.0].as_i64().ok_or("expected integer")?;
+                    let vb = self.stack[sb + b.0].as_i64().ok_or("expected integer")?;
+                    self.stack[sb + dst.0] = Value::I64(va.saturating_sub(vb));
+                }
+                MicroOp::MulSatI64 { dst, a, b } => {
+                    let sb = self.frames.last().unwrap().stack_base;
+                    let va = self.stack[sb + a.0].as_i64().ok_or("expected integer")?;
+                    let vb = self.stack[sb + b.0].as_i64().ok_or("expected integer")?;
+                    self.stack[sb + dst.0] = Value::I64(va.saturating_mul(vb));
+                }
                 MicroOp::NegI64 { dst, src } => {
                     let sb = self.frames.last().unwrap().stack_base;
                     let v = self.stack[sb + src.0].as_i64().ok_or("expected integer")?;
@@ -2370,7 +3279,7 @@ impl VM {
                 MicroOp::F64ReinterpretAsI64 { dst, src } => {
                     let sb = self.frames.last().unwrap().stack_base;
                     let f = self.stack[sb + src.0].as_f64().ok_or("expected float")?;
-                    self.stack[sb + dst.0] = Value::I64(i64::from_ne_bytes(f.to_ne_bytes()));
+                    self.stack[sb + dst.0] = Value::I64(Value::canonical_f64_bits(f) as i64);
                 }
 
                 // ========================================
@@ -2411,6 +3320,9 @@ impl VM {
             Op::I64Const(n) => {
                 self.stack.push(Value::I64(n));
             }
+            Op::FuncRef(func_index) => {
+                self.stack.push(Value::I64(func_index as i64));
+            }
             Op::F32Const(f) => {
                 self.stack.push(Value::F64(f as f64));
             }
@@ -2424,6 +3336,17 @@ impl VM {
                 let r = self.get_or_alloc_string(idx, chunk)?;
                 self.stack.push(Value::Ref(r));
             }
+            Op::ConstArray(idx) => {
+                let template = chunk.const_arrays.get(idx).ok_or_else(|| {
+                    format!("runtime error: invalid constant array index {}", idx)
+                })?;
+                // Same layout as an element-by-element literal: [data_ptr, len]
+                let data = self.heap.alloc_slots(template.clone())?;
+                let array = self
+                    .heap
+                    .alloc_slots(vec![Value::Ref(data), Value::I64(template.len() as i64)])?;
+                self.stack.push(Value::Ref(array));
+            }
 
             // ========================================
             // Local Variables
@@ -2462,12 +3385,7 @@ impl VM {
                 self.stack.push(value);
             }
             Op::Pick(n) => {
-                let len = self.stack.len();
-                if n >= len {
-                    return Err("stack underflow".to_string());
-                }
-                let value = self.stack[len - 1 - n];
-                self.stack.push(value);
+                self.pick(n)?;
             }
             Op::PickDyn => {
                 let depth_val = self.stack.pop().ok_or("stack underflow")?;
@@ -2475,12 +3393,7 @@ impl VM {
                     .as_i64()
                     .ok_or("runtime error: PickDyn requires integer depth")?
                     as usize;
-                let len = self.stack.len();
-                if depth >= len {
-                    return Err("stack underflow".to_string());
-                }
-                let value = self.stack[len - 1 - depth];
-                self.stack.push(value);
+                self.pick(depth)?;
             }
 
             // ========================================
@@ -2557,6 +3470,45 @@ impl VM {
                 }
                 self.stack.push(Value::I64(a % b));
             }
+            Op::I64DivU => {
+                let b = self.pop_int()?;
+                let a = self.pop_int()?;
+                if b == 0 {
+                    return Err("runtime error: division by zero".to_string());
+                }
+                self.stack
+                    .push(Value::I64(((a as u64) / (b as u64)) as i64));
+            }
+            Op::I64RemU => {
+                let b = self.pop_int()?;
+                let a = self.pop_int()?;
+                if b == 0 {
+                    return Err("runtime error: division by zero".to_string());
+                }
+                self.stack
+                    .push(Value::I64(((a as u64) % (b as u64)) as i64));
+            }
+            Op::I64AddSat => {
+                let b = self.pop_int()?;
+                let a = self.pop_int()?;
+                self.stack.push(Value::I64(a.saturating_add(b)));
+            }
+            Op::I64SubSat => {
+                let b = self.pop_int()?;
+                let a = self.pop_int()?;
+                self.stack.push(Value::I64(a.saturating_sub(b)));
+            }
+            Op::I64MulSat => {
+                let b = self.pop_int()?;
+                let a = self.pop_int()?;
+                self.stack.push(Value::I64(a.saturating_mul(b)));
+            }
+            Op::I64AddChecked => {
+                let b = self.pop_int()?;
+                let a = self.pop_int()?;
+                self.stack
+                    .push(a.checked_add(b).map(Value::I64).unwrap_or(Value::Null));
+            }
             Op::I64Neg => {
                 let a = self.stack.pop().ok_or("stack underflow")?;
                 let result = match a {
@@ -2815,6 +3767,18 @@ impl VM {
                 let a = self.stack.pop().ok_or("stack underflow")?;
                 self.stack.push(Value::Bool(a == Value::Null));
             }
+            Op::DeepEq(depth) => {
+                let b = self.stack.pop().ok_or("stack underflow")?;
+                let a = self.stack.pop().ok_or("stack underflow")?;
+                let result = self.deep_equal(&a, &b, depth);
+                self.stack.push(Value::Bool(result));
+            }
+            Op::Compare => {
+                let b = self.stack.pop().ok_or("stack underflow")?;
+                let a = self.stack.pop().ok_or("stack underflow")?;
+                let result = self.compare_strings(&a, &b)?;
+                self.stack.push(Value::I64(result as i64));
+            }
 
             // ========================================
             // Type Conversion
@@ -2874,7 +3838,7 @@ impl VM {
             Op::F64ReinterpretAsI64 => {
                 let f = self.pop_float()?;
                 self.stack
-                    .push(Value::I64(i64::from_ne_bytes(f.to_ne_bytes())));
+                    .push(Value::I64(Value::canonical_f64_bits(f) as i64));
             }
             Op::Jmp(target) => {
                 // Get frame info without holding mutable borrow
@@ -2976,9 +3940,12 @@ impl VM {
                     frame.pc = target;
                 }
             }
-            Op::Call(func_index, argc) => {
+            Op::Call(func_index, argc) | Op::TailCall(func_index, argc) => {
                 let func = &chunk.functions[func_index];
 
+                // Chunk::validate_call_arities checks this when the chunk is
+                // compiled or loaded
+                #[cfg(debug_assertions)]
                 if argc != func.arity {
                     return Err(format!(
                         "runtime error: function '{}' expects {} arguments, got {}",
@@ -3016,6 +3983,18 @@ impl VM {
                 }
 
                 // Fall back to interpreter
+                if matches!(op, Op::TailCall(..)) && !self.observes_every_call() {
+                    // Replace the current frame: drop its locals and temporaries
+                    // and reuse its base for the callee's arguments
+                    let args = self.stack.split_off(self.stack.len() - argc);
+                    let frame = self.frames.last_mut().unwrap();
+                    self.stack.truncate(frame.stack_base);
+                    self.stack.extend(args);
+                    frame.func_index = func_index;
+                    frame.pc = 0;
+                    self.reserve_locals(func.locals_count);
+                    return Ok(ControlFlow::Continue);
+                }
                 let new_stack_base = self.stack.len() - argc;
 
                 self.frames.push(Frame {
@@ -3025,6 +4004,7 @@ impl VM {
                     ret_vreg: None,
                     stack_floor: 0,
                 });
+                self.reserve_locals(func.locals_count);
             }
             Op::Ret => {
                 let return_value = self.stack.pop().unwrap_or(Value::Null);
@@ -3053,14 +4033,24 @@ impl VM {
             }
             Op::TypeOf => {
                 let value = self.stack.pop().ok_or("stack underflow")?;
-                let tag = match value {
-                    Value::I64(_) => 0,
-                    Value::F64(_) => 1,
-                    Value::Bool(_) => 2,
-                    Value::Null => 3,
-                    Value::Ref(_) => 4,
-                };
-                self.stack.push(Value::I64(tag));
+                self.stack.push(Value::I64(value.type_tag() as i64));
+            }
+            Op::TypeName => {
+                let value = self.stack.pop().ok_or("stack underflow")?;
+                let name = type_name_of(&self.heap, &self.globals, &value);
+                let r = self.heap.alloc_string(name)?;
+                self.stack.push(Value::Ref(r));
+            }
+            Op::AssertType(expected) => {
+                let value = self.stack.last().ok_or("stack underflow")?;
+                let actual = value.type_tag();
+                if actual != expected {
+                    return Err(format!(
+                        "runtime error: expected {}, got {}",
+                        Value::tag_name(expected),
+                        Value::tag_name(actual)
+                    ));
+                }
             }
             Op::HeapSize => {
                 let value = self.stack.pop().ok_or("stack underflow")?;
@@ -3073,10 +4063,291 @@ impl VM {
                     .ok_or("runtime error: invalid reference in __heap_size")?;
                 self.stack.push(Value::I64(size as i64));
             }
+            Op::ArraySlice => {
+                let end = self.pop_int()?;
+                let start = self.pop_int()?;
+                let arr = self
+                    .stack
+                    .pop()
+                    .ok_or("stack underflow")?
+                    .as_ref()
+                    .ok_or("runtime error: slice expects an array")?;
+                let len = self
+                    .heap
+                    .read_slot(arr, 1)
+                    .and_then(|v| v.as_i64())
+                    .ok_or("runtime error: slice expects an array")?;
+                if start > end {
+                    return Err(format!(
+                        "runtime error: slice start {} is greater than end {}",
+                        start, end
+                    ));
+                }
+                if start < 0 || end > len {
+                    return Err(format!(
+                        "runtime error: slice range {}..{} out of bounds for length {}",
+                        start, end, len
+                    ));
+                }
+                let data = match self.heap.read_slot(arr, 0) {
+                    Some(Value::Ref(data)) => Value::Ref(self.heap.copy_slots(
+                        data,
+                        start as usize,
+                        (end - start) as usize,
+                    )?),
+                    _ => Value::Null,
+                };
+                let r = self.heap.alloc_slots(vec![data, Value::I64(end - start)])?;
+                self.stack.push(Value::Ref(r));
+            }
+            Op::MapNew => {
+                let map = dict::new(&mut self.heap)?;
+                self.stack.push(Value::Ref(map));
+            }
+            Op::MapGet => {
+                let key = self.stack.pop().ok_or("stack underflow")?;
+                let map = self.pop_map()?;
+                match dict::get(&self.heap, map, key)? {
+                    Some(value) => self.stack.push(value),
+                    None => {
+                        return Err(format!(
+                            "runtime error: key not found in map: {}",
+                            self.value_to_string(&key)?
+                        ));
+                    }
+                }
+            }
+            Op::MapSet => {
+                let value = self.stack.pop().ok_or("stack underflow")?;
+                let key = self.stack.pop().ok_or("stack underflow")?;
+                let map = self.pop_map()?;
+                dict::set(&mut self.heap, map, key, value)?;
+            }
+            Op::MapHas => {
+                let key = self.stack.pop().ok_or("stack underflow")?;
+                let map = self.pop_map()?;
+                let found = dict::has(&self.heap, map, key)?;
+                self.stack.push(Value::Bool(found));
+            }
+            Op::MapDelete => {
+                let key = self.stack.pop().ok_or("stack underflow")?;
+                let map = self.pop_map()?;
+                let removed = dict::delete(&mut self.heap, map, key)?;
+                self.stack.push(Value::Bool(removed));
+            }
+            Op::MapLen => {
+                let map = self.pop_map()?;
+                let len = dict::len(&self.heap, map)?;
+                self.stack.push(Value::I64(len as i64));
+            }
+            Op::MapKeys | Op::MapValues | Op::MapEntries => {
+                let map = self.pop_map()?;
+                let mut items = Vec::new();
+                for (key, value) in dict::entries(&self.heap, map)? {
+                    items.push(match op {
+                        Op::MapKeys => key,
+                        Op::MapValues => value,
+                        _ => Value::Ref(self.heap.alloc_slots(vec![key, value])?),
+                    });
+                }
+                let len = items.len() as i64;
+                let data = self.heap.alloc_slots(items)?;
+                let array = self
+                    .heap
+                    .alloc_slots(vec![Value::Ref(data), Value::I64(len)])?;
+                self.stack.push(Value::Ref(array));
+            }
+            Op::StringSplit => {
+                let sep = self.pop_string("split")?;
+                let s = self.pop_string("split")?;
+                // An empty separator splits into characters; "" has no characters
+                let pieces: Vec<String> = if sep.is_empty() {
+                    s.chars().map(String::from).collect()
+                } else {
+                    s.split(sep.as_str()).map(String::from).collect()
+                };
+                let mut items = Vec::with_capacity(pieces.len());
+                for piece in pieces {
+                    items.push(Value::Ref(self.heap.alloc_string(piece)?));
+                }
+                let len = items.len() as i64;
+                let data = self.heap.alloc_slots(items)?;
+                let array = self
+                    .heap
+                    .alloc_slots(vec![Value::Ref(data), Value::I64(len)])?;
+                self.stack.push(Value::Ref(array));
+            }
+            Op::StringJoin => {
+                let sep = self.pop_string("join")?;
+                let parts = self
+                    .stack
+                    .pop()
+                    .ok_or("stack underflow")?
+                    .as_ref()
+                    .ok_or("runtime error: join expects an array of strings")?;
+                let len = self
+                    .heap
+                    .read_slot(parts, 1)
+                    .and_then(|v| v.as_i64())
+                    .ok_or("runtime error: join expects an array of strings")?;
+                let mut pieces = Vec::with_capacity(len as usize);
+                if let Some(Value::Ref(data)) = self.heap.read_slot(parts, 0) {
+                    for i in 0..len as usize {
+                        let piece = self
+                            .heap
+                            .read_slot(data, i)
+                            .and_then(|v| v.as_ref())
+                            .ok_or("runtime error: join expects an array of strings")?;
+                        pieces.push(self.ref_to_rust_string(piece)?);
+                    }
+                }
+                let joined = self.heap.alloc_string(pieces.join(&sep))?;
+                self.stack.push(Value::Ref(joined));
+            }
+            Op::StringSubstring => {
+                let end = self.pop_int()?;
+                let start = self.pop_int()?;
+                let s = self.pop_string("substring")?;
+                let char_count = s.chars().count() as i64;
+                let start = start.clamp(0, char_count) as usize;
+                let end = end.clamp(0, char_count) as usize;
+                let result: String = s
+                    .chars()
+                    .skip(start)
+                    .take(end.saturating_sub(start))
+                    .collect();
+                let r = self.heap.alloc_string(result)?;
+                self.stack.push(Value::Ref(r));
+            }
+            Op::StringIndexOf => {
+                let needle = self.pop_string("index_of")?;
+                let s = self.pop_string("index_of")?;
+                let index = match s.find(needle.as_str()) {
+                    Some(byte_index) => s[..byte_index].chars().count() as i64,
+                    None => -1,
+                };
+                self.stack.push(Value::I64(index));
+            }
+            Op::StringReplace => {
+                let to = self.pop_string("replace")?;
+                let from = self.pop_string("replace")?;
+                let s = self.pop_string("replace")?;
+                let result = if from.is_empty() {
+                    s
+                } else {
+                    s.replace(from.as_str(), &to)
+                };
+                let r = self.heap.alloc_string(result)?;
+                self.stack.push(Value::Ref(r));
+            }
+            Op::StringBuilderAppend => {
+                let s = self.pop_string("sb_append")?;
+                let sb = self.pop_string_builder("sb_append")?;
+                let (data, len) = self.string_builder_parts(sb)?;
+                let cap = data.and_then(|d| self.heap.slot_count(d)).unwrap_or(0);
+                let needed = len + s.len();
+                let data = match data {
+                    Some(data) if needed <= cap => data,
+                    _ => {
+                        // Grow by doubling so n appends copy O(n) bytes in total
+                        let grown = self
+                            .heap
+                            .alloc_typed_array(needed.max(cap * 2).max(16) as u32, ElemKind::U8)?;
+                        if let Some(old) = data {
+                            for i in 0..len {
+                                let b = self.heap.read_typed(old, i).unwrap_or(0);
+                                self.heap.write_typed(grown, i, b)?;
+                            }
+                        }
+                        self.heap.write_slot(sb, 0, Value::Ref(grown))?;
+                        grown
+                    }
+                };
+                for (i, b) in s.bytes().enumerate() {
+                    self.heap.write_typed(data, len + i, b as u64)?;
+                }
+                self.heap.write_slot(sb, 1, Value::I64(needed as i64))?;
+            }
+            Op::StringBuilderFinish => {
+                let sb = self.pop_string_builder("sb_finish")?;
+                let r = match self.string_builder_parts(sb)? {
+                    (Some(data), len) if len > 0 => {
+                        let copy = self.heap.copy_slots(data, 0, len)?;
+                        self.heap
+                            .alloc_slots(vec![Value::Ref(copy), Value::I64(len as i64)])?
+                    }
+                    _ => self.heap.alloc_string(String::new())?,
+                };
+                self.stack.push(Value::Ref(r));
+            }
+            Op::StringToBytes => {
+                let s = self
+                    .stack
+                    .pop()
+                    .ok_or("stack underflow")?
+                    .as_ref()
+                    .ok_or("runtime error: to_bytes expects a string")?;
+                let len = self
+                    .heap
+                    .read_slot(s, 1)
+                    .and_then(|v| v.as_i64())
+                    .ok_or("runtime error: to_bytes expects a string")?
+                    as usize;
+                let bytes = self.heap.alloc_typed_array(len as u32, ElemKind::I64)?;
+                if let Some(Value::Ref(data)) = self.heap.read_slot(s, 0) {
+                    for i in 0..len {
+                        let byte = self.heap.read_typed(data, i).unwrap_or(0);
+                        self.heap.write_typed(bytes, i, byte)?;
+                    }
+                }
+                let array = self
+                    .heap
+                    .alloc_slots(vec![Value::Ref(bytes), Value::I64(len as i64)])?;
+                self.stack.push(Value::Ref(array));
+            }
+            Op::BytesToString => {
+                let array = self
+                    .stack
+                    .pop()
+                    .ok_or("stack underflow")?
+                    .as_ref()
+                    .ok_or("runtime error: from_bytes expects a byte array")?;
+                let len = self
+                    .heap
+                    .read_slot(array, 1)
+                    .and_then(|v| v.as_i64())
+                    .ok_or("runtime error: from_bytes expects a byte array")?;
+                let mut bytes = Vec::with_capacity(len as usize);
+                if let Some(Value::Ref(data)) = self.heap.read_slot(array, 0) {
+                    for i in 0..len as usize {
+                        match self.heap.read_slot(data, i) {
+                            Some(Value::I64(b @ 0..=255)) => bytes.push(b as u8),
+                            other => {
+                                return Err(format!(
+                                    "runtime error: from_bytes: element {} is not a byte: {}",
+                                    i,
+                                    self.value_to_string(&other.unwrap_or(Value::Null))?
+                                ));
+                            }
+                        }
+                    }
+                }
+                let s = String::from_utf8(bytes).map_err(|e| {
+                    format!(
+                        "runtime error: from_bytes: invalid UTF-8 at byte {}",
+                        e.utf8_error().valid_up_to()
+                    )
+                })?;
+                let r = self.heap.alloc_string(s)?;
+                self.stack.push(Value::Ref(r));
+            }
             Op::Throw => {
                 let value = self.stack.pop().ok_or("stack underflow")?;
-                let msg = self.value_to_string(&value)?;
-                return Err(format!("runtime error: {}", msg));
+                self.thrown = Some(value);
+                return Err(THROWN_VALUE.to_string());
+            }
+            Op::Unreachable => {
+                return Err(UNREACHABLE_CODE.to_string());
             }
             Op::TryBegin(handler_pc) => {
                 let frame = self.frames.last().unwrap();
@@ -3090,60 +4361,49 @@ impl VM {
             Op::TryEnd => {
                 self.try_frames.pop();
             }
-            Op::GcHint(_bytes) => {
-                // Hint about upcoming allocation - might trigger GC
-                if self.heap.should_gc() {
+            Op::FreeTemp => {
+                let value = self.stack.pop().ok_or("stack underflow")?;
+                if let Value::Ref(r) = value {
+                    self.heap.free_temporary(r);
+                }
+            }
+            Op::GcHint(bytes) => {
+                // Hint about upcoming allocation - collect now if it would cross the threshold
+                if self.heap.should_gc_before(bytes) {
                     self.collect_garbage();
                 }
             }
+            // Only the debugger stops here
+            Op::DebugBreak => {}
 
             // Thread operations
-            Op::ThreadSpawn(func_index) => {
-                // Clone the chunk for the new thread
-                let chunk_clone = chunk.clone();
-
-                // Spawn a new thread that creates a VM and runs the function
-                let thread_id = self.thread_spawner.spawn(move || {
-                    let mut vm = VM::new();
-
-                    // Create a wrapper main that calls the target function and captures return
-                    // The wrapper just calls the function and returns its result
-                    let wrapper_main = Function {
-                        name: "__thread_main__".to_string(),
-                        arity: 0,
-                        locals_count: 1, // To store return value
-                        code: vec![
-                            Op::Call(func_index, 0), // Call the target function (must be 0-arity)
-                            Op::Ret,                 // Return the result
-                        ],
-                        stackmap: None,
-                        local_types: vec![],
-                    };
-
-                    let thread_chunk = Chunk {
-                        functions: chunk_clone.functions.clone(),
-                        main: wrapper_main,
-                        strings: chunk_clone.strings.clone(),
-                        type_descriptors: chunk_clone.type_descriptors.clone(),
-                        interface_descriptors: chunk_clone.interface_descriptors.clone(),
-                        debug: None,
-                    };
-
-                    match vm.run_and_get_result(&thread_chunk) {
-                        Ok(result) => result,
-                        Err(_e) => Value::Null,
-                    }
-                });
-
+            Op::ThreadSpawn(func_index, argc) => {
+                let mut cells = Vec::with_capacity(argc);
+                for _ in 0..argc {
+                    cells.push(Arc::clone(self.pop_atomic()?));
+                }
+                cells.reverse();
                 // Push the thread handle ID as the result
+                let thread_id = self.spawn_thread(chunk, func_index, cells);
                 self.stack.push(Value::I64(thread_id as i64));
             }
+            Op::Spawn(func_index) => {
+                let thread_id = self.spawn_thread(chunk, func_index, Vec::new());
+                let promise = self.heap.alloc_slots(vec![
+                    Value::I64(thread_id as i64),
+                    Value::Bool(false),
+                    Value::Null,
+                ])?;
+                self.stack.push(Value::Ref(promise));
+            }
             Op::ChannelCreate => {
                 // Create a new channel and return [sender_id, receiver_id]
                 // For simplicity, we use the same id for both (same underlying channel)
                 let channel = Channel::new();
-                let id = self.channels.len();
-                self.channels.push(channel);
+                let mut channels = self.channels.lock().unwrap();
+                let id = channels.len();
+                channels.push(channel);
+                drop(channels);
 
                 // Create slots with [sender_id, receiver_id] layout
                 let arr = self
@@ -3154,35 +4414,75 @@ impl VM {
             Op::ChannelSend => {
                 let value = self.stack.pop().ok_or("stack underflow")?;
                 let channel_id = self.pop_int()? as usize;
-
-                let channel = self
-                    .channels
-                    .get(channel_id)
-                    .ok_or_else(|| format!("runtime error: channel {} not found", channel_id))?
-                    .clone();
-
-                channel
-                    .send(value)
-                    .map_err(|_| "runtime error: channel closed")?;
+                let channel = self.channel(channel_id)?;
+                self.thread_spawner.send(channel_id, &channel, value)?;
             }
             Op::ChannelRecv => {
                 let channel_id = self.pop_int()? as usize;
-
-                let channel = self
-                    .channels
-                    .get(channel_id)
-                    .ok_or_else(|| format!("runtime error: channel {} not found", channel_id))?
-                    .clone();
-
-                let value = channel.recv().unwrap_or(Value::Null);
+                let channel = self.channel(channel_id)?;
+                let value = self.thread_spawner.recv(channel_id, &channel)?;
                 self.stack.push(value);
             }
             Op::ThreadJoin => {
                 let thread_id = self.pop_int()? as usize;
 
                 let result = self.thread_spawner.join(thread_id)?;
+                let result = self.heap.import(&result)?;
+                self.stack.push(result);
+            }
+            Op::Await => {
+                let promise = self
+                    .stack
+                    .pop()
+                    .ok_or("stack underflow")?
+                    .as_ref()
+                    .ok_or("runtime error: await expects a promise")?;
+                let (Some(Value::I64(thread_id)), Some(Value::Bool(resolved))) = (
+                    self.heap.read_slot(promise, 0),
+                    self.heap.read_slot(promise, 1),
+                ) else {
+                    return Err("runtime error: await expects a promise".to_string());
+                };
+                let result = if resolved {
+                    self.heap
+                        .read_slot(promise, 2)
+                        .ok_or("runtime error: await expects a promise")?
+                } else {
+                    let result = self.thread_spawner.join(thread_id as usize)?;
+                    let result = self.heap.import(&result)?;
+                    self.heap.write_slot(promise, 1, Value::Bool(true))?;
+                    self.heap.write_slot(promise, 2, result)?;
+                    result
+                };
                 self.stack.push(result);
             }
+            Op::AtomicNew => {
+                let init = self.pop_int()?;
+                let atomic = self.alloc_atomic(Arc::new(AtomicI64::new(init)))?;
+                self.stack.push(Value::Ref(atomic));
+            }
+            Op::AtomicLoad => {
+                let value = self.pop_atomic()?.load(Ordering::SeqCst);
+                self.stack.push(Value::I64(value));
+            }
+            Op::AtomicStore => {
+                let value = self.pop_int()?;
+                self.pop_atomic()?.store(value, Ordering::SeqCst);
+            }
+            Op::AtomicFetchAdd => {
+                let delta = self.pop_int()?;
+                let previous = self.pop_atomic()?.fetch_add(delta, Ordering::SeqCst);
+                self.stack.push(Value::I64(previous));
+            }
+            Op::AtomicCompareExchange => {
+                let new = self.pop_int()?;
+                let expected = self.pop_int()?;
+                let swapped = self
+                    .pop_atomic()?
+                    .compare_exchange(expected, new, Ordering::SeqCst, Ordering::SeqCst)
+                    .is_ok();
+                self.stack.push(Value::Bool(swapped));
+            }
 
             // Heap slot operations
             Op::HeapAlloc(n) => {
@@ -3310,6 +4610,13 @@ impl VM {
                 let new_ref = r.with_added_slot_offset(offset as usize);
                 self.stack.push(Value::Ref(new_ref));
             }
+            Op::TypeTag(type_index) => {
+                // Printing a tagged struct reads its name from the descriptor
+                self.global(type_index, chunk)?;
+                if let Some(Value::Ref(r)) = self.stack.last() {
+                    self.heap.set_type_tag(*r, type_index);
+                }
+            }
             Op::HeapAllocDyn => {
                 // Pop size from stack, then pop that many elements as initial values
                 let size_val = self.stack.pop().ok_or("stack underflow")?;
@@ -3331,14 +4638,8 @@ impl VM {
                 let size_val = self.stack.pop().ok_or("stack underflow")?;
                 let size = size_val
                     .as_i64()
-                    .ok_or("runtime error: HeapAllocDynSimple requires integer size")?
-                    as usize;
-                let r = if ek.is_typed() {
-                    self.heap.alloc_typed_array(size as u32, ek)?
-                } else {
-                    let slots = vec![Value::Null; size];
-                    self.heap.alloc_slots(slots)?
-                };
+                    .ok_or("runtime error: HeapAllocDynSimple requires integer size")?;
+                let r = self.alloc_dyn_simple(size, ek)?;
                 self.stack.push(Value::Ref(r));
             }
             // HeapAllocString removed — use HeapAlloc(2) instead
@@ -3415,7 +4716,7 @@ impl VM {
                         "runtime error: function '{}' expects {} arguments (1 closure_ref + {} params), got 1 + {} args",
                         func.name,
                         expected_arity,
-                        expected_arity - 1,
+                        expected_arity.saturating_sub(1),
                         argc
                     ));
                 }
@@ -3434,6 +4735,7 @@ impl VM {
                     ret_vreg: None,
                     stack_floor: 0,
                 });
+                self.reserve_locals(func.locals_count);
             }
 
             // ========================================
@@ -3478,17 +4780,15 @@ impl VM {
                     ret_vreg: None,
                     stack_floor: 0,
                 });
+                self.reserve_locals(func.locals_count);
             }
 
             // ========================================
             // Globals
             // ========================================
             Op::GlobalGet(idx) => {
-                let val = self
-                    .globals
-                    .get(idx)
-                    .ok_or_else(|| format!("invalid global index: {}", idx))?;
-                self.stack.push(*val);
+                let val = self.global(idx, chunk)?;
+                self.stack.push(val);
             }
 
             // ========================================
@@ -3505,7 +4805,7 @@ impl VM {
                     .as_ref()
                     .ok_or("runtime error: VtableLookup expects iface_desc reference")?;
 
-                let result = self.vtable_lookup(ti_ref, iface_ref)?;
+                let result = self.cached_vtable_lookup(ti_ref, iface_ref)?;
                 self.stack.push(result);
             }
         }
@@ -3513,6 +4813,19 @@ impl VM {
         Ok(ControlFlow::Continue)
     }
 
+    /// Look up an interface vtable in a type_info heap object, consulting the
+    /// inline cache of the current call site first.
+    fn cached_vtable_lookup(&mut self, ti_ref: GcRef, iface_ref: GcRef) -> Result<Value, String> {
+        let frame = self.frames.last().unwrap();
+        let site = (frame.func_index, frame.pc);
+        if let Some(vtable) = self.vtable_cache.get(site, ti_ref, iface_ref) {
+            return Ok(vtable);
+        }
+        let vtable = self.vtable_lookup(ti_ref, iface_ref)?;
+        self.vtable_cache.insert(site, ti_ref, iface_ref, vtable);
+        Ok(vtable)
+    }
+
     /// Look up an interface vtable in a type_info heap object.
     /// Walks the vtable entries comparing iface_desc_ref by pointer equality.
     /// Returns vtable_ref (Value::Ref) if found, or Value::Null if not.
@@ -3554,24 +4867,8 @@ impl VM {
             (Value::Ref(a), Value::Ref(b)) => {
                 // String concatenation fallback for cases where codegen
                 // couldn't statically detect Ref+Ref (e.g. array indexing)
-                let a_obj = self.heap.get(a).ok_or("runtime error: invalid reference")?;
-                let b_obj = self.heap.get(b).ok_or("runtime error: invalid reference")?;
-                let a_data_ref = a_obj.slots[0]
-                    .as_ref()
-                    .ok_or("runtime error: invalid string ptr")?;
-                let b_data_ref = b_obj.slots[0]
-                    .as_ref()
-                    .ok_or("runtime error: invalid string ptr")?;
-                let a_data = self
-                    .heap
-                    .get(a_data_ref)
-                    .ok_or("runtime error: invalid string data")?;
-                let b_data = self
-                    .heap
-                    .get(b_data_ref)
-                    .ok_or("runtime error: invalid string data")?;
-                let a_str = a_data.slots_to_string();
-                let b_str = b_data.slots_to_string();
+                let a_str = self.ref_to_rust_string(a)?;
+                let b_str = self.ref_to_rust_string(b)?;
                 let result = format!("{}{}", a_str, b_str);
                 let r = self.heap.alloc_string(result)?;
                 Ok(Value::Ref(r))
@@ -3630,6 +4927,68 @@ impl VM {
         }
     }
 
+    /// Start a thread running the function `func_index` on a fresh VM that
+    /// shares this one's channels. The function is called with an `Atomic`
+    /// object for each of `cells`. Returns the thread id; joining it yields
+    /// the function's result exported from the thread's heap, or its error.
+    fn spawn_thread(
+        &mut self,
+        chunk: &Chunk,
+        func_index: usize,
+        cells: Vec<Arc<AtomicI64>>,
+    ) -> usize {
+        // Clone the chunk for the new thread
+        let chunk_clone = chunk.clone();
+        let channels = Arc::clone(&self.channels);
+
+        // Spawn a new thread that creates a VM and runs the function
+        self.thread_spawner.spawn_with_spawner(move |spawner| {
+            let mut vm = VM::new();
+            vm.thread_spawner = spawner;
+            vm.channels = channels;
+
+            // Create a wrapper main that calls the target function and captures return.
+            // The cells take the first indices of the new VM's table, so the
+            // wrapper builds their `Atomic` objects from those indices
+            let argc = cells.len();
+            vm.atomics = cells.into_iter().map(Some).collect();
+            let mut code: Vec<Op> = (0..argc)
+                .flat_map(|i| [Op::I64Const(i as i64), Op::HeapAlloc(1)])
+                .collect();
+            code.push(Op::Call(func_index, argc));
+            code.push(Op::Ret); // Return the result
+            let wrapper_main = Function {
+                name: "__thread_main__".to_string(),
+                arity: 0,
+                locals_count: 1, // To store return value
+                code,
+                stackmap: None,
+                local_types: vec![],
+            };
+
+            let thread_chunk = Chunk {
+                functions: chunk_clone.functions.clone(),
+                main: wrapper_main,
+                strings: chunk_clone.strings.clone(),
+                type_descriptors: chunk_clone.type_descriptors.clone(),
+                interface_descriptors: chunk_clone.interface_descriptors.clone(),
+                const_arrays: chunk_clone.const_arrays.clone(),
+                debug: None,
+                metadata: chunk_clone.metadata.clone(),
+            };
+
+            // The result outlives this VM's heap, so it leaves as a copy
+            vm.run_and_get_result(&thread_chunk)
+                .and_then(|result| vm.heap.export(result))
+        })
+    }
+
+    /// Three-way comparison behind the ordering operators.
+    ///
+    /// Numbers compare numerically, ints and floats mixed freely (NaN is
+    /// unordered and compares equal). References have no order here: string
+    /// orderings are compiled to `Compare`, and an untyped reference could be
+    /// any object, so every other pairing is an error naming both operands.
     fn compare(&self, a: &Value, b: &Value) -> Result<i32, String> {
         match (a, b) {
             (Value::I64(a), Value::I64(b)) => Ok(a.cmp(b) as i32),
@@ -3642,7 +5001,32 @@ impl VM {
                 let b = *b as f64;
                 Ok(a.partial_cmp(&b).map(|o| o as i32).unwrap_or(0))
             }
-            _ => Err("runtime error: cannot compare these types".to_string()),
+            _ => Err(format!(
+                "runtime error: cannot compare `{}` and `{}`",
+                self.ordering_kind(a),
+                self.ordering_kind(b)
+            )),
+        }
+    }
+
+    /// `Compare`: order two strings lexicographically by their bytes.
+    fn compare_strings(&self, a: &Value, b: &Value) -> Result<i32, String> {
+        let read = |v: &Value| {
+            v.as_ref()
+                .and_then(|r| dict::read_string(&self.heap, r))
+                .ok_or_else(|| "runtime error: Compare expects two strings".to_string())
+        };
+        Ok(read(a)?.cmp(&read(b)?) as i32)
+    }
+
+    /// Kind of `value` as named in comparison errors.
+    fn ordering_kind(&self, value: &Value) -> &'static str {
+        match value {
+            Value::I64(_) => "int",
+            Value::F64(_) => "float",
+            Value::Bool(_) => "bool",
+            Value::Null => "nil",
+            Value::Ref(_) => "ref",
         }
     }
 
@@ -3663,83 +5047,129 @@ impl VM {
         }
     }
 
-    fn value_to_string(&self, value: &Value) -> Result<String, String> {
-        match value {
-            Value::I64(n) => Ok(n.to_string()),
-            Value::F64(f) => {
-                if f.fract() == 0.0 {
-                    Ok(format!("{}.0", f))
-                } else {
-                    Ok(f.to_string())
+    /// Structural equality for arrays and strings.
+    ///
+    /// `depth` is the number of nested `Array<T>` layers in the operands' static
+    /// type. Within those layers both sides are read as `[ptr, len]` headers and
+    /// their elements compared pairwise; below them (structs, `Vec`, `Map`, ...)
+    /// references compare by identity, whatever their heap shape. Each pair of
+    /// objects is compared at most once, so shared and cyclic arrays terminate.
+    fn deep_equal(&self, a: &Value, b: &Value, depth: usize) -> bool {
+        let mut worklist = vec![(*a, *b, depth)];
+        let mut visited: HashSet<(usize, usize)> = HashSet::new();
+
+        while let Some((x, y, depth)) = worklist.pop() {
+            // Past the array layers of the static type, compare by identity
+            let (Value::Ref(rx), Value::Ref(ry), 1..) = (x, y, depth) else {
+                if !self.values_equal(&x, &y) {
+                    return false;
+                }
+                continue;
+            };
+            if rx.index == ry.index || !visited.insert((rx.index, ry.index)) {
+                continue;
+            }
+            let (Some((px, len_x)), Some((py, len_y))) = (self.array_view(rx), self.array_view(ry))
+            else {
+                return false;
+            };
+            if len_x != len_y {
+                return false;
+            }
+            for i in 0..len_x {
+                let ex = px.and_then(|p| self.heap.read_slot(p, i));
+                let ey = py.and_then(|p| self.heap.read_slot(p, i));
+                match (ex, ey) {
+                    (Some(ex), Some(ey)) => worklist.push((ex, ey, depth - 1)),
+                    _ => return false,
                 }
             }
-            Value::Bool(b) => Ok(b.to_string()),
-            Value::Null => Ok("nil".to_string()),
-            Value::Ref(r) => {
-                let obj = self
-                    .heap
-                    .get(*r)
-                    .ok_or("runtime error: invalid reference")?;
-
-                // Detect string vs array for 2-slot [Ref, I64] objects
-                // by checking the data array's ElemKind.
-                if obj.slots.len() == 2
-                    && let (Some(data_ref), Some(len)) =
-                        (obj.slots[0].as_ref(), obj.slots[1].as_i64())
-                {
-                    let len_usize = len as usize;
-                    let data_ek = self.heap.get_elem_kind(data_ref);
+        }
+        true
+    }
 
-                    if data_ek == ElemKind::U8 {
-                        // UTF-8 string: read bytes and decode
-                        let data = self
-                            .heap
-                            .get(data_ref)
-                            .ok_or("runtime error: invalid string data")?;
-                        let bytes: Vec<u8> = data
-                            .slots
-                            .iter()
-                            .take(len_usize)
-                            .filter_map(|v| v.as_i64())
-                            .map(|b| b as u8)
-                            .collect();
-                        return Ok(String::from_utf8_lossy(&bytes).into_owned());
-                    }
-                    // Otherwise display as array
-                    let mut parts = Vec::new();
-                    for i in 0..len_usize {
-                        if let Some(elem) = self.heap.read_slot(data_ref, i) {
-                            parts.push(self.value_to_string(&elem)?);
-                        }
-                    }
-                    return Ok(format!("[{}]", parts.join(", ")));
-                }
+    /// Interpret `r` as an `Array<T>` header `[ptr, len]`, returning the data
+    /// pointer (None when empty/null) and length if the shape matches.
+    fn array_view(&self, r: GcRef) -> Option<(Option<GcRef>, usize)> {
+        if self.heap.slot_count(r)? != 2 {
+            return None;
+        }
+        let Value::I64(len) = self.heap.read_slot(r, 1)? else {
+            return None;
+        };
+        let len = usize::try_from(len).ok()?;
+        match self.heap.read_slot(r, 0)? {
+            Value::Ref(p) if self.heap.slot_count(p)? >= len + p.slot_offset() => {
+                Some((Some(p), len))
+            }
+            Value::Null if len == 0 => Some((None, 0)),
+            _ => None,
+        }
+    }
 
-                // Fallback: show all elements as array/struct
-                let mut parts = Vec::new();
-                for elem in obj.slots.iter() {
-                    parts.push(self.value_to_string(elem)?);
+    fn value_to_string(&self, value: &Value) -> Result<String, String> {
+        format_value(
+            &self.heap,
+            &self.globals,
+            value,
+            DisplayMode::Raw,
+            &mut Vec::new(),
+        )
+    }
+
+    /// Substitute `{}` placeholders in `template` with `args` in order.
+    /// `{{` and `}}` produce literal braces.
+    fn format_template(&self, template: &str, args: &[Value]) -> Result<String, String> {
+        let mut out = String::with_capacity(template.len());
+        let mut next_arg = 0;
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match (c, chars.peek()) {
+                ('{', Some('{')) | ('}', Some('}')) => {
+                    chars.next();
+                    out.push(c);
+                }
+                ('{', Some('}')) => {
+                    chars.next();
+                    if let Some(arg) = args.get(next_arg) {
+                        out.push_str(&self.value_to_string(arg)?);
+                    }
+                    next_arg += 1;
+                }
+                ('{', _) | ('}', _) => {
+                    return Err(format!("format: unmatched '{}' in template", c));
                 }
-                Ok(format!("[{}]", parts.join(", ")))
+                _ => out.push(c),
             }
         }
+        if next_arg != args.len() {
+            return Err(format!(
+                "format: template has {} placeholder(s) but {} argument(s) were given",
+                next_arg,
+                args.len()
+            ));
+        }
+        Ok(out)
     }
 
     /// Convert a heap GcRef (String struct [ptr, len]) to a Rust String.
-    /// Follows the ptr to the data array and reads character slots.
+    /// Follows the ptr to the data array and reads the first `len` byte slots.
     fn ref_to_rust_string(&self, r: GcRef) -> Result<String, String> {
         let obj = self
             .heap
             .get(r)
             .ok_or("runtime error: invalid string reference")?;
-        let data_ref = obj.slots[0]
-            .as_ref()
-            .ok_or("runtime error: invalid string ptr")?;
+        let len = obj.slots.get(1).and_then(|v| v.as_i64()).unwrap_or(0);
+        let data_ref = match obj.slots.first() {
+            Some(Value::Ref(data_ref)) => *data_ref,
+            Some(Value::Null) if len == 0 => return Ok(String::new()),
+            _ => return Err("runtime error: invalid string ptr".to_string()),
+        };
         let data = self
             .heap
             .get(data_ref)
             .ok_or("runtime error: invalid string data")?;
-        Ok(data.slots_to_string())
+        Ok(data.slots_to_string_len(len.max(0) as usize))
     }
 
     /// Pop a value from the operand stack, respecting the register file boundary.
@@ -3751,11 +5181,109 @@ impl VM {
         Ok(self.stack.pop().unwrap())
     }
 
+    /// Push a copy of the value `depth` slots below the top of the stack.
+    /// The slot must lie in the current frame's operand area (above its
+    /// register file, if any), so a bad depth cannot read the caller's values.
+    fn pick(&mut self, depth: usize) -> Result<(), String> {
+        let base = self
+            .frames
+            .last()
+            .map_or(0, |f| f.stack_base.max(f.stack_floor));
+        let available = self.stack.len().saturating_sub(base);
+        if depth >= available {
+            return Err(format!(
+                "runtime error: pick out of frame bounds (depth {}, frame holds {} values)",
+                depth, available
+            ));
+        }
+        let value = self.stack[self.stack.len() - 1 - depth];
+        self.stack.push(value);
+        Ok(())
+    }
+
     fn pop_int(&mut self) -> Result<i64, String> {
         let value = self.stack.pop().ok_or("stack underflow")?;
         value.as_i64().ok_or_else(|| "expected integer".to_string())
     }
 
+    fn pop_string(&mut self, op_name: &str) -> Result<String, String> {
+        let value = self.stack.pop().ok_or("stack underflow")?;
+        let r = value
+            .as_ref()
+            .ok_or_else(|| format!("runtime error: {} expects a string", op_name))?;
+        self.ref_to_rust_string(r)
+    }
+
+    fn pop_string_builder(&mut self, op_name: &str) -> Result<GcRef, String> {
+        let value = self.stack.pop().ok_or("stack underflow")?;
+        value
+            .as_ref()
+            .ok_or_else(|| format!("runtime error: {} expects a StringBuilder", op_name))
+    }
+
+    /// The `[data, len]` of a `StringBuilder`; `data` is None until the
+    /// first append, and its slot count is the buffer's capacity.
+    fn string_builder_parts(&self, sb: GcRef) -> Result<(Option<GcRef>, usize), String> {
+        let invalid = || "runtime error: invalid StringBuilder".to_string();
+        let len = self
+            .heap
+            .read_slot(sb, 1)
+            .and_then(|v| v.as_i64())
+            .ok_or_else(invalid)?;
+        let data = match self.heap.read_slot(sb, 0) {
+            Some(Value::Ref(data)) => Some(data),
+            Some(Value::Null) => None,
+            _ => return Err(invalid()),
+        };
+        Ok((data, len as usize))
+    }
+
+    fn channel(&self, channel_id: usize) -> Result<Arc<Channel<Value>>, String> {
+        self.channels
+            .lock()
+            .unwrap()
+            .get(channel_id)
+            .cloned()
+            .ok_or_else(|| format!("runtime error: channel {} not found", channel_id))
+    }
+
+    /// Allocate an `Atomic` object (`[index]`) for `cell`. Collecting the
+    /// object releases the cell.
+    fn alloc_atomic(&mut self, cell: Arc<AtomicI64>) -> Result<GcRef, String> {
+        let index = match self.free_atomics.pop() {
+            Some(index) => {
+                self.atomics[index] = Some(cell);
+                index
+            }
+            None => {
+                self.atomics.push(Some(cell));
+                self.atomics.len() - 1
+            }
+        };
+        let atomic = self.heap.alloc_slots(vec![Value::I64(index as i64)])?;
+        // Like fd handles, the table is only touched once the GC cycle is over
+        let finalized = Rc::clone(&self.finalized_atomics);
+        self.heap
+            .set_finalizer(atomic, Box::new(move || finalized.borrow_mut().push(index)));
+        Ok(atomic)
+    }
+
+    fn pop_atomic(&mut self) -> Result<&Arc<AtomicI64>, String> {
+        let value = self.stack.pop().ok_or("stack underflow")?;
+        value
+            .as_ref()
+            .and_then(|r| self.heap.read_slot(r, 0))
+            .and_then(|index| self.atomics.get(index.as_i64()? as usize)?.as_ref())
+            .ok_or_else(|| "runtime error: expected an atomic".to_string())
+    }
+
+    fn pop_map(&mut self) -> Result<GcRef, String> {
+        let value = self.stack.pop().ok_or("stack underflow")?;
+        value
+            .as_ref()
+            .ok_or_else(|| "runtime error: expected map".to_string())
+    }
+
     fn pop_float(&mut self) -> Result<f64, String> {
         let value = self.stack.pop().ok_or("stack underflow")?;
         match value {
@@ -3765,7 +5293,90 @@ impl VM {
         }
     }
 
-    fn handle_exception(&mut self, error: String, _chunk: &Chunk) -> Result<bool, String> {
+    /// Append a backtrace (innermost frame first) to an uncaught error.
+    /// `frames` are `(func_index, pc)` pairs with `pc` just past the executing
+    /// op; `op_pc` maps a MicroOp pc back to its bytecode pc.
+    fn format_backtrace(
+        error: String,
+        frames: &[(usize, usize)],
+        chunk: &Chunk,
+        op_pc: impl Fn(usize, usize) -> usize,
+    ) -> String {
+        let mut out = error;
+        out.push_str("\nbacktrace:");
+        for (depth, &(func_index, pc)) in frames.iter().rev().enumerate() {
+            let (name, debug) = if func_index == usize::MAX {
+                ("<main>", chunk.debug.as_ref().map(|d| &d.main))
+            } else {
+                (
+                    chunk.functions[func_index].name.as_str(),
+                    chunk
+                        .debug
+                        .as_ref()
+                        .and_then(|d| d.functions.get(func_index)),
+                )
+            };
+            out.push_str(&format!("\n  {}: {}", depth, name));
+            let pc = op_pc(func_index, pc.saturating_sub(1));
+            if let Some((line, _)) = debug.and_then(|d| d.lines.find_location(pc)) {
+                out.push_str(&format!(" (line {})", line));
+            }
+        }
+        out
+    }
+
+    /// Append a heap summary (object count, bytes and the largest kinds) and
+    /// the innermost interpreter frames to an uncaught error when
+    /// `--print-heap-on-error` is set.
+    fn with_heap_report(&self, error: String, chunk: &Chunk) -> String {
+        const MAX_KINDS: usize = 10;
+        const MAX_FRAMES: usize = 5;
+        if !self.print_heap_on_error {
+            return error;
+        }
+
+        let histogram = self.heap.object_histogram();
+        let count: usize = histogram.iter().map(|e| e.count).sum();
+        let bytes: usize = histogram.iter().map(|e| e.bytes).sum();
+        let mut out = error;
+        out.push_str(&format!("\nheap: {} objects, {} bytes", count, bytes));
+        for entry in histogram.iter().take(MAX_KINDS) {
+            let kind = match entry.kind {
+                ObjectKind::Struct(index) => type_name(&self.heap, &self.globals, index)
+                    .unwrap_or_else(|| format!("struct #{}", index)),
+                ObjectKind::Slots => "untyped slots".to_string(),
+                ObjectKind::Array(elem_kind) => format!("{:?} array", elem_kind),
+            };
+            out.push_str(&format!(
+                "\n  {}: {} objects, {} bytes",
+                kind, entry.count, entry.bytes
+            ));
+        }
+
+        out.push_str("\nframes:");
+        for (depth, frame) in self.frames.iter().rev().take(MAX_FRAMES).enumerate() {
+            let name = chunk
+                .functions
+                .get(frame.func_index)
+                .map_or("<main>", |f| f.name.as_str());
+            out.push_str(&format!("\n  {}: {}", depth, name));
+        }
+        if self.frames.len() > MAX_FRAMES {
+            out.push_str(&format!("\n  ... {} more", self.frames.len() - MAX_FRAMES));
+        }
+        out
+    }
+
+    /// Unwind to the innermost try handler, binding the error message for it.
+    /// A pending `throw` is stringified into `error` here, so the caller sees
+    /// the real message when the error is uncaught.
+    fn handle_exception(&mut self, error: &mut String, _chunk: &Chunk) -> Result<bool, String> {
+        if error == THROWN_VALUE
+            && let Some(value) = self.thrown.take()
+        {
+            *error = format!("runtime error: {}", self.value_to_string(&value)?);
+        }
+
         // Look for a try frame that can handle this exception
         while let Some(try_frame) = self.try_frames.pop() {
             // Unwind call stack to the try frame's depth
@@ -3810,6 +5421,14 @@ impl VM {
     }
 
     fn collect_garbage(&mut self) {
+        // The AArch64 backend doesn't link its frames into `jit_frames`, so
+        // references held only in compiled frames would be missed. Wait until
+        // native code has returned.
+        #[cfg(all(target_arch = "aarch64", feature = "jit"))]
+        if self.jit_native_depth > 0 {
+            return;
+        }
+
         // Collect all roots from the stack
         let mut roots: Vec<Value> = self.stack.clone();
 
@@ -3823,454 +5442,759 @@ impl VM {
             roots.push(*val);
         }
 
+        // Add a thrown value that hasn't been caught yet
+        roots.extend(self.thrown);
+
+        // Add references held only in active JIT frames
+        #[cfg(all(any(target_arch = "aarch64", target_arch = "x86_64"), feature = "jit"))]
+        self.collect_jit_frame_roots(&mut roots);
+
         self.heap.collect(&roots);
-    }
 
-    /// Handle hostcall instructions
-    /// Hostcall numbers:
-    /// - 1: write(fd, buf, count) -> bytes_written
-    /// - 2: open(path, flags) -> fd
-    /// - 3: close(fd) -> 0 on success
-    /// - 4: read(fd, count) -> string (heap ref) or error
-    /// - 10: time() -> epoch seconds
-    /// - 11: time_nanos() -> epoch nanoseconds
-    fn handle_hostcall(&mut self, hostcall_num: usize, args: &[Value]) -> Result<Value, String> {
-        // Hostcall numbers
-        const HOSTCALL_WRITE: usize = 1;
-        const HOSTCALL_OPEN: usize = 2;
-        const HOSTCALL_CLOSE: usize = 3;
-        const HOSTCALL_READ: usize = 4;
-        const HOSTCALL_SOCKET: usize = 5;
-        const HOSTCALL_CONNECT: usize = 6;
-        const HOSTCALL_BIND: usize = 7;
-        const HOSTCALL_LISTEN: usize = 8;
-        const HOSTCALL_ACCEPT: usize = 9;
-        const HOSTCALL_TIME: usize = 10;
-        const HOSTCALL_TIME_NANOS: usize = 11;
-
-        // Error codes (negative return values)
-        const EBADF: i64 = -1; // Bad file descriptor
-        const ENOENT: i64 = -2; // No such file or directory
-        const EACCES: i64 = -3; // Permission denied
-        const ECONNREFUSED: i64 = -4; // Connection refused
-        const ETIMEDOUT: i64 = -5; // Connection timed out
-        const EAFNOSUPPORT: i64 = -6; // Address family not supported
-        const ESOCKTNOSUPPORT: i64 = -7; // Socket type not supported
-        const EADDRINUSE: i64 = -8; // Address already in use
-
-        // Open flags (Linux-compatible values)
-        const O_RDONLY: i64 = 0;
-        const O_WRONLY: i64 = 1;
-        const O_CREAT: i64 = 64;
-        const O_TRUNC: i64 = 512;
-
-        // Socket constants (Linux-compatible values)
-        const AF_INET: i64 = 2;
-        const SOCK_STREAM: i64 = 1;
-
-        match hostcall_num {
-            HOSTCALL_OPEN => {
-                if args.len() != 2 {
-                    return Err(format!(
-                        "open hostcall expects 2 arguments, got {}",
-                        args.len()
-                    ));
-                }
+        // Close fds whose handle objects were swept
+        let finalized = std::mem::take(&mut *self.finalized_fds.borrow_mut());
+        for fd in finalized {
+            self.fd_handles.remove(&fd);
+            self.close_descriptor(fd);
+        }
 
-                // Get path string
-                let path_ref = match &args[0] {
-                    Value::Ref(r) => *r,
-                    _ => return Err("open: path must be a string".to_string()),
-                };
-                let path = self.ref_to_rust_string(path_ref)?;
+        // Release the cells of swept atomics
+        let finalized = std::mem::take(&mut *self.finalized_atomics.borrow_mut());
+        for index in finalized {
+            self.atomics[index] = None;
+            self.free_atomics.push(index);
+        }
+    }
 
-                // Get flags
-                let flags = args[1]
-                    .as_i64()
-                    .ok_or_else(|| "open: flags must be an integer".to_string())?;
+    /// Drop `fd` from the descriptor tables, closing the underlying file or
+    /// socket. Returns whether `fd` was open.
+    fn close_descriptor(&mut self, fd: i64) -> bool {
+        if let Some(handle) = self.fd_handles.remove(&fd) {
+            self.heap.clear_finalizer(handle);
+        }
+        let closed = self.file_descriptors.remove(&fd).is_some()
+            || self.socket_descriptors.remove(&fd).is_some()
+            || self.pending_sockets.remove(&fd)
+            || self.listener_descriptors.remove(&fd).is_some();
+        if closed {
+            self.free_fds.insert(fd);
+        }
+        closed
+    }
 
-                // Build OpenOptions based on flags
-                let mut options = OpenOptions::new();
+    /// Pick the fd number for a newly opened descriptor: the lowest closed
+    /// one if any, otherwise a fresh one.
+    fn alloc_fd(&mut self) -> i64 {
+        self.free_fds.pop_first().unwrap_or_else(|| {
+            let fd = self.next_fd;
+            self.next_fd += 1;
+            fd
+        })
+    }
 
-                // O_RDONLY (0) means read-only if O_WRONLY is not set
-                if flags & O_WRONLY != 0 {
-                    options.write(true);
-                } else {
-                    // O_RDONLY: read-only mode
-                    options.read(true);
-                }
-                let _ = O_RDONLY; // suppress unused warning
-                if flags & O_CREAT != 0 {
-                    options.create(true);
-                }
-                if flags & O_TRUNC != 0 {
-                    options.truncate(true);
-                }
+    fn is_open_descriptor(&self, fd: i64) -> bool {
+        self.file_descriptors.contains_key(&fd)
+            || self.socket_descriptors.contains_key(&fd)
+            || self.pending_sockets.contains(&fd)
+            || self.listener_descriptors.contains_key(&fd)
+    }
 
-                // Try to open the file
-                match options.open(&path) {
-                    Ok(file) => {
-                        let fd = self.next_fd;
-                        self.next_fd += 1;
-                        self.file_descriptors.insert(fd, file);
-                        Ok(Value::I64(fd))
-                    }
-                    Err(e) => {
-                        // Map IO errors to our error codes
-                        let error_code = match e.kind() {
-                            std::io::ErrorKind::NotFound => ENOENT,
-                            std::io::ErrorKind::PermissionDenied => EACCES,
-                            _ => EBADF,
-                        };
-                        Ok(Value::I64(error_code))
-                    }
-                }
+    /// Add references held in active JIT frames to `roots`.
+    ///
+    /// Slots are chosen by each frame's stack map. JIT frame slots can hold
+    /// stale or uninitialized payloads, so only values pointing at an object
+    /// header are kept.
+    #[cfg(all(any(target_arch = "aarch64", target_arch = "x86_64"), feature = "jit"))]
+    fn collect_jit_frame_roots(&self, roots: &mut Vec<Value>) {
+        if self.jit_frames.is_null() {
+            return;
+        }
+        let mut payloads = Vec::new();
+        // SAFETY: records are unlinked before their frames are released, so
+        // every record reachable from the head points to live frame memory.
+        unsafe { JitFrameRecord::collect_ref_payloads(self.jit_frames, &mut payloads) };
+
+        for payload in payloads {
+            let r = GcRef {
+                index: payload as usize,
+            };
+            if self.heap.is_object_header(r) {
+                roots.push(Value::Ref(r));
             }
-            HOSTCALL_CLOSE => {
-                if args.len() != 1 {
-                    return Err(format!(
-                        "close hostcall expects 1 argument, got {}",
-                        args.len()
-                    ));
-                }
+        }
+    }
 
-                let fd = args[0]
-                    .as_i64()
-                    .ok_or_else(|| "close: fd must be an integer".to_string())?;
+    /// Handle hostcall instructions by dispatching through `HOSTCALLS`
+    fn handle_hostcall(&mut self, hostcall_num: usize, args: &[Value]) -> Result<Value, String> {
+        match HOSTCALLS.get(hostcall_num).copied().flatten() {
+            Some(hostcall) => hostcall(self, args),
+            None => Err(format!("unknown hostcall: {}", hostcall_num)),
+        }
+    }
 
-                // Cannot close stdin/stdout/stderr
-                if fd <= 2 {
-                    return Ok(Value::I64(EBADF));
-                }
+    /// open(path, flags) -> fd, or a negative error code
+    fn hostcall_open(&mut self, args: &[Value]) -> Result<Value, String> {
+        if args.len() != 2 {
+            return Err(format!(
+                "open hostcall expects 2 arguments, got {}",
+                args.len()
+            ));
+        }
 
-                // Remove from fd table (File/TcpStream/TcpListener is dropped automatically)
-                let closed = self.file_descriptors.remove(&fd).is_some()
-                    || self.socket_descriptors.remove(&fd).is_some()
-                    || self.pending_sockets.remove(&fd)
-                    || self.listener_descriptors.remove(&fd).is_some();
+        // Get path string
+        let path_ref = match &args[0] {
+            Value::Ref(r) => *r,
+            _ => return Err("open: path must be a string".to_string()),
+        };
+        let path = self.ref_to_rust_string(path_ref)?;
 
-                if closed {
-                    Ok(Value::I64(0)) // Success
-                } else {
-                    Ok(Value::I64(EBADF)) // Invalid fd
-                }
-            }
-            HOSTCALL_WRITE => {
-                if args.len() != 3 {
-                    return Err(format!(
-                        "write hostcall expects 3 arguments, got {}",
-                        args.len()
-                    ));
-                }
+        // Get flags
+        let flags = args[1]
+            .as_i64()
+            .ok_or_else(|| "open: flags must be an integer".to_string())?;
 
-                let fd = args[0]
-                    .as_i64()
-                    .ok_or_else(|| "write: fd must be an integer".to_string())?;
-                let data_ref = match &args[1] {
-                    Value::Ref(r) => *r,
-                    _ => return Err("write: buf must be a reference".to_string()),
-                };
-                let count = args[2]
-                    .as_i64()
-                    .ok_or_else(|| "write: count must be an integer".to_string())?;
+        // Build OpenOptions based on flags
+        let mut options = OpenOptions::new();
 
-                // Read directly from data buffer (caller passes raw data ref, not string struct)
-                let data = self
-                    .heap
-                    .get(data_ref)
-                    .ok_or_else(|| "write: invalid data reference".to_string())?;
-                let actual_count = (count as usize).min(data.slots.len());
-
-                // Get the writer for this fd
-                let writer: &mut dyn std::io::Write = if fd == 1 {
-                    &mut self.output
-                } else if fd == 2 {
-                    &mut self.stderr
-                } else if let Some(file) = self.file_descriptors.get_mut(&fd) {
-                    file
-                } else if let Some(socket) = self.socket_descriptors.get_mut(&fd) {
-                    socket
-                } else {
-                    return Ok(Value::I64(EBADF));
-                };
+        // O_RDONLY (0) means read-only if O_WRONLY is not set
+        if flags & O_WRONLY != 0 {
+            options.write(true);
+        } else {
+            // O_RDONLY: read-only mode
+            options.read(true);
+        }
+        let _ = O_RDONLY; // suppress unused warning
+        if flags & O_CREAT != 0 {
+            options.create(true);
+        }
+        if flags & O_TRUNC != 0 {
+            options.truncate(true);
+        }
 
-                // Convert slots to bytes and write
-                let bytes: Vec<u8> = data
-                    .slots
-                    .iter()
-                    .take(actual_count)
-                    .map(|v| v.as_i64().unwrap_or(0) as u8)
-                    .collect();
-                let result = if writer.write_all(&bytes).is_err() {
-                    EBADF
-                } else {
-                    actual_count as i64
+        // Try to open the file
+        match options.open(&path) {
+            Ok(file) => {
+                let fd = self.alloc_fd();
+                self.file_descriptors.insert(fd, file);
+                Ok(Value::I64(fd))
+            }
+            Err(e) => {
+                // Map IO errors to our error codes
+                let error_code = match e.kind() {
+                    std::io::ErrorKind::NotFound => ENOENT,
+                    std::io::ErrorKind::PermissionDenied => EACCES,
+                    _ => EBADF,
                 };
-
-                Ok(Value::I64(result))
+                Ok(Value::I64(error_code))
             }
-            HOSTCALL_READ => {
-                if args.len() != 2 {
-                    return Err(format!(
-                        "read hostcall expects 2 arguments, got {}",
-                        args.len()
-                    ));
-                }
+        }
+    }
 
-                let fd = args[0]
-                    .as_i64()
-                    .ok_or_else(|| "read: fd must be an integer".to_string())?;
-                let count = args[1]
-                    .as_i64()
-                    .ok_or_else(|| "read: count must be an integer".to_string())?;
+    /// close(fd) -> 0 on success
+    fn hostcall_close(&mut self, args: &[Value]) -> Result<Value, String> {
+        if args.len() != 1 {
+            return Err(format!(
+                "close hostcall expects 1 argument, got {}",
+                args.len()
+            ));
+        }
 
-                // Validate arguments
-                if fd <= 2 || count < 0 {
-                    return Ok(Value::I64(EBADF));
-                }
+        let fd = args[0]
+            .as_i64()
+            .ok_or_else(|| "close: fd must be an integer".to_string())?;
 
-                // Read up to count bytes from file or socket
-                let mut buffer = vec![0u8; count as usize];
-                let bytes_read = if let Some(file) = self.file_descriptors.get_mut(&fd) {
-                    match file.read(&mut buffer) {
-                        Ok(n) => n,
-                        Err(_) => return Ok(Value::I64(EBADF)),
-                    }
-                } else if let Some(socket) = self.socket_descriptors.get_mut(&fd) {
-                    match socket.read(&mut buffer) {
-                        Ok(n) => n,
-                        Err(_) => return Ok(Value::I64(EBADF)),
-                    }
-                } else {
-                    return Ok(Value::I64(EBADF));
-                };
+        // Cannot close stdin/stdout/stderr
+        if fd <= 2 {
+            return Ok(Value::I64(EBADF));
+        }
 
-                // Truncate buffer to actual bytes read
-                buffer.truncate(bytes_read);
+        // Remove from fd table (File/TcpStream/TcpListener is dropped automatically)
+        if self.close_descriptor(fd) {
+            Ok(Value::I64(0)) // Success
+        } else {
+            Ok(Value::I64(EBADF)) // Invalid fd
+        }
+    }
 
-                // Convert to string (assuming UTF-8)
-                let content = match String::from_utf8(buffer) {
-                    Ok(s) => s,
-                    Err(e) => {
-                        // Fall back to lossy conversion for non-UTF8 data
-                        String::from_utf8_lossy(&e.into_bytes()).into_owned()
-                    }
-                };
+    /// write(fd, buf, count) -> bytes_written
+    fn hostcall_write(&mut self, args: &[Value]) -> Result<Value, String> {
+        if args.len() != 3 {
+            return Err(format!(
+                "write hostcall expects 3 arguments, got {}",
+                args.len()
+            ));
+        }
+
+        let fd = args[0]
+            .as_i64()
+            .ok_or_else(|| "write: fd must be an integer".to_string())?;
+        let data_ref = match &args[1] {
+            Value::Ref(r) => *r,
+            _ => return Err("write: buf must be a reference".to_string()),
+        };
+        let count = args[2]
+            .as_i64()
+            .ok_or_else(|| "write: count must be an integer".to_string())?;
+
+        // Read directly from data buffer (caller passes raw data ref, not string struct)
+        let data = self
+            .heap
+            .get(data_ref)
+            .ok_or_else(|| "write: invalid data reference".to_string())?;
+        let actual_count = (count as usize).min(data.slots.len());
+
+        // Get the writer for this fd
+        let writer: &mut dyn std::io::Write = if fd == 1 {
+            &mut self.output
+        } else if fd == 2 {
+            &mut self.stderr
+        } else if let Some(file) = self.file_descriptors.get_mut(&fd) {
+            file
+        } else if let Some(socket) = self.socket_descriptors.get_mut(&fd) {
+            socket
+        } else {
+            return Ok(Value::I64(EBADF));
+        };
 
-                // Allocate string on heap and return reference
-                let heap_ref = self.heap.alloc_string(content)?;
-                Ok(Value::Ref(heap_ref))
+        // Convert slots to bytes and write
+        let bytes: Vec<u8> = data
+            .slots
+            .iter()
+            .take(actual_count)
+            .map(|v| v.as_i64().unwrap_or(0) as u8)
+            .collect();
+        let result = match writer.write_all(&bytes) {
+            Ok(()) => actual_count as i64,
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                ) =>
+            {
+                EAGAIN
             }
-            HOSTCALL_SOCKET => {
-                if args.len() != 2 {
-                    return Err(format!(
-                        "socket hostcall expects 2 arguments, got {}",
-                        args.len()
-                    ));
-                }
+            Err(_) => EBADF,
+        };
 
-                let domain = args[0]
-                    .as_i64()
-                    .ok_or_else(|| "socket: domain must be an integer".to_string())?;
-                let sock_type = args[1]
-                    .as_i64()
-                    .ok_or_else(|| "socket: type must be an integer".to_string())?;
+        Ok(Value::I64(result))
+    }
 
-                // Only support AF_INET (2)
-                if domain != AF_INET {
-                    return Ok(Value::I64(EAFNOSUPPORT));
-                }
+    /// read(fd, count) -> string (heap ref) or error
+    fn hostcall_read(&mut self, args: &[Value]) -> Result<Value, String> {
+        if args.len() != 2 {
+            return Err(format!(
+                "read hostcall expects 2 arguments, got {}",
+                args.len()
+            ));
+        }
+
+        let fd = args[0]
+            .as_i64()
+            .ok_or_else(|| "read: fd must be an integer".to_string())?;
+        let count = args[1]
+            .as_i64()
+            .ok_or_else(|| "read: count must be an integer".to_string())?;
+
+        // Validate arguments
+        if fd <= 2 || count < 0 {
+            return Ok(Value::I64(EBADF));
+        }
 
-                // Only support SOCK_STREAM (1) for TCP
-                if sock_type != SOCK_STREAM {
-                    return Ok(Value::I64(ESOCKTNOSUPPORT));
+        // Read up to count bytes from file or socket
+        let mut buffer = vec![0u8; count as usize];
+        let bytes_read = if let Some(file) = self.file_descriptors.get_mut(&fd) {
+            match file.read(&mut buffer) {
+                Ok(n) => n,
+                Err(_) => return Ok(Value::I64(EBADF)),
+            }
+        } else if let Some(socket) = self.socket_descriptors.get_mut(&fd) {
+            match socket.read(&mut buffer) {
+                Ok(n) => n,
+                // Read timeout elapsed, or no data on a non-blocking socket
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    return Ok(Value::I64(EAGAIN));
                 }
+                Err(_) => return Ok(Value::I64(EBADF)),
+            }
+        } else {
+            return Ok(Value::I64(EBADF));
+        };
 
-                // Allocate fd and mark as pending socket
-                let fd = self.next_fd;
-                self.next_fd += 1;
-                self.pending_sockets.insert(fd);
+        // Truncate buffer to actual bytes read
+        buffer.truncate(bytes_read);
 
-                Ok(Value::I64(fd))
+        // Convert to string (assuming UTF-8)
+        let content = match String::from_utf8(buffer) {
+            Ok(s) => s,
+            Err(e) => {
+                // Fall back to lossy conversion for non-UTF8 data
+                String::from_utf8_lossy(&e.into_bytes()).into_owned()
             }
-            HOSTCALL_CONNECT => {
-                if args.len() != 3 {
-                    return Err(format!(
-                        "connect hostcall expects 3 arguments, got {}",
-                        args.len()
-                    ));
-                }
+        };
 
-                let fd = args[0]
-                    .as_i64()
-                    .ok_or_else(|| "connect: fd must be an integer".to_string())?;
+        // Allocate string on heap and return reference
+        let heap_ref = self.heap.alloc_string(content)?;
+        Ok(Value::Ref(heap_ref))
+    }
 
-                // Get host string
-                let host_ref = match &args[1] {
-                    Value::Ref(r) => *r,
-                    _ => return Err("connect: host must be a string".to_string()),
-                };
-                let host = self.ref_to_rust_string(host_ref)?;
+    /// socket(domain, type) -> fd
+    fn hostcall_socket(&mut self, args: &[Value]) -> Result<Value, String> {
+        if args.len() != 2 {
+            return Err(format!(
+                "socket hostcall expects 2 arguments, got {}",
+                args.len()
+            ));
+        }
 
-                let port = args[2]
-                    .as_i64()
-                    .ok_or_else(|| "connect: port must be an integer".to_string())?;
+        let domain = args[0]
+            .as_i64()
+            .ok_or_else(|| "socket: domain must be an integer".to_string())?;
+        let sock_type = args[1]
+            .as_i64()
+            .ok_or_else(|| "socket: type must be an integer".to_string())?;
 
-                // Check fd is a pending socket
-                if !self.pending_sockets.remove(&fd) {
-                    return Ok(Value::I64(EBADF));
-                }
+        // Only support AF_INET (2)
+        if domain != AF_INET {
+            return Ok(Value::I64(EAFNOSUPPORT));
+        }
 
-                // Try to connect
-                let addr = format!("{}:{}", host, port);
-                match TcpStream::connect(&addr) {
-                    Ok(stream) => {
-                        self.socket_descriptors.insert(fd, stream);
-                        Ok(Value::I64(0)) // Success
-                    }
-                    Err(e) => {
-                        // Map IO errors to our error codes
-                        let error_code = match e.kind() {
-                            std::io::ErrorKind::ConnectionRefused => ECONNREFUSED,
-                            std::io::ErrorKind::TimedOut => ETIMEDOUT,
-                            std::io::ErrorKind::NotFound => ENOENT,
-                            std::io::ErrorKind::PermissionDenied => EACCES,
-                            _ => ECONNREFUSED, // Default to connection refused
-                        };
-                        Ok(Value::I64(error_code))
-                    }
-                }
-            }
-            HOSTCALL_BIND => {
-                if args.len() != 3 {
-                    return Err(format!(
-                        "bind hostcall expects 3 arguments, got {}",
-                        args.len()
-                    ));
-                }
+        // Only support SOCK_STREAM (1) for TCP
+        if sock_type != SOCK_STREAM {
+            return Ok(Value::I64(ESOCKTNOSUPPORT));
+        }
 
-                let fd = args[0]
-                    .as_i64()
-                    .ok_or_else(|| "bind: fd must be an integer".to_string())?;
+        // Allocate fd and mark as pending socket
+        let fd = self.alloc_fd();
+        self.pending_sockets.insert(fd);
 
-                // Get host string
-                let host_ref = match &args[1] {
-                    Value::Ref(r) => *r,
-                    _ => return Err("bind: host must be a string".to_string()),
-                };
-                let host = self.ref_to_rust_string(host_ref)?;
+        Ok(Value::I64(fd))
+    }
 
-                let port = args[2]
-                    .as_i64()
-                    .ok_or_else(|| "bind: port must be an integer".to_string())?;
+    /// connect(fd, host, port) -> 0 on success
+    fn hostcall_connect(&mut self, args: &[Value]) -> Result<Value, String> {
+        if args.len() != 3 {
+            return Err(format!(
+                "connect hostcall expects 3 arguments, got {}",
+                args.len()
+            ));
+        }
 
-                // Check fd is a pending socket
-                if !self.pending_sockets.remove(&fd) {
-                    return Ok(Value::I64(EBADF));
-                }
+        let fd = args[0]
+            .as_i64()
+            .ok_or_else(|| "connect: fd must be an integer".to_string())?;
 
-                // Try to bind (creates TcpListener)
-                let addr = format!("{}:{}", host, port);
-                match TcpListener::bind(&addr) {
-                    Ok(listener) => {
-                        self.listener_descriptors.insert(fd, listener);
-                        Ok(Value::I64(0)) // Success
-                    }
-                    Err(e) => {
-                        // Map IO errors to our error codes
-                        let error_code = match e.kind() {
-                            std::io::ErrorKind::AddrInUse => EADDRINUSE,
-                            std::io::ErrorKind::PermissionDenied => EACCES,
-                            _ => EBADF,
-                        };
-                        Ok(Value::I64(error_code))
-                    }
-                }
-            }
-            HOSTCALL_LISTEN => {
-                if args.len() != 2 {
-                    return Err(format!(
-                        "listen hostcall expects 2 arguments, got {}",
-                        args.len()
-                    ));
-                }
+        // Get host string
+        let host_ref = match &args[1] {
+            Value::Ref(r) => *r,
+            _ => return Err("connect: host must be a string".to_string()),
+        };
+        let host = self.ref_to_rust_string(host_ref)?;
 
-                let fd = args[0]
-                    .as_i64()
-                    .ok_or_else(|| "listen: fd must be an integer".to_string())?;
+        let port = args[2]
+            .as_i64()
+            .ok_or_else(|| "connect: port must be an integer".to_string())?;
 
-                let _backlog = args[1]
-                    .as_i64()
-                    .ok_or_else(|| "listen: backlog must be an integer".to_string())?;
+        // Check fd is a pending socket
+        if !self.pending_sockets.remove(&fd) {
+            return Ok(Value::I64(EBADF));
+        }
 
-                // Check fd is a valid listener (already listening after bind in Rust)
-                if self.listener_descriptors.contains_key(&fd) {
-                    Ok(Value::I64(0)) // Success - already listening
-                } else {
-                    Ok(Value::I64(EBADF)) // Not a valid listener
-                }
+        // Try to connect
+        let addr = format!("{}:{}", host, port);
+        match TcpStream::connect(&addr) {
+            Ok(stream) => {
+                self.socket_descriptors.insert(fd, stream);
+                Ok(Value::I64(0)) // Success
             }
-            HOSTCALL_ACCEPT => {
-                if args.len() != 1 {
-                    return Err(format!(
-                        "accept hostcall expects 1 argument, got {}",
-                        args.len()
-                    ));
-                }
+            Err(e) => {
+                // Map IO errors to our error codes
+                let error_code = match e.kind() {
+                    std::io::ErrorKind::ConnectionRefused => ECONNREFUSED,
+                    std::io::ErrorKind::TimedOut => ETIMEDOUT,
+                    std::io::ErrorKind::NotFound => ENOENT,
+                    std::io::ErrorKind::PermissionDenied => EACCES,
+                    _ => ECONNREFUSED, // Default to connection refused
+                };
+                Ok(Value::I64(error_code))
+            }
+        }
+    }
 
-                let fd = args[0]
-                    .as_i64()
-                    .ok_or_else(|| "accept: fd must be an integer".to_string())?;
+    /// bind(fd, host, port) -> 0 on success
+    fn hostcall_bind(&mut self, args: &[Value]) -> Result<Value, String> {
+        if args.len() != 3 {
+            return Err(format!(
+                "bind hostcall expects 3 arguments, got {}",
+                args.len()
+            ));
+        }
 
-                // Get the listener
-                let listener = match self.listener_descriptors.get(&fd) {
-                    Some(l) => l,
-                    None => return Ok(Value::I64(EBADF)),
-                };
+        let fd = args[0]
+            .as_i64()
+            .ok_or_else(|| "bind: fd must be an integer".to_string())?;
 
-                // Accept a connection
-                match listener.accept() {
-                    Ok((stream, _addr)) => {
-                        let client_fd = self.next_fd;
-                        self.next_fd += 1;
-                        self.socket_descriptors.insert(client_fd, stream);
-                        Ok(Value::I64(client_fd))
-                    }
-                    Err(_) => Ok(Value::I64(EBADF)),
-                }
-            }
-            HOSTCALL_TIME => {
-                if !args.is_empty() {
-                    return Err(format!(
-                        "time hostcall expects 0 arguments, got {}",
-                        args.len()
-                    ));
-                }
+        // Get host string
+        let host_ref = match &args[1] {
+            Value::Ref(r) => *r,
+            _ => return Err("bind: host must be a string".to_string()),
+        };
+        let host = self.ref_to_rust_string(host_ref)?;
 
-                let duration = std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .map_err(|e| format!("time hostcall failed: {}", e))?;
-                Ok(Value::I64(duration.as_secs() as i64))
-            }
-            HOSTCALL_TIME_NANOS => {
-                if !args.is_empty() {
-                    return Err(format!(
-                        "time_nanos hostcall expects 0 arguments, got {}",
-                        args.len()
-                    ));
-                }
+        let port = args[2]
+            .as_i64()
+            .ok_or_else(|| "bind: port must be an integer".to_string())?;
 
-                let duration = std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .map_err(|e| format!("time_nanos hostcall failed: {}", e))?;
-                Ok(Value::I64(duration.as_nanos() as i64))
-            }
-            _ => Err(format!("unknown hostcall: {}", hostcall_num)),
+        // Check fd is a pending socket
+        if !self.pending_sockets.remove(&fd) {
+            return Ok(Value::I64(EBADF));
         }
-    }
-}
 
-/// JIT call helper function.
+        // Try to bind (creates TcpListener)
+        let addr = format!("{}:{}", host, port);
+        match TcpListener::bind(&addr) {
+            Ok(listener) => {
+                self.listener_descriptors.insert(fd, listener);
+                Ok(Value::I64(0)) // Success
+            }
+            Err(e) => {
+                // Map IO errors to our error codes
+                let error_code = match e.kind() {
+                    std::io::ErrorKind::AddrInUse => EADDRINUSE,
+                    std::io::ErrorKind::PermissionDenied => EACCES,
+                    _ => EBADF,
+                };
+                Ok(Value::I64(error_code))
+            }
+        }
+    }
+
+    /// listen(fd, backlog) -> 0 on success
+    fn hostcall_listen(&mut self, args: &[Value]) -> Result<Value, String> {
+        if args.len() != 2 {
+            return Err(format!(
+                "listen hostcall expects 2 arguments, got {}",
+                args.len()
+            ));
+        }
+
+        let fd = args[0]
+            .as_i64()
+            .ok_or_else(|| "listen: fd must be an integer".to_string())?;
+
+        let _backlog = args[1]
+            .as_i64()
+            .ok_or_else(|| "listen: backlog must be an integer".to_string())?;
+
+        // Check fd is a valid listener (already listening after bind in Rust)
+        if self.listener_descriptors.contains_key(&fd) {
+            Ok(Value::I64(0)) // Success - already listening
+        } else {
+            Ok(Value::I64(EBADF)) // Not a valid listener
+        }
+    }
+
+    /// accept(fd) -> fd of the accepted connection
+    fn hostcall_accept(&mut self, args: &[Value]) -> Result<Value, String> {
+        if args.len() != 1 {
+            return Err(format!(
+                "accept hostcall expects 1 argument, got {}",
+                args.len()
+            ));
+        }
+
+        let fd = args[0]
+            .as_i64()
+            .ok_or_else(|| "accept: fd must be an integer".to_string())?;
+
+        // Get the listener
+        let listener = match self.listener_descriptors.get(&fd) {
+            Some(l) => l,
+            None => return Ok(Value::I64(EBADF)),
+        };
+
+        // Accept a connection
+        match listener.accept() {
+            Ok((stream, _addr)) => {
+                let client_fd = self.alloc_fd();
+                self.socket_descriptors.insert(client_fd, stream);
+                Ok(Value::I64(client_fd))
+            }
+            // No pending connection on a non-blocking listener
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => Ok(Value::I64(EAGAIN)),
+            Err(_) => Ok(Value::I64(EBADF)),
+        }
+    }
+
+    /// close_on_collect(handle, fd) -> 0; closes fd once handle is collected
+    fn hostcall_close_on_collect(&mut self, args: &[Value]) -> Result<Value, String> {
+        if args.len() != 2 {
+            return Err(format!(
+                "close_on_collect hostcall expects 2 arguments, got {}",
+                args.len()
+            ));
+        }
+
+        let handle = args[0]
+            .as_ref()
+            .ok_or_else(|| "close_on_collect: handle must be a reference".to_string())?;
+        let fd = args[1]
+            .as_i64()
+            .ok_or_else(|| "close_on_collect: fd must be an integer".to_string())?;
+
+        if fd <= 2 || !self.is_open_descriptor(fd) {
+            return Ok(Value::I64(EBADF));
+        }
+
+        // The finalizer only records the fd; closing it needs the
+        // descriptor tables, so it happens once the GC cycle is over
+        let finalized = Rc::clone(&self.finalized_fds);
+        self.heap
+            .set_finalizer(handle, Box::new(move || finalized.borrow_mut().push(fd)));
+        if let Some(old) = self.fd_handles.insert(fd, handle)
+            && old != handle
+        {
+            self.heap.clear_finalizer(old);
+        }
+        Ok(Value::I64(0))
+    }
+
+    /// set_read_timeout(fd, millis) / set_write_timeout(fd, millis) -> 0 on success
+    fn hostcall_set_timeout(
+        &mut self,
+        hostcall_num: usize,
+        args: &[Value],
+    ) -> Result<Value, String> {
+        let name = if hostcall_num == HOSTCALL_SET_READ_TIMEOUT {
+            "set_read_timeout"
+        } else {
+            "set_write_timeout"
+        };
+        if args.len() != 2 {
+            return Err(format!(
+                "{} hostcall expects 2 arguments, got {}",
+                name,
+                args.len()
+            ));
+        }
+
+        let fd = args[0]
+            .as_i64()
+            .ok_or_else(|| format!("{}: fd must be an integer", name))?;
+        let millis = args[1]
+            .as_i64()
+            .ok_or_else(|| format!("{}: millis must be an integer", name))?;
+
+        let Some(socket) = self.socket_descriptors.get(&fd) else {
+            return Ok(Value::I64(EBADF));
+        };
+        // 0 or less clears the timeout (block indefinitely)
+        let timeout = (millis > 0).then(|| std::time::Duration::from_millis(millis as u64));
+        let result = if hostcall_num == HOSTCALL_SET_READ_TIMEOUT {
+            socket.set_read_timeout(timeout)
+        } else {
+            socket.set_write_timeout(timeout)
+        };
+        Ok(Value::I64(if result.is_ok() { 0 } else { EBADF }))
+    }
+
+    /// set_nonblocking(fd, enabled) -> 0 on success
+    fn hostcall_set_nonblocking(&mut self, args: &[Value]) -> Result<Value, String> {
+        if args.len() != 2 {
+            return Err(format!(
+                "set_nonblocking hostcall expects 2 arguments, got {}",
+                args.len()
+            ));
+        }
+
+        let fd = args[0]
+            .as_i64()
+            .ok_or_else(|| "set_nonblocking: fd must be an integer".to_string())?;
+        let nonblocking = args[1].is_truthy();
+
+        let result = if let Some(socket) = self.socket_descriptors.get(&fd) {
+            socket.set_nonblocking(nonblocking)
+        } else if let Some(listener) = self.listener_descriptors.get(&fd) {
+            listener.set_nonblocking(nonblocking)
+        } else {
+            return Ok(Value::I64(EBADF));
+        };
+        Ok(Value::I64(if result.is_ok() { 0 } else { EBADF }))
+    }
+
+    /// time() -> epoch seconds
+    fn hostcall_time(&mut self, args: &[Value]) -> Result<Value, String> {
+        if !args.is_empty() {
+            return Err(format!(
+                "time hostcall expects 0 arguments, got {}",
+                args.len()
+            ));
+        }
+
+        let duration = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_err(|e| format!("time hostcall failed: {}", e))?;
+        Ok(Value::I64(duration.as_secs() as i64))
+    }
+
+    /// time_nanos() -> epoch nanoseconds
+    fn hostcall_time_nanos(&mut self, args: &[Value]) -> Result<Value, String> {
+        if !args.is_empty() {
+            return Err(format!(
+                "time_nanos hostcall expects 0 arguments, got {}",
+                args.len()
+            ));
+        }
+
+        let duration = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_err(|e| format!("time_nanos hostcall failed: {}", e))?;
+        Ok(Value::I64(duration.as_nanos() as i64))
+    }
+
+    /// format(template, args) -> string
+    fn hostcall_format(&mut self, args: &[Value]) -> Result<Value, String> {
+        if args.len() != 2 {
+            return Err(format!(
+                "format hostcall expects 2 arguments, got {}",
+                args.len()
+            ));
+        }
+
+        let template_ref = match &args[0] {
+            Value::Ref(r) => *r,
+            _ => return Err("format: template must be a string".to_string()),
+        };
+        let template = self.ref_to_rust_string(template_ref)?;
+
+        // Args is an array struct [data, len]
+        let args_ref = args[1].as_ref().ok_or("format: args must be an array")?;
+        let (data_ref, len) = match (
+            self.heap.read_slot(args_ref, 0),
+            self.heap.read_slot(args_ref, 1),
+        ) {
+            (Some(Value::Ref(data)), Some(Value::I64(len))) => (Some(data), len as usize),
+            (Some(Value::Null), Some(Value::I64(0))) => (None, 0),
+            _ => return Err("format: args must be an array".to_string()),
+        };
+        let mut values = Vec::with_capacity(len);
+        for i in 0..len {
+            let value = data_ref
+                .and_then(|data| self.heap.read_slot(data, i))
+                .ok_or("format: invalid args array")?;
+            values.push(value);
+        }
+
+        let formatted = self.format_template(&template, &values)?;
+        let heap_ref = self.heap.alloc_string(formatted)?;
+        Ok(Value::Ref(heap_ref))
+    }
+
+    /// memo_get(key) -> value memoized for key's object, or nil
+    fn hostcall_memo_get(&mut self, args: &[Value]) -> Result<Value, String> {
+        if args.len() != 1 {
+            return Err(format!(
+                "memo_get hostcall expects 1 argument, got {}",
+                args.len()
+            ));
+        }
+        let key = args[0]
+            .as_ref()
+            .ok_or_else(|| "memo_get: key must be a reference".to_string())?;
+        Ok(self.heap.memo_get(key).unwrap_or(Value::Null))
+    }
+
+    /// memo_set(key, value) -> nil
+    fn hostcall_memo_set(&mut self, args: &[Value]) -> Result<Value, String> {
+        if args.len() != 2 {
+            return Err(format!(
+                "memo_set hostcall expects 2 arguments, got {}",
+                args.len()
+            ));
+        }
+        let key = args[0]
+            .as_ref()
+            .ok_or_else(|| "memo_set: key must be a reference".to_string())?;
+        self.heap.memo_set(key, args[1]);
+        Ok(Value::Null)
+    }
+}
+
+// Hostcall numbers (stable: the prelude calls them by number)
+const HOSTCALL_WRITE: usize = 1;
+const HOSTCALL_OPEN: usize = 2;
+const HOSTCALL_CLOSE: usize = 3;
+const HOSTCALL_READ: usize = 4;
+const HOSTCALL_SOCKET: usize = 5;
+const HOSTCALL_CONNECT: usize = 6;
+const HOSTCALL_BIND: usize = 7;
+const HOSTCALL_LISTEN: usize = 8;
+const HOSTCALL_ACCEPT: usize = 9;
+const HOSTCALL_TIME: usize = 10;
+const HOSTCALL_TIME_NANOS: usize = 11;
+const HOSTCALL_FORMAT: usize = 12;
+const HOSTCALL_CLOSE_ON_COLLECT: usize = 13;
+const HOSTCALL_SET_READ_TIMEOUT: usize = 14;
+const HOSTCALL_SET_WRITE_TIMEOUT: usize = 15;
+const HOSTCALL_SET_NONBLOCKING: usize = 16;
+const HOSTCALL_MEMO_GET: usize = 17;
+const HOSTCALL_MEMO_SET: usize = 18;
+
+// Error codes (negative return values)
+const EBADF: i64 = -1; // Bad file descriptor
+const ENOENT: i64 = -2; // No such file or directory
+const EACCES: i64 = -3; // Permission denied
+const ECONNREFUSED: i64 = -4; // Connection refused
+const ETIMEDOUT: i64 = -5; // Connection timed out
+const EAFNOSUPPORT: i64 = -6; // Address family not supported
+const ESOCKTNOSUPPORT: i64 = -7; // Socket type not supported
+const EADDRINUSE: i64 = -8; // Address already in use
+const EAGAIN: i64 = -9; // Timed out or would block
+
+// Open flags (Linux-compatible values)
+const O_RDONLY: i64 = 0;
+const O_WRONLY: i64 = 1;
+const O_CREAT: i64 = 64;
+const O_TRUNC: i64 = 512;
+
+// Socket constants (Linux-compatible values)
+const AF_INET: i64 = 2;
+const SOCK_STREAM: i64 = 1;
+
+/// A hostcall handler, called with the hostcall's arguments.
+type Hostcall = fn(&mut VM, &[Value]) -> Result<Value, String>;
+
+/// Hostcall handlers indexed by hostcall number; `None` marks an unused number.
+const HOSTCALLS: [Option<Hostcall>; 19] = {
+    let mut table: [Option<Hostcall>; 19] = [None; 19];
+    table[HOSTCALL_WRITE] = Some(VM::hostcall_write);
+    table[HOSTCALL_OPEN] = Some(VM::hostcall_open);
+    table[HOSTCALL_CLOSE] = Some(VM::hostcall_close);
+    table[HOSTCALL_READ] = Some(VM::hostcall_read);
+    table[HOSTCALL_SOCKET] = Some(VM::hostcall_socket);
+    table[HOSTCALL_CONNECT] = Some(VM::hostcall_connect);
+    table[HOSTCALL_BIND] = Some(VM::hostcall_bind);
+    table[HOSTCALL_LISTEN] = Some(VM::hostcall_listen);
+    table[HOSTCALL_ACCEPT] = Some(VM::hostcall_accept);
+    table[HOSTCALL_TIME] = Some(VM::hostcall_time);
+    table[HOSTCALL_TIME_NANOS] = Some(VM::hostcall_time_nanos);
+    table[HOSTCALL_FORMAT] = Some(VM::hostcall_format);
+    table[HOSTCALL_CLOSE_ON_COLLECT] = Some(VM::hostcall_close_on_collect);
+    table[HOSTCALL_SET_READ_TIMEOUT] =
+        Some(|vm, args| vm.hostcall_set_timeout(HOSTCALL_SET_READ_TIMEOUT, args));
+    table[HOSTCALL_SET_WRITE_TIMEOUT] =
+        Some(|vm, args| vm.hostcall_set_timeout(HOSTCALL_SET_WRITE_TIMEOUT, args));
+    table[HOSTCALL_SET_NONBLOCKING] = Some(VM::hostcall_set_nonblocking);
+    table[HOSTCALL_MEMO_GET] = Some(VM::hostcall_memo_get);
+    table[HOSTCALL_MEMO_SET] = Some(VM::hostcall_memo_set);
+    table
+};
+
+/// JIT call helper function.
 /// This is called from JIT code when executing a Call instruction.
 /// It executes the target function via the VM and returns the result.
 #[cfg(feature = "jit")]
@@ -4307,6 +6231,9 @@ unsafe extern "C" fn jit_call_helper(
 
     // FAST PATH: If target function is JIT compiled, call directly with stack allocation
     // This avoids heap allocations and VM stack operations for recursive JIT calls.
+    if vm.jit_functions.contains_key(&func_index) {
+        vm.touch_jit_unit(JitUnit::Function(func_index));
+    }
     if let Some(compiled) = vm.jit_functions.get(&func_index) {
         // AArch64: unboxed frame (8B per slot, payload only)
         #[cfg(target_arch = "aarch64")]
@@ -4416,7 +6343,25 @@ unsafe extern "C" fn jit_call_helper(
                     }
                 }
                 Ok(ControlFlow::Exit) => break,
-                Err(_) => {
+                Err(mut e) => {
+                    // A try block opened inside the callee can still catch it
+                    if vm
+                        .try_frames
+                        .last()
+                        .is_some_and(|t| t.frame_depth > starting_frame_depth)
+                        && matches!(vm.handle_exception(&mut e, chunk), Ok(true))
+                    {
+                        continue;
+                    }
+                    // Otherwise unwind the callee and let the JIT code bail out;
+                    // the VM re-raises the error once native code has returned.
+                    vm.frames.truncate(starting_frame_depth);
+                    vm.stack.truncate(new_stack_base);
+                    vm.try_frames
+                        .retain(|t| t.frame_depth <= starting_frame_depth);
+                    vm.jit_error = Some(e);
+                    ctx_ref.pending_error = 1;
+                    ctx_ref.heap_base = vm.heap.memory_base_ptr();
                     return JitReturn { tag: 3, payload: 0 }; // TAG_NIL on error
                 }
             }
@@ -4544,15 +6489,15 @@ unsafe extern "C" fn jit_heap_alloc_dyn_simple_helper(
 
     vm.record_opcode("HeapAllocDynSimple");
 
-    let size = size as usize;
+    // Collect where the interpreter would. Compiled frames are linked for
+    // root scanning and loop registers were spilled before this call.
+    #[cfg(target_arch = "x86_64")]
+    if vm.heap.should_gc() {
+        vm.collect_garbage();
+    }
+
     let ek = ElemKind::from_raw(elem_kind_raw as u8);
-    let result = if ek.is_typed() {
-        vm.heap.alloc_typed_array(size as u32, ek)
-    } else {
-        let slots = vec![Value::Null; size];
-        vm.heap.alloc_slots(slots)
-    };
-    match result {
+    match vm.alloc_dyn_simple(size as i64, ek) {
         Ok(r) => {
             ctx_ref.heap_base = vm.heap.memory_base_ptr();
             JitReturn {
@@ -4560,10 +6505,175 @@ unsafe extern "C" fn jit_heap_alloc_dyn_simple_helper(
                 payload: r.index as u64,
             }
         }
-        Err(_) => JitReturn {
-            tag: 3, // TAG_NIL
-            payload: 0,
-        },
+        Err(e) => {
+            // The compiled code bails out and the VM re-raises the error
+            vm.jit_error = Some(e);
+            ctx_ref.pending_error = 1;
+            JitReturn {
+                tag: 3, // TAG_NIL
+                payload: 0,
+            }
+        }
+    }
+}
+
+/// How `format_value` writes strings.
+#[derive(Clone, Copy)]
+enum DisplayMode {
+    /// As `print` shows them: the characters themselves
+    Raw,
+    /// Quoted, with quotes, backslashes and control characters escaped
+    Debug,
+}
+
+/// Render `value` for inspection rather than printing: strings, including
+/// those nested in arrays, are quoted and escaped so that `"1"` and `1` or
+/// `"nil"` and `nil` can be told apart. Used by the debugger and
+/// `--eval-print`. `types` are the VM's globals, whose type descriptors name
+/// the fields of tagged structs; pass `&[]` to print structs as arrays.
+pub fn debug_string(heap: &Heap, types: &[Value], value: &Value) -> Result<String, String> {
+    format_value(heap, types, value, DisplayMode::Debug, &mut Vec::new())
+}
+
+/// Type name and field names of a struct tagged by `TypeTag`, read from its
+/// type descriptor in `types`. None for untagged objects and for descriptors
+/// whose field count doesn't match the object.
+fn struct_layout(heap: &Heap, types: &[Value], r: GcRef) -> Option<(String, Vec<String>)> {
+    let index = heap.type_tag(r)?;
+    let Value::Ref(td) = *types.get(index)? else {
+        return None;
+    };
+    let field_count = heap.read_slot(td, 2)?.as_i64()? as usize;
+    if heap.slot_count(r)? != field_count {
+        return None;
+    }
+    let fields = (0..field_count)
+        .map(|i| descriptor_string(heap, td, 3 + i))
+        .collect::<Option<Vec<_>>>()?;
+    Some((type_name(heap, types, index)?, fields))
+}
+
+/// Runtime type name of `value` for `type_name`: the VM's primitive names,
+/// `"string"` for strings, the struct's name for tagged structs, and `"ref"`
+/// for any other object.
+fn type_name_of(heap: &Heap, types: &[Value], value: &Value) -> String {
+    let Value::Ref(r) = value else {
+        return match value {
+            Value::I64(_) => "i64",
+            Value::F64(_) => "f64",
+            Value::Bool(_) => "bool",
+            _ => "nil",
+        }
+        .to_string();
+    };
+    if let Some(name) = heap.type_tag(*r).and_then(|i| type_name(heap, types, i)) {
+        return name;
+    }
+    let is_string = heap.slot_count(*r) == Some(2)
+        && matches!(heap.read_slot(*r, 0), Some(Value::Ref(data)) if heap.get_elem_kind(data) == ElemKind::U8);
+    if is_string { "string" } else { "ref" }.to_string()
+}
+
+/// Name of the type whose descriptor is `types[index]`.
+fn type_name(heap: &Heap, types: &[Value], index: usize) -> Option<String> {
+    let Value::Ref(td) = *types.get(index)? else {
+        return None;
+    };
+    descriptor_string(heap, td, 1)
+}
+
+/// The string in slot `slot` of type descriptor `td`.
+fn descriptor_string(heap: &Heap, td: GcRef, slot: usize) -> Option<String> {
+    let name = heap.read_slot(td, slot)?;
+    format_value(heap, &[], &name, DisplayMode::Raw, &mut Vec::new()).ok()
+}
+
+/// Render `value`, reading the objects it refers to from `heap`, for a value
+/// nested inside the objects in `visiting`. Tagged structs print as
+/// `Name { field: value, .. }`. An object that contains itself prints as
+/// `[...]` (or `Name { ... }`) where it recurs.
+fn format_value(
+    heap: &Heap,
+    types: &[Value],
+    value: &Value,
+    mode: DisplayMode,
+    visiting: &mut Vec<usize>,
+) -> Result<String, String> {
+    match value {
+        Value::I64(n) => Ok(n.to_string()),
+        Value::F64(f) => {
+            if f.fract() == 0.0 {
+                Ok(format!("{}.0", f))
+            } else {
+                Ok(f.to_string())
+            }
+        }
+        Value::Bool(b) => Ok(b.to_string()),
+        Value::Null => Ok("nil".to_string()),
+        Value::Ref(r) => {
+            if let Some((name, fields)) = struct_layout(heap, types, *r) {
+                if visiting.contains(&r.base()) {
+                    return Ok(format!("{} {{ ... }}", name));
+                }
+                visiting.push(r.base());
+                let mut parts = Vec::new();
+                for (i, field) in fields.iter().enumerate() {
+                    let elem = heap.read_slot(*r, i).unwrap_or(Value::Null);
+                    let text = format_value(heap, types, &elem, mode, visiting)?;
+                    parts.push(format!("{}: {}", field, text));
+                }
+                visiting.pop();
+                return Ok(if parts.is_empty() {
+                    format!("{} {{}}", name)
+                } else {
+                    format!("{} {{ {} }}", name, parts.join(", "))
+                });
+            }
+            if visiting.contains(&r.base()) {
+                return Ok("[...]".to_string());
+            }
+            let obj = heap.get(*r).ok_or("runtime error: invalid reference")?;
+
+            // Detect string vs array for 2-slot [Ref, I64] objects
+            // by checking the data array's ElemKind.
+            if obj.slots.len() == 2
+                && let (Some(data_ref), Some(len)) = (obj.slots[0].as_ref(), obj.slots[1].as_i64())
+            {
+                let len_usize = len as usize;
+                let data_ek = heap.get_elem_kind(data_ref);
+
+                if data_ek == ElemKind::U8 {
+                    // UTF-8 string: read bytes and decode
+                    let data = heap
+                        .get(data_ref)
+                        .ok_or("runtime error: invalid string data")?;
+                    let text = data.slots_to_string_len(len_usize);
+                    return Ok(match mode {
+                        DisplayMode::Raw => text,
+                        DisplayMode::Debug => format!("{:?}", text),
+                    });
+                }
+                // Otherwise display as array
+                visiting.push(r.base());
+                let mut parts = Vec::new();
+                for i in 0..len_usize {
+                    if let Some(elem) = heap.read_slot(data_ref, i) {
+                        parts.push(format_value(heap, types, &elem, mode, visiting)?);
+                    }
+                }
+                visiting.pop();
+                return Ok(format!("[{}]", parts.join(", ")));
+            }
+
+            // Fallback: show all elements as array/struct
+            visiting.push(r.base());
+            let mut parts = Vec::new();
+            for elem in obj.slots.iter() {
+                parts.push(format_value(heap, types, elem, mode, visiting)?);
+            }
+            visiting.pop();
+            Ok(format!("[{}]", parts.join(", ")))
+        }
     }
 }
 
@@ -4582,134 +6692,1145 @@ impl Default for VM {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::vm::{REMOVED_FUNCTION, TypeDescriptor};
 
     fn run_code(ops: Vec<Op>) -> Result<Vec<Value>, String> {
         let chunk = Chunk {
-            functions: vec![],
+            functions: vec![],
+            main: Function {
+                name: "__main__".to_string(),
+                arity: 0,
+                locals_count: 0,
+                code: ops,
+                stackmap: None,
+                local_types: vec![],
+            },
+            strings: vec![],
+            type_descriptors: vec![],
+            interface_descriptors: vec![],
+            const_arrays: vec![],
+            debug: None,
+            metadata: Default::default(),
+        };
+
+        let mut vm = VM::new();
+        // Use the stack-based interpreter for unit tests that check vm.stack directly
+        vm.set_use_microop(false);
+        vm.run(&chunk)?;
+        Ok(vm.stack)
+    }
+
+    fn run_code_with_strings(ops: Vec<Op>, strings: Vec<String>) -> Result<Vec<Value>, String> {
+        let chunk = Chunk {
+            functions: vec![],
+            main: Function {
+                name: "__main__".to_string(),
+                arity: 0,
+                locals_count: 0,
+                code: ops,
+                stackmap: None,
+                local_types: vec![],
+            },
+            strings,
+            type_descriptors: vec![],
+            interface_descriptors: vec![],
+            const_arrays: vec![],
+            debug: None,
+            metadata: Default::default(),
+        };
+
+        let mut vm = VM::new();
+        // Use the stack-based interpreter for unit tests that check vm.stack directly
+        vm.set_use_microop(false);
+        vm.run(&chunk)?;
+        Ok(vm.stack)
+    }
+
+    #[test]
+    fn test_push_int() {
+        let stack = run_code(vec![Op::I64Const(42)]).unwrap();
+        assert_eq!(stack, vec![Value::I64(42)]);
+    }
+
+    #[test]
+    fn test_push_float() {
+        let stack = run_code(vec![Op::F64Const(2.5)]).unwrap();
+        assert_eq!(stack, vec![Value::F64(2.5)]);
+    }
+
+    #[test]
+    fn test_push_nil() {
+        let stack = run_code(vec![Op::RefNull]).unwrap();
+        assert_eq!(stack, vec![Value::Null]);
+    }
+
+    #[test]
+    fn test_add() {
+        let stack = run_code(vec![Op::I64Const(1), Op::I64Const(2), Op::I64Add]).unwrap();
+        assert_eq!(stack, vec![Value::I64(3)]);
+    }
+
+    #[test]
+    fn test_add_float() {
+        let stack = run_code(vec![Op::F64Const(1.5), Op::F64Const(2.5), Op::F64Add]).unwrap();
+        assert_eq!(stack, vec![Value::F64(4.0)]);
+    }
+
+    #[test]
+    fn test_comparison() {
+        let stack = run_code(vec![Op::I64Const(1), Op::I64Const(2), Op::I64LtS]).unwrap();
+        assert_eq!(stack, vec![Value::Bool(true)]);
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        let result = run_code(vec![Op::I64Const(1), Op::I64Const(0), Op::I64DivS]);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("division by zero"));
+    }
+
+    #[test]
+    fn test_unsigned_division() {
+        let stack = run_code(vec![
+            Op::I64Const(-1),
+            Op::I64Const(2),
+            Op::I64DivS,
+            Op::I64Const(-1),
+            Op::I64Const(2),
+            Op::I64DivU,
+            Op::I64Const(-7),
+            Op::I64Const(3),
+            Op::I64RemU,
+        ])
+        .unwrap();
+        assert_eq!(
+            stack,
+            vec![Value::I64(0), Value::I64(i64::MAX), Value::I64(0)]
+        );
+
+        let result = run_code(vec![Op::I64Const(1), Op::I64Const(0), Op::I64RemU]);
+        assert!(result.unwrap_err().contains("division by zero"));
+    }
+
+    #[test]
+    fn test_saturating_and_checked_add() {
+        let stack = run_code(vec![
+            Op::I64Const(i64::MAX),
+            Op::I64Const(1),
+            Op::I64AddSat,
+            Op::I64Const(i64::MIN),
+            Op::I64Const(1),
+            Op::I64SubSat,
+            Op::I64Const(i64::MIN),
+            Op::I64Const(-1),
+            Op::I64MulSat,
+            Op::I64Const(i64::MAX),
+            Op::I64Const(1),
+            Op::I64AddChecked,
+            Op::I64Const(40),
+            Op::I64Const(2),
+            Op::I64AddChecked,
+        ])
+        .unwrap();
+        assert_eq!(
+            stack,
+            vec![
+                Value::I64(i64::MAX),
+                Value::I64(i64::MIN),
+                Value::I64(i64::MAX),
+                Value::Null,
+                Value::I64(42),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_assert_type() {
+        let stack = run_code(vec![Op::I64Const(7), Op::AssertType(0)]).unwrap();
+        assert_eq!(stack, vec![Value::I64(7)]);
+
+        let err = run_code(vec![Op::F64Const(1.5), Op::AssertType(0)]).unwrap_err();
+        assert_eq!(err, "runtime error: expected int, got float");
+    }
+
+    #[test]
+    fn test_locals() {
+        let stack = run_code(vec![Op::I64Const(42), Op::LocalSet(0), Op::LocalGet(0)]).unwrap();
+        assert_eq!(stack, vec![Value::I64(42), Value::I64(42)]);
+    }
+
+    #[test]
+    fn test_conditional_jump() {
+        // if false, skip push 1, else push 2
+        let stack = run_code(vec![
+            Op::I32Const(0), // false
+            Op::BrIfFalse(4),
+            Op::I64Const(1),
+            Op::Jmp(5),
+            Op::I64Const(2),
+        ])
+        .unwrap();
+        assert_eq!(stack, vec![Value::I64(2)]);
+    }
+
+    #[test]
+    fn test_array_operations() {
+        // Array<T> struct layout: [ptr, len]
+        // ptr points to data array, len is the element count
+        let stack = run_code(vec![
+            Op::I64Const(1),  // element 0
+            Op::I64Const(2),  // element 1
+            Op::I64Const(3),  // element 2
+            Op::HeapAlloc(3), // data array with 3 elements
+            Op::I64Const(3),  // length value
+            Op::HeapAlloc(2), // Array<T> struct [ptr, len]
+            Op::HeapLoad(1),  // read len field
+        ])
+        .unwrap();
+        assert_eq!(stack.len(), 1);
+        assert_eq!(stack[0], Value::I64(3));
+    }
+
+    #[test]
+    fn test_deep_eq_strings() {
+        let mut vm = VM::new();
+        let a = vm.heap.alloc_string("hello".to_string()).unwrap();
+        let b = vm.heap.alloc_string("hello".to_string()).unwrap();
+        let c = vm.heap.alloc_string("help!".to_string()).unwrap();
+        assert!(!vm.values_equal(&Value::Ref(a), &Value::Ref(b)));
+        assert!(vm.deep_equal(&Value::Ref(a), &Value::Ref(b), 1));
+        assert!(!vm.deep_equal(&Value::Ref(a), &Value::Ref(c), 1));
+    }
+
+    #[test]
+    fn test_deep_eq_arrays() {
+        let stack = run_code(vec![
+            Op::I64Const(1),
+            Op::I64Const(2),
+            Op::HeapAlloc(2),
+            Op::I64Const(2),
+            Op::HeapAlloc(2), // [1, 2]
+            Op::I64Const(1),
+            Op::I64Const(2),
+            Op::HeapAlloc(2),
+            Op::I64Const(2),
+            Op::HeapAlloc(2), // [1, 2]
+            Op::DeepEq(1),
+            Op::I64Const(1),
+            Op::HeapAlloc(1),
+            Op::I64Const(1),
+            Op::HeapAlloc(2), // [1]
+            Op::I64Const(1),
+            Op::I64Const(2),
+            Op::HeapAlloc(2),
+            Op::I64Const(2),
+            Op::HeapAlloc(2), // [1, 2]
+            Op::DeepEq(1),
+        ])
+        .unwrap();
+        assert_eq!(stack, vec![Value::Bool(true), Value::Bool(false)]);
+    }
+
+    #[test]
+    fn test_deep_eq_depth_zero_is_identity() {
+        // Two structs shaped like array headers ([nil, 0]) are still distinct
+        let stack = run_code(vec![
+            Op::RefNull,
+            Op::I64Const(0),
+            Op::HeapAlloc(2),
+            Op::RefNull,
+            Op::I64Const(0),
+            Op::HeapAlloc(2),
+            Op::DeepEq(0),
+        ])
+        .unwrap();
+        assert_eq!(stack, vec![Value::Bool(false)]);
+    }
+
+    #[test]
+    fn test_deep_eq_cycle_terminates() {
+        // Two arrays whose single element is the array itself
+        let mut vm = VM::new();
+        let make_cyclic = |vm: &mut VM| {
+            let data = vm.heap.alloc_slots(vec![Value::Null]).unwrap();
+            let header = vm
+                .heap
+                .alloc_slots(vec![Value::Ref(data), Value::I64(1)])
+                .unwrap();
+            vm.heap.write_slot(data, 0, Value::Ref(header)).unwrap();
+            header
+        };
+        let a = make_cyclic(&mut vm);
+        let b = make_cyclic(&mut vm);
+        assert!(vm.deep_equal(&Value::Ref(a), &Value::Ref(b), usize::MAX));
+        assert!(vm.deep_equal(&Value::Ref(a), &Value::Ref(a), usize::MAX));
+        // One layer deep the elements are compared by identity
+        assert!(!vm.deep_equal(&Value::Ref(a), &Value::Ref(b), 1));
+    }
+
+    #[test]
+    fn test_collected_atomics_release_their_cells() {
+        let chunk = Chunk {
+            functions: vec![],
+            main: Function {
+                name: "__main__".to_string(),
+                arity: 0,
+                locals_count: 0,
+                code: vec![
+                    Op::I64Const(1),
+                    Op::AtomicNew,
+                    Op::Drop,
+                    Op::I64Const(2),
+                    Op::AtomicNew,
+                ],
+                stackmap: None,
+                local_types: vec![],
+            },
+            strings: vec![],
+            type_descriptors: vec![],
+            interface_descriptors: vec![],
+            const_arrays: vec![],
+            debug: None,
+            metadata: Default::default(),
+        };
+
+        let mut vm = VM::new();
+        vm.set_use_microop(false);
+        vm.run(&chunk).unwrap();
+        assert_eq!(vm.atomics.len(), 2);
+
+        vm.collect_garbage();
+        assert!(vm.atomics[0].is_none());
+        assert_eq!(vm.free_atomics, vec![0]);
+        // Ints are no longer accepted as atomics
+        vm.stack.push(Value::I64(1));
+        let err = vm.pop_atomic().map(|cell| cell.load(Ordering::SeqCst));
+        assert_eq!(err, Err("runtime error: expected an atomic".to_string()));
+
+        // The live one is untouched, and the next atomic reuses the free cell
+        let live = *vm.stack.last().unwrap();
+        vm.stack.push(live);
+        assert_eq!(vm.pop_atomic().unwrap().load(Ordering::SeqCst), 2);
+        let reused = vm.alloc_atomic(Arc::new(AtomicI64::new(4))).unwrap();
+        assert_eq!(vm.heap.read_slot(reused, 0), Some(Value::I64(0)));
+    }
+
+    #[test]
+    fn test_compare_strings() {
+        let stack = run_code_with_strings(
+            vec![
+                Op::StringConst(0),
+                Op::StringConst(1),
+                Op::Compare, // "apple" vs "apples"
+                Op::StringConst(1),
+                Op::StringConst(0),
+                Op::Compare,
+                Op::StringConst(2),
+                Op::StringConst(2),
+                Op::Compare,
+                Op::StringConst(2),
+                Op::StringConst(0),
+                Op::Compare, // "" vs "apple"
+            ],
+            vec!["apple".to_string(), "apples".to_string(), "".to_string()],
+        )
+        .unwrap();
+        assert_eq!(
+            stack,
+            vec![Value::I64(-1), Value::I64(1), Value::I64(0), Value::I64(-1)]
+        );
+
+        let err = run_code_with_strings(
+            vec![Op::StringConst(0), Op::I64Const(1), Op::Compare],
+            vec!["apple".to_string()],
+        )
+        .unwrap_err();
+        assert!(err.contains("Compare expects two strings"), "{err}");
+
+        // Untyped references are never ordered by guessing their contents
+        let err = run_code_with_strings(
+            vec![Op::StringConst(0), Op::StringConst(0), Op::I64LtS],
+            vec!["apple".to_string()],
+        )
+        .unwrap_err();
+        assert!(err.contains("cannot compare `ref` and `ref`"), "{err}");
+    }
+
+    #[test]
+    fn test_unreachable_errors() {
+        let func = |name: &str, code| Function {
+            name: name.to_string(),
+            arity: 0,
+            locals_count: 0,
+            code,
+            stackmap: None,
+            local_types: vec![],
+        };
+        let chunk = Chunk {
+            functions: vec![func("f", vec![Op::Unreachable])],
+            main: func("__main__", vec![Op::Call(0, 0), Op::Drop]),
+            strings: vec![],
+            type_descriptors: vec![],
+            interface_descriptors: vec![],
+            const_arrays: vec![],
+            debug: None,
+            metadata: Default::default(),
+        };
+
+        for use_microop in [false, true] {
+            let mut vm = VM::new();
+            vm.set_use_microop(use_microop);
+            assert_eq!(vm.run(&chunk), Err(UNREACHABLE_CODE.to_string()));
+        }
+
+        #[cfg(all(target_arch = "x86_64", feature = "jit"))]
+        {
+            let mut vm = VM::new();
+            vm.set_jit_config(true, 1, false);
+            assert_eq!(vm.run(&chunk), Err(UNREACHABLE_CODE.to_string()));
+            assert!(vm.is_jit_compiled(0));
+        }
+    }
+
+    #[test]
+    fn test_local_loads_fold_into_register_adds() {
+        // let a = 1; let b = 2; let c = 4; a + b + c
+        let mut code = vec![
+            Op::I64Const(1),
+            Op::LocalSet(0),
+            Op::I64Const(2),
+            Op::LocalSet(1),
+            Op::I64Const(4),
+            Op::LocalSet(2),
+        ];
+        code.extend([
+            Op::LocalGet(0),
+            Op::LocalGet(1),
+            Op::I64Add,
+            Op::LocalGet(2),
+            Op::I64Add,
+            Op::Ret,
+        ]);
+        let chunk = Chunk {
+            functions: vec![],
+            main: Function {
+                name: "__main__".to_string(),
+                arity: 0,
+                locals_count: 3,
+                code,
+                stackmap: None,
+                local_types: vec![ValueType::I64; 3],
+            },
+            strings: vec![],
+            type_descriptors: vec![],
+            interface_descriptors: vec![],
+            const_arrays: vec![],
+            debug: None,
+            metadata: Default::default(),
+        };
+
+        // The stack interpreter dispatches every load and add on its own
+        let mut vm = VM::new();
+        vm.set_use_microop(false);
+        vm.set_profile_opcodes(true);
+        assert_eq!(vm.run_and_get_result(&chunk).unwrap(), Value::I64(7));
+        let counts = &vm.opcode_profile().counts;
+        assert_eq!(counts.get("LocalGet"), Some(&3));
+        assert_eq!(counts.get("I64Add"), Some(&2));
+        assert_eq!(vm.opcode_profile().total_instructions(), 12);
+
+        // MicroOps read the locals as register operands: three constants,
+        // two fused AddI64s and the return
+        let converted = crate::vm::microop_converter::convert(&chunk.main);
+        assert_eq!(converted.micro_ops.len(), 6);
+        let adds = converted
+            .micro_ops
+            .iter()
+            .filter(|op| matches!(op, crate::vm::microop::MicroOp::AddI64 { .. }))
+            .count();
+        assert_eq!(adds, 2);
+        let mut vm = VM::new();
+        assert_eq!(vm.run_and_get_result(&chunk).unwrap(), Value::I64(7));
+    }
+
+    #[test]
+    fn test_pick_is_bounded_by_current_frame() {
+        let func = |name: &str, arity, code| Function {
+            name: name.to_string(),
+            arity,
+            locals_count: arity,
+            code,
+            stackmap: None,
+            local_types: vec![],
+        };
+        // The caller's operand 7 sits right below `f`'s frame; Pick(2) must not reach it
+        let chunk_with = |pick| Chunk {
+            functions: vec![func("f", 1, vec![Op::I64Const(3), pick, Op::Drop, Op::Ret])],
+            main: func(
+                "__main__",
+                0,
+                vec![Op::I64Const(7), Op::I64Const(1), Op::Call(0, 1), Op::Ret],
+            ),
+            strings: vec![],
+            type_descriptors: vec![],
+            interface_descriptors: vec![],
+            const_arrays: vec![],
+            debug: None,
+            metadata: Default::default(),
+        };
+
+        for use_microop in [false, true] {
+            let mut vm = VM::new();
+            vm.set_use_microop(use_microop);
+            assert_eq!(vm.run(&chunk_with(Op::Pick(0))), Ok(()));
+
+            let mut vm = VM::new();
+            vm.set_use_microop(use_microop);
+            let err = vm.run(&chunk_with(Op::Pick(2))).unwrap_err();
+            assert!(err.contains("pick out of frame bounds"), "{err}");
+        }
+
+        let err = run_code(vec![Op::I64Const(5), Op::I64Const(1), Op::PickDyn]).unwrap_err();
+        assert!(err.contains("pick out of frame bounds"), "{err}");
+    }
+
+    #[cfg(all(target_arch = "x86_64", feature = "jit"))]
+    #[test]
+    fn test_gc_scans_jit_frames() {
+        let func = |name: &str, locals_count, local_types, code| Function {
+            name: name.to_string(),
+            arity: 0,
+            locals_count,
+            code,
+            stackmap: None,
+            local_types,
+        };
+        // `hold` keeps an array only in its JIT frame while calling `churn`,
+        // which runs in the interpreter and forces a collection.
+        let hold = func(
+            "hold",
+            1,
+            vec![ValueType::Ref],
+            vec![
+                Op::I64Const(1),
+                Op::HeapAllocDynSimple(ElemKind::I64),
+                Op::LocalSet(0),
+                Op::LocalGet(0),
+                Op::I64Const(0),
+                Op::I64Const(42),
+                Op::HeapStoreDyn(ElemKind::I64),
+                Op::Call(1, 0),
+                Op::Drop,
+                Op::LocalGet(0),
+                Op::I64Const(0),
+                Op::HeapLoadDyn(ElemKind::I64),
+                Op::Ret,
+            ],
+        );
+        let churn = func(
+            "churn",
+            0,
+            vec![],
+            vec![
+                Op::I64Const(200_000),
+                Op::HeapAllocDynSimple(ElemKind::I64),
+                Op::Drop,
+                // FreeTemp isn't JIT-compilable, so `churn` stays interpreted
+                Op::RefNull,
+                Op::FreeTemp,
+                Op::I64Const(1),
+                Op::HeapAllocDynSimple(ElemKind::I64),
+                Op::I64Const(0),
+                Op::I64Const(7),
+                Op::HeapStoreDyn(ElemKind::I64),
+                Op::I64Const(0),
+                Op::Ret,
+            ],
+        );
+        let chunk = Chunk {
+            functions: vec![hold, churn],
+            main: func("__main__", 0, vec![], vec![Op::Call(0, 0)]),
+            strings: vec![],
+            type_descriptors: vec![],
+            interface_descriptors: vec![],
+            const_arrays: vec![],
+            debug: None,
+            metadata: Default::default(),
+        };
+
+        let mut vm = VM::new();
+        vm.set_use_microop(false);
+        vm.set_jit_config(true, 1, false);
+        vm.run(&chunk).unwrap();
+        assert!(vm.is_jit_compiled(0));
+        assert!(!vm.is_jit_compiled(1));
+        assert_eq!(vm.stack, vec![Value::I64(42)]);
+    }
+
+    #[cfg(all(target_arch = "x86_64", feature = "jit"))]
+    #[test]
+    fn test_jit_code_budget_evicts_lru() {
+        // Ten functions that all get hot; main sums two calls to each, then
+        // calls f0 (evicted by then) twice more
+        let functions: Vec<Function> = (0..10)
+            .map(|k| Function {
+                name: format!("f{}", k),
+                arity: 0,
+                locals_count: 0,
+                code: vec![Op::I64Const(k + 1), Op::Ret],
+                stackmap: None,
+                local_types: vec![],
+            })
+            .collect();
+        let mut main_code = vec![Op::I64Const(0)];
+        for k in (0..10).chain([0]) {
+            main_code.extend([Op::Call(k, 0), Op::I64Add, Op::Call(k, 0), Op::I64Add]);
+        }
+        let chunk = Chunk {
+            functions,
+            main: Function {
+                name: "__main__".to_string(),
+                arity: 0,
+                locals_count: 0,
+                code: main_code,
+                stackmap: None,
+                local_types: vec![],
+            },
+            strings: vec![],
+            type_descriptors: vec![],
+            interface_descriptors: vec![],
+            const_arrays: vec![],
+            debug: None,
+            metadata: Default::default(),
+        };
+        let run = |budget: usize| {
+            let mut vm = VM::new();
+            vm.set_use_microop(false);
+            vm.set_jit_config(true, 1, false);
+            vm.set_jit_code_budget(budget);
+            vm.run(&chunk).unwrap();
+            assert_eq!(vm.stack, vec![Value::I64(2 * 55 + 2)]);
+            (vm.jit_stats().clone(), vm.is_jit_compiled(0))
+        };
+
+        let (unlimited, _) = run(DEFAULT_JIT_CODE_BUDGET);
+        assert_eq!(unlimited.functions, 10);
+        assert_eq!(unlimited.evictions, 0);
+        let per_function = unlimited.code_bytes / 10;
+
+        // Room for three functions: older ones are evicted, and f0 is
+        // compiled again once it gets hot after its eviction
+        let budget = per_function * 3;
+        let (limited, f0_compiled) = run(budget);
+        assert_eq!(limited.functions, 11);
+        assert_eq!(limited.evictions, 8);
+        assert!(limited.code_bytes <= budget);
+        assert!(!limited.budget_exhausted);
+        assert!(f0_compiled);
+
+        // Too small for any function: compilation is refused, and everything
+        // keeps running in the interpreter
+        let (starved, _) = run(per_function - 1);
+        assert_eq!(starved.functions, 0);
+        assert!(starved.budget_exhausted);
+        assert_eq!(starved.code_bytes, 0);
+    }
+
+    fn chunk_with_functions(functions: Vec<Function>, main_code: Vec<Op>) -> Chunk {
+        Chunk {
+            functions,
+            main: Function {
+                name: "__main__".to_string(),
+                arity: 0,
+                locals_count: 0,
+                code: main_code,
+                stackmap: None,
+                local_types: vec![],
+            },
+            strings: vec![],
+            type_descriptors: vec![],
+            interface_descriptors: vec![],
+            const_arrays: vec![],
+            debug: None,
+            metadata: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_jit_auto_decides_per_run() {
+        let func = |name: &str, arity, code| Function {
+            name: name.to_string(),
+            arity,
+            locals_count: arity,
+            code,
+            stackmap: None,
+            local_types: vec![],
+        };
+        // is_even(n) = n == 0 ? 1 : is_odd(n - 1), and is_odd the other way round
+        let parity = |name: &str, base, other| {
+            func(
+                name,
+                1,
+                vec![
+                    Op::LocalGet(0),
+                    Op::I64Const(0),
+                    Op::I64Eq,
+                    Op::BrIfFalse(6),
+                    Op::I64Const(base),
+                    Op::Ret,
+                    Op::LocalGet(0),
+                    Op::I64Const(1),
+                    Op::I64Sub,
+                    Op::Call(other, 1),
+                    Op::Ret,
+                ],
+            )
+        };
+        let mutual = chunk_with_functions(
+            vec![parity("is_even", 1, 1), parity("is_odd", 0, 0)],
+            vec![Op::I64Const(10), Op::Call(0, 1), Op::Ret],
+        );
+        let chain = chunk_with_functions(
+            vec![
+                func("f", 0, vec![Op::Call(1, 0), Op::Ret]),
+                func("g", 0, vec![Op::I64Const(1), Op::Ret]),
+            ],
+            vec![Op::Call(0, 0), Op::Ret],
+        );
+        assert!(VM::has_hot_code(&mutual));
+        assert!(!VM::has_hot_code(&chain));
+
+        // A loopless run doesn't keep JIT off for the next chunk
+        let mut vm = VM::new();
+        vm.set_jit_auto(true);
+        vm.run(&chain).unwrap();
+        assert!(vm.jit_idle);
+        vm.run(&mutual).unwrap();
+        assert!(!vm.jit_idle);
+        assert!(vm.jit_enabled);
+    }
+
+    #[cfg(all(target_arch = "x86_64", feature = "jit"))]
+    #[test]
+    fn test_jit_auto_lowers_threshold_once_code_is_hot() {
+        let func = |name: &str, code| Function {
+            name: name.to_string(),
+            arity: 0,
+            locals_count: 0,
+            code,
+            stackmap: None,
+            local_types: vec![],
+        };
+        // `big` alone is large enough to keep JIT on
+        let mut big: Vec<Op> = (0..31).flat_map(|_| [Op::I64Const(0), Op::Drop]).collect();
+        big.extend([Op::I64Const(0), Op::Ret]);
+        let mut main_code = vec![Op::Call(2, 0), Op::Drop];
+        main_code.extend((0..8).flat_map(|_| [Op::Call(0, 0), Op::Drop]));
+        main_code.extend((0..2).flat_map(|_| [Op::Call(1, 0), Op::Drop]));
+        let chunk = chunk_with_functions(
+            vec![
+                func("first", vec![Op::I64Const(1), Op::Ret]),
+                func("second", vec![Op::I64Const(2), Op::Ret]),
+                func("big", big),
+            ],
+            main_code,
+        );
+
+        let mut vm = VM::new();
+        vm.set_use_microop(false);
+        vm.set_jit_config(true, 8, false);
+        vm.set_jit_auto(true);
+        vm.run(&chunk).unwrap();
+        // `first` turned hot at 8 calls; `second` then only needed 2
+        assert!(vm.is_jit_compiled(0));
+        assert!(vm.is_jit_compiled(1));
+        assert!(!vm.is_jit_compiled(2));
+        assert_eq!(vm.jit_run_threshold, 2);
+
+        // Without auto the configured threshold stays
+        let mut vm = VM::new();
+        vm.set_use_microop(false);
+        vm.set_jit_config(true, 8, false);
+        vm.run(&chunk).unwrap();
+        assert!(vm.is_jit_compiled(0));
+        assert!(!vm.is_jit_compiled(1));
+    }
+
+    #[test]
+    fn test_remap_functions_keeps_calls_dispatching() {
+        let func = |name: &str, arity, code| Function {
+            name: name.to_string(),
+            arity,
+            locals_count: arity,
+            code,
+            stackmap: None,
+            local_types: vec![],
+        };
+        let functions = vec![
+            func("dead", 0, vec![Op::I64Const(-1), Op::Ret]),
+            // Closure body: (closure, x) -> x + 1
+            func(
+                "add_one",
+                2,
+                vec![Op::LocalGet(1), Op::I64Const(1), Op::I64Add, Op::Ret],
+            ),
+            func(
+                "twice",
+                1,
+                vec![Op::LocalGet(0), Op::Call(3, 1), Op::Call(3, 1), Op::Ret],
+            ),
+            func(
+                "inc",
+                1,
+                vec![Op::LocalGet(0), Op::I64Const(1), Op::I64Add, Op::Ret],
+            ),
+        ];
+        let main = func(
+            "__main__",
+            0,
+            vec![
+                Op::I64Const(10),
+                Op::Call(2, 1),
+                Op::FuncRef(1),
+                Op::HeapAlloc(1),
+                Op::I64Const(5),
+                Op::CallIndirect(1),
+                Op::I64Add,
+            ],
+        );
+        let mut chunk = Chunk {
+            functions,
+            main,
+            strings: vec![],
+            type_descriptors: vec![TypeDescriptor {
+                tag_name: "Counter".to_string(),
+                field_names: vec![],
+                field_type_tags: vec![],
+                aux_type_tags: vec![],
+                vtables: vec![(0, vec![3])],
+            }],
+            interface_descriptors: vec![],
+            const_arrays: vec![],
+            debug: None,
+            metadata: Default::default(),
+        };
+        let run = |chunk: &Chunk| {
+            let mut vm = VM::new();
+            vm.set_use_microop(false);
+            vm.run(chunk).unwrap();
+            vm.stack
+        };
+        assert_eq!(run(&chunk), vec![Value::I64(18)]);
+
+        // "twice" calls "inc", so "inc" can't be dropped
+        let original = chunk.clone();
+        assert!(chunk.remap_functions(&[0, 1, 2, REMOVED_FUNCTION]).is_err());
+        assert!(chunk.remap_functions(&[0, 1, 1, 2]).is_err());
+        assert_eq!(format!("{:?}", chunk), format!("{:?}", original));
+
+        chunk.remap_functions(&[REMOVED_FUNCTION, 2, 0, 1]).unwrap();
+        let names: Vec<&str> = chunk.functions.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["twice", "inc", "add_one"]);
+        assert_eq!(chunk.type_descriptors[0].vtables, vec![(0, vec![1])]);
+        assert!(chunk.validate_function_indices().is_ok());
+        assert_eq!(run(&chunk), vec![Value::I64(18)]);
+    }
+
+    #[test]
+    fn test_descriptor_names_are_interned() {
+        let td = |tag: &str, fields: &[&str]| TypeDescriptor {
+            tag_name: tag.to_string(),
+            field_names: fields.iter().map(|f| f.to_string()).collect(),
+            field_type_tags: vec![],
+            aux_type_tags: vec![],
+            vtables: vec![],
+        };
+        let chunk = Chunk {
+            functions: vec![],
+            main: Function {
+                name: "__main__".to_string(),
+                arity: 0,
+                locals_count: 0,
+                code: vec![],
+                stackmap: None,
+                local_types: vec![],
+            },
+            strings: vec![],
+            type_descriptors: vec![
+                td("User", &["id", "name"]),
+                td("Group", &["id", "name", "members"]),
+                td("Tag", &["name", "id"]),
+            ],
+            interface_descriptors: vec![],
+            const_arrays: vec![],
+            debug: None,
+            metadata: Default::default(),
+        };
+        let mut vm = VM::new();
+        vm.set_use_microop(false);
+        vm.set_eager_descriptors(true);
+        vm.run(&chunk).unwrap();
+
+        let field = |td: usize, j: usize| {
+            let Value::Ref(r) = vm.globals[td] else {
+                panic!("type descriptor {} is not a ref", td);
+            };
+            vm.heap
+                .read_slot(r, 3 + j)
+                .and_then(|v| v.as_ref())
+                .unwrap()
+        };
+        let (id, name) = (field(0, 0), field(0, 1));
+        assert_ne!(id, name);
+        assert_eq!(field(1, 0), id);
+        assert_eq!(field(1, 1), name);
+        assert_eq!(field(2, 0), name);
+        assert_eq!(field(2, 1), id);
+    }
+
+    #[test]
+    fn test_call_arity_is_checked_statically_and_dynamically() {
+        let func = |name: &str, arity, code| Function {
+            name: name.to_string(),
+            arity,
+            locals_count: arity,
+            code,
+            stackmap: None,
+            local_types: vec![],
+        };
+        let chunk = |main_code| Chunk {
+            functions: vec![func("one", 1, vec![Op::LocalGet(0), Op::Ret])],
+            main: func("__main__", 0, main_code),
+            strings: vec![],
+            type_descriptors: vec![],
+            interface_descriptors: vec![],
+            const_arrays: vec![],
+            debug: None,
+            metadata: Default::default(),
+        };
+
+        // A static call's arity is checked before the chunk ever runs
+        let bad = chunk(vec![
+            Op::I64Const(1),
+            Op::I64Const(2),
+            Op::Call(0, 2),
+            Op::Ret,
+        ]);
+        let err = bad.validate_call_arities().unwrap_err();
+        assert_eq!(err, "__main__: function 'one' expects 1 arguments, got 2");
+        let err = crate::vm::bytecode::deserialize(&crate::vm::bytecode::serialize(&bad))
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("invalid chunk: "), "{}", err);
+
+        // CallDynamic's callee is only known when it runs
+        let dynamic = chunk(vec![
+            Op::I64Const(0),
+            Op::I64Const(1),
+            Op::I64Const(2),
+            Op::CallDynamic(2),
+            Op::Ret,
+        ]);
+        // ...and so is CallIndirect's: `one` takes just the closure
+        let indirect = chunk(vec![
+            Op::FuncRef(0),
+            Op::HeapAlloc(1),
+            Op::I64Const(1),
+            Op::CallIndirect(1),
+            Op::Ret,
+        ]);
+        assert!(dynamic.validate_call_arities().is_ok());
+        assert!(indirect.validate_call_arities().is_ok());
+        let mut vm = VM::new();
+        vm.set_use_microop(false);
+        let err = vm.run(&dynamic).unwrap_err();
+        assert!(
+            err.contains("function 'one' expects 1 arguments, got 2"),
+            "{}",
+            err
+        );
+        for use_microop in [false, true] {
+            let mut vm = VM::new();
+            vm.set_use_microop(use_microop);
+            let err = vm.run(&indirect).unwrap_err();
+            assert!(
+                err.contains("function 'one' expects 1 arguments (1 closure_ref + 0 params)"),
+                "{}",
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn test_opcode_profile_breaks_ties_by_name() {
+        let expected = vec![("Add", 7), ("Jmp", 3), ("Pop", 3), ("Push", 3), ("Ret", 1)];
+        // Each HashMap gets its own random iteration order
+        for _ in 0..16 {
+            let profile = OpcodeProfile {
+                counts: [("Push", 3), ("Ret", 1), ("Pop", 3), ("Add", 7), ("Jmp", 3)]
+                    .into_iter()
+                    .collect(),
+            };
+            assert_eq!(profile.sorted_by_count(), expected);
+        }
+    }
+
+    #[test]
+    fn test_tail_calls_reuse_the_frame() {
+        // is_even(n) = n == 0 ? 1 : is_odd(n - 1), and is_odd the other way round
+        let parity = |name: &str, base, other| Function {
+            name: name.to_string(),
+            arity: 1,
+            locals_count: 1,
+            code: vec![
+                Op::LocalGet(0),
+                Op::I64Const(0),
+                Op::I64Eq,
+                Op::BrIfFalse(6),
+                Op::I64Const(base),
+                Op::Ret,
+                Op::LocalGet(0),
+                Op::I64Const(1),
+                Op::I64Sub,
+                Op::TailCall(other, 1),
+                Op::Ret,
+            ],
+            stackmap: None,
+            local_types: vec![],
+        };
+        let chunk = Chunk {
+            functions: vec![parity("is_even", 1, 1), parity("is_odd", 0, 0)],
             main: Function {
                 name: "__main__".to_string(),
                 arity: 0,
                 locals_count: 0,
-                code: ops,
+                code: vec![Op::I64Const(1_000_001), Op::Call(0, 1), Op::Ret],
                 stackmap: None,
                 local_types: vec![],
             },
             strings: vec![],
             type_descriptors: vec![],
             interface_descriptors: vec![],
+            const_arrays: vec![],
             debug: None,
+            metadata: Default::default(),
         };
+        // `None` keeps the default JIT settings
+        for use_microop in [false, true] {
+            for jit in [None, Some(true)] {
+                let mut vm = VM::new();
+                vm.set_use_microop(use_microop);
+                if let Some(enabled) = jit {
+                    vm.set_jit_config(enabled, 1, false);
+                }
+                vm.set_max_stack_size(100);
+                assert_eq!(vm.run_and_get_result(&chunk).unwrap(), Value::I64(0));
+                assert!(vm.frames.len() <= 1);
+            }
+        }
+    }
 
-        let mut vm = VM::new();
-        // Use the stack-based interpreter for unit tests that check vm.stack directly
-        vm.set_use_microop(false);
-        vm.run(&chunk)?;
-        Ok(vm.stack)
+    #[test]
+    fn test_reinterpreted_nans_are_canonical() {
+        let inf_minus_inf = vec![
+            Op::F64Const(f64::INFINITY),
+            Op::F64Const(f64::INFINITY),
+            Op::F64Sub,
+        ];
+        let payload_nan = vec![Op::F64Const(f64::from_bits(0xFFF8_0000_DEAD_BEEF))];
+        for nan in [inf_minus_inf, payload_nan] {
+            let mut code = nan;
+            code.extend([Op::F64ReinterpretAsI64, Op::Ret]);
+            let chunk = Chunk {
+                functions: vec![],
+                main: Function {
+                    name: "__main__".to_string(),
+                    arity: 0,
+                    locals_count: 0,
+                    code,
+                    stackmap: None,
+                    local_types: vec![],
+                },
+                strings: vec![],
+                type_descriptors: vec![],
+                interface_descriptors: vec![],
+                const_arrays: vec![],
+                debug: None,
+                metadata: Default::default(),
+            };
+            for use_microop in [false, true] {
+                let mut vm = VM::new();
+                vm.set_use_microop(use_microop);
+                vm.set_jit_config(false, 0, false);
+                assert_eq!(
+                    vm.run_and_get_result(&chunk).unwrap(),
+                    Value::I64(Value::CANONICAL_NAN_BITS as i64)
+                );
+            }
+        }
     }
 
-    fn run_code_with_strings(ops: Vec<Op>, strings: Vec<String>) -> Result<Vec<Value>, String> {
+    #[test]
+    fn test_snapshot_restore() {
         let chunk = Chunk {
             functions: vec![],
             main: Function {
                 name: "__main__".to_string(),
                 arity: 0,
                 locals_count: 0,
-                code: ops,
+                code: vec![],
                 stackmap: None,
                 local_types: vec![],
             },
-            strings,
+            strings: vec![],
             type_descriptors: vec![],
             interface_descriptors: vec![],
+            const_arrays: vec![],
             debug: None,
+            metadata: Default::default(),
         };
-
         let mut vm = VM::new();
-        // Use the stack-based interpreter for unit tests that check vm.stack directly
-        vm.set_use_microop(false);
-        vm.run(&chunk)?;
-        Ok(vm.stack)
-    }
-
-    #[test]
-    fn test_push_int() {
-        let stack = run_code(vec![Op::I64Const(42)]).unwrap();
-        assert_eq!(stack, vec![Value::I64(42)]);
-    }
-
-    #[test]
-    fn test_push_float() {
-        let stack = run_code(vec![Op::F64Const(3.14)]).unwrap();
-        assert_eq!(stack, vec![Value::F64(3.14)]);
-    }
-
-    #[test]
-    fn test_push_nil() {
-        let stack = run_code(vec![Op::RefNull]).unwrap();
-        assert_eq!(stack, vec![Value::Null]);
-    }
-
-    #[test]
-    fn test_add() {
-        let stack = run_code(vec![Op::I64Const(1), Op::I64Const(2), Op::I64Add]).unwrap();
-        assert_eq!(stack, vec![Value::I64(3)]);
-    }
-
-    #[test]
-    fn test_add_float() {
-        let stack = run_code(vec![Op::F64Const(1.5), Op::F64Const(2.5), Op::F64Add]).unwrap();
-        assert_eq!(stack, vec![Value::F64(4.0)]);
-    }
-
-    #[test]
-    fn test_comparison() {
-        let stack = run_code(vec![Op::I64Const(1), Op::I64Const(2), Op::I64LtS]).unwrap();
-        assert_eq!(stack, vec![Value::Bool(true)]);
-    }
-
-    #[test]
-    fn test_division_by_zero() {
-        let result = run_code(vec![Op::I64Const(1), Op::I64Const(0), Op::I64DivS]);
-        assert!(result.is_err());
-        assert!(result.unwrap_err().contains("division by zero"));
-    }
-
-    #[test]
-    fn test_locals() {
-        let stack = run_code(vec![Op::I64Const(42), Op::LocalSet(0), Op::LocalGet(0)]).unwrap();
-        assert_eq!(stack, vec![Value::I64(42), Value::I64(42)]);
-    }
+        vm.frames.push(Frame {
+            func_index: usize::MAX,
+            pc: 0,
+            stack_base: 0,
+            ret_vreg: None,
+            stack_floor: 0,
+        });
 
-    #[test]
-    fn test_conditional_jump() {
-        // if false, skip push 1, else push 2
-        let stack = run_code(vec![
-            Op::I32Const(0), // false
-            Op::BrIfFalse(4),
-            Op::I64Const(1),
-            Op::Jmp(5),
-            Op::I64Const(2),
-        ])
-        .unwrap();
-        assert_eq!(stack, vec![Value::I64(2)]);
-    }
+        for op in [Op::I64Const(1), Op::I64Const(2), Op::HeapAlloc(2)] {
+            vm.execute_op(op, &chunk).unwrap();
+        }
+        let snap = vm.snapshot();
+        let stack_before = vm.stack.clone();
+        let frames_before = vm.frames.clone();
+        let heap_before = vm.heap.snapshot();
 
-    #[test]
-    fn test_array_operations() {
-        // Array<T> struct layout: [ptr, len]
-        // ptr points to data array, len is the element count
-        let stack = run_code(vec![
-            Op::I64Const(1),  // element 0
-            Op::I64Const(2),  // element 1
-            Op::I64Const(3),  // element 2
-            Op::HeapAlloc(3), // data array with 3 elements
-            Op::I64Const(3),  // length value
-            Op::HeapAlloc(2), // Array<T> struct [ptr, len]
-            Op::HeapLoad(1),  // read len field
-        ])
-        .unwrap();
-        assert_eq!(stack.len(), 1);
-        assert_eq!(stack[0], Value::I64(3));
+        for op in [
+            Op::I64Const(3),
+            Op::HeapAlloc(1),
+            Op::I64Const(4),
+            Op::I64Const(5),
+            Op::I64Add,
+        ] {
+            vm.execute_op(op, &chunk).unwrap();
+        }
+        assert_ne!(vm.stack, stack_before);
+        assert_ne!(vm.heap.snapshot(), heap_before);
+
+        vm.restore(&snap);
+        assert_eq!(vm.stack, stack_before);
+        assert_eq!(vm.frames, frames_before);
+        assert!(vm.try_frames.is_empty());
+        assert_eq!(vm.heap.snapshot(), heap_before);
+        assert_eq!(vm.heap.object_count(), 1);
+
+        // Objects allocated before the snapshot survive later allocations
+        let Value::Ref(r) = vm.stack[0] else {
+            panic!("expected ref on stack");
+        };
+        vm.execute_op(Op::HeapAlloc(0), &chunk).unwrap();
+        assert_eq!(vm.heap.read_slot(r, 1), Some(Value::I64(2)));
     }
 
     #[test]
@@ -4752,7 +7873,7 @@ mod tests {
         );
         // The last value should be the element we stored (2)
         let stack = result.unwrap();
-        assert!(stack.iter().any(|v| *v == Value::I64(2)));
+        assert!(stack.contains(&Value::I64(2)));
     }
 
     #[test]
@@ -4838,7 +7959,9 @@ mod tests {
             strings: vec![path_str.clone(), "hello".to_string()],
             type_descriptors: vec![],
             interface_descriptors: vec![],
+            const_arrays: vec![],
             debug: None,
+            metadata: Default::default(),
         };
 
         let mut vm = VM::new();
@@ -4855,6 +7978,125 @@ mod tests {
         let _ = std::fs::remove_file(&temp_path);
     }
 
+    #[test]
+    fn test_collected_fd_handle_closes_fd() {
+        let temp_path = std::env::temp_dir().join("moca_test_fd_handle.txt");
+        let _ = std::fs::remove_file(&temp_path);
+
+        let chunk = Chunk {
+            functions: vec![],
+            main: Function {
+                name: "__main__".to_string(),
+                arity: 0,
+                locals_count: 1,
+                code: vec![
+                    // fd = open(path, O_WRONLY | O_CREAT)
+                    Op::StringConst(0),
+                    Op::I64Const(1 | 64),
+                    Op::Hostcall(2, 2),
+                    Op::LocalSet(0),
+                    // close_on_collect({fd}, fd), then drop the handle
+                    Op::LocalGet(0),
+                    Op::HeapAlloc(1),
+                    Op::LocalGet(0),
+                    Op::Hostcall(13, 2),
+                    Op::Drop,
+                ],
+                stackmap: None,
+                local_types: vec![],
+            },
+            strings: vec![temp_path.to_str().unwrap().to_string()],
+            type_descriptors: vec![],
+            interface_descriptors: vec![],
+            const_arrays: vec![],
+            debug: None,
+            metadata: Default::default(),
+        };
+
+        let mut vm = VM::new();
+        // The stack interpreter leaves no stale handle refs on the stack
+        vm.set_use_microop(false);
+        vm.run(&chunk).unwrap();
+        assert!(vm.file_descriptors.contains_key(&3));
+        assert!(vm.fd_handles.contains_key(&3));
+        let snap = vm.snapshot();
+
+        vm.collect_garbage();
+        assert!(!vm.file_descriptors.contains_key(&3));
+        assert!(vm.fd_handles.is_empty());
+
+        // Rewinding brings the handle back, but its fd stays closed
+        vm.restore(&snap);
+        assert!(vm.fd_handles.is_empty());
+
+        let _ = std::fs::remove_file(&temp_path);
+    }
+
+    #[test]
+    fn test_memo_hostcalls_forget_collected_keys() {
+        let chunk = Chunk {
+            functions: vec![],
+            main: Function {
+                name: "__main__".to_string(),
+                arity: 0,
+                locals_count: 1,
+                code: vec![
+                    // key = {1}; memo_set(key, {2})
+                    Op::I64Const(1),
+                    Op::HeapAlloc(1),
+                    Op::LocalSet(0),
+                    Op::LocalGet(0),
+                    Op::I64Const(2),
+                    Op::HeapAlloc(1),
+                    Op::Hostcall(18, 2),
+                    Op::Drop,
+                    // memo_get(key), twice; the results stay on the stack
+                    Op::LocalGet(0),
+                    Op::Hostcall(17, 1),
+                    Op::LocalGet(0),
+                    Op::Hostcall(17, 1),
+                    // Drop the only reference to the key
+                    Op::RefNull,
+                    Op::LocalSet(0),
+                ],
+                stackmap: None,
+                local_types: vec![],
+            },
+            strings: vec![],
+            type_descriptors: vec![],
+            interface_descriptors: vec![],
+            const_arrays: vec![],
+            debug: None,
+            metadata: Default::default(),
+        };
+
+        let mut vm = VM::new();
+        vm.set_use_microop(false);
+        vm.run(&chunk).unwrap();
+        let [Value::Null, Value::Ref(first), Value::Ref(second)] = vm.stack[..] else {
+            panic!("unexpected stack: {:?}", vm.stack);
+        };
+        assert_eq!(first, second);
+        assert_eq!(vm.heap.read_slot(first, 0), Some(Value::I64(2)));
+        assert_eq!(vm.heap.memo_len(), 1);
+
+        // The memoized value is still on the stack, but its key is gone
+        vm.collect_garbage();
+        assert_eq!(vm.heap.memo_len(), 0);
+        assert_eq!(vm.heap.read_slot(first, 0), Some(Value::I64(2)));
+    }
+
+    #[test]
+    fn test_memo_get_of_unknown_key_is_nil() {
+        let stack = run_code(vec![Op::I64Const(1), Op::HeapAlloc(1), Op::Hostcall(17, 1)]).unwrap();
+        assert_eq!(stack, vec![Value::Null]);
+        assert!(
+            run_code(vec![Op::I64Const(1), Op::Hostcall(17, 1)])
+                .unwrap_err()
+                .contains("memo_get: key must be a reference")
+        );
+    }
+
     #[test]
     fn test_hostcall_read_invalid_fd() {
         // Test reading from invalid fd returns EBADF (-1)
@@ -4927,7 +8169,9 @@ mod tests {
             strings: vec![path_str.clone()],
             type_descriptors: vec![],
             interface_descriptors: vec![],
+            const_arrays: vec![],
             debug: None,
+            metadata: Default::default(),
         };
 
         let mut vm = VM::new();
@@ -5002,7 +8246,9 @@ mod tests {
             strings: vec![path_str.clone()],
             type_descriptors: vec![],
             interface_descriptors: vec![],
+            const_arrays: vec![],
             debug: None,
+            metadata: Default::default(),
         };
 
         let mut vm = VM::new();
@@ -5100,7 +8346,170 @@ mod tests {
         ])
         .unwrap();
         // Last value should be 0 (success)
-        assert!(stack.iter().any(|v| *v == Value::I64(0)));
+        assert!(stack.contains(&Value::I64(0)));
+    }
+
+    #[test]
+    fn test_every_hostcall_number_has_a_handler() {
+        let numbers = [
+            HOSTCALL_WRITE,
+            HOSTCALL_OPEN,
+            HOSTCALL_CLOSE,
+            HOSTCALL_READ,
+            HOSTCALL_SOCKET,
+            HOSTCALL_CONNECT,
+            HOSTCALL_BIND,
+            HOSTCALL_LISTEN,
+            HOSTCALL_ACCEPT,
+            HOSTCALL_TIME,
+            HOSTCALL_TIME_NANOS,
+            HOSTCALL_FORMAT,
+            HOSTCALL_CLOSE_ON_COLLECT,
+            HOSTCALL_SET_READ_TIMEOUT,
+            HOSTCALL_SET_WRITE_TIMEOUT,
+            HOSTCALL_SET_NONBLOCKING,
+            HOSTCALL_MEMO_GET,
+            HOSTCALL_MEMO_SET,
+        ];
+        for n in numbers {
+            assert!(HOSTCALLS[n].is_some(), "hostcall {} has no handler", n);
+        }
+        let handled = HOSTCALLS.iter().filter(|h| h.is_some()).count();
+        assert_eq!(handled, numbers.len());
+
+        let mut vm = VM::new();
+        for n in [0, HOSTCALLS.len()] {
+            assert_eq!(
+                vm.handle_hostcall(n, &[]).unwrap_err(),
+                format!("unknown hostcall: {}", n)
+            );
+        }
+    }
+
+    #[test]
+    fn test_hostcall_dispatch_results() {
+        let mut vm = VM::new();
+        // Reserved and unknown fds
+        assert_eq!(
+            vm.handle_hostcall(HOSTCALL_CLOSE, &[Value::I64(1)])
+                .unwrap(),
+            Value::I64(EBADF)
+        );
+        assert_eq!(
+            vm.handle_hostcall(HOSTCALL_SET_READ_TIMEOUT, &[Value::I64(99), Value::I64(10)])
+                .unwrap(),
+            Value::I64(EBADF)
+        );
+        assert_eq!(
+            vm.handle_hostcall(HOSTCALL_CLOSE, &[]).unwrap_err(),
+            "close hostcall expects 1 argument, got 0"
+        );
+        assert_eq!(
+            vm.handle_hostcall(HOSTCALL_SET_WRITE_TIMEOUT, &[])
+                .unwrap_err(),
+            "set_write_timeout hostcall expects 2 arguments, got 0"
+        );
+
+        let secs = vm.handle_hostcall(HOSTCALL_TIME, &[]).unwrap();
+        assert!(secs.as_i64().unwrap() > 0);
+
+        let key = Value::Ref(vm.heap.alloc_slots(vec![]).unwrap());
+        assert_eq!(
+            vm.handle_hostcall(HOSTCALL_MEMO_GET, &[key]).unwrap(),
+            Value::Null
+        );
+        vm.handle_hostcall(HOSTCALL_MEMO_SET, &[key, Value::I64(7)])
+            .unwrap();
+        assert_eq!(
+            vm.handle_hostcall(HOSTCALL_MEMO_GET, &[key]).unwrap(),
+            Value::I64(7)
+        );
+    }
+
+    #[test]
+    fn test_closed_fds_are_reused() {
+        let mut vm = VM::new();
+        let socket = |vm: &mut VM| {
+            vm.handle_hostcall(5, &[Value::I64(2), Value::I64(1)])
+                .unwrap()
+        };
+
+        for _ in 0..1000 {
+            let fd = socket(&mut vm);
+            assert_eq!(fd, Value::I64(3));
+            assert_eq!(vm.handle_hostcall(3, &[fd]).unwrap(), Value::I64(0));
+        }
+
+        // The lowest closed fd is handed out first
+        let fds: Vec<Value> = (0..3).map(|_| socket(&mut vm)).collect();
+        assert_eq!(fds, vec![Value::I64(3), Value::I64(4), Value::I64(5)]);
+        vm.handle_hostcall(3, &[Value::I64(4)]).unwrap();
+        assert_eq!(socket(&mut vm), Value::I64(4));
+        assert_eq!(socket(&mut vm), Value::I64(6));
+
+        // Closing an already-closed or unknown fd fails cleanly
+        assert_eq!(
+            vm.handle_hostcall(3, &[Value::I64(4)]).unwrap(),
+            Value::I64(0)
+        );
+        assert_eq!(
+            vm.handle_hostcall(3, &[Value::I64(4)]).unwrap(),
+            Value::I64(-1)
+        );
+        assert_eq!(
+            vm.handle_hostcall(3, &[Value::I64(99)]).unwrap(),
+            Value::I64(-1)
+        );
+        assert_eq!(vm.next_fd, 7);
+    }
+
+    #[test]
+    fn test_socket_read_timeout() {
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind");
+        let port = listener.local_addr().unwrap().port() as i64;
+
+        let mut vm = VM::new();
+        let fd = vm
+            .handle_hostcall(5, &[Value::I64(2), Value::I64(1)])
+            .unwrap();
+        let host = Value::Ref(vm.heap.alloc_string("127.0.0.1".to_string()).unwrap());
+        assert_eq!(
+            vm.handle_hostcall(6, &[fd, host, Value::I64(port)])
+                .unwrap(),
+            Value::I64(0)
+        );
+        // Keep the peer open but silent
+        let (_peer, _) = listener.accept().unwrap();
+
+        assert_eq!(
+            vm.handle_hostcall(14, &[fd, Value::I64(50)]).unwrap(),
+            Value::I64(0)
+        );
+        let start = std::time::Instant::now();
+        assert_eq!(
+            vm.handle_hostcall(4, &[fd, Value::I64(16)]).unwrap(),
+            Value::I64(-9)
+        );
+        assert!(start.elapsed() >= std::time::Duration::from_millis(40));
+
+        // A non-blocking read with no data returns at once
+        assert_eq!(
+            vm.handle_hostcall(16, &[fd, Value::Bool(true)]).unwrap(),
+            Value::I64(0)
+        );
+        assert_eq!(
+            vm.handle_hostcall(4, &[fd, Value::I64(16)]).unwrap(),
+            Value::I64(-9)
+        );
+
+        // Only connected sockets take timeouts
+        assert_eq!(
+            vm.handle_hostcall(14, &[Value::I64(99), Value::I64(50)])
+                .unwrap(),
+            Value::I64(-1)
+        );
     }
 
     #[test]
@@ -5176,7 +8585,9 @@ mod tests {
             strings: vec!["127.0.0.1".to_string(), http_request],
             type_descriptors: vec![],
             interface_descriptors: vec![],
+            const_arrays: vec![],
             debug: None,
+            metadata: Default::default(),
         };
 
         let mut vm = VM::new();
@@ -5214,4 +8625,125 @@ mod tests {
         // Wait for server thread to finish
         let _ = server_handle.join();
     }
+
+    fn unbalanced_chunk(code: Vec<Op>) -> Chunk {
+        Chunk {
+            functions: vec![],
+            main: Function {
+                name: "__main__".to_string(),
+                arity: 0,
+                locals_count: 0,
+                code,
+                stackmap: None,
+                local_types: vec![],
+            },
+            strings: vec![],
+            type_descriptors: vec![],
+            interface_descriptors: vec![],
+            const_arrays: vec![],
+            debug: None,
+            metadata: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_runaway_pushes_hit_stack_limit() {
+        // loop { push 1 } never pops
+        let chunk = unbalanced_chunk(vec![Op::I64Const(1), Op::Jmp(0)]);
+        for use_microop in [false, true] {
+            let mut vm = VM::new();
+            vm.set_use_microop(use_microop);
+            vm.set_jit_config(false, 0, false);
+            vm.set_max_stack_size(1000);
+            let err = vm.run(&chunk).unwrap_err();
+            assert_eq!(err, "runtime error: operand stack overflow");
+            assert!(vm.stack.len() <= 1001);
+        }
+    }
+
+    #[test]
+    fn test_stack_overflow_is_catchable() {
+        // try { loop { push 1 } } catch { 7 }
+        let chunk = unbalanced_chunk(vec![
+            Op::TryBegin(3),
+            Op::I64Const(1),
+            Op::Jmp(1),
+            Op::Drop,
+            Op::I64Const(7),
+            Op::Ret,
+        ]);
+        let mut vm = VM::new();
+        vm.set_jit_config(false, 0, false);
+        vm.set_max_stack_size(1000);
+        assert_eq!(vm.run_and_get_result(&chunk).unwrap(), Value::I64(7));
+    }
+
+    #[test]
+    fn test_with_runtime_config_applies_every_field() {
+        let config = RuntimeConfig {
+            jit_mode: crate::config::JitMode::Off,
+            jit_threshold: 7,
+            trace_jit: true,
+            gc_enabled: false,
+            heap_limit: Some(64 * 1024),
+            profile_opcodes: true,
+            trace_calls: true,
+            coverage: true,
+            backtrace: true,
+            print_heap_on_error: true,
+            max_stack_size: 1000,
+            deterministic_threads: true,
+            max_threads: 2,
+            ..RuntimeConfig::default()
+        };
+        let mut vm = VM::with_runtime_config(&config, Box::new(io::sink()), Box::new(io::sink()));
+        assert!(!vm.jit_enabled);
+        assert_eq!(vm.jit_threshold, 7);
+        assert!(vm.trace_jit);
+        assert!(vm.trace_calls);
+        assert!(vm.backtrace);
+        assert!(vm.print_heap_on_error);
+        assert_eq!(vm.max_stack_size, 1000);
+        assert!(vm.thread_spawner.is_deterministic());
+
+        // Profiling and coverage record the ops that run
+        let chunk = unbalanced_chunk(vec![Op::TryBegin(2), Op::TryEnd]);
+        vm.run(&chunk).unwrap();
+        assert_eq!(vm.opcode_profile().counts.get("TryEnd"), Some(&1));
+        assert!(!vm.take_coverage().is_empty());
+
+        // The heap limit applies, and GC never triggers
+        for _ in 0..100 {
+            let _ = vm.heap.alloc_slots(vec![Value::Null; 32]);
+        }
+        assert!(!vm.heap.should_gc());
+        let err = vm.heap.alloc_slots(vec![Value::Null; 8192]).unwrap_err();
+        assert!(err.contains("heap limit exceeded"), "{}", err);
+    }
+
+    #[test]
+    fn test_debug_string_quotes_and_escapes_strings() {
+        let mut vm = VM::new();
+        let plain = Value::Ref(vm.heap.alloc_string("hi".to_string()).unwrap());
+        let tricky = Value::Ref(vm.heap.alloc_string("a\"b\\\n\t".to_string()).unwrap());
+        let data = vm.heap.alloc_slots(vec![plain, Value::Null]).unwrap();
+        let array = Value::Ref(
+            vm.heap
+                .alloc_slots(vec![Value::Ref(data), Value::I64(2)])
+                .unwrap(),
+        );
+
+        // `print` shows strings as they are
+        assert_eq!(vm.value_to_string(&plain).unwrap(), "hi");
+        assert_eq!(vm.value_to_string(&tricky).unwrap(), "a\"b\\\n\t");
+        assert_eq!(vm.value_to_string(&array).unwrap(), "[hi, nil]");
+
+        let debug = |v: &Value| debug_string(&vm.heap, &[], v).unwrap();
+        assert_eq!(debug(&plain), "\"hi\"");
+        assert_eq!(debug(&tricky), r#""a\"b\\\n\t""#);
+        assert_eq!(debug(&array), "[\"hi\", nil]");
+        assert_eq!(debug(&Value::Bool(true)), "true");
+        assert_eq!(debug(&Value::Null), "nil");
+        assert_eq!(debug(&Value::F64(2.0)), "2.0");
+    }
 }
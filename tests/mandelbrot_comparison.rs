@@ -201,11 +201,7 @@ fn compare_mandelbrot(max_iter: i32) {
     println!("Moca execution time: {:?}", moca_duration);
     println!(
         "Time difference: {:?} (Rust - Moca)",
-        if rust_duration > moca_duration {
-            rust_duration - moca_duration
-        } else {
-            moca_duration - rust_duration
-        }
+        rust_duration.abs_diff(moca_duration)
     );
     println!(
         "Moca is {:.2}x {} than Rust",
@@ -7,7 +7,9 @@ use std::io::{Cursor, Write};
 use std::path::Path;
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use moca::compiler::{dump_ast, dump_bytecode, lint_file, run_file_capturing_output, run_tests};
+use moca::compiler::{
+    CapturedOutput, dump_ast, dump_bytecode, lint_file, run_file_capturing_output, run_tests,
+};
 use moca::config::{JitMode, RuntimeConfig};
 use moca::lsp::analyze_source;
 
@@ -191,7 +193,7 @@ fn run_snapshot_dir(dir: &str) {
         .filter(|e| {
             let path = e.path();
             // Include .mc files
-            if path.extension().map_or(false, |ext| ext == "mc") {
+            if path.extension().is_some_and(|ext| ext == "mc") {
                 return true;
             }
             // Include directories that contain main.mc (multi-file tests)
@@ -273,10 +275,10 @@ fn run_gc_snapshot_dir(dir: &str) {
         .filter(|e| {
             let path = e.path();
             // Include .mc files that don't have .gc_disabled in the name
-            path.extension().map_or(false, |ext| ext == "mc")
+            path.extension().is_some_and(|ext| ext == "mc")
                 && !path
                     .file_stem()
-                    .map_or(false, |s| s.to_string_lossy().ends_with(".gc_disabled"))
+                    .is_some_and(|s| s.to_string_lossy().ends_with(".gc_disabled"))
         })
         .collect();
 
@@ -395,7 +397,7 @@ fn run_lint_snapshot_dir(dir: &str) {
     let entries: Vec<_> = fs::read_dir(&dir_path)
         .unwrap_or_else(|e| panic!("Failed to read {:?}: {}", dir_path, e))
         .filter_map(|e| e.ok())
-        .filter(|e| e.path().extension().map_or(false, |ext| ext == "mc"))
+        .filter(|e| e.path().extension().is_some_and(|ext| ext == "mc"))
         .collect();
 
     for entry in entries {
@@ -550,7 +552,7 @@ fn run_lsp_diagnostics_snapshot_dir(dir: &str) {
     let entries: Vec<_> = fs::read_dir(&dir_path)
         .unwrap_or_else(|e| panic!("Failed to read {:?}: {}", dir_path, e))
         .filter_map(|e| e.ok())
-        .filter(|e| e.path().extension().map_or(false, |ext| ext == "mc"))
+        .filter(|e| e.path().extension().is_some_and(|ext| ext == "mc"))
         .collect();
 
     assert!(!entries.is_empty(), "No .mc files found in {:?}", dir_path);
@@ -636,7 +638,7 @@ fn run_test_runner_snapshot(subdir: &str) {
         .join(subdir);
 
     let config = RuntimeConfig::default();
-    let results = run_tests(&base_path, &config).expect("run_tests should succeed");
+    let results = run_tests(&base_path, &config, None, 1).expect("run_tests should succeed");
 
     let actual_output = format_test_results(&results);
 
@@ -692,6 +694,118 @@ fn snapshot_test_runner_mixed() {
     run_test_runner_snapshot("mixed");
 }
 
+#[test]
+fn snapshot_test_runner_hooks() {
+    run_test_runner_snapshot("hooks");
+}
+
+#[test]
+fn test_runner_filter_runs_matching_tests_only() {
+    let base_path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("snapshots")
+        .join("test_runner")
+        .join("mixed");
+    let config = RuntimeConfig::default();
+
+    let results =
+        run_tests(&base_path, &config, Some("pass"), 1).expect("run_tests should succeed");
+    let mut names: Vec<_> = results.results.iter().map(|r| r.name.as_str()).collect();
+    names.sort();
+    assert_eq!(names, vec!["_test_should_pass_1", "_test_should_pass_2"]);
+    assert_eq!((results.passed, results.failed), (2, 0));
+
+    let results =
+        run_tests(&base_path, &config, Some("fail"), 1).expect("run_tests should succeed");
+    assert_eq!(results.results.len(), 1);
+    assert_eq!((results.passed, results.failed), (0, 1));
+
+    let results =
+        run_tests(&base_path, &config, Some("nothing"), 1).expect("run_tests should succeed");
+    assert!(results.results.is_empty());
+    assert_eq!((results.passed, results.failed), (0, 0));
+}
+
+#[test]
+fn test_runner_jobs_do_not_change_results() {
+    let dir = std::env::temp_dir().join(format!("moca_test_jobs_{}", std::process::id()));
+    fs::create_dir_all(&dir).expect("Failed to create temp dir");
+    for file in 0..3 {
+        // Every file defines the same test names, with different outcomes
+        let source = format!(
+            r#"
+fun _test_shared_name() {{
+    assert_eq({file} % 2, 0, "even file");
+}}
+
+fun _test_count_{file}() {{
+    let i = 0;
+    while i < 1000 {{
+        i = i + 1;
+    }}
+    print(i);
+}}
+"#
+        );
+        fs::write(dir.join(format!("file_{file}.mc")), source).expect("Failed to write test file");
+    }
+
+    let config = RuntimeConfig::default();
+    let summarize = |jobs| {
+        let results = run_tests(&dir, &config, None, jobs).expect("run_tests should succeed");
+        let rows: Vec<_> = results
+            .results
+            .iter()
+            .map(|r| (r.name.clone(), r.file.clone(), r.passed, r.error.clone()))
+            .collect();
+        (rows, results.passed, results.failed)
+    };
+    let sequential = summarize(1);
+    let parallel = summarize(4);
+    let _ = fs::remove_dir_all(&dir);
+
+    assert_eq!(sequential.0.len(), 6);
+    assert_eq!((sequential.1, sequential.2), (5, 1));
+    assert_eq!(sequential, parallel);
+}
+
+/// `moca test --coverage` reports a never-taken branch as uncovered.
+#[test]
+fn test_runner_coverage() {
+    let source = r#"fun classify(x: int) -> int {
+    if x > 100 {
+        print("big");
+        return 2;
+    }
+    return 1;
+}
+
+fun _test_classify() {
+    assert_eq(classify(5), 1, "small input");
+}
+"#;
+    let dir = std::env::temp_dir().join(format!("moca_coverage_{}", std::process::id()));
+    fs::create_dir_all(&dir).expect("Failed to create temp dir");
+    let file = dir.join("classify.mc");
+    fs::write(&file, source).expect("Failed to write temp file");
+
+    let config = RuntimeConfig {
+        coverage: true,
+        ..Default::default()
+    };
+    let results = run_tests(&dir, &config, None, 1).expect("run_tests should succeed");
+    let _ = fs::remove_dir_all(&dir);
+    assert!(results.all_passed());
+
+    let report = results.coverage.expect("coverage should be collected");
+    let coverage = &report.files[&file];
+    assert_eq!(coverage.uncovered().collect::<Vec<_>>(), vec![3, 4]);
+    assert!(coverage.covered.contains(&2));
+    assert!(coverage.covered.contains(&6));
+    assert!(coverage.covered.contains(&10));
+    assert!(report.to_lcov().contains("DA:3,0\n"));
+}
+
 // ============================================================================
 // Standard Library Tests
 // ============================================================================
@@ -703,7 +817,8 @@ fn snapshot_stdlib() {
     let std_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("std");
 
     let config = RuntimeConfig::default();
-    let results = run_tests(&std_path, &config).expect("run_tests should succeed for stdlib");
+    let results =
+        run_tests(&std_path, &config, None, 1).expect("run_tests should succeed for stdlib");
 
     // All stdlib tests should pass
     assert!(
@@ -727,6 +842,545 @@ fn snapshot_stdlib() {
     );
 }
 
+// ============================================================================
+// Runtime Option Tests
+// ============================================================================
+
+/// Write `source` to a temp file named after `name`, pass its path to `run`,
+/// and remove the file afterwards.
+fn with_source_file<R>(name: &str, source: &str, run: impl FnOnce(&Path) -> R) -> R {
+    let path = std::env::temp_dir().join(format!("{}_{}.mc", name, std::process::id()));
+    fs::write(&path, source).expect("Failed to write temp file");
+    let result = run(&path);
+    let _ = fs::remove_file(&path);
+    result
+}
+
+/// Run `source` once under `config`, capturing its output.
+fn run_source(
+    name: &str,
+    source: &str,
+    config: &RuntimeConfig,
+) -> (CapturedOutput, Result<(), String>) {
+    with_source_file(name, source, |path| run_file_capturing_output(path, config))
+}
+
+/// `--trace-calls` prints an indented entry/return line for each call.
+#[test]
+fn trace_calls_output() {
+    let source = r#"
+fun inner(x: int) -> int {
+    return x * 2;
+}
+
+fun outer(a: int, b: string) -> int {
+    return inner(a) + 1;
+}
+
+let r = outer(20, "hi");
+"#;
+    let config = RuntimeConfig {
+        trace_calls: true,
+        ..Default::default()
+    };
+    let (output, result) = run_source("trace_calls", source, &config);
+    result.unwrap();
+
+    assert_eq!(
+        output.stderr,
+        "-> outer(20, hi)\n  -> inner(20)\n  <- inner = 40\n<- outer = 41\n"
+    );
+}
+
+/// `--runtime-type-checks` guards a dynamic call result bound to a typed variable.
+#[test]
+fn runtime_type_check_guard() {
+    let source = r#"
+let f = fun(x: int) -> float { return 1.5; };
+let idx: int = __heap_load(f, 0);
+let r: int = __call_func(idx, 3);
+print(r + 1);
+"#;
+    let config = RuntimeConfig {
+        runtime_type_checks: true,
+        ..Default::default()
+    };
+    let (output, result) = run_source("runtime_type_checks", source, &config);
+
+    assert_eq!(
+        result.unwrap_err(),
+        "runtime error: expected int, got float"
+    );
+    assert_eq!(output.stdout, "");
+}
+
+/// `--runtime-type-checks` also guards a dynamic call result used as an
+/// operand, returned, or passed as an argument, without a typed variable.
+#[test]
+fn runtime_type_check_guard_without_typed_let() {
+    let source = r#"
+let f = fun(x: int) -> float { return 1.5; };
+let idx: int = __heap_load(f, 0);
+
+fun twice(n: int) -> int {
+    return n * 2;
+}
+
+fun dispatch(i: int) -> int {
+    return __call_func(i, 3);
+}
+
+try {
+    print(__call_func(idx, 3) + 1);
+} catch e {
+    print(e);
+}
+try {
+    print(dispatch(idx));
+} catch e {
+    print(e);
+}
+try {
+    print(twice(__call_func(idx, 3)));
+} catch e {
+    print(e);
+}
+"#;
+    let config = RuntimeConfig {
+        runtime_type_checks: true,
+        ..Default::default()
+    };
+    let (output, result) = run_source("runtime_type_checks_sites", source, &config);
+
+    result.unwrap();
+    assert_eq!(
+        output.stdout,
+        "runtime error: expected int, got float\n".repeat(3)
+    );
+}
+
+/// `--backtrace` lists the frames of an uncaught throw, innermost first.
+#[test]
+fn uncaught_throw_backtrace() {
+    let source = r#"fun level3(x: int) -> int {
+    if x > 1 {
+        throw "too deep";
+    }
+    return x;
+}
+
+fun level2(x: int) -> int {
+    return level3(x + 1) + 1;
+}
+
+fun level1(x: int) -> int {
+    return level2(x + 1) + 1;
+}
+
+try {
+    level1(5);
+} catch e {
+    print("caught");
+}
+print(level1(0));
+"#;
+    let config = RuntimeConfig {
+        backtrace: true,
+        ..Default::default()
+    };
+    let (output, result) = run_source("backtrace", source, &config);
+
+    assert_eq!(output.stdout, "caught\n");
+    assert_eq!(
+        result.unwrap_err(),
+        "runtime error: too deep\nbacktrace:\n  0: level3 (line 3)\n  1: level2 (line 9)\n  2: level1 (line 13)\n  3: <main> (line 21)"
+    );
+}
+
+/// `--print-heap-on-error` appends a heap summary and the innermost frames
+/// to an uncaught error.
+#[test]
+fn uncaught_error_heap_report() {
+    let source = r#"struct Point { x: int, y: int }
+
+fun fill(n: int) -> int {
+    let points: Vec<Point> = new Vec<Point> {};
+    for i in 0..n {
+        points.push(Point { x: i, y: i });
+    }
+    throw "out of room";
+    return 0;
+}
+
+fun run() -> int {
+    return fill(100);
+}
+
+print(run());
+"#;
+    let config = RuntimeConfig {
+        print_heap_on_error: true,
+        ..Default::default()
+    };
+    let (_, result) = run_source("heap_report", source, &config);
+
+    let err = result.unwrap_err();
+    assert!(
+        err.starts_with("runtime error: out of room\nheap: "),
+        "{}",
+        err
+    );
+    assert!(
+        err.contains("\n  Point: 100 objects, 4000 bytes\n"),
+        "{}",
+        err
+    );
+    assert!(
+        err.ends_with("\nframes:\n  0: fill\n  1: run\n  2: <main>"),
+        "{}",
+        err
+    );
+}
+
+/// `--free-temps` releases concatenation intermediates, so a loop of
+/// throwaway strings fits in a heap that cannot hold them with GC off.
+#[test]
+fn free_temporaries_bound_heap() {
+    let source = r#"
+let word = "moca";
+let total = 0;
+let i = 0;
+while i < 5000 {
+    let line = "<" + word + ">" + word + "<" + word + ">";
+    total = total + len(line);
+    i = i + 1;
+}
+print(total);
+"#;
+    let run = |free_temporaries: bool| {
+        let config = RuntimeConfig {
+            gc_enabled: false,
+            heap_limit: Some(1024 * 1024),
+            free_temporaries,
+            ..Default::default()
+        };
+        run_source("free_temps", source, &config)
+    };
+    let (output, result) = run(true);
+    let (_, baseline) = run(false);
+
+    result.unwrap();
+    assert_eq!(output.stdout, "80000\n");
+    assert!(baseline.unwrap_err().contains("heap limit exceeded"));
+}
+
+/// Dict keys and values stay reachable through collections triggered by garbage.
+#[test]
+fn dict_contents_survive_gc() {
+    let source = r#"
+let d = dict_new();
+for i in 0..100 {
+    dict_set(d, $"key{i}", $"value{i}");
+}
+let junk = 0;
+for i in 0..100000 {
+    let s = $"garbage{i}";
+    junk = junk + len(s);
+}
+let ok = 0;
+for i in 0..100 {
+    if dict_get(d, $"key{i}") == $"value{i}" {
+        ok = ok + 1;
+    }
+}
+print(ok);
+"#;
+    let run = |gc_enabled: bool| {
+        let config = RuntimeConfig {
+            gc_enabled,
+            heap_limit: Some(4 * 1024 * 1024),
+            ..Default::default()
+        };
+        run_source("dict_gc", source, &config)
+    };
+    let (output, result) = run(true);
+    let (_, without_gc) = run(false);
+
+    result.unwrap();
+    assert_eq!(output.stdout, "100\n");
+    assert!(without_gc.unwrap_err().contains("heap limit exceeded"));
+}
+
+/// A function that pushes in a loop without popping hits the operand stack
+/// limit and raises a catchable error instead of growing without bound.
+#[test]
+fn operand_stack_overflow_is_catchable() {
+    let source = r#"
+fun runaway() {
+    while true {
+        asm {
+            __emit("I64Const", 1);
+            __emit("I64Const", 2);
+        };
+    }
+}
+try {
+    runaway();
+} catch e {
+    print(e);
+}
+"#;
+    let config = RuntimeConfig {
+        jit_mode: JitMode::Off,
+        max_stack_size: 10_000,
+        ..Default::default()
+    };
+    let (output, result) = run_source("stack_limit", source, &config);
+
+    result.unwrap();
+    assert_eq!(output.stdout, "runtime error: operand stack overflow\n");
+}
+
+/// Calls in tail position replace the caller's frame, so deep self and mutual
+/// tail recursion stays within a small operand stack.
+#[test]
+fn tail_calls_run_in_constant_stack() {
+    let source = r#"
+fun count(n: int, acc: int) -> int {
+    if n == 0 {
+        return acc;
+    }
+    return count(n - 1, acc + 1);
+}
+
+fun is_even(n: int) -> bool {
+    if n == 0 {
+        return true;
+    }
+    return is_odd(n - 1);
+}
+
+fun is_odd(n: int) -> bool {
+    if n == 0 {
+        return false;
+    }
+    return is_even(n - 1);
+}
+
+print(count(100000, 0));
+print(is_even(1000000));
+print(is_odd(777777));
+"#;
+    // Hot tail-calling functions must not be compiled into native calls
+    for jit_mode in [RuntimeConfig::default().jit_mode, JitMode::On] {
+        let config = RuntimeConfig {
+            jit_mode,
+            max_stack_size: 1000,
+            ..Default::default()
+        };
+        let (output, result) = run_source("tail_calls", source, &config);
+        result.unwrap();
+        assert_eq!(output.stdout, "100000\ntrue\ntrue\n");
+    }
+}
+
+/// `--eval-print` prints the value of a trailing expression (as in `-c "1 + 2"`).
+#[test]
+fn eval_print_last_expression() {
+    let config = RuntimeConfig {
+        eval_print: true,
+        ..Default::default()
+    };
+    let (output, result) = run_source("eval_print", "1 + 2", &config);
+    result.unwrap();
+
+    assert_eq!(output.stdout, "3\n");
+}
+
+/// `--eval-print` quotes and escapes a string result, while `print` stays raw.
+#[test]
+fn eval_print_quotes_strings() {
+    let source = "print(\"say \\\"hi\\\"\");\n\"say \\\"hi\\\"\\n\"";
+    let config = RuntimeConfig {
+        eval_print: true,
+        ..Default::default()
+    };
+    let (output, result) = run_source("eval_print_str", source, &config);
+    result.unwrap();
+
+    assert_eq!(output.stdout, "say \"hi\"\n\"say \\\"hi\\\"\\n\"\n");
+}
+
+/// `--eval-print` shows a struct with its type and field names.
+#[test]
+fn eval_print_struct() {
+    let source = "struct Point { x: int, label: string }\nPoint { x: 3, label: \"origin\" }";
+    let config = RuntimeConfig {
+        eval_print: true,
+        ..Default::default()
+    };
+    let (output, result) = run_source("eval_print_struct", source, &config);
+    result.unwrap();
+
+    assert_eq!(output.stdout, "Point { x: 3, label: \"origin\" }\n");
+}
+
+/// `--jit=off` never compiles, even code that `--jit=on` finds hot.
+#[test]
+fn jit_off_compiles_nothing_on_hot_code() {
+    let path = Path::new("tests/snapshots/jit/fibonacci.mc");
+
+    let off = RuntimeConfig {
+        jit_mode: JitMode::Off,
+        jit_threshold: 1,
+        ..Default::default()
+    };
+    let (_, stderr, exitcode, jit_compile_count) = run_moca_file_inprocess(path, &off);
+    assert_eq!(exitcode, 0, "fibonacci.mc failed: {}", stderr);
+    assert_eq!(jit_compile_count, 0, "JitMode::Off must not JIT compile");
+
+    let on = RuntimeConfig {
+        jit_mode: JitMode::On,
+        jit_threshold: 1,
+        ..Default::default()
+    };
+    let (_, stderr, exitcode, jit_compile_count) = run_moca_file_inprocess(path, &on);
+    assert_eq!(exitcode, 0, "fibonacci.mc failed: {}", stderr);
+    assert!(jit_compile_count > 0, "JitMode::On should JIT compile");
+}
+
+/// `--jit=auto` stays interpreted for a loopless script but still JITs hot loops.
+#[test]
+fn jit_auto_skips_loopless_scripts() {
+    let source = r#"
+fun add(a: int, b: int) -> int { return a + b; }
+if add(1, 2) == 3 { print("three"); }
+if add(3, 4) == 7 { print("seven"); }
+"#;
+    let config = |jit_mode| RuntimeConfig {
+        jit_mode,
+        jit_threshold: 1,
+        ..Default::default()
+    };
+
+    let ((stdout, stderr, exitcode, jit_compile_count), on) =
+        with_source_file("jit_auto", source, |path| {
+            (
+                run_moca_file_inprocess(path, &config(JitMode::Auto)),
+                run_moca_file_inprocess(path, &config(JitMode::On)),
+            )
+        });
+    assert_eq!(exitcode, 0, "tiny script failed: {}", stderr);
+    assert_eq!(stdout, "three\nseven\n");
+    assert_eq!(jit_compile_count, 0, "Auto must not JIT a loopless script");
+
+    if JitMode::Auto.is_enabled() {
+        assert_eq!(on.2, 0, "tiny script failed: {}", on.1);
+        assert!(on.3 > 0, "On should JIT even a tiny script");
+
+        let hot = Path::new("tests/snapshots/jit/sum_loop.mc");
+        let (_, stderr, exitcode, jit_compile_count) =
+            run_moca_file_inprocess(hot, &config(JitMode::Auto));
+        assert_eq!(exitcode, 0, "sum_loop.mc failed: {}", stderr);
+        assert!(jit_compile_count > 0, "Auto should JIT a hot loop");
+    }
+}
+
+/// `--deterministic-threads` interleaves two workers sharing a channel-guarded
+/// counter the same way on every run.
+#[test]
+fn deterministic_threads_are_reproducible() {
+    let source = r#"
+fun work(id: int) -> int {
+    let i = 0;
+    while i < 50 {
+        let n = recv(0);
+        send(1, id);
+        send(0, n + 1);
+        i = i + 1;
+    }
+    return id;
+}
+
+fun worker_a() -> int { return work(1); }
+fun worker_b() -> int { return work(2); }
+
+let counter = channel();
+let log = channel();
+send(0, 0);
+let a = spawn(worker_a);
+let b = spawn(worker_b);
+join(a);
+join(b);
+print(recv(0));
+
+let order = "";
+let i = 0;
+while i < 12 {
+    let who: int = recv(1);
+    order = order + who.to_string();
+    i = i + 1;
+}
+print(order);
+"#;
+    let config = RuntimeConfig {
+        deterministic_threads: true,
+        ..Default::default()
+    };
+    let runs: Vec<_> = with_source_file("deterministic_threads", source, |path| {
+        (0..5)
+            .map(|_| run_moca_file_inprocess(path, &config))
+            .collect()
+    });
+
+    for (stdout, stderr, exitcode, _) in runs {
+        assert_eq!(exitcode, 0, "stderr: {}", stderr);
+        // Workers hand over at every channel operation, so they alternate
+        assert_eq!(stdout, "100\n121212121212\n");
+    }
+}
+
+/// With `max_threads` far below the number of spawns, queued tasks still run
+/// and `join` returns each one's result.
+#[test]
+fn spawns_beyond_max_threads_are_queued() {
+    let source = r#"
+fun worker() -> int {
+    let sum = 0;
+    let i = 0;
+    while i < 100 {
+        sum = sum + i;
+        i = i + 1;
+    }
+    return sum;
+}
+
+let handles = new Vec<int> {};
+let i = 0;
+while i < 200 {
+    handles.push(spawn(worker));
+    i = i + 1;
+}
+let total = 0;
+let j = 0;
+while j < handles.len() {
+    let r: int = join(handles.get(j));
+    total = total + r;
+    j = j + 1;
+}
+print(total);
+"#;
+    let config = RuntimeConfig {
+        max_threads: 2,
+        ..Default::default()
+    };
+    let (stdout, stderr, exitcode, _) = with_source_file("max_threads", source, |path| {
+        run_moca_file_inprocess(path, &config)
+    });
+    assert_eq!(exitcode, 0, "stderr: {}", stderr);
+    assert_eq!(stdout, "990000\n");
+}
+
 // ============================================================================
 // HTTP Server Snapshot Tests
 // ============================================================================
@@ -846,13 +1500,13 @@ fn snapshot_http_server() {
 // HTTP Client Snapshot Tests
 // ============================================================================
 
-/// Run HTTP snapshot tests with a local hyper-based test server.
-/// These tests require a running HTTP server and use template files
-/// with {{PORT}} placeholder that gets replaced with the actual port.
-///
-/// Endpoints:
-/// - GET / : Returns "Hello from test server!"
-/// - POST /echo : Returns the request body as-is
+// Run HTTP snapshot tests with a local hyper-based test server.
+// These tests require a running HTTP server and use template files
+// with {{PORT}} placeholder that gets replaced with the actual port.
+//
+// Endpoints:
+// - GET / : Returns "Hello from test server!"
+// - POST /echo : Returns the request body as-is
 // ============================================================================
 // Performance Benchmark Tests
 // ============================================================================
@@ -975,10 +1629,10 @@ fn rust_mutual_recursion<W: Write>(iterations: i32, writer: &mut W) {
 
 #[cfg(feature = "jit")]
 fn rust_to_letter_index(ch: u8) -> i64 {
-    if ch >= 65 && ch <= 90 {
+    if (65..=90).contains(&ch) {
         return (ch - 65) as i64;
     }
-    if ch >= 97 && ch <= 122 {
+    if (97..=122).contains(&ch) {
         return (ch - 97) as i64;
     }
     -1
@@ -1007,9 +1661,9 @@ fn rust_text_counting<W: Write>(writer: &mut W) {
     for _ in 0..10 {
         let mut max_idx = 0;
         let mut max_val = counts[0];
-        for j in 1..26 {
-            if counts[j] > max_val {
-                max_val = counts[j];
+        for (j, &count) in counts.iter().enumerate().skip(1) {
+            if count > max_val {
+                max_val = count;
                 max_idx = j;
             }
         }
@@ -1094,8 +1748,8 @@ fn rust_array_sum<W: Write>(writer: &mut W) {
     // would be an unfair comparison of JIT quality vs native compilation.
     let mut total: i64 = 0;
     for _ in 0..200 {
-        for i in 0..10000 {
-            total = std::hint::black_box(total) + v[i];
+        for &x in v.iter().take(10000) {
+            total = std::hint::black_box(total) + x;
         }
     }
     writeln!(writer, "{}", total).unwrap();
@@ -1219,7 +1873,7 @@ fn snapshot_performance() {
 
     // Test sum_loop with Rust reference
     let sum_loop_path = perf_dir.join("sum_loop.mc");
-    run_performance_test(&sum_loop_path, |w| rust_sum_loop(w));
+    run_performance_test(&sum_loop_path, rust_sum_loop);
 
     // Test nested_loop with Rust reference
     let nested_loop_path = perf_dir.join("nested_loop.mc");
@@ -1245,19 +1899,19 @@ fn snapshot_performance() {
 
     // Test text character counting with Rust reference
     let text_counting_path = perf_dir.join("text_counting.mc");
-    run_performance_test(&text_counting_path, |w| rust_text_counting(w));
+    run_performance_test(&text_counting_path, rust_text_counting);
 
     // Test quicksort with Rust reference
     let quicksort_path = perf_dir.join("quicksort.mc");
-    run_performance_test(&quicksort_path, |w| rust_quicksort(w));
+    run_performance_test(&quicksort_path, rust_quicksort);
 
     // Test string interpolation with Rust reference
     let string_interp_path = perf_dir.join("string_interpolation.mc");
-    run_performance_test(&string_interp_path, |w| rust_string_interpolation(w));
+    run_performance_test(&string_interp_path, rust_string_interpolation);
 
     // Test print(int) bulk output with Rust reference
     let print_int_path = perf_dir.join("print_int.mc");
-    run_performance_test(&print_int_path, |w| rust_print_int(w));
+    run_performance_test(&print_int_path, rust_print_int);
 
     // Test array sequential sum scan with Rust reference
     let array_sum_path = perf_dir.join("array_sum.mc");
@@ -1329,9 +1983,8 @@ fn snapshot_http() {
                 .path()
                 .file_name()
                 .map(|n| n.to_string_lossy().to_string());
-            name.as_ref().map_or(false, |n| {
-                n.ends_with(".mc.template") && !n.starts_with("http_server")
-            })
+            name.as_ref()
+                .is_some_and(|n| n.ends_with(".mc.template") && !n.starts_with("http_server"))
         })
         .collect();
 
@@ -1468,7 +2121,7 @@ fn snapshot_http() {
 /// Format epoch seconds as "YYYY-MM-DD HH:MM:SS" in UTC (same algorithm as VM).
 fn format_epoch_secs_utc(epoch_secs: i64) -> String {
     let mut days = epoch_secs / 86400;
-    let day_secs = ((epoch_secs % 86400) + 86400) % 86400;
+    let day_secs = epoch_secs.rem_euclid(86400);
     if epoch_secs < 0 && epoch_secs % 86400 != 0 {
         days -= 1;
     }